@@ -321,6 +321,28 @@ def ci(nightly: bool = False):
                 pretty_check_call(["cargo", "test", "-p", "vectoreyes", "--verbose"])
         else:
             build_and_test(features=["serde"], cache_test_output=True)
+    with gitlab_ci_section("fancy-garbling evaluator without default features"):
+        restore_cargo_config()
+        evaluator_args = ["-p", "fancy-garbling", "--no-default-features"]
+        evaluator_args += ["--features", "evaluator"]
+        pretty_check_call(
+            ["cargo", "build", "--target", "wasm32-unknown-unknown"] + evaluator_args
+        )
+        pretty_check_call(["cargo", "test"] + evaluator_args)
+    with gitlab_ci_section("fancy-garbling parser with and without regex"):
+        tree = subprocess.check_output(
+            ["cargo", "tree", "-p", "fancy-garbling", "-e", "normal"], cwd=ROOT
+        ).decode("utf-8")
+        if " regex " in tree:
+            typer.secho(
+                "ERROR: fancy-garbling depends on regex without the regex feature",
+                fg=typer.colors.RED,
+            )
+            raise typer.Exit(code=1)
+        for features in [[], ["--features", "regex"]]:
+            pretty_check_call(
+                ["cargo", "test", "-p", "fancy-garbling", "--lib"] + features + ["parser"]
+            )
 
 
 if __name__ == "__main__":
@@ -5,4 +5,5 @@ in
 rust-bin.fromRustupToolchain {
   channel = rust-toolchain-version;
   components = [ "rustfmt" "llvm-tools-preview" ];
+  targets = [ "wasm32-unknown-unknown" ];
 }
@@ -0,0 +1,42 @@
+//! Benchmark code for building large circuits with `CircuitBuilder`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use fancy_garbling::{
+    circuit::{BinaryCircuit, CircuitBuilder},
+    BinaryGadgets, BundleGadgets,
+};
+use std::time::Duration;
+
+/// Number of 64-bit additions in the chain, giving roughly one million gates.
+const NADDITIONS: usize = 3200;
+const NGATES: usize = 1_004_928;
+
+fn adder_chain(mut b: CircuitBuilder<BinaryCircuit>) -> BinaryCircuit {
+    let mut x = b.bin_garbler_input(64);
+    let y = b.bin_evaluator_input(64);
+    for _ in 0..NADDITIONS {
+        x = b.bin_addition_no_carry(&x, &y).unwrap();
+    }
+    b.output_bundle(&x).unwrap();
+    b.finish()
+}
+
+fn bench_build_adder_chain(c: &mut Criterion) {
+    c.bench_function("builder::adder-chain-1M", |bench| {
+        bench.iter(|| adder_chain(CircuitBuilder::new()));
+    });
+}
+
+fn bench_build_adder_chain_with_capacity(c: &mut Criterion) {
+    c.bench_function("builder::adder-chain-1M-with-capacity", |bench| {
+        bench.iter(|| adder_chain(CircuitBuilder::with_capacity(NGATES, 192)));
+    });
+}
+
+criterion_group! {
+    name = builder;
+    config = Criterion::default().warm_up_time(Duration::from_millis(100)).sample_size(10);
+    targets = bench_build_adder_chain, bench_build_adder_chain_with_capacity
+}
+
+criterion_main!(builder);
@@ -1,7 +1,12 @@
 //! Benchmark code of garbling / evaluating using Nigel's circuits.
 
 use criterion::{criterion_group, criterion_main, Criterion};
-use fancy_garbling::{circuit::BinaryCircuit, classic::garble, AllWire, WireMod2};
+use fancy_garbling::{
+    circuit::{eval_plain, BinaryCircuit, CircuitBuilder, CircuitType, EvaluableCircuit},
+    classic::{garble, garble_parallel},
+    AllWire, Fancy, WireMod2,
+};
+use scuttlebutt::AesRng;
 use std::{fs::File, io::BufReader, time::Duration};
 
 fn circuit(fname: &str) -> BinaryCircuit {
@@ -20,13 +25,27 @@ fn bench_garble_aes_binary(c: &mut Criterion) {
 fn bench_eval_aes_binary(c: &mut Criterion) {
     let circ = circuit("circuits/AES-non-expanded.txt");
     let (en, gc) = garble::<WireMod2, _>(&circ).unwrap();
-    let gb = en.encode_garbler_inputs(&vec![0u16; 128]);
-    let ev = en.encode_evaluator_inputs(&vec![0u16; 128]);
+    let gb = en.encode_garbler_inputs(&vec![0; 128]).unwrap();
+    let ev = en.encode_evaluator_inputs(&vec![0; 128]).unwrap();
     c.bench_function("eval::aes-binary", move |bench| {
         bench.iter(|| gc.eval(&circ, &gb, &ev));
     });
 }
 
+fn bench_parallel_aes_binary(c: &mut Criterion) {
+    let circ = circuit("circuits/AES-non-expanded.txt");
+    let levels = circ.levels();
+    c.bench_function("garble_parallel::aes-binary", |bench| {
+        bench.iter(|| garble_parallel(&circ, AesRng::new(), &levels).unwrap());
+    });
+    let (en, gc) = garble::<WireMod2, _>(&circ).unwrap();
+    let gb = en.encode_garbler_inputs(&[0; 128]).unwrap();
+    let ev = en.encode_evaluator_inputs(&[0; 128]).unwrap();
+    c.bench_function("eval_parallel::aes-binary", |bench| {
+        bench.iter(|| gc.eval_parallel(&circ, &gb, &ev, &levels).unwrap());
+    });
+}
+
 fn bench_garble_sha_1_binary(c: &mut Criterion) {
     let circ = circuit("circuits/sha-1.txt");
     c.bench_function("garble::sha-1-binary", move |bench| {
@@ -37,8 +56,8 @@ fn bench_garble_sha_1_binary(c: &mut Criterion) {
 fn bench_eval_sha_1_binary(c: &mut Criterion) {
     let circ = circuit("circuits/sha-1.txt");
     let (en, gc) = garble::<WireMod2, _>(&circ).unwrap();
-    let gb = en.encode_garbler_inputs(&vec![0u16; 512]);
-    let ev = en.encode_evaluator_inputs(&vec![]);
+    let gb = en.encode_garbler_inputs(&vec![0; 512]).unwrap();
+    let ev = en.encode_evaluator_inputs(&vec![]).unwrap();
     c.bench_function("eval::sha-1-binary", move |bench| {
         bench.iter(|| gc.eval(&circ, &gb, &ev));
     });
@@ -54,8 +73,8 @@ fn bench_garble_sha_256_binary(c: &mut Criterion) {
 fn bench_eval_sha_256_binary(c: &mut Criterion) {
     let circ = circuit("circuits/sha-256.txt");
     let (en, gc) = garble::<WireMod2, _>(&circ).unwrap();
-    let gb = en.encode_garbler_inputs(&vec![0u16; 512]);
-    let ev = en.encode_evaluator_inputs(&vec![]);
+    let gb = en.encode_garbler_inputs(&vec![0; 512]).unwrap();
+    let ev = en.encode_evaluator_inputs(&vec![]).unwrap();
     c.bench_function("eval::sha-256-binary", move |bench| {
         bench.iter(|| gc.eval(&circ, &gb, &ev));
     });
@@ -71,8 +90,8 @@ fn bench_garble_aes_arithmetic(c: &mut Criterion) {
 fn bench_eval_aes_arithmetic(c: &mut Criterion) {
     let circ = circuit("circuits/AES-non-expanded.txt");
     let (en, gc) = garble::<AllWire, _>(&circ).unwrap();
-    let gb = en.encode_garbler_inputs(&vec![0u16; 128]);
-    let ev = en.encode_evaluator_inputs(&vec![0u16; 128]);
+    let gb = en.encode_garbler_inputs(&vec![0; 128]).unwrap();
+    let ev = en.encode_evaluator_inputs(&vec![0; 128]).unwrap();
     c.bench_function("eval::aes-arithmetic", move |bench| {
         bench.iter(|| gc.eval(&circ, &gb, &ev));
     });
@@ -88,8 +107,8 @@ fn bench_garble_sha_1_arithmetic(c: &mut Criterion) {
 fn bench_eval_sha_1_arithmetic(c: &mut Criterion) {
     let circ = circuit("circuits/sha-1.txt");
     let (en, gc) = garble::<AllWire, _>(&circ).unwrap();
-    let gb = en.encode_garbler_inputs(&vec![0u16; 512]);
-    let ev = en.encode_evaluator_inputs(&vec![]);
+    let gb = en.encode_garbler_inputs(&vec![0; 512]).unwrap();
+    let ev = en.encode_evaluator_inputs(&vec![]).unwrap();
     c.bench_function("eval::sha-1-arithmetic", move |bench| {
         bench.iter(|| gc.eval(&circ, &gb, &ev));
     });
@@ -105,20 +124,100 @@ fn bench_garble_sha_256_arithmetic(c: &mut Criterion) {
 fn bench_eval_sha_256_arithmetic(c: &mut Criterion) {
     let circ = circuit("circuits/sha-256.txt");
     let (en, gc) = garble::<AllWire, _>(&circ).unwrap();
-    let gb = en.encode_garbler_inputs(&vec![0u16; 512]);
-    let ev = en.encode_evaluator_inputs(&vec![]);
+    let gb = en.encode_garbler_inputs(&vec![0; 512]).unwrap();
+    let ev = en.encode_evaluator_inputs(&vec![]).unwrap();
     c.bench_function("eval::sha-256-arithmetic", move |bench| {
         bench.iter(|| gc.eval(&circ, &gb, &ev));
     });
 }
 
+fn bench_parse(c: &mut Criterion, name: &str, fname: &str) {
+    // Read the file up front, so that only the parsing is measured.
+    let text = std::fs::read_to_string(fname).unwrap();
+    c.bench_function(&format!("parse::{}", name), move |bench| {
+        bench.iter(|| text.parse::<BinaryCircuit>().unwrap());
+    });
+}
+
+fn bench_parse_aes(c: &mut Criterion) {
+    bench_parse(c, "aes", "circuits/AES-non-expanded.txt");
+}
+
+fn bench_parse_sha_256(c: &mut Criterion) {
+    bench_parse(c, "sha-256", "circuits/sha-256.txt");
+}
+
+/// `n` copies of AES side by side, so that each level is wide enough to be
+/// evaluated in parallel.
+fn wide_aes(n: usize) -> BinaryCircuit {
+    let aes = circuit("circuits/AES-non-expanded.txt");
+    let mut b = CircuitBuilder::<BinaryCircuit>::new();
+    for _ in 0..n {
+        let gb = b.garbler_inputs(&[2; 128]);
+        let ev = b.evaluator_inputs(&[2; 128]);
+        let outputs = aes.eval_output_wires(&mut b, &gb, &ev).unwrap();
+        b.outputs(&outputs).unwrap();
+    }
+    b.finish()
+}
+
+fn bench_eval_plain_wide_aes(c: &mut Criterion) {
+    let circ = wide_aes(64);
+    let levels = circ.levels();
+    let gb = vec![0; circ.num_garbler_inputs()];
+    let ev = vec![1; circ.num_evaluator_inputs()];
+    c.bench_function("eval_plain::wide-aes", |bench| {
+        bench.iter(|| eval_plain(&circ, &gb, &ev).unwrap());
+    });
+    c.bench_function("eval_plain_parallel::wide-aes", |bench| {
+        bench.iter(|| circ.eval_plain_parallel(&gb, &ev, &levels).unwrap());
+    });
+}
+
+fn bench_garble_wide_aes(c: &mut Criterion) {
+    let circ = wide_aes(64);
+    let levels = circ.levels();
+    c.bench_function("garble::wide-aes", |bench| {
+        bench.iter(|| garble::<WireMod2, _>(&circ).unwrap());
+    });
+    c.bench_function("garble_parallel::wide-aes", |bench| {
+        bench.iter(|| garble_parallel(&circ, AesRng::new(), &levels).unwrap());
+    });
+}
+
+fn bench_eval_repeated_adder(c: &mut Criterion) {
+    let circ = circuit("circuits/adder64.txt");
+    let (en, gc) = garble::<WireMod2, _>(&circ).unwrap();
+    let gb = en.encode_garbler_inputs(&[1; 64]).unwrap();
+    let ev = en.encode_evaluator_inputs(&[0; 64]).unwrap();
+    c.bench_function("eval::adder64", |bench| {
+        bench.iter(|| gc.eval(&circ, &gb, &ev).unwrap());
+    });
+    let mut ctx = gc.evaluator(&circ).unwrap();
+    c.bench_function("eval_context::adder64", |bench| {
+        bench.iter(|| ctx.eval(&gb, &ev).unwrap());
+    });
+}
+
 criterion_group! {
     name = parsing;
     config = Criterion::default().warm_up_time(Duration::from_millis(100));
-    targets = bench_garble_aes_binary, bench_garble_aes_arithmetic, bench_eval_aes_binary, bench_eval_aes_arithmetic,  bench_garble_sha_1_binary,  bench_garble_sha_1_arithmetic,
-    bench_eval_sha_1_binary, bench_eval_sha_1_arithmetic,  bench_garble_sha_256_binary, bench_garble_sha_256_arithmetic,  bench_eval_sha_256_binary, bench_eval_sha_256_arithmetic
+    targets = bench_garble_aes_binary, bench_garble_aes_arithmetic, bench_eval_aes_binary, bench_parallel_aes_binary, bench_eval_aes_arithmetic,  bench_garble_sha_1_binary,  bench_garble_sha_1_arithmetic,
+    bench_eval_sha_1_binary, bench_eval_sha_1_arithmetic,  bench_garble_sha_256_binary, bench_garble_sha_256_arithmetic,  bench_eval_sha_256_binary, bench_eval_sha_256_arithmetic, bench_eval_repeated_adder
+
 
+}
 
+criterion_group! {
+    name = parse;
+    config = Criterion::default().warm_up_time(Duration::from_millis(100));
+    targets = bench_parse_aes, bench_parse_sha_256
+}
+
+criterion_group! {
+    name = plaintext;
+    config = Criterion::default().warm_up_time(Duration::from_millis(100));
+    targets = bench_eval_plain_wide_aes, bench_garble_wide_aes
 }
 
-criterion_main!(parsing);
+criterion_main!(parsing, parse, plaintext);
@@ -3,13 +3,13 @@ use fancy_garbling::{
     circuit::{ArithmeticCircuit as Circuit, CircuitBuilder, CircuitType},
     classic::garble,
     util::RngExt,
-    AllWire, FancyArithmetic,
+    AllWire, BundleGadgets, CrtGadgets, Fancy, FancyArithmetic, Modulus,
 };
 use std::time::Duration;
 
-fn bench_garble<F: 'static>(c: &mut Criterion, name: &str, make_circuit: F, q: u16)
+fn bench_garble<F: 'static>(c: &mut Criterion, name: &str, make_circuit: F, q: Modulus)
 where
-    F: Fn(u16) -> Circuit,
+    F: Fn(Modulus) -> Circuit,
 {
     c.bench_function(&format!("garbling::{}_gb ({})", name, q), move |bench| {
         let c = make_circuit(q);
@@ -20,27 +20,28 @@ where
     });
 }
 
-fn bench_eval<F: 'static>(c: &mut Criterion, name: &str, make_circuit: F, q: u16)
+fn bench_eval<F: 'static>(c: &mut Criterion, name: &str, make_circuit: F, q: Modulus)
 where
-    F: Fn(u16) -> Circuit,
+    F: Fn(Modulus) -> Circuit,
 {
     c.bench_function(&format!("garbling::{}_ev ({})", name, q), move |bench| {
         let mut rng = rand::thread_rng();
         let c = make_circuit(q);
         let (en, ev) = garble::<AllWire, _>(&c).unwrap();
         let inps = (0..c.num_garbler_inputs())
-            .map(|i| rng.gen_u16() % c.garbler_input_mod(i))
-            .collect::<Vec<u16>>();
-        let xs = en.encode_garbler_inputs(&inps);
+            .map(|i| rng.gen_residue(c.garbler_input_mod(i)))
+            .collect::<Vec<Modulus>>();
+        let xs = en.encode_garbler_inputs(&inps).unwrap();
+        let ys = en.encode_evaluator_inputs(&[]).unwrap();
         bench.iter(|| {
-            let ys = ev.eval(&c, &xs, &[]).unwrap();
+            let ys = ev.eval(&c, &xs, &ys).unwrap();
             criterion::black_box(ys);
         });
     });
 }
 
-fn proj(q: u16) -> Circuit {
-    let tt = (0..q).map(|i| (i + 1) % q).collect::<Vec<u16>>();
+fn proj(q: Modulus) -> Circuit {
+    let tt = (0..q).map(|i| (i + 1) % q).collect::<Vec<Modulus>>();
     let mut b = CircuitBuilder::new();
     let x = b.garbler_input(q);
     for _ in 0..1000 {
@@ -49,7 +50,7 @@ fn proj(q: u16) -> Circuit {
     b.finish()
 }
 
-fn mul(q: u16) -> Circuit {
+fn mul(q: Modulus) -> Circuit {
     let mut b = CircuitBuilder::new();
     let x = b.garbler_input(q);
     for _ in 0..1000 {
@@ -58,6 +59,87 @@ fn mul(q: u16) -> Circuit {
     b.finish()
 }
 
+/// Multiplications over the CRT moduli {3, 5, 7, 11}, i.e., composite modulus 1155.
+fn crt_mul(_: Modulus) -> Circuit {
+    let mut b = CircuitBuilder::new();
+    let xs = b.garbler_inputs(&[3, 5, 7, 11]);
+    for _ in 0..250 {
+        for x in xs.iter() {
+            let z = b.mul(x, x).unwrap();
+            b.output(&z).unwrap();
+        }
+    }
+    b.finish()
+}
+
+/// 20 squares of a 64-bit CRT value, multiplying the wires of all its primes.
+fn crt_mul_bundle(_: Modulus) -> Circuit {
+    let q = fancy_garbling::util::modulus_with_width(64);
+    let mut b = CircuitBuilder::new();
+    let x = b.crt_garbler_input(q);
+    for _ in 0..20 {
+        let z = b.crt_mul(&x, &x).unwrap();
+        b.output_bundle(&z).unwrap();
+    }
+    b.finish()
+}
+
+/// 10k applications of the 4-bit PRESENT S-box, all sharing one truth table.
+fn sbox(_: Modulus) -> Circuit {
+    let sbox = vec![12, 5, 6, 11, 9, 0, 10, 13, 3, 14, 15, 8, 4, 7, 1, 2];
+    let mut b = CircuitBuilder::new();
+    let mut xs = b.garbler_inputs(&[16; 16]);
+    for _ in 0..625 {
+        for x in xs.iter_mut() {
+            *x = b.proj(x, 16, Some(sbox.clone())).unwrap();
+        }
+    }
+    for x in xs.iter() {
+        b.output(x).unwrap();
+    }
+    b.finish()
+}
+
+/// 1000 reductions of each of five mod-202 values into the CRT moduli
+/// {3, 5, 7, 11, 13}, reusing one truth table per output modulus as CRT range
+/// reductions do.
+fn crt_proj_shared(_: Modulus) -> Circuit {
+    crt_projections(|q_in, q_out, _| (0..q_in).map(|x| (x * x + 1) % q_out).collect())
+}
+
+/// The projections of [`crt_proj_shared`], but each with its own truth
+/// table, so that the garbler can share no table analysis between them.
+fn crt_proj_distinct(_: Modulus) -> Circuit {
+    crt_projections(|q_in, q_out, i| (0..q_in).map(|x| (x * x + i) % q_out).collect())
+}
+
+fn crt_projections(table: impl Fn(Modulus, Modulus, Modulus) -> Vec<Modulus>) -> Circuit {
+    let mut b = CircuitBuilder::new();
+    let xs = b.garbler_inputs(&[202; 5]);
+    for i in 0..200 {
+        for x in xs.iter() {
+            for &q in [3, 5, 7, 11, 13].iter() {
+                let z = b.proj(x, q, Some(table(202, q, i))).unwrap();
+                b.output(&z).unwrap();
+            }
+        }
+    }
+    b.finish()
+}
+
+/// 20 signs of a 32-bit CRT value, whose thousands of projections use a few
+/// dozen distinct truth tables.
+fn crt_sgn(_: Modulus) -> Circuit {
+    let q = fancy_garbling::util::modulus_with_width(32);
+    let mut b = CircuitBuilder::new();
+    let x = b.crt_garbler_input(q);
+    for _ in 0..20 {
+        let z = b.crt_sgn(&x, "100%", None).unwrap();
+        b.output_bundle(&z).unwrap();
+    }
+    b.finish()
+}
+
 fn proj_gb(c: &mut Criterion) {
     bench_garble(c, "proj", proj, 2);
     bench_garble(c, "proj", proj, 17)
@@ -74,11 +156,36 @@ fn mul_ev(c: &mut Criterion) {
     bench_eval(c, "mul", mul, 2);
     bench_eval(c, "mul", mul, 17)
 }
+fn crt_mul_gb(c: &mut Criterion) {
+    bench_garble(c, "crt_mul", crt_mul, 1155);
+    bench_garble(c, "crt_mul_bundle", crt_mul_bundle, 64)
+}
+fn crt_mul_ev(c: &mut Criterion) {
+    bench_eval(c, "crt_mul", crt_mul, 1155);
+    bench_eval(c, "crt_mul_bundle", crt_mul_bundle, 64)
+}
+fn crt_proj_gb(c: &mut Criterion) {
+    bench_garble(c, "crt_proj_shared", crt_proj_shared, 202);
+    bench_garble(c, "crt_proj_distinct", crt_proj_distinct, 202)
+}
+fn crt_sgn_gb(c: &mut Criterion) {
+    bench_garble(c, "crt_sgn", crt_sgn, 32)
+}
+fn crt_sgn_ev(c: &mut Criterion) {
+    bench_eval(c, "crt_sgn", crt_sgn, 32)
+}
+fn sbox_gb(c: &mut Criterion) {
+    bench_garble(c, "sbox", sbox, 16)
+}
+fn sbox_ev(c: &mut Criterion) {
+    bench_eval(c, "sbox", sbox, 16)
+}
 
 criterion_group! {
     name = garbling;
     config = Criterion::default().warm_up_time(Duration::from_millis(100));
-    targets = proj_gb, proj_ev, mul_gb, mul_ev
+    targets = proj_gb, proj_ev, mul_gb, mul_ev, crt_mul_gb, crt_mul_ev, crt_proj_gb,
+        crt_sgn_gb, crt_sgn_ev, sbox_gb, sbox_ev
 }
 
 criterion_main!(garbling);
@@ -2,16 +2,17 @@
 
 use criterion::{criterion_group, criterion_main, Criterion};
 use fancy_garbling::{
-    circuit::{BinaryCircuit as Circuit, EvaluableCircuit},
-    twopac::semihonest::{Evaluator, Garbler},
-    FancyInput, WireMod2,
+    circuit::{BinaryCircuit as Circuit, CircuitType},
+    twopac::semihonest::{Evaluator, Garbler, SemiHonest},
+    FancyInput, Modulus, SecurityParams, WireMod2,
 };
 use ocelot::ot::{AlszReceiver as OtReceiver, AlszSender as OtSender};
-use scuttlebutt::{AesRng, Channel};
+use scuttlebutt::{AbstractChannel, AesRng, Channel};
 use std::{
     fs::File,
     io::{BufReader, BufWriter},
     os::unix::net::UnixStream,
+    sync::Arc,
     time::Duration,
 };
 
@@ -19,12 +20,12 @@ type Reader = BufReader<UnixStream>;
 type Writer = BufWriter<UnixStream>;
 type MyChannel = Channel<Reader, Writer>;
 
-fn circuit(fname: &str) -> Circuit {
-    Circuit::parse(BufReader::new(File::open(fname).unwrap())).unwrap()
+fn circuit(fname: &str) -> Arc<Circuit> {
+    Arc::new(Circuit::parse(BufReader::new(File::open(fname).unwrap())).unwrap())
 }
 
-fn _bench_circuit(circ: &Circuit, gb_inputs: Vec<u16>, ev_inputs: Vec<u16>) {
-    let circ_ = circ.clone();
+fn _bench_circuit(circ: &Arc<Circuit>, gb_inputs: Vec<Modulus>, ev_inputs: Vec<Modulus>) {
+    let circ_ = Arc::clone(circ);
     let (sender, receiver) = UnixStream::pair().unwrap();
     let n_gb_inputs = gb_inputs.len();
     let n_ev_inputs = ev_inputs.len();
@@ -36,7 +37,7 @@ fn _bench_circuit(circ: &Circuit, gb_inputs: Vec<u16>, ev_inputs: Vec<u16>) {
         let mut gb = Garbler::<MyChannel, AesRng, OtSender, WireMod2>::new(channel, rng).unwrap();
         let xs = gb.encode_many(&gb_inputs, &vec![2; n_gb_inputs]).unwrap();
         let ys = gb.receive_many(&vec![2; n_ev_inputs]).unwrap();
-        circ_.eval(&mut gb, &xs, &ys).unwrap();
+        gb.eval_circuit(&*circ_, &xs, &ys).unwrap();
     });
     let rng = AesRng::new();
     let reader = BufReader::new(receiver.try_clone().unwrap());
@@ -45,35 +46,128 @@ fn _bench_circuit(circ: &Circuit, gb_inputs: Vec<u16>, ev_inputs: Vec<u16>) {
     let mut ev = Evaluator::<MyChannel, AesRng, OtReceiver, WireMod2>::new(channel, rng).unwrap();
     let xs = ev.receive_many(&vec![2; n_gb_inputs]).unwrap();
     let ys = ev.encode_many(&ev_inputs, &vec![2; n_ev_inputs]).unwrap();
-    circ.eval(&mut ev, &xs, &ys).unwrap();
+    ev.eval_circuit(&**circ, &xs, &ys).unwrap();
     handle.join().unwrap();
 }
 
+/// Run `circ` on zero inputs, with the parties configured for `security`.
+fn _bench_security(circ: &Arc<Circuit>, security: SecurityParams) {
+    let circ_ = Arc::clone(circ);
+    let (sender, receiver) = UnixStream::pair().unwrap();
+    let channel = |stream: UnixStream| {
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        Channel::new(reader, BufWriter::new(stream))
+    };
+    let config = SemiHonest::builder().security(security);
+    let gb_config = config.clone();
+    let gb_inputs = vec![0; circ.num_garbler_inputs()];
+    let ev_inputs = vec![0; circ.num_evaluator_inputs()];
+    let handle = std::thread::spawn(move || {
+        let channel: MyChannel = channel(sender);
+        let mut gb = gb_config
+            .build_garbler::<_, _, WireMod2>(channel, AesRng::new())
+            .unwrap();
+        gb.execute(&*circ_, &gb_inputs).unwrap();
+    });
+    let channel: MyChannel = channel(receiver);
+    let mut ev = config
+        .build_evaluator::<_, _, WireMod2>(channel, AesRng::new())
+        .unwrap();
+    ev.execute(&**circ, &ev_inputs).unwrap();
+    handle.join().unwrap();
+}
+
+/// Run `circ` `n` times on zero inputs over one channel pair, as executions
+/// of a single session if `reuse`, and in a fresh session each time
+/// otherwise.
+fn _bench_executions(circ: &Arc<Circuit>, n: usize, reuse: bool) {
+    let circ_ = Arc::clone(circ);
+    let (sender, receiver) = UnixStream::pair().unwrap();
+    let channel = |stream: UnixStream| {
+        let reader = BufReader::new(stream.try_clone().unwrap());
+        Channel::new(reader, BufWriter::new(stream))
+    };
+    let gb_inputs = vec![0; circ.num_garbler_inputs()];
+    let ev_inputs = vec![0; circ.num_evaluator_inputs()];
+    let handle = std::thread::spawn(move || {
+        let channel: MyChannel = channel(sender);
+        let new = || {
+            Garbler::<MyChannel, AesRng, OtSender, WireMod2>::new(channel.clone(), AesRng::new())
+                .unwrap()
+        };
+        let mut gb = new();
+        for i in 0..n {
+            if i > 0 && !reuse {
+                gb = new();
+            }
+            gb.execute(&*circ_, &gb_inputs).unwrap();
+        }
+    });
+    let channel: MyChannel = channel(receiver);
+    let new = || {
+        Evaluator::<MyChannel, AesRng, OtReceiver, WireMod2>::new(channel.clone(), AesRng::new())
+            .unwrap()
+    };
+    let mut ev = new();
+    for i in 0..n {
+        if i > 0 && !reuse {
+            ev = new();
+        }
+        ev.execute(&**circ, &ev_inputs).unwrap();
+    }
+    handle.join().unwrap();
+}
+
+fn bench_repeated_adder64(c: &mut Criterion) {
+    let circ = circuit("circuits/adder64.txt");
+    let mut group = c.benchmark_group("twopac::semi-honest (100 x adder64)");
+    group.bench_function("one session", |bench| {
+        bench.iter(|| _bench_executions(&circ, 100, true))
+    });
+    group.bench_function("fresh sessions", |bench| {
+        bench.iter(|| _bench_executions(&circ, 100, false))
+    });
+    group.finish();
+}
+
 fn bench_aes_binary(c: &mut Criterion) {
     let circ = circuit("circuits/AES-non-expanded.txt");
     c.bench_function("twopac::semi-honest (AES-binary)", move |bench| {
-        bench.iter(|| _bench_circuit(&circ, vec![0u16; 128], vec![0u16; 128]))
+        bench.iter(|| _bench_circuit(&circ, vec![0; 128], vec![0; 128]))
+    });
+}
+
+fn bench_security_profiles(c: &mut Criterion) {
+    let circ = circuit("circuits/AES-non-expanded.txt");
+    let mut group = c.benchmark_group("twopac::semi-honest (AES-binary, security)");
+    group.bench_function("default", |bench| {
+        bench.iter(|| _bench_security(&circ, SecurityParams::DEFAULT))
+    });
+    group.bench_function("simulation", |bench| {
+        bench.iter(|| _bench_security(&circ, SecurityParams::SIMULATION))
     });
+    group.finish();
 }
 
 fn bench_sha_1_binary(c: &mut Criterion) {
     let circ = circuit("circuits/sha-1.txt");
     c.bench_function("twopac::semi-honest (SHA-1-binary)", move |bench| {
-        bench.iter(|| _bench_circuit(&circ, vec![0u16; 512], vec![]))
+        bench.iter(|| _bench_circuit(&circ, vec![0; 512], vec![]))
     });
 }
 
 fn bench_sha_256_binary(c: &mut Criterion) {
     let circ = circuit("circuits/sha-256.txt");
     c.bench_function("twopac::semi-honest (SHA-256-binary)", move |bench| {
-        bench.iter(|| _bench_circuit(&circ, vec![0u16; 512], vec![]))
+        bench.iter(|| _bench_circuit(&circ, vec![0; 512], vec![]))
     });
 }
 
 criterion_group! {
     name = semihonest;
     config = Criterion::default().warm_up_time(Duration::from_millis(100)).sample_size(10);
-    targets = bench_aes_binary, bench_sha_1_binary, bench_sha_256_binary
+    targets = bench_aes_binary, bench_security_profiles, bench_sha_1_binary, bench_sha_256_binary,
+        bench_repeated_adder64
 }
 
 criterion_main!(semihonest);
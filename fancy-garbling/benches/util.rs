@@ -1,5 +1,5 @@
 use criterion::{criterion_group, criterion_main, Criterion};
-use fancy_garbling::util;
+use fancy_garbling::{util, Modulus};
 use rand::Rng;
 use std::time::Duration;
 
@@ -30,7 +30,7 @@ fn bench_output_tweak(c: &mut Criterion) {
     c.bench_function("util::output_tweak", move |b| {
         let rng = &mut rand::thread_rng();
         let i = rng.gen::<usize>();
-        let k = rng.gen::<u16>();
+        let k = rng.gen::<Modulus>();
         b.iter(|| {
             let block = util::output_tweak(i, k);
             criterion::black_box(block);
@@ -38,7 +38,7 @@ fn bench_output_tweak(c: &mut Criterion) {
     });
 }
 
-fn base_q_add_eq(c: &mut Criterion, q: u16) {
+fn base_q_add_eq(c: &mut Criterion, q: Modulus) {
     c.bench_function(&format!("util::as_base_q_add_eq ({})", q), move |b| {
         let rng = &mut rand::thread_rng();
         let x = rng.gen::<u128>();
@@ -51,7 +51,7 @@ fn base_q_add_eq(c: &mut Criterion, q: u16) {
     });
 }
 
-fn as_base_q_u128(c: &mut Criterion, q: u16) {
+fn as_base_q_u128(c: &mut Criterion, q: Modulus) {
     c.bench_function(&format!("util::as_base_q_u128 ({})", q), move |b| {
         let rng = &mut rand::thread_rng();
         let x = rng.gen::<u128>();
@@ -62,7 +62,7 @@ fn as_base_q_u128(c: &mut Criterion, q: u16) {
     });
 }
 
-fn from_base_q(c: &mut Criterion, q: u16) {
+fn from_base_q(c: &mut Criterion, q: Modulus) {
     c.bench_function(&format!("util::from_base_q ({})", q), move |b| {
         let rng = &mut rand::thread_rng();
         let x = rng.gen::<u128>();
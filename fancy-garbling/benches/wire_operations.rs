@@ -1,11 +1,11 @@
 use criterion::{criterion_group, criterion_main, Criterion};
-use fancy_garbling::{util::RngExt, AllWire};
+use fancy_garbling::{util::RngExt, AllWire, Modulus};
 use scuttlebutt::{AesRng, Block};
 use std::time::Duration;
 
 use fancy_garbling::WireLabel;
 
-fn bench_digits(c: &mut Criterion, p: u16) {
+fn bench_digits(c: &mut Criterion, p: Modulus) {
     c.bench_function(&format!("wire::digits ({})", p), move |b| {
         let rng = &mut rand::thread_rng();
         let x = Block::from(rng.gen_u128());
@@ -17,7 +17,7 @@ fn bench_digits(c: &mut Criterion, p: u16) {
     });
 }
 
-fn bench_unpack(c: &mut Criterion, p: u16) {
+fn bench_unpack(c: &mut Criterion, p: Modulus) {
     c.bench_function(&format!("wire::from_block ({})", p), move |b| {
         let rng = &mut rand::thread_rng();
         let x = rng.gen_usable_block(p);
@@ -28,7 +28,7 @@ fn bench_unpack(c: &mut Criterion, p: u16) {
     });
 }
 
-fn bench_pack(c: &mut Criterion, p: u16) {
+fn bench_pack(c: &mut Criterion, p: Modulus) {
     c.bench_function(&format!("wire::as_block ({})", p), move |b| {
         let rng = &mut rand::thread_rng();
         let w = AllWire::rand(rng, p);
@@ -39,7 +39,7 @@ fn bench_pack(c: &mut Criterion, p: u16) {
     });
 }
 
-fn bench_plus(c: &mut Criterion, p: u16) {
+fn bench_plus(c: &mut Criterion, p: Modulus) {
     c.bench_function(&format!("wire::plus ({})", p), move |b| {
         let rng = &mut rand::thread_rng();
         let x = AllWire::rand(rng, p);
@@ -51,7 +51,7 @@ fn bench_plus(c: &mut Criterion, p: u16) {
     });
 }
 
-fn bench_plus_eq(c: &mut Criterion, p: u16) {
+fn bench_plus_eq(c: &mut Criterion, p: Modulus) {
     c.bench_function(&format!("wire::plus_eq ({})", p), move |b| {
         let rng = &mut rand::thread_rng();
         let mut x = AllWire::rand(rng, p);
@@ -63,7 +63,7 @@ fn bench_plus_eq(c: &mut Criterion, p: u16) {
     });
 }
 
-fn bench_minus(c: &mut Criterion, p: u16) {
+fn bench_minus(c: &mut Criterion, p: Modulus) {
     c.bench_function(&format!("wire::minus ({})", p), move |b| {
         let rng = &mut rand::thread_rng();
         let x = AllWire::rand(rng, p);
@@ -75,7 +75,7 @@ fn bench_minus(c: &mut Criterion, p: u16) {
     });
 }
 
-fn bench_minus_eq(c: &mut Criterion, p: u16) {
+fn bench_minus_eq(c: &mut Criterion, p: Modulus) {
     c.bench_function(&format!("wire::minus_eq ({})", p), move |b| {
         let rng = &mut rand::thread_rng();
         let mut x = AllWire::rand(rng, p);
@@ -87,11 +87,11 @@ fn bench_minus_eq(c: &mut Criterion, p: u16) {
     });
 }
 
-fn bench_cmul(c: &mut Criterion, p: u16) {
+fn bench_cmul(c: &mut Criterion, p: Modulus) {
     c.bench_function(&format!("wire::cmul ({})", p), move |b| {
         let rng = &mut rand::thread_rng();
         let x = AllWire::rand(rng, p);
-        let c = rng.gen_u16();
+        let c = rng.gen_residue(p);
         b.iter(|| {
             let z = x.cmul(c);
             criterion::black_box(z);
@@ -99,11 +99,11 @@ fn bench_cmul(c: &mut Criterion, p: u16) {
     });
 }
 
-fn bench_cmul_eq(c: &mut Criterion, p: u16) {
+fn bench_cmul_eq(c: &mut Criterion, p: Modulus) {
     c.bench_function(&format!("wire::cmul_eq ({})", p), move |b| {
         let rng = &mut rand::thread_rng();
         let mut x = AllWire::rand(rng, p);
-        let c = rng.gen_u16();
+        let c = rng.gen_residue(p);
         b.iter(|| {
             let z = x.cmul_eq(c);
             criterion::black_box(z);
@@ -111,7 +111,7 @@ fn bench_cmul_eq(c: &mut Criterion, p: u16) {
     });
 }
 
-fn bench_negate(c: &mut Criterion, p: u16) {
+fn bench_negate(c: &mut Criterion, p: Modulus) {
     c.bench_function(&format!("wire::negate ({})", p), move |b| {
         let rng = &mut rand::thread_rng();
         let x = AllWire::rand(rng, p);
@@ -122,7 +122,7 @@ fn bench_negate(c: &mut Criterion, p: u16) {
     });
 }
 
-fn bench_negate_eq(c: &mut Criterion, p: u16) {
+fn bench_negate_eq(c: &mut Criterion, p: Modulus) {
     c.bench_function(&format!("wire::negate_eq ({})", p), move |b| {
         let rng = &mut rand::thread_rng();
         let mut x = AllWire::rand(rng, p);
@@ -133,7 +133,7 @@ fn bench_negate_eq(c: &mut Criterion, p: u16) {
     });
 }
 
-fn bench_hash(c: &mut Criterion, p: u16) {
+fn bench_hash(c: &mut Criterion, p: Modulus) {
     c.bench_function(&format!("wire::hash ({})", p), move |b| {
         let rng = &mut rand::thread_rng();
         let tweak = rand::random::<Block>();
@@ -145,7 +145,7 @@ fn bench_hash(c: &mut Criterion, p: u16) {
     });
 }
 
-fn bench_hashback(c: &mut Criterion, q: u16) {
+fn bench_hashback(c: &mut Criterion, q: Modulus) {
     c.bench_function(&format!("wire::hashback ({})", q), move |b| {
         let rng = &mut rand::thread_rng();
         let tweak = rand::random::<Block>();
@@ -157,7 +157,7 @@ fn bench_hashback(c: &mut Criterion, q: u16) {
     });
 }
 
-fn bench_zero(c: &mut Criterion, p: u16) {
+fn bench_zero(c: &mut Criterion, p: Modulus) {
     c.bench_function(&format!("wire::zero ({})", p), move |b| {
         b.iter(|| {
             let z = AllWire::zero(p);
@@ -166,7 +166,7 @@ fn bench_zero(c: &mut Criterion, p: u16) {
     });
 }
 
-fn bench_rand(c: &mut Criterion, p: u16) {
+fn bench_rand(c: &mut Criterion, p: Modulus) {
     c.bench_function(&format!("wire::rand ({})", p), move |b| {
         let rng = &mut AesRng::new();
         b.iter(|| {
@@ -176,7 +176,7 @@ fn bench_rand(c: &mut Criterion, p: u16) {
     });
 }
 
-fn bench_rand_delta(c: &mut Criterion, p: u16) {
+fn bench_rand_delta(c: &mut Criterion, p: Modulus) {
     c.bench_function(&format!("wire::rand_delta ({})", p), move |b| {
         let rng = &mut AesRng::new();
         b.iter(|| {
@@ -0,0 +1,314 @@
+#ifndef FANCY_GARBLING_H
+#define FANCY_GARBLING_H
+
+/* Generated by cbindgen from src/lib.rs. Do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The version of the C ABI described by `include/fancy_garbling.h`.
+ */
+#define FG_ABI_VERSION 1
+
+/**
+ * The result of a call into the C API.
+ */
+typedef enum {
+  /**
+   * The call succeeded.
+   */
+  FG_STATUS_OK = 0,
+  /**
+   * An argument was `NULL`, had the wrong length, or was out of range.
+   */
+  FG_STATUS_INVALID_ARGUMENT = 1,
+  /**
+   * The circuit file could not be read or parsed.
+   */
+  FG_STATUS_PARSE_ERROR = 2,
+  /**
+   * Garbling failed.
+   */
+  FG_STATUS_GARBLE_ERROR = 3,
+  /**
+   * Evaluation failed.
+   */
+  FG_STATUS_EVAL_ERROR = 4,
+  /**
+   * The library panicked. This indicates a bug in `fancy-garbling`.
+   */
+  FG_STATUS_PANIC = 5,
+  /**
+   * The caller was compiled against a different `FG_ABI_VERSION`.
+   */
+  FG_STATUS_ABI_MISMATCH = 6,
+} FgStatus;
+
+/**
+ * A parsed binary circuit.
+ */
+typedef struct FgCircuit FgCircuit;
+
+/**
+ * The secret input wire labels of a garbled circuit, held by the garbler.
+ */
+typedef struct FgEncoder FgEncoder;
+
+/**
+ * A garbled circuit, sent to the evaluator.
+ */
+typedef struct FgGarbledCircuit FgGarbledCircuit;
+
+/**
+ * Encoded garbler and evaluator inputs, ready for evaluation.
+ */
+typedef struct FgInputs FgInputs;
+
+/**
+ * The inputs and outputs of a circuit, from `fg_circuit_layout`.
+ *
+ * Each party has a single group of input bits. The `_bytes` fields are the
+ * lengths of the same bits when packed for `fg_encode_inputs_bytes` and
+ * `fg_eval_bytes`.
+ */
+typedef struct {
+  /**
+   * The `FG_ABI_VERSION` of the library.
+   */
+  uint32_t abi_version;
+  /**
+   * The number of garbler input bits.
+   */
+  size_t garbler_inputs;
+  /**
+   * The number of bytes holding the packed garbler input bits.
+   */
+  size_t garbler_input_bytes;
+  /**
+   * The number of evaluator input bits.
+   */
+  size_t evaluator_inputs;
+  /**
+   * The number of bytes holding the packed evaluator input bits.
+   */
+  size_t evaluator_input_bytes;
+  /**
+   * The number of output bits.
+   */
+  size_t outputs;
+  /**
+   * The number of bytes holding the packed output bits.
+   */
+  size_t output_bytes;
+} FgLayout;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Parse the Bristol-format circuit at `path`.
+ *
+ * `abi_version` must be `FG_ABI_VERSION`. Returns `NULL` on failure, storing
+ * the reason in `err_out` if it is not `NULL`. Free the circuit with
+ * `fg_circuit_free`.
+ *
+ * # Safety
+ *
+ * `path` must be a NUL-terminated string, and `err_out` must be `NULL` or
+ * valid for writes.
+ */
+FgCircuit *fg_circuit_parse(const char *path, uint32_t abi_version, FgStatus *err_out);
+
+/**
+ * Free a circuit returned by `fg_circuit_parse`.
+ *
+ * # Safety
+ *
+ * `circ` must be `NULL` or a circuit that has not already been freed.
+ */
+void fg_circuit_free(FgCircuit *circ);
+
+/**
+ * The number of garbler inputs to `circ`, or zero if `circ` is `NULL`.
+ *
+ * # Safety
+ *
+ * `circ` must be `NULL` or a live circuit.
+ */
+size_t fg_circuit_num_garbler_inputs(const FgCircuit *circ);
+
+/**
+ * The number of evaluator inputs to `circ`, or zero if `circ` is `NULL`.
+ *
+ * # Safety
+ *
+ * `circ` must be `NULL` or a live circuit.
+ */
+size_t fg_circuit_num_evaluator_inputs(const FgCircuit *circ);
+
+/**
+ * The number of outputs of `circ`, or zero if `circ` is `NULL`.
+ *
+ * # Safety
+ *
+ * `circ` must be `NULL` or a live circuit.
+ */
+size_t fg_circuit_num_outputs(const FgCircuit *circ);
+
+/**
+ * The inputs and outputs of `circ`, or an empty layout if `circ` is `NULL`.
+ *
+ * The `abi_version` field is `FG_ABI_VERSION` either way.
+ *
+ * # Safety
+ *
+ * `circ` must be `NULL` or a live circuit.
+ */
+FgLayout fg_circuit_layout(const FgCircuit *circ);
+
+/**
+ * Garble `circ`, writing the encoder to `encoder_out` and the garbled circuit
+ * to `gc_out`.
+ *
+ * The wire labels are drawn from an RNG seeded with the 16 bytes at `seed`,
+ * or from fresh randomness if `seed` is `NULL`. A fixed seed makes garbling
+ * reproducible, which is only safe for testing. Free the results with
+ * `fg_encoder_free` and `fg_garbled_circuit_free`.
+ *
+ * # Safety
+ *
+ * `circ` must be a live circuit, `seed` must be `NULL` or point to 16
+ * readable bytes, and `encoder_out` and `gc_out` must be valid for writes.
+ */
+FgStatus fg_garble(const FgCircuit *circ,
+                   const uint8_t *seed,
+                   FgEncoder **encoder_out,
+                   FgGarbledCircuit **gc_out);
+
+/**
+ * Free an encoder returned by `fg_garble`.
+ *
+ * # Safety
+ *
+ * `encoder` must be `NULL` or an encoder that has not already been freed.
+ */
+void fg_encoder_free(FgEncoder *encoder);
+
+/**
+ * Free a garbled circuit returned by `fg_garble`.
+ *
+ * # Safety
+ *
+ * `gc` must be `NULL` or a garbled circuit that has not already been freed.
+ */
+void fg_garbled_circuit_free(FgGarbledCircuit *gc);
+
+/**
+ * Encode the garbler's and evaluator's input bits, writing the wire labels to
+ * `inputs_out`.
+ *
+ * Each input is a single bit, stored as a `uint16_t` that is either 0 or 1.
+ * The lengths must match the circuit that was garbled. Free the result with
+ * `fg_inputs_free`.
+ *
+ * # Safety
+ *
+ * `encoder` must be a live encoder, `garbler_inputs` and `evaluator_inputs`
+ * must point to `ngarbler_inputs` and `nevaluator_inputs` readable values
+ * (either may be `NULL` if its length is zero), and `inputs_out` must be
+ * valid for writes.
+ */
+FgStatus fg_encode_inputs(const FgEncoder *encoder,
+                          const uint16_t *garbler_inputs,
+                          size_t ngarbler_inputs,
+                          const uint16_t *evaluator_inputs,
+                          size_t nevaluator_inputs,
+                          FgInputs **inputs_out);
+
+/**
+ * Like `fg_encode_inputs`, but with each party's input bits packed eight to a
+ * byte, least significant bit first: bit `i` is bit `i % 8` of byte `i / 8`.
+ * The padding bits of the last byte must be zero.
+ *
+ * `ngarbler_bytes` and `nevaluator_bytes` must equal the
+ * `garbler_input_bytes` and `evaluator_input_bytes` of the circuit's layout.
+ *
+ * # Safety
+ *
+ * `encoder` must be a live encoder, `garbler_inputs` and `evaluator_inputs`
+ * must point to `ngarbler_bytes` and `nevaluator_bytes` readable bytes
+ * (either may be `NULL` if its length is zero), and `inputs_out` must be
+ * valid for writes.
+ */
+FgStatus fg_encode_inputs_bytes(const FgEncoder *encoder,
+                                const uint8_t *garbler_inputs,
+                                size_t ngarbler_bytes,
+                                const uint8_t *evaluator_inputs,
+                                size_t nevaluator_bytes,
+                                FgInputs **inputs_out);
+
+/**
+ * Free inputs returned by `fg_encode_inputs`.
+ *
+ * # Safety
+ *
+ * `inputs` must be `NULL` or inputs that have not already been freed.
+ */
+void fg_inputs_free(FgInputs *inputs);
+
+/**
+ * Evaluate the garbled circuit `gc` of `circ` on `inputs`, writing the
+ * output bits to `outputs`.
+ *
+ * `noutputs` must equal `fg_circuit_num_outputs(circ)`.
+ *
+ * Fails with `FG_STATUS_EVAL_ERROR` if `inputs` were encoded by the encoder
+ * of another garbling than `gc`, or if `gc` is not a garbling of `circ`.
+ *
+ * # Safety
+ *
+ * `circ`, `gc`, and `inputs` must be live handles, and `outputs` must point
+ * to `noutputs` writable values.
+ */
+FgStatus fg_eval(const FgCircuit *circ,
+                 const FgGarbledCircuit *gc,
+                 const FgInputs *inputs,
+                 uint16_t *outputs,
+                 size_t noutputs);
+
+/**
+ * Like `fg_eval`, but with the output bits packed eight to a byte, least
+ * significant bit first. The padding bits of the last byte are set to zero.
+ *
+ * `noutput_bytes` must equal the `output_bytes` of the circuit's layout.
+ *
+ * # Safety
+ *
+ * `circ`, `gc`, and `inputs` must be live handles, and `outputs` must point
+ * to `noutput_bytes` writable bytes.
+ */
+FgStatus fg_eval_bytes(const FgCircuit *circ,
+                       const FgGarbledCircuit *gc,
+                       const FgInputs *inputs,
+                       uint8_t *outputs,
+                       size_t noutput_bytes);
+
+/**
+ * A description of the most recent failure on the calling thread, or `NULL`
+ * if the most recent call that can fail succeeded.
+ *
+ * The string is owned by the library and is valid until the next call into
+ * the library on the same thread.
+ */
+const char *fg_last_error_message(void);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* FANCY_GARBLING_H */
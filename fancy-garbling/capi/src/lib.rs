@@ -0,0 +1,614 @@
+//! A C API for parsing, garbling, and evaluating binary circuits in Bristol
+//! format.
+//!
+//! The declarations are in `include/fancy_garbling.h`, generated by `cbindgen`
+//! from this crate (`cbindgen --config cbindgen.toml --output
+//! include/fancy_garbling.h` in the crate directory). The crate builds only
+//! the shared library, `libfancy_garbling_capi`, so that Rust users of
+//! `fancy-garbling` don't link a `cdylib` they don't need.
+//!
+//! # Ownership
+//!
+//! * Handles (`FgCircuit`, `FgEncoder`, `FgGarbledCircuit`, and `FgInputs`)
+//!   are opaque. Every handle returned by a function in this crate is owned
+//!   by the caller and must be released exactly once with the matching
+//!   `fg_*_free` function. Passing `NULL` to a `fg_*_free` function does
+//!   nothing.
+//! * Pointer arguments are borrowed for the duration of the call only.
+//! * The string returned by `fg_last_error_message` is owned by the library
+//!   and stays valid until the next call into the library on the same thread.
+//!
+//! Functions report failure through an `FgStatus`. The message describing the
+//! most recent failure on the calling thread is available from
+//! `fg_last_error_message`. Panics are caught at the boundary and reported as
+//! `FG_STATUS_PANIC`.
+//!
+//! # Versioning
+//!
+//! `FG_ABI_VERSION` is incremented whenever the signatures or struct layouts
+//! in this crate change incompatibly. Callers pass the version from the
+//! header they were compiled against to `fg_circuit_parse`, which fails with
+//! `FG_STATUS_ABI_MISMATCH` if it is not the library's. Every other handle is
+//! created from a circuit, so this is the only check needed.
+//!
+//! # Packed bytes
+//!
+//! `fg_encode_inputs_bytes` and `fg_eval_bytes` take and return bits packed
+//! eight to a byte, least significant bit first: bit `i` is bit `i % 8` of byte
+//! `i / 8`, so the buffer read as a little-endian integer has bit `i` set when
+//! wire `i` is 1 (`int.from_bytes(buf, "little")` in Python). A buffer for `n`
+//! bits is `(n + 7) / 8` bytes long, and the padding bits of its last byte
+//! must be zero. `fg_circuit_layout` gives the lengths for a circuit.
+#![deny(clippy::all)]
+#![deny(missing_docs)]
+
+use fancy_garbling::{
+    circuit::{BinaryCircuit, CircuitType},
+    classic::{garble_with_rng, EncodedInputs, Encoder, GarbledCircuit},
+    Modulus, WireMod2,
+};
+use rand::SeedableRng;
+use scuttlebutt::{AesRng, Block};
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString},
+    fs::File,
+    io::BufReader,
+    os::raw::c_char,
+    panic::{catch_unwind, AssertUnwindSafe},
+    ptr, slice,
+};
+
+/// The version of the C ABI described by `include/fancy_garbling.h`.
+pub const FG_ABI_VERSION: u32 = 1;
+
+/// The result of a call into the C API.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FgStatus {
+    /// The call succeeded.
+    Ok = 0,
+    /// An argument was `NULL`, had the wrong length, or was out of range.
+    InvalidArgument = 1,
+    /// The circuit file could not be read or parsed.
+    ParseError = 2,
+    /// Garbling failed.
+    GarbleError = 3,
+    /// Evaluation failed.
+    EvalError = 4,
+    /// The library panicked. This indicates a bug in `fancy-garbling`.
+    Panic = 5,
+    /// The caller was compiled against a different `FG_ABI_VERSION`.
+    AbiMismatch = 6,
+}
+
+/// A parsed binary circuit.
+pub struct FgCircuit(BinaryCircuit);
+
+/// The secret input wire labels of a garbled circuit, held by the garbler.
+pub struct FgEncoder(Encoder<WireMod2>);
+
+/// A garbled circuit, sent to the evaluator.
+pub struct FgGarbledCircuit(GarbledCircuit<WireMod2, BinaryCircuit>);
+
+/// Encoded garbler and evaluator inputs, ready for evaluation.
+pub struct FgInputs {
+    garbler: EncodedInputs<WireMod2>,
+    evaluator: EncodedInputs<WireMod2>,
+}
+
+/// The inputs and outputs of a circuit, from `fg_circuit_layout`.
+///
+/// Each party has a single group of input bits. The `_bytes` fields are the
+/// lengths of the same bits when packed for `fg_encode_inputs_bytes` and
+/// `fg_eval_bytes`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FgLayout {
+    /// The `FG_ABI_VERSION` of the library.
+    pub abi_version: u32,
+    /// The number of garbler input bits.
+    pub garbler_inputs: usize,
+    /// The number of bytes holding the packed garbler input bits.
+    pub garbler_input_bytes: usize,
+    /// The number of evaluator input bits.
+    pub evaluator_inputs: usize,
+    /// The number of bytes holding the packed evaluator input bits.
+    pub evaluator_input_bytes: usize,
+    /// The number of output bits.
+    pub outputs: usize,
+    /// The number of bytes holding the packed output bits.
+    pub output_bytes: usize,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+type Failure = (FgStatus, String);
+
+/// Run `f`, recording any failure or panic as the thread's last error.
+fn guard<T>(f: impl FnOnce() -> Result<T, Failure>) -> Result<T, FgStatus> {
+    let result = catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|panic| {
+        let msg = panic
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        Err((FgStatus::Panic, format!("panic: {}", msg)))
+    });
+    let (result, msg) = match result {
+        Ok(x) => (Ok(x), None),
+        Err((status, msg)) => {
+            // Interior NUL bytes would truncate the message, so drop them.
+            let msg = CString::new(msg.replace('\0', "")).unwrap();
+            (Err(status), Some(msg))
+        }
+    };
+    LAST_ERROR.with(|e| *e.borrow_mut() = msg);
+    result
+}
+
+fn status(result: Result<(), FgStatus>) -> FgStatus {
+    result.err().unwrap_or(FgStatus::Ok)
+}
+
+fn invalid(msg: impl Into<String>) -> Failure {
+    (FgStatus::InvalidArgument, msg.into())
+}
+
+/// Borrow `p`, failing if it is `NULL`.
+unsafe fn deref<'a, T>(p: *const T, name: &str) -> Result<&'a T, Failure> {
+    p.as_ref()
+        .ok_or_else(|| invalid(format!("`{}` is NULL", name)))
+}
+
+/// Borrow `len` elements at `p`, which may be `NULL` if `len` is zero.
+unsafe fn borrow_slice<'a, T>(p: *const T, len: usize, name: &str) -> Result<&'a [T], Failure> {
+    if len == 0 {
+        Ok(&[])
+    } else if p.is_null() {
+        Err(invalid(format!("`{}` is NULL", name)))
+    } else {
+        Ok(slice::from_raw_parts(p, len))
+    }
+}
+
+fn check_bits(bits: &[u16], expected: usize, name: &str) -> Result<(), Failure> {
+    if bits.len() != expected {
+        return Err(invalid(format!(
+            "expected {} {}, got {}",
+            expected,
+            name,
+            bits.len()
+        )));
+    }
+    if let Some(i) = bits.iter().position(|&b| b > 1) {
+        return Err(invalid(format!("{} {} is {}, not a bit", name, i, bits[i])));
+    }
+    Ok(())
+}
+
+// Bits cross the C API as `u16`s, whatever the width of `Modulus`.
+fn from_bits(bits: &[u16]) -> Vec<Modulus> {
+    bits.iter().map(|&b| Modulus::from(b == 1)).collect()
+}
+
+/// The number of bytes holding `nbits` packed bits.
+fn packed_len(nbits: usize) -> usize {
+    nbits / 8 + usize::from(nbits & 7 != 0)
+}
+
+/// Unpack `nbits` bits from `bytes`, least significant bit of each byte first.
+fn unpack(bytes: &[u8], nbits: usize, name: &str) -> Result<Vec<Modulus>, Failure> {
+    if bytes.len() != packed_len(nbits) {
+        return Err(invalid(format!(
+            "expected {} bytes of {}, got {}",
+            packed_len(nbits),
+            name,
+            bytes.len()
+        )));
+    }
+    let used = nbits % 8;
+    if used > 0 && bytes[bytes.len() - 1] >> used != 0 {
+        return Err(invalid(format!(
+            "the padding bits of the {} are not zero",
+            name
+        )));
+    }
+    Ok((0..nbits)
+        .map(|i| Modulus::from((bytes[i / 8] >> (i % 8)) & 1 == 1))
+        .collect())
+}
+
+/// Pack `bits` into `out`, least significant bit of each byte first.
+fn pack(bits: &[Modulus], out: &mut [u8]) {
+    out.fill(0);
+    for (i, &b) in bits.iter().enumerate() {
+        out[i / 8] |= u8::from(b == 1) << (i % 8);
+    }
+}
+
+/// Encode the garbler's and evaluator's inputs, storing them in `inputs_out`.
+unsafe fn encode(
+    en: &Encoder<WireMod2>,
+    gb: &[Modulus],
+    ev: &[Modulus],
+    inputs_out: *mut *mut FgInputs,
+) -> Result<(), Failure> {
+    let garbler = en
+        .encode_garbler_inputs(gb)
+        .map_err(|e| invalid(format!("garbler inputs: {}", e)))?;
+    let evaluator = en
+        .encode_evaluator_inputs(ev)
+        .map_err(|e| invalid(format!("evaluator inputs: {}", e)))?;
+    *inputs_out = Box::into_raw(Box::new(FgInputs { garbler, evaluator }));
+    Ok(())
+}
+
+/// Evaluate the garbled circuit `gc` of `circ` on `inputs`.
+unsafe fn eval(
+    circ: *const FgCircuit,
+    gc: *const FgGarbledCircuit,
+    inputs: *const FgInputs,
+) -> Result<Vec<Modulus>, Failure> {
+    let circ = &deref(circ, "circ")?.0;
+    let gc = &deref(gc, "gc")?.0;
+    let inputs = deref(inputs, "inputs")?;
+    if inputs.garbler.len() != circ.num_garbler_inputs()
+        || inputs.evaluator.len() != circ.num_evaluator_inputs()
+    {
+        return Err(invalid("the inputs were encoded for a different circuit"));
+    }
+    gc.eval(circ, &inputs.garbler, &inputs.evaluator)
+        .map_err(|e| (FgStatus::EvalError, e.to_string()))
+}
+
+/// Parse the Bristol-format circuit at `path`.
+///
+/// `abi_version` must be `FG_ABI_VERSION`. Returns `NULL` on failure, storing
+/// the reason in `err_out` if it is not `NULL`. Free the circuit with
+/// `fg_circuit_free`.
+///
+/// # Safety
+///
+/// `path` must be a NUL-terminated string, and `err_out` must be `NULL` or
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn fg_circuit_parse(
+    path: *const c_char,
+    abi_version: u32,
+    err_out: *mut FgStatus,
+) -> *mut FgCircuit {
+    let result = guard(|| {
+        if abi_version != FG_ABI_VERSION {
+            return Err((
+                FgStatus::AbiMismatch,
+                format!(
+                    "the caller expects ABI version {}, but this library has version {}",
+                    abi_version, FG_ABI_VERSION
+                ),
+            ));
+        }
+        let path = deref(path, "path")?;
+        let path = CStr::from_ptr(path)
+            .to_str()
+            .map_err(|_| invalid("`path` is not valid UTF-8"))?;
+        let parse_error = |e: &dyn std::fmt::Display| {
+            (
+                FgStatus::ParseError,
+                format!("failed to parse {}: {}", path, e),
+            )
+        };
+        let file = File::open(path).map_err(|e| parse_error(&e))?;
+        let circ = BinaryCircuit::parse(BufReader::new(file)).map_err(|e| parse_error(&e))?;
+        Ok(Box::into_raw(Box::new(FgCircuit(circ))))
+    });
+    if let Some(err_out) = err_out.as_mut() {
+        *err_out = status(result.map(|_| ()));
+    }
+    result.unwrap_or(ptr::null_mut())
+}
+
+/// Free a circuit returned by `fg_circuit_parse`.
+///
+/// # Safety
+///
+/// `circ` must be `NULL` or a circuit that has not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn fg_circuit_free(circ: *mut FgCircuit) {
+    if !circ.is_null() {
+        drop(Box::from_raw(circ));
+    }
+}
+
+/// The number of garbler inputs to `circ`, or zero if `circ` is `NULL`.
+///
+/// # Safety
+///
+/// `circ` must be `NULL` or a live circuit.
+#[no_mangle]
+pub unsafe extern "C" fn fg_circuit_num_garbler_inputs(circ: *const FgCircuit) -> usize {
+    circ.as_ref().map_or(0, |c| c.0.num_garbler_inputs())
+}
+
+/// The number of evaluator inputs to `circ`, or zero if `circ` is `NULL`.
+///
+/// # Safety
+///
+/// `circ` must be `NULL` or a live circuit.
+#[no_mangle]
+pub unsafe extern "C" fn fg_circuit_num_evaluator_inputs(circ: *const FgCircuit) -> usize {
+    circ.as_ref().map_or(0, |c| c.0.num_evaluator_inputs())
+}
+
+/// The number of outputs of `circ`, or zero if `circ` is `NULL`.
+///
+/// # Safety
+///
+/// `circ` must be `NULL` or a live circuit.
+#[no_mangle]
+pub unsafe extern "C" fn fg_circuit_num_outputs(circ: *const FgCircuit) -> usize {
+    circ.as_ref().map_or(0, |c| c.0.noutputs())
+}
+
+/// The inputs and outputs of `circ`, or an empty layout if `circ` is `NULL`.
+///
+/// The `abi_version` field is `FG_ABI_VERSION` either way.
+///
+/// # Safety
+///
+/// `circ` must be `NULL` or a live circuit.
+#[no_mangle]
+pub unsafe extern "C" fn fg_circuit_layout(circ: *const FgCircuit) -> FgLayout {
+    let (ngb, nev, nout) = circ.as_ref().map_or((0, 0, 0), |c| {
+        let c = &c.0;
+        (
+            c.num_garbler_inputs(),
+            c.num_evaluator_inputs(),
+            c.noutputs(),
+        )
+    });
+    FgLayout {
+        abi_version: FG_ABI_VERSION,
+        garbler_inputs: ngb,
+        garbler_input_bytes: packed_len(ngb),
+        evaluator_inputs: nev,
+        evaluator_input_bytes: packed_len(nev),
+        outputs: nout,
+        output_bytes: packed_len(nout),
+    }
+}
+
+/// Garble `circ`, writing the encoder to `encoder_out` and the garbled circuit
+/// to `gc_out`.
+///
+/// The wire labels are drawn from an RNG seeded with the 16 bytes at `seed`,
+/// or from fresh randomness if `seed` is `NULL`. A fixed seed makes garbling
+/// reproducible, which is only safe for testing. Free the results with
+/// `fg_encoder_free` and `fg_garbled_circuit_free`.
+///
+/// # Safety
+///
+/// `circ` must be a live circuit, `seed` must be `NULL` or point to 16
+/// readable bytes, and `encoder_out` and `gc_out` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn fg_garble(
+    circ: *const FgCircuit,
+    seed: *const u8,
+    encoder_out: *mut *mut FgEncoder,
+    gc_out: *mut *mut FgGarbledCircuit,
+) -> FgStatus {
+    status(guard(|| {
+        let circ = &deref(circ, "circ")?.0;
+        if encoder_out.is_null() || gc_out.is_null() {
+            return Err(invalid("`encoder_out` and `gc_out` must not be NULL"));
+        }
+        let rng = if seed.is_null() {
+            AesRng::new()
+        } else {
+            let mut bytes = [0; 16];
+            bytes.copy_from_slice(slice::from_raw_parts(seed, 16));
+            AesRng::from_seed(Block::from(bytes))
+        };
+        let (en, gc) = garble_with_rng::<WireMod2, _>(circ, rng)
+            .map_err(|e| (FgStatus::GarbleError, e.to_string()))?;
+        *encoder_out = Box::into_raw(Box::new(FgEncoder(en)));
+        *gc_out = Box::into_raw(Box::new(FgGarbledCircuit(gc)));
+        Ok(())
+    }))
+}
+
+/// Free an encoder returned by `fg_garble`.
+///
+/// # Safety
+///
+/// `encoder` must be `NULL` or an encoder that has not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn fg_encoder_free(encoder: *mut FgEncoder) {
+    if !encoder.is_null() {
+        drop(Box::from_raw(encoder));
+    }
+}
+
+/// Free a garbled circuit returned by `fg_garble`.
+///
+/// # Safety
+///
+/// `gc` must be `NULL` or a garbled circuit that has not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn fg_garbled_circuit_free(gc: *mut FgGarbledCircuit) {
+    if !gc.is_null() {
+        drop(Box::from_raw(gc));
+    }
+}
+
+/// Encode the garbler's and evaluator's input bits, writing the wire labels to
+/// `inputs_out`.
+///
+/// Each input is a single bit, stored as a `uint16_t` that is either 0 or 1.
+/// The lengths must match the circuit that was garbled. Free the result with
+/// `fg_inputs_free`.
+///
+/// # Safety
+///
+/// `encoder` must be a live encoder, `garbler_inputs` and `evaluator_inputs`
+/// must point to `ngarbler_inputs` and `nevaluator_inputs` readable values
+/// (either may be `NULL` if its length is zero), and `inputs_out` must be
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn fg_encode_inputs(
+    encoder: *const FgEncoder,
+    garbler_inputs: *const u16,
+    ngarbler_inputs: usize,
+    evaluator_inputs: *const u16,
+    nevaluator_inputs: usize,
+    inputs_out: *mut *mut FgInputs,
+) -> FgStatus {
+    status(guard(|| {
+        let en = &deref(encoder, "encoder")?.0;
+        let gb = borrow_slice(garbler_inputs, ngarbler_inputs, "garbler_inputs")?;
+        let ev = borrow_slice(evaluator_inputs, nevaluator_inputs, "evaluator_inputs")?;
+        if inputs_out.is_null() {
+            return Err(invalid("`inputs_out` is NULL"));
+        }
+        check_bits(gb, en.num_garbler_inputs(), "garbler inputs")?;
+        check_bits(ev, en.num_evaluator_inputs(), "evaluator inputs")?;
+        encode(en, &from_bits(gb), &from_bits(ev), inputs_out)
+    }))
+}
+
+/// Like `fg_encode_inputs`, but with each party's input bits packed eight to a
+/// byte, least significant bit first: bit `i` is bit `i % 8` of byte `i / 8`.
+/// The padding bits of the last byte must be zero.
+///
+/// `ngarbler_bytes` and `nevaluator_bytes` must equal the
+/// `garbler_input_bytes` and `evaluator_input_bytes` of the circuit's layout.
+///
+/// # Safety
+///
+/// `encoder` must be a live encoder, `garbler_inputs` and `evaluator_inputs`
+/// must point to `ngarbler_bytes` and `nevaluator_bytes` readable bytes
+/// (either may be `NULL` if its length is zero), and `inputs_out` must be
+/// valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn fg_encode_inputs_bytes(
+    encoder: *const FgEncoder,
+    garbler_inputs: *const u8,
+    ngarbler_bytes: usize,
+    evaluator_inputs: *const u8,
+    nevaluator_bytes: usize,
+    inputs_out: *mut *mut FgInputs,
+) -> FgStatus {
+    status(guard(|| {
+        let en = &deref(encoder, "encoder")?.0;
+        let gb = borrow_slice(garbler_inputs, ngarbler_bytes, "garbler_inputs")?;
+        let ev = borrow_slice(evaluator_inputs, nevaluator_bytes, "evaluator_inputs")?;
+        if inputs_out.is_null() {
+            return Err(invalid("`inputs_out` is NULL"));
+        }
+        let gb = unpack(gb, en.num_garbler_inputs(), "garbler inputs")?;
+        let ev = unpack(ev, en.num_evaluator_inputs(), "evaluator inputs")?;
+        encode(en, &gb, &ev, inputs_out)
+    }))
+}
+
+/// Free inputs returned by `fg_encode_inputs`.
+///
+/// # Safety
+///
+/// `inputs` must be `NULL` or inputs that have not already been freed.
+#[no_mangle]
+pub unsafe extern "C" fn fg_inputs_free(inputs: *mut FgInputs) {
+    if !inputs.is_null() {
+        drop(Box::from_raw(inputs));
+    }
+}
+
+/// Evaluate the garbled circuit `gc` of `circ` on `inputs`, writing the
+/// output bits to `outputs`.
+///
+/// `noutputs` must equal `fg_circuit_num_outputs(circ)`.
+///
+/// Fails with `FG_STATUS_EVAL_ERROR` if `inputs` were encoded by the encoder
+/// of another garbling than `gc`, or if `gc` is not a garbling of `circ`.
+///
+/// # Safety
+///
+/// `circ`, `gc`, and `inputs` must be live handles, and `outputs` must point
+/// to `noutputs` writable values.
+#[no_mangle]
+pub unsafe extern "C" fn fg_eval(
+    circ: *const FgCircuit,
+    gc: *const FgGarbledCircuit,
+    inputs: *const FgInputs,
+    outputs: *mut u16,
+    noutputs: usize,
+) -> FgStatus {
+    status(guard(|| {
+        let expected = deref(circ, "circ")?.0.noutputs();
+        if noutputs != expected {
+            return Err(invalid(format!(
+                "expected {} outputs, got {}",
+                expected, noutputs
+            )));
+        }
+        if outputs.is_null() && noutputs > 0 {
+            return Err(invalid("`outputs` is NULL"));
+        }
+        let result = eval(circ, gc, inputs)?;
+        if noutputs > 0 {
+            let outputs = slice::from_raw_parts_mut(outputs, noutputs);
+            for (out, &b) in outputs.iter_mut().zip(&result) {
+                *out = u16::from(b == 1);
+            }
+        }
+        Ok(())
+    }))
+}
+
+/// Like `fg_eval`, but with the output bits packed eight to a byte, least
+/// significant bit first. The padding bits of the last byte are set to zero.
+///
+/// `noutput_bytes` must equal the `output_bytes` of the circuit's layout.
+///
+/// # Safety
+///
+/// `circ`, `gc`, and `inputs` must be live handles, and `outputs` must point
+/// to `noutput_bytes` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fg_eval_bytes(
+    circ: *const FgCircuit,
+    gc: *const FgGarbledCircuit,
+    inputs: *const FgInputs,
+    outputs: *mut u8,
+    noutput_bytes: usize,
+) -> FgStatus {
+    status(guard(|| {
+        let expected = packed_len(deref(circ, "circ")?.0.noutputs());
+        if noutput_bytes != expected {
+            return Err(invalid(format!(
+                "expected {} bytes of outputs, got {}",
+                expected, noutput_bytes
+            )));
+        }
+        if outputs.is_null() && noutput_bytes > 0 {
+            return Err(invalid("`outputs` is NULL"));
+        }
+        let result = eval(circ, gc, inputs)?;
+        if noutput_bytes > 0 {
+            pack(&result, slice::from_raw_parts_mut(outputs, noutput_bytes));
+        }
+        Ok(())
+    }))
+}
+
+/// A description of the most recent failure on the calling thread, or `NULL`
+/// if the most recent call that can fail succeeded.
+///
+/// The string is owned by the library and is valid until the next call into
+/// the library on the same thread.
+#[no_mangle]
+pub extern "C" fn fg_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |msg| msg.as_ptr()))
+}
@@ -0,0 +1,74 @@
+//! Builds the shared library, compiles the programs in `tests/capi` against
+//! it, and runs them.
+
+#![cfg(unix)]
+
+use std::{env, path::PathBuf, process::Command};
+
+/// Build the shared library into the target directory of this test, returning
+/// the directory it is in. `cargo test` builds no `cdylib`-only library.
+fn build_library() -> PathBuf {
+    // Integration tests live in `target/<profile>/deps`.
+    let exe = env::current_exe().unwrap();
+    let profile_dir = exe.parent().unwrap().parent().unwrap().to_path_buf();
+    let mut cargo = Command::new(env!("CARGO"));
+    cargo
+        .arg("build")
+        .arg("--lib")
+        .arg("--manifest-path")
+        .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"))
+        .arg("--target-dir")
+        .arg(profile_dir.parent().unwrap());
+    if profile_dir.ends_with("release") {
+        cargo.arg("--release");
+    }
+    let status = cargo.status().expect("failed to run cargo");
+    assert!(status.success(), "building the shared library failed");
+    profile_dir
+}
+
+/// Compile `tests/capi/<name>.c` and run it on `fancy-garbling/circuits/<circuit>`.
+fn run(name: &str, circuit: &str) {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let lib_dir = build_library();
+    let out_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let exe = out_dir.join(format!("capi_{}", name));
+
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = Command::new(cc)
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join(format!("tests/capi/{}.c", name)))
+        .arg("-o")
+        .arg(&exe)
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lfancy_garbling_capi")
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success(), "compiling {}.c failed", name);
+
+    let output = Command::new(&exe)
+        .arg(manifest_dir.join("../circuits").join(circuit))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{} failed: {}",
+        name,
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn adder64() {
+    run("adder64", "adder64.txt");
+}
+
+#[test]
+fn aes() {
+    run("aes", "AES-non-expanded.txt");
+}
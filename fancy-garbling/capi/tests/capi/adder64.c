@@ -0,0 +1,67 @@
+/* Garbles and evaluates adder64 through the C API. Run by tests/capi.rs. */
+
+#include <stdio.h>
+
+#include "fancy_garbling.h"
+
+#define CHECK(cond)                                                            \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      const char *msg = fg_last_error_message();                               \
+      fprintf(stderr, "%s:%d: check failed: %s (%s)\n", __FILE__, __LINE__,   \
+              #cond, msg ? msg : "no error message");                          \
+      return 1;                                                                \
+    }                                                                          \
+  } while (0)
+
+static void to_bits(uint64_t x, uint16_t *bits) {
+  for (int i = 0; i < 64; i++) {
+    bits[i] = (x >> i) & 1;
+  }
+}
+
+int main(int argc, char **argv) {
+  CHECK(argc == 2);
+
+  FgStatus status = FG_STATUS_OK;
+  CHECK(fg_circuit_parse("no/such/circuit.txt", FG_ABI_VERSION, &status) == NULL);
+  CHECK(status == FG_STATUS_PARSE_ERROR);
+  CHECK(fg_last_error_message() != NULL);
+
+  FgCircuit *circ = fg_circuit_parse(argv[1], FG_ABI_VERSION, &status);
+  CHECK(circ != NULL && status == FG_STATUS_OK);
+  CHECK(fg_last_error_message() == NULL);
+  CHECK(fg_circuit_num_garbler_inputs(circ) == 64);
+  CHECK(fg_circuit_num_evaluator_inputs(circ) == 64);
+  CHECK(fg_circuit_num_outputs(circ) == 64);
+
+  const uint8_t seed[16] = {1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16};
+  FgEncoder *encoder = NULL;
+  FgGarbledCircuit *gc = NULL;
+  CHECK(fg_garble(circ, seed, &encoder, &gc) == FG_STATUS_OK);
+
+  uint64_t x = 0x0123456789abcdefULL, y = 0xfedcba9876543210ULL + 0x1234;
+  uint16_t xs[64], ys[64], outputs[64];
+  to_bits(x, xs);
+  to_bits(y, ys);
+
+  FgInputs *inputs = NULL;
+  CHECK(fg_encode_inputs(encoder, xs, 63, ys, 64, &inputs) == FG_STATUS_INVALID_ARGUMENT);
+  CHECK(inputs == NULL);
+  CHECK(fg_encode_inputs(encoder, xs, 64, ys, 64, &inputs) == FG_STATUS_OK);
+  CHECK(fg_eval(circ, gc, inputs, outputs, 63) == FG_STATUS_INVALID_ARGUMENT);
+  CHECK(fg_eval(circ, gc, inputs, outputs, 64) == FG_STATUS_OK);
+
+  uint16_t expected[64];
+  to_bits(x + y, expected);
+  for (int i = 0; i < 64; i++) {
+    CHECK(outputs[i] == expected[i]);
+  }
+
+  fg_inputs_free(inputs);
+  fg_garbled_circuit_free(gc);
+  fg_encoder_free(encoder);
+  fg_circuit_free(circ);
+  fg_circuit_free(NULL);
+  return 0;
+}
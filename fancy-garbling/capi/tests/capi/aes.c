@@ -0,0 +1,81 @@
+/* Garbles and evaluates AES-128 through the packed-byte C API. Run by
+ * tests/capi.rs. */
+
+#include <stdio.h>
+
+#include "fancy_garbling.h"
+
+#define CHECK(cond)                                                            \
+  do {                                                                         \
+    if (!(cond)) {                                                             \
+      const char *msg = fg_last_error_message();                               \
+      fprintf(stderr, "%s:%d: check failed: %s (%s)\n", __FILE__, __LINE__,   \
+              #cond, msg ? msg : "no error message");                          \
+      return 1;                                                                \
+    }                                                                          \
+  } while (0)
+
+/* The circuit takes each byte most significant bit first, while packed bytes
+ * hold their first bit in the least significant position. */
+static void reverse_bits(const uint8_t *in, uint8_t *out, size_t n) {
+  for (size_t i = 0; i < n; i++) {
+    uint8_t b = 0;
+    for (int j = 0; j < 8; j++) {
+      b |= ((in[i] >> j) & 1) << (7 - j);
+    }
+    out[i] = b;
+  }
+}
+
+int main(int argc, char **argv) {
+  CHECK(argc == 2);
+
+  FgStatus status = FG_STATUS_OK;
+  CHECK(fg_circuit_parse(argv[1], FG_ABI_VERSION + 1, &status) == NULL);
+  CHECK(status == FG_STATUS_ABI_MISMATCH);
+
+  FgCircuit *circ = fg_circuit_parse(argv[1], FG_ABI_VERSION, &status);
+  CHECK(circ != NULL && status == FG_STATUS_OK);
+
+  FgLayout layout = fg_circuit_layout(circ);
+  CHECK(layout.abi_version == FG_ABI_VERSION);
+  CHECK(layout.garbler_inputs == 128 && layout.garbler_input_bytes == 16);
+  CHECK(layout.evaluator_inputs == 128 && layout.evaluator_input_bytes == 16);
+  CHECK(layout.outputs == 128 && layout.output_bytes == 16);
+  CHECK(fg_circuit_layout(NULL).outputs == 0);
+
+  FgEncoder *encoder = NULL;
+  FgGarbledCircuit *gc = NULL;
+  CHECK(fg_garble(circ, NULL, &encoder, &gc) == FG_STATUS_OK);
+
+  /* The known-answer vector from FIPS-197, appendix C.1. The garbler provides
+   * the plaintext and the evaluator the key. */
+  const uint8_t plaintext[16] = {0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77,
+                                 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff};
+  const uint8_t key[16] = {0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+                           0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f};
+  const uint8_t expected[16] = {0x69, 0xc4, 0xe0, 0xd8, 0x6a, 0x7b, 0x04, 0x30,
+                                0xd8, 0xcd, 0xb7, 0x80, 0x70, 0xb4, 0xc5, 0x5a};
+  uint8_t xs[16], ys[16], outputs[16], ciphertext[16];
+  reverse_bits(plaintext, xs, 16);
+  reverse_bits(key, ys, 16);
+
+  FgInputs *inputs = NULL;
+  CHECK(fg_encode_inputs_bytes(encoder, xs, 15, ys, 16, &inputs) ==
+        FG_STATUS_INVALID_ARGUMENT);
+  CHECK(inputs == NULL);
+  CHECK(fg_encode_inputs_bytes(encoder, xs, 16, ys, 16, &inputs) == FG_STATUS_OK);
+  CHECK(fg_eval_bytes(circ, gc, inputs, outputs, 17) == FG_STATUS_INVALID_ARGUMENT);
+  CHECK(fg_eval_bytes(circ, gc, inputs, outputs, 16) == FG_STATUS_OK);
+
+  reverse_bits(outputs, ciphertext, 16);
+  for (int i = 0; i < 16; i++) {
+    CHECK(ciphertext[i] == expected[i]);
+  }
+
+  fg_inputs_free(inputs);
+  fg_garbled_circuit_free(gc);
+  fg_encoder_free(encoder);
+  fg_circuit_free(circ);
+  return 0;
+}
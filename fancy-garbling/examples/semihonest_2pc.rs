@@ -1,75 +1,112 @@
 use fancy_garbling::{
-    circuit::{BinaryCircuit as Circuit, EvaluableCircuit},
-    twopac::semihonest::{Evaluator, Garbler},
-    FancyInput, WireMod2,
+    circuit::BinaryCircuit as Circuit,
+    twopac::{
+        self,
+        semihonest::{self, OutputMode, SemiHonest},
+    },
+    FancyInput, Modulus, WireMod2,
 };
 use ocelot::ot::{AlszReceiver as OtReceiver, AlszSender as OtSender};
-use scuttlebutt::{unix_channel_pair, AesRng, UnixChannel};
-use std::{fs::File, io::BufReader, time::SystemTime};
+use scuttlebutt::{unix_channel_pair, AesRng};
+use std::{
+    fs::File,
+    io::BufReader,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-fn circuit(fname: &str) -> Circuit {
+fn circuit(fname: &str) -> Arc<Circuit> {
     println!("* Circuit: {}", fname);
-    Circuit::parse(BufReader::new(File::open(fname).unwrap())).unwrap()
+    Arc::new(Circuit::parse(BufReader::new(File::open(fname).unwrap())).unwrap())
+}
+
+/// The output bits in hex, four to a digit, the first the most significant.
+fn hex(bits: &[Modulus]) -> String {
+    bits.chunks(4)
+        .map(|nibble| {
+            let digit: Modulus = nibble.iter().fold(0, |acc, &bit| 2 * acc + bit);
+            format!("{:x}", digit)
+        })
+        .collect()
 }
 
-fn run_circuit(circ: &mut Circuit, gb_inputs: Vec<u16>, ev_inputs: Vec<u16>) {
-    let circ_ = circ.clone();
+fn run_circuit(
+    circ: Arc<Circuit>,
+    gb_inputs: Vec<Modulus>,
+    ev_inputs: Vec<Modulus>,
+    time_per_gate: Duration,
+) {
+    // Both threads share the same copy of the circuit.
+    let circ_ = Arc::clone(&circ);
     let (sender, receiver) = unix_channel_pair();
     let n_gb_inputs = gb_inputs.len();
     let n_ev_inputs = ev_inputs.len();
-    let total = SystemTime::now();
+    // Both parties must use the same configuration. Here, the evaluator sends
+    // the outputs back so that the garbler learns them too, and the garbler
+    // flushes every 64 KiB of garbled gates so the evaluator can start early.
+    // Both parties keep stats of their communication and of the time of each
+    // phase.
+    let config = SemiHonest::builder()
+        .ot::<OtSender, OtReceiver>()
+        .flush_threshold(1 << 16)
+        .output_mode(OutputMode::Both)
+        .stats(true);
+    let estimate = twopac::estimate(&*circ, &config)
+        .unwrap()
+        .calibrate(time_per_gate);
+    let config_ = config.clone();
+    let total = Instant::now();
     let handle = std::thread::spawn(move || {
         let rng = AesRng::new();
-        let start = SystemTime::now();
-        let mut gb = Garbler::<UnixChannel, AesRng, OtSender, WireMod2>::new(sender, rng).unwrap();
-        println!(
-            "Garbler :: Initialization: {} ms",
-            start.elapsed().unwrap().as_millis()
-        );
-        let start = SystemTime::now();
+        let mut gb = config_
+            .build_garbler::<_, _, WireMod2>(sender, rng)
+            .unwrap();
         let xs = gb.encode_many(&gb_inputs, &vec![2; n_gb_inputs]).unwrap();
         let ys = gb.receive_many(&vec![2; n_ev_inputs]).unwrap();
-        println!(
-            "Garbler :: Encoding inputs: {} ms",
-            start.elapsed().unwrap().as_millis()
-        );
-        let start = SystemTime::now();
-        circ_.eval(&mut gb, &xs, &ys).unwrap();
-        println!(
-            "Garbler :: Circuit garbling: {} ms",
-            start.elapsed().unwrap().as_millis()
-        );
+        let outputs = gb.eval_circuit(&*circ_, &xs, &ys).unwrap().unwrap();
+        (outputs, gb.stats().unwrap())
     });
     let rng = AesRng::new();
-    let start = SystemTime::now();
-    let mut ev =
-        Evaluator::<UnixChannel, AesRng, OtReceiver, WireMod2>::new(receiver, rng).unwrap();
-    println!(
-        "Evaluator :: Initialization: {} ms",
-        start.elapsed().unwrap().as_millis()
-    );
-    let start = SystemTime::now();
+    let mut ev = config
+        .build_evaluator::<_, _, WireMod2>(receiver, rng)
+        .unwrap();
     let xs = ev.receive_many(&vec![2; n_gb_inputs]).unwrap();
     let ys = ev.encode_many(&ev_inputs, &vec![2; n_ev_inputs]).unwrap();
-    println!(
-        "Evaluator :: Encoding inputs: {} ms",
-        start.elapsed().unwrap().as_millis()
-    );
-    let start = SystemTime::now();
-    circ.eval(&mut ev, &xs, &ys).unwrap();
-    println!(
-        "Evaluator :: Circuit evaluation: {} ms",
-        start.elapsed().unwrap().as_millis()
-    );
-    handle.join().unwrap();
-    println!("Total: {} ms", total.elapsed().unwrap().as_millis());
+    let outputs = ev.eval_circuit(&*circ, &xs, &ys).unwrap();
+    let (gb_outputs, gb_stats) = handle.join().unwrap();
+    let ev_stats = ev.stats().unwrap();
+    print!("Garbler :: {}", gb_stats);
+    print!("Evaluator :: {}", ev_stats);
+    // With `OutputMode::Both`, the garbler learns the same outputs.
+    println!("Garbler :: Outputs: {}", hex(&gb_outputs));
+    println!("Evaluator :: Outputs: {}", hex(&outputs));
+    assert_eq!(gb_outputs, outputs);
+    println!("Total: {} ms", total.elapsed().as_millis());
+
+    // Compare the estimate with what was measured, so that drift shows.
+    print!("{}", estimate);
+    println!("  measured garbler bytes:   {:11}", gb_stats.bytes_written);
+    println!("  measured evaluator bytes: {:11}", ev_stats.bytes_written);
+    println!("  measured circuit time: {:14.3?}", ev_stats.eval_time);
 }
 
 fn main() {
-    let mut circ = circuit("circuits/AES-non-expanded.txt");
-    run_circuit(&mut circ, vec![0; 128], vec![0; 128]);
-    let mut circ = circuit("circuits/sha-1.txt");
-    run_circuit(&mut circ, vec![0; 512], vec![]);
-    let mut circ = circuit("circuits/sha-256.txt");
-    run_circuit(&mut circ, vec![0; 512], vec![]);
+    // With `--features tracing`, log each phase with its duration, and the
+    // progress through the gates of each circuit.
+    #[cfg(feature = "tracing")]
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+        .init();
+
+    let circ = circuit("circuits/AES-non-expanded.txt");
+    // Measure the time per gate once, to calibrate the estimates with.
+    let time_per_gate = semihonest::profile::<WireMod2, _, _>(&*circ, &mut AesRng::new())
+        .unwrap()
+        .time_per_nonfree_gate();
+    run_circuit(circ, vec![0; 128], vec![0; 128], time_per_gate);
+    let circ = circuit("circuits/sha-1.txt");
+    run_circuit(circ, vec![0; 512], vec![], time_per_gate);
+    let circ = circuit("circuits/sha-256.txt");
+    run_circuit(circ, vec![0; 512], vec![], time_per_gate);
 }
@@ -0,0 +1,42 @@
+//! Decodes arbitrary bytes as a garbled circuit. The bytes are also decoded as
+//! the payload of a garbling of `circuits/adder64.txt`, after the garbling id
+//! and circuit digest of such a garbling, and evaluated if they decode.
+
+#![no_main]
+
+use fancy_garbling::{
+    circuit::{BinaryCircuit, CircuitType},
+    classic::{garble_with_rng, GarbledCircuit},
+    format::{self, FormatId},
+    WireMod2,
+};
+use libfuzzer_sys::fuzz_target;
+use rand::SeedableRng;
+use scuttlebutt::{AesRng, Block};
+
+/// The length of the garbling id and circuit digest starting the payload.
+const TAGS_LEN: usize = 16 + 32;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = GarbledCircuit::<WireMod2, BinaryCircuit>::from_bytes(data);
+
+    let circ = BinaryCircuit::parse(&include_bytes!("../../circuits/adder64.txt")[..]).unwrap();
+    let rng = AesRng::from_seed(Block::default());
+    let (en, honest) = garble_with_rng::<WireMod2, _>(&circ, rng).unwrap();
+    // Evaluation is only reached by payloads tagged for this garbling.
+    let honest = honest.to_bytes();
+    let mut payload = honest[format::HEADER_LEN..format::HEADER_LEN + TAGS_LEN].to_vec();
+    payload.extend_from_slice(data);
+    let bytes = format::encode(FormatId::GarbledCircuit, 2, &payload);
+    let gc = match GarbledCircuit::<WireMod2, BinaryCircuit>::from_bytes(&bytes) {
+        Ok(gc) => gc,
+        Err(_) => return,
+    };
+    let gb = en
+        .encode_garbler_inputs(&vec![0; circ.num_garbler_inputs()])
+        .unwrap();
+    let ev = en
+        .encode_evaluator_inputs(&vec![0; circ.num_evaluator_inputs()])
+        .unwrap();
+    let _ = gc.eval(&circ, &gb, &ev);
+});
@@ -0,0 +1,15 @@
+//! Parses arbitrary bytes as a Bristol-format circuit, and evaluates the
+//! circuit in the clear if it parses.
+
+#![no_main]
+
+use fancy_garbling::circuit::{eval_plain, BinaryCircuit, CircuitType};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(circ) = BinaryCircuit::parse(data) {
+        let gb = vec![0; circ.num_garbler_inputs()];
+        let ev = vec![0; circ.num_evaluator_inputs()];
+        let _ = eval_plain(&circ, &gb, &ev);
+    }
+});
@@ -0,0 +1,6 @@
+//! Ready-made protocols for common tasks, built on garbled circuits and the
+//! [`twopac`](crate::twopac) parties.
+
+pub mod compare;
+pub mod membership;
+pub mod oprf_aes;
@@ -0,0 +1,172 @@
+//! Secure comparison, after Yao's millionaires' problem: two parties learn how
+//! their 64-bit values compare, and nothing else about them.
+//!
+//! Each party calls [`run`] with its [`Party`] and value, and both get the
+//! ordering of their own value against the other's:
+//!
+//! ```ignore
+//! // The garbler, holding 3.
+//! assert_eq!(compare::run(Party::Garbler, channel, 3, AesRng::new())?, Ordering::Less);
+//!
+//! // The evaluator, holding 5.
+//! assert_eq!(compare::run(Party::Evaluator, channel, 5, AesRng::new())?, Ordering::Greater);
+//! ```
+//!
+//! The circuit is captured from the less-than and equality gadgets of
+//! [`BinaryGadgets`] by [`comparison_circuit`], and runs between
+//! [`twopac::semihonest`](crate::twopac::semihonest) parties in the default
+//! configuration with [`OutputMode::Both`]. A comparison takes 64 oblivious
+//! transfers and 320 AND gates.
+
+use crate::{
+    circuit::BinaryCircuit,
+    errors::{CircuitBuilderError, FancyError, TwopacError},
+    twopac::semihonest::{OutputMode, Party, SemiHonest},
+    util, BinaryGadgets, Fancy, FancyInput, Modulus, WireMod2,
+};
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, Block};
+use std::cmp::Ordering;
+
+/// The circuit comparing the garbler's 64-bit input `x` with the evaluator's
+/// `y`, outputting `x < y` and then `x == y`.
+///
+/// Each number is least significant bit first, as [`util::u128_to_bits`]
+/// writes it.
+pub fn comparison_circuit() -> Result<BinaryCircuit, FancyError> {
+    BinaryCircuit::from_fancy(|b, mut inputs| {
+        let x = inputs.bin_garbler(b, 64);
+        let y = inputs.bin_evaluator(b, 64);
+        let lt = b.bin_lt(&x, &y)?;
+        let eq = b.bin_eq_bundles(&x, &y)?;
+        b.outputs(&[lt, eq])?;
+        Ok(())
+    })
+    .map_err(|e| match e {
+        CircuitBuilderError::FancyError(e) => e,
+        e => FancyError::InvalidArg(e.to_string()),
+    })
+}
+
+/// Compare `my_value` with the other party's value, as `party`.
+///
+/// Returns `my_value.cmp(&their_value)`, which is the reverse of what the
+/// other party gets. Like [`Garbler::new`](crate::twopac::semihonest::Garbler::new),
+/// this blocks until the other party runs too.
+pub fn run<C, RNG>(
+    party: Party,
+    channel: C,
+    my_value: u64,
+    rng: RNG,
+) -> Result<Ordering, TwopacError>
+where
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng + SeedableRng<Seed = Block>,
+{
+    let circuit = comparison_circuit()?;
+    let bits = util::u128_to_bits(my_value.into(), 64);
+    let config = SemiHonest::builder().output_mode(OutputMode::Both);
+    let ordering = match party {
+        Party::Garbler => {
+            let mut gb = config.build_garbler::<_, _, WireMod2>(channel, rng)?;
+            let xs = gb.encode_many(&bits, &[2; 64])?;
+            let ys = gb.receive_many(&[2; 64])?;
+            let outputs = gb.eval_circuit(&circuit, &xs, &ys)?.ok_or_else(|| {
+                FancyError::InvalidArg("the garbler learns no output".to_string())
+            })?;
+            gb.get_channel().flush()?;
+            ordering(&outputs)?
+        }
+        Party::Evaluator => {
+            let mut ev = config.build_evaluator::<_, _, WireMod2>(channel, rng)?;
+            let xs = ev.receive_many(&[2; 64])?;
+            let ys = ev.encode_many(&bits, &[2; 64])?;
+            let outputs = ev.eval_circuit(&circuit, &xs, &ys)?;
+            ev.get_channel().flush()?;
+            ordering(&outputs)?.reverse()
+        }
+    };
+    Ok(ordering)
+}
+
+/// The ordering of the garbler's value against the evaluator's, from the
+/// outputs of [`comparison_circuit`].
+fn ordering(outputs: &[Modulus]) -> Result<Ordering, FancyError> {
+    match outputs {
+        [_, 1] => Ok(Ordering::Equal),
+        [1, _] => Ok(Ordering::Less),
+        [_, _] => Ok(Ordering::Greater),
+        _ => Err(FancyError::InvalidArgNum {
+            got: outputs.len(),
+            needed: 2,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::circuit::{eval_plain, CircuitType};
+    use scuttlebutt::{unix_channel_pair, AesRng};
+
+    /// Compare `x` and `y` between two parties, returning what the garbler,
+    /// holding `x`, and the evaluator, holding `y`, learn.
+    fn compare(x: u64, y: u64) -> (Ordering, Ordering) {
+        let (sender, receiver) = unix_channel_pair();
+        let handle =
+            std::thread::spawn(move || run(Party::Garbler, sender, x, AesRng::new()).unwrap());
+        let ev = run(Party::Evaluator, receiver, y, AesRng::new()).unwrap();
+        (handle.join().unwrap(), ev)
+    }
+
+    #[test]
+    fn test_edge_cases() {
+        for (x, y) in [
+            (0, 0),
+            (u64::MAX, u64::MAX),
+            (0, u64::MAX),
+            (u64::MAX, 0),
+            (0, 1),
+            (1 << 63, (1 << 63) - 1),
+            (42, 42),
+        ] {
+            assert_eq!(compare(x, y), (x.cmp(&y), y.cmp(&x)), "x={} y={}", x, y);
+        }
+    }
+
+    #[test]
+    fn test_random() {
+        let mut rng = AesRng::new();
+        for _ in 0..4 {
+            let (x, y) = (rng.gen::<u64>(), rng.gen::<u64>());
+            assert_eq!(compare(x, y), (x.cmp(&y), y.cmp(&x)), "x={} y={}", x, y);
+        }
+    }
+
+    #[test]
+    fn test_circuit() {
+        let c = comparison_circuit().unwrap();
+        assert_eq!(c.num_garbler_inputs(), 64);
+        assert_eq!(c.num_evaluator_inputs(), 64);
+        assert_eq!(c.get_output_refs().len(), 2);
+        assert_eq!(c.get_num_nonfree_gates(), 320);
+        let mut rng = AesRng::new();
+        for _ in 0..100 {
+            // Share the high bits often, so that ties are broken low down.
+            let x = rng.gen::<u64>();
+            let y = if rng.gen() {
+                x ^ (rng.gen::<u64>() >> 56)
+            } else {
+                rng.gen()
+            };
+            let out = eval_plain(
+                &c,
+                &util::u128_to_bits(x.into(), 64),
+                &util::u128_to_bits(y.into(), 64),
+            )
+            .unwrap();
+            assert_eq!(ordering(&out).unwrap(), x.cmp(&y), "x={} y={}", x, y);
+        }
+        assert!(ordering(&[1]).is_err());
+    }
+}
@@ -0,0 +1,239 @@
+//! Private set membership: the evaluator learns whether its element is in the
+//! garbler's set, and nothing else.
+//!
+//! [`membership_circuit`] compares the element with every item of the set and
+//! ORs the comparisons together in a balanced tree. Both parties know the
+//! size of the set and the width of its items, `n` and `b` below; the garbler
+//! runs [`garble`] and the evaluator [`evaluate`] on parties built as for any
+//! other circuit:
+//!
+//! ```ignore
+//! // The garbler.
+//! membership::garble(&mut gb, &[3, 14, 15, 92], 8)?;
+//!
+//! // The evaluator.
+//! let found = membership::evaluate(&mut ev, 15, 4, 8)?;
+//! ```
+//!
+//! # Cost
+//!
+//! Each comparison takes `b - 1` AND gates and the tree `n - 1` more, so the
+//! circuit has `n * b - 1` AND gates for a nonempty set. With the half-gates
+//! garbler, each AND gate is two 16-byte ciphertexts, and each item bit a
+//! 16-byte label, so the garbler sends about `48 * n * b` bytes, while the
+//! evaluator's element takes only `b` oblivious transfers. The protocol runs
+//! in the rounds of the oblivious transfer plus one.
+//!
+//! For `n = 1000` 64-bit items that is about 3 MB per query, and it grows
+//! linearly with the set, so a set of more than a few thousand items, or many
+//! queries against the same set, call for a dedicated private set
+//! intersection protocol instead. [`estimate`](crate::twopac::estimate) of
+//! the circuit gives the exact figures for a configuration.
+
+use crate::{
+    circuit::{BinaryCircuit, CircuitBuilder, CircuitRef},
+    errors::{CircuitBuilderError, FancyError, TwopacError},
+    twopac::semihonest::{Evaluator, Garbler},
+    util, BinaryBundle, BinaryGadgets, Fancy, FancyBinary, FancyInput, Modulus, WireMod2,
+};
+use ocelot::ot::{Receiver as OtReceiver, Sender as OtSender};
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, Block, SemiHonest};
+
+/// The circuit outputting whether the evaluator's `nbits`-bit input equals
+/// any of `set_size` `nbits`-bit garbler inputs.
+///
+/// The garbler inputs are the items one after the other, and each number is
+/// least significant bit first, as [`util::u128_to_bits`] writes it. Fails if
+/// `nbits` is not between 1 and 128.
+pub fn membership_circuit(set_size: usize, nbits: usize) -> Result<BinaryCircuit, FancyError> {
+    check_width(nbits)?;
+    let mut b = CircuitBuilder::<BinaryCircuit>::new();
+    let items = (0..set_size)
+        .map(|_| b.bin_garbler_input(nbits))
+        .collect::<Vec<_>>();
+    let element = b.bin_evaluator_input(nbits);
+    build(&mut b, &items, &element).map_err(|e| match e {
+        CircuitBuilderError::FancyError(e) => e,
+        e => FancyError::InvalidArg(e.to_string()),
+    })?;
+    Ok(b.finish())
+}
+
+fn build(
+    b: &mut CircuitBuilder<BinaryCircuit>,
+    items: &[BinaryBundle<CircuitRef>],
+    element: &BinaryBundle<CircuitRef>,
+) -> Result<(), CircuitBuilderError> {
+    let mut found = items
+        .iter()
+        .map(|item| b.bin_eq_bundles(item, element))
+        .collect::<Result<Vec<_>, _>>()?;
+    if found.is_empty() {
+        found.push(b.constant(0, 2)?);
+    }
+    // Halve the comparisons left each round, so the depth is logarithmic in
+    // the size of the set.
+    while found.len() > 1 {
+        found = found
+            .chunks(2)
+            .map(|pair| match pair {
+                [x, y] => b.or(x, y),
+                _ => Ok(pair[0]),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+    }
+    b.output(&found[0])?;
+    Ok(())
+}
+
+/// Run the garbler's side of a membership query against `set`, whose items
+/// are `nbits` wide.
+///
+/// Returns whether the evaluator's element is in the set if the garbler
+/// learns the output, as with [`OutputMode::Both`](crate::twopac::semihonest::OutputMode::Both),
+/// and `None` otherwise.
+pub fn garble<C, RNG, OT>(
+    gb: &mut Garbler<C, RNG, OT, WireMod2>,
+    set: &[u128],
+    nbits: usize,
+) -> Result<Option<bool>, TwopacError>
+where
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng + SeedableRng<Seed = Block>,
+    OT: OtSender<Msg = Block> + SemiHonest,
+{
+    let circuit = membership_circuit(set.len(), nbits)?;
+    let mut bits = Vec::with_capacity(set.len() * nbits);
+    for &item in set {
+        bits.extend(item_bits(item, nbits)?);
+    }
+    let xs = gb.encode_many(&bits, &vec![2; bits.len()])?;
+    let ys = gb.receive_many(&vec![2; nbits])?;
+    let outputs = gb.eval_circuit(&circuit, &xs, &ys)?;
+    Ok(outputs.map(|outputs| outputs == [1]))
+}
+
+/// Run the evaluator's side of a membership query of `element` against the
+/// garbler's set of `set_size` `nbits`-bit items, returning whether it is in
+/// the set.
+pub fn evaluate<C, RNG, OT>(
+    ev: &mut Evaluator<C, RNG, OT, WireMod2>,
+    element: u128,
+    set_size: usize,
+    nbits: usize,
+) -> Result<bool, TwopacError>
+where
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+    OT: OtReceiver<Msg = Block> + SemiHonest,
+{
+    let circuit = membership_circuit(set_size, nbits)?;
+    let bits = item_bits(element, nbits)?;
+    let xs = ev.receive_many(&vec![2; set_size * nbits])?;
+    let ys = ev.encode_many(&bits, &vec![2; nbits])?;
+    Ok(ev.eval_circuit(&circuit, &xs, &ys)? == [1])
+}
+
+fn check_width(nbits: usize) -> Result<(), FancyError> {
+    if nbits == 0 || nbits > 128 {
+        return Err(FancyError::InvalidArg(format!(
+            "items must be 1 to 128 bits wide, not {}",
+            nbits
+        )));
+    }
+    Ok(())
+}
+
+/// The bits of `x`, failing if it does not fit in `nbits`.
+fn item_bits(x: u128, nbits: usize) -> Result<Vec<Modulus>, FancyError> {
+    check_width(nbits)?;
+    if nbits < 128 && x >> nbits != 0 {
+        return Err(FancyError::InvalidArg(format!(
+            "{} does not fit in {} bits",
+            x, nbits
+        )));
+    }
+    Ok(util::u128_to_bits(x, nbits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{eval_plain, CircuitType},
+        twopac::semihonest::{OutputMode, SemiHonest},
+        util::RngExt,
+    };
+    use scuttlebutt::{unix_channel_pair, AesRng};
+
+    /// Query `element` against `set` between two parties, returning what the
+    /// garbler and the evaluator learn.
+    fn query(set: Vec<u128>, element: u128, nbits: usize) -> (Option<bool>, bool) {
+        let config = SemiHonest::builder().output_mode(OutputMode::Both);
+        let gb_config = config.clone();
+        let set_size = set.len();
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut gb = gb_config
+                .build_garbler::<_, _, WireMod2>(sender, AesRng::new())
+                .unwrap();
+            let found = garble(&mut gb, &set, nbits).unwrap();
+            gb.get_channel().flush().unwrap();
+            found
+        });
+        let mut ev = config
+            .build_evaluator::<_, _, WireMod2>(receiver, AesRng::new())
+            .unwrap();
+        let found = evaluate(&mut ev, element, set_size, nbits).unwrap();
+        (handle.join().unwrap(), found)
+    }
+
+    #[test]
+    fn test_hit_and_miss() {
+        let mut rng = AesRng::new();
+        let set = (0..100)
+            .map(|_| rng.gen_u128() % (1 << 32))
+            .collect::<Vec<_>>();
+        for i in [0, 37, 99] {
+            assert_eq!(query(set.clone(), set[i], 32), (Some(true), true));
+        }
+        let miss = (0..)
+            .map(|_| rng.gen_u128() % (1 << 32))
+            .find(|x| !set.contains(x))
+            .unwrap();
+        assert_eq!(query(set, miss, 32), (Some(false), false));
+    }
+
+    #[test]
+    fn test_small_sets() {
+        assert_eq!(query(vec![], 5, 8), (Some(false), false));
+        assert_eq!(query(vec![5], 5, 8), (Some(true), true));
+        assert_eq!(
+            query(vec![u128::MAX, 0, 1], u128::MAX, 128),
+            (Some(true), true)
+        );
+        assert_eq!(query(vec![1, 0], 1, 1), (Some(true), true));
+    }
+
+    #[test]
+    fn test_circuit() {
+        for (n, nbits) in [(1, 1), (5, 8), (64, 16), (1000, 64)] {
+            let c = membership_circuit(n, nbits).unwrap();
+            assert_eq!(c.get_num_nonfree_gates(), n * nbits - 1, "n={}", n);
+        }
+        let c = membership_circuit(7, 4).unwrap();
+        let set = (0..7).map(|i| 2 * i + 1).collect::<Vec<u128>>();
+        let gb = set
+            .iter()
+            .flat_map(|&x| util::u128_to_bits(x, 4))
+            .collect::<Vec<_>>();
+        for x in 0..16 {
+            let out = eval_plain(&c, &gb, &util::u128_to_bits(x, 4)).unwrap();
+            assert_eq!(out, [set.contains(&x) as Modulus], "x={}", x);
+        }
+        assert!(membership_circuit(3, 0).is_err());
+        assert!(membership_circuit(3, 129).is_err());
+        assert!(item_bits(256, 8).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+//! Oblivious evaluation of AES-128 as a pseudorandom function: the evaluator
+//! learns `AES_k(m)` for its message `m` and the garbler's key `k`, and
+//! neither party learns the other's input.
+//!
+//! A [`KeyHolder`] plays the garbler and an [`InputHolder`] the evaluator,
+//! both running the `AES-non-expanded` Bristol circuit, which is embedded in
+//! the crate. Keys, messages, and ciphertexts are plain bytes, as the `aes`
+//! crate takes them:
+//!
+//! ```ignore
+//! // The garbler.
+//! let mut kh = KeyHolder::new(channel, AesRng::new(), OutputMode::Evaluator)?;
+//! kh.evaluate(&key)?;
+//!
+//! // The evaluator.
+//! let mut ih = InputHolder::new(channel, AesRng::new(), OutputMode::Evaluator)?;
+//! let ciphertext = ih.evaluate(&message)?;
+//! ```
+//!
+//! With [`OutputMode::Both`], the key holder learns the ciphertext as well.
+//! A pair of parties can evaluate the function any number of times, with a
+//! new key or message each time; each evaluation takes 128 oblivious
+//! transfers and 6,800 AND gates.
+
+use crate::{
+    circuit::{BinaryCircuit, CircuitType},
+    errors::{FancyError, TwopacError},
+    twopac::semihonest::{Evaluator, Garbler, OutputMode, SemiHonest},
+    BitOrder, FancyInput, Modulus, WireMod2,
+};
+use ocelot::ot::{AlszReceiver, AlszSender, Receiver as OtReceiver, Sender as OtSender};
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, Block, SemiHonest as SemiHonestOt};
+
+/// The AES-128 circuit, taking the message as the garbler's inputs and the key
+/// as the evaluator's, each as bytes most significant bit first, and
+/// outputting the ciphertext the same way.
+///
+/// The parties here hold the other inputs, so each passes the wires of the
+/// key as the evaluator's inputs and those of the message as the garbler's;
+/// a circuit sees wires rather than who encoded them.
+fn aes_128() -> BinaryCircuit {
+    BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+        "../../circuits/AES-non-expanded.txt"
+    )))
+    .expect("the embedded AES circuit parses")
+}
+
+/// The ciphertext of the outputs of the AES circuit.
+fn ciphertext(outputs: &[Modulus]) -> Result<[u8; 16], FancyError> {
+    let bytes = BitOrder::MsbFirst.bits_to_bytes(outputs)?;
+    bytes
+        .try_into()
+        .map_err(|bytes: Vec<u8>| FancyError::InvalidArgNum {
+            got: bytes.len(),
+            needed: 16,
+        })
+}
+
+/// The holder of the AES key, playing the garbler.
+pub struct KeyHolder<C, RNG, OT> {
+    garbler: Garbler<C, RNG, OT, WireMod2>,
+    circuit: BinaryCircuit,
+}
+
+impl<C, RNG> KeyHolder<C, RNG, AlszSender>
+where
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng + SeedableRng<Seed = Block>,
+{
+    /// Make a key holder with the default configuration and the given output
+    /// mode, which the input holder must share.
+    ///
+    /// Like [`Garbler::new`], this blocks until the input holder is made too.
+    pub fn new(channel: C, rng: RNG, mode: OutputMode) -> Result<Self, TwopacError> {
+        let garbler = SemiHonest::builder()
+            .output_mode(mode)
+            .build_garbler(channel, rng)?;
+        Ok(Self::from_garbler(garbler))
+    }
+}
+
+impl<C, RNG, OT> KeyHolder<C, RNG, OT>
+where
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng + SeedableRng<Seed = Block>,
+    OT: OtSender<Msg = Block> + SemiHonestOt,
+{
+    /// Make a key holder from a garbler built with any
+    /// [configuration](crate::twopac::semihonest::SemiHonestBuilder).
+    pub fn from_garbler(garbler: Garbler<C, RNG, OT, WireMod2>) -> Self {
+        KeyHolder {
+            garbler,
+            circuit: aes_128(),
+        }
+    }
+
+    /// Evaluate AES under `key` on the input holder's next message.
+    ///
+    /// Returns the ciphertext if the output mode is [`OutputMode::Both`], and
+    /// `None` otherwise.
+    pub fn evaluate(&mut self, key: &[u8; 16]) -> Result<Option<[u8; 16]>, TwopacError> {
+        let key = self.circuit.assemble_evaluator_inputs(&[key])?;
+        let keys = self.garbler.encode_many(&key, &[2; 128])?;
+        let messages = self.garbler.receive_many(&[2; 128])?;
+        match self.garbler.eval_circuit(&self.circuit, &messages, &keys)? {
+            Some(outputs) => Ok(Some(ciphertext(&outputs)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the garbler, e.g. to flush its channel or read its stats.
+    pub fn garbler(&mut self) -> &mut Garbler<C, RNG, OT, WireMod2> {
+        &mut self.garbler
+    }
+}
+
+/// The holder of the message, playing the evaluator.
+pub struct InputHolder<C, RNG, OT> {
+    evaluator: Evaluator<C, RNG, OT, WireMod2>,
+    circuit: BinaryCircuit,
+}
+
+impl<C, RNG> InputHolder<C, RNG, AlszReceiver>
+where
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+{
+    /// Make an input holder with the default configuration and the given
+    /// output mode, which the key holder must share.
+    ///
+    /// Like [`Evaluator::new`], this blocks until the key holder is made too.
+    pub fn new(channel: C, rng: RNG, mode: OutputMode) -> Result<Self, TwopacError> {
+        let evaluator = SemiHonest::builder()
+            .output_mode(mode)
+            .build_evaluator(channel, rng)?;
+        Ok(Self::from_evaluator(evaluator))
+    }
+}
+
+impl<C, RNG, OT> InputHolder<C, RNG, OT>
+where
+    C: AbstractChannel,
+    RNG: CryptoRng + Rng,
+    OT: OtReceiver<Msg = Block> + SemiHonestOt,
+{
+    /// Make an input holder from an evaluator built with any
+    /// [configuration](crate::twopac::semihonest::SemiHonestBuilder).
+    pub fn from_evaluator(evaluator: Evaluator<C, RNG, OT, WireMod2>) -> Self {
+        InputHolder {
+            evaluator,
+            circuit: aes_128(),
+        }
+    }
+
+    /// Evaluate AES under the key holder's next key on `message`, returning
+    /// the ciphertext.
+    pub fn evaluate(&mut self, message: &[u8; 16]) -> Result<[u8; 16], TwopacError> {
+        let message = self.circuit.assemble_garbler_inputs(&[message])?;
+        let keys = self.evaluator.receive_many(&[2; 128])?;
+        let messages = self.evaluator.encode_many(&message, &[2; 128])?;
+        let outputs = self
+            .evaluator
+            .eval_circuit(&self.circuit, &messages, &keys)?;
+        Ok(ciphertext(&outputs)?)
+    }
+
+    /// Get the evaluator, e.g. to read its stats.
+    pub fn evaluator(&mut self) -> &mut Evaluator<C, RNG, OT, WireMod2> {
+        &mut self.evaluator
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes::{
+        cipher::{BlockEncrypt, KeyInit},
+        Aes128,
+    };
+    use scuttlebutt::{unix_channel_pair, AesRng};
+
+    fn aes(key: &[u8; 16], message: &[u8; 16]) -> [u8; 16] {
+        let mut block = (*message).into();
+        Aes128::new(&(*key).into()).encrypt_block(&mut block);
+        block.into()
+    }
+
+    /// Evaluate AES on `keys` and `messages` pairwise in one session,
+    /// returning what the key holder and the input holder learn.
+    fn run(
+        mode: OutputMode,
+        keys: Vec<[u8; 16]>,
+        messages: Vec<[u8; 16]>,
+    ) -> (Vec<Option<[u8; 16]>>, Vec<[u8; 16]>) {
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut kh = KeyHolder::new(sender, AesRng::new(), mode).unwrap();
+            let outputs = keys
+                .iter()
+                .map(|key| kh.evaluate(key).unwrap())
+                .collect::<Vec<_>>();
+            kh.garbler().get_channel().flush().unwrap();
+            outputs
+        });
+        let mut ih = InputHolder::new(receiver, AesRng::new(), mode).unwrap();
+        let outputs = messages
+            .iter()
+            .map(|message| ih.evaluate(message).unwrap())
+            .collect();
+        (handle.join().unwrap(), outputs)
+    }
+
+    #[test]
+    fn test_random() {
+        let mut rng = AesRng::new();
+        let keys = (0..3).map(|_| rng.gen::<[u8; 16]>()).collect::<Vec<_>>();
+        let messages = (0..3).map(|_| rng.gen::<[u8; 16]>()).collect::<Vec<_>>();
+        let expected = keys
+            .iter()
+            .zip(&messages)
+            .map(|(k, m)| aes(k, m))
+            .collect::<Vec<_>>();
+
+        let (kh, ih) = run(OutputMode::Evaluator, keys.clone(), messages.clone());
+        assert_eq!(kh, [None; 3]);
+        assert_eq!(ih, expected);
+
+        let (kh, ih) = run(OutputMode::Both, keys, messages);
+        assert_eq!(kh, expected.iter().copied().map(Some).collect::<Vec<_>>());
+        assert_eq!(ih, expected);
+    }
+
+    #[test]
+    fn test_fips_197() {
+        // The example vector of FIPS 197, appendix C.1.
+        let key = *b"\x00\x01\x02\x03\x04\x05\x06\x07\x08\x09\x0a\x0b\x0c\x0d\x0e\x0f";
+        let message = *b"\x00\x11\x22\x33\x44\x55\x66\x77\x88\x99\xaa\xbb\xcc\xdd\xee\xff";
+        let expected = *b"\x69\xc4\xe0\xd8\x6a\x7b\x04\x30\xd8\xcd\xb7\x80\x70\xb4\xc5\x5a";
+        assert_eq!(aes(&key, &message), expected);
+        let (_, ih) = run(OutputMode::Evaluator, vec![key], vec![message]);
+        assert_eq!(ih, [expected]);
+    }
+}
@@ -0,0 +1,231 @@
+//! Inspect, evaluate, and garble circuits in Bristol format.
+//!
+//! Inputs and outputs are given in hex, where bit `i` of a value is bit
+//! `i % 8` (least significant first) of byte `i / 8`.
+
+use clap::{Parser, Subcommand};
+use fancy_garbling::{
+    apps::oprf_aes::{InputHolder, KeyHolder},
+    circuit::{eval_plain, BinaryCircuit, CircuitType},
+    classic::{self, garble_with_rng},
+    errors::TwopacError,
+    twopac::semihonest::OutputMode,
+    Modulus, WireMod2,
+};
+use rand::SeedableRng;
+use scuttlebutt::{unix_channel_pair, AbstractChannel, AesRng, Block};
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+#[derive(Parser)]
+#[command(about = "Inspect, evaluate, and garble circuits in Bristol format")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print statistics about a circuit.
+    Info {
+        /// The circuit file.
+        circuit: PathBuf,
+    },
+    /// Evaluate a circuit in plaintext.
+    Eval {
+        /// The circuit file.
+        circuit: PathBuf,
+        /// The garbler's input, in hex.
+        #[arg(long, default_value = "")]
+        garbler_hex: String,
+        /// The evaluator's input, in hex.
+        #[arg(long, default_value = "")]
+        evaluator_hex: String,
+    },
+    /// Garble a circuit, writing the encoder followed by the garbled circuit,
+    /// each framed as described in the `fancy_garbling::format` docs.
+    ///
+    /// The output contains every input wire label, so it must be kept secret.
+    Garble {
+        /// The circuit file.
+        circuit: PathBuf,
+        /// Seed for the wire labels. Fresh randomness is used if omitted.
+        #[arg(long)]
+        seed: Option<u128>,
+        /// The output file.
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Measure garbling and evaluation throughput.
+    Bench {
+        /// The circuit file.
+        circuit: PathBuf,
+        /// Print the measurements as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Evaluate AES-128 obliviously, with a key holder and an input holder
+    /// on two threads, as `fancy_garbling::apps::oprf_aes` does.
+    ///
+    /// The key, the message, and the ciphertext are 16 bytes in hex, in the
+    /// order AES takes them rather than bit by bit.
+    OprfAes {
+        /// The key holder's key, in hex.
+        #[arg(long)]
+        key: String,
+        /// The input holder's message, in hex.
+        #[arg(long)]
+        message: String,
+        /// Let the key holder learn the ciphertext too.
+        #[arg(long)]
+        both: bool,
+    },
+}
+
+fn main() {
+    if let Err(e) = run(Cli::parse()) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    match cli.command {
+        Command::Info { circuit } => info(&parse(&circuit)?),
+        Command::Eval {
+            circuit,
+            garbler_hex,
+            evaluator_hex,
+        } => {
+            let c = parse(&circuit)?;
+            let gb = decode_bits(&garbler_hex, c.num_garbler_inputs())?;
+            let ev = decode_bits(&evaluator_hex, c.num_evaluator_inputs())?;
+            let outputs = eval_plain(&c, &gb, &ev)?;
+            println!("hex:    {}", encode_bits(&outputs));
+            println!(
+                "binary: {}",
+                outputs.iter().map(|b| b.to_string()).collect::<String>()
+            );
+            Ok(())
+        }
+        Command::Garble {
+            circuit,
+            seed,
+            output,
+        } => {
+            let c = parse(&circuit)?;
+            let rng = match seed {
+                Some(seed) => AesRng::from_seed(Block::from(seed)),
+                None => AesRng::new(),
+            };
+            let (en, gc) = garble_with_rng::<WireMod2, _>(&c, rng)?;
+            let mut writer = BufWriter::new(File::create(&output)?);
+            writer.write_all(&en.to_bytes())?;
+            writer.write_all(&gc.to_bytes())?;
+            writer.flush()?;
+            println!(
+                "wrote {} garbled rows and constants to {}",
+                gc.size(),
+                output.display()
+            );
+            Ok(())
+        }
+        Command::Bench { circuit, json } => {
+            let c = parse(&circuit)?;
+            let profile = classic::profile::<WireMod2, _, _>(&c, &mut rand::thread_rng())?;
+            if json {
+                println!("{}", profile.to_json());
+            } else {
+                print!("{}", profile);
+            }
+            Ok(())
+        }
+        Command::OprfAes { key, message, both } => oprf_aes(&key, &message, both),
+    }
+}
+
+fn parse(path: &Path) -> Result<BinaryCircuit, Box<dyn Error>> {
+    let reader = BufReader::new(File::open(path)?);
+    BinaryCircuit::parse(reader)
+        .map_err(|e| format!("failed to parse {}: {}", path.display(), e).into())
+}
+
+fn info(c: &BinaryCircuit) -> Result<(), Box<dyn Error>> {
+    let stats = c.stats();
+    println!("garbler inputs:     {:16}", stats.ngarbler_inputs);
+    println!("evaluator inputs:   {:16}", stats.nevaluator_inputs);
+    println!("outputs:            {:16}", stats.noutputs);
+    println!(
+        "gates:              {:16}",
+        stats.nadds + stats.nmuls + stats.nsubs
+    );
+    println!("  xor:              {:16}", stats.nadds);
+    println!("  and:              {:16}", stats.nmuls);
+    println!("  inv:              {:16}", stats.nsubs);
+    println!("and depth:          {:16}", stats.mul_depth);
+    println!("width:              {:16}", c.liveness().width());
+    Ok(())
+}
+
+fn oprf_aes(key: &str, message: &str, both: bool) -> Result<(), Box<dyn Error>> {
+    let key = decode_block(key)?;
+    let message = decode_block(message)?;
+    let mode = if both {
+        OutputMode::Both
+    } else {
+        OutputMode::Evaluator
+    };
+    let (sender, receiver) = unix_channel_pair();
+    let key_holder = std::thread::spawn(move || -> Result<_, TwopacError> {
+        let mut kh = KeyHolder::new(sender, AesRng::new(), mode)?;
+        let ciphertext = kh.evaluate(&key)?;
+        kh.garbler().get_channel().flush()?;
+        Ok(ciphertext)
+    });
+    let mut ih = InputHolder::new(receiver, AesRng::new(), mode)?;
+    let ciphertext = ih.evaluate(&message)?;
+    let learned = key_holder.join().map_err(|_| "the key holder panicked")??;
+    println!("input holder: {}", hex::encode(ciphertext));
+    match learned {
+        Some(ciphertext) => println!("key holder:   {}", hex::encode(ciphertext)),
+        None => println!("key holder:   nothing"),
+    }
+    Ok(())
+}
+
+/// Decode 16 bytes from `hex`.
+fn decode_block(hex: &str) -> Result<[u8; 16], Box<dyn Error>> {
+    hex::decode(hex)?
+        .try_into()
+        .map_err(|_| format!("expected 32 hex digits, got {}", hex.len()).into())
+}
+
+/// Decode `nbits` bits from `hex`, least significant bit of each byte first.
+fn decode_bits(hex: &str, nbits: usize) -> Result<Vec<Modulus>, Box<dyn Error>> {
+    let bytes = hex::decode(hex)?;
+    if bytes.len() != (nbits + 7) / 8 {
+        return Err(format!(
+            "expected {} hex digits for {} input bits, got {}",
+            (nbits + 7) / 8 * 2,
+            nbits,
+            hex.len()
+        )
+        .into());
+    }
+    Ok((0..nbits)
+        .map(|i| ((bytes[i / 8] >> (i % 8)) & 1) as Modulus)
+        .collect())
+}
+
+/// Encode bits as hex, least significant bit of each byte first.
+fn encode_bits(bits: &[Modulus]) -> String {
+    let mut bytes = vec![0u8; (bits.len() + 7) / 8];
+    for (i, &b) in bits.iter().enumerate() {
+        bytes[i / 8] |= (b as u8 & 1) << (i % 8);
+    }
+    hex::encode(bytes)
+}
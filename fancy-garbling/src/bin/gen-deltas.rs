@@ -1,6 +1,6 @@
 extern crate fancy_garbling;
 
-use fancy_garbling::{AllWire, WireLabel};
+use fancy_garbling::{AllWire, Modulus, WireLabel};
 use std::collections::HashMap;
 
 fn main() {
@@ -10,7 +10,7 @@ fn main() {
         match arg.as_str() {
             "-h" | "--help" => usage_and_exit(),
             q => {
-                match q.parse::<u16>() {
+                match q.parse::<Modulus>() {
                     Ok(q) => {
                         // Generate the delta
                         deltas.insert(q, AllWire::rand_delta(&mut rng, q));
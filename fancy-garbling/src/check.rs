@@ -0,0 +1,208 @@
+//! Differential testing of gadgets against their plaintext specifications.
+//!
+//! [`gadget`] runs a [`Gadget`] on random inputs with every way this crate
+//! has of computing it, and checks each result against a specification
+//! written over plain values:
+//!
+//! * in plaintext, with [`Dummy`];
+//! * garbled and then evaluated, as [`classic`](crate::classic) does, with
+//!   the garbled gates held in memory;
+//! * streamed between a semi-honest [`Garbler`](semihonest::Garbler) and
+//!   [`Evaluator`](semihonest::Evaluator) running in two threads, with the
+//!   evaluator's inputs sent by oblivious transfer.
+//!
+//! Gadgets are generic over the [`Fancy`](crate::Fancy) object they run on,
+//! which closures cannot be, so they are written as implementations of
+//! [`Gadget`], or with the [`gadget!`](crate::gadget!) macro when they capture
+//! nothing:
+//!
+//! ```ignore
+//! use fancy_garbling::{check, gadget, FancyArithmetic};
+//!
+//! check::gadget(
+//!     gadget!(|f, xs| Ok(vec![f.add_many(xs)?])),
+//!     |xs| vec![xs.iter().sum::<u16>() % 7],
+//!     &[7; 4],
+//!     32,
+//!     &mut rand::thread_rng(),
+//! );
+//! ```
+//!
+//! This module needs the `test-utils` feature, and is only built on Unix.
+
+use crate::{
+    dummy::Dummy,
+    errors::{GarblerError, TwopacError},
+    garble::{Evaluator, Garbler},
+    twopac::semihonest,
+    util::RngExt,
+    AllWire, Fancy, FancyArithmetic, FancyBinary, FancyInput, Modulus,
+};
+use rand::{Rng, SeedableRng};
+use scuttlebutt::{unix_channel_pair, AbstractChannel, AesRng, Channel, UnixChannel};
+use std::{fmt::Debug, io, rc::Rc};
+
+/// A computation on wires, generic over the [`Fancy`](crate::Fancy) object
+/// computing it.
+pub trait Gadget {
+    /// Compute the gadget's output wires from its input wires.
+    fn run<F: FancyArithmetic + FancyBinary>(
+        &self,
+        f: &mut F,
+        inputs: &[F::Item],
+    ) -> Result<Vec<F::Item>, F::Error>;
+}
+
+/// Make a [`Gadget`] from closure-like syntax, `gadget!(|f, xs| body)`, where
+/// `f` is the [`Fancy`](crate::Fancy) object, `xs` the input wires, and
+/// `body` evaluates to a `Result` of the output wires.
+///
+/// The body cannot capture variables; implement [`Gadget`] on a struct for
+/// gadgets with parameters.
+#[macro_export]
+macro_rules! gadget {
+    (|$f:ident, $xs:ident| $body:expr) => {{
+        struct Gadget;
+        impl $crate::check::Gadget for Gadget {
+            fn run<F: $crate::FancyArithmetic + $crate::FancyBinary>(
+                &self,
+                $f: &mut F,
+                $xs: &[F::Item],
+            ) -> Result<Vec<F::Item>, F::Error> {
+                $body
+            }
+        }
+        Gadget
+    }};
+}
+
+/// Check `gadget` against `spec` on `trials` random inputs of moduli
+/// `input_moduli`, drawn from `rng`.
+///
+/// In the two-party run, the garbler provides the first half of the inputs
+/// and the evaluator the rest.
+///
+/// # Panics
+///
+/// Panics, naming the inputs and the way the gadget was run, on the first
+/// output that differs from `spec`, or if the gadget fails.
+pub fn gadget<G, S, R>(gadget: G, spec: S, input_moduli: &[Modulus], trials: usize, rng: &mut R)
+where
+    G: Gadget + Sync,
+    S: Fn(&[Modulus]) -> Vec<Modulus>,
+    R: Rng,
+{
+    for _ in 0..trials {
+        let inputs = input_moduli
+            .iter()
+            .map(|&q| rng.gen_residue(q))
+            .collect::<Vec<_>>();
+        let expected = spec(&inputs);
+        let seed = rng.gen();
+        let check = |path: &str, outputs: Vec<Modulus>| {
+            assert_eq!(
+                outputs, expected,
+                "{} disagrees with the specification on inputs {:?}",
+                path, inputs
+            );
+        };
+        check("Dummy", plaintext(&gadget, &inputs, input_moduli));
+        check(
+            "garble and eval",
+            garbled(&gadget, &inputs, input_moduli, AesRng::from_seed(seed)),
+        );
+        check(
+            "twopac",
+            twopac(&gadget, &inputs, input_moduli, AesRng::from_seed(seed)),
+        );
+    }
+}
+
+/// Unwrap the result of running a gadget, naming the way it was run.
+fn expect<T, E: Debug>(r: Result<T, E>, path: &str, inputs: &[Modulus]) -> T {
+    r.unwrap_or_else(|e| panic!("{} failed on inputs {:?}: {:?}", path, inputs, e))
+}
+
+fn plaintext<G: Gadget>(gadget: &G, inputs: &[Modulus], moduli: &[Modulus]) -> Vec<Modulus> {
+    let mut f = Dummy::new();
+    let r = (|| {
+        let xs = f.encode_many(inputs, moduli)?;
+        let zs = gadget.run(&mut f, &xs)?;
+        zs.iter()
+            .map(|z| f.output(z).map(Option::unwrap))
+            .collect::<Result<Vec<_>, _>>()
+    })();
+    expect(r, "Dummy", inputs)
+}
+
+fn garbled<G: Gadget>(
+    gadget: &G,
+    inputs: &[Modulus],
+    moduli: &[Modulus],
+    rng: AesRng,
+) -> Vec<Modulus> {
+    let channel = Channel::new(io::empty(), Vec::new());
+    let mut gb = Garbler::<_, _, AllWire>::new(channel.clone(), rng);
+    let r = (|| {
+        let (zeros, xs) = gb.encode_many_wires(inputs, moduli)?;
+        for z in gadget.run(&mut gb, &zeros)? {
+            gb.output(&z)?;
+        }
+        Ok::<_, GarblerError>(xs)
+    })();
+    let xs = expect(r, "garbling", inputs);
+    drop(gb);
+    let gc = Rc::try_unwrap(channel.writer())
+        .expect("the garbler has been dropped")
+        .into_inner();
+
+    let mut ev = Evaluator::<_, AllWire>::new(Channel::new(io::Cursor::new(gc), io::sink()));
+    let r = (|| {
+        gadget
+            .run(&mut ev, &xs)?
+            .iter()
+            .map(|z| ev.output(z).map(Option::unwrap))
+            .collect::<Result<Vec<_>, _>>()
+    })();
+    expect(r, "evaluation", inputs)
+}
+
+fn twopac<G: Gadget + Sync>(
+    gadget: &G,
+    inputs: &[Modulus],
+    moduli: &[Modulus],
+    mut rng: AesRng,
+) -> Vec<Modulus> {
+    type Gb = semihonest::Garbler<UnixChannel, AesRng, ocelot::ot::AlszSender, AllWire>;
+    type Ev = semihonest::Evaluator<UnixChannel, AesRng, ocelot::ot::AlszReceiver, AllWire>;
+
+    let (gb_rng, ev_rng) = (AesRng::from_seed(rng.gen()), AesRng::from_seed(rng.gen()));
+    let n = inputs.len() / 2;
+    let (sender, receiver) = unix_channel_pair();
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let r = (|| {
+                let mut gb = Gb::new(sender, gb_rng)?;
+                let mut xs = gb.encode_many(&inputs[..n], &moduli[..n])?;
+                xs.extend(gb.receive_many(&moduli[n..])?);
+                for z in gadget.run(&mut gb, &xs)? {
+                    gb.output(&z)?;
+                }
+                gb.get_channel().flush()?;
+                Ok::<_, TwopacError>(())
+            })();
+            expect(r, "twopac garbler", inputs);
+        });
+        let r = (|| {
+            let mut ev = Ev::new(receiver, ev_rng)?;
+            let mut xs = ev.receive_many(&moduli[..n])?;
+            xs.extend(ev.encode_many(&inputs[n..], &moduli[n..])?);
+            gadget
+                .run(&mut ev, &xs)?
+                .iter()
+                .map(|z| ev.output(z).map(Option::unwrap))
+                .collect::<Result<Vec<_>, _>>()
+        })();
+        expect(r, "twopac evaluator", inputs)
+    })
+}
@@ -4,20 +4,56 @@
 use crate::{
     check_binary, derive_binary,
     dummy::{Dummy, DummyVal},
-    errors::{CircuitBuilderError, DummyError, FancyError},
-    fancy::{BinaryBundle, CrtBundle, Fancy, FancyInput, HasModulus},
-    informer::Informer,
-    FancyArithmetic, FancyBinary,
+    errors::{CircuitBuilderError, CircuitValidationError, DummyError, FancyError},
+    fancy::{check_inputs, check_moduli, BinaryBundle, CrtBundle, Fancy, FancyInput, HasModulus},
+    informer::{Informer, InformerStats},
+    util::BitOrder,
+    FancyArithmetic, FancyBinary, Modulus, SecurityParams,
 };
+pub(crate) use custom::check_arity;
+use custom::CustomGates;
+use itertools::Either;
 use itertools::Itertools;
-use std::{collections::HashMap, fmt::Display};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+#[cfg(feature = "std")]
+mod bristol;
+mod cache;
+mod capture;
+mod compose;
+mod custom;
+mod dedup;
+mod equivalence;
+mod input;
+mod levels;
+mod prune;
+mod registry;
+mod stats;
+#[cfg(feature = "std")]
+pub use crate::parser::{ParseLimits, DEFAULT_PARSE_LIMIT};
+pub use cache::CircuitCache;
+pub use capture::CapturedInputs;
+pub use custom::{lower_custom_gate, CustomGate, Majority};
+pub use equivalence::{find_counterexample, Counterexample};
+pub use input::CircuitInput;
+pub use levels::Levels;
+pub(crate) use levels::Sources;
+pub use registry::{CircuitRegistry, RegisteredCircuit};
+#[cfg(feature = "std")]
+pub(crate) use stats::outputs_size;
+pub use stats::CircuitStats;
 
 /// The index and modulus of a gate in a circuit.
 #[derive(Clone, Copy, Debug, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CircuitRef {
     pub(crate) ix: usize,
-    pub(crate) modulus: u16,
+    pub(crate) modulus: Modulus,
 }
 
 impl std::fmt::Display for CircuitRef {
@@ -27,40 +63,54 @@ impl std::fmt::Display for CircuitRef {
 }
 
 impl HasModulus for CircuitRef {
-    fn modulus(&self) -> u16 {
+    fn modulus(&self) -> Modulus {
         self.modulus
     }
 }
 
 /// Static representation of arithmetic computation supported by fancy garbling.
+///
+/// With the `serde` feature, circuits are serialized with a format version and
+/// validated when deserialized.
 #[derive(Clone, Debug, PartialEq)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ArithmeticCircuit {
     pub(crate) gates: Vec<ArithmeticGate>,
-    pub(crate) gate_moduli: Vec<u16>,
+    pub(crate) gate_moduli: Vec<Modulus>,
     pub(crate) garbler_input_refs: Vec<CircuitRef>,
     pub(crate) evaluator_input_refs: Vec<CircuitRef>,
+    pub(crate) public_input_refs: Vec<CircuitRef>,
     pub(crate) const_refs: Vec<CircuitRef>,
     pub(crate) output_refs: Vec<CircuitRef>,
     pub(crate) num_nonfree_gates: usize,
+    pub(crate) output_groups: Vec<usize>,
+    pub(crate) digest: DigestCache,
 }
 
 /// Static representation of binary computation supported by fancy garbling.
+///
+/// With the `serde` feature, circuits are serialized with a format version and
+/// validated when deserialized.
 #[derive(Clone, Debug, PartialEq)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BinaryCircuit {
     pub(crate) gates: Vec<BinaryGate>,
     pub(crate) garbler_input_refs: Vec<CircuitRef>,
     pub(crate) evaluator_input_refs: Vec<CircuitRef>,
+    pub(crate) public_input_refs: Vec<CircuitRef>,
     pub(crate) const_refs: Vec<CircuitRef>,
     pub(crate) output_refs: Vec<CircuitRef>,
     pub(crate) num_nonfree_gates: usize,
+    pub(crate) output_groups: Vec<usize>,
+    pub(crate) custom_gates: CustomGates,
+    pub(crate) digest: DigestCache,
 }
 
 /// Arithmetic computation supported by fancy garbling.
 ///
 /// `id` represents the gate number. `out` gives the output wire index; if `out
 /// = None`, then we use the gate index as the output wire index.
+///
+/// The serialized form of a gate depends on the order of the variants, so new
+/// variants must only be added at the end.
 #[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ArithmeticGate {
@@ -77,7 +127,7 @@ pub enum ArithmeticGate {
     /// Constant value
     Constant {
         /// Value of constant
-        val: u16,
+        val: Modulus,
     },
     /// Add gate
     Add {
@@ -107,7 +157,7 @@ pub enum ArithmeticGate {
         xref: CircuitRef,
 
         /// Constant to muiltiply by
-        c: u16,
+        c: Modulus,
 
         /// Output wire index
         out: Option<usize>,
@@ -132,7 +182,7 @@ pub enum ArithmeticGate {
         xref: CircuitRef,
 
         /// Projection truth table
-        tt: Vec<u16>,
+        tt: Vec<Modulus>,
 
         /// Gate number
         id: usize,
@@ -140,12 +190,20 @@ pub enum ArithmeticGate {
         /// Output wire index
         out: Option<usize>,
     },
+    /// Input known to both parties
+    PublicInput {
+        /// Gate number
+        id: usize,
+    },
 }
 
 /// Binary computation supported by fancy garbling.
 ///
 /// `id` represents the gate number. `out` gives the output wire index; if `out
 /// = None`, then we use the gate index as the output wire index.
+///
+/// The serialized form of a gate depends on the order of the variants, so new
+/// variants must only be added at the end.
 #[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinaryGate {
@@ -162,7 +220,7 @@ pub enum BinaryGate {
     /// Constant value
     Constant {
         /// Value of constant
-        val: u16,
+        val: Modulus,
     },
 
     /// Xor gate
@@ -198,6 +256,115 @@ pub enum BinaryGate {
         /// Output wire index
         out: Option<usize>,
     },
+    /// Input known to both parties
+    PublicInput {
+        /// Gate number
+        id: usize,
+    },
+    /// Custom gate, garbled as its [`CustomGate`] says
+    Custom {
+        /// Index of the gate among the custom gates of the circuit
+        gate: usize,
+
+        /// References to the inputs
+        inputs: Vec<CircuitRef>,
+
+        /// Gate number
+        id: usize,
+
+        /// Output wire index
+        out: Option<usize>,
+    },
+}
+
+/// The wires read by a gate.
+#[derive(Clone, Copy)]
+enum GateInputs<'a> {
+    /// At most two wires, of a built-in gate.
+    Fixed([Option<usize>; 2]),
+    /// The wires of a custom gate.
+    Refs(&'a [CircuitRef]),
+}
+
+impl<'a> GateInputs<'a> {
+    fn iter(self) -> impl Iterator<Item = usize> + 'a {
+        match self {
+            Self::Fixed(ixs) => Either::Left(ixs.into_iter().flatten()),
+            Self::Refs(refs) => Either::Right(refs.iter().map(|r| r.ix)),
+        }
+    }
+
+    /// Call `f` on each wire read, in the order of [`GateInputs::iter`].
+    ///
+    /// Evaluating a circuit calls this for every gate, and stepping through
+    /// the iterator instead costs more than evaluating most binary gates.
+    #[inline]
+    fn for_each(self, mut f: impl FnMut(usize)) {
+        match self {
+            Self::Fixed(ixs) => {
+                for ix in ixs.into_iter().flatten() {
+                    f(ix);
+                }
+            }
+            Self::Refs(refs) => {
+                for r in refs {
+                    f(r.ix);
+                }
+            }
+        }
+    }
+
+    /// The wires read, each once.
+    fn distinct(self) -> impl Iterator<Item = usize> + 'a {
+        self.iter()
+            .enumerate()
+            .filter(move |&(k, ix)| !self.iter().take(k).any(|jx| jx == ix))
+            .map(|(_, ix)| ix)
+    }
+}
+
+impl ArithmeticGate {
+    /// The wires read by this gate and the wire it writes, where `i` is the index
+    /// of the gate.
+    fn wires(&self, i: usize) -> (GateInputs<'_>, usize) {
+        let (inputs, z) = match *self {
+            Self::GarblerInput { .. }
+            | Self::EvaluatorInput { .. }
+            | Self::PublicInput { .. }
+            | Self::Constant { .. } => ([None, None], i),
+            Self::Add { xref, yref, out }
+            | Self::Sub { xref, yref, out }
+            | Self::Mul {
+                xref, yref, out, ..
+            } => ([Some(xref.ix), Some(yref.ix)], out.unwrap_or(i)),
+            Self::Cmul { xref, out, .. } | Self::Proj { xref, out, .. } => {
+                ([Some(xref.ix), None], out.unwrap_or(i))
+            }
+        };
+        (GateInputs::Fixed(inputs), z)
+    }
+}
+
+impl BinaryGate {
+    /// The wires read by this gate and the wire it writes, where `i` is the index
+    /// of the gate.
+    fn wires(&self, i: usize) -> (GateInputs<'_>, usize) {
+        let (inputs, z) = match *self {
+            Self::GarblerInput { .. }
+            | Self::EvaluatorInput { .. }
+            | Self::PublicInput { .. }
+            | Self::Constant { .. } => ([None, None], i),
+            Self::Xor { xref, yref, out }
+            | Self::And {
+                xref, yref, out, ..
+            } => ([Some(xref.ix), Some(yref.ix)], out.unwrap_or(i)),
+            Self::Inv { xref, out } => ([Some(xref.ix), None], out.unwrap_or(i)),
+            Self::Custom {
+                ref inputs, out, ..
+            } => return (GateInputs::Refs(inputs), out.unwrap_or(i)),
+        };
+        (GateInputs::Fixed(inputs), z)
+    }
 }
 
 impl std::fmt::Display for ArithmeticGate {
@@ -205,6 +372,7 @@ impl std::fmt::Display for ArithmeticGate {
         match self {
             Self::GarblerInput { id } => write!(f, "GarblerInput {}", id),
             Self::EvaluatorInput { id } => write!(f, "EvaluatorInput {}", id),
+            Self::PublicInput { id } => write!(f, "PublicInput {}", id),
             Self::Constant { val } => write!(f, "Constant {}", val),
             Self::Add { xref, yref, out } => write!(f, "Add ( {}, {}, {:?} )", xref, yref, out),
             Self::Sub { xref, yref, out } => write!(f, "Sub ( {}, {}, {:?} )", xref, yref, out),
@@ -227,6 +395,7 @@ impl std::fmt::Display for BinaryGate {
         match self {
             Self::GarblerInput { id } => write!(f, "GarblerInput {}", id),
             Self::EvaluatorInput { id } => write!(f, "EvaluatorInput {}", id),
+            Self::PublicInput { id } => write!(f, "PublicInput {}", id),
             Self::Constant { val } => write!(f, "Constant {}", val),
             Self::Xor { xref, yref, out } => write!(f, "Xor ( {}, {}, {:?} )", xref, yref, out),
             Self::And {
@@ -236,6 +405,19 @@ impl std::fmt::Display for BinaryGate {
                 out,
             } => write!(f, "And ( {}, {}, {}, {:?} )", xref, yref, id, out),
             Self::Inv { xref, out } => write!(f, "Inv ( {}, {:?} )", xref, out),
+            Self::Custom {
+                gate,
+                inputs,
+                id,
+                out,
+            } => write!(
+                f,
+                "Custom ( {}, [{}], {}, {:?} )",
+                gate,
+                inputs.iter().join(", "),
+                id,
+                out
+            ),
         }
     }
 }
@@ -245,12 +427,15 @@ impl std::fmt::Display for BinaryGate {
 /// Blanket implementation available for all circuits
 /// that can be evaluated with an `Informer`
 pub trait CircuitInfo {
+    /// Collect the statistics of an `Informer` run on the circuit.
+    fn info(&self) -> Result<InformerStats, DummyError>;
+
     /// Print circuit info
     fn print_info(&self) -> Result<(), DummyError>;
 }
 
 impl<C: EvaluableCircuit<Informer<Dummy>>> CircuitInfo for C {
-    fn print_info(&self) -> Result<(), DummyError> {
+    fn info(&self) -> Result<InformerStats, DummyError> {
         let mut informer = crate::informer::Informer::new(Dummy::new());
 
         // encode inputs as InformerVals
@@ -265,23 +450,312 @@ impl<C: EvaluableCircuit<Informer<Dummy>>> CircuitInfo for C {
             .map(|r| informer.encode(0, r.modulus()))
             .collect::<Result<Vec<DummyVal>, DummyError>>()?;
 
-        let _outputs = self.eval(&mut informer, &gb, &ev)?;
-        println!("{}", informer.stats());
+        // Public inputs cost what constants do.
+        let public = self
+            .get_public_input_refs()
+            .iter()
+            .map(|r| informer.constant(0, r.modulus()))
+            .collect::<Result<Vec<DummyVal>, DummyError>>()?;
+
+        let _outputs = self.eval_with_public(&mut informer, &gb, &ev, &public)?;
+        let mut stats = informer.stats();
+        // `Dummy` cannot receive inputs, so the evaluator's were encoded too.
+        stats.evaluator_input_moduli = stats.garbler_input_moduli.split_off(gb.len());
+        Ok(stats)
+    }
+
+    fn print_info(&self) -> Result<(), DummyError> {
+        print!("{}", self.stats());
+        let liveness = self.liveness();
+        println!(
+            "circuit width: {} of {} wires live at once",
+            liveness.width(),
+            liveness.ngates()
+        );
         Ok(())
     }
 }
 
+/// The number of gates between the `debug` progress events emitted while
+/// evaluating a circuit.
+#[cfg(feature = "tracing")]
+pub const PROGRESS_INTERVAL: usize = 4096;
+
+/// A function called back while a circuit is evaluated, with the `Fancy`
+/// object and the number of gates evaluated so far; see
+/// [`EvaluableCircuit::eval_with_checkpoints`].
+pub type Checkpoint<'a, F> = dyn FnMut(&mut F, usize) -> Result<(), <F as Fancy>::Error> + 'a;
+
+/// A function called back with the index and value of each output of a
+/// circuit as soon as it is known; see [`EvaluableCircuit::eval_streaming`].
+pub type OnOutput<'a> = dyn FnMut(usize, Modulus) + 'a;
+
+/// A function called after each gate of a circuit is evaluated, with the
+/// `Fancy` object, the index of the gate, and the wire values so far.
+type AfterGate<'a, F, S> = dyn FnMut(&mut F, usize, &S) -> Result<(), <F as Fancy>::Error> + 'a;
+
 /// A Circuit that can be evaluated by a given Fancy object
 ///
 /// Supertrait ensures that circuit can be built by `CircuitBuilder`
 pub trait EvaluableCircuit<F: Fancy>: CircuitType {
     /// Function to evaluate the circuit
+    ///
+    /// The outputs of constant gates, listed by
+    /// [`CircuitType::constant_outputs`], are public, and are returned without
+    /// calling `f.output`.
+    ///
+    /// Fails with [`FancyError::ConstantOutOfRange`], naming the gate, on a
+    /// constant that is not less than its modulus, rather than reducing it,
+    /// and, before evaluating any gate, with
+    /// [`FancyError::NotEnoughGarblerInputs`] or
+    /// [`FancyError::NotEnoughEvaluatorInputs`] if there are fewer input wires
+    /// than the circuit has inputs, with [`FancyError::TooManyGarblerInputs`]
+    /// or [`FancyError::TooManyEvaluatorInputs`] if there are more, and with
+    /// [`FancyError::GarblerInputModulus`] or
+    /// [`FancyError::EvaluatorInputModulus`] on a wire whose modulus is not
+    /// that of its input. A garbler in two-party computation thus fails
+    /// before it sends its peer any gate. A circuit with public inputs fails
+    /// with [`FancyError::NotEnoughPublicInputs`]; see
+    /// [`EvaluableCircuit::eval_with_public`].
     fn eval(
         &self,
         f: &mut F,
         garbler_inputs: &[F::Item],
         evaluator_inputs: &[F::Item],
-    ) -> Result<Option<Vec<u16>>, F::Error>;
+    ) -> Result<Option<Vec<Modulus>>, F::Error>;
+
+    /// Evaluate the circuit, reusing the storage of wires once they are no longer
+    /// needed.
+    ///
+    /// Rather than holding a value for every wire of the circuit, this holds at
+    /// most `liveness.width()` values at a time. `liveness` must have been
+    /// computed for this circuit using [`CircuitType::liveness`].
+    fn eval_bounded(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        liveness: &Liveness,
+    ) -> Result<Option<Vec<Modulus>>, F::Error>;
+
+    /// Evaluate the circuit as [`EvaluableCircuit::eval`] does, keeping wire
+    /// values in `buffer` rather than in storage allocated for this call.
+    ///
+    /// Evaluating many times with the same buffer allocates its storage once,
+    /// the first time, rather than on every evaluation.
+    fn eval_with_buffer(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        buffer: &mut EvalBuffer<F::Item>,
+    ) -> Result<Option<Vec<Modulus>>, F::Error>;
+
+    /// Evaluate the gates of the circuit, but not its outputs, returning the
+    /// value computed by each gate, in order.
+    ///
+    /// This holds every value the circuit computes, and is meant for
+    /// debugging, as in `classic::debug_eval`.
+    fn eval_gates(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+    ) -> Result<Vec<F::Item>, F::Error>;
+
+    /// Evaluate the circuit, returning the wires of its outputs rather than
+    /// outputting them.
+    ///
+    /// The wires can be inputs to a later circuit evaluated by `f`, so that,
+    /// in two-party computation, a value carries over from one circuit to the
+    /// next without being decoded and encoded again.
+    fn eval_output_wires(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+    ) -> Result<Vec<F::Item>, F::Error>;
+
+    /// Evaluate the circuit as [`EvaluableCircuit::eval`] does, calling
+    /// `checkpoint` after every `interval` gates.
+    ///
+    /// An error returned by `checkpoint` stops the evaluation and is returned
+    /// as is. [`Hooks`](crate::hooks::Hooks) use this to report progress and
+    /// to cancel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    fn eval_with_checkpoints(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        public_inputs: &[F::Item],
+        interval: usize,
+        checkpoint: &mut Checkpoint<'_, F>,
+    ) -> Result<Option<Vec<Modulus>>, F::Error>;
+
+    /// Evaluate a circuit with public inputs, known to both parties, as
+    /// [`EvaluableCircuit::eval`] does.
+    ///
+    /// The wires of the public inputs are typically constants, as made by
+    /// the `encode_public` methods of the `twopac` parties. Their number and
+    /// moduli are checked as those of the other inputs are, failing with
+    /// [`FancyError::NotEnoughPublicInputs`],
+    /// [`FancyError::TooManyPublicInputs`], or
+    /// [`FancyError::PublicInputModulus`].
+    fn eval_with_public(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        public_inputs: &[F::Item],
+    ) -> Result<Option<Vec<Modulus>>, F::Error>;
+
+    /// Evaluate the circuit as [`EvaluableCircuit::eval_with_public`] does,
+    /// outputting each output as soon as the gate computing it has been
+    /// evaluated rather than once all gates have been.
+    ///
+    /// `on_output` is called with the index and value of each output that
+    /// `f` decodes, and of each constant output, as it is output; the
+    /// outputs are still returned together, in order, at the end. The
+    /// outputs are output in the order of the gates computing them, which
+    /// changes the messages a garbler sends, so both parties of a two-party
+    /// computation must evaluate the circuit this way. `checkpoints`, if
+    /// any, are an interval and a checkpoint as those of
+    /// [`EvaluableCircuit::eval_with_checkpoints`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the checkpoint interval is zero.
+    fn eval_streaming(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        public_inputs: &[F::Item],
+        checkpoints: Option<(usize, &mut Checkpoint<'_, F>)>,
+        on_output: &mut OnOutput<'_>,
+    ) -> Result<Option<Vec<Modulus>>, F::Error>;
+}
+
+/// The values of the output wires of `circuit`, held in `cache`.
+fn output_wires<C: CircuitType, T: Clone, S: WireStore<T>>(
+    circuit: &C,
+    cache: &S,
+) -> Result<Vec<T>, FancyError> {
+    circuit
+        .get_output_refs()
+        .iter()
+        .map(|r| cache.value(r.ix).cloned())
+        .collect::<Option<Vec<_>>>()
+        .ok_or(FancyError::UninitializedValue)
+}
+
+/// Output the values of the output wires of `circuit`, held in `cache`.
+fn outputs<C: CircuitType, F: Fancy, S: WireStore<F::Item>>(
+    circuit: &C,
+    f: &mut F,
+    cache: &S,
+) -> Result<Option<Vec<Modulus>>, F::Error> {
+    #[cfg(feature = "tracing")]
+    let _span = tracing::info_span!("outputs", noutputs = circuit.noutputs()).entered();
+    let mut outputs = Vec::with_capacity(circuit.noutputs());
+    let constants = circuit.constant_outputs();
+    for (r, c) in circuit.get_output_refs().iter().zip(constants) {
+        if c.is_some() {
+            outputs.push(c);
+            continue;
+        }
+        let r = cache
+            .value(r.ix)
+            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?;
+        let out = f.output(r)?;
+        outputs.push(out);
+    }
+    Ok(outputs.into_iter().collect())
+}
+
+/// The outputs of a circuit, output as soon as the gates computing them have
+/// been evaluated; see [`EvaluableCircuit::eval_streaming`].
+struct OutputStream<'a, 'b> {
+    /// Each output, by index, with the index of the last gate writing its
+    /// wire, in the order of those gates.
+    schedule: Vec<(usize, usize)>,
+    next: usize,
+    wires: Vec<usize>,
+    constants: Vec<Option<Modulus>>,
+    values: Vec<Option<Modulus>>,
+    on_output: &'a mut OnOutput<'b>,
+}
+
+impl<'a, 'b> OutputStream<'a, 'b> {
+    fn new<C: CircuitType>(circuit: &C, on_output: &'a mut OnOutput<'b>) -> Self {
+        let gates = circuit.get_gates();
+        let mut writers = vec![usize::MAX; gates.len()];
+        for (i, gate) in gates.iter().enumerate() {
+            if let Some(writer) = writers.get_mut(gate.output_wire(i)) {
+                *writer = i;
+            }
+        }
+        let wires = circuit.get_output_refs().iter().map(|r| r.ix).collect_vec();
+        // An output no gate writes is left to the end, where it fails as it
+        // does when the outputs are output together.
+        let mut schedule = wires
+            .iter()
+            .enumerate()
+            .map(|(j, &ix)| (writers.get(ix).copied().unwrap_or(usize::MAX), j))
+            .collect_vec();
+        schedule.sort_unstable();
+        OutputStream {
+            schedule,
+            next: 0,
+            constants: circuit.constant_outputs(),
+            values: vec![None; wires.len()],
+            wires,
+            on_output,
+        }
+    }
+
+    /// Output the outputs computed by gate `i` and those before it.
+    fn after_gate<F: Fancy, S: WireStore<F::Item>>(
+        &mut self,
+        f: &mut F,
+        i: usize,
+        cache: &S,
+    ) -> Result<(), F::Error> {
+        while let Some(&(gate, j)) = self.schedule.get(self.next) {
+            if gate > i {
+                break;
+            }
+            self.next += 1;
+            let value = match self.constants[j] {
+                Some(c) => Some(c),
+                None => {
+                    let wire = cache
+                        .value(self.wires[j])
+                        .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?;
+                    f.output(wire)?
+                }
+            };
+            if let Some(value) = value {
+                (self.on_output)(j, value);
+            }
+            self.values[j] = value;
+        }
+        Ok(())
+    }
+
+    /// Output the outputs left, and return them all in order.
+    fn finish<F: Fancy, S: WireStore<F::Item>>(
+        mut self,
+        f: &mut F,
+        cache: &S,
+    ) -> Result<Option<Vec<Modulus>>, F::Error> {
+        self.after_gate(f, usize::MAX, cache)?;
+        Ok(self.values.into_iter().collect())
+    }
 }
 
 impl<F: FancyArithmetic> EvaluableCircuit<F> for ArithmeticCircuit {
@@ -290,49 +764,237 @@ impl<F: FancyArithmetic> EvaluableCircuit<F> for ArithmeticCircuit {
         f: &mut F,
         garbler_inputs: &[F::Item],
         evaluator_inputs: &[F::Item],
-    ) -> Result<Option<Vec<u16>>, F::Error> {
+    ) -> Result<Option<Vec<Modulus>>, F::Error> {
+        let cache: Vec<Option<F::Item>> = vec![None; self.gates.len()];
+        self.eval_with(f, garbler_inputs, evaluator_inputs, &[], cache, None)
+    }
+
+    fn eval_bounded(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        liveness: &Liveness,
+    ) -> Result<Option<Vec<Modulus>>, F::Error> {
+        let cache = LiveStore::new(self.gates.len(), liveness)?;
+        self.eval_with(f, garbler_inputs, evaluator_inputs, &[], cache, None)
+    }
+
+    fn eval_with_buffer(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        buffer: &mut EvalBuffer<F::Item>,
+    ) -> Result<Option<Vec<Modulus>>, F::Error> {
+        let cache = buffer.reset(self.gates.len());
+        self.eval_with(f, garbler_inputs, evaluator_inputs, &[], cache, None)
+    }
+
+    fn eval_gates(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+    ) -> Result<Vec<F::Item>, F::Error> {
+        let mut cache = TraceStore::new(self.gates.len());
+        self.eval_gates_with(f, garbler_inputs, evaluator_inputs, &[], &mut cache, None)?;
+        Ok(cache.gates)
+    }
+
+    fn eval_output_wires(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+    ) -> Result<Vec<F::Item>, F::Error> {
+        let mut cache: Vec<Option<F::Item>> = vec![None; self.gates.len()];
+        self.eval_gates_with(f, garbler_inputs, evaluator_inputs, &[], &mut cache, None)?;
+        Ok(output_wires(self, &cache)?)
+    }
+
+    fn eval_with_checkpoints(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        public_inputs: &[F::Item],
+        interval: usize,
+        checkpoint: &mut Checkpoint<'_, F>,
+    ) -> Result<Option<Vec<Modulus>>, F::Error> {
+        assert!(interval > 0, "the checkpoint interval must be positive");
+        let cache: Vec<Option<F::Item>> = vec![None; self.gates.len()];
+        let checkpoints = Some((interval, checkpoint));
+        self.eval_with(
+            f,
+            garbler_inputs,
+            evaluator_inputs,
+            public_inputs,
+            cache,
+            checkpoints,
+        )
+    }
+
+    fn eval_with_public(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        public_inputs: &[F::Item],
+    ) -> Result<Option<Vec<Modulus>>, F::Error> {
+        let cache: Vec<Option<F::Item>> = vec![None; self.gates.len()];
+        self.eval_with(
+            f,
+            garbler_inputs,
+            evaluator_inputs,
+            public_inputs,
+            cache,
+            None,
+        )
+    }
+
+    fn eval_streaming(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        public_inputs: &[F::Item],
+        mut checkpoints: Option<(usize, &mut Checkpoint<'_, F>)>,
+        on_output: &mut OnOutput<'_>,
+    ) -> Result<Option<Vec<Modulus>>, F::Error> {
+        if let Some((interval, _)) = checkpoints {
+            assert!(interval > 0, "the checkpoint interval must be positive");
+        }
         let mut cache: Vec<Option<F::Item>> = vec![None; self.gates.len()];
+        let mut stream = OutputStream::new(self, on_output);
+        self.eval_gates_with(
+            f,
+            garbler_inputs,
+            evaluator_inputs,
+            public_inputs,
+            &mut cache,
+            Some(&mut |f, i, cache| {
+                stream.after_gate(f, i, cache)?;
+                match checkpoints.as_mut() {
+                    Some((interval, checkpoint)) if i % *interval == *interval - 1 => {
+                        checkpoint(f, i + 1)
+                    }
+                    _ => Ok(()),
+                }
+            }),
+        )?;
+        stream.finish(f, &cache)
+    }
+}
+
+impl ArithmeticCircuit {
+    /// Compute the digest of the circuit, which [`CircuitType::digest`]
+    /// caches.
+    fn compute_digest(&self) -> CircuitDigest {
+        let mut w = DigestWriter::new(b"arithmetic");
+        w.int(self.gates.len() as u64);
+        for (gate, &q) in self.gates.iter().zip(self.gate_moduli.iter()) {
+            gate.digest(&mut w);
+            w.modulus(q);
+        }
+        w.refs(&self.garbler_input_refs);
+        w.refs(&self.evaluator_input_refs);
+        w.refs(&self.const_refs);
+        w.refs(&self.output_refs);
+        w.public_refs(&self.public_input_refs);
+        w.finish()
+    }
+
+    /// Evaluate the circuit, keeping wire values in `cache`.
+    fn eval_with<F: FancyArithmetic, S: WireStore<F::Item>>(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        public_inputs: &[F::Item],
+        mut cache: S,
+        checkpoints: Option<(usize, &mut Checkpoint<'_, F>)>,
+    ) -> Result<Option<Vec<Modulus>>, F::Error> {
+        match checkpoints {
+            None => self.eval_gates_with(
+                f,
+                garbler_inputs,
+                evaluator_inputs,
+                public_inputs,
+                &mut cache,
+                None,
+            )?,
+            Some((interval, checkpoint)) => self.eval_gates_with(
+                f,
+                garbler_inputs,
+                evaluator_inputs,
+                public_inputs,
+                &mut cache,
+                Some(&mut |f, i, _| {
+                    if i % interval == interval - 1 {
+                        checkpoint(f, i + 1)
+                    } else {
+                        Ok(())
+                    }
+                }),
+            )?,
+        }
+        outputs(self, f, &cache)
+    }
+
+    /// Evaluate the gates of the circuit, keeping wire values in `cache`, and
+    /// calling `after_gate`, if any, after each gate.
+    fn eval_gates_with<F: FancyArithmetic, S: WireStore<F::Item>>(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        public_inputs: &[F::Item],
+        cache: &mut S,
+        mut after_gate: Option<&mut AfterGate<'_, F, S>>,
+    ) -> Result<(), F::Error> {
+        check_input_wires(self, garbler_inputs, evaluator_inputs, public_inputs)?;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("gates", ngates = self.gates.len()).entered();
         for (i, gate) in self.gates.iter().enumerate() {
             let q = self.modulus(i);
             let (zref_, val) = match *gate {
-                ArithmeticGate::GarblerInput { id } => (None, garbler_inputs[id].clone()),
+                ArithmeticGate::GarblerInput { id } => (None, garbler_input(garbler_inputs, id)?),
                 ArithmeticGate::EvaluatorInput { id } => {
-                    assert!(
-                        id < evaluator_inputs.len(),
-                        "id={} ev_inps.len()={}",
-                        id,
-                        evaluator_inputs.len()
-                    );
-                    (None, evaluator_inputs[id].clone())
+                    (None, evaluator_input(evaluator_inputs, id)?)
+                }
+                ArithmeticGate::PublicInput { id } => (None, public_input(public_inputs, id)?),
+                ArithmeticGate::Constant { val } => {
+                    check_constant(i, val, q)?;
+                    (None, f.constant(val, q)?)
                 }
-                ArithmeticGate::Constant { val } => (None, f.constant(val, q)?),
                 ArithmeticGate::Add { xref, yref, out } => (
                     out,
                     f.add(
-                        cache[xref.ix]
-                            .as_ref()
+                        cache
+                            .value(xref.ix)
                             .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
-                        cache[yref.ix]
-                            .as_ref()
+                        cache
+                            .value(yref.ix)
                             .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
                     )?,
                 ),
                 ArithmeticGate::Sub { xref, yref, out } => (
                     out,
                     f.sub(
-                        cache[xref.ix]
-                            .as_ref()
+                        cache
+                            .value(xref.ix)
                             .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
-                        cache[yref.ix]
-                            .as_ref()
+                        cache
+                            .value(yref.ix)
                             .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
                     )?,
                 ),
                 ArithmeticGate::Cmul { xref, c, out } => (
                     out,
                     f.cmul(
-                        cache[xref.ix]
-                            .as_ref()
+                        cache
+                            .value(xref.ix)
                             .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
                         c,
                     )?,
@@ -342,8 +1004,8 @@ impl<F: FancyArithmetic> EvaluableCircuit<F> for ArithmeticCircuit {
                 } => (
                     out,
                     f.proj(
-                        cache[xref.ix]
-                            .as_ref()
+                        cache
+                            .value(xref.ix)
                             .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
                         q,
                         Some(tt.to_vec()),
@@ -354,26 +1016,27 @@ impl<F: FancyArithmetic> EvaluableCircuit<F> for ArithmeticCircuit {
                 } => (
                     out,
                     f.mul(
-                        cache[xref.ix]
-                            .as_ref()
+                        cache
+                            .value(xref.ix)
                             .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
-                        cache[yref.ix]
-                            .as_ref()
+                        cache
+                            .value(yref.ix)
                             .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
                     )?,
                 ),
             };
-            cache[zref_.unwrap_or(i)] = Some(val);
-        }
-        let mut outputs = Vec::with_capacity(self.noutputs());
-        for r in self.get_output_refs().iter() {
-            let r = cache[r.ix]
-                .as_ref()
-                .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?;
-            let out = f.output(r)?;
-            outputs.push(out);
+            let (inputs, _) = gate.wires(i);
+            inputs.for_each(|ix| cache.release(i, ix));
+            cache.store(i, zref_.unwrap_or(i), val);
+            if let Some(after_gate) = after_gate.as_mut() {
+                after_gate(f, i, cache)?;
+            }
+            #[cfg(feature = "tracing")]
+            if (i + 1) % PROGRESS_INTERVAL == 0 {
+                tracing::debug!(gates = i + 1, "progress");
+            }
         }
-        Ok(outputs.into_iter().collect())
+        Ok(())
     }
 }
 
@@ -383,97 +1046,335 @@ impl<F: FancyBinary> EvaluableCircuit<F> for BinaryCircuit {
         f: &mut F,
         garbler_inputs: &[F::Item],
         evaluator_inputs: &[F::Item],
-    ) -> Result<Option<Vec<u16>>, F::Error> {
-        let mut cache: Vec<Option<F::Item>> = vec![None; self.gates.len()];
-        for (i, gate) in self.gates.iter().enumerate() {
-            let q = 2;
-            let (zref_, val) = match *gate {
-                BinaryGate::GarblerInput { id } => (None, garbler_inputs[id].clone()),
-                BinaryGate::EvaluatorInput { id } => {
-                    assert!(
-                        id < evaluator_inputs.len(),
-                        "id={} ev_inps.len()={}",
-                        id,
-                        evaluator_inputs.len()
-                    );
-                    (None, evaluator_inputs[id].clone())
-                }
-                BinaryGate::Constant { val } => (None, f.constant(val, q)?),
-                BinaryGate::Inv { xref, out } => (
-                    out,
-                    f.negate(
-                        cache[xref.ix]
-                            .as_ref()
-                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
-                    )?,
-                ),
-                BinaryGate::Xor { xref, yref, out } => (
-                    out,
-                    f.xor(
-                        cache[xref.ix]
-                            .as_ref()
-                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
-                        cache[yref.ix]
-                            .as_ref()
-                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
-                    )?,
-                ),
-                BinaryGate::And {
-                    xref, yref, out, ..
-                } => (
-                    out,
-                    f.and(
-                        cache[xref.ix]
-                            .as_ref()
-                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
-                        cache[yref.ix]
-                            .as_ref()
-                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
-                    )?,
-                ),
-            };
-            cache[zref_.unwrap_or(i)] = Some(val);
-        }
-        let mut outputs = Vec::with_capacity(self.noutputs());
-        for r in self.get_output_refs().iter() {
-            let r = cache[r.ix]
-                .as_ref()
-                .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?;
-            let out = f.output(r)?;
-            outputs.push(out);
-        }
-        Ok(outputs.into_iter().collect())
+    ) -> Result<Option<Vec<Modulus>>, F::Error> {
+        let cache: Vec<Option<F::Item>> = vec![None; self.gates.len()];
+        self.eval_with(f, garbler_inputs, evaluator_inputs, &[], cache, None)
     }
-}
-
-/// Trait representing circuit gates that can be used in `CircuitType`
-pub trait GateType: Display {
-    /// Generate constant gate
-    fn make_constant(val: u16) -> Self;
-
-    /// Generate garbler input gate
-    fn make_garbler_input(id: usize) -> Self;
 
-    /// Generate evaluator input gate
-    fn make_evaluator_input(id: usize) -> Self;
-}
-
-impl GateType for BinaryGate {
-    fn make_constant(val: u16) -> Self {
-        Self::Constant { val }
+    fn eval_bounded(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        liveness: &Liveness,
+    ) -> Result<Option<Vec<Modulus>>, F::Error> {
+        let cache = LiveStore::new(self.gates.len(), liveness)?;
+        self.eval_with(f, garbler_inputs, evaluator_inputs, &[], cache, None)
     }
 
-    fn make_garbler_input(id: usize) -> Self {
-        Self::GarblerInput { id }
+    fn eval_with_buffer(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        buffer: &mut EvalBuffer<F::Item>,
+    ) -> Result<Option<Vec<Modulus>>, F::Error> {
+        let cache = buffer.reset(self.gates.len());
+        self.eval_with(f, garbler_inputs, evaluator_inputs, &[], cache, None)
     }
 
-    fn make_evaluator_input(id: usize) -> Self {
-        Self::EvaluatorInput { id }
+    fn eval_gates(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+    ) -> Result<Vec<F::Item>, F::Error> {
+        let mut cache = TraceStore::new(self.gates.len());
+        self.eval_gates_with(f, garbler_inputs, evaluator_inputs, &[], &mut cache, None)?;
+        Ok(cache.gates)
     }
-}
 
-impl GateType for ArithmeticGate {
-    fn make_constant(val: u16) -> Self {
+    fn eval_output_wires(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+    ) -> Result<Vec<F::Item>, F::Error> {
+        let mut cache: Vec<Option<F::Item>> = vec![None; self.gates.len()];
+        self.eval_gates_with(f, garbler_inputs, evaluator_inputs, &[], &mut cache, None)?;
+        Ok(output_wires(self, &cache)?)
+    }
+
+    fn eval_with_checkpoints(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        public_inputs: &[F::Item],
+        interval: usize,
+        checkpoint: &mut Checkpoint<'_, F>,
+    ) -> Result<Option<Vec<Modulus>>, F::Error> {
+        assert!(interval > 0, "the checkpoint interval must be positive");
+        let cache: Vec<Option<F::Item>> = vec![None; self.gates.len()];
+        let checkpoints = Some((interval, checkpoint));
+        self.eval_with(
+            f,
+            garbler_inputs,
+            evaluator_inputs,
+            public_inputs,
+            cache,
+            checkpoints,
+        )
+    }
+
+    fn eval_with_public(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        public_inputs: &[F::Item],
+    ) -> Result<Option<Vec<Modulus>>, F::Error> {
+        let cache: Vec<Option<F::Item>> = vec![None; self.gates.len()];
+        self.eval_with(
+            f,
+            garbler_inputs,
+            evaluator_inputs,
+            public_inputs,
+            cache,
+            None,
+        )
+    }
+
+    fn eval_streaming(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        public_inputs: &[F::Item],
+        mut checkpoints: Option<(usize, &mut Checkpoint<'_, F>)>,
+        on_output: &mut OnOutput<'_>,
+    ) -> Result<Option<Vec<Modulus>>, F::Error> {
+        if let Some((interval, _)) = checkpoints {
+            assert!(interval > 0, "the checkpoint interval must be positive");
+        }
+        let mut cache: Vec<Option<F::Item>> = vec![None; self.gates.len()];
+        let mut stream = OutputStream::new(self, on_output);
+        self.eval_gates_with(
+            f,
+            garbler_inputs,
+            evaluator_inputs,
+            public_inputs,
+            &mut cache,
+            Some(&mut |f, i, cache| {
+                stream.after_gate(f, i, cache)?;
+                match checkpoints.as_mut() {
+                    Some((interval, checkpoint)) if i % *interval == *interval - 1 => {
+                        checkpoint(f, i + 1)
+                    }
+                    _ => Ok(()),
+                }
+            }),
+        )?;
+        stream.finish(f, &cache)
+    }
+}
+
+impl BinaryCircuit {
+    /// Compute the digest of the circuit, which [`CircuitType::digest`]
+    /// caches.
+    fn compute_digest(&self) -> CircuitDigest {
+        let mut w = DigestWriter::new(b"binary");
+        w.int(self.gates.len() as u64);
+        for gate in self.gates.iter() {
+            gate.digest(&mut w);
+        }
+        w.refs(&self.garbler_input_refs);
+        w.refs(&self.evaluator_input_refs);
+        w.refs(&self.const_refs);
+        w.refs(&self.output_refs);
+        w.public_refs(&self.public_input_refs);
+        // the names of the custom gates, if there are any, as for the public
+        // inputs
+        for gate in self.custom_gates.as_slice() {
+            w.bytes(gate.name().as_bytes());
+        }
+        w.finish()
+    }
+
+    /// Evaluate the circuit, keeping wire values in `cache`.
+    fn eval_with<F: FancyBinary, S: WireStore<F::Item>>(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        public_inputs: &[F::Item],
+        mut cache: S,
+        checkpoints: Option<(usize, &mut Checkpoint<'_, F>)>,
+    ) -> Result<Option<Vec<Modulus>>, F::Error> {
+        match checkpoints {
+            None => self.eval_gates_with(
+                f,
+                garbler_inputs,
+                evaluator_inputs,
+                public_inputs,
+                &mut cache,
+                None,
+            )?,
+            Some((interval, checkpoint)) => self.eval_gates_with(
+                f,
+                garbler_inputs,
+                evaluator_inputs,
+                public_inputs,
+                &mut cache,
+                Some(&mut |f, i, _| {
+                    if i % interval == interval - 1 {
+                        checkpoint(f, i + 1)
+                    } else {
+                        Ok(())
+                    }
+                }),
+            )?,
+        }
+        outputs(self, f, &cache)
+    }
+
+    /// Evaluate the gates of the circuit, keeping wire values in `cache`, and
+    /// calling `after_gate`, if any, after each gate.
+    fn eval_gates_with<F: FancyBinary, S: WireStore<F::Item>>(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+        public_inputs: &[F::Item],
+        cache: &mut S,
+        mut after_gate: Option<&mut AfterGate<'_, F, S>>,
+    ) -> Result<(), F::Error> {
+        check_input_wires(self, garbler_inputs, evaluator_inputs, public_inputs)?;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("gates", ngates = self.gates.len()).entered();
+        for (i, gate) in self.gates.iter().enumerate() {
+            let q = 2;
+            let (zref_, val) = match *gate {
+                BinaryGate::GarblerInput { id } => (None, garbler_input(garbler_inputs, id)?),
+                BinaryGate::EvaluatorInput { id } => (None, evaluator_input(evaluator_inputs, id)?),
+                BinaryGate::PublicInput { id } => (None, public_input(public_inputs, id)?),
+                BinaryGate::Constant { val } => {
+                    check_constant(i, val, q)?;
+                    (None, f.constant(val, q)?)
+                }
+                BinaryGate::Inv { xref, out } => (
+                    out,
+                    f.negate(
+                        cache
+                            .value(xref.ix)
+                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
+                    )?,
+                ),
+                BinaryGate::Xor { xref, yref, out } => (
+                    out,
+                    f.xor(
+                        cache
+                            .value(xref.ix)
+                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
+                        cache
+                            .value(yref.ix)
+                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
+                    )?,
+                ),
+                BinaryGate::And {
+                    xref, yref, out, ..
+                } => (
+                    out,
+                    f.and(
+                        cache
+                            .value(xref.ix)
+                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
+                        cache
+                            .value(yref.ix)
+                            .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))?,
+                    )?,
+                ),
+                BinaryGate::Custom {
+                    gate,
+                    ref inputs,
+                    out,
+                    ..
+                } => {
+                    let gate = self.custom_gates.get(gate).ok_or_else(|| {
+                        F::Error::from(FancyError::InvalidArg(format!(
+                            "no custom gate {} is registered",
+                            gate
+                        )))
+                    })?;
+                    let inputs = inputs
+                        .iter()
+                        .map(|r| {
+                            cache
+                                .value(r.ix)
+                                .cloned()
+                                .ok_or_else(|| F::Error::from(FancyError::UninitializedValue))
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    (out, f.custom(gate, &inputs)?)
+                }
+            };
+            let (inputs, _) = gate.wires(i);
+            inputs.for_each(|ix| cache.release(i, ix));
+            cache.store(i, zref_.unwrap_or(i), val);
+            if let Some(after_gate) = after_gate.as_mut() {
+                after_gate(f, i, cache)?;
+            }
+            #[cfg(feature = "tracing")]
+            if (i + 1) % PROGRESS_INTERVAL == 0 {
+                tracing::debug!(gates = i + 1, "progress");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Trait representing circuit gates that can be used in `CircuitType`
+pub trait GateType: Display {
+    /// Generate constant gate
+    fn make_constant(val: Modulus) -> Self;
+
+    /// Generate garbler input gate
+    fn make_garbler_input(id: usize) -> Self;
+
+    /// Generate evaluator input gate
+    fn make_evaluator_input(id: usize) -> Self;
+
+    /// Generate public input gate
+    fn make_public_input(id: usize) -> Self;
+
+    /// The value of this gate, if it is a constant gate.
+    fn constant_value(&self) -> Option<Modulus>;
+
+    /// The wire this gate writes, where `i` is the index of the gate.
+    fn output_wire(&self, i: usize) -> usize;
+}
+
+impl GateType for BinaryGate {
+    fn make_constant(val: Modulus) -> Self {
+        Self::Constant { val }
+    }
+
+    fn make_garbler_input(id: usize) -> Self {
+        Self::GarblerInput { id }
+    }
+
+    fn make_evaluator_input(id: usize) -> Self {
+        Self::EvaluatorInput { id }
+    }
+
+    fn make_public_input(id: usize) -> Self {
+        Self::PublicInput { id }
+    }
+
+    fn constant_value(&self) -> Option<Modulus> {
+        match *self {
+            Self::Constant { val } => Some(val),
+            _ => None,
+        }
+    }
+
+    fn output_wire(&self, i: usize) -> usize {
+        self.wires(i).1
+    }
+}
+
+impl GateType for ArithmeticGate {
+    fn make_constant(val: Modulus) -> Self {
         Self::Constant { val }
     }
 
@@ -484,6 +1385,21 @@ impl GateType for ArithmeticGate {
     fn make_evaluator_input(id: usize) -> Self {
         Self::EvaluatorInput { id }
     }
+
+    fn make_public_input(id: usize) -> Self {
+        Self::PublicInput { id }
+    }
+
+    fn constant_value(&self) -> Option<Modulus> {
+        match *self {
+            Self::Constant { val } => Some(val),
+            _ => None,
+        }
+    }
+
+    fn output_wire(&self, i: usize) -> usize {
+        self.wires(i).1
+    }
 }
 
 /// Trait representing circuits that can be built by `CircuitBuilder`
@@ -497,6 +1413,12 @@ pub trait CircuitType: Clone {
     /// Make a new `Circuit` object.
     fn new(ngates: Option<usize>) -> Self;
 
+    /// Reserve capacity for at least `ngates` more gates.
+    fn reserve_gates(&mut self, ngates: usize);
+
+    /// Reserve capacity for at least `nwires` more input and output wires.
+    fn reserve_wires(&mut self, nwires: usize);
+
     /// Get all output refs
     fn get_output_refs(&self) -> &[CircuitRef];
 
@@ -506,242 +1428,2131 @@ pub trait CircuitType: Clone {
     /// Get all evaluator input refs
     fn get_evaluator_input_refs(&self) -> &[CircuitRef];
 
+    /// Get all public input refs
+    fn get_public_input_refs(&self) -> &[CircuitRef];
+
+    /// Get all gates
+    fn get_gates(&self) -> &[Self::Gate];
+
+    /// Return, for each output, its value if it is the output of a constant
+    /// gate whose wire no later gate overwrites, and `None` otherwise.
+    ///
+    /// The values of these outputs are public: evaluating the circuit outputs
+    /// them without decoding, so garbling it sends no output ciphertexts for
+    /// them.
+    fn constant_outputs(&self) -> Vec<Option<Modulus>> {
+        let gates = self.get_gates();
+        let mut outputs = self
+            .get_output_refs()
+            .iter()
+            .map(|r| gates.get(r.ix).and_then(GateType::constant_value))
+            .collect_vec();
+        if outputs.iter().any(Option::is_some) {
+            let candidates = self
+                .get_output_refs()
+                .iter()
+                .zip(&outputs)
+                .filter(|(_, c)| c.is_some())
+                .map(|(r, _)| r.ix)
+                .collect::<HashSet<_>>();
+            let overwritten = gates
+                .iter()
+                .enumerate()
+                .map(|(i, gate)| (i, gate.output_wire(i)))
+                .filter(|&(i, ix)| i > ix && candidates.contains(&ix))
+                .map(|(_, ix)| ix)
+                .collect::<HashSet<_>>();
+            for (r, c) in self.get_output_refs().iter().zip(outputs.iter_mut()) {
+                if overwritten.contains(&r.ix) {
+                    *c = None;
+                }
+            }
+        }
+        outputs
+    }
+
     /// Get number of nonfree gates
     fn get_num_nonfree_gates(&self) -> usize;
 
     /// Add a gate
     fn push_gates(&mut self, gate: Self::Gate);
 
-    /// Add a constant ref
-    fn push_const_ref(&mut self, xref: CircuitRef);
+    /// Add a constant ref
+    fn push_const_ref(&mut self, xref: CircuitRef);
+
+    /// Add an output ref
+    fn push_output_ref(&mut self, xref: CircuitRef);
+
+    /// Get the widths of the output groups made explicitly; see
+    /// [`CircuitType::output_widths`].
+    fn get_output_groups(&self) -> &[usize];
+
+    /// Add an output group of `width` outputs, following the last group.
+    fn push_output_group(&mut self, width: usize);
+
+    /// Add a garbler input ref
+    fn push_garbler_input_ref(&mut self, xref: CircuitRef);
+
+    /// Add an evaluator input ref
+    fn push_evaluator_input_ref(&mut self, xref: CircuitRef);
+
+    /// Add a public input ref
+    fn push_public_input_ref(&mut self, xref: CircuitRef);
+
+    /// Add wire moulus
+    fn push_modulus(&mut self, modulus: Modulus);
+
+    /// Return the modulus of the garbler input indexed by `i`.
+    fn garbler_input_mod(&self, i: usize) -> Modulus;
+
+    /// Return the modulus of the evaluator input indexed by `i`.
+    fn evaluator_input_mod(&self, i: usize) -> Modulus;
+
+    /// Compute when each wire of the circuit is last used, for use with
+    /// [`EvaluableCircuit::eval_bounded`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the circuit has `u32::MAX` gates or more.
+    fn liveness(&self) -> Liveness;
+
+    /// Count the gates, inputs, outputs, and wires of the circuit, and find
+    /// its multiplicative depth.
+    fn stats(&self) -> CircuitStats;
+
+    /// Group the gates of the circuit into topological levels, for use with
+    /// [`BinaryCircuit::eval_plain_parallel`] and
+    /// [`ArithmeticCircuit::eval_plain_parallel`].
+    fn levels(&self) -> Levels;
+
+    /// The number of bytes garbling the circuit with `security` sends, found
+    /// from its gates without garbling it: the garbled gates, the labels of
+    /// the constants, and the hashes decoding the outputs, but not the labels
+    /// of the inputs.
+    ///
+    /// This is exactly what a streaming garbler writes for the circuit, and,
+    /// rounded up to whole blocks, the size of the garbled circuit
+    /// [`garble`](crate::classic::garble) returns, [`GarbledCircuit::nbytes`].
+    /// Binary AND gates cost two labels, XOR and INV gates nothing, and
+    /// projections from modulus `q` take `q - 1` blocks.
+    ///
+    /// [`GarbledCircuit::nbytes`]: crate::classic::GarbledCircuit::nbytes
+    fn garbled_size_estimate(&self, security: SecurityParams) -> usize;
+
+    /// Compute a digest of the circuit, identifying it among circuits that
+    /// differ in any gate, input, constant, or output.
+    fn digest(&self) -> CircuitDigest;
+
+    /// Concatenate the bits of `parts` into the garbler's inputs.
+    ///
+    /// See [`CircuitInput`] for the bit order. Fails if the parts do not fill
+    /// the garbler's inputs exactly, or if those inputs are not all binary.
+    fn assemble_garbler_inputs(
+        &self,
+        parts: &[&dyn CircuitInput],
+    ) -> Result<Vec<Modulus>, FancyError> {
+        input::assemble(parts, self.num_garbler_inputs(), |i| {
+            self.garbler_input_mod(i)
+        })
+    }
+
+    /// Concatenate the bits of `parts` into the evaluator's inputs.
+    ///
+    /// See [`CircuitInput`] for the bit order. Fails if the parts do not fill
+    /// the evaluator's inputs exactly, or if those inputs are not all binary.
+    fn assemble_evaluator_inputs(
+        &self,
+        parts: &[&dyn CircuitInput],
+    ) -> Result<Vec<Modulus>, FancyError> {
+        input::assemble(parts, self.num_evaluator_inputs(), |i| {
+            self.evaluator_input_mod(i)
+        })
+    }
+
+    /// Return the number of garbler inputs.
+    #[inline]
+    fn num_garbler_inputs(&self) -> usize {
+        self.get_garbler_input_refs().len()
+    }
+
+    /// Return the number of evaluator inputs.
+    #[inline]
+    fn num_evaluator_inputs(&self) -> usize {
+        self.get_evaluator_input_refs().len()
+    }
+
+    /// Return the number of public inputs.
+    #[inline]
+    fn num_public_inputs(&self) -> usize {
+        self.get_public_input_refs().len()
+    }
+
+    /// Return the number of outputs.
+    #[inline]
+    fn noutputs(&self) -> usize {
+        self.get_output_refs().len()
+    }
+
+    /// Return the widths of the groups the outputs are divided into, in order.
+    ///
+    /// Outputs are grouped as, e.g., the numbers a circuit computes. Outputs
+    /// following the groups made explicitly, if any, form one last group, so
+    /// a circuit with no explicit groups has a single group of all its
+    /// outputs. Groups do not affect evaluation, nor the digest.
+    fn output_widths(&self) -> Vec<usize> {
+        let mut widths = self.get_output_groups().to_vec();
+        let grouped = widths.iter().sum::<usize>();
+        if grouped < self.noutputs() {
+            widths.push(self.noutputs() - grouped);
+        }
+        widths
+    }
+
+    /// Split the flat outputs of an evaluation of the circuit into its output
+    /// groups.
+    ///
+    /// Groups are cut short if there are fewer outputs than the circuit has.
+    fn group_outputs<T: Clone>(&self, outputs: &[T]) -> Vec<Vec<T>> {
+        let mut rest = outputs;
+        self.output_widths()
+            .into_iter()
+            .map(|width| {
+                let (group, tail) = rest.split_at(width.min(rest.len()));
+                rest = tail;
+                group.to_vec()
+            })
+            .collect()
+    }
+}
+
+/// Evaluate the circuit in plaintext.
+///
+/// An input that is not less than the modulus of its wire is an
+/// [`FancyError::InputOutOfRange`], whose index counts the garbler's inputs and
+/// then the evaluator's. Giving fewer or more inputs of a party than the
+/// circuit has is a [`DummyError::NotEnoughGarblerInputs`] or
+/// [`DummyError::NotEnoughEvaluatorInputs`].
+pub fn eval_plain<C: EvaluableCircuit<Dummy>>(
+    circuit: &C,
+    garbler_inputs: &[Modulus],
+    evaluator_inputs: &[Modulus],
+) -> Result<Vec<Modulus>, DummyError> {
+    let mut dummy = crate::dummy::Dummy::new();
+    let (gb, ev) = encode_plain(circuit, garbler_inputs, evaluator_inputs)?;
+    let outputs = circuit.eval(&mut dummy, &gb, &ev)?;
+    Ok(outputs.expect("dummy will always return Some(Modulus) output"))
+}
+
+/// Evaluate a circuit with public inputs in plaintext.
+///
+/// See [`eval_plain`] and [`EvaluableCircuit::eval_with_public`]. The index of
+/// an [`FancyError::InputOutOfRange`] counts the garbler's inputs, then the
+/// evaluator's, then the public ones.
+pub fn eval_plain_with_public<C: EvaluableCircuit<Dummy>>(
+    circuit: &C,
+    garbler_inputs: &[Modulus],
+    evaluator_inputs: &[Modulus],
+    public_inputs: &[Modulus],
+) -> Result<Vec<Modulus>, DummyError> {
+    let mut dummy = crate::dummy::Dummy::new();
+    let (gb, ev) = encode_plain(circuit, garbler_inputs, evaluator_inputs)?;
+    let refs = circuit.get_public_input_refs();
+    let (got, needed) = (public_inputs.len(), refs.len());
+    if got < needed {
+        return Err(FancyError::NotEnoughPublicInputs { got, needed }.into());
+    }
+    if got > needed {
+        return Err(FancyError::TooManyPublicInputs { got, needed }.into());
+    }
+    check_inputs(public_inputs, refs.iter().map(|r| r.modulus())).map_err(|e| match e {
+        FancyError::InputOutOfRange {
+            index,
+            value,
+            modulus,
+        } => FancyError::InputOutOfRange {
+            index: gb.len() + ev.len() + index,
+            value,
+            modulus,
+        },
+        e => e,
+    })?;
+    let public = public_inputs
+        .iter()
+        .zip(refs.iter())
+        .map(|(x, r)| DummyVal::new(*x, r.modulus()))
+        .collect_vec();
+    let outputs = circuit.eval_with_public(&mut dummy, &gb, &ev, &public)?;
+    Ok(outputs.expect("dummy will always return Some(Modulus) output"))
+}
+
+/// Evaluate the circuit in plaintext, returning its outputs divided into its
+/// output groups.
+///
+/// See [`eval_plain`] and [`CircuitType::output_widths`].
+pub fn eval_plain_grouped<C: EvaluableCircuit<Dummy>>(
+    circuit: &C,
+    garbler_inputs: &[Modulus],
+    evaluator_inputs: &[Modulus],
+) -> Result<Vec<Vec<Modulus>>, DummyError> {
+    let outputs = eval_plain(circuit, garbler_inputs, evaluator_inputs)?;
+    Ok(circuit.group_outputs(&outputs))
+}
+
+/// Evaluate a binary circuit in plaintext on inputs and outputs packed into
+/// bytes.
+///
+/// The bits of the garbler's and evaluator's inputs are those of
+/// `garbler_inputs` and `evaluator_inputs` in `order`, as
+/// [`BitOrder::bytes_to_bits`] lays them out, and the outputs are packed
+/// into bytes by [`BitOrder::bits_to_bytes`]. For the in-tree Bristol
+/// circuits, such as AES and SHA-256, the order is [`BitOrder::MsbFirst`].
+/// Fails with [`FancyError::ArgNotBinary`] if an input or output of the
+/// circuit is not binary, and with [`FancyError::InvalidArgNum`] if the
+/// number of outputs is not a multiple of 8.
+pub fn eval_plain_bytes<C: EvaluableCircuit<Dummy>>(
+    circuit: &C,
+    garbler_inputs: &[u8],
+    evaluator_inputs: &[u8],
+    order: BitOrder,
+) -> Result<Vec<u8>, DummyError> {
+    let refs = circuit
+        .get_garbler_input_refs()
+        .iter()
+        .chain(circuit.get_evaluator_input_refs())
+        .chain(circuit.get_output_refs());
+    if refs.map(|r| r.modulus()).any(|q| q != 2) {
+        return Err(FancyError::ArgNotBinary.into());
+    }
+    let outputs = eval_plain(
+        circuit,
+        &order.bytes_to_bits(garbler_inputs),
+        &order.bytes_to_bits(evaluator_inputs),
+    )?;
+    Ok(order.bits_to_bytes(&outputs)?)
+}
+
+/// Evaluate the circuit in plaintext, holding only the values of live wires.
+///
+/// See [`EvaluableCircuit::eval_bounded`].
+pub fn eval_plain_bounded<C: EvaluableCircuit<Dummy>>(
+    circuit: &C,
+    garbler_inputs: &[Modulus],
+    evaluator_inputs: &[Modulus],
+    liveness: &Liveness,
+) -> Result<Vec<Modulus>, DummyError> {
+    let mut dummy = crate::dummy::Dummy::new();
+    let (gb, ev) = encode_plain(circuit, garbler_inputs, evaluator_inputs)?;
+    let outputs = circuit.eval_bounded(&mut dummy, &gb, &ev, liveness)?;
+    Ok(outputs.expect("dummy will always return Some(Modulus) output"))
+}
+
+/// Encode plaintext inputs to `circuit` as `DummyVal`s.
+pub(crate) fn encode_plain<C: CircuitType>(
+    circuit: &C,
+    garbler_inputs: &[Modulus],
+    evaluator_inputs: &[Modulus],
+) -> Result<(Vec<DummyVal>, Vec<DummyVal>), DummyError> {
+    if garbler_inputs.len() != circuit.num_garbler_inputs() {
+        return Err(DummyError::NotEnoughGarblerInputs {
+            got: garbler_inputs.len(),
+            needed: circuit.num_garbler_inputs(),
+        });
+    }
+
+    if evaluator_inputs.len() != circuit.num_evaluator_inputs() {
+        return Err(DummyError::NotEnoughEvaluatorInputs {
+            got: evaluator_inputs.len(),
+            needed: circuit.num_evaluator_inputs(),
+        });
+    }
+
+    let gb_moduli = circuit.get_garbler_input_refs().iter().map(|r| r.modulus());
+    let ev_moduli = circuit
+        .get_evaluator_input_refs()
+        .iter()
+        .map(|r| r.modulus());
+    check_inputs(garbler_inputs, gb_moduli)?;
+    check_inputs(evaluator_inputs, ev_moduli).map_err(|e| match e {
+        FancyError::InputOutOfRange {
+            index,
+            value,
+            modulus,
+        } => FancyError::InputOutOfRange {
+            index: garbler_inputs.len() + index,
+            value,
+            modulus,
+        },
+        e => e,
+    })?;
+
+    // encode inputs as DummyVals
+    let gb = garbler_inputs
+        .iter()
+        .zip(circuit.get_garbler_input_refs().iter())
+        .map(|(x, r)| DummyVal::new(*x, r.modulus()))
+        .collect_vec();
+    let ev = evaluator_inputs
+        .iter()
+        .zip(circuit.get_evaluator_input_refs().iter())
+        .map(|(x, r)| DummyVal::new(*x, r.modulus()))
+        .collect_vec();
+    Ok((gb, ev))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Digests
+
+/// A SHA-256 digest of a circuit, computed by [`CircuitType::digest`].
+///
+/// The digest covers every gate, input, constant, and output, in an encoding
+/// that does not depend on the width of `usize` or `Modulus`, so a circuit
+/// has the same digest on every platform.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CircuitDigest([u8; 32]);
+
+impl CircuitDigest {
+    /// The digest with the given bytes.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        CircuitDigest(bytes)
+    }
+
+    /// The bytes of the digest.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for CircuitDigest {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for b in self.0.iter() {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for CircuitDigest {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "CircuitDigest({})", self)
+    }
+}
+
+/// The digest of a circuit, once computed, so that evaluating a garbling of
+/// the circuit many times hashes it only once.
+///
+/// A circuit clears it whenever it changes. Clones start without a digest,
+/// and comparisons of circuits ignore it.
+#[derive(Default)]
+pub(crate) struct DigestCache(Mutex<Option<CircuitDigest>>);
+
+impl DigestCache {
+    fn lock(&self) -> MutexGuard<'_, Option<CircuitDigest>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The cached digest, computing it with `compute` if there is none.
+    fn get_or_compute(&self, compute: impl FnOnce() -> CircuitDigest) -> CircuitDigest {
+        *self.lock().get_or_insert_with(compute)
+    }
+
+    /// Forget the cached digest, as the circuit has changed.
+    pub(crate) fn clear(&mut self) {
+        *self.0.get_mut().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+}
+
+impl Clone for DigestCache {
+    fn clone(&self) -> Self {
+        DigestCache::default()
+    }
+}
+
+impl PartialEq for DigestCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl std::fmt::Debug for DigestCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_tuple("DigestCache").field(&*self.lock()).finish()
+    }
+}
+
+/// Feeds the parts of a circuit to SHA-256, writing every integer as a
+/// little-endian `u64`.
+struct DigestWriter(Sha256);
+
+impl DigestWriter {
+    fn new(kind: &[u8]) -> Self {
+        let mut w = DigestWriter(Sha256::new());
+        w.bytes(kind);
+        w
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.int(bytes.len() as u64);
+        self.0.update(bytes);
+    }
+
+    fn int(&mut self, x: u64) {
+        self.0.update(x.to_le_bytes());
+    }
+
+    fn modulus(&mut self, q: Modulus) {
+        self.int(u64::from(q));
+    }
+
+    fn cref(&mut self, r: &CircuitRef) {
+        self.int(r.ix as u64);
+        self.modulus(r.modulus);
+    }
+
+    fn out(&mut self, out: Option<usize>) {
+        match out {
+            None => self.int(0),
+            Some(ix) => {
+                self.int(1);
+                self.int(ix as u64);
+            }
+        }
+    }
+
+    fn refs(&mut self, refs: &[CircuitRef]) {
+        self.int(refs.len() as u64);
+        for r in refs.iter() {
+            self.cref(r);
+        }
+    }
+
+    /// Write the refs of the public inputs, if there are any, so that
+    /// circuits without public inputs keep the digests they had before
+    /// public inputs were added.
+    fn public_refs(&mut self, refs: &[CircuitRef]) {
+        if !refs.is_empty() {
+            self.refs(refs);
+        }
+    }
+
+    fn finish(self) -> CircuitDigest {
+        CircuitDigest(self.0.finalize().into())
+    }
+}
+
+impl ArithmeticGate {
+    /// Write the gate to `w`, tagged with the index of its variant.
+    fn digest(&self, w: &mut DigestWriter) {
+        match self {
+            Self::GarblerInput { id } => {
+                w.int(0);
+                w.int(*id as u64);
+            }
+            Self::EvaluatorInput { id } => {
+                w.int(1);
+                w.int(*id as u64);
+            }
+            Self::Constant { val } => {
+                w.int(2);
+                w.modulus(*val);
+            }
+            Self::Add { xref, yref, out } => {
+                w.int(3);
+                w.cref(xref);
+                w.cref(yref);
+                w.out(*out);
+            }
+            Self::Sub { xref, yref, out } => {
+                w.int(4);
+                w.cref(xref);
+                w.cref(yref);
+                w.out(*out);
+            }
+            Self::Cmul { xref, c, out } => {
+                w.int(5);
+                w.cref(xref);
+                w.modulus(*c);
+                w.out(*out);
+            }
+            Self::Mul {
+                xref,
+                yref,
+                id,
+                out,
+            } => {
+                w.int(6);
+                w.cref(xref);
+                w.cref(yref);
+                w.int(*id as u64);
+                w.out(*out);
+            }
+            Self::Proj { xref, tt, id, out } => {
+                w.int(7);
+                w.cref(xref);
+                w.int(tt.len() as u64);
+                for &x in tt.iter() {
+                    w.modulus(x);
+                }
+                w.int(*id as u64);
+                w.out(*out);
+            }
+            Self::PublicInput { id } => {
+                w.int(8);
+                w.int(*id as u64);
+            }
+        }
+    }
+}
+
+impl BinaryGate {
+    /// Write the gate to `w`, tagged with the index of its variant.
+    fn digest(&self, w: &mut DigestWriter) {
+        match self {
+            Self::GarblerInput { id } => {
+                w.int(0);
+                w.int(*id as u64);
+            }
+            Self::EvaluatorInput { id } => {
+                w.int(1);
+                w.int(*id as u64);
+            }
+            Self::Constant { val } => {
+                w.int(2);
+                w.modulus(*val);
+            }
+            Self::Xor { xref, yref, out } => {
+                w.int(3);
+                w.cref(xref);
+                w.cref(yref);
+                w.out(*out);
+            }
+            Self::And {
+                xref,
+                yref,
+                id,
+                out,
+            } => {
+                w.int(4);
+                w.cref(xref);
+                w.cref(yref);
+                w.int(*id as u64);
+                w.out(*out);
+            }
+            Self::Inv { xref, out } => {
+                w.int(5);
+                w.cref(xref);
+                w.out(*out);
+            }
+            Self::PublicInput { id } => {
+                w.int(6);
+                w.int(*id as u64);
+            }
+            Self::Custom {
+                gate,
+                inputs,
+                id,
+                out,
+            } => {
+                w.int(7);
+                w.int(*gate as u64);
+                w.refs(inputs);
+                w.int(*id as u64);
+                w.out(*out);
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Liveness
+
+/// When the output of each gate of a circuit is last read.
+///
+/// Computed by [`CircuitType::liveness`], and used by
+/// [`EvaluableCircuit::eval_bounded`] to drop wire values as soon as they are
+/// dead and reuse their storage.
+#[derive(Clone, Debug)]
+pub struct Liveness {
+    /// For each gate, the index of the last gate that reads its output. This is
+    /// the gate itself if its output is never read, and `NONE` if its output
+    /// is a circuit output.
+    last_use: Vec<u32>,
+    width: usize,
+}
+
+impl Liveness {
+    /// The last use of a circuit output, and the definition of a wire no gate
+    /// has written.
+    const NONE: u32 = u32::MAX;
+
+    /// Compute liveness from the wires read and written by each gate, in order.
+    ///
+    /// Gate indices are kept as `u32`s, so the circuit must have fewer than
+    /// `u32::MAX` gates.
+    fn new<'a>(
+        ngates: usize,
+        wires: impl Iterator<Item = (GateInputs<'a>, usize)> + Clone,
+        outputs: &[CircuitRef],
+    ) -> Self {
+        assert!(
+            ngates < Self::NONE as usize,
+            "liveness of a circuit of {} gates",
+            ngates
+        );
+        // the gate currently writing each wire
+        let mut defs = vec![Self::NONE; ngates];
+        let mut last_use = (0..ngates as u32).collect_vec();
+        for (i, (inputs, z)) in wires.clone().enumerate() {
+            let i = i as u32;
+            for ix in inputs.iter() {
+                if let Some(&def) = defs.get(ix).filter(|&&def| def != Self::NONE) {
+                    last_use[def as usize] = i;
+                }
+            }
+            defs[z] = i;
+        }
+        for r in outputs {
+            if let Some(&def) = defs.get(r.ix).filter(|&&def| def != Self::NONE) {
+                last_use[def as usize] = Self::NONE;
+            }
+        }
+
+        // replay the evaluation to find how many values are live at once
+        defs.iter_mut().for_each(|def| *def = Self::NONE);
+        let mut live = 0;
+        let mut width = 0;
+        for (i, (inputs, z)) in wires.enumerate() {
+            let i = i as u32;
+            let dies = |&def: &u32| def != Self::NONE && last_use[def as usize] == i;
+            for ix in inputs.distinct() {
+                if defs.get(ix).map_or(false, dies) {
+                    live -= 1;
+                }
+            }
+            if last_use[i as usize] != i {
+                live += 1;
+                width = width.max(live);
+            }
+            defs[z] = i;
+        }
+        Liveness { last_use, width }
+    }
+
+    /// The number of gates in the circuit.
+    pub fn ngates(&self) -> usize {
+        self.last_use.len()
+    }
+
+    /// The index of the last gate that reads the output of gate `i`, or `None` if
+    /// the output of gate `i` is a circuit output.
+    ///
+    /// Returns `Some(i)` if the output of gate `i` is never read.
+    pub fn last_use(&self, i: usize) -> Option<usize> {
+        Some(self.last_use[i])
+            .filter(|&j| j != Self::NONE)
+            .map(|j| j as usize)
+    }
+
+    /// The largest number of wire values live at once during evaluation.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+}
+
+/// Storage for the wire values of a circuit, reused across evaluations by
+/// [`EvaluableCircuit::eval_with_buffer`].
+///
+/// The storage grows to fit the largest circuit evaluated with the buffer, and
+/// is cleared, but not freed, before every evaluation.
+#[derive(Clone, Debug)]
+pub struct EvalBuffer<T> {
+    wires: Vec<Option<T>>,
+}
+
+impl<T> EvalBuffer<T> {
+    /// Create an empty buffer, which allocates nothing until it is used.
+    pub fn new() -> Self {
+        EvalBuffer { wires: Vec::new() }
+    }
+
+    /// Clear the buffer and make room for `nwires` wires.
+    fn reset(&mut self, nwires: usize) -> &mut Vec<Option<T>> {
+        self.wires.clear();
+        self.wires.resize_with(nwires, || None);
+        &mut self.wires
+    }
+}
+
+impl<T> Default for EvalBuffer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Storage for wire values during circuit evaluation.
+trait WireStore<T> {
+    /// The value on wire `ix`, if any.
+    fn value(&self, ix: usize) -> Option<&T>;
+
+    /// Called after gate `i` reads wire `ix`.
+    fn release(&mut self, i: usize, ix: usize);
+
+    /// Set wire `ix` to the output of gate `i`.
+    fn store(&mut self, i: usize, ix: usize, val: T);
+}
+
+impl<T> WireStore<T> for Vec<Option<T>> {
+    fn value(&self, ix: usize) -> Option<&T> {
+        self[ix].as_ref()
+    }
+
+    fn release(&mut self, _: usize, _: usize) {}
+
+    fn store(&mut self, _: usize, ix: usize, val: T) {
+        self[ix] = Some(val);
+    }
+}
+
+impl<T, S: WireStore<T>> WireStore<T> for &mut S {
+    fn value(&self, ix: usize) -> Option<&T> {
+        (**self).value(ix)
+    }
+
+    fn release(&mut self, i: usize, ix: usize) {
+        (**self).release(i, ix)
+    }
+
+    fn store(&mut self, i: usize, ix: usize, val: T) {
+        (**self).store(i, ix, val)
+    }
+}
+
+/// Stores the values of all wires, and the value computed by each gate.
+struct TraceStore<T> {
+    wires: Vec<Option<T>>,
+    gates: Vec<T>,
+}
+
+impl<T> TraceStore<T> {
+    fn new(nwires: usize) -> Self {
+        TraceStore {
+            wires: (0..nwires).map(|_| None).collect(),
+            gates: Vec::with_capacity(nwires),
+        }
+    }
+}
+
+impl<T: Clone> WireStore<T> for TraceStore<T> {
+    fn value(&self, ix: usize) -> Option<&T> {
+        self.wires[ix].as_ref()
+    }
+
+    fn release(&mut self, _: usize, _: usize) {}
+
+    fn store(&mut self, _: usize, ix: usize, val: T) {
+        self.gates.push(val.clone());
+        self.wires[ix] = Some(val);
+    }
+}
+
+/// Stores the values of live wires only, so that it holds at most as many
+/// values as the circuit is wide, whatever its number of wires.
+struct LiveStore<'a, T> {
+    liveness: &'a Liveness,
+    // map from each live wire to its value and the index of the last gate to
+    // read it.
+    live: HashMap<usize, (T, usize)>,
+}
+
+impl<'a, T> LiveStore<'a, T> {
+    fn new(nwires: usize, liveness: &'a Liveness) -> Result<Self, FancyError> {
+        if liveness.ngates() != nwires {
+            return Err(FancyError::InvalidArg(
+                "liveness was computed for a different circuit".to_string(),
+            ));
+        }
+        Ok(LiveStore {
+            liveness,
+            live: HashMap::with_capacity(liveness.width()),
+        })
+    }
+}
+
+impl<T> WireStore<T> for LiveStore<'_, T> {
+    fn value(&self, ix: usize) -> Option<&T> {
+        self.live.get(&ix).map(|(val, _)| val)
+    }
+
+    fn release(&mut self, i: usize, ix: usize) {
+        if matches!(self.live.get(&ix), Some(&(_, last)) if last == i) {
+            self.live.remove(&ix);
+        }
+    }
+
+    fn store(&mut self, i: usize, ix: usize, val: T) {
+        match self.liveness.last_use(i) {
+            // nothing reads this value
+            Some(last) if last == i => {
+                self.live.remove(&ix);
+            }
+            last => {
+                self.live.insert(ix, (val, last.unwrap_or(usize::MAX)));
+                debug_assert!(self.live.len() <= self.liveness.width());
+            }
+        }
+    }
+}
+
+impl CircuitType for BinaryCircuit {
+    type Gate = BinaryGate;
+
+    fn new(ngates: Option<usize>) -> Self {
+        let gates = Vec::with_capacity(ngates.unwrap_or(0));
+        Self {
+            gates,
+            garbler_input_refs: Vec::new(),
+            evaluator_input_refs: Vec::new(),
+            public_input_refs: Vec::new(),
+            const_refs: Vec::new(),
+            output_refs: Vec::new(),
+            num_nonfree_gates: 0,
+            output_groups: Vec::new(),
+            custom_gates: CustomGates::default(),
+            digest: DigestCache::default(),
+        }
+    }
+
+    fn push_gates(&mut self, gate: Self::Gate) {
+        self.digest.clear();
+        self.gates.push(gate)
+    }
+
+    fn push_const_ref(&mut self, xref: CircuitRef) {
+        self.digest.clear();
+        self.const_refs.push(xref)
+    }
+
+    fn push_output_ref(&mut self, xref: CircuitRef) {
+        self.digest.clear();
+        self.output_refs.push(xref)
+    }
+
+    fn get_output_groups(&self) -> &[usize] {
+        &self.output_groups
+    }
+
+    fn push_output_group(&mut self, width: usize) {
+        self.output_groups.push(width)
+    }
+
+    fn push_garbler_input_ref(&mut self, xref: CircuitRef) {
+        self.digest.clear();
+        self.garbler_input_refs.push(xref)
+    }
+
+    fn push_modulus(&mut self, modulus: Modulus) {
+        assert_eq!(modulus, 2);
+    }
+
+    fn reserve_gates(&mut self, ngates: usize) {
+        self.gates.reserve_exact(ngates);
+    }
+
+    fn reserve_wires(&mut self, nwires: usize) {
+        self.garbler_input_refs.reserve(nwires);
+        self.evaluator_input_refs.reserve(nwires);
+        self.output_refs.reserve(nwires);
+    }
+
+    fn push_evaluator_input_ref(&mut self, xref: CircuitRef) {
+        self.digest.clear();
+        self.evaluator_input_refs.push(xref)
+    }
+
+    fn push_public_input_ref(&mut self, xref: CircuitRef) {
+        self.digest.clear();
+        self.public_input_refs.push(xref)
+    }
+
+    fn increment_nonfree_gates(&mut self) {
+        self.num_nonfree_gates += 1;
+    }
+
+    fn get_gates(&self) -> &[Self::Gate] {
+        &self.gates
+    }
+
+    fn get_num_nonfree_gates(&self) -> usize {
+        self.num_nonfree_gates
+    }
+
+    fn get_output_refs(&self) -> &[CircuitRef] {
+        &self.output_refs
+    }
+
+    fn get_garbler_input_refs(&self) -> &[CircuitRef] {
+        &self.garbler_input_refs
+    }
+
+    fn get_evaluator_input_refs(&self) -> &[CircuitRef] {
+        &self.evaluator_input_refs
+    }
+
+    fn get_public_input_refs(&self) -> &[CircuitRef] {
+        &self.public_input_refs
+    }
+
+    fn garbler_input_mod(&self, _: usize) -> Modulus {
+        2
+    }
+
+    fn evaluator_input_mod(&self, _: usize) -> Modulus {
+        2
+    }
+
+    fn liveness(&self) -> Liveness {
+        let wires = self.gates.iter().enumerate().map(|(i, g)| g.wires(i));
+        Liveness::new(self.gates.len(), wires, &self.output_refs)
+    }
+
+    fn stats(&self) -> CircuitStats {
+        stats::binary_stats(self)
+    }
+
+    fn levels(&self) -> Levels {
+        let wires = self.gates.iter().enumerate().map(|(i, g)| g.wires(i));
+        Levels::new(self.gates.len(), wires, &self.output_refs)
+    }
+
+    fn garbled_size_estimate(&self, security: SecurityParams) -> usize {
+        stats::binary_garbled_size(self, security)
+    }
+
+    fn digest(&self) -> CircuitDigest {
+        self.digest.get_or_compute(|| self.compute_digest())
+    }
+}
+
+impl CircuitType for ArithmeticCircuit {
+    type Gate = ArithmeticGate;
+
+    fn new(ngates: Option<usize>) -> ArithmeticCircuit {
+        let gates = Vec::with_capacity(ngates.unwrap_or(0));
+        let gate_moduli = Vec::with_capacity(ngates.unwrap_or(0));
+        ArithmeticCircuit {
+            gates,
+            garbler_input_refs: Vec::new(),
+            evaluator_input_refs: Vec::new(),
+            public_input_refs: Vec::new(),
+            const_refs: Vec::new(),
+            output_refs: Vec::new(),
+            gate_moduli,
+            num_nonfree_gates: 0,
+            output_groups: Vec::new(),
+            digest: DigestCache::default(),
+        }
+    }
+
+    fn push_gates(&mut self, gate: Self::Gate) {
+        self.digest.clear();
+        self.gates.push(gate)
+    }
+
+    fn push_const_ref(&mut self, xref: CircuitRef) {
+        self.digest.clear();
+        self.const_refs.push(xref)
+    }
+
+    fn push_output_ref(&mut self, xref: CircuitRef) {
+        self.digest.clear();
+        self.output_refs.push(xref)
+    }
+
+    fn get_output_groups(&self) -> &[usize] {
+        &self.output_groups
+    }
+
+    fn push_output_group(&mut self, width: usize) {
+        self.output_groups.push(width)
+    }
+
+    fn push_garbler_input_ref(&mut self, xref: CircuitRef) {
+        self.digest.clear();
+        self.garbler_input_refs.push(xref)
+    }
+
+    fn push_modulus(&mut self, modulus: Modulus) {
+        self.digest.clear();
+        self.gate_moduli.push(modulus)
+    }
+
+    fn reserve_gates(&mut self, ngates: usize) {
+        self.gates.reserve_exact(ngates);
+        self.gate_moduli.reserve_exact(ngates);
+    }
+
+    fn reserve_wires(&mut self, nwires: usize) {
+        self.garbler_input_refs.reserve(nwires);
+        self.evaluator_input_refs.reserve(nwires);
+        self.output_refs.reserve(nwires);
+    }
+
+    fn push_evaluator_input_ref(&mut self, xref: CircuitRef) {
+        self.digest.clear();
+        self.evaluator_input_refs.push(xref)
+    }
+
+    fn push_public_input_ref(&mut self, xref: CircuitRef) {
+        self.digest.clear();
+        self.public_input_refs.push(xref)
+    }
+
+    fn increment_nonfree_gates(&mut self) {
+        self.num_nonfree_gates += 1;
+    }
+
+    fn get_gates(&self) -> &[Self::Gate] {
+        &self.gates
+    }
+
+    fn get_num_nonfree_gates(&self) -> usize {
+        self.num_nonfree_gates
+    }
+
+    fn get_output_refs(&self) -> &[CircuitRef] {
+        &self.output_refs
+    }
+
+    fn get_garbler_input_refs(&self) -> &[CircuitRef] {
+        &self.garbler_input_refs
+    }
+
+    fn get_evaluator_input_refs(&self) -> &[CircuitRef] {
+        &self.evaluator_input_refs
+    }
+
+    fn get_public_input_refs(&self) -> &[CircuitRef] {
+        &self.public_input_refs
+    }
+
+    fn garbler_input_mod(&self, i: usize) -> Modulus {
+        let r = self.garbler_input_refs[i];
+        r.modulus()
+    }
+
+    fn evaluator_input_mod(&self, i: usize) -> Modulus {
+        let r = self.evaluator_input_refs[i];
+        r.modulus()
+    }
+
+    fn liveness(&self) -> Liveness {
+        let wires = self.gates.iter().enumerate().map(|(i, g)| g.wires(i));
+        Liveness::new(self.gates.len(), wires, &self.output_refs)
+    }
+
+    fn stats(&self) -> CircuitStats {
+        stats::arithmetic_stats(self)
+    }
+
+    fn levels(&self) -> Levels {
+        let wires = self.gates.iter().enumerate().map(|(i, g)| g.wires(i));
+        Levels::new(self.gates.len(), wires, &self.output_refs)
+    }
+
+    fn garbled_size_estimate(&self, security: SecurityParams) -> usize {
+        stats::arithmetic_garbled_size(self, security)
+    }
+
+    fn digest(&self) -> CircuitDigest {
+        self.digest.get_or_compute(|| self.compute_digest())
+    }
+}
+
+impl ArithmeticCircuit {
+    /// Return the modulus of the gate indexed by `i`.
+    #[inline]
+    pub fn modulus(&self, i: usize) -> Modulus {
+        self.gate_moduli[i]
+    }
+
+    /// Return the gates of the circuit.
+    pub fn gates(&self) -> &[ArithmeticGate] {
+        &self.gates
+    }
+
+    /// Divide the outputs into groups of the given widths, replacing any
+    /// groups the circuit had; see [`CircuitType::output_widths`].
+    ///
+    /// Fails if the widths add up to more than the number of outputs.
+    pub fn set_output_widths(&mut self, widths: &[usize]) -> Result<(), CircuitValidationError> {
+        check_output_widths(widths, self.noutputs())?;
+        self.output_groups = widths.to_vec();
+        Ok(())
+    }
+
+    /// Make some garbler and evaluator inputs public inputs, known to both
+    /// parties, as for a circuit parsed from a Bristol file, which has none of
+    /// its own.
+    ///
+    /// The new public inputs follow any the circuit has, in the order of their
+    /// gates. The inputs each party keeps keep their order, and are numbered
+    /// from zero. Fails, leaving the circuit unchanged, with
+    /// [`FancyError::NotEnoughGarblerInputs`] or
+    /// [`FancyError::NotEnoughEvaluatorInputs`] if an id is not that of an
+    /// input.
+    pub fn make_inputs_public(
+        &mut self,
+        garbler_inputs: &[usize],
+        evaluator_inputs: &[usize],
+    ) -> Result<(), FancyError> {
+        let gb = kept_input_ids(
+            garbler_inputs,
+            self.garbler_input_refs.len(),
+            |got, needed| FancyError::NotEnoughGarblerInputs { got, needed },
+        )?;
+        let ev = kept_input_ids(
+            evaluator_inputs,
+            self.evaluator_input_refs.len(),
+            |got, needed| FancyError::NotEnoughEvaluatorInputs { got, needed },
+        )?;
+        self.digest.clear();
+        for gate in self.gates.iter_mut() {
+            let (ids, refs, id, garbler) = match *gate {
+                ArithmeticGate::GarblerInput { id } => (&gb, &self.garbler_input_refs, id, true),
+                ArithmeticGate::EvaluatorInput { id } => {
+                    (&ev, &self.evaluator_input_refs, id, false)
+                }
+                _ => continue,
+            };
+            *gate = match ids.get(id) {
+                Some(&Some(id)) if garbler => ArithmeticGate::GarblerInput { id },
+                Some(&Some(id)) => ArithmeticGate::EvaluatorInput { id },
+                Some(None) => {
+                    self.public_input_refs.push(refs[id]);
+                    ArithmeticGate::PublicInput {
+                        id: self.public_input_refs.len() - 1,
+                    }
+                }
+                None => continue,
+            };
+        }
+        self.garbler_input_refs = kept_refs(&self.garbler_input_refs, &gb);
+        self.evaluator_input_refs = kept_refs(&self.evaluator_input_refs, &ev);
+        Ok(())
+    }
+
+    /// Check that the circuit is well-formed: every reference points to a wire
+    /// that has been assigned and has a matching modulus, every gate has a valid
+    /// modulus, every constant is less than its modulus, every projection has
+    /// a truth table matching its moduli, and the output groups are no wider
+    /// than the outputs.
+    pub fn validate(&self) -> Result<(), CircuitValidationError> {
+        if self.gate_moduli.len() != self.gates.len() {
+            return Err(CircuitValidationError::MissingModuli {
+                got: self.gate_moduli.len(),
+                needed: self.gates.len(),
+            });
+        }
+        let mut wires = WireCheck::new(self.gates.len());
+        for (i, gate) in self.gates.iter().enumerate() {
+            let q = self.gate_moduli[i];
+            if q < 2 {
+                return Err(CircuitValidationError::InvalidModulus {
+                    gate: i,
+                    modulus: q,
+                });
+            }
+            let out = match *gate {
+                ArithmeticGate::GarblerInput { id } => {
+                    check_input(i, id, self.garbler_input_refs.len())?;
+                    None
+                }
+                ArithmeticGate::EvaluatorInput { id } => {
+                    check_input(i, id, self.evaluator_input_refs.len())?;
+                    None
+                }
+                ArithmeticGate::PublicInput { id } => {
+                    check_input(i, id, self.public_input_refs.len())?;
+                    None
+                }
+                ArithmeticGate::Constant { val } => {
+                    check_constant_gate(i, val, q)?;
+                    None
+                }
+                ArithmeticGate::Add { xref, yref, out }
+                | ArithmeticGate::Sub { xref, yref, out }
+                | ArithmeticGate::Mul {
+                    xref, yref, out, ..
+                } => {
+                    wires.read(xref)?;
+                    wires.read(yref)?;
+                    out
+                }
+                ArithmeticGate::Cmul { xref, out, .. } => {
+                    wires.read(xref)?;
+                    out
+                }
+                ArithmeticGate::Proj {
+                    xref, ref tt, out, ..
+                } => {
+                    wires.read(xref)?;
+                    if tt.len() < xref.modulus as usize || tt.iter().any(|&y| y >= q) {
+                        return Err(CircuitValidationError::InvalidTruthTable(i));
+                    }
+                    out
+                }
+            };
+            wires.write(out.unwrap_or(i), q)?;
+        }
+        wires.read_all(&self.garbler_input_refs)?;
+        wires.read_all(&self.evaluator_input_refs)?;
+        wires.read_all(&self.public_input_refs)?;
+        wires.read_all(&self.const_refs)?;
+        wires.read_all(&self.output_refs)?;
+        check_output_groups(self)
+    }
+}
+
+impl BinaryCircuit {
+    /// Return the gates of the circuit.
+    pub fn gates(&self) -> &[BinaryGate] {
+        &self.gates
+    }
+
+    /// Return the custom gates registered on the circuit, which
+    /// [`BinaryGate::Custom`] gates refer to by index.
+    pub fn custom_gates(&self) -> &[Arc<dyn CustomGate>] {
+        self.custom_gates.as_slice()
+    }
+
+    /// Divide the outputs into groups of the given widths, replacing any
+    /// groups the circuit had; see [`CircuitType::output_widths`].
+    ///
+    /// Fails if the widths add up to more than the number of outputs.
+    pub fn set_output_widths(&mut self, widths: &[usize]) -> Result<(), CircuitValidationError> {
+        check_output_widths(widths, self.noutputs())?;
+        self.output_groups = widths.to_vec();
+        Ok(())
+    }
+
+    /// Make some garbler and evaluator inputs public inputs, known to both
+    /// parties, as for a circuit parsed from a Bristol file, which has none of
+    /// its own.
+    ///
+    /// The new public inputs follow any the circuit has, in the order of their
+    /// gates. The inputs each party keeps keep their order, and are numbered
+    /// from zero. Fails, leaving the circuit unchanged, with
+    /// [`FancyError::NotEnoughGarblerInputs`] or
+    /// [`FancyError::NotEnoughEvaluatorInputs`] if an id is not that of an
+    /// input.
+    pub fn make_inputs_public(
+        &mut self,
+        garbler_inputs: &[usize],
+        evaluator_inputs: &[usize],
+    ) -> Result<(), FancyError> {
+        let gb = kept_input_ids(
+            garbler_inputs,
+            self.garbler_input_refs.len(),
+            |got, needed| FancyError::NotEnoughGarblerInputs { got, needed },
+        )?;
+        let ev = kept_input_ids(
+            evaluator_inputs,
+            self.evaluator_input_refs.len(),
+            |got, needed| FancyError::NotEnoughEvaluatorInputs { got, needed },
+        )?;
+        self.digest.clear();
+        for gate in self.gates.iter_mut() {
+            let (ids, refs, id, garbler) = match *gate {
+                BinaryGate::GarblerInput { id } => (&gb, &self.garbler_input_refs, id, true),
+                BinaryGate::EvaluatorInput { id } => (&ev, &self.evaluator_input_refs, id, false),
+                _ => continue,
+            };
+            *gate = match ids.get(id) {
+                Some(&Some(id)) if garbler => BinaryGate::GarblerInput { id },
+                Some(&Some(id)) => BinaryGate::EvaluatorInput { id },
+                Some(None) => {
+                    self.public_input_refs.push(refs[id]);
+                    BinaryGate::PublicInput {
+                        id: self.public_input_refs.len() - 1,
+                    }
+                }
+                None => continue,
+            };
+        }
+        self.garbler_input_refs = kept_refs(&self.garbler_input_refs, &gb);
+        self.evaluator_input_refs = kept_refs(&self.evaluator_input_refs, &ev);
+        Ok(())
+    }
+
+    /// Check that the circuit is well-formed: every reference points to a wire
+    /// that has been assigned, every wire has modulus 2, every constant is 0 or
+    /// 1, every custom gate is registered and has as many inputs as it takes,
+    /// and the output groups are no wider than the outputs.
+    pub fn validate(&self) -> Result<(), CircuitValidationError> {
+        let mut wires = WireCheck::new(self.gates.len());
+        for (i, gate) in self.gates.iter().enumerate() {
+            match *gate {
+                BinaryGate::GarblerInput { id } => {
+                    check_input(i, id, self.garbler_input_refs.len())?
+                }
+                BinaryGate::EvaluatorInput { id } => {
+                    check_input(i, id, self.evaluator_input_refs.len())?
+                }
+                BinaryGate::PublicInput { id } => check_input(i, id, self.public_input_refs.len())?,
+                BinaryGate::Constant { val } => check_constant_gate(i, val, 2)?,
+                BinaryGate::Xor { xref, yref, .. } | BinaryGate::And { xref, yref, .. } => {
+                    wires.read(xref)?;
+                    wires.read(yref)?;
+                }
+                BinaryGate::Inv { xref, .. } => wires.read(xref)?,
+                BinaryGate::Custom {
+                    gate, ref inputs, ..
+                } => {
+                    match self.custom_gates.get(gate) {
+                        Some(g) if g.arity() == inputs.len() => (),
+                        _ => return Err(CircuitValidationError::InvalidCustomGate(i)),
+                    }
+                    for &r in inputs {
+                        wires.read(r)?;
+                    }
+                }
+            }
+            let (_, z) = gate.wires(i);
+            wires.write(z, 2)?;
+        }
+        wires.read_all(&self.garbler_input_refs)?;
+        wires.read_all(&self.evaluator_input_refs)?;
+        wires.read_all(&self.public_input_refs)?;
+        wires.read_all(&self.const_refs)?;
+        wires.read_all(&self.output_refs)?;
+        check_output_groups(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod format {
+    //! The serialized form of circuits.
+    //!
+    //! Circuits are serialized as a struct holding a format version followed by
+    //! the fields of the circuit. The version is bumped whenever the layout
+    //! changes, and deserialization converts payloads of older versions.
 
-    /// Add an output ref
-    fn push_output_ref(&mut self, xref: CircuitRef);
+    use super::*;
+    use crate::{errors::FormatError, format::FormatId};
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    /// The current version of the circuit format, both within the serialized
+    /// circuit and in the frames written by `to_bytes`. Version 1 lacked the
+    /// output groups, and version 2 the public inputs.
+    pub(super) const VERSION: u16 = 3;
+
+    impl ArithmeticCircuit {
+        /// Serialize the circuit as a [`FormatId::ArithmeticCircuit`] frame
+        /// (see [`crate::format`]).
+        pub fn to_bytes(&self) -> Vec<u8> {
+            crate::format::serialize(FormatId::ArithmeticCircuit, VERSION, self)
+        }
 
-    /// Add a garbler input ref
-    fn push_garbler_input_ref(&mut self, xref: CircuitRef);
+        /// Deserialize and validate a circuit written by
+        /// [`ArithmeticCircuit::to_bytes`].
+        pub fn from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+            let format = FormatId::ArithmeticCircuit;
+            match crate::format::decode(bytes, format, 1..=VERSION)? {
+                (1, payload) => {
+                    let c: ArithmeticCircuitV1 =
+                        crate::format::deserialize_payload(payload, format)?;
+                    Self::try_from(c).map_err(|e| FormatError::InvalidPayload {
+                        format,
+                        reason: e.to_string(),
+                    })
+                }
+                (2, payload) => {
+                    let c: ArithmeticCircuitV2 =
+                        crate::format::deserialize_payload(payload, format)?;
+                    Self::try_from(c).map_err(|e| FormatError::InvalidPayload {
+                        format,
+                        reason: e.to_string(),
+                    })
+                }
+                (_, payload) => crate::format::deserialize_payload(payload, format),
+            }
+        }
+    }
 
-    /// Add an evaluator input ref
-    fn push_evaluator_input_ref(&mut self, xref: CircuitRef);
+    impl BinaryCircuit {
+        /// Serialize the circuit as a [`FormatId::BinaryCircuit`] frame (see
+        /// [`crate::format`]).
+        ///
+        /// # Panics
+        ///
+        /// Panics if the circuit has [custom gates](CustomGate), which cannot
+        /// be serialized.
+        pub fn to_bytes(&self) -> Vec<u8> {
+            crate::format::serialize(FormatId::BinaryCircuit, VERSION, self)
+        }
 
-    /// Add wire moulus
-    fn push_modulus(&mut self, modulus: u16);
+        /// Deserialize and validate a circuit written by
+        /// [`BinaryCircuit::to_bytes`].
+        pub fn from_bytes(bytes: &[u8]) -> Result<Self, FormatError> {
+            let format = FormatId::BinaryCircuit;
+            match crate::format::decode(bytes, format, 1..=VERSION)? {
+                (1, payload) => {
+                    let c: BinaryCircuitV1 = crate::format::deserialize_payload(payload, format)?;
+                    Self::try_from(c).map_err(|e| FormatError::InvalidPayload {
+                        format,
+                        reason: e.to_string(),
+                    })
+                }
+                (2, payload) => {
+                    let c: BinaryCircuitV2 = crate::format::deserialize_payload(payload, format)?;
+                    Self::try_from(c).map_err(|e| FormatError::InvalidPayload {
+                        format,
+                        reason: e.to_string(),
+                    })
+                }
+                (_, payload) => crate::format::deserialize_payload(payload, format),
+            }
+        }
+    }
 
-    /// Return the modulus of the garbler input indexed by `i`.
-    fn garbler_input_mod(&self, i: usize) -> u16;
+    /// JSON payloads written before the format was versioned lack a version,
+    /// and have the same layout as version 1.
+    fn unversioned() -> u16 {
+        1
+    }
 
-    /// Return the modulus of the evaluator input indexed by `i`.
-    fn evaluator_input_mod(&self, i: usize) -> u16;
+    #[derive(Serialize)]
+    #[serde(rename = "ArithmeticCircuit")]
+    struct ArithmeticCircuitRef<'a> {
+        version: u16,
+        gates: &'a [ArithmeticGate],
+        gate_moduli: &'a [Modulus],
+        garbler_input_refs: &'a [CircuitRef],
+        evaluator_input_refs: &'a [CircuitRef],
+        const_refs: &'a [CircuitRef],
+        output_refs: &'a [CircuitRef],
+        num_nonfree_gates: usize,
+        output_groups: &'a [usize],
+        public_input_refs: &'a [CircuitRef],
+    }
 
-    /// Return the number of garbler inputs.
-    #[inline]
-    fn num_garbler_inputs(&self) -> usize {
-        self.get_garbler_input_refs().len()
+    #[derive(Deserialize)]
+    #[serde(rename = "ArithmeticCircuit")]
+    pub(super) struct ArithmeticCircuitV1 {
+        #[serde(default = "unversioned")]
+        version: u16,
+        gates: Vec<ArithmeticGate>,
+        gate_moduli: Vec<Modulus>,
+        garbler_input_refs: Vec<CircuitRef>,
+        evaluator_input_refs: Vec<CircuitRef>,
+        const_refs: Vec<CircuitRef>,
+        output_refs: Vec<CircuitRef>,
+        num_nonfree_gates: usize,
     }
 
-    /// Return the number of evaluator inputs.
-    #[inline]
-    fn num_evaluator_inputs(&self) -> usize {
-        self.get_evaluator_input_refs().len()
+    /// Version 2 added the output groups.
+    #[derive(Deserialize)]
+    #[serde(rename = "ArithmeticCircuit")]
+    pub(super) struct ArithmeticCircuitV2 {
+        #[serde(default = "unversioned")]
+        version: u16,
+        gates: Vec<ArithmeticGate>,
+        gate_moduli: Vec<Modulus>,
+        garbler_input_refs: Vec<CircuitRef>,
+        evaluator_input_refs: Vec<CircuitRef>,
+        const_refs: Vec<CircuitRef>,
+        output_refs: Vec<CircuitRef>,
+        num_nonfree_gates: usize,
+        #[serde(default)]
+        output_groups: Vec<usize>,
     }
 
-    /// Return the number of outputs.
-    #[inline]
-    fn noutputs(&self) -> usize {
-        self.get_output_refs().len()
+    /// Version 3 added the public inputs. Self-describing formats, like JSON,
+    /// read payloads of versions 1 and 2 with this too.
+    #[derive(Deserialize)]
+    #[serde(rename = "ArithmeticCircuit")]
+    pub(super) struct ArithmeticCircuitV3 {
+        #[serde(default = "unversioned")]
+        version: u16,
+        gates: Vec<ArithmeticGate>,
+        gate_moduli: Vec<Modulus>,
+        garbler_input_refs: Vec<CircuitRef>,
+        evaluator_input_refs: Vec<CircuitRef>,
+        const_refs: Vec<CircuitRef>,
+        output_refs: Vec<CircuitRef>,
+        num_nonfree_gates: usize,
+        #[serde(default)]
+        output_groups: Vec<usize>,
+        #[serde(default)]
+        public_input_refs: Vec<CircuitRef>,
     }
-}
 
-/// Evaluate the circuit in plaintext.
-pub fn eval_plain<C: EvaluableCircuit<Dummy>>(
-    circuit: &C,
-    garbler_inputs: &[u16],
-    evaluator_inputs: &[u16],
-) -> Result<Vec<u16>, DummyError> {
-    let mut dummy = crate::dummy::Dummy::new();
+    impl From<ArithmeticCircuitV2> for ArithmeticCircuitV3 {
+        fn from(c: ArithmeticCircuitV2) -> Self {
+            ArithmeticCircuitV3 {
+                version: c.version,
+                gates: c.gates,
+                gate_moduli: c.gate_moduli,
+                garbler_input_refs: c.garbler_input_refs,
+                evaluator_input_refs: c.evaluator_input_refs,
+                const_refs: c.const_refs,
+                output_refs: c.output_refs,
+                num_nonfree_gates: c.num_nonfree_gates,
+                output_groups: c.output_groups,
+                public_input_refs: Vec::new(),
+            }
+        }
+    }
 
-    if garbler_inputs.len() != circuit.num_garbler_inputs() {
-        return Err(DummyError::NotEnoughGarblerInputs);
+    impl From<ArithmeticCircuitV1> for ArithmeticCircuitV2 {
+        fn from(c: ArithmeticCircuitV1) -> Self {
+            ArithmeticCircuitV2 {
+                version: c.version,
+                gates: c.gates,
+                gate_moduli: c.gate_moduli,
+                garbler_input_refs: c.garbler_input_refs,
+                evaluator_input_refs: c.evaluator_input_refs,
+                const_refs: c.const_refs,
+                output_refs: c.output_refs,
+                num_nonfree_gates: c.num_nonfree_gates,
+                output_groups: Vec::new(),
+            }
+        }
     }
 
-    if evaluator_inputs.len() != circuit.num_evaluator_inputs() {
-        return Err(DummyError::NotEnoughEvaluatorInputs);
+    #[derive(Serialize)]
+    #[serde(rename = "BinaryCircuit")]
+    struct BinaryCircuitRef<'a> {
+        version: u16,
+        gates: &'a [BinaryGate],
+        garbler_input_refs: &'a [CircuitRef],
+        evaluator_input_refs: &'a [CircuitRef],
+        const_refs: &'a [CircuitRef],
+        output_refs: &'a [CircuitRef],
+        num_nonfree_gates: usize,
+        output_groups: &'a [usize],
+        public_input_refs: &'a [CircuitRef],
     }
 
-    // encode inputs as DummyVals
-    let gb = garbler_inputs
-        .iter()
-        .zip(circuit.get_garbler_input_refs().iter())
-        .map(|(x, r)| DummyVal::new(*x, r.modulus()))
-        .collect_vec();
-    let ev = evaluator_inputs
-        .iter()
-        .zip(circuit.get_evaluator_input_refs().iter())
-        .map(|(x, r)| DummyVal::new(*x, r.modulus()))
-        .collect_vec();
+    #[derive(Deserialize)]
+    #[serde(rename = "BinaryCircuit")]
+    pub(super) struct BinaryCircuitV1 {
+        #[serde(default = "unversioned")]
+        version: u16,
+        gates: Vec<BinaryGate>,
+        garbler_input_refs: Vec<CircuitRef>,
+        evaluator_input_refs: Vec<CircuitRef>,
+        const_refs: Vec<CircuitRef>,
+        output_refs: Vec<CircuitRef>,
+        num_nonfree_gates: usize,
+    }
 
-    let outputs = circuit.eval(&mut dummy, &gb, &ev)?;
-    Ok(outputs.expect("dummy will always return Some(u16) output"))
-}
+    /// Version 2 added the output groups.
+    #[derive(Deserialize)]
+    #[serde(rename = "BinaryCircuit")]
+    pub(super) struct BinaryCircuitV2 {
+        #[serde(default = "unversioned")]
+        version: u16,
+        gates: Vec<BinaryGate>,
+        garbler_input_refs: Vec<CircuitRef>,
+        evaluator_input_refs: Vec<CircuitRef>,
+        const_refs: Vec<CircuitRef>,
+        output_refs: Vec<CircuitRef>,
+        num_nonfree_gates: usize,
+        #[serde(default)]
+        output_groups: Vec<usize>,
+    }
 
-impl CircuitType for BinaryCircuit {
-    type Gate = BinaryGate;
+    /// Version 3 added the public inputs. Self-describing formats, like JSON,
+    /// read payloads of versions 1 and 2 with this too.
+    #[derive(Deserialize)]
+    #[serde(rename = "BinaryCircuit")]
+    pub(super) struct BinaryCircuitV3 {
+        #[serde(default = "unversioned")]
+        version: u16,
+        gates: Vec<BinaryGate>,
+        garbler_input_refs: Vec<CircuitRef>,
+        evaluator_input_refs: Vec<CircuitRef>,
+        const_refs: Vec<CircuitRef>,
+        output_refs: Vec<CircuitRef>,
+        num_nonfree_gates: usize,
+        #[serde(default)]
+        output_groups: Vec<usize>,
+        #[serde(default)]
+        public_input_refs: Vec<CircuitRef>,
+    }
 
-    fn new(ngates: Option<usize>) -> Self {
-        let gates = Vec::with_capacity(ngates.unwrap_or(0));
-        Self {
-            gates,
-            garbler_input_refs: Vec::new(),
-            evaluator_input_refs: Vec::new(),
-            const_refs: Vec::new(),
-            output_refs: Vec::new(),
-            num_nonfree_gates: 0,
+    impl From<BinaryCircuitV2> for BinaryCircuitV3 {
+        fn from(c: BinaryCircuitV2) -> Self {
+            BinaryCircuitV3 {
+                version: c.version,
+                gates: c.gates,
+                garbler_input_refs: c.garbler_input_refs,
+                evaluator_input_refs: c.evaluator_input_refs,
+                const_refs: c.const_refs,
+                output_refs: c.output_refs,
+                num_nonfree_gates: c.num_nonfree_gates,
+                output_groups: c.output_groups,
+                public_input_refs: Vec::new(),
+            }
         }
     }
 
-    fn push_gates(&mut self, gate: Self::Gate) {
-        self.gates.push(gate)
+    impl From<BinaryCircuitV1> for BinaryCircuitV2 {
+        fn from(c: BinaryCircuitV1) -> Self {
+            BinaryCircuitV2 {
+                version: c.version,
+                gates: c.gates,
+                garbler_input_refs: c.garbler_input_refs,
+                evaluator_input_refs: c.evaluator_input_refs,
+                const_refs: c.const_refs,
+                output_refs: c.output_refs,
+                num_nonfree_gates: c.num_nonfree_gates,
+                output_groups: Vec::new(),
+            }
+        }
     }
 
-    fn push_const_ref(&mut self, xref: CircuitRef) {
-        self.const_refs.push(xref)
+    impl Serialize for ArithmeticCircuit {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            ArithmeticCircuitRef {
+                version: VERSION,
+                gates: &self.gates,
+                gate_moduli: &self.gate_moduli,
+                garbler_input_refs: &self.garbler_input_refs,
+                evaluator_input_refs: &self.evaluator_input_refs,
+                const_refs: &self.const_refs,
+                output_refs: &self.output_refs,
+                num_nonfree_gates: self.num_nonfree_gates,
+                output_groups: &self.output_groups,
+                public_input_refs: &self.public_input_refs,
+            }
+            .serialize(serializer)
+        }
     }
 
-    fn push_output_ref(&mut self, xref: CircuitRef) {
-        self.output_refs.push(xref)
-    }
+    impl TryFrom<ArithmeticCircuitV1> for ArithmeticCircuit {
+        type Error = CircuitValidationError;
 
-    fn push_garbler_input_ref(&mut self, xref: CircuitRef) {
-        self.garbler_input_refs.push(xref)
+        fn try_from(c: ArithmeticCircuitV1) -> Result<Self, Self::Error> {
+            ArithmeticCircuit::try_from(ArithmeticCircuitV2::from(c))
+        }
     }
 
-    fn push_modulus(&mut self, modulus: u16) {
-        assert_eq!(modulus, 2);
+    impl TryFrom<ArithmeticCircuitV2> for ArithmeticCircuit {
+        type Error = CircuitValidationError;
+
+        fn try_from(c: ArithmeticCircuitV2) -> Result<Self, Self::Error> {
+            ArithmeticCircuit::try_from(ArithmeticCircuitV3::from(c))
+        }
     }
 
-    fn push_evaluator_input_ref(&mut self, xref: CircuitRef) {
-        self.evaluator_input_refs.push(xref)
+    impl TryFrom<ArithmeticCircuitV3> for ArithmeticCircuit {
+        type Error = CircuitValidationError;
+
+        fn try_from(c: ArithmeticCircuitV3) -> Result<Self, Self::Error> {
+            if !(1..=VERSION).contains(&c.version) {
+                return Err(CircuitValidationError::UnsupportedVersion(c.version));
+            }
+            let circuit = ArithmeticCircuit {
+                gates: c.gates,
+                gate_moduli: c.gate_moduli,
+                garbler_input_refs: c.garbler_input_refs,
+                evaluator_input_refs: c.evaluator_input_refs,
+                const_refs: c.const_refs,
+                output_refs: c.output_refs,
+                num_nonfree_gates: c.num_nonfree_gates,
+                output_groups: c.output_groups,
+                public_input_refs: c.public_input_refs,
+                digest: DigestCache::default(),
+            };
+            circuit.validate()?;
+            Ok(circuit)
+        }
     }
 
-    fn increment_nonfree_gates(&mut self) {
-        self.num_nonfree_gates += 1;
+    impl<'de> Deserialize<'de> for ArithmeticCircuit {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let c = ArithmeticCircuitV3::deserialize(deserializer)?;
+            ArithmeticCircuit::try_from(c).map_err(D::Error::custom)
+        }
     }
 
-    fn get_num_nonfree_gates(&self) -> usize {
-        self.num_nonfree_gates
+    impl Serialize for BinaryCircuit {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if !self.custom_gates.as_slice().is_empty() {
+                return Err(serde::ser::Error::custom(
+                    "circuits with custom gates cannot be serialized",
+                ));
+            }
+            BinaryCircuitRef {
+                version: VERSION,
+                gates: &self.gates,
+                garbler_input_refs: &self.garbler_input_refs,
+                evaluator_input_refs: &self.evaluator_input_refs,
+                const_refs: &self.const_refs,
+                output_refs: &self.output_refs,
+                num_nonfree_gates: self.num_nonfree_gates,
+                output_groups: &self.output_groups,
+                public_input_refs: &self.public_input_refs,
+            }
+            .serialize(serializer)
+        }
     }
 
-    fn get_output_refs(&self) -> &[CircuitRef] {
-        &self.output_refs
+    impl TryFrom<BinaryCircuitV1> for BinaryCircuit {
+        type Error = CircuitValidationError;
+
+        fn try_from(c: BinaryCircuitV1) -> Result<Self, Self::Error> {
+            BinaryCircuit::try_from(BinaryCircuitV2::from(c))
+        }
     }
 
-    fn get_garbler_input_refs(&self) -> &[CircuitRef] {
-        &self.garbler_input_refs
+    impl TryFrom<BinaryCircuitV2> for BinaryCircuit {
+        type Error = CircuitValidationError;
+
+        fn try_from(c: BinaryCircuitV2) -> Result<Self, Self::Error> {
+            BinaryCircuit::try_from(BinaryCircuitV3::from(c))
+        }
     }
 
-    fn get_evaluator_input_refs(&self) -> &[CircuitRef] {
-        &self.evaluator_input_refs
+    impl TryFrom<BinaryCircuitV3> for BinaryCircuit {
+        type Error = CircuitValidationError;
+
+        fn try_from(c: BinaryCircuitV3) -> Result<Self, Self::Error> {
+            if !(1..=VERSION).contains(&c.version) {
+                return Err(CircuitValidationError::UnsupportedVersion(c.version));
+            }
+            let circuit = BinaryCircuit {
+                gates: c.gates,
+                garbler_input_refs: c.garbler_input_refs,
+                evaluator_input_refs: c.evaluator_input_refs,
+                const_refs: c.const_refs,
+                output_refs: c.output_refs,
+                num_nonfree_gates: c.num_nonfree_gates,
+                output_groups: c.output_groups,
+                public_input_refs: c.public_input_refs,
+                custom_gates: CustomGates::default(),
+                digest: DigestCache::default(),
+            };
+            circuit.validate()?;
+            Ok(circuit)
+        }
     }
 
-    fn garbler_input_mod(&self, _: usize) -> u16 {
-        2
+    impl<'de> Deserialize<'de> for BinaryCircuit {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let c = BinaryCircuitV3::deserialize(deserializer)?;
+            BinaryCircuit::try_from(c).map_err(D::Error::custom)
+        }
     }
+}
 
-    fn evaluator_input_mod(&self, _: usize) -> u16 {
-        2
+/// The new id of each of the `ninputs` inputs of a party once those in
+/// `public` are made public, or `None` for those, failing with the error
+/// `missing` makes if an id in `public` is not that of an input.
+fn kept_input_ids(
+    public: &[usize],
+    ninputs: usize,
+    missing: impl Fn(usize, usize) -> FancyError,
+) -> Result<Vec<Option<usize>>, FancyError> {
+    let mut ids = vec![Some(0); ninputs];
+    for &id in public {
+        *ids.get_mut(id)
+            .ok_or_else(|| missing(ninputs, id.saturating_add(1)))? = None;
     }
+    for (k, id) in ids.iter_mut().flatten().enumerate() {
+        *id = k;
+    }
+    Ok(ids)
 }
 
-impl CircuitType for ArithmeticCircuit {
-    type Gate = ArithmeticGate;
+/// The refs of the inputs of a party kept by [`kept_input_ids`].
+fn kept_refs(refs: &[CircuitRef], ids: &[Option<usize>]) -> Vec<CircuitRef> {
+    refs.iter()
+        .zip(ids)
+        .filter(|(_, id)| id.is_some())
+        .map(|(r, _)| *r)
+        .collect()
+}
 
-    fn new(ngates: Option<usize>) -> ArithmeticCircuit {
-        let gates = Vec::with_capacity(ngates.unwrap_or(0));
-        ArithmeticCircuit {
-            gates,
-            garbler_input_refs: Vec::new(),
-            evaluator_input_refs: Vec::new(),
-            const_refs: Vec::new(),
-            output_refs: Vec::new(),
-            gate_moduli: Vec::new(),
-            num_nonfree_gates: 0,
-        }
-    }
+/// Check that the output groups of `circuit` are no wider than its outputs.
+fn check_output_groups<C: CircuitType>(circuit: &C) -> Result<(), CircuitValidationError> {
+    check_output_widths(circuit.get_output_groups(), circuit.noutputs())
+}
 
-    fn push_gates(&mut self, gate: Self::Gate) {
-        self.gates.push(gate)
+/// Check that output groups of the given widths fit in `noutputs` outputs.
+fn check_output_widths(widths: &[usize], noutputs: usize) -> Result<(), CircuitValidationError> {
+    let width = widths
+        .iter()
+        .try_fold(0usize, |sum, &w| sum.checked_add(w))
+        .unwrap_or(usize::MAX);
+    if width <= noutputs {
+        Ok(())
+    } else {
+        Err(CircuitValidationError::OutputGroupsTooWide { width, noutputs })
     }
+}
 
-    fn push_const_ref(&mut self, xref: CircuitRef) {
-        self.const_refs.push(xref)
+/// Check that input gate `gate` refers to one of `ninputs` inputs.
+fn check_input(gate: usize, id: usize, ninputs: usize) -> Result<(), CircuitValidationError> {
+    if id < ninputs {
+        Ok(())
+    } else {
+        Err(CircuitValidationError::InputOutOfRange { gate, id })
     }
+}
 
-    fn push_output_ref(&mut self, xref: CircuitRef) {
-        self.output_refs.push(xref)
+/// Check that constant gate `gate` has a value less than its modulus `q`.
+fn check_constant_gate(
+    gate: usize,
+    val: Modulus,
+    q: Modulus,
+) -> Result<(), CircuitValidationError> {
+    if val < q {
+        Ok(())
+    } else {
+        Err(CircuitValidationError::ConstantOutOfRange {
+            gate,
+            value: val,
+            modulus: q,
+        })
     }
+}
 
-    fn push_garbler_input_ref(&mut self, xref: CircuitRef) {
-        self.garbler_input_refs.push(xref)
+/// Check, while evaluating a circuit, that constant gate `gate` has a value
+/// less than its modulus `q`.
+///
+/// Such constants are rejected rather than reduced, by validation and by
+/// every evaluation of a circuit, so that `eval_plain` and the garbler agree.
+pub(crate) fn check_constant(gate: usize, val: Modulus, q: Modulus) -> Result<(), FancyError> {
+    if val < q {
+        Ok(())
+    } else {
+        Err(FancyError::ConstantOutOfRange {
+            gate,
+            value: val,
+            modulus: q,
+        })
     }
+}
 
-    fn push_modulus(&mut self, modulus: u16) {
-        self.gate_moduli.push(modulus)
+/// Check that there is exactly one input wire for each input of `c`, with
+/// the modulus of the input.
+pub(crate) fn check_input_wires<C: CircuitType, T: HasModulus>(
+    c: &C,
+    garbler_inputs: &[T],
+    evaluator_inputs: &[T],
+    public_inputs: &[T],
+) -> Result<(), FancyError> {
+    let (ngarbler, nevaluator) = (garbler_inputs.len(), evaluator_inputs.len());
+    if ngarbler < c.num_garbler_inputs() {
+        return Err(FancyError::NotEnoughGarblerInputs {
+            got: ngarbler,
+            needed: c.num_garbler_inputs(),
+        });
     }
-
-    fn push_evaluator_input_ref(&mut self, xref: CircuitRef) {
-        self.evaluator_input_refs.push(xref)
+    if ngarbler > c.num_garbler_inputs() {
+        return Err(FancyError::TooManyGarblerInputs {
+            got: ngarbler,
+            needed: c.num_garbler_inputs(),
+        });
+    }
+    if nevaluator < c.num_evaluator_inputs() {
+        return Err(FancyError::NotEnoughEvaluatorInputs {
+            got: nevaluator,
+            needed: c.num_evaluator_inputs(),
+        });
+    }
+    if nevaluator > c.num_evaluator_inputs() {
+        return Err(FancyError::TooManyEvaluatorInputs {
+            got: nevaluator,
+            needed: c.num_evaluator_inputs(),
+        });
     }
+    let npublic = public_inputs.len();
+    if npublic < c.num_public_inputs() {
+        return Err(FancyError::NotEnoughPublicInputs {
+            got: npublic,
+            needed: c.num_public_inputs(),
+        });
+    }
+    if npublic > c.num_public_inputs() {
+        return Err(FancyError::TooManyPublicInputs {
+            got: npublic,
+            needed: c.num_public_inputs(),
+        });
+    }
+    let gb = c.get_garbler_input_refs().iter().zip(garbler_inputs);
+    if let Some((id, (r, x))) = gb.enumerate().find(|(_, (r, x))| r.modulus != x.modulus()) {
+        return Err(FancyError::GarblerInputModulus {
+            id,
+            got: x.modulus(),
+            needed: r.modulus,
+        });
+    }
+    let ev = c.get_evaluator_input_refs().iter().zip(evaluator_inputs);
+    if let Some((id, (r, x))) = ev.enumerate().find(|(_, (r, x))| r.modulus != x.modulus()) {
+        return Err(FancyError::EvaluatorInputModulus {
+            id,
+            got: x.modulus(),
+            needed: r.modulus,
+        });
+    }
+    let public = c.get_public_input_refs().iter().zip(public_inputs);
+    if let Some((id, (r, x))) = public
+        .enumerate()
+        .find(|(_, (r, x))| r.modulus != x.modulus())
+    {
+        return Err(FancyError::PublicInputModulus {
+            id,
+            got: x.modulus(),
+            needed: r.modulus,
+        });
+    }
+    Ok(())
+}
 
-    fn increment_nonfree_gates(&mut self) {
-        self.num_nonfree_gates += 1;
+/// The wire of garbler input `id`.
+///
+/// Circuits check their input counts up front, but an unvalidated circuit may
+/// still have an input gate with an id beyond them.
+pub(crate) fn garbler_input<T: Clone>(inputs: &[T], id: usize) -> Result<T, FancyError> {
+    inputs
+        .get(id)
+        .cloned()
+        .ok_or(FancyError::NotEnoughGarblerInputs {
+            got: inputs.len(),
+            needed: id.saturating_add(1),
+        })
+}
+
+/// The wire of evaluator input `id`.
+pub(crate) fn evaluator_input<T: Clone>(inputs: &[T], id: usize) -> Result<T, FancyError> {
+    inputs
+        .get(id)
+        .cloned()
+        .ok_or(FancyError::NotEnoughEvaluatorInputs {
+            got: inputs.len(),
+            needed: id.saturating_add(1),
+        })
+}
+
+/// The wire of public input `id`.
+pub(crate) fn public_input<T: Clone>(inputs: &[T], id: usize) -> Result<T, FancyError> {
+    inputs
+        .get(id)
+        .cloned()
+        .ok_or(FancyError::NotEnoughPublicInputs {
+            got: inputs.len(),
+            needed: id.saturating_add(1),
+        })
+}
+
+/// Tracks the moduli of the wires assigned so far while validating a circuit.
+struct WireCheck {
+    // the modulus of each wire, or zero if it has not been assigned.
+    moduli: Vec<Modulus>,
+}
+
+impl WireCheck {
+    fn new(nwires: usize) -> Self {
+        WireCheck {
+            moduli: vec![0; nwires],
+        }
     }
 
-    fn get_num_nonfree_gates(&self) -> usize {
-        self.num_nonfree_gates
+    fn read(&self, r: CircuitRef) -> Result<(), CircuitValidationError> {
+        match self.moduli.get(r.ix) {
+            None => Err(CircuitValidationError::RefOutOfRange {
+                ix: r.ix,
+                nwires: self.moduli.len(),
+            }),
+            Some(0) => Err(CircuitValidationError::UndefinedRef(r.ix)),
+            Some(&q) if q != r.modulus => Err(CircuitValidationError::ModulusMismatch {
+                ix: r.ix,
+                got: r.modulus,
+                needed: q,
+            }),
+            Some(_) => Ok(()),
+        }
     }
 
-    fn get_output_refs(&self) -> &[CircuitRef] {
-        &self.output_refs
+    fn read_all(&self, refs: &[CircuitRef]) -> Result<(), CircuitValidationError> {
+        refs.iter().try_for_each(|&r| self.read(r))
     }
 
-    fn get_garbler_input_refs(&self) -> &[CircuitRef] {
-        &self.garbler_input_refs
+    fn write(&mut self, ix: usize, q: Modulus) -> Result<(), CircuitValidationError> {
+        let nwires = self.moduli.len();
+        let modulus = self
+            .moduli
+            .get_mut(ix)
+            .ok_or(CircuitValidationError::RefOutOfRange { ix, nwires })?;
+        *modulus = q;
+        Ok(())
     }
+}
 
-    fn get_evaluator_input_refs(&self) -> &[CircuitRef] {
-        &self.evaluator_input_refs
+/// Maximum number of gates stored per chunk of a `GateArena`.
+const GATE_CHUNK_SIZE: usize = 1 << 16;
+
+/// Append-only gate storage used while building a circuit.
+///
+/// Gates are stored in chunks which are never reallocated, so growing the arena
+/// never copies previously pushed gates. Each gate is moved exactly once, when
+/// the arena is drained into the finished circuit.
+struct GateArena<T> {
+    chunks: Vec<Vec<T>>,
+    len: usize,
+}
+
+impl<T> GateArena<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        let chunks = if capacity == 0 {
+            Vec::new()
+        } else {
+            vec![Vec::with_capacity(capacity)]
+        };
+        GateArena { chunks, len: 0 }
     }
 
-    fn garbler_input_mod(&self, i: usize) -> u16 {
-        let r = self.garbler_input_refs[i];
-        r.modulus()
+    fn push(&mut self, x: T) {
+        match self.chunks.last_mut() {
+            Some(chunk) if chunk.len() < chunk.capacity() => chunk.push(x),
+            _ => {
+                // Chunks grow geometrically up to `GATE_CHUNK_SIZE`.
+                let mut chunk = Vec::with_capacity(self.len.clamp(64, GATE_CHUNK_SIZE));
+                chunk.push(x);
+                self.chunks.push(chunk);
+            }
+        }
+        self.len += 1;
     }
 
-    fn evaluator_input_mod(&self, i: usize) -> u16 {
-        let r = self.evaluator_input_refs[i];
-        r.modulus()
+    fn len(&self) -> usize {
+        self.len
     }
-}
 
-impl ArithmeticCircuit {
-    /// Return the modulus of the gate indexed by `i`.
-    #[inline]
-    pub fn modulus(&self, i: usize) -> u16 {
-        self.gate_moduli[i]
+    fn into_iter(self) -> impl Iterator<Item = T> {
+        self.chunks.into_iter().flatten()
     }
 }
 
 /// CircuitBuilder is used to build circuits.
-pub struct CircuitBuilder<Circuit> {
+pub struct CircuitBuilder<Circuit: CircuitType> {
     next_ref_ix: usize,
     next_garbler_input_id: usize,
     next_evaluator_input_id: usize,
-    const_map: HashMap<(u16, u16), CircuitRef>,
+    next_public_input_id: usize,
+    const_map: HashMap<(Modulus, Modulus), CircuitRef>,
+    gates: GateArena<(Circuit::Gate, Modulus)>,
     circ: Circuit,
 }
 
 impl FancyBinary for CircuitBuilder<BinaryCircuit> {
     fn xor(&mut self, xref: &Self::Item, yref: &Self::Item) -> Result<Self::Item, Self::Error> {
+        check_binary!(xref);
+        check_binary!(yref);
         let gate = BinaryGate::Xor {
             xref: *xref,
             yref: *yref,
@@ -752,6 +3563,7 @@ impl FancyBinary for CircuitBuilder<BinaryCircuit> {
     }
 
     fn negate(&mut self, xref: &Self::Item) -> Result<Self::Item, Self::Error> {
+        check_binary!(xref);
         let gate = BinaryGate::Inv {
             xref: *xref,
             out: None,
@@ -760,6 +3572,8 @@ impl FancyBinary for CircuitBuilder<BinaryCircuit> {
     }
 
     fn and(&mut self, xref: &Self::Item, yref: &Self::Item) -> Result<Self::Item, Self::Error> {
+        check_binary!(xref);
+        check_binary!(yref);
         let gate = BinaryGate::And {
             xref: *xref,
             yref: *yref,
@@ -769,6 +3583,26 @@ impl FancyBinary for CircuitBuilder<BinaryCircuit> {
 
         Ok(self.gate(gate, xref.modulus()))
     }
+
+    /// Record the gate, registering it on the circuit if no gate of its name
+    /// is.
+    fn custom(
+        &mut self,
+        gate: &Arc<dyn CustomGate>,
+        inputs: &[Self::Item],
+    ) -> Result<Self::Item, Self::Error> {
+        check_arity(gate.as_ref(), inputs.len())?;
+        for x in inputs {
+            check_binary!(x);
+        }
+        let gate = BinaryGate::Custom {
+            gate: self.circ.custom_gates.register(gate),
+            inputs: inputs.to_vec(),
+            id: self.get_next_ciphertext_id(),
+            out: None,
+        };
+        Ok(self.gate(gate, 2))
+    }
 }
 
 // We can construct an arithmetic circuit out of binary operations
@@ -776,9 +3610,7 @@ derive_binary!(CircuitBuilder<ArithmeticCircuit>);
 
 impl FancyArithmetic for CircuitBuilder<ArithmeticCircuit> {
     fn add(&mut self, xref: &CircuitRef, yref: &CircuitRef) -> Result<CircuitRef, Self::Error> {
-        if xref.modulus() != yref.modulus() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
-        }
+        check_moduli("add", xref.modulus(), yref.modulus())?;
         let gate = ArithmeticGate::Add {
             xref: *xref,
             yref: *yref,
@@ -788,9 +3620,7 @@ impl FancyArithmetic for CircuitBuilder<ArithmeticCircuit> {
     }
 
     fn sub(&mut self, xref: &CircuitRef, yref: &CircuitRef) -> Result<CircuitRef, Self::Error> {
-        if xref.modulus() != yref.modulus() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
-        }
+        check_moduli("sub", xref.modulus(), yref.modulus())?;
         let gate = ArithmeticGate::Sub {
             xref: *xref,
             yref: *yref,
@@ -799,7 +3629,7 @@ impl FancyArithmetic for CircuitBuilder<ArithmeticCircuit> {
         Ok(self.gate(gate, xref.modulus()))
     }
 
-    fn cmul(&mut self, xref: &CircuitRef, c: u16) -> Result<CircuitRef, Self::Error> {
+    fn cmul(&mut self, xref: &CircuitRef, c: Modulus) -> Result<CircuitRef, Self::Error> {
         Ok(self.gate(
             ArithmeticGate::Cmul {
                 xref: *xref,
@@ -813,8 +3643,8 @@ impl FancyArithmetic for CircuitBuilder<ArithmeticCircuit> {
     fn proj(
         &mut self,
         xref: &CircuitRef,
-        output_modulus: u16,
-        tt: Option<Vec<u16>>,
+        output_modulus: Modulus,
+        tt: Option<Vec<Modulus>>,
     ) -> Result<CircuitRef, Self::Error> {
         let tt = tt.ok_or_else(|| Self::Error::from(FancyError::NoTruthTable))?;
         if tt.len() < xref.modulus() as usize || !tt.iter().all(|&x| x < output_modulus) {
@@ -849,10 +3679,11 @@ impl<Circuit: CircuitType> Fancy for CircuitBuilder<Circuit> {
     type Item = CircuitRef;
     type Error = CircuitBuilderError;
 
-    fn constant(&mut self, val: u16, modulus: u16) -> Result<CircuitRef, Self::Error> {
+    fn constant(&mut self, val: Modulus, modulus: Modulus) -> Result<CircuitRef, Self::Error> {
         match self.const_map.get(&(val, modulus)) {
             Some(&r) => Ok(r),
             None => {
+                check_constant(self.next_ref_ix, val, modulus)?;
                 let gate = Circuit::Gate::make_constant(val);
                 let r = self.gate(gate, modulus);
                 self.const_map.insert((val, modulus), r);
@@ -862,8 +3693,7 @@ impl<Circuit: CircuitType> Fancy for CircuitBuilder<Circuit> {
         }
     }
 
-    fn output(&mut self, xref: &CircuitRef) -> Result<Option<u16>, Self::Error> {
-        println!("output called");
+    fn output(&mut self, xref: &CircuitRef) -> Result<Option<Modulus>, Self::Error> {
         self.circ.push_output_ref(*xref);
         Ok(None)
     }
@@ -876,14 +3706,41 @@ impl<Circuit: CircuitType> CircuitBuilder<Circuit> {
             next_ref_ix: 0,
             next_garbler_input_id: 0,
             next_evaluator_input_id: 0,
+            next_public_input_id: 0,
             const_map: HashMap::new(),
+            gates: GateArena::with_capacity(0),
             circ: Circuit::new(None),
         }
     }
 
+    /// Make a new `CircuitBuilder`, preallocating space for `ngates` gates and
+    /// `nwires` input and output wires.
+    ///
+    /// The hints only affect performance: the resulting circuit is identical to
+    /// one built using [`CircuitBuilder::new`].
+    pub fn with_capacity(ngates: usize, nwires: usize) -> Self {
+        let mut circ = Circuit::new(None);
+        circ.reserve_wires(nwires);
+        CircuitBuilder {
+            next_ref_ix: 0,
+            next_garbler_input_id: 0,
+            next_evaluator_input_id: 0,
+            next_public_input_id: 0,
+            const_map: HashMap::new(),
+            gates: GateArena::with_capacity(ngates),
+            circ,
+        }
+    }
+
     /// Finish circuit building, outputting the resulting circuit.
     pub fn finish(self) -> Circuit {
-        self.circ
+        let mut circ = self.circ;
+        circ.reserve_gates(self.gates.len());
+        for (gate, modulus) in self.gates.into_iter() {
+            circ.push_gates(gate);
+            circ.push_modulus(modulus);
+        }
+        circ
     }
 
     fn get_next_garbler_input_id(&mut self) -> usize {
@@ -898,6 +3755,12 @@ impl<Circuit: CircuitType> CircuitBuilder<Circuit> {
         current
     }
 
+    fn get_next_public_input_id(&mut self) -> usize {
+        let current = self.next_public_input_id;
+        self.next_public_input_id += 1;
+        current
+    }
+
     fn get_next_ciphertext_id(&mut self) -> usize {
         let current = self.circ.get_num_nonfree_gates();
         self.circ.increment_nonfree_gates();
@@ -910,15 +3773,14 @@ impl<Circuit: CircuitType> CircuitBuilder<Circuit> {
         current
     }
 
-    fn gate(&mut self, gate: Circuit::Gate, modulus: u16) -> CircuitRef {
-        self.circ.push_gates(gate);
-        self.circ.push_modulus(modulus);
+    fn gate(&mut self, gate: Circuit::Gate, modulus: Modulus) -> CircuitRef {
+        self.gates.push((gate, modulus));
         let ix = self.get_next_ref_ix();
         CircuitRef { ix, modulus }
     }
 
     /// Get CircuitRef for a garbler input wire.
-    pub fn garbler_input(&mut self, modulus: u16) -> CircuitRef {
+    pub fn garbler_input(&mut self, modulus: Modulus) -> CircuitRef {
         let id = self.get_next_garbler_input_id();
         let r = self.gate(Circuit::Gate::make_garbler_input(id), modulus);
         self.circ.push_garbler_input_ref(r);
@@ -926,21 +3788,44 @@ impl<Circuit: CircuitType> CircuitBuilder<Circuit> {
     }
 
     /// Get CircuitRef for an evaluator input wire.
-    pub fn evaluator_input(&mut self, modulus: u16) -> CircuitRef {
+    pub fn evaluator_input(&mut self, modulus: Modulus) -> CircuitRef {
         let id = self.get_next_evaluator_input_id();
         let r = self.gate(Circuit::Gate::make_evaluator_input(id), modulus);
         self.circ.push_evaluator_input_ref(r);
         r
     }
 
+    /// Get CircuitRef for a public input wire, whose value both parties know.
+    ///
+    /// See [`EvaluableCircuit::eval_with_public`].
+    pub fn public_input(&mut self, modulus: Modulus) -> CircuitRef {
+        let id = self.get_next_public_input_id();
+        let r = self.gate(Circuit::Gate::make_public_input(id), modulus);
+        self.circ.push_public_input_ref(r);
+        r
+    }
+
     /// Get a vec of CircuitRefs for garbler inputs.
-    pub fn garbler_inputs(&mut self, mods: &[u16]) -> Vec<CircuitRef> {
-        mods.iter().map(|q| self.garbler_input(*q)).collect()
+    pub fn garbler_inputs(&mut self, mods: &[Modulus]) -> Vec<CircuitRef> {
+        self.garbler_inputs_iter(mods.iter().copied())
     }
 
     /// Get a vec of CircuitRefs for garbler inputs.
-    pub fn evaluator_inputs(&mut self, mods: &[u16]) -> Vec<CircuitRef> {
-        mods.iter().map(|q| self.evaluator_input(*q)).collect()
+    pub fn evaluator_inputs(&mut self, mods: &[Modulus]) -> Vec<CircuitRef> {
+        self.evaluator_inputs_iter(mods.iter().copied())
+    }
+
+    /// Get a vec of CircuitRefs for public inputs.
+    pub fn public_inputs(&mut self, mods: &[Modulus]) -> Vec<CircuitRef> {
+        mods.iter().map(|&q| self.public_input(q)).collect()
+    }
+
+    fn garbler_inputs_iter(&mut self, mods: impl Iterator<Item = Modulus>) -> Vec<CircuitRef> {
+        mods.map(|q| self.garbler_input(q)).collect()
+    }
+
+    fn evaluator_inputs_iter(&mut self, mods: impl Iterator<Item = Modulus>) -> Vec<CircuitRef> {
+        mods.map(|q| self.evaluator_input(q)).collect()
     }
 
     /// Get a CrtBundle for the garbler using composite modulus Q
@@ -955,12 +3840,61 @@ impl<Circuit: CircuitType> CircuitBuilder<Circuit> {
 
     /// Get a BinaryBundle for the garbler with n bits.
     pub fn bin_garbler_input(&mut self, nbits: usize) -> BinaryBundle<CircuitRef> {
-        BinaryBundle::new(self.garbler_inputs(&vec![2; nbits]))
+        BinaryBundle::new(self.garbler_inputs_iter((0..nbits).map(|_| 2)))
     }
 
     /// Get a BinaryBundle for the evaluator with n bits.
     pub fn bin_evaluator_input(&mut self, nbits: usize) -> BinaryBundle<CircuitRef> {
-        BinaryBundle::new(self.evaluator_inputs(&vec![2; nbits]))
+        BinaryBundle::new(self.evaluator_inputs_iter((0..nbits).map(|_| 2)))
+    }
+
+    /// Get a BinaryBundle for a public input with n bits.
+    pub fn bin_public_input(&mut self, nbits: usize) -> BinaryBundle<CircuitRef> {
+        BinaryBundle::new(self.public_inputs(&vec![2; nbits]))
+    }
+
+    /// Get a BinaryBundle for the garbler with n bits, which the garbler inputs
+    /// in `order`.
+    pub fn bin_garbler_input_ordered(
+        &mut self,
+        nbits: usize,
+        order: BitOrder,
+    ) -> BinaryBundle<CircuitRef> {
+        BinaryBundle::from_order(self.garbler_inputs_iter((0..nbits).map(|_| 2)), order)
+    }
+
+    /// Get a BinaryBundle for the evaluator with n bits, which the evaluator
+    /// inputs in `order`.
+    pub fn bin_evaluator_input_ordered(
+        &mut self,
+        nbits: usize,
+        order: BitOrder,
+    ) -> BinaryBundle<CircuitRef> {
+        BinaryBundle::from_order(self.evaluator_inputs_iter((0..nbits).map(|_| 2)), order)
+    }
+
+    /// Output the bits of `x` in `order`.
+    pub fn bin_output_ordered(
+        &mut self,
+        x: &BinaryBundle<CircuitRef>,
+        order: BitOrder,
+    ) -> Result<(), CircuitBuilderError> {
+        self.outputs(&x.wires_in(order)).map(|_| ())
+    }
+
+    /// Output `xs` as a group of their own; see [`CircuitType::output_widths`].
+    ///
+    /// Outputs made by other methods since the last group form a group before
+    /// this one.
+    pub fn output_group(&mut self, xs: &[CircuitRef]) -> Result<(), CircuitBuilderError> {
+        let grouped = self.circ.get_output_groups().iter().sum::<usize>();
+        if grouped < self.circ.noutputs() {
+            let width = self.circ.noutputs() - grouped;
+            self.circ.push_output_group(width);
+        }
+        self.outputs(xs)?;
+        self.circ.push_output_group(xs.len());
+        Ok(())
     }
 }
 
@@ -983,9 +3917,9 @@ mod plaintext {
         let c = b.finish();
 
         for _ in 0..16 {
-            let mut inps: Vec<u16> = Vec::new();
+            let mut inps: Vec<Modulus> = Vec::new();
             for _ in 0..n {
-                inps.push(rng.gen_bool() as u16);
+                inps.push(rng.gen_bool() as Modulus);
             }
             let res = inps.iter().fold(1, |acc, &x| x & acc);
             let out = eval_plain(&c, &[], &inps).unwrap()[0];
@@ -1007,9 +3941,9 @@ mod plaintext {
         let c = b.finish();
 
         for _ in 0..16 {
-            let mut inps: Vec<u16> = Vec::new();
+            let mut inps: Vec<Modulus> = Vec::new();
             for _ in 0..n {
-                inps.push(rng.gen_bool() as u16);
+                inps.push(rng.gen_bool() as Modulus);
             }
             let res = inps.iter().fold(0, |acc, &x| x | acc);
             let out = eval_plain(&c, &[], &inps).unwrap()[0];
@@ -1031,9 +3965,9 @@ mod plaintext {
         let c = b.finish();
 
         for _ in 0..16 {
-            let mut inps: Vec<u16> = Vec::new();
+            let mut inps: Vec<Modulus> = Vec::new();
             for _ in 0..n {
-                inps.push(rng.gen_bool() as u16);
+                inps.push(rng.gen_bool() as Modulus);
             }
             let res = inps.iter().fold(0, |acc, &x| x | acc);
             let out = eval_plain(&c, &[], &inps).unwrap()[0];
@@ -1055,8 +3989,8 @@ mod plaintext {
         b.output(&z).unwrap();
         let c = b.finish();
         for _ in 0..16 {
-            let x = rng.gen_u16() % q;
-            let y = rng.gen_u16() % q;
+            let x = rng.gen_residue(q);
+            let y = rng.gen_residue(q);
             let out = eval_plain(&c, &[x], &[y]).unwrap();
             assert_eq!(out[0], x * y % q);
         }
@@ -1072,8 +4006,8 @@ mod plaintext {
         b.output(&z).unwrap();
         let c = b.finish();
         for _ in 0..16 {
-            let x = rng.gen_u16() % q;
-            let y = rng.gen_u16() % q;
+            let x = rng.gen_residue(q);
+            let y = rng.gen_residue(q);
             let out = eval_plain(&c, &[x], &[y]).unwrap();
             assert_eq!(out[0], x * y % q);
         }
@@ -1091,7 +4025,7 @@ mod plaintext {
         b.output(&z).unwrap();
         let c = b.finish();
         for _ in 0..16 {
-            let x = rng.gen_u16() % p;
+            let x = rng.gen_residue(p);
             let out = eval_plain(&c, &[x], &[]).unwrap();
             assert_eq!(out[0], x % q);
         }
@@ -1104,7 +4038,7 @@ mod plaintext {
         let args = b.garbler_inputs(&vec![2; n]);
         let wires = args
             .iter()
-            .map(|x| b.mod_change(x, n as u16 + 1).unwrap())
+            .map(|x| b.mod_change(x, n as Modulus + 1).unwrap())
             .collect_vec();
         let s = b.add_many(&wires).unwrap();
         b.output(&s).unwrap();
@@ -1113,9 +4047,9 @@ mod plaintext {
         let mut rng = thread_rng();
         for _ in 0..64 {
             let inps = (0..c.num_garbler_inputs())
-                .map(|i| rng.gen_u16() % c.garbler_input_mod(i))
+                .map(|i| rng.gen_residue(c.garbler_input_mod(i)))
                 .collect_vec();
-            let s: u16 = inps.iter().sum();
+            let s: Modulus = inps.iter().sum();
             println!("{:?}, sum={}", inps, s);
             let out = eval_plain(&c, &inps, &[]).unwrap();
             assert_eq!(out[0], s);
@@ -1128,7 +4062,7 @@ mod plaintext {
         let mut rng = thread_rng();
 
         let q = rng.gen_modulus();
-        let c = rng.gen_u16() % q;
+        let c = rng.gen_residue(q);
 
         let x = b.evaluator_input(q);
         let y = b.constant(c, q).unwrap();
@@ -1138,12 +4072,112 @@ mod plaintext {
         let circ = b.finish();
 
         for _ in 0..64 {
-            let x = rng.gen_u16() % q;
+            let x = rng.gen_residue(q);
             let z = eval_plain(&circ, &[], &[x]).unwrap();
             assert_eq!(z[0], (x + c) % q);
         }
     }
     //}}}
+    #[test] // unequal_moduli {{{
+    fn unequal_moduli() {
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(3);
+        let y = b.evaluator_input(5);
+        for (op, res) in [("add", b.add(&x, &y)), ("sub", b.sub(&x, &y))] {
+            match res {
+                Err(CircuitBuilderError::FancyError(FancyError::UnequalModuli {
+                    op: o,
+                    x: 3,
+                    y: 5,
+                })) => assert_eq!(o, op),
+                r => panic!("{} of mod 3 and mod 5 succeeded: {:?}", op, r),
+            }
+        }
+        assert_eq!(b.mul(&x, &y).unwrap().modulus(), 5);
+        assert!(matches!(
+            b.xor(&x, &y),
+            Err(CircuitBuilderError::FancyError(FancyError::InvalidArgMod {
+                got: 3,
+                needed: 2
+            }))
+        ));
+
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.garbler_input(2);
+        let y = b.constant(1, 3).unwrap();
+        assert!(b.xor(&x, &y).is_err());
+        assert!(b.and(&y, &x).is_err());
+        assert!(b.negate(&y).is_err());
+    }
+    //}}}
+    #[test] // out_of_range_inputs {{{
+    fn out_of_range_inputs() {
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.garbler_inputs(&[2; 4]);
+        let ys = b.evaluator_inputs(&[2; 4]);
+        let z = b.and_many(&[xs, ys].concat()).unwrap();
+        b.output(&z).unwrap();
+        let c = b.finish();
+        let liveness = c.liveness();
+
+        assert_eq!(eval_plain(&c, &[1; 4], &[1; 4]).unwrap(), [1]);
+        assert_eq!(eval_plain(&c, &[1, 1, 0, 1], &[1; 4]).unwrap(), [0]);
+        for i in 0..8 {
+            for x in [2, 3, Modulus::MAX] {
+                let mut inps = [1; 8];
+                inps[i] = x;
+                let (gb, ev) = inps.split_at(4);
+                for res in [
+                    eval_plain(&c, gb, ev),
+                    eval_plain_bounded(&c, gb, ev, &liveness),
+                ] {
+                    match res {
+                        Err(DummyError::FancyError(FancyError::InputOutOfRange {
+                            index,
+                            value,
+                            modulus,
+                        })) => assert_eq!((index, value, modulus), (i, x, 2)),
+                        r => panic!("input {} of {} was accepted: {:?}", i, x, r),
+                    }
+                }
+            }
+        }
+    }
+    //}}}
+    #[test] // bytes {{{
+    fn bytes() {
+        // A bitwise XOR of two 16-bit numbers, the same in either order.
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.garbler_inputs(&[2; 16]);
+        let ys = b.evaluator_inputs(&[2; 16]);
+        for (x, y) in xs.iter().zip(&ys) {
+            let z = b.xor(x, y).unwrap();
+            b.output(&z).unwrap();
+        }
+        let c = b.finish();
+        for order in [BitOrder::LsbFirst, BitOrder::MsbFirst] {
+            let out = eval_plain_bytes(&c, &[0x12, 0x34], &[0xf0, 0x0f], order).unwrap();
+            assert_eq!(out, [0xe2, 0x3b]);
+        }
+        assert!(eval_plain_bytes(&c, &[0x12], &[0xf0, 0x0f], BitOrder::MsbFirst).is_err());
+
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.garbler_inputs(&[2; 8]);
+        b.outputs(&xs[..4]).unwrap();
+        match eval_plain_bytes(&b.finish(), &[0xff], &[], BitOrder::MsbFirst) {
+            Err(DummyError::FancyError(FancyError::InvalidArgNum { got: 4, needed: 8 })) => (),
+            r => panic!("four outputs were packed into bytes: {:?}", r),
+        }
+
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let xs = b.garbler_inputs(&[3; 8]);
+        b.outputs(&xs).unwrap();
+        match eval_plain_bytes(&b.finish(), &[0], &[], BitOrder::MsbFirst) {
+            Err(DummyError::FancyError(FancyError::ArgNotBinary)) => (),
+            r => panic!("a mod-3 circuit was evaluated on bytes: {:?}", r),
+        }
+    }
+    //}}}
 }
 
 #[cfg(test)]
@@ -1179,6 +4213,73 @@ mod bundle {
         }
     }
 
+    //}}}
+    #[test] // bundle out of range inputs {{{
+    fn test_out_of_range_inputs() {
+        let q = util::product(&[3, 5, 7]);
+        let mut b = CircuitBuilder::new();
+        let x = b.crt_garbler_input(q);
+        let y = b.crt_evaluator_input(q);
+        let z = b.crt_add(&x, &y).unwrap();
+        b.output_bundle(&z).unwrap();
+        let c: ArithmeticCircuit = b.finish();
+
+        let (x, y) = (crt_factor(100, q), crt_factor(4, q));
+        let res = eval_plain(&c, &x, &y).unwrap();
+        assert_eq!(crt_inv_factor(&res, q), 104);
+
+        for i in 0..6 {
+            let modulus = [3, 5, 7][i % 3];
+            let (mut x, mut y) = (x.clone(), y.clone());
+            let inps = if i < 3 { &mut x } else { &mut y };
+            inps[i % 3] = modulus;
+            match eval_plain(&c, &x, &y) {
+                Err(DummyError::FancyError(FancyError::InputOutOfRange {
+                    index,
+                    value,
+                    modulus: q,
+                })) => assert_eq!((index, value, q), (i, modulus, modulus)),
+                r => panic!("input {} was accepted: {:?}", i, r),
+            }
+        }
+    }
+    //}}}
+    #[test] // wrong number of inputs {{{
+    fn test_wrong_number_of_inputs() {
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.garbler_inputs(&[2; 3]);
+        let ys = b.evaluator_inputs(&[2; 2]);
+        let z = b.xor(&xs[0], &ys[1]).unwrap();
+        b.output(&z).unwrap();
+        let c = b.finish();
+        assert_eq!(eval_plain(&c, &[1, 0, 0], &[0, 1]).unwrap(), [0]);
+
+        for n in [0, 2, 4] {
+            assert!(matches!(
+                eval_plain(&c, &vec![0; n], &[0; 2]),
+                Err(DummyError::NotEnoughGarblerInputs { got, needed: 3 }) if got == n
+            ));
+        }
+        for n in [0, 1, 3] {
+            assert!(matches!(
+                eval_plain(&c, &[0; 3], &vec![0; n]),
+                Err(DummyError::NotEnoughEvaluatorInputs { got, needed: 2 }) if got == n
+            ));
+        }
+        // The counts are checked before the values.
+        assert!(matches!(
+            eval_plain(&c, &[2; 4], &[0; 2]),
+            Err(DummyError::NotEnoughGarblerInputs { .. })
+        ));
+        assert!(matches!(
+            eval_plain(&c, &[0; 3], &[0, 2]),
+            Err(DummyError::FancyError(FancyError::InputOutOfRange {
+                index: 4,
+                value: 2,
+                modulus: 2
+            }))
+        ));
+    }
     //}}}
     #[test] // bundle addition {{{
     fn test_addition() {
@@ -1267,7 +4368,7 @@ mod bundle {
     fn test_cexp() {
         let mut rng = thread_rng();
         let q = util::modulus_with_width(10);
-        let y = rng.gen_u16() % 10;
+        let y = rng.gen_residue(10);
 
         let mut b = CircuitBuilder::new();
         let x = b.crt_garbler_input(q);
@@ -1321,13 +4422,13 @@ mod bundle {
         // lets have at least one test where they are surely equal
         let x = rng.gen_u128() % q;
         let res = eval_plain(&c, &crt_factor(x, q), &crt_factor(x, q)).unwrap();
-        assert_eq!(res, &[(x == x) as u16]);
+        assert_eq!(res, &[(x == x) as Modulus]);
 
         for _ in 0..64 {
             let x = rng.gen_u128() % q;
             let y = rng.gen_u128() % q;
             let res = eval_plain(&c, &crt_factor(x, q), &crt_factor(y, q)).unwrap();
-            assert_eq!(res, &[(x == y) as u16]);
+            assert_eq!(res, &[(x == y) as Modulus]);
         }
     }
     //}}}
@@ -1430,13 +4531,13 @@ mod bundle {
         // lets have at least one test where they are surely equal
         let x = rng.gen_u128() % q / 2;
         let res = eval_plain(&c, &crt_factor(x, q), &crt_factor(x, q)).unwrap();
-        assert_eq!(res, &[(x < x) as u16], "x={}", x);
+        assert_eq!(res, &[(x < x) as Modulus], "x={}", x);
 
         for _ in 0..64 {
             let x = rng.gen_u128() % q / 2;
             let y = rng.gen_u128() % q / 2;
             let res = eval_plain(&c, &crt_factor(x, q), &crt_factor(y, q)).unwrap();
-            assert_eq!(res, &[(x < y) as u16], "x={} y={}", x, y);
+            assert_eq!(res, &[(x < y) as Modulus], "x={} y={}", x, y);
         }
     }
     //}}}
@@ -1489,13 +4590,78 @@ mod bundle {
             let y = rng.gen_u128() % Q;
             println!("x={} y={}", x, y);
             let res_should_be = (x + y) % Q;
-            let carry_should_be = (x + y >= Q) as u16;
+            let carry_should_be = (x + y >= Q) as Modulus;
             let res = eval_plain(&c, &util::u128_to_bits(x, n), &util::u128_to_bits(y, n)).unwrap();
             assert_eq!(util::u128_from_bits(&res[1..]), res_should_be);
             assert_eq!(res[0], carry_should_be);
         }
     }
     //}}}
+    #[test] // binary addition in a chosen bit order {{{
+    fn test_binary_addition_ordered() {
+        let mut rng = thread_rng();
+        let n = 2 + (rng.gen_usize() % 10);
+        let Q = 1 << n as u128;
+
+        for order in [BitOrder::LsbFirst, BitOrder::MsbFirst] {
+            let mut b = CircuitBuilder::<BinaryCircuit>::new();
+            let x = b.bin_garbler_input_ordered(n, order);
+            let y = b.bin_evaluator_input_ordered(n, BitOrder::MsbFirst);
+            let z = b.bin_addition_no_carry(&x, &y).unwrap();
+            b.bin_output_ordered(&z, order).unwrap();
+            let c = b.finish();
+
+            for _ in 0..16 {
+                let x = rng.gen_u128() % Q;
+                let y = rng.gen_u128() % Q;
+                let res = eval_plain(&c, &order.bits(x, n), &BitOrder::MsbFirst.bits(y, n));
+                assert_eq!(order.value(&res.unwrap()), (x + y) % Q);
+            }
+        }
+    }
+    //}}}
+    #[test] // output groups {{{
+    fn test_output_groups() {
+        let mut rng = thread_rng();
+        let n = 2 + (rng.gen_usize() % 10);
+        let Q = 1 << n as u128;
+
+        // An adder outputting its carry, its sum, and its inputs.
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.bin_garbler_input(n);
+        let y = b.bin_evaluator_input(n);
+        let (z, carry) = b.bin_addition(&x, &y).unwrap();
+        b.output(&carry).unwrap();
+        b.output_group(z.wires()).unwrap();
+        b.output_bundle(&x).unwrap();
+        b.output_bundle(&y).unwrap();
+        let mut c = b.finish();
+        assert_eq!(c.output_widths(), [1, n, 2 * n]);
+
+        for _ in 0..16 {
+            let (x, y) = (rng.gen_u128() % Q, rng.gen_u128() % Q);
+            let (xs, ys) = (util::u128_to_bits(x, n), util::u128_to_bits(y, n));
+            let flat = eval_plain(&c, &xs, &ys).unwrap();
+            let groups = eval_plain_grouped(&c, &xs, &ys).unwrap();
+            assert_eq!(groups.concat(), flat);
+            assert_eq!(groups[0], [((x + y) >= Q) as Modulus]);
+            assert_eq!(util::u128_from_bits(&groups[1]), (x + y) % Q);
+            assert_eq!(groups[2], [xs, ys].concat());
+        }
+
+        c.set_output_widths(&[1, n, n, n]).unwrap();
+        assert_eq!(c.output_widths(), [1, n, n, n]);
+        c.set_output_widths(&[]).unwrap();
+        assert_eq!(c.output_widths(), [3 * n + 1]);
+        assert!(matches!(
+            c.set_output_widths(&[3 * n, 2]),
+            Err(CircuitValidationError::OutputGroupsTooWide { width, noutputs })
+                if width == 3 * n + 2 && noutputs == 3 * n + 1
+        ));
+        assert!(c.set_output_widths(&[usize::MAX, 2]).is_err());
+        assert_eq!(c.output_widths(), [3 * n + 1]);
+    }
+    //}}}
     #[test] // binary demux {{{
     fn test_bin_demux() {
         let mut rng = thread_rng();
@@ -1526,4 +4692,820 @@ mod bundle {
         }
     }
     //}}}
+    #[test] // capacity hints {{{
+    fn test_capacity_hint() {
+        fn binary(mut b: CircuitBuilder<BinaryCircuit>) -> BinaryCircuit {
+            let mut x = b.bin_garbler_input(64);
+            let y = b.bin_evaluator_input(64);
+            for _ in 0..400 {
+                x = b.bin_addition_no_carry(&x, &y).unwrap();
+            }
+            b.output_bundle(&x).unwrap();
+            b.finish()
+        }
+
+        fn arithmetic(mut b: CircuitBuilder<ArithmeticCircuit>) -> ArithmeticCircuit {
+            let q = util::modulus_with_width(32);
+            let x = b.crt_garbler_input(q);
+            let y = b.crt_evaluator_input(q);
+            let z = b.crt_mul(&x, &y).unwrap();
+            let z = b.crt_relu(&z, "100%", None).unwrap();
+            b.output_bundle(&z).unwrap();
+            b.finish()
+        }
+
+        let c = binary(CircuitBuilder::new());
+        assert!(c.gates.len() > GATE_CHUNK_SIZE);
+        for (ngates, nwires) in [(0, 0), (1, 1), (c.gates.len(), 192)] {
+            assert_eq!(c, binary(CircuitBuilder::with_capacity(ngates, nwires)));
+        }
+
+        let c = arithmetic(CircuitBuilder::new());
+        for (ngates, nwires) in [(0, 0), (1, 1), (c.gates.len(), 64)] {
+            assert_eq!(c, arithmetic(CircuitBuilder::with_capacity(ngates, nwires)));
+        }
+    }
+    //}}}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod bounded {
+    use super::*;
+    use crate::{
+        classic::garble,
+        fancy::{BundleGadgets, CrtGadgets},
+        util::{self, RngExt},
+        AllWire, WireMod2,
+    };
+    use itertools::Itertools;
+    use rand::thread_rng;
+
+    fn parse(bytes: &'static [u8]) -> BinaryCircuit {
+        BinaryCircuit::parse(std::io::Cursor::new(bytes)).unwrap()
+    }
+
+    fn check_binary(c: &BinaryCircuit) {
+        let mut rng = thread_rng();
+        let liveness = c.liveness();
+        let gb = (0..c.num_garbler_inputs())
+            .map(|_| rng.gen_bool() as Modulus)
+            .collect_vec();
+        let ev = (0..c.num_evaluator_inputs())
+            .map(|_| rng.gen_bool() as Modulus)
+            .collect_vec();
+        let expected = eval_plain(c, &gb, &ev).unwrap();
+        assert_eq!(
+            eval_plain_bounded(c, &gb, &ev, &liveness).unwrap(),
+            expected
+        );
+
+        let (en, gc) = garble::<WireMod2, _>(c).unwrap();
+        let xs = en.encode_garbler_inputs(&gb).unwrap();
+        let ys = en.encode_evaluator_inputs(&ev).unwrap();
+        assert_eq!(gc.eval_bounded(c, &xs, &ys, &liveness).unwrap(), expected);
+    }
+
+    #[test]
+    fn adder64_info() {
+        let c = parse(include_bytes!("../circuits/adder64.txt"));
+        let info = c.info().unwrap();
+        assert_eq!(info.garbler_input_moduli, vec![2; 64]);
+        assert_eq!(info.evaluator_input_moduli, vec![2; 64]);
+        assert_eq!(info.output_moduli, vec![2; 64]);
+        assert_eq!(info.nmuls, 125);
+        assert_eq!(info.nciphertexts, 250);
+    }
+
+    #[test] // aes_128 {{{
+    fn aes_128() {
+        let c = parse(include_bytes!("../circuits/AES-non-expanded.txt"));
+        check_binary(&c);
+        let width = c.liveness().width();
+        assert!(width > 128 && width < c.gates.len() / 20, "width {}", width);
+    }
+    //}}}
+    #[test] // sha256 {{{
+    fn sha256() {
+        let c = parse(include_bytes!("../circuits/sha-256.txt"));
+        check_binary(&c);
+        let width = c.liveness().width();
+        assert!(width > 256 && width < c.gates.len() / 20, "width {}", width);
+    }
+    //}}}
+    #[test] // arithmetic {{{
+    fn arithmetic() {
+        let mut rng = thread_rng();
+        let q = util::modulus_with_width(16);
+        let mut b = CircuitBuilder::new();
+        let x = b.crt_garbler_input(q);
+        let y = b.crt_evaluator_input(q);
+        let z = b.crt_mul(&x, &y).unwrap();
+        b.output_bundle(&z).unwrap();
+        let z = b.crt_sub(&z, &x).unwrap();
+        let z = b.crt_relu(&z, "100%", None).unwrap();
+        b.output_bundle(&z).unwrap();
+        let c = b.finish();
+        let liveness = c.liveness();
+
+        let gb = util::crt(rng.gen_u128() % q, &util::factor(q));
+        let ev = util::crt(rng.gen_u128() % q, &util::factor(q));
+        let expected = eval_plain(&c, &gb, &ev).unwrap();
+        assert_eq!(
+            eval_plain_bounded(&c, &gb, &ev, &liveness).unwrap(),
+            expected
+        );
+
+        let (en, gc) = garble::<AllWire, _>(&c).unwrap();
+        let xs = en.encode_garbler_inputs(&gb).unwrap();
+        let ys = en.encode_evaluator_inputs(&ev).unwrap();
+        assert_eq!(gc.eval_bounded(&c, &xs, &ys, &liveness).unwrap(), expected);
+
+        // liveness of another circuit is rejected
+        let other = parse(include_bytes!("../circuits/adder_32bit.txt")).liveness();
+        assert!(eval_plain_bounded(&c, &gb, &ev, &other).is_err());
+    }
+    //}}}
+    #[test]
+    fn store_is_as_wide_as_the_circuit() {
+        // A long chain of gates over a few inputs, of which only a handful of
+        // values are live at once.
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.bin_garbler_input(8);
+        let mut z = xs.wires()[0];
+        for x in xs.wires().iter().cycle().take(100_000) {
+            z = b.and(&z, x).unwrap();
+        }
+        b.output(&z).unwrap();
+        let c = b.finish();
+        let liveness = c.liveness();
+        assert!(liveness.width() <= 10, "width {}", liveness.width());
+
+        let inputs = vec![1; 8];
+        let (gb, ev) = encode_plain(&c, &inputs, &[]).unwrap();
+        let mut store = LiveStore::new(c.gates.len(), &liveness).unwrap();
+        let outputs = c
+            .eval_with(&mut Dummy::new(), &gb, &ev, &[], &mut store, None)
+            .unwrap();
+        assert_eq!(outputs, Some(vec![1]));
+        // Nothing in the store grows with the number of gates.
+        assert!(
+            store.live.capacity() <= 2 * liveness.width(),
+            "capacity {} for width {}",
+            store.live.capacity(),
+            liveness.width()
+        );
+    }
+}
+
+#[cfg(all(test, feature = "serde", feature = "std"))]
+mod serialization {
+    use super::*;
+    use crate::{
+        fancy::{BinaryGadgets, BundleGadgets},
+        util::RngExt,
+    };
+    use itertools::Itertools;
+    use rand::thread_rng;
+
+    fn adder64() -> BinaryCircuit {
+        let mut b = CircuitBuilder::new();
+        let x = b.bin_garbler_input(64);
+        let y = b.bin_evaluator_input(64);
+        let z = b.bin_addition_no_carry(&x, &y).unwrap();
+        b.output_bundle(&z).unwrap();
+        b.finish()
+    }
+
+    fn aes_128() -> BinaryCircuit {
+        BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap()
+    }
+
+    fn check_eval(c: &BinaryCircuit, d: &BinaryCircuit) {
+        let mut rng = thread_rng();
+        let gb = (0..c.num_garbler_inputs())
+            .map(|_| rng.gen_bool() as Modulus)
+            .collect_vec();
+        let ev = (0..c.num_evaluator_inputs())
+            .map(|_| rng.gen_bool() as Modulus)
+            .collect_vec();
+        assert_eq!(
+            eval_plain(c, &gb, &ev).unwrap(),
+            eval_plain(d, &gb, &ev).unwrap()
+        );
+    }
+
+    #[test] // round trip {{{
+    fn round_trip() {
+        for c in [adder64(), aes_128()] {
+            let bytes = bincode::serialize(&c).unwrap();
+            let d: BinaryCircuit = bincode::deserialize(&bytes).unwrap();
+            assert_eq!(c, d);
+            check_eval(&c, &d);
+
+            let json = serde_json::to_string(&c).unwrap();
+            let d: BinaryCircuit = serde_json::from_str(&json).unwrap();
+            assert_eq!(c, d);
+            check_eval(&c, &d);
+        }
+
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(5);
+        let y = b.evaluator_input(5);
+        let z = b.mul(&x, &y).unwrap();
+        let z = b.proj(&z, 3, Some(vec![0, 1, 2, 0, 1])).unwrap();
+        b.output(&z).unwrap();
+        let c = b.finish();
+        let d: ArithmeticCircuit = bincode::deserialize(&bincode::serialize(&c).unwrap()).unwrap();
+        assert_eq!(c, d);
+    }
+    //}}}
+    #[test] // garbling {{{
+    fn garble_deserialized() {
+        // A cached circuit garbles and evaluates as the circuit it was made
+        // from.
+        let c = aes_128();
+        let d = BinaryCircuit::from_bytes(&c.to_bytes()).unwrap();
+        assert_eq!(c.gates(), d.gates());
+        assert_eq!(c.get_num_nonfree_gates(), d.get_num_nonfree_gates());
+        let mut rng = thread_rng();
+        for _ in 0..4 {
+            let gb = (0..128).map(|_| rng.gen_bool() as Modulus).collect_vec();
+            let ev = (0..128).map(|_| rng.gen_bool() as Modulus).collect_vec();
+            let (en, gc) = crate::classic::garble::<crate::WireMod2, _>(&d).unwrap();
+            let xs = en.encode_garbler_inputs(&gb).unwrap();
+            let ys = en.encode_evaluator_inputs(&ev).unwrap();
+            assert_eq!(
+                gc.eval(&d, &xs, &ys).unwrap(),
+                eval_plain(&c, &gb, &ev).unwrap()
+            );
+        }
+    }
+    //}}}
+    #[test] // output groups {{{
+    fn output_groups() {
+        let mut c = adder64();
+        c.set_output_widths(&[32, 16]).unwrap();
+        assert_eq!(c.output_widths(), [32, 16, 16]);
+        let d = BinaryCircuit::from_bytes(&c.to_bytes()).unwrap();
+        assert_eq!(d.output_widths(), [32, 16, 16]);
+        let json = serde_json::to_value(&c).unwrap();
+        let d: BinaryCircuit = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(c, d);
+
+        // Version 1 lacked the groups, which are left out of the digest.
+        let mut json = json;
+        json.as_object_mut().unwrap().remove("output_groups");
+        json["version"] = 1.into();
+        let d: BinaryCircuit = serde_json::from_value(json).unwrap();
+        assert_eq!(d.output_widths(), [64]);
+        assert_eq!(c.digest(), d.digest());
+        let bytes = d.to_bytes();
+        let (_, payload) = crate::format::decode(
+            &bytes,
+            crate::format::FormatId::BinaryCircuit,
+            format::VERSION..=format::VERSION,
+        )
+        .unwrap();
+        // Drop the empty lists of groups and public inputs.
+        let v1 = &payload[..payload.len() - 16];
+        let v1 = crate::format::encode(crate::format::FormatId::BinaryCircuit, 1, v1);
+        assert_eq!(BinaryCircuit::from_bytes(&v1).unwrap(), d);
+
+        let mut json = serde_json::to_value(&c).unwrap();
+        json["output_groups"] = serde_json::json!([64, 1]);
+        let e = serde_json::from_value::<BinaryCircuit>(json).unwrap_err();
+        assert!(e.to_string().contains("output groups"), "{}", e);
+    }
+    //}}}
+    #[test] // public inputs {{{
+    fn public_inputs() {
+        let mut c = aes_128();
+        c.make_inputs_public(&[0, 5], &[3]).unwrap();
+        let d = BinaryCircuit::from_bytes(&c.to_bytes()).unwrap();
+        assert_eq!(c, d);
+        assert_eq!(d.num_public_inputs(), 3);
+        let json = serde_json::to_value(&c).unwrap();
+        let d: BinaryCircuit = serde_json::from_value(json).unwrap();
+        assert_eq!(c, d);
+
+        // Version 2 lacked the public inputs.
+        let c = adder64();
+        let bytes = c.to_bytes();
+        let (_, payload) = crate::format::decode(
+            &bytes,
+            crate::format::FormatId::BinaryCircuit,
+            format::VERSION..=format::VERSION,
+        )
+        .unwrap();
+        let v2 = &payload[..payload.len() - 8];
+        let v2 = crate::format::encode(crate::format::FormatId::BinaryCircuit, 2, v2);
+        assert_eq!(BinaryCircuit::from_bytes(&v2).unwrap(), c);
+    }
+    //}}}
+    #[test] // versions {{{
+    fn versions() {
+        let c = adder64();
+        let mut json: serde_json::Value = serde_json::to_value(&c).unwrap();
+        assert_eq!(json["version"], format::VERSION);
+
+        // payloads from before the version tag are read as version 1
+        json.as_object_mut().unwrap().remove("version");
+        let d: BinaryCircuit = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(c, d);
+
+        json["version"] = 99.into();
+        let e = serde_json::from_value::<BinaryCircuit>(json).unwrap_err();
+        assert!(e.to_string().contains("version 99"), "{}", e);
+    }
+    //}}}
+    #[test] // corrupted payloads {{{
+    fn corrupted() {
+        let c = adder64();
+        let mut json: serde_json::Value = serde_json::to_value(&c).unwrap();
+        json["output_refs"][0]["ix"] = 1_000_000.into();
+        let v = serde_json::from_value::<format::BinaryCircuitV1>(json.clone()).unwrap();
+        assert!(matches!(
+            BinaryCircuit::try_from(v),
+            Err(CircuitValidationError::RefOutOfRange { ix: 1_000_000, .. })
+        ));
+        assert!(serde_json::from_value::<BinaryCircuit>(json).is_err());
+
+        let mut d = c;
+        d.gates.push(BinaryGate::And {
+            xref: CircuitRef { ix: 0, modulus: 2 },
+            yref: CircuitRef {
+                ix: d.gates.len() + 1,
+                modulus: 2,
+            },
+            id: 0,
+            out: None,
+        });
+        d.gates.push(BinaryGate::Constant { val: 1 });
+        assert!(matches!(
+            d.validate(),
+            Err(CircuitValidationError::UndefinedRef(_))
+        ));
+        let bytes = bincode::serialize(&d).unwrap();
+        assert!(bincode::deserialize::<BinaryCircuit>(&bytes).is_err());
+
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(5);
+        let z = b.proj(&x, 3, Some(vec![0, 1, 2, 0, 1])).unwrap();
+        b.output(&z).unwrap();
+        let c = b.finish();
+        for (corrupt, check) in [
+            (
+                (|c: &mut ArithmeticCircuit| c.output_refs[0].modulus = 5)
+                    as fn(&mut ArithmeticCircuit),
+                (|e: &CircuitValidationError| {
+                    matches!(e, CircuitValidationError::ModulusMismatch { .. })
+                }) as fn(&CircuitValidationError) -> bool,
+            ),
+            (
+                |c| c.gate_moduli[1] = 2,
+                |e| matches!(e, CircuitValidationError::InvalidTruthTable(1)),
+            ),
+            (
+                |c| c.gate_moduli[0] = 1,
+                |e| matches!(e, CircuitValidationError::InvalidModulus { gate: 0, .. }),
+            ),
+            (
+                |c| {
+                    c.gate_moduli.pop();
+                },
+                |e| matches!(e, CircuitValidationError::MissingModuli { .. }),
+            ),
+            (
+                |c| c.gates[0] = ArithmeticGate::GarblerInput { id: 1 },
+                |e| {
+                    matches!(
+                        e,
+                        CircuitValidationError::InputOutOfRange { gate: 0, id: 1 }
+                    )
+                },
+            ),
+        ] {
+            let mut d = c.clone();
+            corrupt(&mut d);
+            let e = d.validate().unwrap_err();
+            assert!(check(&e), "{}", e);
+            let bytes = bincode::serialize(&d).unwrap();
+            assert!(bincode::deserialize::<ArithmeticCircuit>(&bytes).is_err());
+        }
+    }
+    //}}}
+    #[test] // constant_out_of_range {{{
+    fn constant_out_of_range() {
+        use crate::{classic::garble, errors::GarblerError, AllWire, WireMod2};
+
+        fn is_constant_error(e: &FancyError, gate: usize, modulus: Modulus) -> bool {
+            matches!(
+                *e,
+                FancyError::ConstantOutOfRange { gate: g, modulus: q, .. } if g == gate && q == modulus
+            )
+        }
+
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.garbler_input(2);
+        let y = b.constant(1, 2).unwrap();
+        let z = b.xor(&x, &y).unwrap();
+        b.output(&z).unwrap();
+        let c = b.finish();
+        let mut json = serde_json::to_value(&c).unwrap();
+        json["gates"][1]["Constant"]["val"] = 2.into();
+        let v = serde_json::from_value::<format::BinaryCircuitV1>(json.clone()).unwrap();
+        assert!(matches!(
+            BinaryCircuit::try_from(v),
+            Err(CircuitValidationError::ConstantOutOfRange {
+                gate: 1,
+                value: 2,
+                modulus: 2
+            })
+        ));
+        let e = serde_json::from_value::<BinaryCircuit>(json).unwrap_err();
+        assert!(e.to_string().contains("constant gate 1"), "{}", e);
+
+        // The same circuit, built without validation, is rejected by both
+        // plaintext and garbled evaluation.
+        let mut d = c;
+        d.gates[1] = BinaryGate::Constant { val: 2 };
+        assert!(d.validate().is_err());
+        match eval_plain(&d, &[0], &[]) {
+            Err(DummyError::FancyError(e)) => assert!(is_constant_error(&e, 1, 2), "{}", e),
+            r => panic!("expected an out-of-range constant: {:?}", r),
+        }
+        match garble::<WireMod2, _>(&d) {
+            Err(GarblerError::FancyError(e)) => assert!(is_constant_error(&e, 1, 2), "{}", e),
+            r => panic!("expected an out-of-range constant: {:?}", r.map(drop)),
+        }
+
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(5);
+        let y = b.constant(4, 5).unwrap();
+        let z = b.add(&x, &y).unwrap();
+        b.output(&z).unwrap();
+        let c = b.finish();
+        let mut json = serde_json::to_value(&c).unwrap();
+        json["gates"][1]["Constant"]["val"] = 7.into();
+        let v = serde_json::from_value::<format::ArithmeticCircuitV1>(json).unwrap();
+        assert!(matches!(
+            ArithmeticCircuit::try_from(v),
+            Err(CircuitValidationError::ConstantOutOfRange {
+                gate: 1,
+                value: 7,
+                modulus: 5
+            })
+        ));
+        let mut d = c;
+        d.gates[1] = ArithmeticGate::Constant { val: 7 };
+        assert!(d.validate().is_err());
+        assert!(matches!(
+            eval_plain(&d, &[0], &[]),
+            Err(DummyError::FancyError(ref e)) if is_constant_error(e, 1, 5)
+        ));
+        assert!(matches!(
+            garble::<AllWire, _>(&d).map(drop),
+            Err(GarblerError::FancyError(ref e)) if is_constant_error(e, 1, 5)
+        ));
+
+        // The builder refuses to make such a gate.
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        b.garbler_input(5);
+        assert!(matches!(
+            b.constant(5, 5),
+            Err(CircuitBuilderError::FancyError(
+                FancyError::ConstantOutOfRange {
+                    gate: 1,
+                    value: 5,
+                    modulus: 5
+                }
+            ))
+        ));
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        assert!(b.constant(2, 2).is_err());
+    }
+    //}}}
+}
+
+#[cfg(all(test, feature = "std"))]
+mod digest {
+    use super::*;
+
+    fn parse(bytes: &'static [u8]) -> BinaryCircuit {
+        BinaryCircuit::parse(std::io::Cursor::new(bytes)).unwrap()
+    }
+
+    #[test]
+    fn binary() {
+        let c = parse(include_bytes!("../circuits/adder64.txt"));
+        assert_eq!(
+            c.digest(),
+            parse(include_bytes!("../circuits/adder64.txt")).digest()
+        );
+        assert_ne!(
+            c.digest(),
+            parse(include_bytes!("../circuits/adder_32bit.txt")).digest()
+        );
+
+        let mut d = c.clone();
+        d.output_refs.swap(0, 1);
+        assert_ne!(d.digest(), c.digest());
+        let mut d = c.clone();
+        d.output_refs.pop();
+        assert_ne!(d.digest(), c.digest());
+        let mut d = c.clone();
+        let i = d
+            .gates
+            .iter()
+            .position(|g| matches!(g, BinaryGate::Xor { .. }))
+            .unwrap();
+        if let BinaryGate::Xor { xref, yref, out } = d.gates[i] {
+            d.gates[i] = BinaryGate::And {
+                xref,
+                yref,
+                id: 0,
+                out,
+            };
+        }
+        assert_ne!(d.digest(), c.digest());
+    }
+
+    #[test]
+    fn arithmetic() {
+        let circuit = |q, c| {
+            let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+            let x = b.garbler_input(q);
+            let y = b.constant(c, q).unwrap();
+            let z = b.add(&x, &y).unwrap();
+            b.output(&z).unwrap();
+            b.finish()
+        };
+        let c = circuit(5, 1);
+        assert_eq!(c.digest(), circuit(5, 1).digest());
+        assert_ne!(c.digest(), circuit(5, 2).digest());
+        assert_ne!(c.digest(), circuit(7, 1).digest());
+
+        // Only the garbler's inputs are swapped for the evaluator's.
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.evaluator_input(5);
+        let y = b.constant(1, 5).unwrap();
+        let z = b.add(&x, &y).unwrap();
+        b.output(&z).unwrap();
+        assert_ne!(c.digest(), b.finish().digest());
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod public {
+    use super::*;
+    use crate::{
+        fancy::{BinaryGadgets, BundleGadgets},
+        util::{self, RngExt},
+    };
+    use itertools::Itertools;
+
+    fn parse(bytes: &'static [u8]) -> BinaryCircuit {
+        BinaryCircuit::parse(std::io::Cursor::new(bytes)).unwrap()
+    }
+
+    #[test]
+    fn builder() {
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.bin_garbler_input(8);
+        let y = b.bin_public_input(8);
+        let z = b.bin_addition_no_carry(&x, &y).unwrap();
+        b.output_bundle(&z).unwrap();
+        let c = b.finish();
+        assert_eq!(c.num_public_inputs(), 8);
+        c.validate().unwrap();
+        let out = eval_plain_with_public(
+            &c,
+            &util::u128_to_bits(100, 8),
+            &[],
+            &util::u128_to_bits(27, 8),
+        )
+        .unwrap();
+        assert_eq!(util::u128_from_bits(&out), 127);
+
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.evaluator_input(7);
+        let y = b.public_input(7);
+        let z = b.mul(&x, &y).unwrap();
+        b.output(&z).unwrap();
+        let c = b.finish();
+        c.validate().unwrap();
+        assert_eq!(eval_plain_with_public(&c, &[], &[3], &[4]).unwrap(), [5]);
+        c.print_info().unwrap();
+    }
+
+    #[test]
+    fn errors() {
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(5);
+        let y = b.public_input(3);
+        let one = b.constant(1, 5).unwrap();
+        let z = b.add(&x, &one).unwrap();
+        b.output(&z).unwrap();
+        b.output(&y).unwrap();
+        let c = b.finish();
+        assert!(matches!(
+            eval_plain_with_public(&c, &[1], &[], &[]),
+            Err(DummyError::FancyError(FancyError::NotEnoughPublicInputs {
+                got: 0,
+                needed: 1
+            }))
+        ));
+        assert!(matches!(
+            eval_plain_with_public(&c, &[1], &[], &[1, 2]),
+            Err(DummyError::FancyError(FancyError::TooManyPublicInputs {
+                got: 2,
+                needed: 1
+            }))
+        ));
+        assert!(matches!(
+            eval_plain_with_public(&c, &[1], &[], &[3]),
+            Err(DummyError::FancyError(FancyError::InputOutOfRange {
+                index: 1,
+                value: 3,
+                modulus: 3
+            }))
+        ));
+        // Circuits with public inputs need them.
+        assert!(matches!(
+            eval_plain(&c, &[1], &[]),
+            Err(DummyError::FancyError(
+                FancyError::NotEnoughPublicInputs { .. }
+            ))
+        ));
+        let mut dummy = Dummy::new();
+        let x = dummy.encode(1, 5).unwrap();
+        let y = dummy.encode(1, 5).unwrap();
+        assert!(matches!(
+            c.eval_with_public(&mut dummy, &[x], &[], &[y]),
+            Err(DummyError::FancyError(FancyError::PublicInputModulus {
+                id: 0,
+                got: 5,
+                needed: 3
+            }))
+        ));
+    }
+
+    #[test]
+    fn make_inputs_public() {
+        let c = parse(include_bytes!("../circuits/AES-non-expanded.txt"));
+        let mut rng = rand::thread_rng();
+        let key = (0..128)
+            .map(|_| Modulus::from(rng.gen_bool()))
+            .collect_vec();
+        let text = (0..128)
+            .map(|_| Modulus::from(rng.gen_bool()))
+            .collect_vec();
+        let target = eval_plain(&c, &key, &text).unwrap();
+
+        let mut d = c.clone();
+        let public = [3, 64, 127];
+        d.make_inputs_public(&[], &public).unwrap();
+        d.validate().unwrap();
+        assert_eq!(d.num_evaluator_inputs(), 125);
+        assert_eq!(d.num_public_inputs(), 3);
+        let kept = (0..128)
+            .filter(|i| !public.contains(i))
+            .map(|i| text[i])
+            .collect_vec();
+        let values = public.iter().map(|&i| text[i]).collect_vec();
+        assert_eq!(
+            eval_plain_with_public(&d, &key, &kept, &values).unwrap(),
+            target
+        );
+
+        // More public inputs follow those already there.
+        d.make_inputs_public(&[0], &[]).unwrap();
+        assert_eq!(d.num_garbler_inputs(), 127);
+        let mut values = values;
+        values.push(key[0]);
+        assert_eq!(
+            eval_plain_with_public(&d, &key[1..], &kept, &values).unwrap(),
+            target
+        );
+
+        let mut e = c.clone();
+        assert!(matches!(
+            e.make_inputs_public(&[128], &[]),
+            Err(FancyError::NotEnoughGarblerInputs { .. })
+        ));
+        assert!(matches!(
+            e.make_inputs_public(&[0], &[200]),
+            Err(FancyError::NotEnoughEvaluatorInputs { .. })
+        ));
+        assert_eq!(e, c);
+    }
+
+    #[test]
+    fn digest() {
+        let c = parse(include_bytes!("../circuits/adder64.txt"));
+        let mut d = c.clone();
+        d.make_inputs_public(&[], &[]).unwrap();
+        assert_eq!(c.digest(), d.digest());
+        d.make_inputs_public(&[1], &[]).unwrap();
+        assert_ne!(c.digest(), d.digest());
+        let mut e = c;
+        e.make_inputs_public(&[2], &[]).unwrap();
+        assert_ne!(d.digest(), e.digest());
+        // The digest is cached, and forgotten when the circuit changes.
+        assert_eq!(e.digest(), e.compute_digest());
+        e.push_output_ref(e.garbler_input_refs[0]);
+        assert_ne!(d.digest(), e.digest());
+        assert_eq!(e.digest(), e.compute_digest());
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod streaming {
+    use super::*;
+    use crate::{dummy::Dummy, util::RngExt};
+    use itertools::Itertools;
+    use std::cell::Cell;
+
+    /// Evaluate `c` in plaintext with streamed outputs, returning the outputs
+    /// and each streamed output with the number of gates checkpointed when it
+    /// was output, which is the index of the gate computing it.
+    fn stream(
+        c: &BinaryCircuit,
+        gb: &[Modulus],
+        ev: &[Modulus],
+    ) -> (Vec<Modulus>, Vec<(usize, Modulus, usize)>) {
+        let (gb, ev) = encode_plain(c, gb, ev).unwrap();
+        let done = Cell::new(0);
+        let mut streamed = Vec::new();
+        let outputs = c
+            .eval_streaming(
+                &mut Dummy::new(),
+                &gb,
+                &ev,
+                &[],
+                Some((1, &mut |_, n| {
+                    done.set(n);
+                    Ok(())
+                })),
+                &mut |i, v| streamed.push((i, v, done.get())),
+            )
+            .unwrap()
+            .unwrap();
+        (outputs, streamed)
+    }
+
+    #[test]
+    fn early_outputs() {
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.garbler_input(2);
+        let y = b.evaluator_input(2);
+        let early = b.and(&x, &y).unwrap();
+        b.output(&early).unwrap();
+        let mut z = early;
+        for _ in 0..100 {
+            z = b.xor(&z, &x).unwrap();
+            z = b.and(&z, &y).unwrap();
+        }
+        b.output(&z).unwrap();
+        // An input, output before the gate computing the first output.
+        b.output(&x).unwrap();
+        let one = b.constant(1, 2).unwrap();
+        b.output(&one).unwrap();
+        b.output(&early).unwrap();
+        let c = b.finish();
+        let last = c.gates.len() - 1;
+
+        for (gb, ev) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            let expected = eval_plain(&c, &[gb], &[ev]).unwrap();
+            let (outputs, streamed) = stream(&c, &[gb], &[ev]);
+            assert_eq!(outputs, expected);
+            let order = streamed.iter().map(|&(i, _, done)| (i, done)).collect_vec();
+            assert_eq!(order[..3], [(2, 0), (0, 2), (4, 2)]);
+            assert_eq!(order[3..].iter().map(|&(_, done)| done).max(), Some(last));
+            let sorted = streamed.iter().sorted().map(|&(_, v, _)| v).collect_vec();
+            assert_eq!(sorted, expected);
+        }
+    }
+
+    #[test]
+    fn matches_eval() {
+        let c = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap();
+        let mut rng = rand::thread_rng();
+        let gb = (0..128)
+            .map(|_| Modulus::from(rng.gen_bool()))
+            .collect_vec();
+        let ev = (0..128)
+            .map(|_| Modulus::from(rng.gen_bool()))
+            .collect_vec();
+        let (outputs, streamed) = stream(&c, &gb, &ev);
+        assert_eq!(outputs, eval_plain(&c, &gb, &ev).unwrap());
+        assert_eq!(streamed.len(), 128);
+        assert!(streamed.windows(2).all(|w| w[0].2 <= w[1].2));
+    }
 }
@@ -0,0 +1,354 @@
+//! Writing circuits in Bristol Fashion, as read by
+//! [`BinaryCircuit::parse`].
+//!
+//! The wires are numbered anew as Bristol Fashion has them: the garbler
+//! inputs, then the evaluator inputs, then the wires written by the gates,
+//! and the outputs last. Every gate writes a wire of its own, so wires are
+//! never reused. Outputs that are inputs, or that repeat another output, are
+//! copied to their wires with `EQW` gates, and constants are set with `EQ`
+//! gates.
+
+use super::{ArithmeticCircuit, ArithmeticGate, BinaryCircuit, BinaryGate, CircuitType};
+use crate::{errors::CircuitWriteError, Modulus};
+use std::io::{BufWriter, Write};
+
+/// A gate of Bristol Fashion, on the wires of the circuit it was made from.
+#[derive(Clone, Copy)]
+enum Op {
+    GarblerInput(usize),
+    EvaluatorInput(usize),
+    Constant(Modulus),
+    Xor(usize, usize),
+    And(usize, usize),
+    Inv(usize),
+    Copy(usize),
+}
+
+impl Op {
+    /// The wires read by the gate.
+    fn inputs(self) -> [Option<usize>; 2] {
+        match self {
+            Op::GarblerInput(_) | Op::EvaluatorInput(_) | Op::Constant(_) => [None, None],
+            Op::Xor(x, y) | Op::And(x, y) => [Some(x), Some(y)],
+            Op::Inv(x) | Op::Copy(x) => [Some(x), None],
+        }
+    }
+}
+
+/// The part a gate plays in the written circuit.
+#[derive(Clone, Copy, PartialEq)]
+enum Role {
+    /// An input, with its wire.
+    Input(usize),
+    /// The gate writing the output of the given index.
+    Output(usize),
+    /// Any other gate that is written.
+    Internal,
+    /// A constant that nothing reads, which is not written.
+    Unused,
+}
+
+impl BinaryCircuit {
+    /// Write the circuit in Bristol Fashion, with the garbler inputs as its
+    /// first input value, the evaluator inputs as its second, and an output
+    /// value for each output group (see [`CircuitType::output_widths`]).
+    ///
+    /// The gates are written as `AND`, `XOR`, and `INV` gates, constants as
+    /// `EQ` gates, and outputs that are inputs or repeat another output as
+    /// `EQW` gates. Fails if the circuit is not well-formed, or has public
+    /// inputs or custom gates, which Bristol Fashion cannot express.
+    pub fn write_bristol<W: Write>(&self, w: W) -> Result<(), CircuitWriteError> {
+        self.validate()?;
+        let ops = self
+            .gates
+            .iter()
+            .enumerate()
+            .map(|(i, gate)| {
+                let op = match *gate {
+                    BinaryGate::GarblerInput { id } => Op::GarblerInput(id),
+                    BinaryGate::EvaluatorInput { id } => Op::EvaluatorInput(id),
+                    BinaryGate::Constant { val } => Op::Constant(val),
+                    BinaryGate::Xor { xref, yref, .. } => Op::Xor(xref.ix, yref.ix),
+                    BinaryGate::And { xref, yref, .. } => Op::And(xref.ix, yref.ix),
+                    BinaryGate::Inv { xref, .. } => Op::Inv(xref.ix),
+                    BinaryGate::PublicInput { .. } => {
+                        return Err(CircuitWriteError::UnsupportedGate(i))
+                    }
+                    BinaryGate::Custom { .. } => return Err(CircuitWriteError::UnsupportedGate(i)),
+                };
+                Ok((op, gate.wires(i).1))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        write_ops(self, &ops, w)
+    }
+}
+
+impl ArithmeticCircuit {
+    /// Write the circuit in Bristol Fashion, as [`BinaryCircuit::write_bristol`]
+    /// does.
+    ///
+    /// Every wire must have modulus 2: additions and subtractions are written
+    /// as `XOR` gates, multiplications as `AND` gates, and multiplications by
+    /// constants and projections as the copies, negations, or constants they
+    /// compute. Fails if a gate has another modulus, or the circuit has
+    /// public inputs.
+    pub fn write_bristol<W: Write>(&self, w: W) -> Result<(), CircuitWriteError> {
+        self.validate()?;
+        let ops = self
+            .gates
+            .iter()
+            .enumerate()
+            .map(|(i, gate)| {
+                let modulus = self.gate_moduli[i];
+                if modulus != 2 {
+                    return Err(CircuitWriteError::NonBinaryModulus { gate: i, modulus });
+                }
+                let op = match *gate {
+                    ArithmeticGate::GarblerInput { id } => Op::GarblerInput(id),
+                    ArithmeticGate::EvaluatorInput { id } => Op::EvaluatorInput(id),
+                    ArithmeticGate::Constant { val } => Op::Constant(val),
+                    ArithmeticGate::Add { xref, yref, .. }
+                    | ArithmeticGate::Sub { xref, yref, .. } => Op::Xor(xref.ix, yref.ix),
+                    ArithmeticGate::Mul { xref, yref, .. } => Op::And(xref.ix, yref.ix),
+                    ArithmeticGate::Cmul { xref, c, .. } if c % 2 == 1 => Op::Copy(xref.ix),
+                    ArithmeticGate::Cmul { .. } => Op::Constant(0),
+                    // The truth table has an entry for each value of `x`.
+                    ArithmeticGate::Proj { xref, ref tt, .. } => match (tt[0], tt[1]) {
+                        (0, 1) => Op::Copy(xref.ix),
+                        (1, 0) => Op::Inv(xref.ix),
+                        (c, _) => Op::Constant(c),
+                    },
+                    ArithmeticGate::PublicInput { .. } => {
+                        return Err(CircuitWriteError::UnsupportedGate(i))
+                    }
+                };
+                Ok((op, gate.wires(i).1))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        write_ops(self, &ops, w)
+    }
+}
+
+/// Write the gates `ops` of `circuit`, each with the wire it writes, in
+/// Bristol Fashion.
+fn write_ops<C: CircuitType>(
+    circuit: &C,
+    ops: &[(Op, usize)],
+    w: impl Write,
+) -> Result<(), CircuitWriteError> {
+    let n1 = circuit.num_garbler_inputs();
+    let n2 = circuit.num_evaluator_inputs();
+
+    // Find the gate that last wrote each wire read, and each output.
+    let mut writer = vec![usize::MAX; ops.len()];
+    let mut read = vec![false; ops.len()];
+    let mut roles = Vec::with_capacity(ops.len());
+    for (i, &(op, z)) in ops.iter().enumerate() {
+        for x in op.inputs().into_iter().flatten() {
+            read[writer[x]] = true;
+        }
+        writer[z] = i;
+        roles.push(match op {
+            Op::GarblerInput(id) => Role::Input(id),
+            Op::EvaluatorInput(id) => Role::Input(n1 + id),
+            Op::Constant(_) => Role::Unused,
+            _ => Role::Internal,
+        });
+    }
+    for (i, r) in read.iter().enumerate() {
+        if *r && roles[i] == Role::Unused {
+            roles[i] = Role::Internal;
+        }
+    }
+    // The gate writing an output writes its wire directly, unless the gate is
+    // an input or writes an earlier output, when the output is a copy.
+    let mut copies = Vec::new();
+    for (j, r) in circuit.get_output_refs().iter().enumerate() {
+        let i = writer[r.ix];
+        match roles[i] {
+            Role::Internal | Role::Unused => roles[i] = Role::Output(j),
+            _ => copies.push((i, j)),
+        }
+    }
+
+    // Number the wires: inputs first, then the other gates, then outputs.
+    let ninternal = roles.iter().filter(|&&r| r == Role::Internal).count();
+    let noutputs = circuit.noutputs();
+    let first_output = n1 + n2 + ninternal;
+    let mut next = n1 + n2;
+    let numbers = roles
+        .iter()
+        .map(|&role| match role {
+            Role::Input(k) => k,
+            Role::Output(j) => first_output + j,
+            Role::Internal => {
+                next += 1;
+                next - 1
+            }
+            Role::Unused => usize::MAX,
+        })
+        .collect::<Vec<_>>();
+
+    let ngates = roles
+        .iter()
+        .filter(|r| matches!(r, Role::Output(_) | Role::Internal))
+        .count()
+        + copies.len();
+    let mut w = BufWriter::new(w);
+    writeln!(w, "{} {}", ngates, first_output + noutputs)?;
+    writeln!(w, "2 {} {}", n1, n2)?;
+    let widths = circuit.output_widths();
+    if widths.is_empty() {
+        writeln!(w, "1 0")?;
+    } else {
+        let widths = widths.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        writeln!(w, "{} {}", widths.len(), widths.join(" "))?;
+    }
+    writeln!(w)?;
+
+    // The number of the wire each wire of the circuit holds now.
+    let mut current = vec![usize::MAX; ops.len()];
+    for (i, &(op, z)) in ops.iter().enumerate() {
+        let n = numbers[i];
+        let wire = |x: usize| current[x];
+        match op {
+            Op::GarblerInput(_) | Op::EvaluatorInput(_) => (),
+            Op::Constant(_) if roles[i] == Role::Unused => (),
+            Op::Constant(c) => writeln!(w, "1 1 {} {} EQ", c, n)?,
+            Op::Xor(x, y) => writeln!(w, "2 1 {} {} {} XOR", wire(x), wire(y), n)?,
+            Op::And(x, y) => writeln!(w, "2 1 {} {} {} AND", wire(x), wire(y), n)?,
+            Op::Inv(x) => writeln!(w, "1 1 {} {} INV", wire(x), n)?,
+            Op::Copy(x) => writeln!(w, "1 1 {} {} EQW", wire(x), n)?,
+        }
+        current[z] = n;
+    }
+    for (i, j) in copies {
+        writeln!(w, "1 1 {} {} EQW", numbers[i], first_output + j)?;
+    }
+    w.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        circuit::{eval_plain, ArithmeticCircuit, BinaryCircuit, CircuitBuilder, CircuitType},
+        errors::CircuitWriteError,
+        util::RngExt,
+        BinaryGadgets, Fancy, FancyArithmetic, FancyBinary, Modulus,
+    };
+    use rand::thread_rng;
+
+    /// Write `circ` and parse it back.
+    fn round_trip<C: CircuitType>(
+        circ: &C,
+        write: impl Fn(&C, &mut Vec<u8>) -> Result<(), CircuitWriteError>,
+    ) -> BinaryCircuit {
+        let mut bytes = Vec::new();
+        write(circ, &mut bytes).unwrap();
+        BinaryCircuit::parse(&bytes[..]).unwrap()
+    }
+
+    fn random_bits(n: usize) -> Vec<Modulus> {
+        let mut rng = thread_rng();
+        (0..n).map(|_| rng.gen_bool() as Modulus).collect()
+    }
+
+    #[test]
+    fn test_adder64() {
+        let circ: BinaryCircuit = include_str!("../../circuits/adder64.txt").parse().unwrap();
+        let written = round_trip(&circ, |c, w| c.write_bristol(w));
+        assert_eq!(written.num_garbler_inputs(), 64);
+        assert_eq!(written.num_evaluator_inputs(), 64);
+        assert_eq!(written.output_widths(), [64]);
+        for _ in 0..16 {
+            let (x, y) = (random_bits(64), random_bits(64));
+            assert_eq!(
+                eval_plain(&written, &x, &y).unwrap(),
+                eval_plain(&circ, &x, &y).unwrap()
+            );
+        }
+        // Writing the written circuit gives the same file.
+        let mut once = Vec::new();
+        written.write_bristol(&mut once).unwrap();
+        let twice = round_trip(&written, |c, w| c.write_bristol(w));
+        let mut again = Vec::new();
+        twice.write_bristol(&mut again).unwrap();
+        assert_eq!(once, again);
+    }
+
+    #[test]
+    fn test_built() {
+        // Outputs that are constants, inputs, and repeats of other outputs.
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.bin_garbler_input(4);
+        let y = b.bin_evaluator_input(4);
+        let (z, carry) = b.bin_addition(&x, &y).unwrap();
+        let one = b.constant(1, 2).unwrap();
+        let zero = b.constant(0, 2).unwrap();
+        let nz = b.negate(&z.wires()[0]).unwrap();
+        b.output(&carry).unwrap();
+        b.output_group(z.wires()).unwrap();
+        b.outputs(&[one, zero, x.wires()[1], carry, nz]).unwrap();
+        let circ = b.finish();
+
+        let written = round_trip(&circ, |c, w| c.write_bristol(w));
+        assert_eq!(written.output_widths(), circ.output_widths());
+        for _ in 0..16 {
+            let (x, y) = (random_bits(4), random_bits(4));
+            assert_eq!(
+                eval_plain(&written, &x, &y).unwrap(),
+                eval_plain(&circ, &x, &y).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(2);
+        let y = b.evaluator_input(2);
+        let s = b.add(&x, &y).unwrap();
+        let d = b.sub(&x, &y).unwrap();
+        let m = b.mul(&x, &y).unwrap();
+        let c = b.cmul(&m, 3).unwrap();
+        let c0 = b.cmul(&m, 2).unwrap();
+        let n = b.proj(&s, 2, Some(vec![1, 0])).unwrap();
+        let p = b.proj(&s, 2, Some(vec![0, 1])).unwrap();
+        let k = b.proj(&s, 2, Some(vec![1, 1])).unwrap();
+        b.outputs(&[s, d, m, c, c0, n, p, k]).unwrap();
+        let circ = b.finish();
+
+        let written = round_trip(&circ, |c, w| c.write_bristol(w));
+        for (x, y) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            let out = eval_plain(&written, &[x], &[y]).unwrap();
+            assert_eq!(out, [x ^ y, x ^ y, x & y, x & y, 0, 1 - (x ^ y), x ^ y, 1]);
+        }
+
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(3);
+        b.output(&x).unwrap();
+        assert!(matches!(
+            b.finish().write_bristol(Vec::new()),
+            Err(CircuitWriteError::NonBinaryModulus {
+                gate: 0,
+                modulus: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.bin_garbler_input(1);
+        let p = b.bin_public_input(1);
+        let z = b.and(&x.wires()[0], &p.wires()[0]).unwrap();
+        b.output(&z).unwrap();
+        let err = b.finish().write_bristol(Vec::new()).unwrap_err();
+        assert!(matches!(err, CircuitWriteError::UnsupportedGate(1)));
+        assert_eq!(
+            err.to_string(),
+            "gate 1 cannot be written in Bristol Fashion"
+        );
+    }
+}
@@ -0,0 +1,428 @@
+//! A thread-safe cache of circuits, for servers garbling the same few
+//! circuits in many sessions.
+//!
+//! A [`CircuitCache`] holds up to a fixed number of circuits, each behind an
+//! [`Arc`], evicting the least recently used one to make room for another.
+//! Circuits parsed from Bristol files are keyed by their path, and parsed
+//! again when the modification time or length of the file changes; circuits
+//! built otherwise are keyed by a [`CircuitDigest`] the caller provides:
+//!
+//! ```ignore
+//! let cache = CircuitCache::new(16);
+//! let aes = cache.get_or_parse("circuits/AES-non-expanded.txt")?;
+//! let adder = cache.get_or_insert(ADDER_DIGEST, || build_adder())?;
+//! ```
+//!
+//! A circuit is only ever parsed or built by one thread at a time: other
+//! threads asking for it meanwhile wait for it rather than parsing it too.
+
+use super::{BinaryCircuit, CircuitDigest};
+#[cfg(feature = "std")]
+use crate::errors::CircuitParserError;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+#[cfg(feature = "std")]
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// What a circuit is cached under.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key {
+    /// The canonical path of the file it was parsed from.
+    #[cfg(feature = "std")]
+    File(PathBuf),
+    /// A digest provided by the caller.
+    Digest(CircuitDigest),
+}
+
+/// The modification time and length of a file when it was parsed, or `None`
+/// for a circuit not parsed from a file.
+#[cfg(feature = "std")]
+type Stamp = Option<(Option<SystemTime>, u64)>;
+#[cfg(not(feature = "std"))]
+type Stamp = Option<()>;
+
+/// A circuit once it has been loaded, which the thread loading it holds the
+/// lock of meanwhile.
+type Slot<C> = Arc<Mutex<Option<Arc<C>>>>;
+
+#[cfg(feature = "std")]
+type ParseFn = dyn Fn(&Path) + Send + Sync;
+
+struct Entry<C> {
+    stamp: Stamp,
+    slot: Slot<C>,
+    /// The value of the clock when the entry was last asked for.
+    used: u64,
+}
+
+struct State<C> {
+    entries: HashMap<Key, Entry<C>>,
+    clock: u64,
+}
+
+/// A cache of up to [`capacity`](CircuitCache::capacity) circuits, shareable
+/// between threads.
+///
+/// The cache hands out circuits as `Arc`s, so a circuit evicted from the
+/// cache stays valid for as long as anyone holds it.
+pub struct CircuitCache<C = BinaryCircuit> {
+    capacity: usize,
+    state: Mutex<State<C>>,
+    #[cfg(feature = "std")]
+    on_parse: Option<Arc<ParseFn>>,
+}
+
+impl<C> std::fmt::Debug for CircuitCache<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("CircuitCache")
+            .field("capacity", &self.capacity)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// Lock `mutex`, ignoring poisoning: a thread panicking while loading a
+/// circuit leaves its slot empty, for the next thread to load it again.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<C> CircuitCache<C> {
+    /// Make an empty cache holding up to `capacity` circuits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "the capacity of a cache must be positive");
+        CircuitCache {
+            capacity,
+            state: Mutex::new(State {
+                entries: HashMap::new(),
+                clock: 0,
+            }),
+            #[cfg(feature = "std")]
+            on_parse: None,
+        }
+    }
+
+    /// The number of circuits the cache holds at most.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of circuits in the cache, including any being loaded.
+    pub fn len(&self) -> usize {
+        lock(&self.state).entries.len()
+    }
+
+    /// Whether the cache holds no circuits.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove every circuit from the cache.
+    pub fn clear(&self) {
+        lock(&self.state).entries.clear();
+    }
+
+    /// The circuit cached under `digest`, calling `build` to make it if the
+    /// cache does not hold it.
+    ///
+    /// If `build` fails, its error is returned and nothing is cached, and
+    /// the next call for `digest` builds the circuit again. The cache does
+    /// not check that `digest` is the digest of what `build` returns.
+    pub fn get_or_insert<E>(
+        &self,
+        digest: CircuitDigest,
+        build: impl FnOnce() -> Result<C, E>,
+    ) -> Result<Arc<C>, E> {
+        self.get_or_load(Key::Digest(digest), None, build)
+    }
+
+    fn get_or_load<E>(
+        &self,
+        key: Key,
+        stamp: Stamp,
+        load: impl FnOnce() -> Result<C, E>,
+    ) -> Result<Arc<C>, E> {
+        let slot = {
+            let mut state = lock(&self.state);
+            state.clock += 1;
+            let clock = state.clock;
+            match state.entries.get_mut(&key) {
+                Some(entry) if entry.stamp == stamp => {
+                    entry.used = clock;
+                    entry.slot.clone()
+                }
+                _ => {
+                    if !state.entries.contains_key(&key) && state.entries.len() >= self.capacity {
+                        state.evict();
+                    }
+                    let slot = Slot::default();
+                    let entry = Entry {
+                        stamp,
+                        slot: slot.clone(),
+                        used: clock,
+                    };
+                    state.entries.insert(key.clone(), entry);
+                    slot
+                }
+            }
+        };
+        // Threads asking for the same circuit wait here for the first to
+        // load it.
+        let mut value = lock(&slot);
+        if let Some(c) = value.as_ref() {
+            return Ok(c.clone());
+        }
+        match load() {
+            Ok(c) => {
+                let c = Arc::new(c);
+                *value = Some(c.clone());
+                Ok(c)
+            }
+            Err(e) => {
+                let mut state = lock(&self.state);
+                if matches!(state.entries.get(&key), Some(entry) if Arc::ptr_eq(&entry.slot, &slot))
+                {
+                    state.entries.remove(&key);
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+impl<C> State<C> {
+    /// Remove the least recently used entry.
+    fn evict(&mut self) {
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.used)
+            .map(|(key, _)| key.clone());
+        if let Some(key) = oldest {
+            self.entries.remove(&key);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl CircuitCache<BinaryCircuit> {
+    /// Call `f` with the path of every file the cache parses, as it starts
+    /// parsing it.
+    pub fn on_parse(mut self, f: impl Fn(&Path) + Send + Sync + 'static) -> Self {
+        self.on_parse = Some(Arc::new(f));
+        self
+    }
+
+    /// The circuit in the Bristol file at `path`, parsing the file if the
+    /// cache does not hold it, or if the file has been modified since it
+    /// was parsed.
+    ///
+    /// Files are keyed by their canonical path, so different paths to the
+    /// same file share an entry.
+    pub fn get_or_parse(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<Arc<BinaryCircuit>, CircuitParserError> {
+        let path = fs::canonicalize(path)?;
+        let metadata = fs::metadata(&path)?;
+        let stamp = Some((metadata.modified().ok(), metadata.len()));
+        self.get_or_load(Key::File(path.clone()), stamp, || {
+            if let Some(f) = &self.on_parse {
+                f(&path);
+            }
+            BinaryCircuit::parse_file(&path)
+        })
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{CircuitBuilder, CircuitType},
+        Fancy,
+    };
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Barrier,
+    };
+
+    const ADDER: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/circuits/adder64.txt");
+
+    fn digest(i: u8) -> CircuitDigest {
+        CircuitDigest::from_bytes([i; 32])
+    }
+
+    fn circuit(n: usize) -> BinaryCircuit {
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.evaluator_inputs(&vec![2; n]);
+        b.outputs(&xs).unwrap();
+        b.finish()
+    }
+
+    /// A cache counting the files it parses.
+    fn counting_cache(capacity: usize) -> (CircuitCache, Arc<AtomicUsize>) {
+        let parses = Arc::new(AtomicUsize::new(0));
+        let counter = parses.clone();
+        let cache = CircuitCache::new(capacity).on_parse(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        (cache, parses)
+    }
+
+    #[test]
+    fn concurrent_parse_once() {
+        let (cache, parses) = counting_cache(4);
+        let n = 8;
+        let barrier = Barrier::new(n);
+        let circuits = std::thread::scope(|s| {
+            // All threads must be spawned before any is joined, or the
+            // barrier never opens.
+            let mut handles = Vec::with_capacity(n);
+            for _ in 0..n {
+                handles.push(s.spawn(|| {
+                    barrier.wait();
+                    cache.get_or_parse(ADDER).unwrap()
+                }));
+            }
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(parses.load(Ordering::SeqCst), 1);
+        assert!(circuits.iter().all(|c| Arc::ptr_eq(c, &circuits[0])));
+        assert_eq!(circuits[0].num_garbler_inputs(), 64);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn concurrent_build_once() {
+        let cache = CircuitCache::new(4);
+        let builds = AtomicUsize::new(0);
+        let n = 8;
+        let barrier = Barrier::new(n);
+        let circuits = std::thread::scope(|s| {
+            let mut handles = Vec::with_capacity(n);
+            for _ in 0..n {
+                handles.push(s.spawn(|| {
+                    barrier.wait();
+                    cache
+                        .get_or_insert(digest(1), || {
+                            builds.fetch_add(1, Ordering::SeqCst);
+                            Ok::<_, ()>(circuit(3))
+                        })
+                        .unwrap()
+                }));
+            }
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(builds.load(Ordering::SeqCst), 1);
+        assert!(circuits.iter().all(|c| Arc::ptr_eq(c, &circuits[0])));
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = CircuitCache::new(2);
+        let builds = AtomicUsize::new(0);
+        let get = |i: u8| {
+            cache
+                .get_or_insert(digest(i), || {
+                    builds.fetch_add(1, Ordering::SeqCst);
+                    Ok::<_, ()>(circuit(i as usize))
+                })
+                .unwrap()
+        };
+        let one = get(1);
+        get(2);
+        // Using 1 again leaves 2 the least recently used, to be evicted by 3.
+        assert!(Arc::ptr_eq(&get(1), &one));
+        get(3);
+        assert_eq!(builds.load(Ordering::SeqCst), 3);
+        assert_eq!(cache.len(), 2);
+        get(1);
+        get(3);
+        assert_eq!(builds.load(Ordering::SeqCst), 3);
+        get(2);
+        assert_eq!(builds.load(Ordering::SeqCst), 4);
+        // 1 was used before 3, so it made room for 2.
+        get(3);
+        assert_eq!(builds.load(Ordering::SeqCst), 4);
+        get(1);
+        assert_eq!(builds.load(Ordering::SeqCst), 5);
+        // The evicted circuit is still valid for those holding it.
+        assert_eq!(one.num_evaluator_inputs(), 1);
+    }
+
+    #[test]
+    fn failed_build_not_cached() {
+        let cache = CircuitCache::<BinaryCircuit>::new(2);
+        assert_eq!(cache.get_or_insert(digest(1), || Err("no")), Err("no"));
+        assert!(cache.is_empty());
+        let c = cache.get_or_insert(digest(1), || Ok::<_, ()>(circuit(2)));
+        assert_eq!(c.unwrap().num_evaluator_inputs(), 2);
+    }
+
+    #[test]
+    fn reparses_modified_file() {
+        let path =
+            std::env::temp_dir().join(format!("fancy-garbling-cache-{}.txt", std::process::id()));
+        fs::copy(ADDER, &path).unwrap();
+        let (cache, parses) = counting_cache(2);
+        let adder = cache.get_or_parse(&path).unwrap();
+        assert!(Arc::ptr_eq(&adder, &cache.get_or_parse(&path).unwrap()));
+        assert_eq!(parses.load(Ordering::SeqCst), 1);
+
+        fs::write(&path, "1 3\n1 1 1\n\n2 1 0 1 2 XOR\n").unwrap();
+        let xor = cache.get_or_parse(&path);
+        fs::remove_file(&path).unwrap();
+        let xor = xor.unwrap();
+        assert_eq!(parses.load(Ordering::SeqCst), 2);
+        assert_eq!(xor.num_garbler_inputs(), 1);
+        // The new circuit replaces the old one rather than taking another
+        // entry.
+        assert_eq!(cache.len(), 1);
+        assert_eq!(adder.num_garbler_inputs(), 64);
+    }
+
+    #[test]
+    fn missing_file() {
+        let (cache, parses) = counting_cache(2);
+        let r = cache.get_or_parse(std::env::temp_dir().join("fancy-garbling-no-such-circuit"));
+        assert!(matches!(r, Err(CircuitParserError::IoError(_))));
+        assert_eq!(parses.load(Ordering::SeqCst), 0);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn digest_keys() {
+        let c = circuit(2);
+        let cache = CircuitCache::new(1);
+        let cached = cache.get_or_insert(c.digest(), || Ok::<_, ()>(c)).unwrap();
+        let again = cache
+            .get_or_insert(cached.digest(), || -> Result<_, ()> {
+                panic!("already cached")
+            })
+            .unwrap();
+        assert!(Arc::ptr_eq(&cached, &again));
+    }
+
+    #[test]
+    #[should_panic]
+    fn zero_capacity() {
+        CircuitCache::<BinaryCircuit>::new(0);
+    }
+}
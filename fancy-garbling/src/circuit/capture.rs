@@ -0,0 +1,308 @@
+//! Capturing a computation written against [`Fancy`](crate::Fancy) into a
+//! circuit.
+//!
+//! A computation generic over its `Fancy` object runs as well on a
+//! [`CircuitBuilder`] as on a garbler, which then records its gates instead
+//! of computing them. [`BinaryCircuit::from_fancy`] and
+//! [`ArithmeticCircuit::from_fancy`] run such a computation once and return
+//! the circuit it recorded, which can then be saved, optimized, or garbled
+//! with [`classic`](crate::classic):
+//!
+//! ```ignore
+//! let adder = BinaryCircuit::from_fancy(|b, mut inputs| {
+//!     let x = inputs.bin_garbler(b, 64);
+//!     let y = inputs.bin_evaluator(b, 64);
+//!     let z = b.bin_addition_no_carry(&x, &y)?;
+//!     b.outputs(z.wires())?;
+//!     Ok(())
+//! })?;
+//! ```
+
+use super::{ArithmeticCircuit, BinaryCircuit, CircuitBuilder, CircuitRef, CircuitType};
+use crate::{
+    errors::{CircuitBuilderError, CircuitValidationError},
+    fancy::{BinaryBundle, CrtBundle},
+    Modulus,
+};
+
+/// The inputs of a computation being captured, declared in order.
+///
+/// Each party's inputs become the inputs of the captured circuit in the order
+/// they are declared, whether or not they are interleaved with the other
+/// party's inputs or with gates.
+#[derive(Debug)]
+pub struct CapturedInputs {
+    _private: (),
+}
+
+impl CapturedInputs {
+    /// Declare a garbler input of modulus `q`.
+    pub fn garbler<C: CircuitType>(&mut self, b: &mut CircuitBuilder<C>, q: Modulus) -> CircuitRef {
+        b.garbler_input(q)
+    }
+
+    /// Declare an evaluator input of modulus `q`.
+    pub fn evaluator<C: CircuitType>(
+        &mut self,
+        b: &mut CircuitBuilder<C>,
+        q: Modulus,
+    ) -> CircuitRef {
+        b.evaluator_input(q)
+    }
+
+    /// Declare a garbler input for each of `moduli`.
+    pub fn garbler_many<C: CircuitType>(
+        &mut self,
+        b: &mut CircuitBuilder<C>,
+        moduli: &[Modulus],
+    ) -> Vec<CircuitRef> {
+        b.garbler_inputs(moduli)
+    }
+
+    /// Declare an evaluator input for each of `moduli`.
+    pub fn evaluator_many<C: CircuitType>(
+        &mut self,
+        b: &mut CircuitBuilder<C>,
+        moduli: &[Modulus],
+    ) -> Vec<CircuitRef> {
+        b.evaluator_inputs(moduli)
+    }
+
+    /// Declare an `nbits`-bit number as garbler inputs, least significant bit
+    /// first.
+    pub fn bin_garbler<C: CircuitType>(
+        &mut self,
+        b: &mut CircuitBuilder<C>,
+        nbits: usize,
+    ) -> BinaryBundle<CircuitRef> {
+        b.bin_garbler_input(nbits)
+    }
+
+    /// Declare an `nbits`-bit number as evaluator inputs, least significant
+    /// bit first.
+    pub fn bin_evaluator<C: CircuitType>(
+        &mut self,
+        b: &mut CircuitBuilder<C>,
+        nbits: usize,
+    ) -> BinaryBundle<CircuitRef> {
+        b.bin_evaluator_input(nbits)
+    }
+
+    /// Declare a number modulo the composite `modulus` as garbler inputs, one
+    /// for each of its prime factors.
+    pub fn crt_garbler<C: CircuitType>(
+        &mut self,
+        b: &mut CircuitBuilder<C>,
+        modulus: u128,
+    ) -> CrtBundle<CircuitRef> {
+        b.crt_garbler_input(modulus)
+    }
+
+    /// Declare a number modulo the composite `modulus` as evaluator inputs,
+    /// one for each of its prime factors.
+    pub fn crt_evaluator<C: CircuitType>(
+        &mut self,
+        b: &mut CircuitBuilder<C>,
+        modulus: u128,
+    ) -> CrtBundle<CircuitRef> {
+        b.crt_evaluator_input(modulus)
+    }
+}
+
+/// Run `f` on a fresh builder and check the circuit it builds with
+/// `validate`.
+fn capture<C, F>(
+    f: F,
+    validate: impl FnOnce(&C) -> Result<(), CircuitValidationError>,
+) -> Result<C, CircuitBuilderError>
+where
+    C: CircuitType,
+    F: FnOnce(&mut CircuitBuilder<C>, CapturedInputs) -> Result<(), CircuitBuilderError>,
+{
+    let mut b = CircuitBuilder::new();
+    f(&mut b, CapturedInputs { _private: () })?;
+    let circ = b.finish();
+    if circ.noutputs() == 0 {
+        return Err(CircuitBuilderError::NoOutputs);
+    }
+    validate(&circ).map_err(CircuitBuilderError::InvalidCircuit)?;
+    Ok(circ)
+}
+
+impl BinaryCircuit {
+    /// Capture the computation `f` into a circuit.
+    ///
+    /// `f` declares its inputs with the [`CapturedInputs`] it is given and
+    /// computes on the builder with any binary gadget. The wires it outputs,
+    /// in order, are the outputs of the circuit. Fails with
+    /// [`CircuitBuilderError::NoOutputs`] if `f` outputs nothing, and with
+    /// [`CircuitBuilderError::InvalidCircuit`] if it uses or outputs a wire
+    /// that is not of this builder, such as one captured from another.
+    /// Revealing a value is not possible while capturing, since no value is
+    /// known until the circuit is evaluated.
+    pub fn from_fancy<F>(f: F) -> Result<Self, CircuitBuilderError>
+    where
+        F: FnOnce(&mut CircuitBuilder<Self>, CapturedInputs) -> Result<(), CircuitBuilderError>,
+    {
+        capture(f, Self::validate)
+    }
+}
+
+impl ArithmeticCircuit {
+    /// Capture the computation `f` into a circuit.
+    ///
+    /// See [`BinaryCircuit::from_fancy`]; `f` may use any arithmetic gadget.
+    pub fn from_fancy<F>(f: F) -> Result<Self, CircuitBuilderError>
+    where
+        F: FnOnce(&mut CircuitBuilder<Self>, CapturedInputs) -> Result<(), CircuitBuilderError>,
+    {
+        capture(f, Self::validate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{eval_plain, find_counterexample},
+        errors::CircuitValidationError,
+        util, BinaryGadgets, CrtGadgets, Fancy, FancyArithmetic,
+    };
+    use rand::thread_rng;
+
+    fn adder(nbits: usize) -> BinaryCircuit {
+        BinaryCircuit::from_fancy(|b, mut inputs| {
+            let x = inputs.bin_garbler(b, nbits);
+            let y = inputs.bin_evaluator(b, nbits);
+            let z = b.bin_addition_no_carry(&x, &y)?;
+            b.outputs(z.wires())?;
+            Ok(())
+        })
+        .unwrap()
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn adder64_matches_parsed() {
+        let parsed = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../circuits/adder64.txt"
+        )))
+        .unwrap();
+        let captured = adder(64);
+        assert_eq!(captured.num_garbler_inputs(), 64);
+        assert_eq!(captured.num_evaluator_inputs(), 64);
+        let found = find_counterexample(&captured, &parsed, 256, &mut thread_rng()).unwrap();
+        assert_eq!(found, None);
+    }
+
+    #[test]
+    fn counterexample_found() {
+        let sub = BinaryCircuit::from_fancy(|b, mut inputs| {
+            let x = inputs.bin_garbler(b, 8);
+            let y = inputs.bin_evaluator(b, 8);
+            let (z, _) = b.bin_subtraction(&x, &y)?;
+            b.outputs(z.wires())?;
+            Ok(())
+        })
+        .unwrap();
+        let found = find_counterexample(&adder(8), &sub, 16, &mut thread_rng())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            eval_plain(&sub, &found.garbler_inputs, &found.evaluator_inputs).unwrap(),
+            found.right
+        );
+        assert_ne!(found.left, found.right);
+
+        // Circuits of different inputs cannot be compared.
+        assert!(find_counterexample(&adder(8), &adder(7), 16, &mut thread_rng()).is_err());
+    }
+
+    #[test]
+    fn inputs_in_declared_order() {
+        // Inputs interleaved between the parties and with gates keep their
+        // order within each party.
+        let c = ArithmeticCircuit::from_fancy(|b, mut inputs| {
+            let x = inputs.garbler(b, 5);
+            let y = inputs.evaluator(b, 7);
+            let z = b.cmul(&x, 2)?;
+            let w = inputs.garbler(b, 7);
+            let v = inputs.evaluator_many(b, &[5]);
+            let s = b.add(&w, &y)?;
+            let t = b.add(&z, &v[0])?;
+            b.outputs(&[s, t])?;
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(c.garbler_input_mod(0), 5);
+        assert_eq!(c.garbler_input_mod(1), 7);
+        assert_eq!(c.evaluator_input_mod(0), 7);
+        assert_eq!(c.evaluator_input_mod(1), 5);
+        assert_eq!(eval_plain(&c, &[3, 4], &[6, 2]).unwrap(), vec![3, 3]);
+    }
+
+    #[test]
+    fn crt_gadgets() {
+        let q = util::modulus_with_width(10);
+        let c = ArithmeticCircuit::from_fancy(|b, mut inputs| {
+            let x = inputs.crt_garbler(b, q);
+            let y = inputs.crt_evaluator(b, q);
+            let z = b.crt_mul(&x, &y)?;
+            b.outputs(z.wires())?;
+            Ok(())
+        })
+        .unwrap();
+        let ps = util::factor(q);
+        let (x, y) = (123, 456);
+        let out = eval_plain(&c, &util::crt(x, &ps), &util::crt(y, &ps)).unwrap();
+        assert_eq!(util::crt_inv(&out, &ps), x * y % q);
+    }
+
+    #[test]
+    fn rejects_no_outputs() {
+        let r = BinaryCircuit::from_fancy(|b, mut inputs| {
+            let x = inputs.bin_garbler(b, 8);
+            b.bin_addition_no_carry(&x, &x)?;
+            Ok(())
+        });
+        assert!(matches!(r, Err(CircuitBuilderError::NoOutputs)), "{:?}", r);
+    }
+
+    #[test]
+    fn rejects_foreign_wires() {
+        let mut other = CircuitBuilder::<BinaryCircuit>::new();
+        let foreign = other.bin_garbler_input(8);
+        let r = BinaryCircuit::from_fancy(|b, mut inputs| {
+            let x = inputs.bin_garbler(b, 4);
+            b.outputs(x.wires())?;
+            b.outputs(foreign.wires())?;
+            Ok(())
+        });
+        assert!(
+            matches!(
+                r,
+                Err(CircuitBuilderError::InvalidCircuit(
+                    CircuitValidationError::RefOutOfRange { .. }
+                        | CircuitValidationError::UndefinedRef(_)
+                ))
+            ),
+            "{:?}",
+            r
+        );
+    }
+
+    #[test]
+    fn errors_propagate() {
+        let r = BinaryCircuit::from_fancy(|b, mut inputs| {
+            let x = inputs.bin_garbler(b, 8);
+            let y = inputs.bin_evaluator(b, 4);
+            b.bin_addition_no_carry(&x, &y)?;
+            Ok(())
+        });
+        assert!(
+            matches!(r, Err(CircuitBuilderError::FancyError(_))),
+            "{:?}",
+            r
+        );
+    }
+}
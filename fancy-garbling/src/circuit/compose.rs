@@ -0,0 +1,256 @@
+//! Composition of circuits, feeding the outputs of one into the inputs of
+//! another.
+//!
+//! The composed circuit is built by evaluating both circuits on a
+//! [`CircuitBuilder`], which renumbers their wires and garbled gates as it
+//! records them, so the result garbles as any built circuit does.
+
+use super::{
+    output_wires, ArithmeticCircuit, BinaryCircuit, CircuitBuilder, CircuitRef, CircuitType,
+};
+use crate::{
+    errors::{CircuitBuilderError, CircuitComposeError},
+    fancy::HasModulus,
+    Fancy,
+};
+
+/// The wires evaluating a circuit on a builder needs and returns: the garbler,
+/// evaluator, and public inputs it reads, and the outputs it computes.
+type Eval<'a, C> = dyn Fn(
+        &C,
+        &mut CircuitBuilder<C>,
+        &[CircuitRef],
+        &[CircuitRef],
+        &[CircuitRef],
+    ) -> Result<Vec<CircuitRef>, CircuitBuilderError>
+    + 'a;
+
+/// Compose `first` and `second` along `wiring`, evaluating each with `eval`.
+fn compose<C: CircuitType>(
+    first: &C,
+    second: &C,
+    wiring: &[(usize, usize)],
+    eval: &Eval<'_, C>,
+) -> Result<C, CircuitComposeError> {
+    let nsecond_garbler = second.num_garbler_inputs();
+    let ninputs = nsecond_garbler + second.num_evaluator_inputs();
+    let input_modulus = |j: usize| {
+        if j < nsecond_garbler {
+            second.garbler_input_mod(j)
+        } else {
+            second.evaluator_input_mod(j - nsecond_garbler)
+        }
+    };
+
+    // For each garbler and evaluator input of `second`, the output of
+    // `first` feeding it, if any.
+    let mut sources = vec![None; ninputs];
+    for &(i, j) in wiring {
+        let noutputs = first.noutputs();
+        let output =
+            first
+                .get_output_refs()
+                .get(i)
+                .ok_or(CircuitComposeError::OutputOutOfRange {
+                    output: i,
+                    noutputs,
+                })?;
+        let source = sources
+            .get_mut(j)
+            .ok_or(CircuitComposeError::InputOutOfRange { input: j, ninputs })?;
+        if source.is_some() {
+            return Err(CircuitComposeError::InputWiredTwice(j));
+        }
+        if output.modulus() != input_modulus(j) {
+            return Err(CircuitComposeError::ModulusMismatch {
+                output: i,
+                input: j,
+                output_modulus: output.modulus(),
+                input_modulus: input_modulus(j),
+            });
+        }
+        *source = Some(i);
+    }
+
+    let mut b = CircuitBuilder::new();
+    let moduli = |refs: &[CircuitRef]| refs.iter().map(|r| r.modulus()).collect::<Vec<_>>();
+    let gb = b.garbler_inputs(&moduli(first.get_garbler_input_refs()));
+    let ev = b.evaluator_inputs(&moduli(first.get_evaluator_input_refs()));
+    let public = b.public_inputs(&moduli(first.get_public_input_refs()));
+    let outputs = eval(first, &mut b, &gb, &ev, &public)?;
+
+    let mut inputs = Vec::with_capacity(ninputs);
+    for (j, source) in sources.iter().enumerate() {
+        inputs.push(match *source {
+            Some(i) => outputs[i],
+            None if j < nsecond_garbler => b.garbler_input(input_modulus(j)),
+            None => b.evaluator_input(input_modulus(j)),
+        });
+    }
+    let public = b.public_inputs(&moduli(second.get_public_input_refs()));
+    let (gb, ev) = inputs.split_at(nsecond_garbler);
+    let outputs = eval(second, &mut b, gb, ev, &public)?;
+    b.outputs(&outputs)?;
+    let mut circ = b.finish();
+    for &width in second.get_output_groups() {
+        circ.push_output_group(width);
+    }
+    Ok(circ)
+}
+
+impl BinaryCircuit {
+    /// Compose this circuit with `other`, feeding outputs of this circuit
+    /// into inputs of `other`.
+    ///
+    /// Each pair `(i, j)` of `wiring` feeds output `i` of this circuit into
+    /// input `j` of `other`, where the inputs of `other` are numbered with its
+    /// garbler inputs first, then its evaluator inputs. An output may feed
+    /// several inputs. The composed circuit has the inputs of this circuit
+    /// followed by those of `other` that are not wired, each party's in
+    /// order, and the outputs of `other`; the outputs of this circuit are not
+    /// outputs of the composed circuit. Fails with
+    /// [`CircuitComposeError::ModulusMismatch`] if a wired output and input
+    /// have different moduli.
+    pub fn compose(
+        &self,
+        other: &Self,
+        wiring: &[(usize, usize)],
+    ) -> Result<Self, CircuitComposeError> {
+        compose(self, other, wiring, &|c, b, gb, ev, public| {
+            let mut cache = vec![None; c.gates.len()];
+            c.eval_gates_with(b, gb, ev, public, &mut cache, None)?;
+            Ok(output_wires(c, &cache)?)
+        })
+    }
+}
+
+impl ArithmeticCircuit {
+    /// Compose this circuit with `other`, feeding outputs of this circuit
+    /// into inputs of `other`.
+    ///
+    /// See [`BinaryCircuit::compose`].
+    pub fn compose(
+        &self,
+        other: &Self,
+        wiring: &[(usize, usize)],
+    ) -> Result<Self, CircuitComposeError> {
+        compose(self, other, wiring, &|c, b, gb, ev, public| {
+            let mut cache = vec![None; c.gates.len()];
+            c.eval_gates_with(b, gb, ev, public, &mut cache, None)?;
+            Ok(output_wires(c, &cache)?)
+        })
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{circuit::eval_plain, classic, util, FancyArithmetic, WireMod2};
+    use rand::{thread_rng, Rng};
+
+    fn adder64() -> BinaryCircuit {
+        include_str!("../../circuits/adder64.txt").parse().unwrap()
+    }
+
+    #[test]
+    fn test_three_operand_adder() {
+        // (x + y) + z, with the sum of the first adder as its garbler input.
+        let c = adder64();
+        let wiring = (0..64).map(|i| (i, i)).collect::<Vec<_>>();
+        let sum = c.compose(&c, &wiring).unwrap();
+        assert_eq!(sum.num_garbler_inputs(), 64);
+        assert_eq!(sum.num_evaluator_inputs(), 128);
+        assert_eq!(sum.noutputs(), 64);
+        assert_eq!(sum.get_num_nonfree_gates(), 2 * c.get_num_nonfree_gates());
+
+        let mut rng = thread_rng();
+        for _ in 0..16 {
+            let (x, y, z) = (rng.gen::<u64>(), rng.gen::<u64>(), rng.gen::<u64>());
+            let gb = util::u128_to_bits(x as u128, 64);
+            let ev = [
+                util::u128_to_bits(y as u128, 64),
+                util::u128_to_bits(z as u128, 64),
+            ]
+            .concat();
+            let out = eval_plain(&sum, &gb, &ev).unwrap();
+            let expected = x.wrapping_add(y).wrapping_add(z);
+            assert_eq!(util::u128_from_bits(&out), expected as u128);
+
+            let (en, gc) = classic::garble::<WireMod2, _>(&sum).unwrap();
+            let xs = en.encode_garbler_inputs(&gb).unwrap();
+            let ys = en.encode_evaluator_inputs(&ev).unwrap();
+            assert_eq!(gc.eval(&sum, &xs, &ys).unwrap(), out);
+        }
+    }
+
+    #[test]
+    fn test_unwired_inputs() {
+        // Feeding the sum into the evaluator's operand keeps the garbler's.
+        let c = adder64();
+        let wiring = (0..64).map(|i| (i, 64 + i)).collect::<Vec<_>>();
+        let sum = c.compose(&c, &wiring).unwrap();
+        assert_eq!(sum.num_garbler_inputs(), 128);
+        assert_eq!(sum.num_evaluator_inputs(), 64);
+        let gb = [util::u128_to_bits(3, 64), util::u128_to_bits(5, 64)].concat();
+        let ev = util::u128_to_bits(7, 64);
+        let out = eval_plain(&sum, &gb, &ev).unwrap();
+        assert_eq!(util::u128_from_bits(&out), 15);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(7);
+        let y = b.evaluator_input(7);
+        let z = b.mul(&x, &y).unwrap();
+        let w = b.proj(&z, 3, Some(vec![0, 1, 2, 0, 1, 2, 0])).unwrap();
+        b.outputs(&[z, w]).unwrap();
+        let first = b.finish();
+
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(7);
+        let y = b.evaluator_input(7);
+        let z = b.mul(&x, &y).unwrap();
+        b.outputs(&[z]).unwrap();
+        let second = b.finish();
+
+        // x * y * z
+        let c = first.compose(&second, &[(0, 0)]).unwrap();
+        assert_eq!(c.get_num_nonfree_gates(), 3);
+        assert_eq!(c.garbler_input_mod(0), 7);
+        assert_eq!(eval_plain(&c, &[3], &[4, 5]).unwrap(), [(3 * 4 * 5) % 7]);
+        // The projection has modulus 3, and the inputs of `second` 7.
+        assert!(matches!(
+            first.compose(&second, &[(1, 1)]),
+            Err(CircuitComposeError::ModulusMismatch {
+                output: 1,
+                input: 1,
+                output_modulus: 3,
+                input_modulus: 7,
+            })
+        ));
+    }
+
+    #[test]
+    fn test_bad_wiring() {
+        let c = adder64();
+        assert!(matches!(
+            c.compose(&c, &[(64, 0)]),
+            Err(CircuitComposeError::OutputOutOfRange {
+                output: 64,
+                noutputs: 64
+            })
+        ));
+        assert!(matches!(
+            c.compose(&c, &[(0, 128)]),
+            Err(CircuitComposeError::InputOutOfRange {
+                input: 128,
+                ninputs: 128
+            })
+        ));
+        assert!(matches!(
+            c.compose(&c, &[(0, 3), (1, 3)]),
+            Err(CircuitComposeError::InputWiredTwice(3))
+        ));
+    }
+}
@@ -0,0 +1,435 @@
+//! Gates defined outside the crate, with plaintext and garbled semantics of
+//! their own.
+//!
+//! Some functions have a garbled form much cheaper than the AND gates of
+//! their truth table. A [`CustomGate`] gives both: [`Dummy`], the garbler and
+//! evaluator over [`WireMod2`], and the [`twopac`](crate::twopac) parties over
+//! those run it natively, and every other [`FancyBinary`] lowers it to AND
+//! and XOR gates with [`lower_custom_gate`].
+//!
+//! [`FancyBinary::custom`] applies a gate to some wires; a
+//! [`CircuitBuilder`](super::CircuitBuilder) records it as a
+//! [`BinaryGate::Custom`](super::BinaryGate::Custom) and registers the gate
+//! on the circuit it builds:
+//!
+//! ```ignore
+//! let maj: Arc<dyn CustomGate> = Arc::new(Majority);
+//! let mut b = CircuitBuilder::<BinaryCircuit>::new();
+//! let xs = b.garbler_inputs(&[2; 3]);
+//! let z = b.custom(&maj, &xs)?;
+//! b.output(&z)?;
+//! ```
+//!
+//! [`Majority`] is the majority of three bits, which garbles to one AND gate
+//! where its truth table lowers to three.
+//!
+//! Circuits with custom gates cannot be serialized, as the code of their
+//! gates cannot.
+//!
+//! [`Dummy`]: crate::dummy::Dummy
+
+use crate::{
+    errors::FancyError, evaluate_and_gate, garble_and_gate, FancyBinary, Modulus, WireLabel,
+    WireMod2,
+};
+use scuttlebutt::Block;
+use std::sync::Arc;
+
+/// A gate on binary wires with plaintext and garbled semantics of its own.
+///
+/// The garbled form sees the labels of the gate's wires under the scheme of
+/// [the garbler](crate::Garbler): the garbler holds the zero label `W` of
+/// each wire and the global offset `Δ`, and the evaluator the label
+/// `W ⊕ v·Δ` of its value `v`.
+pub trait CustomGate: std::fmt::Debug + Send + Sync {
+    /// The name of the gate, which tells it apart from the other custom gates
+    /// of a circuit.
+    fn name(&self) -> &str;
+
+    /// The number of inputs.
+    fn arity(&self) -> usize;
+
+    /// The output of the gate on plaintext inputs.
+    fn eval(&self, inputs: &[bool]) -> bool;
+
+    /// The number of ciphertexts the garbled gate sends.
+    fn num_ciphertexts(&self) -> usize;
+
+    /// Garble the gate, given the zero labels of its inputs and `delta`.
+    ///
+    /// Returns the zero label of the output and the ciphertexts to send,
+    /// [`num_ciphertexts`](Self::num_ciphertexts) of them. `gate_num` is the
+    /// number of the gate, unique within the garbling, which the gate must
+    /// tweak its hashes with, e.g. by [`util::tweak2`](crate::util::tweak2).
+    fn garble(
+        &self,
+        inputs: &[WireMod2],
+        delta: &WireMod2,
+        gate_num: usize,
+    ) -> (WireMod2, Vec<Block>);
+
+    /// Evaluate the garbled gate, given the labels of its inputs and the
+    /// ciphertexts [`garble`](Self::garble) made for the same `gate_num`.
+    ///
+    /// Returns the label of the output.
+    fn evaluate(&self, inputs: &[WireMod2], ciphertexts: &[Block], gate_num: usize) -> WireMod2;
+}
+
+/// Fail unless `gate` takes `ninputs` inputs.
+pub(crate) fn check_arity(gate: &dyn CustomGate, ninputs: usize) -> Result<(), FancyError> {
+    if ninputs != gate.arity() {
+        return Err(FancyError::InvalidArgNum {
+            got: ninputs,
+            needed: gate.arity(),
+        });
+    }
+    Ok(())
+}
+
+/// Compute `gate` on `inputs` with the AND, XOR, and negation gates of `f`,
+/// following its truth table.
+///
+/// This is the default [`FancyBinary::custom`]. The truth table is split on
+/// the last input first, and constant halves are folded, so that a gate takes
+/// at most `2^arity - 1` AND gates, and often far fewer. Fails if the number
+/// of inputs is not the arity of the gate.
+pub fn lower_custom_gate<F: FancyBinary + ?Sized>(
+    f: &mut F,
+    gate: &dyn CustomGate,
+    inputs: &[F::Item],
+) -> Result<F::Item, F::Error> {
+    check_arity(gate, inputs.len())?;
+    let table = (0..1usize << inputs.len())
+        .map(|i| {
+            let bits = (0..inputs.len())
+                .map(|j| (i >> j) & 1 == 1)
+                .collect::<Vec<_>>();
+            gate.eval(&bits)
+        })
+        .collect::<Vec<_>>();
+    match lower(f, inputs, &table)? {
+        Bit::Wire(z) => Ok(z),
+        Bit::Const(c) => f.constant(c as Modulus, 2),
+    }
+}
+
+/// A bit of a lowered gate, folded while it is constant.
+enum Bit<T> {
+    Const(bool),
+    Wire(T),
+}
+
+/// The function of `inputs` with truth table `table`, indexed by the inputs
+/// as bits, the first least significant.
+fn lower<F: FancyBinary + ?Sized>(
+    f: &mut F,
+    inputs: &[F::Item],
+    table: &[bool],
+) -> Result<Bit<F::Item>, F::Error> {
+    let Some((x, rest)) = inputs.split_last() else {
+        return Ok(Bit::Const(table[0]));
+    };
+    let (lo, hi) = table.split_at(table.len() / 2);
+    let lo = lower(f, rest, lo)?;
+    let hi = lower(f, rest, hi)?;
+    // x ? hi : lo = lo ^ (x & (lo ^ hi))
+    let d = xor(f, &lo, &hi)?;
+    let t = match d {
+        Bit::Const(false) => Bit::Const(false),
+        Bit::Const(true) => Bit::Wire(x.clone()),
+        Bit::Wire(d) => Bit::Wire(f.and(x, &d)?),
+    };
+    xor(f, &lo, &t)
+}
+
+fn xor<F: FancyBinary + ?Sized>(
+    f: &mut F,
+    x: &Bit<F::Item>,
+    y: &Bit<F::Item>,
+) -> Result<Bit<F::Item>, F::Error> {
+    Ok(match (x, y) {
+        (Bit::Const(a), Bit::Const(b)) => Bit::Const(a ^ b),
+        (Bit::Const(false), Bit::Wire(w)) | (Bit::Wire(w), Bit::Const(false)) => {
+            Bit::Wire(w.clone())
+        }
+        (Bit::Const(true), Bit::Wire(w)) | (Bit::Wire(w), Bit::Const(true)) => {
+            Bit::Wire(f.negate(w)?)
+        }
+        (Bit::Wire(a), Bit::Wire(b)) => Bit::Wire(f.xor(a, b)?),
+    })
+}
+
+/// The majority of three bits.
+///
+/// As `maj(a, b, c) = a ⊕ ((a ⊕ b) ∧ (a ⊕ c))`, this garbles to a single
+/// half-gates AND gate of two ciphertexts, where the sum of products
+/// `ab ⊕ ac ⊕ bc` takes three.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Majority;
+
+impl CustomGate for Majority {
+    fn name(&self) -> &str {
+        "majority"
+    }
+
+    fn arity(&self) -> usize {
+        3
+    }
+
+    fn eval(&self, inputs: &[bool]) -> bool {
+        inputs.iter().filter(|&&x| x).count() >= 2
+    }
+
+    fn num_ciphertexts(&self) -> usize {
+        2
+    }
+
+    fn garble(
+        &self,
+        inputs: &[WireMod2],
+        delta: &WireMod2,
+        gate_num: usize,
+    ) -> (WireMod2, Vec<Block>) {
+        let [a, b, c] = [&inputs[0], &inputs[1], &inputs[2]];
+        let (gate0, gate1, z) = garble_and_gate(&a.plus(b), &a.plus(c), delta, gate_num);
+        (z.plus_mov(a), vec![gate0, gate1])
+    }
+
+    fn evaluate(&self, inputs: &[WireMod2], ciphertexts: &[Block], gate_num: usize) -> WireMod2 {
+        let [a, b, c] = [&inputs[0], &inputs[1], &inputs[2]];
+        let z = evaluate_and_gate(
+            &a.plus(b),
+            &a.plus(c),
+            &ciphertexts[0],
+            &ciphertexts[1],
+            gate_num,
+        );
+        z.plus_mov(a)
+    }
+}
+
+/// The custom gates of a circuit, which compare equal when their names do.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CustomGates(Vec<Arc<dyn CustomGate>>);
+
+impl CustomGates {
+    /// The index of `gate`, registering it if no gate of its name is.
+    pub(crate) fn register(&mut self, gate: &Arc<dyn CustomGate>) -> usize {
+        match self.0.iter().position(|g| g.name() == gate.name()) {
+            Some(k) => k,
+            None => {
+                self.0.push(Arc::clone(gate));
+                self.0.len() - 1
+            }
+        }
+    }
+
+    pub(crate) fn get(&self, k: usize) -> Option<&Arc<dyn CustomGate>> {
+        self.0.get(k)
+    }
+
+    pub(crate) fn as_slice(&self) -> &[Arc<dyn CustomGate>] {
+        &self.0
+    }
+}
+
+impl PartialEq for CustomGates {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.len() == other.0.len()
+            && self
+                .0
+                .iter()
+                .zip(&other.0)
+                .all(|(g, h)| g.name() == h.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{
+            eval_plain, find_counterexample, BinaryCircuit, CircuitBuilder, CircuitInfo,
+            CircuitType,
+        },
+        classic,
+        dummy::Dummy,
+        errors::CircuitValidationError,
+        util::{self, RngExt},
+        AllWire, Fancy, FancyInput,
+    };
+    use scuttlebutt::AesRng;
+
+    fn majority() -> Arc<dyn CustomGate> {
+        Arc::new(Majority)
+    }
+
+    /// An `nbits`-bit adder of the garbler's and the evaluator's inputs, with
+    /// a carry out, whose carries are majority gates, either custom or
+    /// lowered.
+    fn adder(nbits: usize, lowered: bool) -> BinaryCircuit {
+        let maj = majority();
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.garbler_inputs(&vec![2; nbits]);
+        let ys = b.evaluator_inputs(&vec![2; nbits]);
+        let z = b.xor(&xs[0], &ys[0]).unwrap();
+        b.output(&z).unwrap();
+        let mut carry = b.and(&xs[0], &ys[0]).unwrap();
+        for (x, y) in xs.iter().zip(&ys).skip(1) {
+            let z = b.xor(x, y).unwrap();
+            let z = b.xor(&z, &carry).unwrap();
+            b.output(&z).unwrap();
+            let inputs = [*x, *y, carry];
+            carry = if lowered {
+                lower_custom_gate(&mut b, maj.as_ref(), &inputs).unwrap()
+            } else {
+                b.custom(&maj, &inputs).unwrap()
+            };
+        }
+        b.output(&carry).unwrap();
+        b.finish()
+    }
+
+    fn bits(x: u128, nbits: usize) -> Vec<Modulus> {
+        util::u128_to_bits(x, nbits)
+    }
+
+    #[test]
+    fn test_plaintext() {
+        let maj = majority();
+        let mut f = Dummy::new();
+        for i in 0..8 {
+            let xs = bits(i, 3);
+            let expected = xs.iter().sum::<Modulus>() >= 2;
+            let bools = xs.iter().map(|&x| x == 1).collect::<Vec<_>>();
+            assert_eq!(Majority.eval(&bools), expected);
+            let wires = f.encode_many(&xs, &[2; 3]).unwrap();
+            let native = f.custom(&maj, &wires).unwrap();
+            let lowered = lower_custom_gate(&mut f, maj.as_ref(), &wires).unwrap();
+            assert_eq!(native.val(), expected as Modulus);
+            assert_eq!(lowered.val(), expected as Modulus);
+        }
+        let wires = f.encode_many(&[1, 1], &[2; 2]).unwrap();
+        assert!(f.custom(&maj, &wires).is_err());
+        assert!(lower_custom_gate(&mut f, maj.as_ref(), &wires).is_err());
+    }
+
+    #[test]
+    fn test_lowered_circuit() {
+        let native = adder(16, false);
+        let lowered = adder(16, true);
+        assert_eq!(native.custom_gates().len(), 1);
+        assert!(lowered.custom_gates().is_empty());
+        native.validate().unwrap();
+        // one AND gate for the first carry, then one majority gate, or three
+        // AND gates, for each of the others
+        assert_eq!(native.get_num_nonfree_gates(), 16);
+        assert_eq!(lowered.get_num_nonfree_gates(), 46);
+
+        let mut rng = AesRng::new();
+        assert_eq!(
+            find_counterexample(&native, &lowered, 100, &mut rng).unwrap(),
+            None
+        );
+        for _ in 0..16 {
+            let (x, y) = (rng.gen_u128() % (1 << 16), rng.gen_u128() % (1 << 16));
+            let out = eval_plain(&native, &bits(x, 16), &bits(y, 16)).unwrap();
+            assert_eq!(util::u128_from_bits(&out), x + y);
+        }
+
+        let stats = native.info().unwrap();
+        assert_eq!(stats.num_customs(), 15);
+        assert_eq!(stats.num_muls(), 1);
+        assert_eq!(stats.num_ciphertexts(), 32);
+        assert_eq!(lowered.info().unwrap().num_ciphertexts(), 92);
+    }
+
+    #[test]
+    fn test_garbled() {
+        let mut rng = AesRng::new();
+        let mut sizes = Vec::new();
+        for lowered in [false, true] {
+            let c = adder(16, lowered);
+            let (en, gc) = classic::garble::<WireMod2, _>(&c).unwrap();
+            sizes.push(gc.size());
+            let (en_all, gc_all) = classic::garble::<AllWire, _>(&c).unwrap();
+            for _ in 0..16 {
+                let (x, y) = (rng.gen_u128() % (1 << 16), rng.gen_u128() % (1 << 16));
+                let xs = en.encode_garbler_inputs(&bits(x, 16)).unwrap();
+                let ys = en.encode_evaluator_inputs(&bits(y, 16)).unwrap();
+                let out = gc.eval(&c, &xs, &ys).unwrap();
+                assert_eq!(util::u128_from_bits(&out), x + y, "lowered={}", lowered);
+                // AllWire lowers majority gates on both sides
+                let xs = en_all.encode_garbler_inputs(&bits(x, 16)).unwrap();
+                let ys = en_all.encode_evaluator_inputs(&bits(y, 16)).unwrap();
+                let out = gc_all.eval(&c, &xs, &ys).unwrap();
+                assert_eq!(util::u128_from_bits(&out), x + y, "lowered={}", lowered);
+            }
+        }
+        // two rows for each of the 15 majority gates, rather than six
+        assert_eq!(sizes[1] - sizes[0], 60);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_twopac() {
+        use crate::twopac::semihonest::{OutputMode, SemiHonest};
+        use scuttlebutt::{unix_channel_pair, AbstractChannel};
+
+        let mut rng = AesRng::new();
+        let (x, y) = (rng.gen_u128() % (1 << 32), rng.gen_u128() % (1 << 32));
+        let config = SemiHonest::builder().output_mode(OutputMode::Both);
+        let gb_config = config.clone();
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let c = adder(32, false);
+            let mut gb = gb_config
+                .build_garbler::<_, _, WireMod2>(sender, AesRng::new())
+                .unwrap();
+            let xs = gb.encode_many(&bits(x, 32), &[2; 32]).unwrap();
+            let ys = gb.receive_many(&[2; 32]).unwrap();
+            let out = gb.eval_circuit(&c, &xs, &ys).unwrap().unwrap();
+            gb.get_channel().flush().unwrap();
+            out
+        });
+        let c = adder(32, false);
+        let mut ev = config
+            .build_evaluator::<_, _, WireMod2>(receiver, AesRng::new())
+            .unwrap();
+        let xs = ev.receive_many(&[2; 32]).unwrap();
+        let ys = ev.encode_many(&bits(y, 32), &[2; 32]).unwrap();
+        let out = ev.eval_circuit(&c, &xs, &ys).unwrap();
+        assert_eq!(util::u128_from_bits(&out), x + y);
+        assert_eq!(handle.join().unwrap(), out);
+    }
+
+    #[test]
+    fn test_registration() {
+        let maj = majority();
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.garbler_inputs(&[2; 3]);
+        assert!(b.custom(&maj, &xs[..2]).is_err());
+        let z = b.custom(&maj, &xs).unwrap();
+        // the same computation twice, under a gate registered once
+        let w = b.custom(&majority(), &xs).unwrap();
+        b.outputs(&[z, w]).unwrap();
+        let mut c = b.finish();
+        assert_eq!(c.custom_gates().len(), 1);
+        assert_eq!(c.dedup_gates().unwrap(), 1);
+        assert_eq!(c.get_num_nonfree_gates(), 1);
+        assert_eq!(eval_plain(&c, &[1, 0, 1], &[]).unwrap(), [1, 1]);
+
+        #[cfg(feature = "serde")]
+        assert!(serde_json::to_string(&c).is_err());
+
+        let digest = c.digest();
+        let mut unregistered = c.clone();
+        unregistered.custom_gates = CustomGates::default();
+        assert_ne!(unregistered.digest(), digest);
+        assert!(matches!(
+            unregistered.validate(),
+            Err(CircuitValidationError::InvalidCustomGate(3))
+        ));
+        assert!(eval_plain(&unregistered, &[1, 0, 1], &[]).is_err());
+    }
+}
@@ -0,0 +1,520 @@
+//! Elimination of duplicate gates.
+//!
+//! Two gates are duplicates when they compute the same operation on the same
+//! values: the same kind of gate, with the same constant, truth table, and
+//! modulus, reading wires last written by the same gates. Operands of XOR,
+//! AND, and addition gates, and of multiplications of wires of equal
+//! moduli, are compared as unordered pairs.
+//!
+//! The deduplicated circuit writes the output of every gate to its own index,
+//! so wires are never reused, and numbers its garbled gates from zero in
+//! order, as [`CircuitBuilder`](super::CircuitBuilder) does.
+
+use super::{ArithmeticCircuit, ArithmeticGate, BinaryCircuit, BinaryGate, CircuitRef};
+use crate::{errors::CircuitValidationError, Modulus};
+use std::collections::HashMap;
+
+/// The operation a gate computes, on the gates of the deduplicated circuit
+/// that last wrote its operands.
+#[derive(PartialEq, Eq, Hash)]
+enum GateKey<'a> {
+    Constant {
+        val: Modulus,
+        q: Modulus,
+    },
+    Unary {
+        op: Op,
+        x: usize,
+        c: Modulus,
+        q: Modulus,
+    },
+    Binary {
+        op: Op,
+        x: usize,
+        y: usize,
+        q: Modulus,
+    },
+    Proj {
+        x: usize,
+        tt: &'a [Modulus],
+        q: Modulus,
+    },
+    Custom {
+        gate: usize,
+        inputs: Vec<usize>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum Op {
+    Xor,
+    And,
+    Inv,
+    Add,
+    Sub,
+    Cmul,
+    Mul,
+}
+
+impl GateKey<'_> {
+    /// The key of a gate whose operands may be swapped.
+    fn commutative(op: Op, x: usize, y: usize, q: Modulus) -> Self {
+        GateKey::Binary {
+            op,
+            x: x.min(y),
+            y: x.max(y),
+            q,
+        }
+    }
+}
+
+/// The state of a deduplication pass over the gates of a circuit.
+struct Dedup<'a, G> {
+    // for each wire of the original circuit, the gate of the new circuit that
+    // last wrote it.
+    defs: Vec<usize>,
+    canonical: HashMap<GateKey<'a>, usize>,
+    gates: Vec<G>,
+    nonfree: usize,
+}
+
+impl<'a, G> Dedup<'a, G> {
+    fn new(ngates: usize) -> Self {
+        Dedup {
+            defs: vec![usize::MAX; ngates],
+            canonical: HashMap::new(),
+            gates: Vec::with_capacity(ngates),
+            nonfree: 0,
+        }
+    }
+
+    /// The reference in the new circuit for `r`.
+    fn map(&self, r: CircuitRef) -> CircuitRef {
+        CircuitRef {
+            ix: self.defs[r.ix],
+            modulus: r.modulus,
+        }
+    }
+
+    /// The next id of a garbled gate.
+    fn next_id(&mut self) -> usize {
+        self.nonfree += 1;
+        self.nonfree - 1
+    }
+
+    /// Write wire `z` with the gate of key `key` if there is one already, and
+    /// otherwise with the gate that `make` builds, which must not have an
+    /// output wire of its own. Returns whether a gate was added.
+    fn push(
+        &mut self,
+        z: usize,
+        key: Option<GateKey<'a>>,
+        make: impl FnOnce(&mut Self) -> G,
+    ) -> bool {
+        if let Some(&j) = key.as_ref().and_then(|k| self.canonical.get(k)) {
+            self.defs[z] = j;
+            return false;
+        }
+        let gate = make(self);
+        let j = self.gates.len();
+        self.gates.push(gate);
+        if let Some(k) = key {
+            self.canonical.insert(k, j);
+        }
+        self.defs[z] = j;
+        true
+    }
+
+    /// Map `refs` into the new circuit, dropping repeated references if
+    /// `dedup` is set.
+    fn map_refs(&self, refs: &[CircuitRef], dedup: bool) -> Vec<CircuitRef> {
+        let mut seen = vec![false; if dedup { self.gates.len() } else { 0 }];
+        refs.iter()
+            .map(|&r| self.map(r))
+            .filter(|r| !dedup || !std::mem::replace(&mut seen[r.ix], true))
+            .collect()
+    }
+}
+
+impl BinaryCircuit {
+    /// Remove gates that duplicate an earlier gate, rewriting the gates that
+    /// read them to read the earlier gate instead, and return the number of
+    /// gates removed.
+    ///
+    /// Afterwards every gate writes its output to its own index, and the AND
+    /// gates are numbered from zero. Fails, leaving the circuit unchanged, if
+    /// it does not [`validate`](Self::validate).
+    pub fn dedup_gates(&mut self) -> Result<usize, CircuitValidationError> {
+        self.validate()?;
+        let mut d = Dedup::new(self.gates.len());
+        for (i, gate) in self.gates.iter().enumerate() {
+            let (_, z) = gate.wires(i);
+            match *gate {
+                BinaryGate::GarblerInput { .. }
+                | BinaryGate::EvaluatorInput { .. }
+                | BinaryGate::PublicInput { .. } => d.push(z, None, |_| gate.clone()),
+                BinaryGate::Constant { val } => {
+                    d.push(z, Some(GateKey::Constant { val, q: 2 }), |_| gate.clone())
+                }
+                BinaryGate::Xor { xref, yref, .. } => {
+                    let (xref, yref) = (d.map(xref), d.map(yref));
+                    let key = GateKey::commutative(Op::Xor, xref.ix, yref.ix, 2);
+                    d.push(z, Some(key), |_| BinaryGate::Xor {
+                        xref,
+                        yref,
+                        out: None,
+                    })
+                }
+                BinaryGate::And { xref, yref, .. } => {
+                    let (xref, yref) = (d.map(xref), d.map(yref));
+                    let key = GateKey::commutative(Op::And, xref.ix, yref.ix, 2);
+                    d.push(z, Some(key), |d| BinaryGate::And {
+                        xref,
+                        yref,
+                        id: d.next_id(),
+                        out: None,
+                    })
+                }
+                BinaryGate::Inv { xref, .. } => {
+                    let xref = d.map(xref);
+                    let key = GateKey::Unary {
+                        op: Op::Inv,
+                        x: xref.ix,
+                        c: 0,
+                        q: 2,
+                    };
+                    d.push(z, Some(key), |_| BinaryGate::Inv { xref, out: None })
+                }
+                BinaryGate::Custom {
+                    gate, ref inputs, ..
+                } => {
+                    let inputs = inputs.iter().map(|&r| d.map(r)).collect::<Vec<_>>();
+                    let key = GateKey::Custom {
+                        gate,
+                        inputs: inputs.iter().map(|r| r.ix).collect(),
+                    };
+                    d.push(z, Some(key), |d| BinaryGate::Custom {
+                        gate,
+                        inputs,
+                        id: d.next_id(),
+                        out: None,
+                    })
+                }
+            };
+        }
+        let removed = self.gates.len() - d.gates.len();
+        self.digest.clear();
+        self.garbler_input_refs = d.map_refs(&self.garbler_input_refs, false);
+        self.evaluator_input_refs = d.map_refs(&self.evaluator_input_refs, false);
+        self.public_input_refs = d.map_refs(&self.public_input_refs, false);
+        self.const_refs = d.map_refs(&self.const_refs, true);
+        self.output_refs = d.map_refs(&self.output_refs, false);
+        self.num_nonfree_gates = d.nonfree;
+        self.gates = d.gates;
+        Ok(removed)
+    }
+}
+
+impl ArithmeticCircuit {
+    /// Remove gates that duplicate an earlier gate, rewriting the gates that
+    /// read them to read the earlier gate instead, and return the number of
+    /// gates removed.
+    ///
+    /// Afterwards every gate writes its output to its own index, and the
+    /// multiplication and projection gates are numbered from zero. Fails,
+    /// leaving the circuit unchanged, if it does not
+    /// [`validate`](Self::validate).
+    pub fn dedup_gates(&mut self) -> Result<usize, CircuitValidationError> {
+        self.validate()?;
+        let mut d = Dedup::new(self.gates.len());
+        let mut moduli = Vec::with_capacity(self.gates.len());
+        for (i, (gate, &q)) in self.gates.iter().zip(&self.gate_moduli).enumerate() {
+            let (_, z) = gate.wires(i);
+            let added = match *gate {
+                ArithmeticGate::GarblerInput { .. }
+                | ArithmeticGate::EvaluatorInput { .. }
+                | ArithmeticGate::PublicInput { .. } => d.push(z, None, |_| gate.clone()),
+                ArithmeticGate::Constant { val } => {
+                    d.push(z, Some(GateKey::Constant { val, q }), |_| gate.clone())
+                }
+                ArithmeticGate::Add { xref, yref, .. } => {
+                    let (xref, yref) = (d.map(xref), d.map(yref));
+                    let key = GateKey::commutative(Op::Add, xref.ix, yref.ix, q);
+                    d.push(z, Some(key), |_| ArithmeticGate::Add {
+                        xref,
+                        yref,
+                        out: None,
+                    })
+                }
+                ArithmeticGate::Sub { xref, yref, .. } => {
+                    let (xref, yref) = (d.map(xref), d.map(yref));
+                    let key = GateKey::Binary {
+                        op: Op::Sub,
+                        x: xref.ix,
+                        y: yref.ix,
+                        q,
+                    };
+                    d.push(z, Some(key), |_| ArithmeticGate::Sub {
+                        xref,
+                        yref,
+                        out: None,
+                    })
+                }
+                ArithmeticGate::Cmul { xref, c, .. } => {
+                    let xref = d.map(xref);
+                    let key = GateKey::Unary {
+                        op: Op::Cmul,
+                        x: xref.ix,
+                        c,
+                        q,
+                    };
+                    d.push(z, Some(key), |_| ArithmeticGate::Cmul {
+                        xref,
+                        c,
+                        out: None,
+                    })
+                }
+                ArithmeticGate::Mul { xref, yref, .. } => {
+                    let (xref, yref) = (d.map(xref), d.map(yref));
+                    // garbling a multiplication depends on the order of
+                    // operands of different moduli
+                    let key = if xref.modulus == yref.modulus {
+                        GateKey::commutative(Op::Mul, xref.ix, yref.ix, q)
+                    } else {
+                        GateKey::Binary {
+                            op: Op::Mul,
+                            x: xref.ix,
+                            y: yref.ix,
+                            q,
+                        }
+                    };
+                    d.push(z, Some(key), |d| ArithmeticGate::Mul {
+                        xref,
+                        yref,
+                        id: d.next_id(),
+                        out: None,
+                    })
+                }
+                ArithmeticGate::Proj { xref, ref tt, .. } => {
+                    let xref = d.map(xref);
+                    let key = GateKey::Proj { x: xref.ix, tt, q };
+                    d.push(z, Some(key), |d| ArithmeticGate::Proj {
+                        xref,
+                        tt: tt.clone(),
+                        id: d.next_id(),
+                        out: None,
+                    })
+                }
+            };
+            if added {
+                moduli.push(q);
+            }
+        }
+        let removed = self.gates.len() - d.gates.len();
+        self.digest.clear();
+        self.garbler_input_refs = d.map_refs(&self.garbler_input_refs, false);
+        self.evaluator_input_refs = d.map_refs(&self.evaluator_input_refs, false);
+        self.public_input_refs = d.map_refs(&self.public_input_refs, false);
+        self.const_refs = d.map_refs(&self.const_refs, true);
+        self.output_refs = d.map_refs(&self.output_refs, false);
+        self.num_nonfree_gates = d.nonfree;
+        self.gates = d.gates;
+        self.gate_moduli = moduli;
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{eval_plain, CircuitBuilder, CircuitType},
+        util::RngExt,
+        Fancy, FancyArithmetic, FancyBinary,
+    };
+    use rand::{thread_rng, Rng};
+
+    fn random_inputs<C: CircuitType>(c: &C, rng: &mut impl Rng) -> (Vec<Modulus>, Vec<Modulus>) {
+        let gb = (0..c.num_garbler_inputs())
+            .map(|i| rng.gen_residue(c.garbler_input_mod(i)))
+            .collect();
+        let ev = (0..c.num_evaluator_inputs())
+            .map(|i| rng.gen_residue(c.evaluator_input_mod(i)))
+            .collect();
+        (gb, ev)
+    }
+
+    /// Check that deduplicating `c` keeps a valid circuit computing the same
+    /// function, and return the number of gates removed.
+    fn check_equivalent<C>(c: &C, dedup: impl Fn(&mut C) -> usize) -> usize
+    where
+        C: CircuitType + crate::circuit::EvaluableCircuit<crate::dummy::Dummy>,
+    {
+        let mut rng = thread_rng();
+        let mut d = c.clone();
+        let removed = dedup(&mut d);
+        for _ in 0..16 {
+            let (gb, ev) = random_inputs(c, &mut rng);
+            assert_eq!(
+                eval_plain(&d, &gb, &ev).unwrap(),
+                eval_plain(c, &gb, &ev).unwrap()
+            );
+        }
+        removed
+    }
+
+    #[test]
+    fn binary_equivalence() {
+        let mut rng = thread_rng();
+        for _ in 0..16 {
+            let mut b = CircuitBuilder::<BinaryCircuit>::new();
+            let mut wires = b.garbler_inputs(&[2; 8]);
+            wires.extend(b.evaluator_inputs(&[2; 8]));
+            wires.push(b.constant(1, 2).unwrap());
+            let mut ops = Vec::new();
+            for _ in 0..200 {
+                // repeat an earlier gate, with its operands swapped, half the time
+                let (op, x, y) = match ops.len() {
+                    n if n > 0 && RngExt::gen_bool(&mut rng) => {
+                        let (op, x, y) = ops[rng.gen_usize() % n];
+                        (op, y, x)
+                    }
+                    _ => (
+                        rng.gen_usize() % 3,
+                        wires[rng.gen_usize() % wires.len()],
+                        wires[rng.gen_usize() % wires.len()],
+                    ),
+                };
+                ops.push((op, x, y));
+                let z = match op {
+                    0 => b.xor(&x, &y).unwrap(),
+                    1 => b.and(&x, &y).unwrap(),
+                    _ => b.negate(&x).unwrap(),
+                };
+                wires.push(z);
+            }
+            for z in &wires[wires.len() - 16..] {
+                b.output(z).unwrap();
+            }
+            let c = b.finish();
+            let removed = check_equivalent(&c, |c| c.dedup_gates().unwrap());
+            assert!(removed > 0);
+        }
+    }
+
+    #[test]
+    fn arithmetic_equivalence() {
+        let mut rng = thread_rng();
+        for _ in 0..16 {
+            let q = rng.gen_modulus();
+            let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+            let mut wires = b.garbler_inputs(&[q; 4]);
+            wires.extend(b.evaluator_inputs(&[q; 4]));
+            wires.push(b.constant(rng.gen_residue(q), q).unwrap());
+            let tt = (0..q).map(|_| rng.gen_residue(q)).collect::<Vec<_>>();
+            let c = rng.gen_residue(q);
+            let mut ops = Vec::new();
+            for _ in 0..100 {
+                let (op, x, y) = match ops.len() {
+                    n if n > 0 && RngExt::gen_bool(&mut rng) => ops[rng.gen_usize() % n],
+                    _ => (
+                        rng.gen_usize() % 5,
+                        wires[rng.gen_usize() % wires.len()],
+                        wires[rng.gen_usize() % wires.len()],
+                    ),
+                };
+                ops.push((op, x, y));
+                let z = match op {
+                    0 => b.add(&x, &y).unwrap(),
+                    1 => b.sub(&x, &y).unwrap(),
+                    2 => b.cmul(&x, c).unwrap(),
+                    3 => b.mul(&x, &y).unwrap(),
+                    _ => b.proj(&x, q, Some(tt.clone())).unwrap(),
+                };
+                wires.push(z);
+            }
+            for z in &wires[wires.len() - 8..] {
+                b.output(z).unwrap();
+            }
+            let c = b.finish();
+            let removed = check_equivalent(&c, |c| c.dedup_gates().unwrap());
+            assert!(removed > 0);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn aes_equivalence() {
+        let c = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap();
+        check_equivalent(&c, |c| c.dedup_gates().unwrap());
+        let mut d = c.clone();
+        d.dedup_gates().unwrap();
+        d.validate().unwrap();
+        assert!(d.gates.len() <= c.gates.len());
+        // a deduplicated circuit has no duplicates left
+        assert_eq!(d.clone().dedup_gates().unwrap(), 0);
+    }
+
+    #[test]
+    fn known_duplication() {
+        // 8 AND gates, each duplicated with its operands swapped, and an XOR
+        // of each AND gate with its duplicate: 8 of 40 gates are duplicates.
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.garbler_inputs(&[2; 8]);
+        let ys = b.evaluator_inputs(&[2; 8]);
+        for (x, y) in xs.iter().zip(&ys) {
+            let z = b.and(x, y).unwrap();
+            let w = b.and(y, x).unwrap();
+            let v = b.xor(&z, &w).unwrap();
+            b.output(&w).unwrap();
+            b.output(&v).unwrap();
+        }
+        let mut c = b.finish();
+        assert_eq!(c.gates.len(), 40);
+        assert_eq!(c.num_nonfree_gates, 16);
+        let removed = check_equivalent(&c, |c| c.dedup_gates().unwrap());
+        assert_eq!(removed, 8);
+
+        c.dedup_gates().unwrap();
+        assert_eq!(c.gates.len(), 32);
+        assert_eq!(c.num_nonfree_gates, 8);
+        let ids = c
+            .gates
+            .iter()
+            .filter_map(|g| match *g {
+                BinaryGate::And { id, .. } => Some(id),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ids, (0..8).collect::<Vec<_>>());
+        // the outputs read the surviving AND gates
+        for (k, r) in c.output_refs.iter().step_by(2).enumerate() {
+            assert!(matches!(c.gates[r.ix], BinaryGate::And { id, .. } if id == k));
+        }
+    }
+
+    #[test]
+    fn invalid_circuit() {
+        let mut c = BinaryCircuit {
+            gates: vec![BinaryGate::Inv {
+                xref: CircuitRef { ix: 0, modulus: 2 },
+                out: None,
+            }],
+            garbler_input_refs: vec![],
+            evaluator_input_refs: vec![],
+            public_input_refs: vec![],
+            const_refs: vec![],
+            output_refs: vec![],
+            output_groups: vec![],
+            num_nonfree_gates: 0,
+            custom_gates: Default::default(),
+            digest: Default::default(),
+        };
+        let before = c.clone();
+        assert!(c.dedup_gates().is_err());
+        assert_eq!(c, before);
+    }
+}
@@ -0,0 +1,104 @@
+//! Testing whether two circuits compute the same function.
+//!
+//! Circuits are compared by evaluating both in plaintext on the same inputs:
+//! all zeros, all maximal, and then random ones. This finds any difference
+//! that random inputs hit with reasonable probability, but proves nothing
+//! when it finds none.
+
+use super::{eval_plain, CircuitType, EvaluableCircuit};
+use crate::{
+    dummy::Dummy,
+    errors::{DummyError, FancyError},
+    util::RngExt,
+    Modulus,
+};
+use rand::Rng;
+
+/// Inputs on which two circuits compute different outputs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Counterexample {
+    /// The garbler's inputs.
+    pub garbler_inputs: Vec<Modulus>,
+    /// The evaluator's inputs.
+    pub evaluator_inputs: Vec<Modulus>,
+    /// The outputs of the first circuit.
+    pub left: Vec<Modulus>,
+    /// The outputs of the second circuit.
+    pub right: Vec<Modulus>,
+}
+
+/// Check that `b` takes inputs of the same number and moduli as `a`.
+fn check_interface<C: CircuitType>(a: &C, b: &C) -> Result<(), FancyError> {
+    let (n, m) = (a.num_garbler_inputs(), b.num_garbler_inputs());
+    if m < n {
+        return Err(FancyError::NotEnoughGarblerInputs { got: m, needed: n });
+    }
+    if m > n {
+        return Err(FancyError::TooManyGarblerInputs { got: m, needed: n });
+    }
+    let (n, m) = (a.num_evaluator_inputs(), b.num_evaluator_inputs());
+    if m < n {
+        return Err(FancyError::NotEnoughEvaluatorInputs { got: m, needed: n });
+    }
+    if m > n {
+        return Err(FancyError::TooManyEvaluatorInputs { got: m, needed: n });
+    }
+    for id in 0..a.num_garbler_inputs() {
+        let (needed, got) = (a.garbler_input_mod(id), b.garbler_input_mod(id));
+        if got != needed {
+            return Err(FancyError::GarblerInputModulus { id, got, needed });
+        }
+    }
+    for id in 0..a.num_evaluator_inputs() {
+        let (needed, got) = (a.evaluator_input_mod(id), b.evaluator_input_mod(id));
+        if got != needed {
+            return Err(FancyError::EvaluatorInputModulus { id, got, needed });
+        }
+    }
+    Ok(())
+}
+
+/// Search for inputs on which `a` and `b` compute different outputs, trying
+/// all zeros, all maximal values, and `trials` random inputs.
+///
+/// Returns `None` if the circuits agree on every input tried. Fails if `b`
+/// does not take inputs of the same number and moduli as `a`, or if either
+/// circuit fails to evaluate.
+pub fn find_counterexample<C: EvaluableCircuit<Dummy>>(
+    a: &C,
+    b: &C,
+    trials: usize,
+    rng: &mut impl Rng,
+) -> Result<Option<Counterexample>, DummyError> {
+    check_interface(a, b)?;
+    let gb_mods = (0..a.num_garbler_inputs())
+        .map(|i| a.garbler_input_mod(i))
+        .collect::<Vec<_>>();
+    let ev_mods = (0..a.num_evaluator_inputs())
+        .map(|i| a.evaluator_input_mod(i))
+        .collect::<Vec<_>>();
+    let zeros = |mods: &[Modulus]| vec![0; mods.len()];
+    let maxes = |mods: &[Modulus]| mods.iter().map(|q| q - 1).collect::<Vec<_>>();
+    let fixed = [
+        (zeros(&gb_mods), zeros(&ev_mods)),
+        (maxes(&gb_mods), maxes(&ev_mods)),
+    ];
+    let random = (0..trials).map(|_| {
+        let mut sample =
+            |mods: &[Modulus]| mods.iter().map(|&q| rng.gen_residue(q)).collect::<Vec<_>>();
+        (sample(&gb_mods), sample(&ev_mods))
+    });
+    for (gb, ev) in fixed.into_iter().chain(random) {
+        let left = eval_plain(a, &gb, &ev)?;
+        let right = eval_plain(b, &gb, &ev)?;
+        if left != right {
+            return Ok(Some(Counterexample {
+                garbler_inputs: gb,
+                evaluator_inputs: ev,
+                left,
+                right,
+            }));
+        }
+    }
+    Ok(None)
+}
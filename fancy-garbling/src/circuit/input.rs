@@ -0,0 +1,166 @@
+//! Conversion of typed values into circuit input bits.
+
+use crate::{errors::FancyError, Modulus};
+
+/// A value that can be fed to a binary circuit as a sequence of input bits.
+///
+/// Bits are written most significant first, and sequences are written in
+/// order, so that a byte array is written byte by byte, each byte most
+/// significant bit first. This matches the in-tree Bristol circuits (e.g.
+/// `circuits/AES-non-expanded.txt`), and means that an integer is written the
+/// same way as its big-endian bytes. Signed integers are written in two's
+/// complement.
+///
+/// That is, values are written in [`BitOrder::MsbFirst`](crate::util::BitOrder).
+/// Circuits built with [`CircuitBuilder`](super::CircuitBuilder) represent
+/// binary numbers least significant bit first, so their inputs should be
+/// made with [`CircuitBuilder::bin_garbler_input_ordered`](super::CircuitBuilder::bin_garbler_input_ordered)
+/// and its evaluator counterpart.
+pub trait CircuitInput {
+    /// Append the bits of `self` to `out`, each as a `Modulus` that is 0 or 1.
+    fn to_bits(&self, out: &mut Vec<Modulus>);
+}
+
+impl CircuitInput for bool {
+    fn to_bits(&self, out: &mut Vec<Modulus>) {
+        out.push(*self as Modulus);
+    }
+}
+
+macro_rules! impl_circuit_input_int {
+    ($($t:ty),*) => {$(
+        impl CircuitInput for $t {
+            fn to_bits(&self, out: &mut Vec<Modulus>) {
+                let nbits = <$t>::BITS;
+                out.extend((0..nbits).rev().map(|i| ((*self >> i) & 1) as Modulus));
+            }
+        }
+    )*};
+}
+
+impl_circuit_input_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl<T: CircuitInput> CircuitInput for [T] {
+    fn to_bits(&self, out: &mut Vec<Modulus>) {
+        for x in self {
+            x.to_bits(out);
+        }
+    }
+}
+
+impl<T: CircuitInput, const N: usize> CircuitInput for [T; N] {
+    fn to_bits(&self, out: &mut Vec<Modulus>) {
+        self[..].to_bits(out)
+    }
+}
+
+impl<T: CircuitInput> CircuitInput for Vec<T> {
+    fn to_bits(&self, out: &mut Vec<Modulus>) {
+        self[..].to_bits(out)
+    }
+}
+
+impl<T: CircuitInput + ?Sized> CircuitInput for &T {
+    fn to_bits(&self, out: &mut Vec<Modulus>) {
+        (**self).to_bits(out)
+    }
+}
+
+macro_rules! impl_circuit_input_tuple {
+    ($(($($t:ident $i:tt),+))*) => {$(
+        impl<$($t: CircuitInput),+> CircuitInput for ($($t,)+) {
+            fn to_bits(&self, out: &mut Vec<Modulus>) {
+                $(self.$i.to_bits(out);)+
+            }
+        }
+    )*};
+}
+
+impl_circuit_input_tuple! {
+    (A 0)
+    (A 0, B 1)
+    (A 0, B 1, C 2)
+    (A 0, B 1, C 2, D 3)
+    (A 0, B 1, C 2, D 3, E 4)
+    (A 0, B 1, C 2, D 3, E 4, F 5)
+}
+
+/// Concatenate the bits of `parts`, checking that they fill `ninputs` binary
+/// inputs whose moduli are given by `modulus`.
+pub(super) fn assemble(
+    parts: &[&dyn CircuitInput],
+    ninputs: usize,
+    modulus: impl Fn(usize) -> Modulus,
+) -> Result<Vec<Modulus>, FancyError> {
+    let mut bits = Vec::with_capacity(ninputs);
+    for part in parts {
+        part.to_bits(&mut bits);
+    }
+    if bits.len() != ninputs {
+        return Err(FancyError::InvalidArgNum {
+            got: bits.len(),
+            needed: ninputs,
+        });
+    }
+    if let Some(q) = (0..ninputs).map(modulus).find(|&q| q != 2) {
+        return Err(FancyError::InvalidArgMod { got: q, needed: 2 });
+    }
+    Ok(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{ArithmeticCircuit, CircuitBuilder, CircuitType},
+        Fancy,
+    };
+
+    fn bits(x: &dyn CircuitInput) -> String {
+        let mut out = Vec::new();
+        x.to_bits(&mut out);
+        out.iter().map(|b| b.to_string()).collect()
+    }
+
+    #[test]
+    fn bit_order() {
+        assert_eq!(bits(&true), "1");
+        assert_eq!(bits(&0x81u8), "10000001");
+        assert_eq!(bits(&0x1234u16), "0001001000110100");
+        assert_eq!(bits(&-2i8), "11111110");
+        assert_eq!(bits(&0xdead_beefu32), bits(&0xdead_beefu32.to_be_bytes()));
+        assert_eq!(bits(&u128::MAX), "1".repeat(128));
+        assert_eq!(bits(&[0x01u8, 0x80]), "0000000110000000");
+        assert_eq!(bits(&vec![true, false]), "10");
+        assert_eq!(bits(&&[0x0fu8][..]), "00001111");
+        assert_eq!(
+            bits(&(0x1u8, [true; 3], 0x8000u16)),
+            "000000011111000000000000000"
+        );
+    }
+
+    #[test]
+    fn assemble_checks_width() {
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let xs = b.garbler_inputs(&[2; 20]);
+        let ys = b.evaluator_inputs(&[2, 3]);
+        b.outputs(&xs).unwrap();
+        b.outputs(&ys).unwrap();
+        let c = b.finish();
+
+        let gb = c.assemble_garbler_inputs(&[&0xabcu16, &[true; 4]]).unwrap();
+        assert_eq!(gb.len(), 20);
+        assert_eq!(gb[4..16], [1, 0, 1, 0, 1, 0, 1, 1, 1, 1, 0, 0]);
+        assert!(matches!(
+            c.assemble_garbler_inputs(&[&0xabcu16]),
+            Err(FancyError::InvalidArgNum {
+                got: 16,
+                needed: 20
+            })
+        ));
+        assert!(matches!(
+            c.assemble_evaluator_inputs(&[&true, &false]),
+            Err(FancyError::InvalidArgMod { got: 3, needed: 2 })
+        ));
+    }
+}
@@ -0,0 +1,528 @@
+//! The topological levels of a circuit, and plaintext evaluation level by
+//! level.
+//!
+//! The gates of a level read only values computed by earlier levels, so they
+//! can be evaluated in any order, or at once. Each gate's value is kept by
+//! the index of the gate rather than of the wire it writes, so that gates
+//! writing a wire that a gate of the same level reads, as parsed circuits
+//! do, do not interfere.
+
+use super::{
+    check_constant, check_input_wires, encode_plain, evaluator_input, garbler_input, public_input,
+    ArithmeticCircuit, ArithmeticGate, BinaryCircuit, BinaryGate, CircuitRef, CircuitType,
+    GateInputs,
+};
+use crate::{
+    dummy::{Dummy, DummyVal},
+    errors::{DummyError, FancyError},
+    util, Fancy, FancyArithmetic, FancyBinary, Modulus,
+};
+
+/// The gates of a circuit grouped into topological levels.
+///
+/// Inputs and constants are on level 0, and every other gate is on the level
+/// after the latest level of a gate whose value it reads. Computed by
+/// [`CircuitType::levels`], and used by
+/// [`BinaryCircuit::eval_plain_parallel`] and
+/// [`ArithmeticCircuit::eval_plain_parallel`].
+#[derive(Clone, Debug)]
+pub struct Levels {
+    /// The gates, level by level, each level in the order of the circuit.
+    order: Vec<usize>,
+    /// The start of each level in `order`, and then the number of gates.
+    starts: Vec<usize>,
+    /// For each gate, the gates whose values it reads, in the order of its
+    /// inputs, or `usize::MAX` for a wire no gate has written.
+    sources: Vec<usize>,
+    /// The start of the sources of each gate in `sources`, and then the
+    /// number of sources.
+    source_starts: Vec<usize>,
+    /// For each output, the gate computing it, or `usize::MAX`.
+    outputs: Vec<usize>,
+}
+
+impl Levels {
+    /// Compute the levels from the wires read and written by each gate, in
+    /// order.
+    pub(super) fn new<'a>(
+        ngates: usize,
+        wires: impl Iterator<Item = (GateInputs<'a>, usize)>,
+        outputs: &[CircuitRef],
+    ) -> Self {
+        // the gate currently writing each wire
+        let mut defs = vec![usize::MAX; ngates];
+        let mut sources = Vec::with_capacity(2 * ngates);
+        let mut source_starts = Vec::with_capacity(ngates + 1);
+        let mut level = Vec::with_capacity(ngates);
+        for (i, (inputs, z)) in wires.enumerate() {
+            source_starts.push(sources.len());
+            let mut l = 0;
+            for ix in inputs.iter() {
+                let def = defs.get(ix).copied().unwrap_or(usize::MAX);
+                if def != usize::MAX {
+                    l = l.max(level[def] + 1);
+                }
+                sources.push(def);
+            }
+            level.push(l);
+            defs[z] = i;
+        }
+        source_starts.push(sources.len());
+
+        // sort the gates by level, keeping their order within each level
+        let nlevels = level.iter().max().map_or(0, |&l| l + 1);
+        let mut starts = vec![0; nlevels + 1];
+        for &l in &level {
+            starts[l + 1] += 1;
+        }
+        for l in 0..nlevels {
+            starts[l + 1] += starts[l];
+        }
+        let mut next = starts.clone();
+        let mut order = vec![0; level.len()];
+        for (i, &l) in level.iter().enumerate() {
+            order[next[l]] = i;
+            next[l] += 1;
+        }
+
+        let outputs = outputs
+            .iter()
+            .map(|r| defs.get(r.ix).copied().unwrap_or(usize::MAX))
+            .collect();
+        Levels {
+            order,
+            starts,
+            sources,
+            source_starts,
+            outputs,
+        }
+    }
+
+    /// The number of gates of the circuit.
+    pub fn ngates(&self) -> usize {
+        self.order.len()
+    }
+
+    /// The number of levels.
+    pub fn nlevels(&self) -> usize {
+        self.starts.len() - 1
+    }
+
+    /// The gates of level `l`, in the order of the circuit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `l` is not less than [`Levels::nlevels`].
+    pub fn level(&self, l: usize) -> &[usize] {
+        &self.order[self.starts[l]..self.starts[l + 1]]
+    }
+
+    /// The number of gates of the widest level.
+    pub fn width(&self) -> usize {
+        self.starts
+            .windows(2)
+            .map(|w| w[1] - w[0])
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The gates whose values gate `i` reads.
+    fn sources(&self, i: usize) -> &[usize] {
+        &self.sources[self.source_starts[i]..self.source_starts[i + 1]]
+    }
+}
+
+impl Levels {
+    /// Evaluate the gates level by level, computing the value of gate `i`
+    /// and something to pass on with `gate`, and calling `sink` with the
+    /// latter for each gate in the order of the levels.
+    ///
+    /// Returns the values of all gates.
+    pub(crate) fn eval<T, X, E, G, S>(&self, gate: G, mut sink: S) -> Result<Vec<Option<T>>, E>
+    where
+        T: Send + Sync,
+        X: Send,
+        E: Send,
+        G: Fn(usize, &Sources<T>) -> Result<(T, X), E> + Sync + Send,
+        S: FnMut(usize, X),
+    {
+        let mut values: Vec<Option<T>> = Vec::with_capacity(self.ngates());
+        values.resize_with(self.ngates(), || None);
+        for l in 0..self.nlevels() {
+            let level = self.level(l);
+            let results = util::par_map(level, |&i| {
+                let sources = Sources {
+                    values: &values,
+                    gates: self.sources(i),
+                };
+                gate(i, &sources)
+            });
+            for (&i, result) in level.iter().zip(results) {
+                let (val, x) = result?;
+                values[i] = Some(val);
+                sink(i, x);
+            }
+        }
+        Ok(values)
+    }
+
+    /// Evaluate the gates level by level as [`Levels::eval`] does, passing
+    /// `gates` the gates of each level up to `chunk` at a time, so that it
+    /// can compute several gates together.
+    ///
+    /// `gates` pushes the value of each gate of its chunk and something to
+    /// pass on to `out`, which it is given empty, in the order of the chunk.
+    /// The chunks are computed one after another into the same buffer, except
+    /// that with the `rayon` feature, the chunks of levels wide enough are
+    /// computed on the rayon thread pool.
+    pub(crate) fn eval_chunks<T, X, E, G, S>(
+        &self,
+        chunk: usize,
+        gates: G,
+        mut sink: S,
+    ) -> Result<Vec<Option<T>>, E>
+    where
+        T: Send + Sync,
+        X: Send,
+        E: Send,
+        G: Fn(&[usize], &LevelValues<T>, &mut Vec<(T, X)>) -> Result<(), E> + Sync + Send,
+        S: FnMut(usize, X),
+    {
+        let mut values: Vec<Option<T>> = Vec::with_capacity(self.ngates());
+        values.resize_with(self.ngates(), || None);
+        let mut out = Vec::with_capacity(chunk);
+        for l in 0..self.nlevels() {
+            let level = self.level(l);
+            #[cfg(feature = "rayon")]
+            if level.len() >= chunk * util::PARALLEL_THRESHOLD {
+                let chunks = level.chunks(chunk).collect::<Vec<_>>();
+                let results = util::par_map(&chunks, |chunk| {
+                    let values = LevelValues {
+                        levels: self,
+                        values: &values,
+                    };
+                    let mut out = Vec::with_capacity(chunk.len());
+                    gates(chunk, &values, &mut out).map(|()| out)
+                });
+                for (chunk, result) in chunks.iter().zip(results) {
+                    for (&i, (val, x)) in chunk.iter().zip(result?) {
+                        values[i] = Some(val);
+                        sink(i, x);
+                    }
+                }
+                continue;
+            }
+            // The gates of a level read only earlier levels, so the values of
+            // a chunk can be stored before the next chunk is computed.
+            for chunk in level.chunks(chunk) {
+                out.clear();
+                let level_values = LevelValues {
+                    levels: self,
+                    values: &values,
+                };
+                gates(chunk, &level_values, &mut out)?;
+                for (&i, (val, x)) in chunk.iter().zip(out.drain(..)) {
+                    values[i] = Some(val);
+                    sink(i, x);
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    /// The values of the outputs, given the values of all gates.
+    pub(crate) fn outputs<T: Clone>(&self, values: &[Option<T>]) -> Result<Vec<T>, FancyError> {
+        self.outputs
+            .iter()
+            .map(|&g| {
+                values
+                    .get(g)
+                    .and_then(Option::as_ref)
+                    .cloned()
+                    .ok_or(FancyError::UninitializedValue)
+            })
+            .collect()
+    }
+}
+
+/// The values of the gates of earlier levels, passed to the gates of a level
+/// by [`Levels::eval_chunks`].
+pub(crate) struct LevelValues<'a, T> {
+    levels: &'a Levels,
+    values: &'a [Option<T>],
+}
+
+impl<'a, T> LevelValues<'a, T> {
+    /// The values gate `i` reads.
+    pub(crate) fn sources(&self, i: usize) -> Sources<'a, T> {
+        Sources {
+            values: self.values,
+            gates: self.levels.sources(i),
+        }
+    }
+}
+
+/// The values a gate reads, computed by earlier levels.
+pub(crate) struct Sources<'a, T> {
+    values: &'a [Option<T>],
+    gates: &'a [usize],
+}
+
+impl<'a, T: Clone> Sources<'a, T> {
+    /// The value of input `k` of the gate.
+    pub(crate) fn get(&self, k: usize) -> Result<&'a T, FancyError> {
+        self.gates
+            .get(k)
+            .and_then(|&g| self.values.get(g))
+            .and_then(Option::as_ref)
+            .ok_or(FancyError::UninitializedValue)
+    }
+
+    /// The values of all inputs of the gate.
+    pub(crate) fn all(&self) -> Result<Vec<T>, FancyError> {
+        (0..self.gates.len())
+            .map(|k| self.get(k).cloned())
+            .collect()
+    }
+}
+
+/// The inputs of a circuit being evaluated in plaintext.
+struct Inputs {
+    garbler: Vec<DummyVal>,
+    evaluator: Vec<DummyVal>,
+}
+
+/// Evaluate `circuit` level by level, evaluating gate `i` with `gate`.
+fn eval_levels<C, G>(
+    circuit: &C,
+    garbler_inputs: &[Modulus],
+    evaluator_inputs: &[Modulus],
+    levels: &Levels,
+    gate: G,
+) -> Result<Vec<Modulus>, DummyError>
+where
+    C: CircuitType,
+    G: Fn(&mut Dummy, usize, &Inputs, &Sources<DummyVal>) -> Result<DummyVal, DummyError>
+        + Sync
+        + Send,
+{
+    assert_eq!(
+        levels.ngates(),
+        circuit.get_gates().len(),
+        "the levels are not those of this circuit"
+    );
+    let (garbler, evaluator) = encode_plain(circuit, garbler_inputs, evaluator_inputs)?;
+    check_input_wires(circuit, &garbler, &evaluator, &[])?;
+    let inputs = Inputs { garbler, evaluator };
+    let values = levels.eval(
+        |i, sources| Ok::<_, DummyError>((gate(&mut Dummy::new(), i, &inputs, sources)?, ())),
+        |_, ()| {},
+    )?;
+    let outputs = levels.outputs(&values)?;
+    Ok(outputs.iter().map(DummyVal::val).collect())
+}
+
+impl BinaryCircuit {
+    /// Evaluate the circuit in plaintext, evaluating the gates of each of
+    /// `levels` at once.
+    ///
+    /// The outputs are those [`eval_plain`](super::eval_plain) computes.
+    /// `levels` must have been computed for this circuit using
+    /// [`CircuitType::levels`], which can be done once for many evaluations.
+    /// With the `rayon` feature, the gates of wide levels are evaluated on the
+    /// rayon thread pool, and otherwise one after another.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `levels` do not have as many gates as the circuit.
+    pub fn eval_plain_parallel(
+        &self,
+        garbler_inputs: &[Modulus],
+        evaluator_inputs: &[Modulus],
+        levels: &Levels,
+    ) -> Result<Vec<Modulus>, DummyError> {
+        eval_levels(
+            self,
+            garbler_inputs,
+            evaluator_inputs,
+            levels,
+            |f, i, inputs, x| {
+                let val = match self.gates[i] {
+                    BinaryGate::GarblerInput { id } => garbler_input(&inputs.garbler, id)?,
+                    BinaryGate::EvaluatorInput { id } => evaluator_input(&inputs.evaluator, id)?,
+                    BinaryGate::PublicInput { id } => public_input::<DummyVal>(&[], id)?,
+                    BinaryGate::Constant { val } => {
+                        check_constant(i, val, 2)?;
+                        f.constant(val, 2)?
+                    }
+                    BinaryGate::Xor { .. } => f.xor(x.get(0)?, x.get(1)?)?,
+                    BinaryGate::And { .. } => f.and(x.get(0)?, x.get(1)?)?,
+                    BinaryGate::Inv { .. } => f.negate(x.get(0)?)?,
+                    BinaryGate::Custom { gate, .. } => {
+                        let gate = self.custom_gates.get(gate).ok_or_else(|| {
+                            FancyError::InvalidArg(format!("no custom gate {} is registered", gate))
+                        })?;
+                        f.custom(gate, &x.all()?)?
+                    }
+                };
+                Ok(val)
+            },
+        )
+    }
+}
+
+impl ArithmeticCircuit {
+    /// Evaluate the circuit in plaintext, evaluating the gates of each of
+    /// `levels` at once.
+    ///
+    /// See [`BinaryCircuit::eval_plain_parallel`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `levels` do not have as many gates as the circuit.
+    pub fn eval_plain_parallel(
+        &self,
+        garbler_inputs: &[Modulus],
+        evaluator_inputs: &[Modulus],
+        levels: &Levels,
+    ) -> Result<Vec<Modulus>, DummyError> {
+        eval_levels(
+            self,
+            garbler_inputs,
+            evaluator_inputs,
+            levels,
+            |f, i, inputs, x| {
+                let q = self.modulus(i);
+                let val = match self.gates[i] {
+                    ArithmeticGate::GarblerInput { id } => garbler_input(&inputs.garbler, id)?,
+                    ArithmeticGate::EvaluatorInput { id } => {
+                        evaluator_input(&inputs.evaluator, id)?
+                    }
+                    ArithmeticGate::PublicInput { id } => public_input::<DummyVal>(&[], id)?,
+                    ArithmeticGate::Constant { val } => {
+                        check_constant(i, val, q)?;
+                        f.constant(val, q)?
+                    }
+                    ArithmeticGate::Add { .. } => f.add(x.get(0)?, x.get(1)?)?,
+                    ArithmeticGate::Sub { .. } => f.sub(x.get(0)?, x.get(1)?)?,
+                    ArithmeticGate::Cmul { c, .. } => f.cmul(x.get(0)?, c)?,
+                    ArithmeticGate::Mul { .. } => f.mul(x.get(0)?, x.get(1)?)?,
+                    ArithmeticGate::Proj { ref tt, .. } => {
+                        f.proj(x.get(0)?, q, Some(tt.to_vec()))?
+                    }
+                };
+                Ok(val)
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{eval_plain, CircuitBuilder},
+        util::RngExt,
+    };
+    use rand::thread_rng;
+
+    /// Check that every gate reads only values of earlier levels.
+    fn check_levels<C: CircuitType>(c: &C, levels: &Levels) {
+        let mut level = vec![usize::MAX; levels.ngates()];
+        for l in 0..levels.nlevels() {
+            for &i in levels.level(l) {
+                level[i] = l;
+            }
+        }
+        for (i, &l) in level.iter().enumerate() {
+            assert!(l < levels.nlevels(), "gate {} is on no level", i);
+            for &g in levels.sources(i) {
+                assert!(level[g] < l, "gate {} reads gate {} of its level", i, g);
+            }
+        }
+        assert_eq!(levels.ngates(), c.get_gates().len());
+        assert!(levels.width() <= levels.ngates());
+    }
+
+    #[cfg(feature = "std")]
+    fn random_bits(n: usize, rng: &mut impl rand::Rng) -> Vec<Modulus> {
+        (0..n).map(|_| rng.gen::<bool>() as Modulus).collect()
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_aes_sha256() {
+        let mut rng = thread_rng();
+        for c in [
+            include_str!("../../circuits/AES-non-expanded.txt"),
+            include_str!("../../circuits/sha-256.txt"),
+        ] {
+            let c: BinaryCircuit = c.parse().unwrap();
+            let levels = c.levels();
+            check_levels(&c, &levels);
+            for _ in 0..4 {
+                let gb = random_bits(c.num_garbler_inputs(), &mut rng);
+                let ev = random_bits(c.num_evaluator_inputs(), &mut rng);
+                assert_eq!(
+                    c.eval_plain_parallel(&gb, &ev, &levels).unwrap(),
+                    eval_plain(&c, &gb, &ev).unwrap()
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_overwritten_wire() {
+        // The INV gate overwrites the input the XOR gate of its level reads.
+        let c: BinaryCircuit = "3 4\n1 1 1\n\n2 1 0 1 2 XOR\n1 1 0 0 INV\n2 1 0 2 3 AND\n"
+            .parse()
+            .unwrap();
+        let levels = c.levels();
+        check_levels(&c, &levels);
+        assert_eq!(levels.nlevels(), 3);
+        assert_eq!(levels.width(), 3);
+        for x in 0..2 {
+            for y in 0..2 {
+                assert_eq!(
+                    c.eval_plain_parallel(&[x], &[y], &levels).unwrap(),
+                    eval_plain(&c, &[x], &[y]).unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut rng = thread_rng();
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let xs = b.garbler_inputs(&[5; 8]);
+        let ys = b.evaluator_inputs(&[5; 8]);
+        let mut zs = Vec::new();
+        for (x, y) in xs.iter().zip(&ys) {
+            let z = b.mul(x, y).unwrap();
+            let z = b.cmul(&z, 3).unwrap();
+            let z = b.sub(&z, x).unwrap();
+            zs.push(b.proj(&z, 7, Some(vec![1, 2, 3, 4, 5])).unwrap());
+        }
+        let sum = zs[1..]
+            .iter()
+            .try_fold(zs[0], |acc, z| b.add(&acc, z))
+            .unwrap();
+        b.output(&sum).unwrap();
+        b.outputs(&zs).unwrap();
+        let c = b.finish();
+        let levels = c.levels();
+        check_levels(&c, &levels);
+        assert_eq!(levels.width(), 16);
+        for _ in 0..16 {
+            let gb = (0..8).map(|_| rng.gen_residue(5)).collect::<Vec<_>>();
+            let ev = (0..8).map(|_| rng.gen_residue(5)).collect::<Vec<_>>();
+            assert_eq!(
+                c.eval_plain_parallel(&gb, &ev, &levels).unwrap(),
+                eval_plain(&c, &gb, &ev).unwrap()
+            );
+        }
+        assert!(c.eval_plain_parallel(&[0; 7], &[0; 8], &levels).is_err());
+    }
+}
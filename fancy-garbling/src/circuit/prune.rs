@@ -0,0 +1,376 @@
+//! Elimination of dead gates.
+//!
+//! A gate is dead when no output of the circuit depends on the value it
+//! computes. Dead gates are found by walking the gates backwards from the
+//! outputs, and are removed; input gates are always kept, so that the inputs
+//! of the circuit, and how they are encoded, do not change.
+//!
+//! As after [deduplication](super::dedup), the pruned circuit writes the
+//! output of every gate to its own index, and numbers its garbled gates from
+//! zero in order.
+
+use super::{ArithmeticCircuit, ArithmeticGate, BinaryCircuit, BinaryGate, CircuitRef, GateInputs};
+use crate::errors::CircuitValidationError;
+
+/// Find the live gates of a circuit of `ngates` gates, given the wires each
+/// gate reads and writes, whether it is an input gate, and the outputs of
+/// the circuit.
+fn live_gates<'a>(
+    ngates: usize,
+    gates: impl DoubleEndedIterator<Item = (GateInputs<'a>, usize, bool)> + ExactSizeIterator,
+    outputs: &[CircuitRef],
+) -> Vec<bool> {
+    // Whether the value a wire holds at this point is read by a live gate or
+    // is an output.
+    let mut needed = vec![false; ngates];
+    for r in outputs {
+        needed[r.ix] = true;
+    }
+    let mut live = vec![false; ngates];
+    for (i, (inputs, z, input)) in gates.enumerate().rev() {
+        if !std::mem::replace(&mut needed[z], false) && !input {
+            continue;
+        }
+        live[i] = true;
+        for x in inputs.iter() {
+            needed[x] = true;
+        }
+    }
+    live
+}
+
+/// The state of rewriting the live gates of a circuit.
+struct Prune {
+    // for each wire of the original circuit, the gate of the new circuit that
+    // last wrote it.
+    defs: Vec<usize>,
+    // for each gate of the original circuit, its index in the new circuit,
+    // if it is kept.
+    kept: Vec<Option<usize>>,
+    ngates: usize,
+    nonfree: usize,
+}
+
+impl Prune {
+    fn new(ngates: usize) -> Self {
+        Prune {
+            defs: vec![usize::MAX; ngates],
+            kept: vec![None; ngates],
+            ngates: 0,
+            nonfree: 0,
+        }
+    }
+
+    /// The reference in the new circuit for `r`.
+    fn map(&self, r: CircuitRef) -> CircuitRef {
+        CircuitRef {
+            ix: self.defs[r.ix],
+            modulus: r.modulus,
+        }
+    }
+
+    /// The next id of a garbled gate.
+    fn next_id(&mut self) -> usize {
+        self.nonfree += 1;
+        self.nonfree - 1
+    }
+
+    /// Record that gate `i` of the original circuit, writing wire `z`, is
+    /// the next gate of the new circuit.
+    fn push(&mut self, i: usize, z: usize) {
+        self.defs[z] = self.ngates;
+        self.kept[i] = Some(self.ngates);
+        self.ngates += 1;
+    }
+
+    /// Map `refs`, each naming the gate writing it, as input and constant
+    /// references do, into the new circuit, dropping those of removed gates.
+    fn map_gate_refs(&self, refs: &[CircuitRef]) -> Vec<CircuitRef> {
+        refs.iter()
+            .filter_map(|r| {
+                self.kept[r.ix].map(|ix| CircuitRef {
+                    ix,
+                    modulus: r.modulus,
+                })
+            })
+            .collect()
+    }
+}
+
+impl BinaryCircuit {
+    /// Remove gates that no output depends on, and return the number of
+    /// gates removed.
+    ///
+    /// The inputs of the circuit are kept, in order, even if no output
+    /// depends on them. Afterwards every gate writes its output to its own
+    /// index, and the AND gates are numbered from zero. Fails, leaving the
+    /// circuit unchanged, if it does not [`validate`](Self::validate).
+    pub fn prune_gates(&mut self) -> Result<usize, CircuitValidationError> {
+        self.validate()?;
+        let wires = self.gates.iter().enumerate().map(|(i, gate)| {
+            let (inputs, z) = gate.wires(i);
+            let input = matches!(
+                gate,
+                BinaryGate::GarblerInput { .. }
+                    | BinaryGate::EvaluatorInput { .. }
+                    | BinaryGate::PublicInput { .. }
+            );
+            (inputs, z, input)
+        });
+        let live = live_gates(self.gates.len(), wires, &self.output_refs);
+        let mut p = Prune::new(self.gates.len());
+        let mut gates = Vec::with_capacity(live.iter().filter(|&&l| l).count());
+        for (i, gate) in self.gates.iter().enumerate() {
+            if !live[i] {
+                continue;
+            }
+            let gate = match *gate {
+                BinaryGate::GarblerInput { .. }
+                | BinaryGate::EvaluatorInput { .. }
+                | BinaryGate::PublicInput { .. }
+                | BinaryGate::Constant { .. } => gate.clone(),
+                BinaryGate::Xor { xref, yref, .. } => BinaryGate::Xor {
+                    xref: p.map(xref),
+                    yref: p.map(yref),
+                    out: None,
+                },
+                BinaryGate::And { xref, yref, .. } => BinaryGate::And {
+                    xref: p.map(xref),
+                    yref: p.map(yref),
+                    id: p.next_id(),
+                    out: None,
+                },
+                BinaryGate::Inv { xref, .. } => BinaryGate::Inv {
+                    xref: p.map(xref),
+                    out: None,
+                },
+                BinaryGate::Custom {
+                    gate, ref inputs, ..
+                } => BinaryGate::Custom {
+                    gate,
+                    inputs: inputs.iter().map(|&r| p.map(r)).collect(),
+                    id: p.next_id(),
+                    out: None,
+                },
+            };
+            let (_, z) = self.gates[i].wires(i);
+            p.push(i, z);
+            gates.push(gate);
+        }
+        let removed = self.gates.len() - gates.len();
+        self.digest.clear();
+        self.garbler_input_refs = p.map_gate_refs(&self.garbler_input_refs);
+        self.evaluator_input_refs = p.map_gate_refs(&self.evaluator_input_refs);
+        self.public_input_refs = p.map_gate_refs(&self.public_input_refs);
+        self.const_refs = p.map_gate_refs(&self.const_refs);
+        self.output_refs = self.output_refs.iter().map(|&r| p.map(r)).collect();
+        self.num_nonfree_gates = p.nonfree;
+        self.gates = gates;
+        Ok(removed)
+    }
+}
+
+impl ArithmeticCircuit {
+    /// Remove gates that no output depends on, and return the number of
+    /// gates removed.
+    ///
+    /// The inputs of the circuit are kept, in order, even if no output
+    /// depends on them. Afterwards every gate writes its output to its own
+    /// index, and the multiplication and projection gates are numbered from
+    /// zero. Fails, leaving the circuit unchanged, if it does not
+    /// [`validate`](Self::validate).
+    pub fn prune_gates(&mut self) -> Result<usize, CircuitValidationError> {
+        self.validate()?;
+        let wires = self.gates.iter().enumerate().map(|(i, gate)| {
+            let (inputs, z) = gate.wires(i);
+            let input = matches!(
+                gate,
+                ArithmeticGate::GarblerInput { .. }
+                    | ArithmeticGate::EvaluatorInput { .. }
+                    | ArithmeticGate::PublicInput { .. }
+            );
+            (inputs, z, input)
+        });
+        let live = live_gates(self.gates.len(), wires, &self.output_refs);
+        let mut p = Prune::new(self.gates.len());
+        let ngates = live.iter().filter(|&&l| l).count();
+        let mut gates = Vec::with_capacity(ngates);
+        let mut moduli = Vec::with_capacity(ngates);
+        for (i, (gate, &q)) in self.gates.iter().zip(&self.gate_moduli).enumerate() {
+            if !live[i] {
+                continue;
+            }
+            let gate = match *gate {
+                ArithmeticGate::GarblerInput { .. }
+                | ArithmeticGate::EvaluatorInput { .. }
+                | ArithmeticGate::PublicInput { .. }
+                | ArithmeticGate::Constant { .. } => gate.clone(),
+                ArithmeticGate::Add { xref, yref, .. } => ArithmeticGate::Add {
+                    xref: p.map(xref),
+                    yref: p.map(yref),
+                    out: None,
+                },
+                ArithmeticGate::Sub { xref, yref, .. } => ArithmeticGate::Sub {
+                    xref: p.map(xref),
+                    yref: p.map(yref),
+                    out: None,
+                },
+                ArithmeticGate::Cmul { xref, c, .. } => ArithmeticGate::Cmul {
+                    xref: p.map(xref),
+                    c,
+                    out: None,
+                },
+                ArithmeticGate::Mul { xref, yref, .. } => ArithmeticGate::Mul {
+                    xref: p.map(xref),
+                    yref: p.map(yref),
+                    id: p.next_id(),
+                    out: None,
+                },
+                ArithmeticGate::Proj { xref, ref tt, .. } => ArithmeticGate::Proj {
+                    xref: p.map(xref),
+                    tt: tt.clone(),
+                    id: p.next_id(),
+                    out: None,
+                },
+            };
+            let (_, z) = self.gates[i].wires(i);
+            p.push(i, z);
+            gates.push(gate);
+            moduli.push(q);
+        }
+        let removed = self.gates.len() - gates.len();
+        self.digest.clear();
+        self.garbler_input_refs = p.map_gate_refs(&self.garbler_input_refs);
+        self.evaluator_input_refs = p.map_gate_refs(&self.evaluator_input_refs);
+        self.public_input_refs = p.map_gate_refs(&self.public_input_refs);
+        self.const_refs = p.map_gate_refs(&self.const_refs);
+        self.output_refs = self.output_refs.iter().map(|&r| p.map(r)).collect();
+        self.num_nonfree_gates = p.nonfree;
+        self.gates = gates;
+        self.gate_moduli = moduli;
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{eval_plain, CircuitBuilder, CircuitType},
+        classic, Fancy, FancyArithmetic, FancyBinary, Modulus, WireMod2,
+    };
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn binary_dead_gates() {
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.garbler_inputs(&[2; 4]);
+        let ys = b.evaluator_inputs(&[2; 4]);
+        let one = b.constant(1, 2).unwrap();
+        let mut outputs = Vec::new();
+        for (x, y) in xs.iter().zip(&ys) {
+            // dead gates between the live ones, reading them and each other
+            let dead = b.and(x, &one).unwrap();
+            let z = b.and(x, y).unwrap();
+            let dead = b.xor(&dead, &z).unwrap();
+            b.negate(&dead).unwrap();
+            outputs.push(b.xor(&z, y).unwrap());
+        }
+        // an input no output depends on
+        b.garbler_input(2);
+        b.outputs(&outputs).unwrap();
+        let c = b.finish();
+
+        let mut d = c.clone();
+        assert_eq!(d.prune_gates().unwrap(), 13);
+        assert_eq!(d.num_garbler_inputs(), 5);
+        assert_eq!(d.num_evaluator_inputs(), 4);
+        assert_eq!(d.get_num_nonfree_gates(), 4);
+        assert_eq!(d.stats().nmuls, 4);
+        assert_eq!(d.stats().nconsts, 0);
+        d.validate().unwrap();
+        assert_eq!(d.clone().prune_gates().unwrap(), 0);
+
+        let mut rng = thread_rng();
+        for _ in 0..16 {
+            let gb = (0..5)
+                .map(|_| rng.gen::<bool>() as Modulus)
+                .collect::<Vec<_>>();
+            let ev = (0..4)
+                .map(|_| rng.gen::<bool>() as Modulus)
+                .collect::<Vec<_>>();
+            let expected = eval_plain(&c, &gb, &ev).unwrap();
+            assert_eq!(eval_plain(&d, &gb, &ev).unwrap(), expected);
+            let (en, gc) = classic::garble::<WireMod2, _>(&d).unwrap();
+            let xs = en.encode_garbler_inputs(&gb).unwrap();
+            let ys = en.encode_evaluator_inputs(&ev).unwrap();
+            assert_eq!(gc.eval(&d, &xs, &ys).unwrap(), expected);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn aes_outputs_dropped() {
+        // Parsed circuits reuse wires, which a pruned circuit does not.
+        let c: BinaryCircuit = include_str!("../../circuits/AES-non-expanded.txt")
+            .parse()
+            .unwrap();
+        let mut d = c.clone();
+        assert_eq!(d.prune_gates().unwrap(), 1);
+        assert_eq!(d.stats().nconsts, 0);
+
+        // Keeping the first byte of the ciphertext only needs some of the
+        // gates of the last round.
+        let mut d = c.clone();
+        d.output_refs.truncate(8);
+        d.output_groups.clear();
+        let before = d.stats();
+        let removed = d.prune_gates().unwrap();
+        let after = d.stats();
+        assert_eq!(before.ngates() - after.ngates(), removed);
+        assert!(after.nmuls < before.nmuls);
+        assert_eq!(after.nmuls, d.get_num_nonfree_gates());
+        assert_eq!(after.ngarbler_inputs, 128);
+        assert_eq!(after.nevaluator_inputs, 128);
+
+        let mut rng = thread_rng();
+        for _ in 0..4 {
+            let gb = (0..128)
+                .map(|_| rng.gen::<bool>() as Modulus)
+                .collect::<Vec<_>>();
+            let ev = (0..128)
+                .map(|_| rng.gen::<bool>() as Modulus)
+                .collect::<Vec<_>>();
+            let expected = eval_plain(&c, &gb, &ev).unwrap();
+            assert_eq!(eval_plain(&d, &gb, &ev).unwrap(), expected[..8]);
+        }
+    }
+
+    #[test]
+    fn arithmetic_dead_gates() {
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(5);
+        let y = b.evaluator_input(5);
+        let z = b.mul(&x, &y).unwrap();
+        let dead = b.cmul(&z, 2).unwrap();
+        let dead = b.proj(&dead, 3, Some(vec![0, 1, 2, 0, 1])).unwrap();
+        b.sub(&dead, &dead).unwrap();
+        let w = b.proj(&z, 7, Some(vec![6, 5, 4, 3, 2])).unwrap();
+        b.outputs(&[z, w]).unwrap();
+        let c = b.finish();
+
+        let mut d = c.clone();
+        assert_eq!(d.prune_gates().unwrap(), 3);
+        assert_eq!(d.get_num_nonfree_gates(), 2);
+        assert_eq!(d.gate_moduli, [5, 5, 5, 7]);
+        d.validate().unwrap();
+        for x in 0..5 {
+            for y in 0..5 {
+                assert_eq!(
+                    eval_plain(&d, &[x], &[y]).unwrap(),
+                    eval_plain(&c, &[x], &[y]).unwrap()
+                );
+            }
+        }
+    }
+}
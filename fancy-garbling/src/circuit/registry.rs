@@ -0,0 +1,219 @@
+//! A library of named circuits, with the split of their inputs into numbers.
+//!
+//! Parties agreeing at runtime on a circuit to run, as with
+//! [`twopac::semihonest::propose`](crate::twopac::semihonest::propose), each
+//! look the circuit up by name in a [`CircuitRegistry`] of their own, and
+//! check that their copies have the same digest and input widths.
+//! [`CircuitRegistry::embedded`] holds the circuits that ship with the crate:
+//!
+//! | name      | garbler inputs | evaluator inputs |
+//! |-----------|----------------|------------------|
+//! | `aes128`  | message, 128   | key, 128         |
+//! | `adder64` | 64             | 64               |
+//!
+//! and, with the `stdlib` feature, the circuits of [`stdlib`](crate::stdlib)
+//! under their function names, from `cmp32` to `max64`, each taking a number
+//! from either party.
+
+use super::{BinaryCircuit, CircuitDigest, CircuitType};
+use crate::errors::FancyError;
+use std::{collections::BTreeMap, sync::Arc};
+
+/// A circuit of a [`CircuitRegistry`].
+#[derive(Clone, Debug)]
+pub struct RegisteredCircuit {
+    circuit: Arc<BinaryCircuit>,
+    digest: CircuitDigest,
+    garbler_widths: Vec<usize>,
+    evaluator_widths: Vec<usize>,
+}
+
+impl RegisteredCircuit {
+    /// The circuit.
+    pub fn circuit(&self) -> &Arc<BinaryCircuit> {
+        &self.circuit
+    }
+
+    /// The digest of the circuit.
+    pub fn digest(&self) -> CircuitDigest {
+        self.digest
+    }
+
+    /// The widths of the numbers the garbler's inputs are split into, in
+    /// order.
+    pub fn garbler_widths(&self) -> &[usize] {
+        &self.garbler_widths
+    }
+
+    /// The widths of the numbers the evaluator's inputs are split into, in
+    /// order.
+    pub fn evaluator_widths(&self) -> &[usize] {
+        &self.evaluator_widths
+    }
+}
+
+/// Named binary circuits, each with the widths of its inputs.
+#[derive(Clone, Debug, Default)]
+pub struct CircuitRegistry {
+    circuits: BTreeMap<String, RegisteredCircuit>,
+}
+
+impl CircuitRegistry {
+    /// Make an empty registry.
+    pub fn new() -> Self {
+        CircuitRegistry::default()
+    }
+
+    /// Make a registry of the circuits that ship with the crate, as listed in
+    /// the [module docs](self).
+    #[cfg(feature = "std")]
+    pub fn embedded() -> Self {
+        let mut registry = CircuitRegistry::new();
+        let builtins: &[(&str, &[u8], usize)] = &[
+            (
+                "aes128",
+                include_bytes!("../../circuits/AES-non-expanded.txt"),
+                128,
+            ),
+            ("adder64", include_bytes!("../../circuits/adder64.txt"), 64),
+        ];
+        for &(name, bytes, width) in builtins {
+            let circuit = BinaryCircuit::parse(std::io::Cursor::new(bytes))
+                .expect("the embedded circuits parse");
+            registry
+                .insert(name, circuit, vec![width], vec![width])
+                .expect("the embedded circuits have the widths given");
+        }
+        #[cfg(feature = "stdlib")]
+        {
+            use crate::stdlib;
+            let functions: &[(&str, fn() -> BinaryCircuit, usize)] = &[
+                ("cmp32", stdlib::cmp32, 32),
+                ("cmp64", stdlib::cmp64, 64),
+                ("eq32", stdlib::eq32, 32),
+                ("eq64", stdlib::eq64, 64),
+                ("mul32", stdlib::mul32, 32),
+                ("mul64", stdlib::mul64, 64),
+                ("max32", stdlib::max32, 32),
+                ("max64", stdlib::max64, 64),
+            ];
+            for &(name, build, width) in functions {
+                registry
+                    .insert(name, build(), vec![width], vec![width])
+                    .expect("the stdlib circuits have the widths given");
+            }
+        }
+        registry
+    }
+
+    /// Register `circuit` under `name`, with its garbler's inputs split into
+    /// numbers of `garbler_widths` bits and its evaluator's into numbers of
+    /// `evaluator_widths` bits, replacing any circuit of that name.
+    ///
+    /// Fails if a width is zero, or if the widths do not add up to the
+    /// number of inputs of the party.
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        circuit: impl Into<Arc<BinaryCircuit>>,
+        garbler_widths: Vec<usize>,
+        evaluator_widths: Vec<usize>,
+    ) -> Result<(), FancyError> {
+        let circuit = circuit.into();
+        check_widths(&garbler_widths, circuit.num_garbler_inputs(), "garbler")?;
+        check_widths(
+            &evaluator_widths,
+            circuit.num_evaluator_inputs(),
+            "evaluator",
+        )?;
+        let entry = RegisteredCircuit {
+            digest: circuit.digest(),
+            circuit,
+            garbler_widths,
+            evaluator_widths,
+        };
+        self.circuits.insert(name.into(), entry);
+        Ok(())
+    }
+
+    /// The circuit registered under `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&RegisteredCircuit> {
+        self.circuits.get(name)
+    }
+
+    /// The names of the circuits, in order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.circuits.keys().map(String::as_str)
+    }
+
+    /// The number of circuits.
+    pub fn len(&self) -> usize {
+        self.circuits.len()
+    }
+
+    /// Whether there are no circuits.
+    pub fn is_empty(&self) -> bool {
+        self.circuits.is_empty()
+    }
+}
+
+fn check_widths(widths: &[usize], ninputs: usize, party: &str) -> Result<(), FancyError> {
+    if widths.contains(&0) {
+        return Err(FancyError::InvalidArg(format!(
+            "the {} inputs are split into a number of no bits",
+            party
+        )));
+    }
+    let total = widths.iter().sum::<usize>();
+    if total != ninputs {
+        return Err(FancyError::InvalidArg(format!(
+            "the {} widths add up to {} bits, not the {} inputs of the circuit",
+            party, total, ninputs
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded() {
+        let registry = CircuitRegistry::embedded();
+        let aes = registry.get("aes128").unwrap();
+        assert_eq!(aes.circuit().get_num_nonfree_gates(), 6800);
+        assert_eq!(aes.digest(), aes.circuit().digest());
+        assert_eq!(
+            (aes.garbler_widths(), aes.evaluator_widths()),
+            (&[128][..], &[128][..])
+        );
+        assert!(registry.get("adder64").is_some());
+        assert!(registry.get("sha-256").is_none());
+        #[cfg(feature = "stdlib")]
+        assert_eq!(registry.len(), 10);
+        assert!(registry.names().any(|name| name == "adder64"));
+    }
+
+    #[test]
+    fn test_widths() {
+        let adder = CircuitRegistry::embedded()
+            .get("adder64")
+            .unwrap()
+            .circuit()
+            .clone();
+        let mut registry = CircuitRegistry::new();
+        assert!(registry.is_empty());
+        registry
+            .insert("split", adder.clone(), vec![32, 32], vec![64])
+            .unwrap();
+        assert_eq!(registry.get("split").unwrap().garbler_widths(), [32, 32]);
+        assert!(registry
+            .insert("short", adder.clone(), vec![63], vec![64])
+            .is_err());
+        assert!(registry
+            .insert("empty", adder, vec![64, 0], vec![64])
+            .is_err());
+        assert_eq!(registry.len(), 1);
+    }
+}
@@ -0,0 +1,337 @@
+//! Statistics of the gates and wires of a circuit, counted from the circuit
+//! itself rather than by evaluating it.
+
+use super::{ArithmeticCircuit, ArithmeticGate, BinaryCircuit, BinaryGate, CircuitType};
+use crate::{Modulus, SecurityParams};
+use std::collections::BTreeMap;
+
+/// The statistics of a circuit returned by [`CircuitType::stats`].
+///
+/// Binary and arithmetic gates that do the same for binary wires are counted
+/// together: an AND gate is a multiplication, an XOR gate an addition, and an
+/// INV gate a subtraction from one. With the `serde` feature, this serializes
+/// with the field names below.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CircuitStats {
+    /// Number of garbler inputs.
+    pub ngarbler_inputs: usize,
+    /// Number of evaluator inputs.
+    pub nevaluator_inputs: usize,
+    /// Number of public inputs.
+    pub npublic_inputs: usize,
+    /// Number of outputs.
+    pub noutputs: usize,
+    /// Number of constant gates.
+    pub nconsts: usize,
+    /// Number of additions and XOR gates.
+    pub nadds: usize,
+    /// Number of subtractions and INV gates.
+    pub nsubs: usize,
+    /// Number of scalar multiplications.
+    pub ncmuls: usize,
+    /// Number of multiplications and AND gates.
+    pub nmuls: usize,
+    /// Number of projections.
+    pub nprojs: usize,
+    /// Number of custom gates.
+    pub ncustoms: usize,
+    /// The number of gates producing a wire of each modulus, not counting
+    /// inputs.
+    pub moduli: BTreeMap<Modulus, usize>,
+    /// Number of distinct wires the gates and inputs write.
+    pub nwires: usize,
+    /// The largest number of garbled gates, that is multiplications,
+    /// projections, and custom gates, on any path through the circuit.
+    pub mul_depth: usize,
+}
+
+impl CircuitStats {
+    /// Number of gates, not counting inputs.
+    pub fn ngates(&self) -> usize {
+        self.nconsts
+            + self.nadds
+            + self.nsubs
+            + self.ncmuls
+            + self.nmuls
+            + self.nprojs
+            + self.ncustoms
+    }
+}
+
+impl std::fmt::Display for CircuitStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "circuit stats:")?;
+        writeln!(f, "  garbler inputs:     {:16}", self.ngarbler_inputs)?;
+        writeln!(f, "  evaluator inputs:   {:16}", self.nevaluator_inputs)?;
+        if self.npublic_inputs > 0 {
+            writeln!(f, "  public inputs:      {:16}", self.npublic_inputs)?;
+        }
+        writeln!(f, "  outputs:            {:16}", self.noutputs)?;
+        writeln!(f, "  gates:              {:16}", self.ngates())?;
+        writeln!(f, "    constants:        {:16}", self.nconsts)?;
+        writeln!(f, "    additions:        {:16}", self.nadds)?;
+        writeln!(f, "    subtractions:     {:16}", self.nsubs)?;
+        writeln!(f, "    cmuls:            {:16}", self.ncmuls)?;
+        writeln!(f, "    multiplications:  {:16}", self.nmuls)?;
+        writeln!(f, "    projections:      {:16}", self.nprojs)?;
+        if self.ncustoms > 0 {
+            writeln!(f, "    custom gates:     {:16}", self.ncustoms)?;
+        }
+        writeln!(f, "  wires:              {:16}", self.nwires)?;
+        writeln!(f, "  mul depth:          {:16}", self.mul_depth)?;
+        writeln!(f, "  wire moduli: {:?}", self.moduli)
+    }
+}
+
+/// The part of a gate in the statistics.
+enum Kind {
+    Input,
+    Constant,
+    Add,
+    Sub,
+    Cmul,
+    Mul,
+    Proj,
+    Custom,
+}
+
+/// Count the gates of `circuit`, each with its kind, the wires it reads, the
+/// wire it writes, and the modulus of that wire.
+fn count<C: CircuitType>(
+    circuit: &C,
+    gates: impl Iterator<Item = (Kind, Vec<usize>, usize, Modulus)>,
+) -> CircuitStats {
+    let mut stats = CircuitStats {
+        ngarbler_inputs: circuit.num_garbler_inputs(),
+        nevaluator_inputs: circuit.num_evaluator_inputs(),
+        npublic_inputs: circuit.num_public_inputs(),
+        noutputs: circuit.noutputs(),
+        ..CircuitStats::default()
+    };
+    // The depth of the value each wire holds, if it has been written.
+    let mut depths = Vec::<Option<usize>>::new();
+    for (kind, inputs, z, q) in gates {
+        let depth = inputs
+            .iter()
+            .map(|&x| depths.get(x).copied().flatten().unwrap_or(0))
+            .max()
+            .unwrap_or(0);
+        let depth = match kind {
+            Kind::Input => 0,
+            Kind::Constant => {
+                stats.nconsts += 1;
+                0
+            }
+            Kind::Add => {
+                stats.nadds += 1;
+                depth
+            }
+            Kind::Sub => {
+                stats.nsubs += 1;
+                depth
+            }
+            Kind::Cmul => {
+                stats.ncmuls += 1;
+                depth
+            }
+            Kind::Mul => {
+                stats.nmuls += 1;
+                depth + 1
+            }
+            Kind::Proj => {
+                stats.nprojs += 1;
+                depth + 1
+            }
+            Kind::Custom => {
+                stats.ncustoms += 1;
+                depth + 1
+            }
+        };
+        if !matches!(kind, Kind::Input) {
+            *stats.moduli.entry(q).or_default() += 1;
+        }
+        if depths.len() <= z {
+            depths.resize(z + 1, None);
+        }
+        depths[z] = Some(depth);
+        stats.mul_depth = stats.mul_depth.max(depth);
+    }
+    stats.nwires = depths.iter().flatten().count();
+    stats
+}
+
+pub(super) fn binary_stats(circuit: &BinaryCircuit) -> CircuitStats {
+    let gates = circuit.gates.iter().enumerate().map(|(i, gate)| {
+        let kind = match gate {
+            BinaryGate::GarblerInput { .. }
+            | BinaryGate::EvaluatorInput { .. }
+            | BinaryGate::PublicInput { .. } => Kind::Input,
+            BinaryGate::Constant { .. } => Kind::Constant,
+            BinaryGate::Xor { .. } => Kind::Add,
+            BinaryGate::Inv { .. } => Kind::Sub,
+            BinaryGate::And { .. } => Kind::Mul,
+            BinaryGate::Custom { .. } => Kind::Custom,
+        };
+        let (inputs, z) = gate.wires(i);
+        (kind, inputs.iter().collect(), z, 2)
+    });
+    count(circuit, gates)
+}
+
+pub(super) fn arithmetic_stats(circuit: &ArithmeticCircuit) -> CircuitStats {
+    let gates = circuit.gates.iter().enumerate().map(|(i, gate)| {
+        let kind = match gate {
+            ArithmeticGate::GarblerInput { .. }
+            | ArithmeticGate::EvaluatorInput { .. }
+            | ArithmeticGate::PublicInput { .. } => Kind::Input,
+            ArithmeticGate::Constant { .. } => Kind::Constant,
+            ArithmeticGate::Add { .. } => Kind::Add,
+            ArithmeticGate::Sub { .. } => Kind::Sub,
+            ArithmeticGate::Cmul { .. } => Kind::Cmul,
+            ArithmeticGate::Mul { .. } => Kind::Mul,
+            ArithmeticGate::Proj { .. } => Kind::Proj,
+        };
+        let (inputs, z) = gate.wires(i);
+        (kind, inputs.iter().collect(), z, circuit.gate_moduli[i])
+    });
+    count(circuit, gates)
+}
+
+/// The bytes garbling the outputs of `circuit` sends: a hash for each value of
+/// each output that is not constant.
+pub(crate) fn outputs_size<C: CircuitType>(circuit: &C, security: SecurityParams) -> usize {
+    circuit
+        .get_output_refs()
+        .iter()
+        .zip(circuit.constant_outputs())
+        .filter(|(_, c)| c.is_none())
+        .map(|(r, _)| r.modulus as usize * security.hash_bytes())
+        .sum()
+}
+
+pub(super) fn binary_garbled_size(circuit: &BinaryCircuit, security: SecurityParams) -> usize {
+    let label = security.label_bytes();
+    let gates = circuit
+        .gates
+        .iter()
+        .map(|gate| match gate {
+            BinaryGate::Constant { .. } => label,
+            BinaryGate::And { .. } => 2 * label,
+            BinaryGate::Custom { gate, .. } => circuit
+                .custom_gates
+                .get(*gate)
+                .map_or(0, |g| g.num_ciphertexts() * label),
+            _ => 0,
+        })
+        .sum::<usize>();
+    gates + outputs_size(circuit, security)
+}
+
+pub(super) fn arithmetic_garbled_size(
+    circuit: &ArithmeticCircuit,
+    security: SecurityParams,
+) -> usize {
+    let block = std::mem::size_of::<scuttlebutt::Block>();
+    let gates = circuit
+        .gates
+        .iter()
+        .enumerate()
+        .map(|(i, gate)| match gate {
+            ArithmeticGate::Constant { .. } if circuit.gate_moduli[i] == 2 => {
+                security.label_bytes()
+            }
+            ArithmeticGate::Constant { .. } => block,
+            // Half gates, and a block of colors when the moduli differ.
+            ArithmeticGate::Mul { xref, yref, .. } => {
+                let (q, qb) = (xref.modulus, yref.modulus);
+                let rows = (q + qb) as usize - 2 + usize::from(q != qb);
+                rows * block
+            }
+            ArithmeticGate::Proj { xref, .. } => (xref.modulus as usize - 1) * block,
+            _ => 0,
+        })
+        .sum::<usize>();
+    gates + outputs_size(circuit, security)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use crate::{
+        circuit::{
+            ArithmeticCircuit, BinaryCircuit, CircuitBuilder, CircuitType, EvaluableCircuit,
+        },
+        depth_informer::DepthInformer,
+        Fancy, FancyArithmetic, FancyInput,
+    };
+
+    /// The multiplicative depth `DepthInformer` finds for `circuit`.
+    fn informer_depth(circuit: &BinaryCircuit) -> usize {
+        let mut depth = DepthInformer::new();
+        let gb = (0..circuit.num_garbler_inputs())
+            .map(|_| depth.encode(0, 2).unwrap())
+            .collect::<Vec<_>>();
+        let ev = (0..circuit.num_evaluator_inputs())
+            .map(|_| depth.encode(0, 2).unwrap())
+            .collect::<Vec<_>>();
+        circuit.eval(&mut depth, &gb, &ev).unwrap();
+        depth.mul_depth()
+    }
+
+    #[test]
+    fn test_adder64() {
+        let c: BinaryCircuit = include_str!("../../circuits/adder64.txt").parse().unwrap();
+        let stats = c.stats();
+        assert_eq!(stats.ngarbler_inputs, 64);
+        assert_eq!(stats.nevaluator_inputs, 64);
+        assert_eq!(stats.noutputs, 64);
+        assert_eq!(stats.nmuls, 125);
+        assert_eq!(stats.nadds, 189);
+        assert_eq!(stats.nsubs, 0);
+        assert_eq!(stats.nconsts, 1);
+        assert_eq!(stats.moduli[&2], stats.ngates());
+        assert_eq!(stats.nwires, 442);
+        assert_eq!(stats.mul_depth, 63);
+        assert_eq!(stats.mul_depth, informer_depth(&c));
+    }
+
+    #[test]
+    fn test_aes() {
+        let c: BinaryCircuit = include_str!("../../circuits/AES-non-expanded.txt")
+            .parse()
+            .unwrap();
+        let stats = c.stats();
+        assert_eq!(stats.ngarbler_inputs, 128);
+        assert_eq!(stats.nevaluator_inputs, 128);
+        assert_eq!(stats.noutputs, 128);
+        assert_eq!(stats.nmuls, 6800);
+        assert_eq!(stats.nmuls, c.get_num_nonfree_gates());
+        assert_eq!(stats.nadds, 25124);
+        assert_eq!(stats.nsubs, 1692);
+        assert_eq!(stats.mul_depth, 40);
+        assert_eq!(stats.mul_depth, informer_depth(&c));
+        assert!(stats.to_string().contains("mul depth:"));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(5);
+        let y = b.evaluator_input(5);
+        let z = b.mul(&x, &y).unwrap();
+        let z = b.cmul(&z, 3).unwrap();
+        let z = b.sub(&z, &x).unwrap();
+        let p = b.proj(&z, 3, Some(vec![0, 1, 2, 0, 1])).unwrap();
+        let w = b.mul(&z, &y).unwrap();
+        b.outputs(&[p, w]).unwrap();
+        let stats = b.finish().stats();
+        assert_eq!(
+            (stats.nmuls, stats.ncmuls, stats.nsubs, stats.nprojs),
+            (2, 1, 1, 1)
+        );
+        assert_eq!(stats.moduli[&5], 4);
+        assert_eq!(stats.moduli[&3], 1);
+        assert_eq!(stats.nwires, 7);
+        assert_eq!(stats.mul_depth, 2);
+    }
+}
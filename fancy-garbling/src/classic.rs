@@ -1,15 +1,90 @@
 //! Provides objects and functions for statically garbling and evaluating a
 //! circuit without streaming.
 
+#[cfg(feature = "serde")]
+use crate::errors::FormatError;
 use crate::{
-    circuit::EvaluableCircuit,
-    errors::{EvaluatorError, GarblerError},
-    garble::{Evaluator, Garbler},
-    WireLabel,
+    circuit::{
+        check_arity, check_constant, check_input_wires, evaluator_input, garbler_input,
+        public_input, BinaryCircuit, BinaryGate, CircuitDigest, CircuitType, EvalBuffer,
+        EvaluableCircuit, Levels, Liveness, Sources,
+    },
+    errors::{EvaluatorError, FancyError, GarbledCircuitDecodingError, GarblerError, LabelError},
+    fancy::check_inputs,
+    format::{self, FormatId},
+    garble::{
+        decode_output, evaluate_and_gate, evaluate_and_gates, garble_and_gate, garble_and_gates,
+        truncate, Evaluator, Garbler,
+    },
+    hooks::Hooks,
+    util, ExportedLabel, Modulus, SecurityParams, WireLabel, WireMod2, WireRole,
 };
+#[cfg(feature = "test-utils")]
+use crate::{dummy::Dummy, errors::DivergenceReport};
+#[cfg(feature = "std")]
+use crate::{errors::ProfileError, HasModulus};
 use itertools::Itertools;
+use rand::Rng;
+#[cfg(feature = "test-utils")]
+use rand::SeedableRng;
 use scuttlebutt::{AbstractChannel, AesRng, Block, Channel};
-use std::{collections::HashMap, marker::PhantomData, rc::Rc};
+use sha2::{Digest, Sha256};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+use std::{collections::HashMap, io::Write, marker::PhantomData, ops::Deref, rc::Rc};
+
+mod privacy_free;
+
+pub use privacy_free::{garble_privacy_free, OutputVerifier, PrivacyFreeCircuit};
+
+/// The version of the [`FormatId::GarbledCircuit`] format written by
+/// [`GarbledCircuit::to_bytes`]. Version 2 lacked the security parameters,
+/// and is read as using the defaults. Version 1 lacked the garbling id and the
+/// circuit digest, and is no longer read.
+const GARBLED_CIRCUIT_VERSION: u16 = 3;
+
+/// The version of the [`FormatId::Encoder`] format written by
+/// [`Encoder::to_bytes`]. Version 1 lacked the garbling id, and is no longer
+/// read.
+#[cfg(feature = "serde")]
+const ENCODER_VERSION: u16 = 2;
+
+/// The length of the garbling id and circuit digest that start the payload of
+/// a garbled circuit, before the number of blocks.
+const GARBLED_CIRCUIT_TAGS_LEN: usize = 16 + 32;
+
+/// The length of the security parameters that follow the tags in version 3 of
+/// the format: the label width, statistical security, and hash width, each a
+/// little-endian `u16`.
+const GARBLED_CIRCUIT_SECURITY_LEN: usize = 6;
+
+/// A random tag identifying one garbling of a circuit.
+///
+/// [`garble`] gives the [`Encoder`] and the [`GarbledCircuit`] it makes the
+/// same id, and the encoder tags the [`EncodedInputs`] it makes with it, so
+/// that [`GarbledCircuit::eval`] can reject inputs encoded for a different
+/// garbling.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GarblingId(Block);
+
+impl GarblingId {
+    fn random<R: Rng>(rng: &mut R) -> Self {
+        GarblingId(rng.gen())
+    }
+}
+
+impl std::fmt::Display for GarblingId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:032x}", u128::from(self.0))
+    }
+}
+
+impl std::fmt::Debug for GarblingId {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "GarblingId({})", self)
+    }
+}
 
 /// Static evaluator for a circuit, created by the `garble` function.
 ///
@@ -18,41 +93,606 @@ use std::{collections::HashMap, marker::PhantomData, rc::Rc};
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GarbledCircuit<W, C> {
     blocks: Vec<Block>,
+    garbling: GarblingId,
+    circuit: CircuitDigest,
+    #[cfg_attr(feature = "serde", serde(default))]
+    security: SecurityParams,
     _phantom_wire: PhantomData<W>,
     _phantom_circ: PhantomData<C>,
 }
 
 impl<W, C> GarbledCircuit<W, C> {
-    /// Create a new object from a vector of garbled gates and constant wires.
-    pub fn new(blocks: Vec<Block>) -> Self {
+    /// Create a new object from a vector of garbled gates and constant wires,
+    /// the id of the garbling they come from, and the digest of the circuit
+    /// that was garbled, garbled with the default security parameters.
+    pub fn new(blocks: Vec<Block>, garbling: GarblingId, circuit: CircuitDigest) -> Self {
         GarbledCircuit {
             blocks,
+            garbling,
+            circuit,
+            security: SecurityParams::DEFAULT,
             _phantom_wire: PhantomData,
             _phantom_circ: PhantomData,
         }
     }
 
-    /// The number of garbled rows and constant wires in the garbled circuit.
+    /// The number of blocks in the garbled circuit: with the default security
+    /// parameters, its number of garbled rows and constant wires.
+    ///
+    /// With narrower labels, the rows and wires are packed into fewer blocks,
+    /// and with wider labels they take more.
     pub fn size(&self) -> usize {
         self.blocks.len()
     }
+
+    /// The number of bytes in the garbled circuit, its blocks.
+    ///
+    /// This is what sending the garbled gates, constant wires, and output
+    /// hashes takes, not counting the encoder or the input labels, and is
+    /// [`CircuitType::garbled_size_estimate`] of the circuit that was garbled
+    /// rounded up to a whole block.
+    pub fn nbytes(&self) -> usize {
+        self.blocks.len() * std::mem::size_of::<Block>()
+    }
+
+    /// The security parameters the circuit was garbled with.
+    pub fn security(&self) -> SecurityParams {
+        self.security
+    }
+
+    /// The id of the garbling this garbled circuit comes from.
+    pub fn garbling(&self) -> GarblingId {
+        self.garbling
+    }
+
+    /// The digest of the circuit that was garbled.
+    pub fn circuit_digest(&self) -> CircuitDigest {
+        self.circuit
+    }
+
+    /// The SHA-256 hash of the blocks of the garbled circuit, in the order
+    /// they are sent, so that two parties can check that they garbled the
+    /// same circuit the same way without sending it.
+    ///
+    /// With the default security parameters, this is the hash of the bytes
+    /// [`garble_into`] writes for the same circuit and rng.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        for block in &self.blocks {
+            hasher.update(block.as_ref());
+        }
+        hasher.finalize().into()
+    }
+
+    /// Serialize the garbled circuit as a [`FormatId::GarbledCircuit`] frame
+    /// (see [`format`]), whose payload is the garbling id, the circuit digest,
+    /// the security parameters, the number of blocks as a little-endian
+    /// `u64`, and the blocks.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let len =
+            GARBLED_CIRCUIT_TAGS_LEN + GARBLED_CIRCUIT_SECURITY_LEN + 8 + 16 * self.blocks.len();
+        let header = format::Header {
+            format: FormatId::GarbledCircuit,
+            version: GARBLED_CIRCUIT_VERSION,
+            len: len as u64,
+        };
+        let mut bytes = Vec::with_capacity(format::HEADER_LEN + len);
+        bytes.extend_from_slice(&header.to_bytes());
+        bytes.extend_from_slice(self.garbling.0.as_ref());
+        bytes.extend_from_slice(self.circuit.as_bytes());
+        for bits in [
+            self.security.label_bits(),
+            self.security.statistical_bits(),
+            self.security.hash_bits(),
+        ] {
+            bytes.extend_from_slice(&(bits as u16).to_le_bytes());
+        }
+        bytes.extend_from_slice(&(self.blocks.len() as u64).to_le_bytes());
+        for block in self.blocks.iter() {
+            bytes.extend_from_slice(block.as_ref());
+        }
+        bytes
+    }
+
+    /// Deserialize a garbled circuit written by [`GarbledCircuit::to_bytes`].
+    ///
+    /// The number of blocks in the payload is checked against its length
+    /// before anything is allocated.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, GarbledCircuitDecodingError> {
+        let (version, bytes) =
+            format::decode(bytes, FormatId::GarbledCircuit, 2..=GARBLED_CIRCUIT_VERSION)?;
+        let security_len = if version >= 3 {
+            GARBLED_CIRCUIT_SECURITY_LEN
+        } else {
+            0
+        };
+        if bytes.len() < GARBLED_CIRCUIT_TAGS_LEN + security_len + 8 {
+            return Err(GarbledCircuitDecodingError::MissingHeader(bytes.len()));
+        }
+        let (tags, bytes) = bytes.split_at(GARBLED_CIRCUIT_TAGS_LEN);
+        let (security, bytes) = bytes.split_at(security_len);
+        let security = match security {
+            [] => SecurityParams::DEFAULT,
+            _ => {
+                let bits = |i: usize| u16::from_le_bytes([security[i], security[i + 1]]) as usize;
+                SecurityParams::new(bits(0), bits(2), bits(4))?
+            }
+        };
+        let mut garbling = [0; 16];
+        garbling.copy_from_slice(&tags[..16]);
+        let mut circuit = [0; 32];
+        circuit.copy_from_slice(&tags[16..]);
+        let (header, rest) = bytes.split_at(8);
+        let mut nblocks = [0; 8];
+        nblocks.copy_from_slice(header);
+        let nblocks = u64::from_le_bytes(nblocks);
+        if rest.len() % 16 != 0 || (rest.len() / 16) as u64 != nblocks {
+            return Err(GarbledCircuitDecodingError::LengthMismatch {
+                nblocks,
+                got: rest.len(),
+            });
+        }
+        let blocks = rest
+            .chunks_exact(16)
+            .map(|chunk| {
+                let mut block = [0; 16];
+                block.copy_from_slice(chunk);
+                Block::from(block)
+            })
+            .collect();
+        Ok(Self {
+            security,
+            ..Self::new(
+                blocks,
+                GarblingId(Block::from(garbling)),
+                CircuitDigest::from_bytes(circuit),
+            )
+        })
+    }
 }
 
-type Ev<Wire> = Evaluator<Channel<GarbledReader, GarbledWriter>, Wire>;
-type Gb<Wire> = Garbler<Channel<GarbledReader, GarbledWriter>, AesRng, Wire>;
+pub(crate) type Ev<Wire> = Evaluator<Channel<GarbledReader, GarbledWriter>, Wire>;
+pub(crate) type Gb<Wire> = Garbler<Channel<GarbledReader, GarbledWriter>, AesRng, Wire>;
 
 impl<Wire: WireLabel, Circuit: EvaluableCircuit<Ev<Wire>>> GarbledCircuit<Wire, Circuit> {
     /// Evaluate the garbled circuit.
+    ///
+    /// Fails with [`EvaluatorError::MismatchedGarbling`] if either set of
+    /// inputs was encoded for another garbling, with
+    /// [`EvaluatorError::MismatchedCircuit`] if `c` is not the circuit that
+    /// was garbled, and with [`EvaluatorError::Security`] if its labels are
+    /// wider than `Wire` holds.
     pub fn eval(
         &self,
         c: &Circuit,
-        garbler_inputs: &[Wire],
-        evaluator_inputs: &[Wire],
-    ) -> Result<Vec<u16>, EvaluatorError> {
+        garbler_inputs: &EncodedInputs<Wire>,
+        evaluator_inputs: &EncodedInputs<Wire>,
+    ) -> Result<Vec<Modulus>, EvaluatorError> {
+        self.eval_inner(c, garbler_inputs, evaluator_inputs, None)
+    }
+
+    /// Make a context for evaluating the garbled circuit many times, as
+    /// [`GarbledCircuit::eval`] does, without allocating storage for every
+    /// evaluation; see [`GcEvalContext`].
+    ///
+    /// Fails with [`EvaluatorError::MismatchedCircuit`] if `c` is not the
+    /// circuit that was garbled. This is checked once, here, rather than on
+    /// every evaluation.
+    pub fn evaluator<'a>(
+        &self,
+        c: &'a Circuit,
+    ) -> Result<GcEvalContext<'a, Wire, Circuit>, EvaluatorError> {
+        let digest = c.digest();
+        if digest != self.circuit {
+            return Err(EvaluatorError::MismatchedCircuit {
+                expected: self.circuit,
+                found: digest,
+            });
+        }
+        Ok(GcEvalContext {
+            circuit: c,
+            garbling: self.garbling,
+            security: self.security,
+            bytes: GarbledReader::new(&self.blocks).bytes,
+            buffer: EvalBuffer::new(),
+        })
+    }
+
+    /// Evaluate the garbled circuit as [`GarbledCircuit::eval`] does, calling
+    /// `hooks` every [`Hooks::interval`] gates.
+    ///
+    /// Fails with [`EvaluatorError::Cancelled`] if the hooks cancel it.
+    pub fn eval_with_hooks(
+        &self,
+        c: &Circuit,
+        garbler_inputs: &EncodedInputs<Wire>,
+        evaluator_inputs: &EncodedInputs<Wire>,
+        hooks: &Hooks,
+    ) -> Result<Vec<Modulus>, EvaluatorError> {
+        self.eval_inner(c, garbler_inputs, evaluator_inputs, Some(hooks))
+    }
+
+    fn eval_inner(
+        &self,
+        c: &Circuit,
+        garbler_inputs: &EncodedInputs<Wire>,
+        evaluator_inputs: &EncodedInputs<Wire>,
+        hooks: Option<&Hooks>,
+    ) -> Result<Vec<Modulus>, EvaluatorError> {
+        self.security.check_label_bits(Wire::MAX_LABEL_BITS)?;
+        check_pairing(
+            self.garbling,
+            self.circuit,
+            c,
+            garbler_inputs,
+            evaluator_inputs,
+        )?;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!(
+            "evaluate",
+            bytes = self.blocks.len() * std::mem::size_of::<Block>()
+        )
+        .entered();
+        let channel = Channel::new(GarbledReader::new(&self.blocks), GarbledWriter::new(None));
+        let mut evaluator = Evaluator::with_security(channel, self.security);
+        let outputs = match hooks {
+            None => c.eval(&mut evaluator, garbler_inputs, evaluator_inputs)?,
+            Some(hooks) => {
+                let total = c.get_gates().len();
+                let outputs = c.eval_with_checkpoints(
+                    &mut evaluator,
+                    garbler_inputs,
+                    evaluator_inputs,
+                    &[],
+                    hooks.interval(),
+                    &mut |_, done| {
+                        if hooks.checkpoint(done, total) {
+                            return Err(EvaluatorError::Cancelled { gates_done: done });
+                        }
+                        Ok(())
+                    },
+                )?;
+                hooks.progress(total, total);
+                outputs
+            }
+        };
+        Ok(outputs.expect("evaluator outputs always are Some(Modulus)"))
+    }
+
+    /// Evaluate the garbled circuit, returning its outputs divided into the
+    /// output groups of `c`.
+    ///
+    /// See [`GarbledCircuit::eval`] and
+    /// [`CircuitType::output_widths`](crate::circuit::CircuitType::output_widths).
+    pub fn eval_grouped(
+        &self,
+        c: &Circuit,
+        garbler_inputs: &EncodedInputs<Wire>,
+        evaluator_inputs: &EncodedInputs<Wire>,
+    ) -> Result<Vec<Vec<Modulus>>, EvaluatorError> {
+        let outputs = self.eval(c, garbler_inputs, evaluator_inputs)?;
+        Ok(c.group_outputs(&outputs))
+    }
+
+    /// Evaluate the garbled circuit to the labels of its outputs, exported in
+    /// the role [`WireRole::Output`], rather than decoding them.
+    ///
+    /// The labels can be handed to another protocol; see [`ExportedLabel`].
+    /// See [`GarbledCircuit::eval`] for the checks made on the inputs.
+    pub fn eval_output_labels(
+        &self,
+        c: &Circuit,
+        garbler_inputs: &EncodedInputs<Wire>,
+        evaluator_inputs: &EncodedInputs<Wire>,
+    ) -> Result<Vec<ExportedLabel>, EvaluatorError> {
+        self.security.check_label_bits(Wire::MAX_LABEL_BITS)?;
+        check_pairing(
+            self.garbling,
+            self.circuit,
+            c,
+            garbler_inputs,
+            evaluator_inputs,
+        )?;
+        let channel = Channel::new(GarbledReader::new(&self.blocks), GarbledWriter::new(None));
+        let mut evaluator = Evaluator::with_security(channel, self.security);
+        let wires = c.eval_output_wires(&mut evaluator, garbler_inputs, evaluator_inputs)?;
+        Ok(export_all(&wires, WireRole::Output))
+    }
+
+    /// Evaluate the garbled circuit to the labels of its outputs, for the
+    /// garbler to decode with the [`Decoder`] of a garbling made by
+    /// [`garble_with_decoder`].
+    ///
+    /// See [`GarbledCircuit::eval`] for the checks made on the inputs.
+    pub fn eval_labels(
+        &self,
+        c: &Circuit,
+        garbler_inputs: &EncodedInputs<Wire>,
+        evaluator_inputs: &EncodedInputs<Wire>,
+    ) -> Result<Vec<Wire>, EvaluatorError> {
+        self.security.check_label_bits(Wire::MAX_LABEL_BITS)?;
+        check_pairing(
+            self.garbling,
+            self.circuit,
+            c,
+            garbler_inputs,
+            evaluator_inputs,
+        )?;
+        let channel = Channel::new(GarbledReader::new(&self.blocks), GarbledWriter::new(None));
+        let mut evaluator = Evaluator::with_security(channel, self.security);
+        c.eval_output_wires(&mut evaluator, garbler_inputs, evaluator_inputs)
+    }
+
+    /// Evaluate the garbled circuit, holding only the labels of live wires.
+    ///
+    /// See [`EvaluableCircuit::eval_bounded`], and [`GarbledCircuit::eval`]
+    /// for the checks made on the inputs.
+    pub fn eval_bounded(
+        &self,
+        c: &Circuit,
+        garbler_inputs: &EncodedInputs<Wire>,
+        evaluator_inputs: &EncodedInputs<Wire>,
+        liveness: &Liveness,
+    ) -> Result<Vec<Modulus>, EvaluatorError> {
+        self.security.check_label_bits(Wire::MAX_LABEL_BITS)?;
+        check_pairing(
+            self.garbling,
+            self.circuit,
+            c,
+            garbler_inputs,
+            evaluator_inputs,
+        )?;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!(
+            "evaluate",
+            bytes = self.blocks.len() * std::mem::size_of::<Block>()
+        )
+        .entered();
         let channel = Channel::new(GarbledReader::new(&self.blocks), GarbledWriter::new(None));
-        let mut evaluator = Evaluator::new(channel);
-        let outputs = c.eval(&mut evaluator, garbler_inputs, evaluator_inputs)?;
-        Ok(outputs.expect("evaluator outputs always are Some(u16)"))
+        let mut evaluator = Evaluator::with_security(channel, self.security);
+        let outputs = c.eval_bounded(&mut evaluator, garbler_inputs, evaluator_inputs, liveness)?;
+        Ok(outputs.expect("evaluator outputs always are Some(Modulus)"))
+    }
+}
+
+impl GarbledCircuit<WireMod2, BinaryCircuit> {
+    /// Evaluate the garbled circuit as [`GarbledCircuit::eval`] does,
+    /// evaluating the gates of each of `levels` at once.
+    ///
+    /// The AND gates of a level are evaluated four at a time, hashing the
+    /// labels of all four at once. `levels` must have been computed for this
+    /// circuit using [`CircuitType::levels`](crate::circuit::CircuitType::levels),
+    /// which can be done once for many evaluations. With the `rayon` feature,
+    /// the gates of wide levels are evaluated on the rayon thread pool, and
+    /// otherwise one after another.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `levels` do not have as many gates as the circuit.
+    pub fn eval_parallel(
+        &self,
+        c: &BinaryCircuit,
+        garbler_inputs: &EncodedInputs<WireMod2>,
+        evaluator_inputs: &EncodedInputs<WireMod2>,
+        levels: &Levels,
+    ) -> Result<Vec<Modulus>, EvaluatorError> {
+        assert_eq!(
+            levels.ngates(),
+            c.get_gates().len(),
+            "the levels are not those of this circuit"
+        );
+        check_pairing(
+            self.garbling,
+            self.circuit,
+            c,
+            garbler_inputs,
+            evaluator_inputs,
+        )?;
+        check_input_wires(c, garbler_inputs, evaluator_inputs, &[])?;
+
+        // Find the ciphertexts of each gate, and its gate number, as the
+        // sequential evaluator reads them.
+        let bytes: &[u8] = bytemuck::cast_slice(&self.blocks);
+        let label_bytes = self.security.label_bytes();
+        let mut firsts = Vec::with_capacity(c.get_gates().len());
+        let (mut offset, mut ngarbled) = (0, 0);
+        for gate in c.get_gates() {
+            firsts.push((offset, ngarbled));
+            match *gate {
+                BinaryGate::Constant { .. } => offset += label_bytes,
+                BinaryGate::And { .. } => {
+                    offset += 2 * label_bytes;
+                    ngarbled += 1;
+                }
+                BinaryGate::Custom { gate, .. } => {
+                    let gate = c.custom_gates().get(gate).ok_or_else(|| {
+                        FancyError::InvalidArg(format!("no custom gate {} is registered", gate))
+                    })?;
+                    offset += gate.num_ciphertexts() * label_bytes;
+                    ngarbled += 1;
+                }
+                _ => {}
+            }
+        }
+        let constant_outputs = c.constant_outputs();
+        let hash_bytes = self.security.hash_bytes();
+        let noutputs = constant_outputs.iter().filter(|c| c.is_none()).count();
+        if bytes.len() < offset + 2 * noutputs * hash_bytes {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        let read = |offset: usize, len: usize| {
+            let mut block = Block::default();
+            block.as_mut()[..len].copy_from_slice(&bytes[offset..offset + len]);
+            block
+        };
+        let label = |offset: usize| read(offset, label_bytes);
+
+        let eval_gate = |i: usize, x: &Sources<WireMod2>| {
+            let (first, gate_num) = firsts[i];
+            let z = match c.get_gates()[i] {
+                BinaryGate::GarblerInput { id } => garbler_input(garbler_inputs, id)?,
+                BinaryGate::EvaluatorInput { id } => evaluator_input(evaluator_inputs, id)?,
+                BinaryGate::PublicInput { id } => public_input::<WireMod2>(&[], id)?,
+                BinaryGate::Constant { .. } => WireMod2::from_block(label(first), 2),
+                BinaryGate::Xor { .. } => x.get(0)?.plus(x.get(1)?),
+                BinaryGate::Inv { .. } => *x.get(0)?,
+                BinaryGate::And { .. } => {
+                    let (gate0, gate1) = (label(first), label(first + label_bytes));
+                    let z = evaluate_and_gate(x.get(0)?, x.get(1)?, &gate0, &gate1, gate_num);
+                    truncate(self.security, z)
+                }
+                BinaryGate::Custom { gate, .. } => {
+                    let gate = &c.custom_gates()[gate];
+                    let inputs = x.all()?;
+                    check_arity(gate.as_ref(), inputs.len())?;
+                    let ciphertexts = (0..gate.num_ciphertexts())
+                        .map(|k| label(first + k * label_bytes))
+                        .collect_vec();
+                    truncate(
+                        self.security,
+                        gate.evaluate(&inputs, &ciphertexts, gate_num),
+                    )
+                }
+            };
+            Ok::<_, EvaluatorError>((z, ()))
+        };
+        let values = levels.eval_chunks(
+            AND_BATCH_CHUNK,
+            |gates, values, out| {
+                // The AND gates of the chunk waiting to be evaluated, by their
+                // place in `out`, four at a time.
+                let zero = WireMod2::zero(2);
+                let mut ands = [(0, &zero, &zero, Block::default(), Block::default(), 0); 4];
+                let mut nands = 0;
+                for &i in gates {
+                    let x = values.sources(i);
+                    if let BinaryGate::And { .. } = c.get_gates()[i] {
+                        let (first, gate_num) = firsts[i];
+                        let (gate0, gate1) = (label(first), label(first + label_bytes));
+                        ands[nands] = (out.len(), x.get(0)?, x.get(1)?, gate0, gate1, gate_num);
+                        nands += 1;
+                        out.push((zero, ()));
+                        if nands == ands.len() {
+                            let zs = evaluate_and_gates(std::array::from_fn(|j| {
+                                let (_, a, b, gate0, gate1, gate_num) = &ands[j];
+                                (*a, *b, gate0, gate1, *gate_num)
+                            }));
+                            for (&(k, ..), z) in ands.iter().zip(zs) {
+                                out[k].0 = truncate(self.security, z);
+                            }
+                            nands = 0;
+                        }
+                    } else {
+                        out.push(eval_gate(i, &x)?);
+                    }
+                }
+                for &(k, a, b, gate0, gate1, gate_num) in &ands[..nands] {
+                    let z = evaluate_and_gate(a, b, &gate0, &gate1, gate_num);
+                    out[k].0 = truncate(self.security, z);
+                }
+                Ok::<_, EvaluatorError>(())
+            },
+            |_, ()| {},
+        )?;
+
+        let outputs = levels.outputs(&values)?;
+        let mut i = 0;
+        outputs
+            .iter()
+            .zip(constant_outputs)
+            .map(|(z, constant)| match constant {
+                Some(value) => Ok(value),
+                None => {
+                    let first = offset + 2 * i * hash_bytes;
+                    let ct = [
+                        read(first, hash_bytes),
+                        read(first + hash_bytes, hash_bytes),
+                    ];
+                    i += 1;
+                    decode_output(z, i - 1, &ct, hash_bytes)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Check that the inputs were encoded for the garbling `garbling`, and that
+/// `c` is the circuit with digest `circuit` that was garbled.
+fn check_pairing<Wire, Circuit: CircuitType>(
+    garbling: GarblingId,
+    circuit: CircuitDigest,
+    c: &Circuit,
+    garbler_inputs: &EncodedInputs<Wire>,
+    evaluator_inputs: &EncodedInputs<Wire>,
+) -> Result<(), EvaluatorError> {
+    check_garbling(garbling, garbler_inputs, evaluator_inputs)?;
+    let digest = c.digest();
+    if digest != circuit {
+        return Err(EvaluatorError::MismatchedCircuit {
+            expected: circuit,
+            found: digest,
+        });
+    }
+    Ok(())
+}
+
+/// Check that the inputs were encoded for the garbling `garbling`.
+fn check_garbling<Wire>(
+    garbling: GarblingId,
+    garbler_inputs: &EncodedInputs<Wire>,
+    evaluator_inputs: &EncodedInputs<Wire>,
+) -> Result<(), EvaluatorError> {
+    for inputs in [garbler_inputs, evaluator_inputs] {
+        if inputs.garbling != garbling {
+            return Err(EvaluatorError::MismatchedGarbling {
+                expected: garbling,
+                found: inputs.garbling,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// A context for evaluating a garbled circuit many times, made by
+/// [`GarbledCircuit::evaluator`].
+///
+/// The context holds the bytes of the garbled circuit and the wire labels of
+/// the circuit across evaluations, so that, after the first, an evaluation
+/// allocates only its outputs. Its results are those of
+/// [`GarbledCircuit::eval`].
+#[derive(Debug)]
+pub struct GcEvalContext<'a, Wire, Circuit> {
+    circuit: &'a Circuit,
+    garbling: GarblingId,
+    security: SecurityParams,
+    bytes: Rc<[u8]>,
+    buffer: EvalBuffer<Wire>,
+}
+
+impl<Wire: WireLabel, Circuit: EvaluableCircuit<Ev<Wire>>> GcEvalContext<'_, Wire, Circuit> {
+    /// Evaluate the garbled circuit, as [`GarbledCircuit::eval`] does.
+    ///
+    /// Fails with [`EvaluatorError::MismatchedGarbling`] if either set of
+    /// inputs was encoded for another garbling.
+    pub fn eval(
+        &mut self,
+        garbler_inputs: &EncodedInputs<Wire>,
+        evaluator_inputs: &EncodedInputs<Wire>,
+    ) -> Result<Vec<Modulus>, EvaluatorError> {
+        self.security.check_label_bits(Wire::MAX_LABEL_BITS)?;
+        check_garbling(self.garbling, garbler_inputs, evaluator_inputs)?;
+        let channel = Channel::new(
+            GarbledReader::shared(self.bytes.clone()),
+            GarbledWriter::new(None),
+        );
+        let mut evaluator = Evaluator::with_security(channel, self.security);
+        let outputs = self.circuit.eval_with_buffer(
+            &mut evaluator,
+            garbler_inputs,
+            evaluator_inputs,
+            &mut self.buffer,
+        )?;
+        Ok(outputs.expect("evaluator outputs always are Some(Modulus)"))
     }
 }
 
@@ -60,14 +700,122 @@ impl<Wire: WireLabel, Circuit: EvaluableCircuit<Ev<Wire>>> GarbledCircuit<Wire,
 pub fn garble<Wire: WireLabel, Circuit: EvaluableCircuit<Gb<Wire>>>(
     c: &Circuit,
 ) -> Result<(Encoder<Wire>, GarbledCircuit<Wire, Circuit>), GarblerError> {
+    garble_with_rng(c, AesRng::new())
+}
+
+/// Garble a circuit without streaming, drawing the wire labels from `rng`.
+///
+/// Garbling with an `rng` seeded with a fixed seed produces the same garbled
+/// circuit and encoder every time, which is useful for testing and for
+/// protocols such as dual execution and cut-and-choose that regenerate or
+/// compare garblings, e.g. by their [`GarbledCircuit::digest`], but insecure
+/// otherwise.
+///
+/// The garbling id is drawn first, then the zero labels of the garbler
+/// inputs and of the evaluator inputs, in order, and then the gates are
+/// garbled in the order of the circuit, each constant drawing its zero label
+/// and each other gate what it needs, such as the randomness of a
+/// multiplication of unequal moduli. The delta of each modulus is drawn the
+/// first time it is needed. The ciphertexts are written in the order of the
+/// gates, followed by those of the outputs. This order is part of the interface:
+/// it does not change in patch releases, and a change in any other release is
+/// noted in the changelog.
+pub fn garble_with_rng<Wire: WireLabel, Circuit: EvaluableCircuit<Gb<Wire>>>(
+    c: &Circuit,
+    rng: AesRng,
+) -> Result<(Encoder<Wire>, GarbledCircuit<Wire, Circuit>), GarblerError> {
+    let (en, gc, _) = garble_inner(c, rng, SecurityParams::DEFAULT, Outputs::Decodable(None))?;
+    Ok((en, gc))
+}
+
+/// Garble a circuit without streaming, drawing the wire labels from `rng`,
+/// using the security parameters `security`.
+///
+/// The garbled circuit records the parameters, and is evaluated with them.
+/// Fails with [`GarblerError::Security`] if the labels of `security` are
+/// wider than `Wire` holds, as those of [`SecurityParams::LONG_TERM`] are for
+/// any wire but [`WideWireMod2`](crate::WideWireMod2).
+pub fn garble_with_security<Wire: WireLabel, Circuit: EvaluableCircuit<Gb<Wire>>>(
+    c: &Circuit,
+    rng: AesRng,
+    security: SecurityParams,
+) -> Result<(Encoder<Wire>, GarbledCircuit<Wire, Circuit>), GarblerError> {
+    let (en, gc, _) = garble_inner(c, rng, security, Outputs::Decodable(None))?;
+    Ok((en, gc))
+}
+
+/// Garble a circuit without streaming, drawing the wire labels from `rng`,
+/// and calling `hooks` every [`Hooks::interval`] gates.
+///
+/// Fails with [`GarblerError::Cancelled`] if the hooks cancel it, leaving
+/// nothing of the garbling.
+pub fn garble_with_hooks<Wire: WireLabel, Circuit: EvaluableCircuit<Gb<Wire>>>(
+    c: &Circuit,
+    rng: AesRng,
+    hooks: &Hooks,
+) -> Result<(Encoder<Wire>, GarbledCircuit<Wire, Circuit>), GarblerError> {
+    let (en, gc, _) = garble_inner(
+        c,
+        rng,
+        SecurityParams::DEFAULT,
+        Outputs::Decodable(Some(hooks)),
+    )?;
+    Ok((en, gc))
+}
+
+/// Garble a circuit without streaming, leaving its outputs for the garbler
+/// to decode with the [`Decoder`] returned, so that the evaluator learns only
+/// their labels.
+///
+/// The garbled circuit has no ciphertexts decoding the outputs, so it is
+/// evaluated with [`GarbledCircuit::eval_labels`] rather than
+/// [`GarbledCircuit::eval`], and is smaller by their size. [`garble`] and
+/// [`GarbledCircuit::eval`] do both steps at once, letting the evaluator
+/// decode.
+pub fn garble_with_decoder<Wire: WireLabel, Circuit: EvaluableCircuit<Gb<Wire>>>(
+    c: &Circuit,
+) -> Result<(Encoder<Wire>, GarbledCircuit<Wire, Circuit>, Decoder<Wire>), GarblerError> {
+    let (en, gc, zeros) = garble_inner(c, AesRng::new(), SecurityParams::DEFAULT, Outputs::Labels)?;
+    let deltas = zeros
+        .iter()
+        .map(|zero| Ok((zero.modulus(), en.delta(zero.modulus())?.clone())))
+        .collect::<Result<_, FancyError>>()?;
+    Ok((en, gc, Decoder { zeros, deltas }))
+}
+
+/// What [`garble_inner`] does with the outputs of a circuit.
+enum Outputs<'a> {
+    /// Garble them for the evaluator to decode, calling the hooks, if any,
+    /// as the gates are garbled.
+    Decodable(Option<&'a Hooks>),
+    /// Return their zero labels, for a [`Decoder`].
+    Labels,
+}
+
+/// Garble `c`, returning the encoder, the garbled circuit, and with
+/// [`Outputs::Labels`] the zero labels of the outputs.
+fn garble_inner<Wire: WireLabel, Circuit: EvaluableCircuit<Gb<Wire>>>(
+    c: &Circuit,
+    mut rng: AesRng,
+    security: SecurityParams,
+    outputs: Outputs,
+) -> Result<(Encoder<Wire>, GarbledCircuit<Wire, Circuit>, Vec<Wire>), GarblerError> {
+    #[cfg(feature = "tracing")]
+    let span = tracing::info_span!(
+        "garble",
+        nonfree_gates = c.get_num_nonfree_gates(),
+        bytes = tracing::field::Empty
+    )
+    .entered();
     let channel = Channel::new(
         GarbledReader::new(&[]),
         GarbledWriter::new(Some(c.get_num_nonfree_gates())),
     );
     let channel_ = channel.clone();
 
-    let rng = AesRng::new();
-    let mut garbler = Garbler::new(channel_, rng);
+    security.check_label_bits(Wire::MAX_LABEL_BITS)?;
+    let garbling = GarblingId::random(&mut rng);
+    let mut garbler = Garbler::with_security(channel_, rng, security);
 
     // get input wires, ignoring encoded values
     let gb_inps = (0..c.num_garbler_inputs())
@@ -86,127 +834,876 @@ pub fn garble<Wire: WireLabel, Circuit: EvaluableCircuit<Gb<Wire>>>(
         })
         .collect_vec();
 
-    c.eval(&mut garbler, &gb_inps, &ev_inps)?;
+    let mut zeros = Vec::new();
+    match outputs {
+        Outputs::Decodable(None) => {
+            c.eval(&mut garbler, &gb_inps, &ev_inps)?;
+        }
+        Outputs::Labels => {
+            zeros = c.eval_output_wires(&mut garbler, &gb_inps, &ev_inps)?;
+        }
+        Outputs::Decodable(Some(hooks)) => {
+            let total = c.get_gates().len();
+            c.eval_with_checkpoints(
+                &mut garbler,
+                &gb_inps,
+                &ev_inps,
+                &[],
+                hooks.interval(),
+                &mut |_, done| {
+                    if hooks.checkpoint(done, total) {
+                        return Err(GarblerError::Cancelled { gates_done: done });
+                    }
+                    Ok(())
+                },
+            )?;
+            hooks.progress(total, total);
+        }
+    }
 
-    let en = Encoder::new(gb_inps, ev_inps, garbler.get_deltas());
+    let mut en = Encoder::new(gb_inps, ev_inps, garbler.get_deltas());
+    en.garbling = garbling;
 
-    let gc = GarbledCircuit::new(
-        Rc::try_unwrap(channel.writer())
-            .unwrap()
-            .into_inner()
-            .blocks,
+    let gc = GarbledCircuit {
+        security,
+        ..GarbledCircuit::new(
+            Rc::try_unwrap(channel.writer())
+                .expect("the garbler has been consumed")
+                .into_inner()
+                .into_blocks(),
+            garbling,
+            c.digest(),
+        )
+    };
+    #[cfg(feature = "tracing")]
+    span.record("bytes", gc.nbytes());
+
+    Ok((en, gc, zeros))
+}
+
+/// Garble a circuit as [`garble_with_rng`] does, writing the ciphertexts of
+/// each gate to `channel` as soon as it is garbled rather than collecting
+/// them into a [`GarbledCircuit`].
+///
+/// What is written are the blocks of the garbled circuit [`garble_with_rng`]
+/// makes from the same `rng`, so that [`eval_from`] evaluates either. Only the
+/// labels of live wires are held, as in [`EvaluableCircuit::eval_bounded`];
+/// `liveness` must have been computed for this circuit using
+/// [`CircuitType::liveness`](crate::circuit::CircuitType::liveness).
+///
+/// `on_encoder` is called before any gate is garbled with an encoder of the
+/// inputs, so that they can be encoded and sent while the gates stream. The
+/// encoder returned also has the deltas of the moduli of the gates.
+pub fn garble_into<Wire, Circuit, C, F>(
+    c: &Circuit,
+    mut rng: AesRng,
+    liveness: &Liveness,
+    channel: C,
+    on_encoder: F,
+) -> Result<Encoder<Wire>, GarblerError>
+where
+    Wire: WireLabel,
+    Circuit: EvaluableCircuit<Garbler<C, AesRng, Wire>>,
+    C: AbstractChannel,
+    F: FnOnce(&Encoder<Wire>) -> Result<(), GarblerError>,
+{
+    let garbling = GarblingId::random(&mut rng);
+    let mut flusher = channel.clone();
+    let mut garbler = Garbler::new(channel, rng);
+
+    let gb_mods = (0..c.num_garbler_inputs())
+        .map(|i| c.garbler_input_mod(i))
+        .collect_vec();
+    let ev_mods = (0..c.num_evaluator_inputs())
+        .map(|i| c.evaluator_input_mod(i))
+        .collect_vec();
+    let gb_inps = gb_mods
+        .iter()
+        .map(|&q| garbler.encode_wire(0, q).0)
+        .collect_vec();
+    let ev_inps = ev_mods
+        .iter()
+        .map(|&q| garbler.encode_wire(0, q).0)
+        .collect_vec();
+
+    let deltas = gb_mods
+        .iter()
+        .chain(&ev_mods)
+        .map(|&q| (q, garbler.delta(q)))
+        .collect();
+    let mut en = Encoder::new(gb_inps.clone(), ev_inps.clone(), deltas);
+    en.garbling = garbling;
+    on_encoder(&en)?;
+
+    c.eval_bounded(&mut garbler, &gb_inps, &ev_inps, liveness)?;
+    flusher.flush()?;
+
+    let mut en = Encoder::new(gb_inps, ev_inps, garbler.get_deltas());
+    en.garbling = garbling;
+    Ok(en)
+}
+
+/// Evaluate a circuit garbled by [`garble_into`], reading the ciphertexts of
+/// each gate from `channel` as it is evaluated.
+///
+/// `garbler_inputs` and `evaluator_inputs` are the labels of the inputs, from
+/// the encoder [`garble_into`] makes. Only the labels of live wires are held;
+/// see [`garble_into`].
+pub fn eval_from<Wire, Circuit, C>(
+    c: &Circuit,
+    channel: C,
+    garbler_inputs: &[Wire],
+    evaluator_inputs: &[Wire],
+    liveness: &Liveness,
+) -> Result<Vec<Modulus>, EvaluatorError>
+where
+    Wire: WireLabel,
+    Circuit: EvaluableCircuit<Evaluator<C, Wire>>,
+    C: AbstractChannel,
+{
+    let mut evaluator = Evaluator::new(channel);
+    let outputs = c.eval_bounded(&mut evaluator, garbler_inputs, evaluator_inputs, liveness)?;
+    Ok(outputs.expect("evaluator outputs always are Some(Modulus)"))
+}
+
+/// The ciphertexts of a gate garbled by [`garble_parallel`].
+enum Ciphertexts {
+    /// A free gate, or a constant, whose label was drawn beforehand.
+    None,
+    /// An AND gate.
+    And([Block; 2]),
+    /// A custom gate.
+    Custom(Vec<Block>),
+}
+
+/// The number of gates of a level that [`garble_parallel`] and
+/// [`GarbledCircuit::eval_parallel`] garble or evaluate together, batching the
+/// hashes of their AND gates.
+const AND_BATCH_CHUNK: usize = 64;
+
+/// Garble a binary circuit as [`garble_with_rng`] does, garbling the AND and
+/// custom gates of each of `levels` at once.
+///
+/// The garbled circuit and encoder are those [`garble_with_rng`] makes from
+/// the same `rng`: the labels are drawn in the same order, each gate has the
+/// same gate number in its tweak, and the ciphertexts are in the order of the
+/// gates. `levels` must have been computed for this circuit using
+/// [`CircuitType::levels`](crate::circuit::CircuitType::levels). With the
+/// `rayon` feature, the gates of wide levels are garbled on the rayon thread
+/// pool, and otherwise one after another.
+///
+/// # Panics
+///
+/// Panics if `levels` do not have as many gates as the circuit.
+pub fn garble_parallel(
+    c: &BinaryCircuit,
+    mut rng: AesRng,
+    levels: &Levels,
+) -> Result<(Encoder<WireMod2>, GarbledCircuit<WireMod2, BinaryCircuit>), GarblerError> {
+    assert_eq!(
+        levels.ngates(),
+        c.get_gates().len(),
+        "the levels are not those of this circuit"
     );
+    let security = SecurityParams::DEFAULT;
+    let channel = Channel::new(GarbledReader::new(&[]), GarbledWriter::new(None));
+    let garbling = GarblingId::random(&mut rng);
+    let mut garbler = Gb::<WireMod2>::with_security(channel, rng, security);
+
+    let gb_inps = (0..c.num_garbler_inputs())
+        .map(|_| garbler.encode_wire(0, 2).0)
+        .collect_vec();
+    let ev_inps = (0..c.num_evaluator_inputs())
+        .map(|_| garbler.encode_wire(0, 2).0)
+        .collect_vec();
+
+    // Draw the labels of the constants and the delta in the order the
+    // sequential garbler does, numbering the gates with ciphertexts and
+    // leaving room for their ciphertexts among the constant labels.
+    let mut blocks = Vec::with_capacity(2 * c.get_num_nonfree_gates());
+    let mut constants = HashMap::new();
+    let mut firsts = Vec::with_capacity(c.get_gates().len());
+    let mut ngarbled = 0;
+    for (i, gate) in c.get_gates().iter().enumerate() {
+        firsts.push((blocks.len(), ngarbled));
+        match *gate {
+            BinaryGate::Constant { val } => {
+                check_constant(i, val, 2)?;
+                let (zero, wire) = garbler.encode_wire(val, 2);
+                blocks.push(wire.as_block());
+                constants.insert(i, zero);
+            }
+            BinaryGate::And { .. } => {
+                garbler.delta(2);
+                blocks.extend([Block::default(); 2]);
+                ngarbled += 1;
+            }
+            BinaryGate::Custom { gate, .. } => {
+                garbler.delta(2);
+                let gate = c.custom_gates().get(gate).ok_or_else(|| {
+                    FancyError::InvalidArg(format!("no custom gate {} is registered", gate))
+                })?;
+                blocks.resize(blocks.len() + gate.num_ciphertexts(), Block::default());
+                ngarbled += 1;
+            }
+            BinaryGate::Inv { .. } => {
+                garbler.delta(2);
+            }
+            _ => {}
+        }
+    }
+    // outputs known to be constant are not garbled
+    let constant_outputs = c.constant_outputs();
+    if constant_outputs.iter().any(Option::is_none) {
+        garbler.delta(2);
+    }
+    let deltas = garbler.get_deltas();
+    let delta = deltas.get(&2).copied().unwrap_or_else(|| WireMod2::zero(2));
+
+    let garble_gate = |i: usize, x: &Sources<WireMod2>| {
+        let (_, gate_num) = firsts[i];
+        let z = match c.get_gates()[i] {
+            BinaryGate::GarblerInput { id } => garbler_input(&gb_inps, id)?,
+            BinaryGate::EvaluatorInput { id } => evaluator_input(&ev_inps, id)?,
+            BinaryGate::PublicInput { id } => public_input::<WireMod2>(&[], id)?,
+            BinaryGate::Constant { .. } => constants[&i],
+            BinaryGate::Xor { .. } => x.get(0)?.plus(x.get(1)?),
+            BinaryGate::Inv { .. } => delta.plus(x.get(0)?),
+            BinaryGate::And { .. } => {
+                let (gate0, gate1, z) = garble_and_gate(x.get(0)?, x.get(1)?, &delta, gate_num);
+                return Ok((truncate(security, z), Ciphertexts::And([gate0, gate1])));
+            }
+            BinaryGate::Custom { gate, .. } => {
+                let gate = &c.custom_gates()[gate];
+                let inputs = x.all()?;
+                check_arity(gate.as_ref(), inputs.len())?;
+                let (z, ciphertexts) = gate.garble(&inputs, &delta, gate_num);
+                if ciphertexts.len() != gate.num_ciphertexts() {
+                    return Err(GarblerError::FancyError(FancyError::InvalidArg(format!(
+                        "custom gate {} made {} ciphertexts rather than {}",
+                        gate.name(),
+                        ciphertexts.len(),
+                        gate.num_ciphertexts()
+                    ))));
+                }
+                return Ok((truncate(security, z), Ciphertexts::Custom(ciphertexts)));
+            }
+        };
+        Ok((z, Ciphertexts::None))
+    };
+    // The AND gates of a chunk of a level are garbled two at a time, hashing
+    // the labels of both at once.
+    let values = levels.eval_chunks(
+        AND_BATCH_CHUNK,
+        |gates, values, out| {
+            let zero = WireMod2::zero(2);
+            // an AND gate of the chunk waiting for another, by its place in
+            // `out`
+            let mut pending = None;
+            for &i in gates {
+                let x = values.sources(i);
+                if let BinaryGate::And { .. } = c.get_gates()[i] {
+                    let and = (out.len(), x.get(0)?, x.get(1)?, firsts[i].1);
+                    out.push((zero, Ciphertexts::None));
+                    let Some((k0, a0, b0, n0)) = pending.take() else {
+                        pending = Some(and);
+                        continue;
+                    };
+                    let (k1, a1, b1, n1) = and;
+                    let [(c00, c01, z0), (c10, c11, z1)] =
+                        garble_and_gates([(a0, b0, n0), (a1, b1, n1)], &delta);
+                    out[k0] = (truncate(security, z0), Ciphertexts::And([c00, c01]));
+                    out[k1] = (truncate(security, z1), Ciphertexts::And([c10, c11]));
+                } else {
+                    out.push(garble_gate(i, &x)?);
+                }
+            }
+            if let Some((k, a, b, gate_num)) = pending {
+                let (gate0, gate1, z) = garble_and_gate(a, b, &delta, gate_num);
+                out[k] = (truncate(security, z), Ciphertexts::And([gate0, gate1]));
+            }
+            Ok::<_, GarblerError>(())
+        },
+        |i, ciphertexts| {
+            let (first, _) = firsts[i];
+            match ciphertexts {
+                Ciphertexts::None => {}
+                Ciphertexts::And(cts) => blocks[first..first + 2].copy_from_slice(&cts),
+                Ciphertexts::Custom(cts) => blocks[first..first + cts.len()].copy_from_slice(&cts),
+            }
+        },
+    )?;
+    let outputs = levels.outputs(&values)?;
+    let outputs = outputs
+        .iter()
+        .zip(&constant_outputs)
+        .filter(|(_, c)| c.is_none())
+        .map(|(z, _)| z)
+        .enumerate()
+        .collect_vec();
+    let hashes = util::par_map(&outputs, |&(i, z)| {
+        [
+            z.hash(util::output_tweak(i, 0)),
+            z.plus(&delta).hash(util::output_tweak(i, 1)),
+        ]
+    });
+
+    let mut writer = GarbledWriter::new(Some(c.get_num_nonfree_gates()));
+    let write = |writer: &mut GarbledWriter, block: &Block, len: usize| {
+        writer
+            .write_all(&block.as_ref()[..len])
+            .expect("writing to memory does not fail")
+    };
+    for block in &blocks {
+        write(&mut writer, block, security.label_bytes());
+    }
+    for block in hashes.iter().flatten() {
+        write(&mut writer, block, security.hash_bytes());
+    }
 
+    let mut en = Encoder::new(gb_inps, ev_inps, deltas);
+    en.garbling = garbling;
+    let gc = GarbledCircuit {
+        security,
+        ..GarbledCircuit::new(writer.into_blocks(), garbling, c.digest())
+    };
     Ok((en, gc))
 }
 
+/// Garble and evaluate a circuit on the given plaintext inputs, and check
+/// the value of every gate against its value in plaintext, for debugging new
+/// gates and garbling schemes.
+///
+/// Unlike [`garble`], this keeps the zero label of every wire, so that the
+/// evaluator's label on each wire can be decoded. The first gate whose label
+/// decodes to a value other than its plaintext value, or to no value at all,
+/// is reported, with its index, the gate, and both values.
+///
+/// This needs the `test-utils` feature.
+#[cfg(feature = "test-utils")]
+pub fn debug_eval<Wire, Circuit, R>(
+    c: &Circuit,
+    garbler_inputs: &[Modulus],
+    evaluator_inputs: &[Modulus],
+    rng: &mut R,
+) -> Result<(), DivergenceReport>
+where
+    Wire: WireLabel,
+    Circuit: EvaluableCircuit<Dummy> + EvaluableCircuit<Gb<Wire>> + EvaluableCircuit<Ev<Wire>>,
+    R: Rng,
+{
+    debug_eval_tampered::<Wire, _, _>(c, garbler_inputs, evaluator_inputs, rng, |_| ())
+}
+
+/// [`debug_eval`], calling `tamper` on the garbled gates before evaluating
+/// them.
+#[cfg(feature = "test-utils")]
+fn debug_eval_tampered<Wire, Circuit, R>(
+    c: &Circuit,
+    garbler_inputs: &[Modulus],
+    evaluator_inputs: &[Modulus],
+    rng: &mut R,
+    tamper: impl FnOnce(&mut [Block]),
+) -> Result<(), DivergenceReport>
+where
+    Wire: WireLabel,
+    Circuit: EvaluableCircuit<Dummy> + EvaluableCircuit<Gb<Wire>> + EvaluableCircuit<Ev<Wire>>,
+    R: Rng,
+{
+    let (gb, ev) = crate::circuit::encode_plain(c, garbler_inputs, evaluator_inputs)?;
+    let plaintext = c.eval_gates(&mut Dummy::new(), &gb, &ev)?;
+
+    let channel = Channel::new(GarbledReader::new(&[]), GarbledWriter::new(None));
+    let mut garbler = Garbler::new(channel.clone(), AesRng::from_seed(rng.gen()));
+    let mut encode = |inputs: &[Modulus], moduli: &dyn Fn(usize) -> Modulus| {
+        inputs
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| garbler.encode_wire(x, moduli(i)))
+            .unzip::<_, _, Vec<_>, Vec<_>>()
+    };
+    let (gb_zeros, gb_labels) = encode(garbler_inputs, &|i| c.garbler_input_mod(i));
+    let (ev_zeros, ev_labels) = encode(evaluator_inputs, &|i| c.evaluator_input_mod(i));
+    let zeros = c.eval_gates(&mut garbler, &gb_zeros, &ev_zeros)?;
+    let deltas = garbler.get_deltas();
+    let mut blocks = Rc::try_unwrap(channel.writer())
+        .expect("the garbler has been consumed")
+        .into_inner()
+        .into_blocks();
+    tamper(&mut blocks);
+
+    let channel = Channel::new(GarbledReader::new(&blocks), GarbledWriter::new(None));
+    let labels = c.eval_gates(&mut Evaluator::new(channel), &gb_labels, &ev_labels)?;
+
+    for (i, ((x, zero), label)) in plaintext.iter().zip(&zeros).zip(&labels).enumerate() {
+        let q = zero.modulus();
+        let garbled = deltas.get(&q).and_then(|delta| {
+            (0..q).find(|&k| zero.plus(&delta.cmul(k)).as_block512() == label.as_block512())
+        });
+        if garbled != Some(x.val()) {
+            return Err(DivergenceReport::Divergence {
+                gate: i,
+                gate_type: c.get_gates()[i].to_string(),
+                plaintext: x.val(),
+                garbled,
+            });
+        }
+    }
+    Ok(())
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // Encoder
 
+/// Input wire labels made by an [`Encoder`], tagged with the id of its
+/// garbling.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EncodedInputs<Wire> {
+    garbling: GarblingId,
+    wires: Vec<Wire>,
+}
+
+impl<Wire> EncodedInputs<Wire> {
+    /// The id of the garbling the inputs were encoded for.
+    pub fn garbling(&self) -> GarblingId {
+        self.garbling
+    }
+
+    /// Tag `wires` as the inputs of the garbling `garbling`, in the order of
+    /// the inputs, e.g. labels encoded one at a time with
+    /// [`Encoder::encode_garbler_input`] as the values become known.
+    ///
+    /// The labels are taken as they are: nothing checks that they are labels
+    /// of that garbling.
+    pub fn from_wires(garbling: GarblingId, wires: Vec<Wire>) -> Self {
+        EncodedInputs { garbling, wires }
+    }
+
+    /// The wire labels, without their tag.
+    pub fn into_wires(self) -> Vec<Wire> {
+        self.wires
+    }
+}
+
+impl<Wire: WireLabel> EncodedInputs<Wire> {
+    /// Import labels made elsewhere as inputs of the garbling `garbling`; see
+    /// [`ExportedLabel`].
+    ///
+    /// The labels are taken as they are: nothing checks that they are labels
+    /// of that garbling.
+    pub fn import(garbling: GarblingId, labels: &[ExportedLabel]) -> Result<Self, LabelError> {
+        let wires = labels
+            .iter()
+            .map(|&label| Wire::import(label))
+            .collect::<Result<_, _>>()?;
+        Ok(EncodedInputs { garbling, wires })
+    }
+
+    /// Export the labels in the role `role`; see [`ExportedLabel`].
+    pub fn export(&self, role: WireRole) -> Vec<ExportedLabel> {
+        export_all(&self.wires, role)
+    }
+}
+
+fn export_all<Wire: WireLabel>(wires: &[Wire], role: WireRole) -> Vec<ExportedLabel> {
+    wires
+        .iter()
+        .map(|wire| ExportedLabel::new(wire, role))
+        .collect()
+}
+
+impl<Wire> Deref for EncodedInputs<Wire> {
+    type Target = [Wire];
+
+    fn deref(&self) -> &[Wire] {
+        &self.wires
+    }
+}
+
 /// Encode inputs statically.
+///
+/// An encoder holds the labels of both values of every input wire and the
+/// deltas, from which the labels of every wire of the garbled circuit follow.
+/// Anyone holding it, or its serialization, learns the evaluator's inputs from
+/// their labels, and can decode every wire the evaluator sees, so it must stay
+/// with the garbler and never be sent along with the garbled circuit.
 #[derive(Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-pub struct Encoder<Wire> {
+pub struct Encoder<Wire: WireLabel> {
+    garbling: GarblingId,
     garbler_inputs: Vec<Wire>,
     evaluator_inputs: Vec<Wire>,
-    deltas: HashMap<u16, Wire>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_sorted"))]
+    deltas: HashMap<Modulus, Wire>,
+}
+
+/// Serialize a map in the order of its keys, so that equal maps serialize to
+/// the same bytes.
+#[cfg(feature = "serde")]
+fn serialize_sorted<S, V>(map: &HashMap<Modulus, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    V: serde::Serialize,
+{
+    serde::Serialize::serialize(
+        &map.iter().collect::<std::collections::BTreeMap<_, _>>(),
+        serializer,
+    )
 }
 
 impl<Wire: WireLabel> Encoder<Wire> {
     /// Make a new `Encoder` from lists of garbler and evaluator inputs,
     /// alongside a map of moduli-to-wire-offsets.
+    ///
+    /// The encoder gets a fresh, random [`GarblingId`], so its inputs are
+    /// rejected by every garbled circuit made by [`garble`].
     pub fn new(
         garbler_inputs: Vec<Wire>,
         evaluator_inputs: Vec<Wire>,
-        deltas: HashMap<u16, Wire>,
+        deltas: HashMap<Modulus, Wire>,
     ) -> Self {
         Encoder {
+            garbling: GarblingId::random(&mut AesRng::new()),
             garbler_inputs,
             evaluator_inputs,
             deltas,
         }
     }
 
+    /// The id of the garbling this encoder encodes inputs for.
+    pub fn garbling(&self) -> GarblingId {
+        self.garbling
+    }
+
     /// Output the number of garbler inputs.
     pub fn num_garbler_inputs(&self) -> usize {
         self.garbler_inputs.len()
     }
 
+    /// Serialize the encoder as a [`FormatId::Encoder`] frame (see
+    /// [`format`]).
+    ///
+    /// The encoder holds every input wire label and the deltas, so the output
+    /// must be kept secret.
+    #[cfg(feature = "serde")]
+    pub fn to_bytes(&self) -> Vec<u8>
+    where
+        Wire: serde::Serialize,
+    {
+        format::serialize(FormatId::Encoder, ENCODER_VERSION, self)
+    }
+
+    /// Deserialize an encoder written by [`Encoder::to_bytes`].
+    ///
+    /// Fails with [`FormatError::InvalidPayload`] unless there is a delta of
+    /// the modulus of every input wire.
+    #[cfg(feature = "serde")]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FormatError>
+    where
+        Wire: serde::de::DeserializeOwned,
+    {
+        let encoder: Self =
+            format::deserialize(bytes, FormatId::Encoder, ENCODER_VERSION..=ENCODER_VERSION)?;
+        let deltas_ok = encoder.deltas.iter().all(|(&q, d)| d.modulus() == q);
+        let inputs_ok = encoder
+            .garbler_inputs
+            .iter()
+            .chain(&encoder.evaluator_inputs)
+            .all(|x| encoder.deltas.contains_key(&x.modulus()));
+        if !(deltas_ok && inputs_ok) {
+            return Err(FormatError::InvalidPayload {
+                format: FormatId::Encoder,
+                reason: "the deltas do not match the moduli of the inputs".to_string(),
+            });
+        }
+        Ok(encoder)
+    }
+
     /// Output the number of evaluator inputs.
     pub fn num_evaluator_inputs(&self) -> usize {
         self.evaluator_inputs.len()
     }
 
     /// Encode a single garbler input into its associated wire-label.
-    pub fn encode_garbler_input(&self, x: u16, id: usize) -> Wire {
-        let X = &self.garbler_inputs[id];
-        let q = X.modulus();
-        X.plus(&self.deltas[&q].cmul(x))
+    ///
+    /// Fails with [`FancyError::InputOutOfRange`], naming `id`, if `x` is not
+    /// less than the modulus of the input, and with
+    /// [`FancyError::NotEnoughGarblerInputs`] if there is no input `id`.
+    pub fn encode_garbler_input(&self, x: Modulus, id: usize) -> Result<Wire, FancyError> {
+        let zero = self
+            .garbler_inputs
+            .get(id)
+            .ok_or(FancyError::NotEnoughGarblerInputs {
+                got: self.garbler_inputs.len(),
+                needed: id.saturating_add(1),
+            })?;
+        self.encode_input(zero, x, id)
     }
 
     /// Encode a single evaluator input into its associated wire-label.
-    pub fn encode_evaluator_input(&self, x: u16, id: usize) -> Wire {
-        let X = &self.evaluator_inputs[id];
-        let q = X.modulus();
-        X.plus(&self.deltas[&q].cmul(x))
+    ///
+    /// Fails with [`FancyError::InputOutOfRange`], naming `id`, if `x` is not
+    /// less than the modulus of the input, and with
+    /// [`FancyError::NotEnoughEvaluatorInputs`] if there is no input `id`.
+    pub fn encode_evaluator_input(&self, x: Modulus, id: usize) -> Result<Wire, FancyError> {
+        let zero = self
+            .evaluator_inputs
+            .get(id)
+            .ok_or(FancyError::NotEnoughEvaluatorInputs {
+                got: self.evaluator_inputs.len(),
+                needed: id.saturating_add(1),
+            })?;
+        self.encode_input(zero, x, id)
     }
 
     /// Encode a slice of garbler inputs into their associated wire-labels.
-    pub fn encode_garbler_inputs(&self, inputs: &[u16]) -> Vec<Wire> {
-        debug_assert_eq!(inputs.len(), self.garbler_inputs.len());
-        (0..inputs.len())
-            .zip(inputs)
-            .map(|(id, &x)| self.encode_garbler_input(x, id))
-            .collect()
+    ///
+    /// Fails with [`FancyError::InputOutOfRange`] if an input is not less than
+    /// its modulus, and with [`FancyError::InvalidArgNum`] if there are not as
+    /// many inputs as the encoder has garbler inputs. With the `rayon` feature
+    /// enabled, large inputs are encoded in parallel.
+    pub fn encode_garbler_inputs(
+        &self,
+        inputs: &[Modulus],
+    ) -> Result<EncodedInputs<Wire>, FancyError> {
+        self.encode_inputs(&self.garbler_inputs, inputs)
     }
 
     /// Encode a slice of evaluator inputs into their associated wire-labels.
-    pub fn encode_evaluator_inputs(&self, inputs: &[u16]) -> Vec<Wire> {
-        debug_assert_eq!(inputs.len(), self.evaluator_inputs.len());
-        (0..inputs.len())
-            .zip(inputs)
-            .map(|(id, &x)| self.encode_evaluator_input(x, id))
+    ///
+    /// Fails with [`FancyError::InputOutOfRange`] if an input is not less than
+    /// its modulus, and with [`FancyError::InvalidArgNum`] if there are not as
+    /// many inputs as the encoder has evaluator inputs. With the `rayon`
+    /// feature enabled, large inputs are encoded in parallel.
+    pub fn encode_evaluator_inputs(
+        &self,
+        inputs: &[Modulus],
+    ) -> Result<EncodedInputs<Wire>, FancyError> {
+        self.encode_inputs(&self.evaluator_inputs, inputs)
+    }
+
+    /// Encode a slice of garbler inputs as [`Encoder::encode_garbler_inputs`]
+    /// does, exporting their labels in the role [`WireRole::GarblerInput`].
+    pub fn export_garbler_inputs(
+        &self,
+        inputs: &[Modulus],
+    ) -> Result<Vec<ExportedLabel>, FancyError> {
+        Ok(self
+            .encode_garbler_inputs(inputs)?
+            .export(WireRole::GarblerInput))
+    }
+
+    /// Encode a slice of evaluator inputs as
+    /// [`Encoder::encode_evaluator_inputs`] does, exporting their labels in
+    /// the role [`WireRole::EvaluatorInput`].
+    pub fn export_evaluator_inputs(
+        &self,
+        inputs: &[Modulus],
+    ) -> Result<Vec<ExportedLabel>, FancyError> {
+        Ok(self
+            .encode_evaluator_inputs(inputs)?
+            .export(WireRole::EvaluatorInput))
+    }
+
+    /// The delta of modulus `q`.
+    ///
+    /// An encoder made with [`Encoder::new`] may lack one.
+    fn delta(&self, q: Modulus) -> Result<&Wire, FancyError> {
+        self.deltas
+            .get(&q)
+            .ok_or_else(|| FancyError::InvalidArg(format!("no delta of modulus {}", q)))
+    }
+
+    fn encode_input(&self, zero: &Wire, x: Modulus, id: usize) -> Result<Wire, FancyError> {
+        let q = zero.modulus();
+        if x >= q {
+            return Err(FancyError::InputOutOfRange {
+                index: id,
+                value: x,
+                modulus: q,
+            });
+        }
+        Ok(zero.plus(&self.delta(q)?.cmul(x)))
+    }
+
+    fn encode_inputs(
+        &self,
+        zeros: &[Wire],
+        inputs: &[Modulus],
+    ) -> Result<EncodedInputs<Wire>, FancyError> {
+        if inputs.len() != zeros.len() {
+            return Err(FancyError::InvalidArgNum {
+                got: inputs.len(),
+                needed: zeros.len(),
+            });
+        }
+        check_inputs(inputs, zeros.iter().map(|X| X.modulus()))?;
+        let triples = zeros
+            .iter()
+            .zip(inputs.iter())
+            .map(|(X, &x)| Ok((X, x, self.delta(X.modulus())?)))
+            .collect::<Result<Vec<_>, FancyError>>()?;
+        let wires = util::par_map(&triples, |(X, x, delta)| X.plus(&delta.cmul(*x)));
+        Ok(EncodedInputs {
+            garbling: self.garbling,
+            wires,
+        })
+    }
+}
+
+// With the `zeroize` feature, an encoder wipes its labels and deltas when
+// dropped.
+#[cfg(feature = "zeroize")]
+impl<Wire: WireLabel> zeroize::Zeroize for Encoder<Wire> {
+    fn zeroize(&mut self) {
+        self.garbler_inputs
+            .iter_mut()
+            .chain(&mut self.evaluator_inputs)
+            .chain(self.deltas.values_mut())
+            .for_each(WireLabel::wipe);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<Wire: WireLabel> Drop for Encoder<Wire> {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<Wire: WireLabel> zeroize::ZeroizeOnDrop for Encoder<Wire> {}
+
+////////////////////////////////////////////////////////////////////////////////
+// Decoder
+
+/// Decode the output labels of a garbling made by [`garble_with_decoder`].
+///
+/// A decoder holds the zero labels of the outputs and the deltas of their
+/// moduli, so like an [`Encoder`] it must stay with the garbler.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Decoder<Wire: WireLabel> {
+    zeros: Vec<Wire>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_sorted"))]
+    deltas: HashMap<Modulus, Wire>,
+}
+
+impl<Wire: WireLabel> Decoder<Wire> {
+    /// The number of outputs.
+    pub fn noutputs(&self) -> usize {
+        self.zeros.len()
+    }
+
+    /// Decode the labels of the outputs, as [`GarbledCircuit::eval_labels`]
+    /// returns them.
+    ///
+    /// Fails with [`FancyError::InvalidArgNum`] unless there is a label for
+    /// each output, and with [`GarblerError::InvalidOutputLabel`] for the
+    /// first label that is no label of its output, which an evaluator
+    /// following the protocol never returns.
+    pub fn decode(&self, labels: &[Wire]) -> Result<Vec<Modulus>, GarblerError> {
+        if labels.len() != self.zeros.len() {
+            return Err(GarblerError::FancyError(FancyError::InvalidArgNum {
+                got: labels.len(),
+                needed: self.zeros.len(),
+            }));
+        }
+        labels
+            .iter()
+            .zip(&self.zeros)
+            .enumerate()
+            .map(|(i, (label, zero))| {
+                let q = zero.modulus();
+                let delta = self
+                    .deltas
+                    .get(&q)
+                    .ok_or_else(|| FancyError::InvalidArg(format!("no delta of modulus {}", q)))?;
+                if label.modulus() == q {
+                    let mut candidate = zero.clone();
+                    for k in 0..q {
+                        if candidate.as_block512() == label.as_block512() {
+                            return Ok(k);
+                        }
+                        candidate.plus_eq(delta);
+                    }
+                }
+                Err(GarblerError::InvalidOutputLabel(i))
+            })
             .collect()
     }
 }
 
+// With the `zeroize` feature, a decoder wipes its labels and deltas when
+// dropped.
+#[cfg(feature = "zeroize")]
+impl<Wire: WireLabel> zeroize::Zeroize for Decoder<Wire> {
+    fn zeroize(&mut self) {
+        self.zeros
+            .iter_mut()
+            .chain(self.deltas.values_mut())
+            .for_each(WireLabel::wipe);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<Wire: WireLabel> Drop for Decoder<Wire> {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<Wire: WireLabel> zeroize::ZeroizeOnDrop for Decoder<Wire> {}
+
 ////////////////////////////////////////////////////////////////////////////////
 // Reader and Writer impls for simple local structures to collect and release blocks
 
 /// Implementation of the `Read` trait for use by the `Evaluator`.
 #[derive(Debug)]
 pub struct GarbledReader {
-    blocks: Vec<Block>,
+    bytes: Rc<[u8]>,
     index: usize,
 }
 
 impl GarbledReader {
     fn new(blocks: &[Block]) -> Self {
-        Self {
-            blocks: blocks.to_vec(),
-            index: 0,
+        let mut bytes = Vec::with_capacity(16 * blocks.len());
+        for block in blocks {
+            bytes.extend_from_slice(block.as_ref());
         }
+        Self::shared(bytes.into())
+    }
+
+    /// A reader of `bytes`, which are shared rather than copied.
+    fn shared(bytes: Rc<[u8]>) -> Self {
+        Self { bytes, index: 0 }
     }
 }
 
 impl std::io::Read for GarbledReader {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-        assert_eq!(buf.len() % 16, 0);
-        for data in buf.chunks_mut(16) {
-            let block: [u8; 16] = self.blocks[self.index].into();
-            for (a, b) in data.iter_mut().zip(block.iter()) {
-                *a = *b;
-            }
-            self.index += 1;
+        // A garbled circuit that is too short for its circuit is an error
+        // rather than a panic, since it may come from an untrusted source.
+        if buf.len() > self.bytes.len() - self.index {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
         }
+        buf.copy_from_slice(&self.bytes[self.index..self.index + buf.len()]);
+        self.index += buf.len();
         Ok(buf.len())
     }
 }
 
 /// Implementation of the `Write` trait for use by `Garbler`.
+///
+/// The bytes written are packed into blocks, the last of which is padded with
+/// zeros by [`GarbledWriter::into_blocks`].
 #[derive(Debug)]
 pub struct GarbledWriter {
     blocks: Vec<Block>,
+    // The bytes written since the last whole block.
+    partial: Vec<u8>,
 }
 
 impl GarbledWriter {
@@ -217,23 +1714,47 @@ impl GarbledWriter {
         } else {
             Vec::new()
         };
-        Self { blocks }
+        Self {
+            blocks,
+            partial: Vec::with_capacity(16),
+        }
+    }
+
+    /// The blocks written, the last padded with zeros if it is partial.
+    pub fn into_blocks(mut self) -> Vec<Block> {
+        if !self.partial.is_empty() {
+            self.partial.resize(16, 0);
+            self.push_partial();
+        }
+        self.blocks
+    }
+
+    fn push_partial(&mut self) {
+        let mut block = Block::default();
+        block.as_mut().copy_from_slice(&self.partial);
+        self.blocks.push(block);
+        self.partial.clear();
     }
 }
 
 impl std::io::Write for GarbledWriter {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        for item in buf.chunks(16) {
-            let bytes: [u8; 16] = match item.try_into() {
-                Ok(bytes) => bytes,
-                Err(_) => {
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidData,
-                        "unable to map bytes to block",
-                    ));
-                }
-            };
-            self.blocks.push(Block::from(bytes));
+        let mut rest = buf;
+        if !self.partial.is_empty() {
+            let n = rest.len().min(16 - self.partial.len());
+            self.partial.extend_from_slice(&rest[..n]);
+            rest = &rest[n..];
+            if self.partial.len() < 16 {
+                return Ok(buf.len());
+            }
+            self.push_partial();
+        }
+        let chunks = rest.chunks_exact(16);
+        self.partial.extend_from_slice(chunks.remainder());
+        for chunk in chunks {
+            let mut block = Block::default();
+            block.as_mut().copy_from_slice(chunk);
+            self.blocks.push(block);
         }
         Ok(buf.len())
     }
@@ -241,3 +1762,1247 @@ impl std::io::Write for GarbledWriter {
         Ok(())
     }
 }
+
+////////////////////////////////////////////////////////////////////////////////
+// Profiling
+
+/// Throughput measurements of garbling and evaluating a circuit, produced by
+/// [`profile`].
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct GarbleProfile {
+    /// The number of non-free gates in the circuit.
+    pub nonfree_gates: usize,
+    /// The number of ciphertexts in the garbled circuit.
+    pub ciphertexts: usize,
+    /// The number of those ciphertexts used for decoding the outputs.
+    pub output_ciphertexts: usize,
+    /// The size of the garbled circuit in bytes.
+    pub ciphertext_bytes: usize,
+    /// Wall time spent garbling.
+    pub garble_time: Duration,
+    /// Wall time spent evaluating.
+    pub eval_time: Duration,
+}
+
+#[cfg(feature = "std")]
+impl GarbleProfile {
+    /// The number of non-free gates garbled per second.
+    pub fn garble_gates_per_sec(&self) -> f64 {
+        self.nonfree_gates as f64 / self.garble_time.as_secs_f64()
+    }
+
+    /// The number of non-free gates evaluated per second.
+    pub fn eval_gates_per_sec(&self) -> f64 {
+        self.nonfree_gates as f64 / self.eval_time.as_secs_f64()
+    }
+
+    /// The number of garbled circuit bytes per non-free gate, not counting
+    /// output decoding information.
+    pub fn bytes_per_gate(&self) -> f64 {
+        let nbytes = (self.ciphertexts - self.output_ciphertexts) * std::mem::size_of::<Block>();
+        nbytes as f64 / self.nonfree_gates as f64
+    }
+
+    /// Serialize the profile as JSON.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("profile always serializes")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::fmt::Display for GarbleProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "garbling profile:")?;
+        writeln!(f, "  non-free gates:     {:16}", self.nonfree_gates)?;
+        writeln!(
+            f,
+            "  ciphertexts:        {:16} // {:.2} Kb",
+            self.ciphertexts,
+            self.ciphertext_bytes as f64 / 1000.0
+        )?;
+        writeln!(f, "  output ciphertexts: {:16}", self.output_ciphertexts)?;
+        writeln!(f, "  bytes per gate:     {:16.2}", self.bytes_per_gate())?;
+        writeln!(
+            f,
+            "  garbling:        {:16.3?} // {:.0} gates/s",
+            self.garble_time,
+            self.garble_gates_per_sec()
+        )?;
+        writeln!(
+            f,
+            "  evaluation:      {:16.3?} // {:.0} gates/s",
+            self.eval_time,
+            self.eval_gates_per_sec()
+        )?;
+        Ok(())
+    }
+}
+
+/// Measure the time taken to garble and evaluate `c` on random inputs drawn
+/// from `rng`.
+///
+/// A warmup iteration is run before the timed one.
+#[cfg(feature = "std")]
+pub fn profile<Wire, Circuit, R>(c: &Circuit, rng: &mut R) -> Result<GarbleProfile, ProfileError>
+where
+    Wire: WireLabel,
+    Circuit: EvaluableCircuit<Gb<Wire>> + EvaluableCircuit<Ev<Wire>>,
+    R: Rng,
+{
+    let gb_inps = (0..c.num_garbler_inputs())
+        .map(|i| rng.gen::<Modulus>() % c.garbler_input_mod(i))
+        .collect_vec();
+    let ev_inps = (0..c.num_evaluator_inputs())
+        .map(|i| rng.gen::<Modulus>() % c.evaluator_input_mod(i))
+        .collect_vec();
+
+    let run = || -> Result<(Duration, Duration, usize), ProfileError> {
+        let start = Instant::now();
+        let (en, gc) = garble::<Wire, Circuit>(c)?;
+        let garble_time = start.elapsed();
+
+        let xs = en
+            .encode_garbler_inputs(&gb_inps)
+            .map_err(GarblerError::from)?;
+        let ys = en
+            .encode_evaluator_inputs(&ev_inps)
+            .map_err(GarblerError::from)?;
+        let start = Instant::now();
+        gc.eval(c, &xs, &ys)?;
+        let eval_time = start.elapsed();
+        Ok((garble_time, eval_time, gc.size()))
+    };
+
+    // Warm up caches and the allocator before measuring.
+    run()?;
+    let (garble_time, eval_time, ciphertexts) = run()?;
+
+    Ok(GarbleProfile {
+        nonfree_gates: c.get_num_nonfree_gates(),
+        ciphertexts,
+        output_ciphertexts: c
+            .get_output_refs()
+            .iter()
+            .map(|r| r.modulus() as usize)
+            .sum(),
+        ciphertext_bytes: ciphertexts * std::mem::size_of::<Block>(),
+        garble_time,
+        eval_time,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{util::RngExt, AllWire};
+    use rand::SeedableRng;
+
+    #[test]
+    fn parallel_encoding_matches_serial() {
+        let mut rng = AesRng::from_seed(Block::from(0x1234_u128));
+        let n = 100_000;
+        let moduli = [2, 3, 5, 17];
+        let deltas = moduli
+            .iter()
+            .map(|&q| (q, AllWire::rand_delta(&mut rng, q)))
+            .collect::<HashMap<_, _>>();
+        let qs = (0..n).map(|i| moduli[i % moduli.len()]).collect_vec();
+        let gb_zeros = qs.iter().map(|&q| AllWire::rand(&mut rng, q)).collect_vec();
+        let ev_zeros = qs.iter().map(|&q| AllWire::rand(&mut rng, q)).collect_vec();
+        let en = Encoder::new(gb_zeros, ev_zeros, deltas);
+
+        let inputs = qs.iter().map(|&q| rng.gen_residue(q)).collect_vec();
+
+        let gb = en.encode_garbler_inputs(&inputs).unwrap();
+        let ev = en.encode_evaluator_inputs(&inputs).unwrap();
+        for (i, &x) in inputs.iter().enumerate() {
+            assert_eq!(gb[i], en.encode_garbler_input(x, i).unwrap());
+            assert_eq!(ev[i], en.encode_evaluator_input(x, i).unwrap());
+        }
+    }
+
+    #[test]
+    fn encode_out_of_range() {
+        let mut rng = AesRng::from_seed(Block::from(0x1234_u128));
+        let qs = [2, 3, 5, 17, 2];
+        let deltas = qs
+            .iter()
+            .map(|&q| (q, AllWire::rand_delta(&mut rng, q)))
+            .collect::<HashMap<_, _>>();
+        let mut zeros = || qs.iter().map(|&q| AllWire::rand(&mut rng, q)).collect_vec();
+        let (gb_zeros, ev_zeros) = (zeros(), zeros());
+        let en = Encoder::new(gb_zeros, ev_zeros, deltas);
+
+        let out_of_range = |r: Result<_, FancyError>| match r {
+            Err(FancyError::InputOutOfRange { index, modulus, .. }) => (index, modulus),
+            r => panic!(
+                "expected an out-of-range input: {:?}",
+                r.map(|_: EncodedInputs<_>| ())
+            ),
+        };
+        for (i, &q) in qs.iter().enumerate() {
+            let mut inputs = vec![1; qs.len()];
+            for x in [q, q + 1, Modulus::MAX] {
+                inputs[i] = x;
+                assert_eq!(out_of_range(en.encode_garbler_inputs(&inputs)), (i, q));
+                assert_eq!(out_of_range(en.encode_evaluator_inputs(&inputs)), (i, q));
+                assert!(en.encode_garbler_input(x, i).is_err());
+                assert!(en.encode_evaluator_input(x, i).is_err());
+            }
+            assert!(en.encode_garbler_input(q - 1, i).is_ok());
+        }
+
+        for n in [0, qs.len() - 1, qs.len() + 1] {
+            let inputs = vec![0; n];
+            for r in [
+                en.encode_garbler_inputs(&inputs),
+                en.encode_evaluator_inputs(&inputs),
+            ] {
+                assert!(matches!(
+                    r,
+                    Err(FancyError::InvalidArgNum { got, needed: 5 }) if got == n
+                ));
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn decoder_decodes_output_labels() {
+        use crate::{
+            circuit::{eval_plain, ArithmeticCircuit, BinaryCircuit, CircuitBuilder},
+            Fancy, FancyArithmetic, WireMod2,
+        };
+
+        let c: BinaryCircuit = include_str!("../circuits/AES-non-expanded.txt")
+            .parse()
+            .unwrap();
+        let (en, gc, decoder) = garble_with_decoder::<WireMod2, _>(&c).unwrap();
+        assert_eq!(decoder.noutputs(), 128);
+        // The two output ciphertexts of each output are not sent.
+        let (_, decodable) = garble::<WireMod2, _>(&c).unwrap();
+        assert_eq!(gc.size(), decodable.size() - 2 * 128);
+        let mut rng = rand::thread_rng();
+        let mut bits = || (0..128).map(|_| rng.gen::<bool>() as Modulus).collect_vec();
+        let (key, block) = (bits(), bits());
+        let xs = en.encode_garbler_inputs(&key).unwrap();
+        let ys = en.encode_evaluator_inputs(&block).unwrap();
+        let mut labels = gc.eval_labels(&c, &xs, &ys).unwrap();
+        let expected = eval_plain(&c, &key, &block).unwrap();
+        assert_eq!(decoder.decode(&labels).unwrap(), expected);
+        // Without the output ciphertexts, the evaluator cannot decode.
+        assert!(gc.eval(&c, &xs, &ys).is_err());
+
+        labels[17] = WireMod2::rand(&mut rng, 2);
+        assert!(matches!(
+            decoder.decode(&labels),
+            Err(GarblerError::InvalidOutputLabel(17))
+        ));
+        assert!(matches!(
+            decoder.decode(&labels[1..]),
+            Err(GarblerError::FancyError(FancyError::InvalidArgNum {
+                got: 127,
+                needed: 128
+            }))
+        ));
+
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(5);
+        let y = b.evaluator_input(5);
+        let z = b.mul(&x, &y).unwrap();
+        let p = b.proj(&z, 7, Some(vec![6, 5, 4, 3, 2])).unwrap();
+        let k = b.constant(3, 11).unwrap();
+        b.outputs(&[z, p, k]).unwrap();
+        let c = b.finish();
+        let (en, gc, decoder) = garble_with_decoder::<AllWire, _>(&c).unwrap();
+        for (x, y) in [(0, 4), (2, 3), (4, 4)] {
+            let xs = en.encode_garbler_inputs(&[x]).unwrap();
+            let ys = en.encode_evaluator_inputs(&[y]).unwrap();
+            let mut labels = gc.eval_labels(&c, &xs, &ys).unwrap();
+            let expected = eval_plain(&c, &[x], &[y]).unwrap();
+            assert_eq!(decoder.decode(&labels).unwrap(), expected);
+            labels[1] = AllWire::rand(&mut rng, 7);
+            assert!(matches!(
+                decoder.decode(&labels),
+                Err(GarblerError::InvalidOutputLabel(1))
+            ));
+            // A label of another modulus is no label of the output.
+            labels[1] = labels[0].clone();
+            assert!(matches!(
+                decoder.decode(&labels),
+                Err(GarblerError::InvalidOutputLabel(1))
+            ));
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn evaluator_context_matches_eval() {
+        use crate::{
+            circuit::{eval_plain, ArithmeticCircuit, BinaryCircuit, CircuitBuilder},
+            Fancy, FancyArithmetic, WireMod2,
+        };
+
+        let c: BinaryCircuit = include_str!("../circuits/adder64.txt").parse().unwrap();
+        let (en, gc) = garble::<WireMod2, _>(&c).unwrap();
+        let mut ctx = gc.evaluator(&c).unwrap();
+        let mut rng = rand::thread_rng();
+        for _ in 0..16 {
+            let mut bits = || (0..64).map(|_| rng.gen::<bool>() as Modulus).collect_vec();
+            let (x, y) = (bits(), bits());
+            let xs = en.encode_garbler_inputs(&x).unwrap();
+            let ys = en.encode_evaluator_inputs(&y).unwrap();
+            let outputs = ctx.eval(&xs, &ys).unwrap();
+            assert_eq!(outputs, gc.eval(&c, &xs, &ys).unwrap());
+            assert_eq!(outputs, eval_plain(&c, &x, &y).unwrap());
+        }
+
+        // Inputs of another garbling are caught on every evaluation, and
+        // another circuit when the context is made.
+        let (other, _) = garble::<WireMod2, _>(&c).unwrap();
+        let xs = other.encode_garbler_inputs(&[0; 64]).unwrap();
+        let ys = en.encode_evaluator_inputs(&[0; 64]).unwrap();
+        assert!(matches!(
+            ctx.eval(&xs, &ys),
+            Err(EvaluatorError::MismatchedGarbling { .. })
+        ));
+        let aes: BinaryCircuit = include_str!("../circuits/AES-non-expanded.txt")
+            .parse()
+            .unwrap();
+        assert!(matches!(
+            gc.evaluator(&aes),
+            Err(EvaluatorError::MismatchedCircuit { .. })
+        ));
+
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(5);
+        let y = b.evaluator_input(5);
+        let z = b.mul(&x, &y).unwrap();
+        let p = b.proj(&z, 7, Some(vec![6, 5, 4, 3, 2])).unwrap();
+        let k = b.constant(3, 11).unwrap();
+        b.outputs(&[z, p, k]).unwrap();
+        let c = b.finish();
+        let (en, gc) = garble::<AllWire, _>(&c).unwrap();
+        let mut ctx = gc.evaluator(&c).unwrap();
+        for x in 0..5 {
+            for y in 0..5 {
+                let xs = en.encode_garbler_inputs(&[x]).unwrap();
+                let ys = en.encode_evaluator_inputs(&[y]).unwrap();
+                let outputs = ctx.eval(&xs, &ys).unwrap();
+                assert_eq!(outputs, gc.eval(&c, &xs, &ys).unwrap());
+                assert_eq!(outputs, eval_plain(&c, &[x], &[y]).unwrap());
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn encode_inputs_one_at_a_time() {
+        use crate::{
+            circuit::{eval_plain, BinaryCircuit},
+            WireMod2,
+        };
+        use rand::seq::SliceRandom;
+
+        let c: BinaryCircuit = include_str!("../circuits/AES-non-expanded.txt")
+            .parse()
+            .unwrap();
+        let (en, gc) = garble::<WireMod2, _>(&c).unwrap();
+        assert_eq!(en.num_garbler_inputs(), 128);
+        assert_eq!(en.num_evaluator_inputs(), 128);
+        let mut rng = rand::thread_rng();
+        let key = (0..128).map(|_| rng.gen::<bool>() as Modulus).collect_vec();
+        let block = (0..128).map(|_| rng.gen::<bool>() as Modulus).collect_vec();
+
+        // Encode the inputs in a random order, as they become known.
+        let mut order = (0..256).collect_vec();
+        order.shuffle(&mut rng);
+        let mut gb = vec![None; 128];
+        let mut ev = vec![None; 128];
+        for i in order {
+            if i < 128 {
+                gb[i] = Some(en.encode_garbler_input(key[i], i).unwrap());
+            } else {
+                ev[i - 128] = Some(en.encode_evaluator_input(block[i - 128], i - 128).unwrap());
+            }
+        }
+        let assemble = |wires: Vec<Option<WireMod2>>| {
+            EncodedInputs::from_wires(en.garbling(), wires.into_iter().flatten().collect())
+        };
+        let (xs, ys) = (assemble(gb), assemble(ev));
+        assert_eq!(xs, en.encode_garbler_inputs(&key).unwrap());
+        assert_eq!(ys, en.encode_evaluator_inputs(&block).unwrap());
+        assert_eq!(
+            gc.eval(&c, &xs, &ys).unwrap(),
+            eval_plain(&c, &key, &block).unwrap()
+        );
+
+        assert!(matches!(
+            en.encode_garbler_input(0, 128),
+            Err(FancyError::NotEnoughGarblerInputs {
+                got: 128,
+                needed: 129
+            })
+        ));
+        assert!(matches!(
+            en.encode_evaluator_input(0, usize::MAX),
+            Err(FancyError::NotEnoughEvaluatorInputs { got: 128, .. })
+        ));
+        assert!(matches!(
+            en.encode_evaluator_input(2, 5),
+            Err(FancyError::InputOutOfRange {
+                index: 5,
+                value: 2,
+                modulus: 2
+            })
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn profile_adder64() {
+        use crate::{
+            circuit::{BinaryCircuit, CircuitBuilder, CircuitType},
+            BinaryGadgets, BundleGadgets, Fancy, WireMod2,
+        };
+
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.bin_garbler_input(64);
+        let y = b.bin_evaluator_input(64);
+        let (z, carry) = b.bin_addition(&x, &y).unwrap();
+        b.output_bundle(&z).unwrap();
+        b.output(&carry).unwrap();
+        let circ = b.finish();
+
+        let mut rng = AesRng::new();
+        let p = profile::<WireMod2, _, _>(&circ, &mut rng).unwrap();
+        assert_eq!(p.nonfree_gates, circ.get_num_nonfree_gates());
+        assert!(p.nonfree_gates > 0);
+        // Two half-gate ciphertexts per AND gate, and two decoding
+        // ciphertexts per output.
+        assert_eq!(p.output_ciphertexts, 2 * 65);
+        assert_eq!(p.ciphertexts, 2 * p.nonfree_gates + p.output_ciphertexts);
+        assert_eq!(p.ciphertext_bytes, p.ciphertexts * 16);
+        assert_eq!(p.bytes_per_gate(), 32.0);
+        assert!(p.garble_time > Duration::ZERO);
+        assert!(p.eval_time > Duration::ZERO);
+        assert!(p.garble_gates_per_sec().is_finite());
+        assert!(p.eval_gates_per_sec().is_finite());
+        assert!(p.to_string().contains("non-free gates"));
+    }
+    #[cfg(feature = "std")]
+    #[test]
+    fn seeded_garbling_is_reproducible() {
+        use crate::{
+            circuit::{eval_plain, BinaryCircuit},
+            WireMod2,
+        };
+
+        let c: BinaryCircuit = include_str!("../circuits/adder64.txt").parse().unwrap();
+        let garble = |seed: u128| {
+            garble_with_rng::<WireMod2, _>(&c, AesRng::from_seed(Block::from(seed))).unwrap()
+        };
+        let (en, gc) = garble(7);
+        let (en_, gc_) = garble(7);
+        assert_eq!(gc.digest(), gc_.digest());
+        assert_eq!(gc.to_bytes(), gc_.to_bytes());
+        let (gb, ev) = ([1; 64], [0; 64]);
+        let xs = en.encode_garbler_inputs(&gb).unwrap();
+        let ys = en.encode_evaluator_inputs(&ev).unwrap();
+        assert_eq!(xs, en_.encode_garbler_inputs(&gb).unwrap());
+        assert_eq!(ys, en_.encode_evaluator_inputs(&ev).unwrap());
+        assert_eq!(
+            gc_.eval(&c, &xs, &ys).unwrap(),
+            eval_plain(&c, &gb, &ev).unwrap()
+        );
+
+        let (_, other) = garble(8);
+        assert_ne!(gc.digest(), other.digest());
+    }
+
+    // The order labels are drawn in and ciphertexts written is part of the
+    // interface, so this digest changes only when that order does, or with
+    // features that change the hash.
+    #[cfg(not(any(feature = "blake3-hash", feature = "wide-moduli")))]
+    #[cfg(feature = "std")]
+    #[test]
+    fn seeded_garbling_is_frozen() {
+        use crate::{circuit::BinaryCircuit, WireMod2};
+
+        let c: BinaryCircuit = include_str!("../circuits/adder64.txt").parse().unwrap();
+        let rng = AesRng::from_seed(Block::from(7_u128));
+        let (_, gc) = garble_with_rng::<WireMod2, _>(&c, rng).unwrap();
+        let hex = gc.digest().iter().map(|b| format!("{:02x}", b)).join("");
+        assert_eq!(
+            hex,
+            "24d9a47298bb0d211eef0048adc09fe1ac56482120c86844e958c83df960928e"
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn garbled_circuit_bytes() {
+        use crate::{
+            circuit::{BinaryCircuit, CircuitBuilder, CircuitType},
+            errors::FormatError,
+            BinaryGadgets, BundleGadgets, WireMod2,
+        };
+
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.bin_garbler_input(8);
+        let y = b.bin_evaluator_input(8);
+        let (z, _) = b.bin_addition(&x, &y).unwrap();
+        b.output_bundle(&z).unwrap();
+        let circ = b.finish();
+
+        let rng = AesRng::from_seed(Block::from(7_u128));
+        let (en, gc) = garble_with_rng::<WireMod2, _>(&circ, rng).unwrap();
+        let bytes = gc.to_bytes();
+        assert_eq!(
+            bytes.len(),
+            format::HEADER_LEN + 48 + 6 + 8 + 16 * gc.size()
+        );
+        let gc = GarbledCircuit::<WireMod2, BinaryCircuit>::from_bytes(&bytes).unwrap();
+        assert_eq!(gc.garbling(), en.garbling());
+        assert_eq!(gc.circuit_digest(), circ.digest());
+        let xs = en.encode_garbler_inputs(&[1, 0, 1, 0, 0, 0, 0, 0]).unwrap();
+        let ys = en
+            .encode_evaluator_inputs(&[1, 1, 0, 0, 0, 0, 0, 0])
+            .unwrap();
+        assert_eq!(gc.eval(&circ, &xs, &ys).unwrap(), [0, 0, 0, 1, 0, 0, 0, 0]);
+
+        let decode = GarbledCircuit::<WireMod2, BinaryCircuit>::from_bytes;
+        let frame = |payload: &[u8]| {
+            format::encode(FormatId::GarbledCircuit, GARBLED_CIRCUIT_VERSION, payload)
+        };
+        let (tags, payload) = bytes[format::HEADER_LEN..]
+            .split_at(GARBLED_CIRCUIT_TAGS_LEN + GARBLED_CIRCUIT_SECURITY_LEN);
+        assert!(matches!(
+            decode(&bytes[..5]),
+            Err(GarbledCircuitDecodingError::Format(FormatError::Truncated(
+                5
+            )))
+        ));
+        assert!(matches!(
+            decode(&bytes[..bytes.len() - 1]),
+            Err(GarbledCircuitDecodingError::Format(
+                FormatError::LengthMismatch { .. }
+            ))
+        ));
+        assert!(matches!(
+            decode(&frame(&tags[..5])),
+            Err(GarbledCircuitDecodingError::MissingHeader(5))
+        ));
+        assert!(matches!(
+            decode(&frame(&bytes[format::HEADER_LEN..bytes.len() - 1])),
+            Err(GarbledCircuitDecodingError::LengthMismatch { .. })
+        ));
+        let mut forged = tags.to_vec();
+        forged.extend_from_slice(&u64::MAX.to_le_bytes());
+        forged.extend_from_slice(&payload[8..]);
+        assert!(matches!(
+            decode(&frame(&forged)),
+            Err(GarbledCircuitDecodingError::LengthMismatch {
+                nblocks: u64::MAX,
+                ..
+            })
+        ));
+
+        // A well-formed encoding of too few blocks fails evaluation.
+        let mut short = tags.to_vec();
+        short.extend_from_slice(&((gc.size() - 1) as u64).to_le_bytes());
+        short.extend_from_slice(&payload[8..payload.len() - 16]);
+        let short = decode(&frame(&short)).unwrap();
+        assert!(matches!(
+            short.eval(&circ, &xs, &ys),
+            Err(EvaluatorError::IoError(_))
+        ));
+
+        // Version 2 lacks the security parameters, which are the defaults.
+        let mut v2 = tags[..GARBLED_CIRCUIT_TAGS_LEN].to_vec();
+        v2.extend_from_slice(payload);
+        let v2 = decode(&format::encode(FormatId::GarbledCircuit, 2, &v2)).unwrap();
+        assert_eq!(v2.security(), SecurityParams::DEFAULT);
+        assert_eq!(v2.eval(&circ, &xs, &ys).unwrap(), [0, 0, 0, 1, 0, 0, 0, 0]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn eighty_bit_labels() {
+        use crate::{
+            circuit::{eval_plain, BinaryCircuit},
+            WireMod2,
+        };
+
+        let circ: BinaryCircuit = include_str!("../circuits/adder64.txt").parse().unwrap();
+        let short = SecurityParams::new(80, 40, 80).unwrap();
+        let mut rng = AesRng::new();
+        let mut sizes = vec![];
+        for security in [SecurityParams::DEFAULT, short] {
+            let (en, gc) =
+                garble_with_security::<WireMod2, _>(&circ, AesRng::new(), security).unwrap();
+            assert_eq!(gc.security(), security);
+            for _ in 0..8 {
+                let x = (0..64).map(|_| rng.gen::<bool>() as Modulus).collect_vec();
+                let y = (0..64).map(|_| rng.gen::<bool>() as Modulus).collect_vec();
+                let xs = en.encode_garbler_inputs(&x).unwrap();
+                let ys = en.encode_evaluator_inputs(&y).unwrap();
+                assert_eq!(
+                    gc.eval(&circ, &xs, &ys).unwrap(),
+                    eval_plain(&circ, &x, &y).unwrap()
+                );
+            }
+            sizes.push(gc.nbytes());
+        }
+        // Every ciphertext and hash of a binary circuit takes 10 bytes rather
+        // than 16, padded to a whole block.
+        assert_eq!(sizes[1], (sizes[0] * 5 / 8 + 15) / 16 * 16);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn narrow_labels() {
+        use crate::{
+            circuit::{eval_plain, BinaryCircuit},
+            WireMod2,
+        };
+
+        let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap();
+        let mut rng = AesRng::new();
+        let gb_inputs = (0..128).map(|_| rng.gen_residue(2)).collect_vec();
+        let ev_inputs = (0..128).map(|_| rng.gen_residue(2)).collect_vec();
+        let target = eval_plain(&circ, &gb_inputs, &ev_inputs).unwrap();
+
+        let (_, wide) = garble::<WireMod2, _>(&circ).unwrap();
+        let security = SecurityParams::SIMULATION;
+        let (en, gc) = garble_with_security::<WireMod2, _>(&circ, rng, security).unwrap();
+        assert_eq!(gc.security(), security);
+        // The half-gate ciphertexts take half the bytes, and the output hashes
+        // a quarter.
+        assert!(2 * gc.size() <= wide.size());
+        let xs = en.encode_garbler_inputs(&gb_inputs).unwrap();
+        let ys = en.encode_evaluator_inputs(&ev_inputs).unwrap();
+        assert_eq!(gc.eval(&circ, &xs, &ys).unwrap(), target);
+
+        // The parameters are kept through serialization.
+        let bytes = gc.to_bytes();
+        let gc = GarbledCircuit::<WireMod2, BinaryCircuit>::from_bytes(&bytes).unwrap();
+        assert_eq!(gc.security(), security);
+        assert_eq!(gc.eval(&circ, &xs, &ys).unwrap(), target);
+
+        // Invalid parameters are rejected.
+        let mut bytes = bytes;
+        bytes[format::HEADER_LEN + 48..][..2].copy_from_slice(&264u16.to_le_bytes());
+        assert!(matches!(
+            GarbledCircuit::<WireMod2, BinaryCircuit>::from_bytes(&bytes),
+            Err(GarbledCircuitDecodingError::Security(
+                crate::errors::SecurityParamsError::LabelBits(264)
+            ))
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn wide_labels() {
+        use crate::{
+            circuit::{eval_plain, BinaryCircuit},
+            errors::SecurityParamsError,
+            WideWireMod2, WireMod2,
+        };
+
+        let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap();
+        let mut rng = AesRng::new();
+        let gb_inputs = (0..128).map(|_| rng.gen_residue(2)).collect_vec();
+        let ev_inputs = (0..128).map(|_| rng.gen_residue(2)).collect_vec();
+        let target = eval_plain(&circ, &gb_inputs, &ev_inputs).unwrap();
+
+        let (_, narrow) = garble::<WireMod2, _>(&circ).unwrap();
+        let security = SecurityParams::LONG_TERM;
+        let (en, gc) = garble_with_security::<WideWireMod2, _>(&circ, rng, security).unwrap();
+        assert_eq!(gc.security(), security);
+        // The half-gate ciphertexts take twice the bytes, the output hashes
+        // the same.
+        assert!(10 * gc.size() >= 19 * narrow.size());
+        let xs = en.encode_garbler_inputs(&gb_inputs).unwrap();
+        let ys = en.encode_evaluator_inputs(&ev_inputs).unwrap();
+        assert_eq!(gc.eval(&circ, &xs, &ys).unwrap(), target);
+
+        // The parameters are kept through serialization.
+        let bytes = gc.to_bytes();
+        let gc = GarbledCircuit::<WideWireMod2, BinaryCircuit>::from_bytes(&bytes).unwrap();
+        assert_eq!(gc.security(), security);
+        assert_eq!(gc.eval(&circ, &xs, &ys).unwrap(), target);
+
+        // Narrower wires cannot hold the labels.
+        let unsupported = SecurityParamsError::UnsupportedLabelBits {
+            label_bits: 256,
+            max: 128,
+        };
+        assert!(matches!(
+            garble_with_security::<WireMod2, _>(&circ, AesRng::new(), security),
+            Err(GarblerError::Security(e)) if e == unsupported
+        ));
+        let gc = GarbledCircuit::<WireMod2, BinaryCircuit>::from_bytes(&bytes).unwrap();
+        let (en, _) = garble::<WireMod2, _>(&circ).unwrap();
+        let xs = en.encode_garbler_inputs(&gb_inputs).unwrap();
+        let ys = en.encode_evaluator_inputs(&ev_inputs).unwrap();
+        assert!(matches!(
+            gc.eval_labels(&circ, &xs, &ys),
+            Err(EvaluatorError::Security(e)) if e == unsupported
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn exported_labels() {
+        use crate::{
+            circuit::{BinaryCircuit, CircuitBuilder},
+            BinaryGadgets, BundleGadgets, WireMod2,
+        };
+
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.bin_garbler_input(8);
+        let y = b.bin_evaluator_input(8);
+        let (z, _) = b.bin_addition(&x, &y).unwrap();
+        b.output_bundle(&z).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble::<WireMod2, _>(&circ).unwrap();
+
+        // Labels survive a trip through bytes, tagged with their roles.
+        let xs = en.export_garbler_inputs(&[1, 0, 1, 0, 0, 0, 0, 0]).unwrap();
+        let ys = en
+            .export_evaluator_inputs(&[1, 1, 0, 0, 0, 0, 0, 0])
+            .unwrap();
+        assert!(xs.iter().all(|x| x.role == WireRole::GarblerInput));
+        assert!(ys.iter().all(|y| y.role == WireRole::EvaluatorInput));
+        let bytes = ExportedLabel::to_bytes_many(&ys);
+        let ys = ExportedLabel::from_bytes_many(&bytes).unwrap();
+
+        let xs = EncodedInputs::<WireMod2>::import(gc.garbling(), &xs).unwrap();
+        let ys = EncodedInputs::<WireMod2>::import(gc.garbling(), &ys).unwrap();
+        assert_eq!(
+            xs.export(WireRole::GarblerInput),
+            en.export_garbler_inputs(&[1, 0, 1, 0, 0, 0, 0, 0]).unwrap()
+        );
+        assert_eq!(gc.eval(&circ, &xs, &ys).unwrap(), [0, 0, 0, 1, 0, 0, 0, 0]);
+
+        // The output labels depend only on the outputs.
+        let outputs = gc.eval_output_labels(&circ, &xs, &ys).unwrap();
+        assert_eq!(outputs.len(), 8);
+        assert!(outputs.iter().all(|z| z.role == WireRole::Output));
+        let xs = en.encode_garbler_inputs(&[0, 1, 1, 0, 0, 0, 0, 0]).unwrap();
+        let ys = en
+            .encode_evaluator_inputs(&[0, 0, 0, 0, 0, 0, 0, 0])
+            .unwrap();
+        let others = gc.eval_output_labels(&circ, &xs, &ys).unwrap();
+        assert_eq!(outputs[0], others[0]);
+        assert_ne!(outputs[3], others[3]);
+    }
+
+    #[test]
+    fn mismatched_inputs() {
+        use crate::{
+            circuit::{BinaryCircuit, CircuitBuilder, CircuitType},
+            BinaryGadgets, BundleGadgets, WireMod2,
+        };
+
+        let adder = |n| {
+            let mut b = CircuitBuilder::<BinaryCircuit>::new();
+            let x = b.bin_garbler_input(n);
+            let y = b.bin_evaluator_input(n);
+            let (z, _) = b.bin_addition(&x, &y).unwrap();
+            b.output_bundle(&z).unwrap();
+            b.finish()
+        };
+        let circ = adder(4);
+        let (mut en1, gc1) = garble::<WireMod2, _>(&circ).unwrap();
+        let (en2, gc2) = garble::<WireMod2, _>(&circ).unwrap();
+        assert_ne!(en1.garbling(), en2.garbling());
+        let encode = |en: &Encoder<WireMod2>| {
+            (
+                en.encode_garbler_inputs(&[1, 1, 0, 0]).unwrap(),
+                en.encode_evaluator_inputs(&[1, 0, 0, 0]).unwrap(),
+            )
+        };
+        let (xs1, ys1) = encode(&en1);
+        let (xs2, ys2) = encode(&en2);
+
+        assert_eq!(gc1.eval(&circ, &xs1, &ys1).unwrap(), [0, 0, 1, 0]);
+        assert_eq!(gc2.eval(&circ, &xs2, &ys2).unwrap(), [0, 0, 1, 0]);
+        for (xs, ys) in [(&xs2, &ys1), (&xs1, &ys2), (&xs2, &ys2)] {
+            match gc1.eval(&circ, xs, ys) {
+                Err(EvaluatorError::MismatchedGarbling { expected, found }) => {
+                    assert_eq!(expected, en1.garbling());
+                    assert_eq!(found, en2.garbling());
+                }
+                r => panic!("expected mismatched garblings: {:?}", r),
+            }
+        }
+        // An encoder made outside `garble` matches no garbled circuit.
+        let en3 = Encoder::new(
+            en1.garbler_inputs.clone(),
+            en1.evaluator_inputs.clone(),
+            std::mem::take(&mut en1.deltas),
+        );
+        let (xs3, ys3) = encode(&en3);
+        assert!(matches!(
+            gc1.eval(&circ, &xs3, &ys3),
+            Err(EvaluatorError::MismatchedGarbling { .. })
+        ));
+
+        // A circuit of the same shape, computing something else.
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.bin_garbler_input(4);
+        let y = b.bin_evaluator_input(4);
+        let z = b.bin_xor(&x, &y).unwrap();
+        b.output_bundle(&z).unwrap();
+        let other = b.finish();
+        assert!(matches!(
+            gc1.eval(&other, &xs1, &ys1),
+            Err(EvaluatorError::MismatchedCircuit { .. })
+        ));
+        let liveness = other.liveness();
+        assert!(matches!(
+            gc1.eval_bounded(&other, &xs1, &ys1, &liveness),
+            Err(EvaluatorError::MismatchedCircuit { .. })
+        ));
+        assert!(matches!(
+            gc1.eval(&adder(3), &xs1, &ys1),
+            Err(EvaluatorError::MismatchedCircuit { .. })
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn encoder_bytes_keep_garbling() {
+        use crate::{
+            circuit::{BinaryCircuit, CircuitBuilder},
+            Fancy, FancyBinary, WireMod2,
+        };
+
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.garbler_input(2);
+        let y = b.evaluator_input(2);
+        let z = b.and(&x, &y).unwrap();
+        b.output(&z).unwrap();
+        let circ = b.finish();
+        let (en, gc) = garble::<WireMod2, _>(&circ).unwrap();
+        let en = Encoder::<WireMod2>::from_bytes(&en.to_bytes()).unwrap();
+        assert_eq!(en.garbling(), gc.garbling());
+        let xs = en.encode_garbler_inputs(&[1]).unwrap();
+        let ys = en.encode_evaluator_inputs(&[1]).unwrap();
+        assert_eq!(gc.eval(&circ, &xs, &ys).unwrap(), [1]);
+    }
+
+    #[cfg(all(feature = "serde", feature = "std"))]
+    #[test]
+    fn serde_aes() {
+        use crate::{
+            circuit::{eval_plain, BinaryCircuit},
+            WireMod2,
+        };
+
+        let c: BinaryCircuit = include_str!("../circuits/AES-non-expanded.txt")
+            .parse()
+            .unwrap();
+        let (gc_bytes, en_bytes) = {
+            let (en, gc) = garble::<WireMod2, _>(&c).unwrap();
+            let bytes = (
+                bincode::serialize(&gc).unwrap(),
+                bincode::serialize(&en).unwrap(),
+            );
+            assert_eq!(bincode::serialize(&gc).unwrap(), bytes.0);
+            assert_eq!(bincode::serialize(&en).unwrap(), bytes.1);
+            bytes
+        };
+
+        // Everything the evaluation needs comes from the bytes.
+        let gc: GarbledCircuit<WireMod2, BinaryCircuit> = bincode::deserialize(&gc_bytes).unwrap();
+        let en: Encoder<WireMod2> = bincode::deserialize(&en_bytes).unwrap();
+        assert_eq!(bincode::serialize(&gc).unwrap(), gc_bytes);
+        assert_eq!(bincode::serialize(&en).unwrap(), en_bytes);
+        assert_eq!(gc.garbling(), en.garbling());
+        assert_eq!(gc.circuit_digest(), c.digest());
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..4 {
+            let mut bits = || (0..128).map(|_| rng.gen::<bool>() as Modulus).collect_vec();
+            let (x, y) = (bits(), bits());
+            let xs = en.encode_garbler_inputs(&x).unwrap();
+            let ys = en.encode_evaluator_inputs(&y).unwrap();
+            assert_eq!(
+                gc.eval(&c, &xs, &ys).unwrap(),
+                eval_plain(&c, &x, &y).unwrap()
+            );
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_encoder_is_stable() {
+        // The deltas serialize in the order of their moduli, whatever the
+        // order of the map holding them.
+        let mut rng = AesRng::new();
+        let qs = [2, 3, 5, 7, 11, 13, 17, 19];
+        let deltas = qs
+            .iter()
+            .map(|&q| (q, AllWire::rand_delta(&mut rng, q)))
+            .collect::<HashMap<_, _>>();
+        let zeros = qs.iter().map(|&q| AllWire::rand(&mut rng, q)).collect_vec();
+        let en = Encoder::new(zeros.clone(), zeros, deltas);
+        let bytes = bincode::serialize(&en).unwrap();
+        for _ in 0..8 {
+            // Each deserialized map hashes its keys with a fresh random state.
+            let en: Encoder<AllWire> = bincode::deserialize(&bytes).unwrap();
+            assert_eq!(bincode::serialize(&en).unwrap(), bytes);
+        }
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn encoder_zeroize() {
+        use zeroize::Zeroize;
+
+        fn assert_zeroize_on_drop<T: zeroize::ZeroizeOnDrop>() {}
+        assert_zeroize_on_drop::<Encoder<AllWire>>();
+        assert_zeroize_on_drop::<Gb<AllWire>>();
+
+        let mut rng = AesRng::from_seed(Block::from(1));
+        let deltas = [(2, AllWire::rand_delta(&mut rng, 2))]
+            .into_iter()
+            .collect();
+        let mut en = Encoder::new(vec![AllWire::rand(&mut rng, 2)], vec![], deltas);
+        en.zeroize();
+        assert_eq!(en.encode_garbler_input(1, 0).unwrap(), AllWire::zero(2));
+    }
+
+    #[cfg(feature = "test-utils")]
+    fn multiplier(nbits: usize) -> BinaryCircuit {
+        use crate::{circuit::CircuitBuilder, BinaryGadgets, BundleGadgets, FancyBinary};
+
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.bin_garbler_input(nbits);
+        let y = b.bin_evaluator_input(nbits);
+        let z = b.bin_multiplication_lower_half(&x, &y).unwrap();
+        let (z, _) = b.bin_addition(&z, &x).unwrap();
+        let z = z
+            .wires()
+            .iter()
+            .map(|w| b.negate(w))
+            .collect::<Result<_, _>>()
+            .unwrap();
+        b.output_bundle(&crate::BinaryBundle::new(z)).unwrap();
+        b.finish()
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[test]
+    fn debug_eval_binary() {
+        use crate::WireMod2;
+
+        let mut rng = rand::thread_rng();
+        let circ = multiplier(8);
+        for _ in 0..16 {
+            let x = util::u128_to_bits(rng.gen_u128(), 8);
+            let y = util::u128_to_bits(rng.gen_u128(), 8);
+            debug_eval::<WireMod2, _, _>(&circ, &x, &y, &mut rng).unwrap();
+        }
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[test]
+    fn debug_eval_projections() {
+        use crate::{
+            circuit::{ArithmeticCircuit, CircuitBuilder},
+            Fancy, FancyArithmetic,
+        };
+
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(5);
+        let y = b.evaluator_input(5);
+        let z = b.mul(&x, &y).unwrap();
+        let z = b.proj(&z, 7, Some(vec![6, 0, 2, 4, 1])).unwrap();
+        let w = b.mod_change(&y, 3).unwrap();
+        let w = b.mod_change(&w, 7).unwrap();
+        let z = b.add(&z, &w).unwrap();
+        b.output(&z).unwrap();
+        let circ = b.finish();
+
+        let mut rng = rand::thread_rng();
+        for x in 0..5 {
+            for y in 0..5 {
+                debug_eval::<AllWire, _, _>(&circ, &[x], &[y], &mut rng).unwrap();
+            }
+        }
+    }
+
+    #[cfg(feature = "test-utils")]
+    #[test]
+    fn debug_eval_reports_divergence() {
+        use crate::{circuit::CircuitType, errors::DivergenceReport, WireMod2};
+
+        let mut rng = AesRng::from_seed(Block::from(0x1234_u128));
+        let circ = multiplier(8);
+        let (x, y) = (util::u128_to_bits(45, 8), util::u128_to_bits(201, 8));
+        let tamper = |blocks: &mut [Block]| {
+            for block in blocks {
+                *block ^= Block::from(1_u128);
+            }
+        };
+        match debug_eval_tampered::<WireMod2, _, _>(&circ, &x, &y, &mut rng, tamper) {
+            Err(DivergenceReport::Divergence {
+                gate,
+                gate_type,
+                plaintext,
+                garbled,
+            }) => {
+                assert_eq!(gate_type, circ.get_gates()[gate].to_string());
+                assert!(gate_type.starts_with("And"), "{}", gate_type);
+                assert_ne!(garbled, Some(plaintext));
+            }
+            r => panic!("the tampered circuit was not caught: {:?}", r),
+        }
+    }
+
+    /// Check that garbling `c` in parallel makes what garbling it one gate
+    /// after another does, and that the garbling evaluates as `c` does.
+    fn check_garble_parallel(c: &BinaryCircuit) {
+        use crate::circuit::eval_plain;
+
+        let levels = c.levels();
+        let mut rng = rand::thread_rng();
+        for seed in [0_u128, 1, rng.gen_u128()] {
+            let seeded = || AesRng::from_seed(Block::from(seed));
+            let (en, gc) = garble_with_rng::<WireMod2, _>(c, seeded()).unwrap();
+            let (en_, gc_) = garble_parallel(c, seeded(), &levels).unwrap();
+            assert_eq!(gc_.blocks, gc.blocks);
+            assert_eq!(gc_.garbling, gc.garbling);
+            assert_eq!(gc_.circuit, gc.circuit);
+            assert_eq!(en_.garbling, en.garbling);
+            assert_eq!(en_.garbler_inputs, en.garbler_inputs);
+            assert_eq!(en_.evaluator_inputs, en.evaluator_inputs);
+            assert_eq!(en_.deltas, en.deltas);
+
+            let mut bits = |n| (0..n).map(|_| rng.gen::<bool>() as Modulus).collect_vec();
+            let gb = bits(c.num_garbler_inputs());
+            let ev = bits(c.num_evaluator_inputs());
+            let xs = en_.encode_garbler_inputs(&gb).unwrap();
+            let ys = en_.encode_evaluator_inputs(&ev).unwrap();
+            let expected = eval_plain(c, &gb, &ev).unwrap();
+            assert_eq!(gc_.eval(c, &xs, &ys).unwrap(), expected);
+            assert_eq!(gc_.eval_parallel(c, &xs, &ys, &levels).unwrap(), expected);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn garble_parallel_parsed() {
+        for c in [
+            include_str!("../circuits/AES-non-expanded.txt"),
+            include_str!("../circuits/sha-256.txt"),
+            // The INV gate overwrites the input the XOR gate of its level reads.
+            "3 4\n1 1 1\n\n2 1 0 1 2 XOR\n1 1 0 0 INV\n2 1 0 2 3 AND\n",
+        ] {
+            check_garble_parallel(&c.parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn garble_parallel_constants_and_custom_gates() {
+        use crate::{
+            circuit::{BinaryCircuit, CircuitBuilder, CustomGate, Majority},
+            Fancy, FancyBinary,
+        };
+        use std::sync::Arc;
+
+        let maj: Arc<dyn CustomGate> = Arc::new(Majority);
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.garbler_inputs(&[2; 3]);
+        let ys = b.evaluator_inputs(&[2; 2]);
+        let one = b.constant(1, 2).unwrap();
+        let m = b.custom(&maj, &[xs[0], xs[1], ys[0]]).unwrap();
+        let z = b.and(&m, &one).unwrap();
+        let n = b.negate(&ys[1]).unwrap();
+        let w = b.and(&n, &xs[2]).unwrap();
+        let zero = b.constant(0, 2).unwrap();
+        let v = b.xor(&w, &zero).unwrap();
+        b.outputs(&[z, w, v, n, one]).unwrap();
+        check_garble_parallel(&b.finish());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn eval_parallel_narrow_labels_and_errors() {
+        use crate::{
+            circuit::{eval_plain, BinaryCircuit},
+            WireMod2,
+        };
+
+        let c: BinaryCircuit = include_str!("../circuits/AES-non-expanded.txt")
+            .parse()
+            .unwrap();
+        let levels = c.levels();
+        let security = SecurityParams::new(80, 40, 80).unwrap();
+        let (en, gc) = garble_with_security::<WireMod2, _>(&c, AesRng::new(), security).unwrap();
+        let mut rng = rand::thread_rng();
+        let mut bits = || (0..128).map(|_| rng.gen::<bool>() as Modulus).collect_vec();
+        let (key, block) = (bits(), bits());
+        let xs = en.encode_garbler_inputs(&key).unwrap();
+        let ys = en.encode_evaluator_inputs(&block).unwrap();
+        let expected = eval_plain(&c, &key, &block).unwrap();
+        assert_eq!(gc.eval_parallel(&c, &xs, &ys, &levels).unwrap(), expected);
+
+        let (other, _) = garble::<WireMod2, _>(&c).unwrap();
+        let ys_ = other.encode_evaluator_inputs(&block).unwrap();
+        assert!(matches!(
+            gc.eval_parallel(&c, &xs, &ys_, &levels),
+            Err(EvaluatorError::MismatchedGarbling { .. })
+        ));
+        let mut short = gc;
+        short.blocks.pop();
+        assert!(matches!(
+            short.eval_parallel(&c, &xs, &ys, &levels),
+            Err(EvaluatorError::IoError(_))
+        ));
+        assert!(short.eval(&c, &xs, &ys).is_err());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    #[cfg(feature = "std")]
+    fn garbled_size_estimate_is_exact() {
+        use crate::{
+            circuit::{ArithmeticCircuit, BinaryCircuit, CircuitBuilder, CustomGate, Majority},
+            Fancy, FancyArithmetic, FancyBinary, WireMod2,
+        };
+        use std::sync::Arc;
+
+        fn check<Wire: WireLabel, C: EvaluableCircuit<Gb<Wire>>>(c: &C) {
+            let (_, gc) = garble::<Wire, _>(c).unwrap();
+            assert_eq!(
+                c.garbled_size_estimate(SecurityParams::DEFAULT),
+                gc.nbytes()
+            );
+            let security = SecurityParams::SIMULATION;
+            let rng = AesRng::new();
+            let (_, gc) = garble_with_security::<Wire, _>(c, rng, security).unwrap();
+            assert_eq!((c.garbled_size_estimate(security) + 15) / 16, gc.size());
+        }
+
+        for c in [
+            include_str!("../circuits/AES-non-expanded.txt"),
+            include_str!("../circuits/sha-256.txt"),
+        ] {
+            let c: BinaryCircuit = c.parse().unwrap();
+            // Two labels for each AND gate, a label for each constant, and
+            // two hashes for each output.
+            let stats = c.stats();
+            let expected = 16 * (2 * stats.nmuls + stats.nconsts + 2 * stats.noutputs);
+            assert_eq!(c.garbled_size_estimate(SecurityParams::DEFAULT), expected);
+            check::<WireMod2, _>(&c);
+        }
+
+        // Constants, one of them an output, and custom gates.
+        let maj: Arc<dyn CustomGate> = Arc::new(Majority);
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.garbler_inputs(&[2; 3]);
+        let y = b.evaluator_input(2);
+        let one = b.constant(1, 2).unwrap();
+        let m = b.custom(&maj, &[xs[0], xs[1], y]).unwrap();
+        let z = b.and(&m, &one).unwrap();
+        let w = b.xor(&z, &xs[2]).unwrap();
+        b.outputs(&[z, w, one]).unwrap();
+        check::<WireMod2, _>(&b.finish());
+
+        // Multiplications of equal and unequal moduli, and projections.
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(7);
+        let y = b.evaluator_input(7);
+        let s = b.evaluator_input(3);
+        let c = b.constant(1, 2).unwrap();
+        let z = b.mul(&x, &y).unwrap();
+        let z = b.mul(&z, &s).unwrap();
+        let p = b.proj(&z, 3, Some(vec![0, 1, 2, 0, 1, 2, 0])).unwrap();
+        let q = b.mul(&p, &s).unwrap();
+        b.outputs(&[z, q, c]).unwrap();
+        check::<AllWire, _>(&b.finish());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn garble_into_channel() {
+        use crate::{circuit::eval_plain, WireMod2};
+        use scuttlebutt::unix_channel_pair;
+
+        let c: BinaryCircuit = include_str!("../circuits/AES-non-expanded.txt")
+            .parse()
+            .unwrap();
+        let liveness = c.liveness();
+        let mut rng = rand::thread_rng();
+        let mut bits = || (0..128).map(|_| rng.gen::<bool>() as Modulus).collect_vec();
+        let (gb, ev) = (bits(), bits());
+        let expected = eval_plain(&c, &gb, &ev).unwrap();
+
+        // Stream the gates through a socket, sending the inputs first.
+        let (sender, receiver) = unix_channel_pair();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let handle = std::thread::spawn({
+            let (c, liveness, gb, ev) = (c.clone(), liveness.clone(), gb.clone(), ev.clone());
+            move || {
+                garble_into::<WireMod2, _, _, _>(&c, AesRng::new(), &liveness, sender, |en| {
+                    let xs = en.encode_garbler_inputs(&gb)?;
+                    let ys = en.encode_evaluator_inputs(&ev)?;
+                    tx.send((xs, ys)).unwrap();
+                    Ok(())
+                })
+                .unwrap();
+            }
+        });
+        let (xs, ys) = rx.recv().unwrap();
+        assert_eq!(
+            eval_from(&c, receiver, &xs, &ys, &liveness).unwrap(),
+            expected
+        );
+        handle.join().unwrap();
+
+        // The stream is the garbled circuit made in memory, and either is
+        // evaluated by the other's evaluator.
+        let seed = Block::from(rng.gen_u128());
+        let (en, gc) = garble_with_rng::<WireMod2, _>(&c, AesRng::from_seed(seed)).unwrap();
+        let channel = Channel::new(GarbledReader::new(&[]), GarbledWriter::new(None));
+        let en_ = garble_into::<WireMod2, _, _, _>(
+            &c,
+            AesRng::from_seed(seed),
+            &liveness,
+            channel.clone(),
+            |_| Ok(()),
+        )
+        .unwrap();
+        let blocks = Rc::try_unwrap(channel.writer())
+            .unwrap()
+            .into_inner()
+            .into_blocks();
+        assert_eq!(blocks, gc.blocks);
+        assert_eq!(en_.garbling, en.garbling);
+        assert_eq!(en_.garbler_inputs, en.garbler_inputs);
+        assert_eq!(en_.evaluator_inputs, en.evaluator_inputs);
+        assert_eq!(en_.deltas, en.deltas);
+
+        let xs = en.encode_garbler_inputs(&gb).unwrap();
+        let ys = en.encode_evaluator_inputs(&ev).unwrap();
+        let channel = Channel::new(GarbledReader::new(&blocks), GarbledWriter::new(None));
+        assert_eq!(
+            eval_from(&c, channel, &xs, &ys, &liveness).unwrap(),
+            expected
+        );
+    }
+}
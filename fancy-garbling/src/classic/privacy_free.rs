@@ -0,0 +1,442 @@
+//! Privacy-free garbling of binary circuits, for zero-knowledge proofs in
+//! which the evaluator knows every input.
+//!
+//! This is the privacy-free half-gates scheme of Zahur, Rosulek, and Evans.
+//! The zero label of every wire has color 0, so that the color of a label is
+//! the value it encodes: nothing is hidden from the evaluator, but it cannot
+//! make the label of a value a wire does not have. XOR gates are free, and an
+//! AND gate sends one ciphertext rather than two. With `H'(x)` for `H(x, j)`
+//! with its color cleared, where `j` numbers the AND gates from 0, the garbler
+//! of an AND gate with input zero labels `A` and `B` computes
+//!
+//! ```text
+//! G = H'(B) ⊕ H'(B ⊕ Δ) ⊕ A
+//! C = H'(B)
+//! ```
+//!
+//! and the evaluator, holding `X` and `Y` where `Y` has color `b`, computes
+//! `Z = H'(Y) ⊕ b·(G ⊕ X)`. Negating a wire would give its zero label color
+//! 1, so an INV gate adds instead the label of a wire holding one, which is
+//! sent before everything else if the circuit has INV gates. Constants are
+//! sent as the labels of their values. Outputs are not hashed: the evaluator
+//! reads the value of an output from the color of its label, and proves it
+//! with the label, which [`OutputVerifier::verify`] checks.
+
+use super::{check_pairing, EncodedInputs, Encoder, GarblingId};
+use crate::{
+    circuit::{
+        check_constant, evaluator_input, garbler_input, public_input, BinaryCircuit, BinaryGate,
+        CircuitDigest, CircuitType, Sources,
+    },
+    errors::{EvaluatorError, FancyError, GarblerError},
+    util::tweak,
+    Modulus, WireLabel, WireMod2,
+};
+use itertools::Itertools;
+use rand::Rng;
+use scuttlebutt::{AesRng, Block};
+use std::collections::HashMap;
+
+/// A binary circuit garbled privacy-free by [`garble_privacy_free`].
+///
+/// Evaluating it gives the labels of the outputs, whose colors are their
+/// values, and which the garbler checks with the [`OutputVerifier`] of the
+/// garbling.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PrivacyFreeCircuit {
+    blocks: Vec<Block>,
+    garbling: GarblingId,
+    circuit: CircuitDigest,
+}
+
+/// The zero labels of the outputs of a privacy-free garbling, and its delta,
+/// which the garbler keeps to check the output labels the evaluator returns.
+///
+/// Like an [`Encoder`], it must never be sent to the evaluator.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutputVerifier {
+    zeros: Vec<WireMod2>,
+    delta: WireMod2,
+}
+
+/// Where the label or ciphertext of each gate is in a privacy-free garbled
+/// circuit.
+struct Layout {
+    /// Whether the label of one comes first, for the INV gates.
+    one: bool,
+    /// For each gate, the index of its block, and its number among the AND
+    /// gates.
+    firsts: Vec<(usize, usize)>,
+    /// The number of blocks.
+    nblocks: usize,
+}
+
+impl Layout {
+    fn new(c: &BinaryCircuit) -> Result<Self, FancyError> {
+        let gates = c.get_gates();
+        let one = gates.iter().any(|g| matches!(g, BinaryGate::Inv { .. }));
+        let mut nblocks = usize::from(one);
+        let mut nands = 0;
+        let mut firsts = Vec::with_capacity(gates.len());
+        for (i, gate) in gates.iter().enumerate() {
+            firsts.push((nblocks, nands));
+            match *gate {
+                BinaryGate::Constant { val } => {
+                    check_constant(i, val, 2)?;
+                    nblocks += 1;
+                }
+                BinaryGate::And { .. } => {
+                    nblocks += 1;
+                    nands += 1;
+                }
+                BinaryGate::Custom { .. } => {
+                    return Err(FancyError::InvalidArg(format!(
+                        "gate {} is a custom gate, which cannot be garbled privacy-free",
+                        i
+                    )))
+                }
+                _ => {}
+            }
+        }
+        Ok(Layout {
+            one,
+            firsts,
+            nblocks,
+        })
+    }
+}
+
+/// Clear the color of `block`.
+fn clear_color(block: Block) -> Block {
+    Block::from(u128::from(block) & !1)
+}
+
+/// Garble a privacy-free AND gate number `gate_num` with input zero labels `a`
+/// and `b`, returning its ciphertext and its output zero label.
+fn garble_and(a: &WireMod2, b: &WireMod2, delta: &WireMod2, gate_num: usize) -> (Block, WireMod2) {
+    let t = tweak(gate_num);
+    let hb0 = clear_color(b.hash(t));
+    let hb1 = clear_color(b.plus(delta).hash(t));
+    (hb0 ^ hb1 ^ a.as_block(), WireMod2::from_block(hb0, 2))
+}
+
+/// Evaluate a privacy-free AND gate number `gate_num` on the labels `a` and
+/// `b` with its ciphertext `gate`.
+fn eval_and(a: &WireMod2, b: &WireMod2, gate: &Block, gate_num: usize) -> WireMod2 {
+    let hb = clear_color(b.hash(tweak(gate_num)));
+    let z = if b.color() == 1 {
+        hb ^ *gate ^ a.as_block()
+    } else {
+        hb
+    };
+    WireMod2::from_block(z, 2)
+}
+
+/// Garble a binary circuit privacy-free, for an evaluator that knows all of
+/// its inputs.
+///
+/// AND gates take one ciphertext each, and XOR gates none. The encoder
+/// encodes the inputs as [`garble`](super::garble)'s does, but the colors of
+/// the labels are their values. Fails if the circuit has custom gates or
+/// public inputs. The garbling uses the default security parameters.
+pub fn garble_privacy_free(
+    c: &BinaryCircuit,
+) -> Result<(Encoder<WireMod2>, PrivacyFreeCircuit, OutputVerifier), GarblerError> {
+    let layout = Layout::new(c)?;
+    let mut rng = AesRng::new();
+    let garbling = GarblingId::random(&mut rng);
+    let delta = WireMod2::rand_delta(&mut rng, 2);
+    let mut zero = || WireMod2::from_block(clear_color(rng.gen()), 2);
+
+    let gb_inps = (0..c.num_garbler_inputs()).map(|_| zero()).collect_vec();
+    let ev_inps = (0..c.num_evaluator_inputs()).map(|_| zero()).collect_vec();
+    let mut blocks = vec![Block::default(); layout.nblocks];
+    let one = zero();
+    if layout.one {
+        blocks[0] = one.plus(&delta).as_block();
+    }
+    let mut constants = HashMap::new();
+    for (i, gate) in c.get_gates().iter().enumerate() {
+        if let BinaryGate::Constant { val } = *gate {
+            let z = zero();
+            blocks[layout.firsts[i].0] = z.plus(&delta.cmul(val)).as_block();
+            constants.insert(i, z);
+        }
+    }
+
+    let levels = c.levels();
+    let values = levels.eval(
+        |i, x: &Sources<WireMod2>| {
+            let z = match c.get_gates()[i] {
+                BinaryGate::GarblerInput { id } => garbler_input(&gb_inps, id)?,
+                BinaryGate::EvaluatorInput { id } => evaluator_input(&ev_inps, id)?,
+                BinaryGate::PublicInput { id } => public_input::<WireMod2>(&[], id)?,
+                BinaryGate::Constant { .. } => constants[&i],
+                BinaryGate::Xor { .. } => x.get(0)?.plus(x.get(1)?),
+                BinaryGate::Inv { .. } => x.get(0)?.plus(&one),
+                BinaryGate::And { .. } => {
+                    let (_, gate_num) = layout.firsts[i];
+                    let (gate, z) = garble_and(x.get(0)?, x.get(1)?, &delta, gate_num);
+                    return Ok((z, Some(gate)));
+                }
+                BinaryGate::Custom { .. } => unreachable!("the layout has no custom gates"),
+            };
+            Ok::<_, GarblerError>((z, None))
+        },
+        |i, gate| {
+            if let Some(gate) = gate {
+                blocks[layout.firsts[i].0] = gate;
+            }
+        },
+    )?;
+    let zeros = levels.outputs(&values)?;
+
+    let mut en = Encoder::new(gb_inps, ev_inps, HashMap::from([(2, delta)]));
+    en.garbling = garbling;
+    let gc = PrivacyFreeCircuit {
+        blocks,
+        garbling,
+        circuit: c.digest(),
+    };
+    Ok((en, gc, OutputVerifier { zeros, delta }))
+}
+
+impl PrivacyFreeCircuit {
+    /// Evaluate the garbled circuit, returning the labels of its outputs. The
+    /// value of each output is the color of its label.
+    ///
+    /// Fails as [`GarbledCircuit::eval`](super::GarbledCircuit::eval) does if
+    /// the inputs or `c` are not those of this garbling.
+    pub fn eval(
+        &self,
+        c: &BinaryCircuit,
+        garbler_inputs: &EncodedInputs<WireMod2>,
+        evaluator_inputs: &EncodedInputs<WireMod2>,
+    ) -> Result<Vec<WireMod2>, EvaluatorError> {
+        check_pairing(
+            self.garbling,
+            self.circuit,
+            c,
+            garbler_inputs,
+            evaluator_inputs,
+        )?;
+        let layout = Layout::new(c)?;
+        if self.blocks.len() != layout.nblocks {
+            return Err(EvaluatorError::IoError(
+                std::io::ErrorKind::UnexpectedEof.into(),
+            ));
+        }
+        let one = WireMod2::from_block(self.blocks.first().copied().unwrap_or_default(), 2);
+
+        let levels = c.levels();
+        let values = levels.eval(
+            |i, x: &Sources<WireMod2>| {
+                let (first, gate_num) = layout.firsts[i];
+                let z = match c.get_gates()[i] {
+                    BinaryGate::GarblerInput { id } => garbler_input(garbler_inputs, id)?,
+                    BinaryGate::EvaluatorInput { id } => evaluator_input(evaluator_inputs, id)?,
+                    BinaryGate::PublicInput { id } => public_input::<WireMod2>(&[], id)?,
+                    BinaryGate::Constant { .. } => WireMod2::from_block(self.blocks[first], 2),
+                    BinaryGate::Xor { .. } => x.get(0)?.plus(x.get(1)?),
+                    BinaryGate::Inv { .. } => x.get(0)?.plus(&one),
+                    BinaryGate::And { .. } => {
+                        eval_and(x.get(0)?, x.get(1)?, &self.blocks[first], gate_num)
+                    }
+                    BinaryGate::Custom { .. } => unreachable!("the layout has no custom gates"),
+                };
+                Ok::<_, EvaluatorError>((z, ()))
+            },
+            |_, ()| {},
+        )?;
+        Ok(levels.outputs(&values)?)
+    }
+
+    /// The number of blocks in the garbled circuit: the label of one if the
+    /// circuit has INV gates, a label for each constant, and a ciphertext for
+    /// each AND gate.
+    pub fn size(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// The id of the garbling this garbled circuit comes from.
+    pub fn garbling(&self) -> GarblingId {
+        self.garbling
+    }
+}
+
+impl OutputVerifier {
+    /// Check that `labels` are labels of the outputs of the garbling, and
+    /// return the values they encode.
+    ///
+    /// Fails with [`GarblerError::InvalidOutputLabel`] for the first label
+    /// that is neither label of its output, which an evaluator following the
+    /// protocol never returns.
+    pub fn verify(&self, labels: &[WireMod2]) -> Result<Vec<Modulus>, GarblerError> {
+        if labels.len() != self.zeros.len() {
+            return Err(GarblerError::FancyError(FancyError::InvalidArgNum {
+                got: labels.len(),
+                needed: self.zeros.len(),
+            }));
+        }
+        labels
+            .iter()
+            .zip(&self.zeros)
+            .enumerate()
+            .map(|(i, (label, zero))| {
+                if label == zero {
+                    Ok(0)
+                } else if *label == zero.plus(&self.delta) {
+                    Ok(1)
+                } else {
+                    Err(GarblerError::InvalidOutputLabel(i))
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{eval_plain, CircuitBuilder, CustomGate, Majority},
+        Fancy, FancyBinary,
+    };
+    use std::sync::Arc;
+
+    /// Garble `c` privacy-free, evaluate it on `gb` and `ev`, check the
+    /// outputs against those in plaintext, and return the output labels.
+    fn check(
+        c: &BinaryCircuit,
+        gb: &[Modulus],
+        ev: &[Modulus],
+    ) -> (PrivacyFreeCircuit, OutputVerifier, Vec<WireMod2>) {
+        let (en, gc, verifier) = garble_privacy_free(c).unwrap();
+        let xs = en.encode_garbler_inputs(gb).unwrap();
+        let ys = en.encode_evaluator_inputs(ev).unwrap();
+        let labels = gc.eval(c, &xs, &ys).unwrap();
+        let expected = eval_plain(c, gb, ev).unwrap();
+        assert_eq!(labels.iter().map(WireLabel::color).collect_vec(), expected);
+        assert_eq!(verifier.verify(&labels).unwrap(), expected);
+        (gc, verifier, labels)
+    }
+
+    /// A circuit with every kind of gate garbled privacy-free.
+    fn small() -> BinaryCircuit {
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.garbler_input(2);
+        let y = b.evaluator_input(2);
+        let z = b.evaluator_input(2);
+        let one = b.constant(1, 2).unwrap();
+        let a = b.and(&x, &y).unwrap();
+        let n = b.negate(&a).unwrap();
+        let o = b.xor(&n, &z).unwrap();
+        let w = b.and(&o, &one).unwrap();
+        let v = b.and(&w, &x).unwrap();
+        b.outputs(&[a, n, o, w, v, one]).unwrap();
+        b.finish()
+    }
+
+    #[test]
+    fn small_circuits() {
+        let c = small();
+        for bits in 0..8 {
+            let (gc, _, _) = check(&c, &[bits & 1], &[(bits >> 1) & 1, bits >> 2]);
+            // The label of one, the constant, and a ciphertext per AND gate.
+            assert_eq!(gc.size(), 1 + 1 + 3);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn aes_128() {
+        let c: BinaryCircuit = include_str!("../../circuits/AES-non-expanded.txt")
+            .parse()
+            .unwrap();
+        let mut rng = rand::thread_rng();
+        let mut bits = || (0..128).map(|_| rng.gen::<bool>() as Modulus).collect_vec();
+        let (key, block) = (bits(), bits());
+        let (gc, _, _) = check(&c, &key, &block);
+        // Half of what the half-gates scheme sends for the AND gates.
+        let stats = c.stats();
+        assert_eq!(gc.size(), 1 + stats.nconsts + stats.nmuls);
+    }
+
+    #[test]
+    fn outputs_are_authentic() {
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.garbler_input(2);
+        let y = b.evaluator_input(2);
+        let z = b.and(&x, &y).unwrap();
+        b.output(&z).unwrap();
+        let c = b.finish();
+
+        for (gb, ev) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            let (en, gc, verifier) = garble_privacy_free(&c).unwrap();
+            let xs = en.encode_garbler_inputs(&[gb]).unwrap();
+            let ys = en.encode_evaluator_inputs(&[ev]).unwrap();
+            let label = gc.eval(&c, &xs, &ys).unwrap()[0];
+            assert_eq!(verifier.verify(&[label]).unwrap(), [gb & ev]);
+
+            // What the evaluator can compute from its labels and the
+            // ciphertext does not give the label of the other value.
+            let (x, g) = (xs[0].as_block(), gc.blocks[0]);
+            let hy = ys[0].hash(tweak(0));
+            let forgeries = [
+                label.as_block() ^ Block::from(1),
+                label.as_block() ^ g,
+                label.as_block() ^ x,
+                label.as_block() ^ g ^ x,
+                hy ^ g,
+                clear_color(hy) ^ g ^ x ^ Block::from(1),
+            ];
+            for forgery in forgeries {
+                let forgery = WireMod2::from_block(forgery, 2);
+                assert!(matches!(
+                    verifier.verify(&[forgery]),
+                    Err(GarblerError::InvalidOutputLabel(0))
+                ));
+            }
+
+            // Nor does evaluating a tampered ciphertext.
+            let tampered = PrivacyFreeCircuit {
+                blocks: vec![g ^ Block::from(2)],
+                ..gc
+            };
+            let label = tampered.eval(&c, &xs, &ys).unwrap()[0];
+            assert_eq!(verifier.verify(&[label]).is_ok(), ev == 0);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_inputs() {
+        let c = small();
+        let (en, gc, verifier) = garble_privacy_free(&c).unwrap();
+        assert!(matches!(
+            verifier.verify(&[]),
+            Err(GarblerError::FancyError(FancyError::InvalidArgNum {
+                got: 0,
+                needed: 6
+            }))
+        ));
+        let (other, _, _) = garble_privacy_free(&c).unwrap();
+        let xs = en.encode_garbler_inputs(&[1]).unwrap();
+        let ys = other.encode_evaluator_inputs(&[1, 0]).unwrap();
+        assert!(matches!(
+            gc.eval(&c, &xs, &ys),
+            Err(EvaluatorError::MismatchedGarbling { .. })
+        ));
+
+        let maj: Arc<dyn CustomGate> = Arc::new(Majority);
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.garbler_inputs(&[2; 3]);
+        let m = b.custom(&maj, &xs).unwrap();
+        b.output(&m).unwrap();
+        assert!(matches!(
+            garble_privacy_free(&b.finish()),
+            Err(GarblerError::FancyError(FancyError::InvalidArg(_)))
+        ));
+    }
+}
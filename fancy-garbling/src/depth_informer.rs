@@ -2,26 +2,27 @@
 
 use crate::{
     errors::FancyError,
-    fancy::{Fancy, FancyInput, FancyReveal, HasModulus},
-    FancyArithmetic, FancyBinary,
+    fancy::{check_inputs, check_moduli, Fancy, FancyInput, FancyReveal, HasModulus},
+    FancyArithmetic, FancyBinary, Modulus,
 };
 use std::cmp::max;
 
 /// Carries the depth of the computation.
 #[derive(Clone, Debug)]
 pub struct DepthItem {
-    modulus: u16,
+    modulus: Modulus,
     depth: usize,
 }
 
 impl HasModulus for DepthItem {
-    fn modulus(&self) -> u16 {
+    fn modulus(&self) -> Modulus {
         self.modulus
     }
 }
 
 /// Errors thrown by the Fancy computation.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum DepthError {
     /// Projection is unsupported by the depth informer
     ProjUnsupported,
@@ -44,6 +45,15 @@ impl std::fmt::Display for DepthError {
     }
 }
 
+impl std::error::Error for DepthError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Underlying(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 /// Fancy Object which computes information about the circuit of interest to FHE.
 #[derive(Clone, Debug)]
 pub struct DepthInformer {
@@ -88,13 +98,18 @@ impl DepthInformer {
             mul_depth: 0,
         }
     }
+
+    /// The multiplicative depth of the computation.
+    pub fn mul_depth(&self) -> usize {
+        self.mul_depth
+    }
 }
 
 impl FancyInput for DepthInformer {
     type Item = DepthItem;
     type Error = DepthError;
 
-    fn receive_many(&mut self, moduli: &[u16]) -> Result<Vec<Self::Item>, Self::Error> {
+    fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<Self::Item>, Self::Error> {
         self.ninputs += moduli.len();
         Ok(moduli
             .iter()
@@ -107,9 +122,10 @@ impl FancyInput for DepthInformer {
 
     fn encode_many(
         &mut self,
-        _values: &[u16],
-        moduli: &[u16],
+        values: &[Modulus],
+        moduli: &[Modulus],
     ) -> Result<Vec<Self::Item>, Self::Error> {
+        check_inputs(values, moduli.iter().copied())?;
         self.receive_many(moduli)
     }
 }
@@ -134,6 +150,7 @@ impl FancyBinary for DepthInformer {
 
 impl FancyArithmetic for DepthInformer {
     fn add(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        check_moduli("add", x.modulus, y.modulus)?;
         self.nadds += 1;
         Ok(DepthItem {
             modulus: x.modulus,
@@ -142,6 +159,7 @@ impl FancyArithmetic for DepthInformer {
     }
 
     fn sub(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        check_moduli("sub", x.modulus, y.modulus)?;
         self.nsubs += 1;
         Ok(DepthItem {
             modulus: x.modulus,
@@ -149,7 +167,7 @@ impl FancyArithmetic for DepthInformer {
         })
     }
 
-    fn cmul(&mut self, x: &Self::Item, _y: u16) -> Result<Self::Item, Self::Error> {
+    fn cmul(&mut self, x: &Self::Item, _y: Modulus) -> Result<Self::Item, Self::Error> {
         self.ncmuls += 1;
         Ok(DepthItem {
             modulus: x.modulus,
@@ -160,7 +178,7 @@ impl FancyArithmetic for DepthInformer {
     fn mul(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
         self.nmuls += 1;
         Ok(DepthItem {
-            modulus: x.modulus,
+            modulus: max(x.modulus, y.modulus),
             depth: max(x.depth, y.depth) + 1,
         })
     }
@@ -168,8 +186,8 @@ impl FancyArithmetic for DepthInformer {
     fn proj(
         &mut self,
         _x: &Self::Item,
-        _q: u16,
-        _tt: Option<Vec<u16>>,
+        _q: Modulus,
+        _tt: Option<Vec<Modulus>>,
     ) -> Result<Self::Item, Self::Error> {
         Err(DepthError::ProjUnsupported)
     }
@@ -179,7 +197,7 @@ impl Fancy for DepthInformer {
     type Item = DepthItem;
     type Error = DepthError;
 
-    fn constant(&mut self, _val: u16, q: u16) -> Result<Self::Item, Self::Error> {
+    fn constant(&mut self, _val: Modulus, q: Modulus) -> Result<Self::Item, Self::Error> {
         self.nconstants += 1;
         Ok(DepthItem {
             modulus: q,
@@ -187,14 +205,36 @@ impl Fancy for DepthInformer {
         })
     }
 
-    fn output(&mut self, x: &Self::Item) -> Result<Option<u16>, Self::Error> {
+    fn output(&mut self, x: &Self::Item) -> Result<Option<Modulus>, Self::Error> {
         self.mul_depth = max(self.mul_depth, x.depth);
         Ok(None)
     }
 }
 
 impl FancyReveal for DepthInformer {
-    fn reveal(&mut self, _x: &Self::Item) -> Result<u16, Self::Error> {
+    fn reveal(&mut self, _x: &Self::Item) -> Result<Modulus, Self::Error> {
         Ok(0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unequal_moduli() {
+        let mut f = DepthInformer::new();
+        let x = f.encode(0, 3).unwrap();
+        let y = f.encode(0, 5).unwrap();
+        assert!(matches!(
+            f.add(&x, &y),
+            Err(DepthError::Underlying(FancyError::UnequalModuli {
+                op: "add",
+                x: 3,
+                y: 5
+            }))
+        ));
+        assert!(f.sub(&x, &y).is_err());
+        assert_eq!(f.mul(&x, &y).unwrap().modulus(), 5);
+    }
+}
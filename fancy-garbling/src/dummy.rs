@@ -4,36 +4,41 @@
 //! creating any circuits.
 
 use crate::{
-    check_binary, derive_binary,
+    check_binary,
+    circuit::{check_arity, CustomGate},
     errors::{DummyError, FancyError},
-    fancy::{Fancy, FancyInput, FancyReveal, HasModulus},
-    FancyArithmetic, FancyBinary,
+    fancy::{check_inputs, check_moduli, Fancy, FancyInput, FancyReveal, HasModulus},
+    util, FancyArithmetic, FancyBinary, Modulus,
 };
+use std::sync::Arc;
 
-/// Simple struct that performs the fancy computation over `u16`.
+mod profiler;
+pub use profiler::{DummyProfiler, FlagStats, Probe, ProfileReport, RangeStats};
+
+/// Simple struct that performs the fancy computation over `Modulus`.
 pub struct Dummy {}
 
-/// Wrapper around `u16`.
+/// Wrapper around `Modulus`.
 #[derive(Clone, Debug)]
 pub struct DummyVal {
-    val: u16,
-    modulus: u16,
+    val: Modulus,
+    modulus: Modulus,
 }
 
 impl HasModulus for DummyVal {
-    fn modulus(&self) -> u16 {
+    fn modulus(&self) -> Modulus {
         self.modulus
     }
 }
 
 impl DummyVal {
     /// Create a new DummyVal.
-    pub fn new(val: u16, modulus: u16) -> Self {
+    pub fn new(val: Modulus, modulus: Modulus) -> Self {
         Self { val, modulus }
     }
 
     /// Extract the value.
-    pub fn val(&self) -> u16 {
+    pub fn val(&self) -> Modulus {
         self.val
     }
 }
@@ -50,15 +55,21 @@ impl FancyInput for Dummy {
     type Error = DummyError;
 
     /// Encode a single dummy value.
-    fn encode(&mut self, value: u16, modulus: u16) -> Result<DummyVal, DummyError> {
+    fn encode(&mut self, value: Modulus, modulus: Modulus) -> Result<DummyVal, DummyError> {
+        check_inputs(&[value], [modulus])?;
         Ok(DummyVal::new(value, modulus))
     }
 
     /// Encode a slice of inputs and a slice of moduli as DummyVals.
-    fn encode_many(&mut self, xs: &[u16], moduli: &[u16]) -> Result<Vec<DummyVal>, DummyError> {
+    fn encode_many(
+        &mut self,
+        xs: &[Modulus],
+        moduli: &[Modulus],
+    ) -> Result<Vec<DummyVal>, DummyError> {
         if xs.len() != moduli.len() {
             return Err(DummyError::EncodingError);
         }
+        check_inputs(xs, moduli.iter().copied())?;
         Ok(xs
             .iter()
             .zip(moduli.iter())
@@ -66,45 +77,77 @@ impl FancyInput for Dummy {
             .collect())
     }
 
-    fn receive_many(&mut self, _moduli: &[u16]) -> Result<Vec<DummyVal>, DummyError> {
+    fn receive_many(&mut self, _moduli: &[Modulus]) -> Result<Vec<DummyVal>, DummyError> {
         // Receive is undefined for Dummy which is a single party "protocol"
         Err(DummyError::EncodingError)
     }
 }
 
-derive_binary!(Dummy);
+impl FancyBinary for Dummy {
+    fn xor(&mut self, x: &DummyVal, y: &DummyVal) -> Result<DummyVal, Self::Error> {
+        check_binary!(x);
+        check_binary!(y);
+        self.add(x, y)
+    }
+
+    fn and(&mut self, x: &DummyVal, y: &DummyVal) -> Result<DummyVal, Self::Error> {
+        check_binary!(x);
+        check_binary!(y);
+        self.mul(x, y)
+    }
+
+    fn negate(&mut self, x: &DummyVal) -> Result<DummyVal, Self::Error> {
+        check_binary!(x);
+        let c = self.constant(1, 2)?;
+        self.xor(x, &c)
+    }
+
+    /// Evaluate the gate on the plaintext values.
+    fn custom(
+        &mut self,
+        gate: &Arc<dyn CustomGate>,
+        inputs: &[DummyVal],
+    ) -> Result<DummyVal, Self::Error> {
+        check_arity(gate.as_ref(), inputs.len())?;
+        let mut bits = Vec::with_capacity(inputs.len());
+        for x in inputs {
+            check_binary!(x);
+            bits.push(x.val == 1);
+        }
+        Ok(DummyVal::new(gate.eval(&bits) as Modulus, 2))
+    }
+}
 
 impl FancyArithmetic for Dummy {
     fn add(&mut self, x: &DummyVal, y: &DummyVal) -> Result<DummyVal, Self::Error> {
-        if x.modulus() != y.modulus() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
-        }
+        check_moduli("add", x.modulus(), y.modulus())?;
         Ok(DummyVal {
-            val: (x.val + y.val) % x.modulus,
+            val: util::add_mod(x.val, y.val, x.modulus),
             modulus: x.modulus,
         })
     }
 
     fn sub(&mut self, x: &DummyVal, y: &DummyVal) -> Result<DummyVal, Self::Error> {
-        if x.modulus() != y.modulus() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
-        }
+        check_moduli("sub", x.modulus(), y.modulus())?;
         Ok(DummyVal {
-            val: (x.modulus + x.val - y.val) % x.modulus,
+            val: util::add_mod(x.val, x.modulus - y.val, x.modulus),
             modulus: x.modulus,
         })
     }
 
-    fn cmul(&mut self, x: &DummyVal, c: u16) -> Result<DummyVal, Self::Error> {
+    fn cmul(&mut self, x: &DummyVal, c: Modulus) -> Result<DummyVal, Self::Error> {
         Ok(DummyVal {
-            val: (x.val * c) % x.modulus,
+            val: util::mul_mod(x.val, c, x.modulus),
             modulus: x.modulus,
         })
     }
 
     fn mul(&mut self, x: &DummyVal, y: &DummyVal) -> Result<DummyVal, Self::Error> {
+        if x.modulus() < y.modulus() {
+            return self.mul(y, x);
+        }
         Ok(DummyVal {
-            val: x.val * y.val % x.modulus,
+            val: util::mul_mod(x.val, y.val, x.modulus),
             modulus: x.modulus,
         })
     }
@@ -112,8 +155,8 @@ impl FancyArithmetic for Dummy {
     fn proj(
         &mut self,
         x: &DummyVal,
-        modulus: u16,
-        tt: Option<Vec<u16>>,
+        modulus: Modulus,
+        tt: Option<Vec<Modulus>>,
     ) -> Result<DummyVal, Self::Error> {
         let tt = tt.ok_or_else(|| Self::Error::from(FancyError::NoTruthTable))?;
         if tt.len() < x.modulus() as usize || !tt.iter().all(|&x| x < modulus) {
@@ -128,17 +171,17 @@ impl Fancy for Dummy {
     type Item = DummyVal;
     type Error = DummyError;
 
-    fn constant(&mut self, val: u16, modulus: u16) -> Result<DummyVal, Self::Error> {
+    fn constant(&mut self, val: Modulus, modulus: Modulus) -> Result<DummyVal, Self::Error> {
         Ok(DummyVal { val, modulus })
     }
 
-    fn output(&mut self, x: &DummyVal) -> Result<Option<u16>, Self::Error> {
+    fn output(&mut self, x: &DummyVal) -> Result<Option<Modulus>, Self::Error> {
         Ok(Some(x.val))
     }
 }
 
 impl FancyReveal for Dummy {
-    fn reveal(&mut self, x: &DummyVal) -> Result<u16, DummyError> {
+    fn reveal(&mut self, x: &DummyVal) -> Result<Modulus, DummyError> {
         Ok(x.val)
     }
 }
@@ -193,6 +236,44 @@ mod bundle {
         }
     }
 
+    #[test]
+    fn test_bin_to_crt() {
+        let mut rng = thread_rng();
+        for _ in 0..64 {
+            let q = rng.gen_usable_composite_modulus();
+            let nbits = 1 + rng.gen_usize() % 128;
+            let x = rng.gen_u128() & (u128::MAX >> (128 - nbits));
+            let mut d = Dummy::new();
+            let out;
+            {
+                let x = d.bin_encode(x, nbits).unwrap();
+                let z = d.bin_to_crt(&x, q).unwrap();
+                assert_eq!(z.composite_modulus(), q);
+                out = d.crt_output(&z).unwrap().unwrap();
+            }
+            assert_eq!(out, x % q);
+        }
+    }
+
+    #[test]
+    fn test_crt_to_bin() {
+        let mut rng = thread_rng();
+        for _ in 0..64 {
+            let q = rng.gen_usable_composite_modulus();
+            let nbits = 1 + rng.gen_usize() % 128;
+            let x = rng.gen_u128() % q;
+            let mut d = Dummy::new();
+            let out;
+            {
+                let x = d.crt_encode(x, q).unwrap();
+                let z = d.crt_to_bin(&x, nbits).unwrap();
+                assert_eq!(z.size(), nbits);
+                out = d.bin_output(&z).unwrap().unwrap();
+            }
+            assert_eq!(out, x & (u128::MAX >> (128 - nbits)));
+        }
+    }
+
     #[test]
     fn test_binary_cmul() {
         let mut rng = thread_rng();
@@ -479,7 +560,7 @@ mod bundle {
             let mut d = Dummy::new();
             let out;
             {
-                let b = d.encode(b as u16, 2).unwrap();
+                let b = d.encode(b as Modulus, 2).unwrap();
                 let x = d.crt_encode(x, q).unwrap();
                 let z = d.mask(&b, &x).unwrap().into();
                 out = d.crt_output(&z).unwrap().unwrap();
@@ -561,7 +642,7 @@ mod bundle {
                 let z = d.bin_eq_bundles(&x, &y).unwrap();
                 out = d.output(&z).unwrap().unwrap();
             }
-            assert_eq!(out, (x == y) as u16);
+            assert_eq!(out, (x == y) as Modulus);
         }
     }
 
@@ -585,7 +666,7 @@ mod bundle {
                 let z = d.eq_bundles(&x, &y).unwrap();
                 out = d.output(&z).unwrap().unwrap();
             }
-            assert_eq!(out, (x == y) as u16);
+            assert_eq!(out, (x == y) as Modulus);
         }
     }
 
@@ -695,7 +776,7 @@ mod bundle {
 mod pmr_tests {
     use super::*;
     use crate::{
-        fancy::{BundleGadgets, CrtGadgets, FancyInput},
+        fancy::{BundleGadgets, CrtBundle, CrtGadgets, FancyInput},
         util::RngExt,
     };
 
@@ -717,12 +798,12 @@ mod pmr_tests {
         }
     }
 
-    fn to_pmr_pt(x: u128, ps: &[u16]) -> Vec<u16> {
+    fn to_pmr_pt(x: u128, ps: &[Modulus]) -> Vec<Modulus> {
         let mut ds = vec![0; ps.len()];
         let mut q = 1;
         for i in 0..ps.len() {
             let p = ps[i] as u128;
-            ds[i] = ((x / q) % p) as u16;
+            ds[i] = ((x / q) % p) as Modulus;
             q *= p;
         }
         ds
@@ -772,6 +853,112 @@ mod pmr_tests {
         }
     }
 
+    #[test]
+    fn unequal_moduli() {
+        use crate::{BinaryGadgets, CrtGadgets};
+
+        let mut f = Dummy::new();
+        let x = f.encode(2, 3).unwrap();
+        let y = f.encode(4, 5).unwrap();
+        for (op, res) in [("add", f.add(&x, &y)), ("sub", f.sub(&x, &y))] {
+            match res {
+                Err(DummyError::FancyError(FancyError::UnequalModuli { op: o, x: 3, y: 5 })) => {
+                    assert_eq!(o, op)
+                }
+                r => panic!("{} of mod 3 and mod 5 succeeded: {:?}", op, r),
+            }
+        }
+        // Multiplication takes the larger modulus, whichever side it is on.
+        for z in [f.mul(&x, &y).unwrap(), f.mul(&y, &x).unwrap()] {
+            assert_eq!((z.val(), z.modulus()), (3, 5));
+        }
+
+        let xs = f.bin_encode(1, 8).unwrap();
+        let ys = f.bin_encode(1, 4).unwrap();
+        assert!(matches!(
+            f.bin_addition(&xs, &ys),
+            Err(DummyError::FancyError(FancyError::InvalidArgNum {
+                got: 4,
+                needed: 8
+            }))
+        ));
+        let xs = f.crt_encode(5, 3 * 5 * 7).unwrap();
+        let ys = f.crt_encode(5, 2 * 5 * 7).unwrap();
+        assert!(matches!(
+            f.crt_div(&xs, &ys),
+            Err(DummyError::FancyError(FancyError::UnequalModuli {
+                op: "crt_div",
+                x: 3,
+                y: 2
+            }))
+        ));
+    }
+
+    #[test]
+    fn out_of_range_inputs() {
+        let mut f = Dummy::new();
+        let out_of_range = |r: Result<_, DummyError>| match r {
+            Err(DummyError::FancyError(FancyError::InputOutOfRange { index, modulus, .. })) => {
+                (index, modulus)
+            }
+            r => panic!(
+                "expected an out-of-range input: {:?}",
+                r.map(|_: Vec<_>| ())
+            ),
+        };
+        assert_eq!(out_of_range(f.encode(2, 2).map(|x| vec![x])), (0, 2));
+        assert_eq!(out_of_range(f.encode_many(&[0, 4, 9], &[2, 5, 7])), (2, 7));
+        assert_eq!(
+            out_of_range(f.encode_bundle(&[1, 1, 2], &[2, 2, 2]).map(|_| vec![])),
+            (2, 2)
+        );
+        assert_eq!(
+            out_of_range(f.encode_bundle(&[3, 1, 6], &[3, 5, 7]).map(|_| vec![])),
+            (0, 3)
+        );
+
+        let xs = f.encode_many(&[1, 4, 6], &[2, 5, 7]).unwrap();
+        assert_eq!(xs.iter().map(DummyVal::val).collect::<Vec<_>>(), [1, 4, 6]);
+    }
+
+    #[test]
+    fn crt_boundary() {
+        let mut f = Dummy::new();
+        let q = util::product(&util::PRIMES[..26]);
+        let x = f.crt_encode(q - 1, q).unwrap();
+        assert_eq!(x.moduli_product_checked(), Some(q));
+        assert_eq!(f.crt_output(&x).unwrap(), Some(q - 1));
+        assert!(matches!(
+            f.crt_encode(q, q),
+            Err(DummyError::FancyError(
+                FancyError::CrtValueOutOfRange { .. }
+            ))
+        ));
+        assert!(matches!(
+            f.crt_encode_many(&[0, q], q),
+            Err(DummyError::FancyError(
+                FancyError::CrtValueOutOfRange { .. }
+            ))
+        ));
+        assert!(matches!(
+            f.crt_encode(0, 9),
+            Err(DummyError::FancyError(FancyError::UnfactorableModulus(9)))
+        ));
+
+        let ps = &util::PRIMES[..27];
+        let ws = f.encode_many(&[0; 27], ps).unwrap();
+        let x = CrtBundle::new(ws.clone());
+        assert_eq!(x.moduli_product_checked(), None);
+        assert!(matches!(
+            CrtBundle::new_checked(ws),
+            Err(FancyError::CrtModulusOverflow)
+        ));
+        assert!(matches!(
+            f.crt_output(&x),
+            Err(DummyError::FancyError(FancyError::CrtModulusOverflow))
+        ));
+    }
+
     #[test]
     #[ignore]
     fn crt_div() {
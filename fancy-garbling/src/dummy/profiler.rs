@@ -0,0 +1,434 @@
+//! `DummyProfiler` runs a fancy computation in the clear, like `Dummy`, and
+//! records the range of the values passing through labeled probes.
+//!
+//! This is the intended way to choose the widths of fixed-point numbers, and
+//! the moduli of CRT bundles: run the computation on representative inputs,
+//! probing the intermediate bundles whose range matters, and read the
+//! smallest width that holds each of them off the report.
+//!
+//! ```ignore
+//! let mut p = DummyProfiler::new();
+//! let mut acc = p.bin_constant_bundle(0, 32)?;
+//! for (x, w) in xs.iter().zip(&ws) {
+//!     let xw = p.bin_multiplication_lower_half(x, w)?;
+//!     let (sum, carry) = p.bin_addition(&acc, &xw)?;
+//!     p.flag("carry", &carry)?;
+//!     p.probe("acc", &sum)?;
+//!     acc = sum;
+//! }
+//! println!("{}", p.report());
+//! ```
+
+use crate::{
+    circuit::CustomGate,
+    dummy::{Dummy, DummyVal},
+    errors::DummyError,
+    fancy::{BinaryBundle, CrtBundle, Fancy, FancyInput, FancyReveal, HasModulus},
+    util, FancyArithmetic, FancyBinary, FancyError, Modulus,
+};
+use std::{collections::BTreeMap, sync::Arc};
+
+/// A `Dummy` that records the values of the bundles and flags it is asked to
+/// probe, by label.
+///
+/// Every other operation is `Dummy`'s, so the gadgets run on it unchanged.
+pub struct DummyProfiler {
+    dummy: Dummy,
+    report: ProfileReport,
+}
+
+/// The values a [`DummyProfiler`] recorded, by label.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProfileReport {
+    /// The range of the values probed under each label.
+    pub ranges: BTreeMap<String, RangeStats>,
+    /// How often the flag probed under each label was set.
+    pub flags: BTreeMap<String, FlagStats>,
+}
+
+/// The range of the values probed under one label.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RangeStats {
+    /// The number of values probed.
+    pub count: u64,
+    /// The smallest value probed.
+    pub min: i128,
+    /// The largest value probed.
+    pub max: i128,
+    /// The mean of the values probed.
+    pub mean: f64,
+}
+
+/// How often a flag probed under one label was set.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FlagStats {
+    /// The number of times the flag was probed.
+    pub count: u64,
+    /// The number of times it was set.
+    pub set: u64,
+}
+
+/// A bundle whose value a [`DummyProfiler`] can probe.
+///
+/// Values are signed: a binary bundle is in two's complement, as
+/// `bin_lt_signed` and `bin_rsa` take it, and a CRT bundle of modulus `Q` is
+/// negative from `Q / 2` on, as `crt_sign` takes it.
+pub trait Probe {
+    /// The signed value of the bundle.
+    fn signed_value(&self) -> Result<i128, FancyError>;
+}
+
+impl Probe for BinaryBundle<DummyVal> {
+    fn signed_value(&self) -> Result<i128, FancyError> {
+        let bits = self.wires().iter().map(DummyVal::val).collect::<Vec<_>>();
+        let n = bits.len();
+        if n > 128 {
+            return Err(FancyError::InvalidArg(format!(
+                "cannot probe a {}-bit bundle",
+                n
+            )));
+        }
+        if n == 0 {
+            return Ok(0);
+        }
+        // sign-extend from the top bit
+        Ok(((util::u128_from_bits(&bits) << (128 - n)) as i128) >> (128 - n))
+    }
+}
+
+impl Probe for CrtBundle<DummyVal> {
+    fn signed_value(&self) -> Result<i128, FancyError> {
+        let xs = self.wires().iter().map(DummyVal::val).collect::<Vec<_>>();
+        let q = self
+            .moduli_product_checked()
+            .ok_or(FancyError::CrtModulusOverflow)?;
+        let x = util::crt_inv_checked(&xs, &self.moduli())?;
+        if x >= q - q / 2 {
+            // x - q is at least -q / 2, which fits
+            Ok(x.wrapping_sub(q) as i128)
+        } else {
+            Ok(x as i128)
+        }
+    }
+}
+
+impl RangeStats {
+    fn new(x: i128) -> Self {
+        RangeStats {
+            count: 1,
+            min: x,
+            max: x,
+            mean: x as f64,
+        }
+    }
+
+    fn record(&mut self, x: i128) {
+        self.count += 1;
+        self.min = self.min.min(x);
+        self.max = self.max.max(x);
+        self.mean += (x as f64 - self.mean) / self.count as f64;
+    }
+
+    /// The smallest number of bits holding every value probed in two's
+    /// complement.
+    pub fn signed_bits(&self) -> usize {
+        signed_bits(self.min).max(signed_bits(self.max))
+    }
+}
+
+fn signed_bits(x: i128) -> usize {
+    let magnitude = if x < 0 { !x } else { x };
+    129 - magnitude.leading_zeros() as usize
+}
+
+impl ProfileReport {
+    /// Serialize the report as JSON.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("reports always serialize")
+    }
+}
+
+impl std::fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "value profile:")?;
+        for (label, r) in &self.ranges {
+            writeln!(
+                f,
+                "  {}: {} values in [{}, {}], mean {:.2}, {} bits",
+                label,
+                r.count,
+                r.min,
+                r.max,
+                r.mean,
+                r.signed_bits()
+            )?;
+        }
+        for (label, flag) in &self.flags {
+            writeln!(f, "  {}: set {} of {} times", label, flag.set, flag.count)?;
+        }
+        Ok(())
+    }
+}
+
+impl DummyProfiler {
+    /// Create a new profiler with nothing recorded.
+    pub fn new() -> Self {
+        DummyProfiler {
+            dummy: Dummy::new(),
+            report: ProfileReport::default(),
+        }
+    }
+
+    /// Record the value of `x` under `label`, and return it.
+    pub fn probe<P: Probe + ?Sized>(&mut self, label: &str, x: &P) -> Result<i128, DummyError> {
+        let x = x.signed_value()?;
+        match self.report.ranges.get_mut(label) {
+            Some(r) => r.record(x),
+            None => {
+                self.report
+                    .ranges
+                    .insert(label.to_string(), RangeStats::new(x));
+            }
+        }
+        Ok(x)
+    }
+
+    /// Record whether the binary wire `x` is set under `label`, and return
+    /// it.
+    ///
+    /// This counts overflows, by probing the carry of `bin_addition`, or the
+    /// flag of any other checked gadget.
+    pub fn flag(&mut self, label: &str, x: &DummyVal) -> Result<bool, DummyError> {
+        if x.modulus() != 2 {
+            return Err(DummyError::FancyError(FancyError::InvalidArgMod {
+                got: x.modulus(),
+                needed: 2,
+            }));
+        }
+        let set = x.val() == 1;
+        let flag = self.report.flags.entry(label.to_string()).or_default();
+        flag.count += 1;
+        flag.set += set as u64;
+        Ok(set)
+    }
+
+    /// The values recorded so far.
+    pub fn report(&self) -> &ProfileReport {
+        &self.report
+    }
+
+    /// Finish the run, returning the values recorded.
+    pub fn into_report(self) -> ProfileReport {
+        self.report
+    }
+}
+
+impl FancyInput for DummyProfiler {
+    type Item = DummyVal;
+    type Error = DummyError;
+
+    fn encode(&mut self, value: Modulus, modulus: Modulus) -> Result<DummyVal, DummyError> {
+        self.dummy.encode(value, modulus)
+    }
+
+    fn encode_many(
+        &mut self,
+        xs: &[Modulus],
+        moduli: &[Modulus],
+    ) -> Result<Vec<DummyVal>, DummyError> {
+        self.dummy.encode_many(xs, moduli)
+    }
+
+    fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<DummyVal>, DummyError> {
+        self.dummy.receive_many(moduli)
+    }
+}
+
+impl FancyBinary for DummyProfiler {
+    fn xor(&mut self, x: &DummyVal, y: &DummyVal) -> Result<DummyVal, Self::Error> {
+        self.dummy.xor(x, y)
+    }
+
+    fn and(&mut self, x: &DummyVal, y: &DummyVal) -> Result<DummyVal, Self::Error> {
+        self.dummy.and(x, y)
+    }
+
+    fn negate(&mut self, x: &DummyVal) -> Result<DummyVal, Self::Error> {
+        self.dummy.negate(x)
+    }
+
+    fn custom(
+        &mut self,
+        gate: &Arc<dyn CustomGate>,
+        inputs: &[DummyVal],
+    ) -> Result<DummyVal, Self::Error> {
+        self.dummy.custom(gate, inputs)
+    }
+}
+
+impl FancyArithmetic for DummyProfiler {
+    fn add(&mut self, x: &DummyVal, y: &DummyVal) -> Result<DummyVal, Self::Error> {
+        self.dummy.add(x, y)
+    }
+
+    fn sub(&mut self, x: &DummyVal, y: &DummyVal) -> Result<DummyVal, Self::Error> {
+        self.dummy.sub(x, y)
+    }
+
+    fn cmul(&mut self, x: &DummyVal, c: Modulus) -> Result<DummyVal, Self::Error> {
+        self.dummy.cmul(x, c)
+    }
+
+    fn mul(&mut self, x: &DummyVal, y: &DummyVal) -> Result<DummyVal, Self::Error> {
+        self.dummy.mul(x, y)
+    }
+
+    fn proj(
+        &mut self,
+        x: &DummyVal,
+        modulus: Modulus,
+        tt: Option<Vec<Modulus>>,
+    ) -> Result<DummyVal, Self::Error> {
+        self.dummy.proj(x, modulus, tt)
+    }
+}
+
+impl Fancy for DummyProfiler {
+    type Item = DummyVal;
+    type Error = DummyError;
+
+    fn constant(&mut self, val: Modulus, modulus: Modulus) -> Result<DummyVal, Self::Error> {
+        self.dummy.constant(val, modulus)
+    }
+
+    fn output(&mut self, x: &DummyVal) -> Result<Option<Modulus>, Self::Error> {
+        self.dummy.output(x)
+    }
+}
+
+impl FancyReveal for DummyProfiler {
+    fn reveal(&mut self, x: &DummyVal) -> Result<Modulus, DummyError> {
+        self.dummy.reveal(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BinaryGadgets, CrtGadgets};
+
+    /// Fractional bits of the fixed-point numbers below.
+    const SCALE: usize = 8;
+
+    fn fixed(x: f64) -> u128 {
+        ((x * (1 << SCALE) as f64) as i128 as u128) & 0xffff
+    }
+
+    /// Sign-extend a 16-bit bundle to 32 bits.
+    fn extend(x: &BinaryBundle<DummyVal>) -> BinaryBundle<DummyVal> {
+        let mut ws = x.wires().to_vec();
+        let sign = ws[ws.len() - 1].clone();
+        ws.resize(32, sign);
+        BinaryBundle::new(ws)
+    }
+
+    #[test]
+    fn test_dot_product() {
+        let xs = [1.5, -2.25, 0.5, 3.0];
+        let ws = [0.5, 1.0, -4.0, 0.25];
+        let mut p = DummyProfiler::new();
+        let mut acc = p.bin_constant_bundle(0, 32).unwrap();
+        for (&x, &w) in xs.iter().zip(&ws) {
+            let x = p.bin_encode(fixed(x), 16).unwrap();
+            let w = p.bin_encode(fixed(w), 16).unwrap();
+            let xw = p
+                .bin_multiplication_lower_half(&extend(&x), &extend(&w))
+                .unwrap();
+            let xw = p.bin_rsa(&xw, SCALE).unwrap();
+            p.probe("product", &xw).unwrap();
+            let (sum, carry) = p.bin_addition(&acc, &xw).unwrap();
+            p.flag("carry", &carry).unwrap();
+            p.probe("acc", &sum).unwrap();
+            acc = sum;
+        }
+        // -2.75
+        assert_eq!(p.probe("result", &acc).unwrap(), -704);
+
+        let report = p.into_report();
+        // products 0.75, -2.25, -2, and 0.75
+        let product = &report.ranges["product"];
+        assert_eq!((product.count, product.min, product.max), (4, -576, 192));
+        assert_eq!(product.mean, -176.0);
+        assert_eq!(product.signed_bits(), 11);
+        // partial sums 0.75, -1.5, -3.5, and -2.75
+        let acc = &report.ranges["acc"];
+        assert_eq!((acc.count, acc.min, acc.max), (4, -896, 192));
+        assert_eq!(acc.mean, -448.0);
+        assert_eq!(acc.signed_bits(), 11);
+        // only -1.5 + -2 carries out of the top bit
+        assert_eq!(report.flags["carry"], FlagStats { count: 4, set: 1 });
+        assert!(report.to_string().contains("acc: 4 values in [-896, 192]"));
+
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            serde_json::from_str::<ProfileReport>(&report.to_json()).unwrap(),
+            report
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut p = DummyProfiler::new();
+        let mut acc = p.bin_encode(0, 8).unwrap();
+        for x in [200, 100, 50, 250] {
+            let x = p.bin_encode(x, 8).unwrap();
+            let (sum, overflow) = p.bin_addition(&acc, &x).unwrap();
+            p.flag("overflow", &overflow).unwrap();
+            acc = sum;
+        }
+        // 200, 300, 350, 600: the sum wraps at 300 and 600
+        assert_eq!(p.report().flags["overflow"], FlagStats { count: 4, set: 2 });
+        let x = p.bin_encode(0, 8).unwrap();
+        assert!(p.flag("bundle", &x.wires()[0]).is_ok());
+        let x = p.encode(1, 3).unwrap();
+        assert!(p.flag("ternary", &x).is_err());
+    }
+
+    #[test]
+    fn test_probe_values() {
+        let mut p = DummyProfiler::new();
+        for (x, nbits, expected) in [
+            (0, 1, 0),
+            (1, 1, -1),
+            (0x7f, 8, 127),
+            (0x80, 8, -128),
+            (u128::MAX, 128, -1),
+            (1 << 127, 128, i128::MIN),
+        ] {
+            let x = p.bin_encode(x, nbits).unwrap();
+            assert_eq!(p.probe("bin", &x).unwrap(), expected);
+        }
+        let bin = &p.report().ranges["bin"];
+        assert_eq!((bin.min, bin.max, bin.signed_bits()), (i128::MIN, 127, 128));
+
+        let q = util::modulus_with_width(32);
+        let x = p.crt_encode(5, q).unwrap();
+        let y = p.crt_encode(7, q).unwrap();
+        let z = p.crt_sub(&x, &y).unwrap();
+        assert_eq!(p.probe("crt", &z).unwrap(), -2);
+        assert_eq!(p.probe("crt", &y).unwrap(), 7);
+        let z = p.crt_sign(&z, "100%").unwrap();
+        assert_eq!(p.output(&z).unwrap(), Some(1));
+
+        let x = p.bin_encode(0, 129);
+        assert!(p.probe("wide", &x.unwrap()).is_err());
+        assert!(p.report().ranges.get("wide").is_none());
+        assert_eq!(signed_bits(0), 1);
+        assert_eq!(signed_bits(-1), 1);
+        assert_eq!(signed_bits(1), 2);
+    }
+}
@@ -1,14 +1,26 @@
 //! Errors that may be output by this library.
 
+use crate::{circuit::CircuitDigest, classic::GarblingId, format::FormatId, Modulus};
 use scuttlebutt::Block;
-use std::fmt::{self, Display, Formatter};
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
 
 /// Errors that may occur when using the `Fancy` trait. These errors are
 /// API-usage errors, such as trying to add two `Items` with different moduli.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum FancyError {
-    /// Unequal moduli.
-    UnequalModuli,
+    /// An operation was given wires of different moduli.
+    UnequalModuli {
+        /// The operation, e.g. `add` or `bin_addition`.
+        op: &'static str,
+        /// The modulus of its first argument.
+        x: Modulus,
+        /// The modulus of the argument that differs from it.
+        y: Modulus,
+    },
     /// Invalid argument.
     InvalidArg(String),
     /// Invalid number of arguments.
@@ -21,9 +33,9 @@ pub enum FancyError {
     /// Invalid argument modulus.
     InvalidArgMod {
         /// Received modulus.
-        got: u16,
+        got: Modulus,
         /// Expected modulus.
-        needed: u16,
+        needed: Modulus,
     },
     /// Expected binary argument.
     ArgNotBinary,
@@ -33,15 +45,138 @@ pub enum FancyError {
     InvalidTruthTable,
     /// Uninitialized value encountered.
     UninitializedValue,
+    /// An input value is not reduced modulo the modulus of its wire.
+    InputOutOfRange {
+        /// The position of the value among the inputs being encoded.
+        index: usize,
+        /// The value.
+        value: Modulus,
+        /// The modulus of its wire.
+        modulus: Modulus,
+    },
+    /// A constant gate's value is not reduced modulo the gate's modulus.
+    ConstantOutOfRange {
+        /// The index of the gate.
+        gate: usize,
+        /// The value of the constant.
+        value: Modulus,
+        /// The modulus of the gate.
+        modulus: Modulus,
+    },
+    /// Fewer garbler input wires or values were given than are needed.
+    NotEnoughGarblerInputs {
+        /// The number given.
+        got: usize,
+        /// The number needed.
+        needed: usize,
+    },
+    /// Fewer evaluator input wires or values were given than are needed.
+    NotEnoughEvaluatorInputs {
+        /// The number given.
+        got: usize,
+        /// The number needed.
+        needed: usize,
+    },
+    /// More garbler input wires were given than a circuit has garbler inputs.
+    TooManyGarblerInputs {
+        /// The number given.
+        got: usize,
+        /// The number needed.
+        needed: usize,
+    },
+    /// More evaluator input wires were given than a circuit has evaluator
+    /// inputs.
+    TooManyEvaluatorInputs {
+        /// The number given.
+        got: usize,
+        /// The number needed.
+        needed: usize,
+    },
+    /// A garbler input wire does not have the modulus of its circuit input.
+    GarblerInputModulus {
+        /// The id of the input.
+        id: usize,
+        /// The modulus of the wire.
+        got: Modulus,
+        /// The modulus of the input.
+        needed: Modulus,
+    },
+    /// An evaluator input wire does not have the modulus of its circuit input.
+    EvaluatorInputModulus {
+        /// The id of the input.
+        id: usize,
+        /// The modulus of the wire.
+        got: Modulus,
+        /// The modulus of the input.
+        needed: Modulus,
+    },
+    /// Fewer public input wires were given than a circuit has public inputs.
+    NotEnoughPublicInputs {
+        /// The number given.
+        got: usize,
+        /// The number needed.
+        needed: usize,
+    },
+    /// More public input wires were given than a circuit has public inputs.
+    TooManyPublicInputs {
+        /// The number given.
+        got: usize,
+        /// The number needed.
+        needed: usize,
+    },
+    /// A public input wire does not have the modulus of its circuit input.
+    PublicInputModulus {
+        /// The id of the input.
+        id: usize,
+        /// The modulus of the wire.
+        got: Modulus,
+        /// The modulus of the input.
+        needed: Modulus,
+    },
+    /// The product of a set of CRT moduli does not fit in a `u128`.
+    CrtModulusOverflow,
+    /// A value is not less than the composite modulus of its CRT representation.
+    CrtValueOutOfRange {
+        /// The value.
+        value: u128,
+        /// The composite modulus.
+        modulus: u128,
+    },
+    /// Two moduli of a CRT representation share a factor.
+    CrtModuliNotCoprime {
+        /// One of the moduli.
+        p: Modulus,
+        /// The other.
+        q: Modulus,
+    },
+    /// A CRT modulus is not a product of distinct primes from `util::PRIMES`.
+    UnfactorableModulus(u128),
+    /// There are not enough primes for a CRT representation of the given
+    /// width whose composite modulus fits in a `u128`.
+    CrtWidthUnachievable {
+        /// The width in bits.
+        nbits: u32,
+    },
 }
 
 /// Errors from the dummy fancy object.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum DummyError {
-    /// Not enough garbler inputs provided.
-    NotEnoughGarblerInputs,
-    /// Not enough evaluator inputs provided.
-    NotEnoughEvaluatorInputs,
+    /// Not as many garbler inputs were provided as the circuit has.
+    NotEnoughGarblerInputs {
+        /// The number provided.
+        got: usize,
+        /// The number of garbler inputs of the circuit.
+        needed: usize,
+    },
+    /// Not as many evaluator inputs were provided as the circuit has.
+    NotEnoughEvaluatorInputs {
+        /// The number provided.
+        got: usize,
+        /// The number of evaluator inputs of the circuit.
+        needed: usize,
+    },
     /// Encoding error.
     EncodingError,
     /// A fancy error has occurred.
@@ -50,6 +185,7 @@ pub enum DummyError {
 
 /// Errors from the evaluator.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum EvaluatorError {
     /// Not enough garbler inputs provided.
     NotEnoughGarblerInputs,
@@ -59,17 +195,52 @@ pub enum EvaluatorError {
     DecodingFailed,
     /// A communication error has occurred.
     CommunicationError(String),
+    /// An I/O error occurred on the channel.
+    IoError(std::io::Error),
+    /// Oblivious transfer of the evaluator's inputs failed.
+    #[cfg(feature = "std")]
+    OtError(ocelot::Error),
     /// A fancy error has occurred.
     FancyError(FancyError),
+    /// Inputs encoded for one garbling were given to the garbled circuit of
+    /// another.
+    MismatchedGarbling {
+        /// The garbling of the garbled circuit.
+        expected: GarblingId,
+        /// The garbling the inputs were encoded for.
+        found: GarblingId,
+    },
+    /// A garbled circuit was evaluated against a circuit other than the one
+    /// that was garbled.
+    MismatchedCircuit {
+        /// The digest of the circuit that was garbled.
+        expected: CircuitDigest,
+        /// The digest of the circuit given.
+        found: CircuitDigest,
+    },
+    /// Evaluation was cancelled by its hooks; see `hooks::Hooks`.
+    Cancelled {
+        /// The number of gates evaluated before it stopped.
+        gates_done: usize,
+    },
+    /// The security parameters of the garbled circuit do not fit the wire
+    /// labels; see `SecurityParams`.
+    Security(SecurityParamsError),
 }
 
 /// Errors from the garbler.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum GarblerError {
     /// An error occurred while processing a message.
     CommunicationError(String),
+    /// An I/O error occurred on the channel.
+    IoError(std::io::Error),
+    /// Oblivious transfer of the evaluator's inputs failed.
+    #[cfg(feature = "std")]
+    OtError(ocelot::Error),
     /// Asymmetric moduli error.
-    AsymmetricHalfGateModuliMax8(u16),
+    AsymmetricHalfGateModuliMax8(Modulus),
     /// A truth table was missing.
     TruthTableRequired,
     /// Delta required for wire reuse.
@@ -78,20 +249,106 @@ pub enum GarblerError {
     EncodingError,
     /// A fancy error has occurred.
     FancyError(FancyError),
+    /// Garbling was cancelled by its hooks; see `hooks::Hooks`.
+    Cancelled {
+        /// The number of gates garbled before it stopped.
+        gates_done: usize,
+    },
+    /// The label given for an output is none of its labels; see
+    /// `classic::Decoder` and `classic::OutputVerifier`.
+    InvalidOutputLabel(usize),
+    /// The security parameters do not fit the wire labels; see
+    /// `SecurityParams`.
+    Security(SecurityParamsError),
 }
 
 /// Errors emitted when building a circuit.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum CircuitBuilderError {
     /// Reuse not supported.
     ReuseUndefined,
     /// A fancy error has occurred.
     FancyError(FancyError),
+    /// A captured computation output nothing.
+    NoOutputs,
+    /// A captured computation built an invalid circuit, e.g. by using a wire
+    /// of another builder.
+    InvalidCircuit(CircuitValidationError),
+}
+
+/// Errors emitted when a circuit is structurally invalid.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CircuitValidationError {
+    /// The circuit was serialized in an unsupported format version.
+    UnsupportedVersion(u16),
+    /// A reference or gate output points past the end of the circuit.
+    RefOutOfRange {
+        /// The wire index referenced.
+        ix: usize,
+        /// The number of wires in the circuit.
+        nwires: usize,
+    },
+    /// A reference points to a wire that has not been assigned yet.
+    UndefinedRef(usize),
+    /// The modulus of a reference differs from the modulus of its wire.
+    ModulusMismatch {
+        /// The wire index referenced.
+        ix: usize,
+        /// The modulus of the reference.
+        got: Modulus,
+        /// The modulus of the wire.
+        needed: Modulus,
+    },
+    /// A gate has a modulus that is not supported.
+    InvalidModulus {
+        /// The index of the gate.
+        gate: usize,
+        /// The modulus of the gate.
+        modulus: Modulus,
+    },
+    /// The number of gate moduli differs from the number of gates.
+    MissingModuli {
+        /// The number of gate moduli.
+        got: usize,
+        /// The number of gates.
+        needed: usize,
+    },
+    /// An input gate refers to an input that does not exist.
+    InputOutOfRange {
+        /// The index of the gate.
+        gate: usize,
+        /// The input id of the gate.
+        id: usize,
+    },
+    /// A projection gate has a truth table that does not match its moduli.
+    InvalidTruthTable(usize),
+    /// A constant gate's value is not reduced modulo the gate's modulus.
+    ConstantOutOfRange {
+        /// The index of the gate.
+        gate: usize,
+        /// The value of the constant.
+        value: Modulus,
+        /// The modulus of the gate.
+        modulus: Modulus,
+    },
+    /// The output groups of the circuit are wider than its outputs.
+    OutputGroupsTooWide {
+        /// The total width of the output groups.
+        width: usize,
+        /// The number of outputs.
+        noutputs: usize,
+    },
+    /// A custom gate refers to a custom gate that is not registered on the
+    /// circuit, or has a different number of inputs than that gate takes.
+    InvalidCustomGate(usize),
 }
 
 /// General wire deserialization error
 #[cfg(feature = "serde")]
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum WireDeserializationError {
     /// Deserialization of `WireMod3` failed
     InvalidWireMod3,
@@ -102,16 +359,17 @@ pub enum WireDeserializationError {
 /// `WireModQ` wire deserialization error
 #[cfg(feature = "serde")]
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum ModQDeserializationError {
     /// Modulus must be greater than 1
-    BadModulus(u16),
+    BadModulus(Modulus),
 
     /// One of the digits is larger than the modulus
     DigitTooLarge {
         /// The invalid digit
-        digit: u16,
+        digit: Modulus,
         /// Modulus of wire
-        modulus: u16,
+        modulus: Modulus,
     },
 
     /// Unexpected number of digits
@@ -141,6 +399,16 @@ impl Display for WireDeserializationError {
     }
 }
 
+#[cfg(feature = "serde")]
+impl Error for WireDeserializationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            WireDeserializationError::InvalidWireModQ(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 #[cfg(feature = "serde")]
 impl Display for ModQDeserializationError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
@@ -166,6 +434,9 @@ impl Display for ModQDeserializationError {
     }
 }
 
+#[cfg(feature = "serde")]
+impl Error for ModQDeserializationError {}
+
 ////////////////////////////////////////////////////////////////////////////////
 // fancy error
 //
@@ -173,12 +444,14 @@ impl Display for ModQDeserializationError {
 impl Display for FancyError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
-            FancyError::UnequalModuli => "unequal moduli".fmt(f),
+            FancyError::UnequalModuli { op, x, y } => {
+                write!(f, "unequal moduli in {}: mod {} and mod {}", op, x, y)
+            }
             FancyError::InvalidArg(s) => write!(f, "invalid argument: {}", s),
             FancyError::InvalidArgNum { got, needed } => write!(
                 f,
                 "invalid number of arguments: needed {} but got {}",
-                got, needed
+                needed, got
             ),
             FancyError::InvalidArgMod { got, needed } => write!(
                 f,
@@ -191,24 +464,125 @@ impl Display for FancyError {
             FancyError::UninitializedValue => {
                 "uninitialized value in circuit. is the circuit topologically sorted?".fmt(f)
             }
+            FancyError::InputOutOfRange {
+                index,
+                value,
+                modulus,
+            } => write!(
+                f,
+                "input {} is {}, which is not less than its modulus {}",
+                index, value, modulus
+            ),
+            FancyError::ConstantOutOfRange {
+                gate,
+                value,
+                modulus,
+            } => write!(
+                f,
+                "constant gate {} has value {}, which is not less than its modulus {}",
+                gate, value, modulus
+            ),
+            FancyError::NotEnoughGarblerInputs { got, needed } => write!(
+                f,
+                "not enough garbler inputs: needed {} but got {}",
+                needed, got
+            ),
+            FancyError::NotEnoughEvaluatorInputs { got, needed } => write!(
+                f,
+                "not enough evaluator inputs: needed {} but got {}",
+                needed, got
+            ),
+            FancyError::TooManyGarblerInputs { got, needed } => write!(
+                f,
+                "too many garbler inputs: needed {} but got {}",
+                needed, got
+            ),
+            FancyError::TooManyEvaluatorInputs { got, needed } => write!(
+                f,
+                "too many evaluator inputs: needed {} but got {}",
+                needed, got
+            ),
+            FancyError::GarblerInputModulus { id, got, needed } => write!(
+                f,
+                "garbler input {} has modulus {} but its wire has modulus {}",
+                id, needed, got
+            ),
+            FancyError::EvaluatorInputModulus { id, got, needed } => write!(
+                f,
+                "evaluator input {} has modulus {} but its wire has modulus {}",
+                id, needed, got
+            ),
+            FancyError::NotEnoughPublicInputs { got, needed } => write!(
+                f,
+                "not enough public inputs: needed {} but got {}",
+                needed, got
+            ),
+            FancyError::TooManyPublicInputs { got, needed } => write!(
+                f,
+                "too many public inputs: needed {} but got {}",
+                needed, got
+            ),
+            FancyError::PublicInputModulus { id, got, needed } => write!(
+                f,
+                "public input {} has modulus {} but its wire has modulus {}",
+                id, needed, got
+            ),
+            FancyError::CrtModulusOverflow => "CRT composite modulus overflows u128".fmt(f),
+            FancyError::CrtValueOutOfRange { value, modulus } => write!(
+                f,
+                "{} is not less than the CRT composite modulus {}",
+                value, modulus
+            ),
+            FancyError::CrtModuliNotCoprime { p, q } => {
+                write!(f, "CRT moduli {} and {} are not coprime", p, q)
+            }
+            FancyError::UnfactorableModulus(q) => write!(
+                f,
+                "{} is not a product of distinct primes from util::PRIMES",
+                q
+            ),
+            FancyError::CrtWidthUnachievable { nbits } => write!(
+                f,
+                "not enough primes for a {}-bit CRT representation",
+                nbits
+            ),
         }
     }
 }
 
+impl Error for FancyError {}
+
 ////////////////////////////////////////////////////////////////////////////////
 // Dummy error
 
 impl Display for DummyError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
-            DummyError::NotEnoughGarblerInputs => "not enough garbler inputs".fmt(f),
-            DummyError::NotEnoughEvaluatorInputs => "not enough evaluator inputs".fmt(f),
+            DummyError::NotEnoughGarblerInputs { got, needed } => write!(
+                f,
+                "expected {} garbler inputs, but {} were given",
+                needed, got
+            ),
+            DummyError::NotEnoughEvaluatorInputs { got, needed } => write!(
+                f,
+                "expected {} evaluator inputs, but {} were given",
+                needed, got
+            ),
             DummyError::EncodingError => "not enough inputs or moduli".fmt(f),
             DummyError::FancyError(e) => write!(f, "fancy error: {}", e),
         }
     }
 }
 
+impl Error for DummyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DummyError::FancyError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 impl From<FancyError> for DummyError {
     fn from(e: FancyError) -> DummyError {
         DummyError::FancyError(e)
@@ -225,7 +599,50 @@ impl Display for EvaluatorError {
             EvaluatorError::NotEnoughEvaluatorInputs => "not enough evaluator inputs".fmt(f),
             EvaluatorError::DecodingFailed => write!(f, "decodiing failed"),
             EvaluatorError::CommunicationError(s) => write!(f, "communication error: {}", s),
+            EvaluatorError::IoError(e) => write!(f, "communication error: {}", e),
+            #[cfg(feature = "std")]
+            EvaluatorError::OtError(e) => write!(f, "oblivious transfer error: {}", e),
             EvaluatorError::FancyError(e) => write!(f, "fancy error: {}", e),
+            EvaluatorError::MismatchedGarbling { expected, found } => write!(
+                f,
+                "inputs were encoded for garbling {} but the garbled circuit is from garbling {}",
+                found, expected
+            ),
+            EvaluatorError::MismatchedCircuit { expected, found } => write!(
+                f,
+                "circuit has digest {} but the garbled circuit is of a circuit with digest {}",
+                found, expected
+            ),
+            EvaluatorError::Cancelled { gates_done } => {
+                write!(f, "evaluation cancelled after {} gates", gates_done)
+            }
+            EvaluatorError::Security(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for EvaluatorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EvaluatorError::IoError(e) => Some(e),
+            #[cfg(feature = "std")]
+            EvaluatorError::OtError(e) => Some(e),
+            EvaluatorError::FancyError(e) => Some(e),
+            EvaluatorError::Security(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl EvaluatorError {
+    /// How the channel failed, if this error came from a failed read or write
+    /// on it.
+    pub fn channel_error(&self) -> Option<scuttlebutt::ChannelError> {
+        match self {
+            EvaluatorError::IoError(e) => Some(e.into()),
+            #[cfg(feature = "std")]
+            EvaluatorError::OtError(e) => e.channel_error(),
+            _ => None,
         }
     }
 }
@@ -238,7 +655,13 @@ impl From<FancyError> for EvaluatorError {
 
 impl From<std::io::Error> for EvaluatorError {
     fn from(e: std::io::Error) -> Self {
-        EvaluatorError::CommunicationError(e.to_string())
+        EvaluatorError::IoError(e)
+    }
+}
+
+impl From<SecurityParamsError> for EvaluatorError {
+    fn from(e: SecurityParamsError) -> Self {
+        EvaluatorError::Security(e)
     }
 }
 
@@ -255,6 +678,9 @@ impl Display for GarblerError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
             GarblerError::CommunicationError(s) => write!(f, "{}", s),
+            GarblerError::IoError(e) => write!(f, "{}", e),
+            #[cfg(feature = "std")]
+            GarblerError::OtError(e) => write!(f, "oblivious transfer error: {}", e),
             GarblerError::AsymmetricHalfGateModuliMax8(q) => write!(
                 f,
                 "the small modulus in a half gate with asymmetric moduli is capped at 8, got {}",
@@ -271,6 +697,39 @@ impl Display for GarblerError {
                 "encoding failed: unequal length input values and moduli".fmt(f)
             }
             GarblerError::FancyError(e) => write!(f, "{}", e),
+            GarblerError::Cancelled { gates_done } => {
+                write!(f, "garbling cancelled after {} gates", gates_done)
+            }
+            GarblerError::InvalidOutputLabel(i) => {
+                write!(f, "the label of output {} is not a label of that output", i)
+            }
+            GarblerError::Security(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for GarblerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GarblerError::IoError(e) => Some(e),
+            #[cfg(feature = "std")]
+            GarblerError::OtError(e) => Some(e),
+            GarblerError::FancyError(e) => Some(e),
+            GarblerError::Security(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl GarblerError {
+    /// How the channel failed, if this error came from a failed read or write
+    /// on it.
+    pub fn channel_error(&self) -> Option<scuttlebutt::ChannelError> {
+        match self {
+            GarblerError::IoError(e) => Some(e.into()),
+            #[cfg(feature = "std")]
+            GarblerError::OtError(e) => e.channel_error(),
+            _ => None,
         }
     }
 }
@@ -283,7 +742,13 @@ impl From<FancyError> for GarblerError {
 
 impl From<std::io::Error> for GarblerError {
     fn from(e: std::io::Error) -> Self {
-        GarblerError::CommunicationError(e.to_string())
+        GarblerError::IoError(e)
+    }
+}
+
+impl From<SecurityParamsError> for GarblerError {
+    fn from(e: SecurityParamsError) -> Self {
+        GarblerError::Security(e)
     }
 }
 
@@ -305,6 +770,24 @@ impl Display for CircuitBuilderError {
                 "reuse is undefined for circuits. it is unclear what it means to reuse a
                 CircuitRef from a previous circuit."
             ),
+            CircuitBuilderError::NoOutputs => write!(f, "the captured computation output nothing"),
+            CircuitBuilderError::InvalidCircuit(e) => {
+                write!(
+                    f,
+                    "the captured computation built an invalid circuit: {}",
+                    e
+                )
+            }
+        }
+    }
+}
+
+impl Error for CircuitBuilderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CircuitBuilderError::FancyError(e) => Some(e),
+            CircuitBuilderError::InvalidCircuit(e) => Some(e),
+            _ => None,
         }
     }
 }
@@ -315,12 +798,517 @@ impl From<FancyError> for CircuitBuilderError {
     }
 }
 
+impl Display for CircuitValidationError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            CircuitValidationError::UnsupportedVersion(v) => {
+                write!(f, "unsupported circuit format version {}", v)
+            }
+            CircuitValidationError::RefOutOfRange { ix, nwires } => write!(
+                f,
+                "reference to wire {} out of range for circuit with {} wires",
+                ix, nwires
+            ),
+            CircuitValidationError::UndefinedRef(ix) => {
+                write!(f, "reference to wire {} before it is assigned", ix)
+            }
+            CircuitValidationError::ModulusMismatch { ix, got, needed } => write!(
+                f,
+                "reference to wire {} has modulus {} but the wire has modulus {}",
+                ix, got, needed
+            ),
+            CircuitValidationError::InvalidModulus { gate, modulus } => {
+                write!(f, "gate {} has invalid modulus {}", gate, modulus)
+            }
+            CircuitValidationError::MissingModuli { got, needed } => {
+                write!(f, "circuit has {} gate moduli but {} gates", got, needed)
+            }
+            CircuitValidationError::InputOutOfRange { gate, id } => {
+                write!(f, "gate {} refers to nonexistent input {}", gate, id)
+            }
+            CircuitValidationError::InvalidTruthTable(gate) => {
+                write!(f, "gate {} has an invalid truth table", gate)
+            }
+            CircuitValidationError::ConstantOutOfRange {
+                gate,
+                value,
+                modulus,
+            } => write!(
+                f,
+                "constant gate {} has value {}, which is not less than its modulus {}",
+                gate, value, modulus
+            ),
+            CircuitValidationError::OutputGroupsTooWide { width, noutputs } => write!(
+                f,
+                "output groups of total width {} exceed the {} outputs",
+                width, noutputs
+            ),
+            CircuitValidationError::InvalidCustomGate(gate) => write!(
+                f,
+                "gate {} is an unregistered custom gate or has the wrong number of inputs",
+                gate
+            ),
+        }
+    }
+}
+
+impl Error for CircuitValidationError {}
+
+////////////////////////////////////////////////////////////////////////////////
+// garbled circuit decoding errors
+
+/// Errors emitted when decoding a garbled circuit from bytes.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum GarbledCircuitDecodingError {
+    /// The input is shorter than the header, which gives the number of blocks.
+    MissingHeader(usize),
+    /// The number of bytes after the header differs from the number of blocks
+    /// given by the header.
+    LengthMismatch {
+        /// The number of blocks given by the header.
+        nblocks: u64,
+        /// The number of bytes after the header.
+        got: usize,
+    },
+    /// The framing of the input is invalid.
+    Format(FormatError),
+    /// The security parameters in the header are invalid.
+    Security(SecurityParamsError),
+}
+
+impl Display for GarbledCircuitDecodingError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            GarbledCircuitDecodingError::MissingHeader(n) => {
+                write!(f, "input of {} bytes is too short for the header", n)
+            }
+            GarbledCircuitDecodingError::LengthMismatch { nblocks, got } => write!(
+                f,
+                "header gives {} blocks but {} bytes follow it",
+                nblocks, got
+            ),
+            GarbledCircuitDecodingError::Format(e) => e.fmt(f),
+            GarbledCircuitDecodingError::Security(e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for GarbledCircuitDecodingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GarbledCircuitDecodingError::Format(e) => Some(e),
+            GarbledCircuitDecodingError::Security(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<FormatError> for GarbledCircuitDecodingError {
+    fn from(e: FormatError) -> GarbledCircuitDecodingError {
+        GarbledCircuitDecodingError::Format(e)
+    }
+}
+
+impl From<SecurityParamsError> for GarbledCircuitDecodingError {
+    fn from(e: SecurityParamsError) -> GarbledCircuitDecodingError {
+        GarbledCircuitDecodingError::Security(e)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// file format errors
+
+/// Errors emitted when reading a framed artifact; see [`crate::format`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FormatError {
+    /// The input of the given length is shorter than the header.
+    Truncated(usize),
+    /// The input starts with these bytes rather than the magic bytes.
+    BadMagic([u8; 4]),
+    /// The header has a format id this build does not know.
+    UnknownFormat(u16),
+    /// The input holds another kind of artifact than the one being read.
+    WrongFormat {
+        /// The kind of artifact being read.
+        expected: FormatId,
+        /// The kind of artifact in the input.
+        found: FormatId,
+    },
+    /// The input is of a version of its format this build does not read.
+    UnsupportedVersion {
+        /// The kind of artifact.
+        format: FormatId,
+        /// The version of the input.
+        found: u16,
+        /// The oldest version this build reads.
+        oldest: u16,
+        /// The newest version this build reads.
+        newest: u16,
+    },
+    /// The payload length in the header differs from the number of bytes
+    /// following the header.
+    LengthMismatch {
+        /// The payload length in the header.
+        expected: u64,
+        /// The number of bytes following the header.
+        got: usize,
+    },
+    /// The header is valid but the payload could not be decoded.
+    InvalidPayload {
+        /// The kind of artifact.
+        format: FormatId,
+        /// Why decoding failed.
+        reason: String,
+    },
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            FormatError::Truncated(n) => {
+                write!(f, "input of {} bytes is too short for the header", n)
+            }
+            FormatError::BadMagic(magic) => {
+                write!(f, "not a fancy-garbling file: starts with {:02x?}", magic)
+            }
+            FormatError::UnknownFormat(id) => {
+                write!(f, "unknown format id {}, from a newer build?", id)
+            }
+            FormatError::WrongFormat { expected, found } => write!(
+                f,
+                "this is a {} file but a {} file was expected",
+                found, expected
+            ),
+            FormatError::UnsupportedVersion {
+                format,
+                found,
+                oldest,
+                newest,
+            } => {
+                write!(
+                    f,
+                    "this is a {} file v{} but this build only reads v{}",
+                    format, found, oldest
+                )?;
+                if newest != oldest {
+                    write!(f, " to v{}", newest)?;
+                }
+                Ok(())
+            }
+            FormatError::LengthMismatch { expected, got } => write!(
+                f,
+                "header gives a payload of {} bytes but {} bytes follow it",
+                expected, got
+            ),
+            FormatError::InvalidPayload { format, reason } => {
+                write!(f, "invalid {} file: {}", format, reason)
+            }
+        }
+    }
+}
+
+impl Error for FormatError {}
+
+////////////////////////////////////////////////////////////////////////////////
+// wire label interchange errors
+
+/// Errors emitted when importing wire labels; see
+/// [`ExportedLabel`](crate::ExportedLabel).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LabelError {
+    /// The input of the given length is not a whole number of labels.
+    Truncated(usize),
+    /// The label is of a version of the format this build does not read.
+    UnsupportedVersion(u8),
+    /// The label has a role this build does not know.
+    UnknownRole(u8),
+    /// The label sets flags or reserved bytes this version does not define.
+    ReservedBits,
+    /// The label has a modulus that the wire type imported into cannot hold.
+    UnsupportedModulus(u32),
+    /// The label bytes are not those of any label of its modulus.
+    InvalidLabel {
+        /// The modulus of the label.
+        modulus: u32,
+    },
+    /// The color given with the label is not that of the label.
+    ColorMismatch,
+}
+
+impl Display for LabelError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            LabelError::Truncated(n) => {
+                write!(f, "input of {} bytes is not a whole number of labels", n)
+            }
+            LabelError::UnsupportedVersion(v) => {
+                write!(
+                    f,
+                    "label of format v{}, but this build reads only v{}",
+                    v,
+                    crate::LABEL_FORMAT_VERSION
+                )
+            }
+            LabelError::UnknownRole(role) => write!(f, "unknown wire role {}", role),
+            LabelError::ReservedBits => "the label sets reserved bits".fmt(f),
+            LabelError::UnsupportedModulus(q) => {
+                write!(f, "this wire type cannot hold a label of modulus {}", q)
+            }
+            LabelError::InvalidLabel { modulus } => {
+                write!(f, "not a label of modulus {}", modulus)
+            }
+            LabelError::ColorMismatch => "the color given is not that of the label".fmt(f),
+        }
+    }
+}
+
+impl Error for LabelError {}
+
+////////////////////////////////////////////////////////////////////////////////
+// security parameter errors
+
+/// Errors emitted when making [`SecurityParams`](crate::SecurityParams).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SecurityParamsError {
+    /// The label width is not a multiple of 8 from 32 to 256 bits.
+    LabelBits(usize),
+    /// The statistical security is not a multiple of 8 from 8 to 128 bits.
+    StatisticalBits(usize),
+    /// The hash width is not a multiple of 8 from 16 to 128 bits.
+    HashBits(usize),
+    /// The labels are wider than the wire labels used hold, such as labels
+    /// over 128 bits with `WireMod2` rather than `WideWireMod2`.
+    UnsupportedLabelBits {
+        /// The label width of the parameters.
+        label_bits: usize,
+        /// The widest labels supported.
+        max: usize,
+    },
+}
+
+impl Display for SecurityParamsError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            SecurityParamsError::LabelBits(n) if *n > 256 => {
+                write!(f, "labels of {} bits are wider than the 256 supported", n)
+            }
+            SecurityParamsError::LabelBits(n) => write!(
+                f,
+                "labels of {} bits are not a multiple of 8 from 32 to 256 bits",
+                n
+            ),
+            SecurityParamsError::StatisticalBits(n) => write!(
+                f,
+                "statistical security of {} bits is not a multiple of 8 from 8 to 128 bits",
+                n
+            ),
+            SecurityParamsError::HashBits(n) => write!(
+                f,
+                "hashes of {} bits are not a multiple of 8 from 16 to 128 bits",
+                n
+            ),
+            SecurityParamsError::UnsupportedLabelBits { label_bits, max } => write!(
+                f,
+                "labels of {} bits are wider than the {} bits supported here",
+                label_bits, max
+            ),
+        }
+    }
+}
+
+impl Error for SecurityParamsError {}
+
+////////////////////////////////////////////////////////////////////////////////
+// planning errors
+
+/// Errors emitted when planning the representation of arithmetic; see
+/// [`crate::plan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PlanError {
+    /// The bit width is not from 2 to
+    /// [`MAX_BITWIDTH`](crate::plan::MAX_BITWIDTH).
+    Bitwidth(usize),
+    /// The program has no operations.
+    Empty,
+    /// An operation uses the value of an operation that does not come before
+    /// it.
+    Operand {
+        /// The index of the operation.
+        op: usize,
+        /// The index of the value it uses.
+        operand: usize,
+    },
+}
+
+impl Display for PlanError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            PlanError::Bitwidth(n) => write!(
+                f,
+                "arithmetic on {} bits is not supported; plans take 2 to {} bits",
+                n,
+                crate::plan::MAX_BITWIDTH
+            ),
+            PlanError::Empty => write!(f, "the program has no operations"),
+            PlanError::Operand { op, operand } => write!(
+                f,
+                "operation {} uses the value of operation {}, which does not come before it",
+                op, operand
+            ),
+        }
+    }
+}
+
+impl Error for PlanError {}
+
+////////////////////////////////////////////////////////////////////////////////
+// profiling errors
+
+/// Errors emitted when profiling garbling and evaluation.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ProfileError {
+    /// The garbler produced an error.
+    GarblerError(GarblerError),
+    /// The evaluator produced an error.
+    EvaluatorError(EvaluatorError),
+}
+
+impl Display for ProfileError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ProfileError::GarblerError(e) => write!(f, "garbler error: {}", e),
+            ProfileError::EvaluatorError(e) => write!(f, "evaluator error: {}", e),
+        }
+    }
+}
+
+impl Error for ProfileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ProfileError::GarblerError(e) => Some(e),
+            ProfileError::EvaluatorError(e) => Some(e),
+        }
+    }
+}
+
+impl From<GarblerError> for ProfileError {
+    fn from(e: GarblerError) -> Self {
+        ProfileError::GarblerError(e)
+    }
+}
+
+impl From<EvaluatorError> for ProfileError {
+    fn from(e: EvaluatorError) -> Self {
+        ProfileError::EvaluatorError(e)
+    }
+}
+
+/// The result of `classic::debug_eval` finding a gate whose garbled value
+/// differs from its plaintext value, or failing to evaluate the circuit.
+#[cfg(feature = "test-utils")]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DivergenceReport {
+    /// The garbled output of a gate decodes to a value other than its
+    /// plaintext output.
+    Divergence {
+        /// The index of the gate.
+        gate: usize,
+        /// The gate, as it is displayed.
+        gate_type: String,
+        /// The output of the gate in plaintext.
+        plaintext: Modulus,
+        /// The value the garbled output decodes to, or `None` if it is not a
+        /// label of its wire.
+        garbled: Option<Modulus>,
+    },
+    /// Evaluating the circuit in plaintext failed.
+    DummyError(DummyError),
+    /// The garbler produced an error.
+    GarblerError(GarblerError),
+    /// The evaluator produced an error.
+    EvaluatorError(EvaluatorError),
+}
+
+#[cfg(feature = "test-utils")]
+impl Display for DivergenceReport {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            DivergenceReport::Divergence {
+                gate,
+                gate_type,
+                plaintext,
+                garbled: Some(garbled),
+            } => write!(
+                f,
+                "gate {} ({}) computes {} in plaintext but {} garbled",
+                gate, gate_type, plaintext, garbled
+            ),
+            DivergenceReport::Divergence {
+                gate,
+                gate_type,
+                plaintext,
+                garbled: None,
+            } => write!(
+                f,
+                "gate {} ({}) computes {} in plaintext but a garbled label of no value",
+                gate, gate_type, plaintext
+            ),
+            DivergenceReport::DummyError(e) => write!(f, "plaintext error: {}", e),
+            DivergenceReport::GarblerError(e) => write!(f, "garbler error: {}", e),
+            DivergenceReport::EvaluatorError(e) => write!(f, "evaluator error: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "test-utils")]
+impl Error for DivergenceReport {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DivergenceReport::Divergence { .. } => None,
+            DivergenceReport::DummyError(e) => Some(e),
+            DivergenceReport::GarblerError(e) => Some(e),
+            DivergenceReport::EvaluatorError(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(feature = "test-utils")]
+impl From<DummyError> for DivergenceReport {
+    fn from(e: DummyError) -> Self {
+        DivergenceReport::DummyError(e)
+    }
+}
+
+#[cfg(feature = "test-utils")]
+impl From<GarblerError> for DivergenceReport {
+    fn from(e: GarblerError) -> Self {
+        DivergenceReport::GarblerError(e)
+    }
+}
+
+#[cfg(feature = "test-utils")]
+impl From<EvaluatorError> for DivergenceReport {
+    fn from(e: EvaluatorError) -> Self {
+        DivergenceReport::EvaluatorError(e)
+    }
+}
+
 /// Errors emitted by the circuit parser.
+#[cfg(feature = "std")]
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum CircuitParserError {
     /// An I/O error occurred.
     IoError(std::io::Error),
     /// A regular expression parsing error occurred.
+    #[cfg(feature = "regex")]
     RegexError(regex::Error),
     /// An error occurred parsing an integer.
     ParseIntError,
@@ -328,43 +1316,259 @@ pub enum CircuitParserError {
     ParseLineError(String),
     /// An error occurred parsing a gate type.
     ParseGateError(String),
+    /// The counts in the header are inconsistent.
+    InvalidHeader(String),
+    /// The parsed circuit is not well-formed, e.g. a gate reads a wire that
+    /// no earlier gate or input assigns.
+    InvalidCircuit(CircuitValidationError),
+    /// The number of lines of gates differs from the count in the header.
+    GateCountMismatch {
+        /// The number of gates in the header.
+        header: usize,
+        /// The number of lines of gates in the file.
+        found: usize,
+    },
+    /// A gate refers to a wire past the number of wires in the header.
+    WireOutOfRange {
+        /// The wire referred to.
+        wire: usize,
+        /// The number of wires in the header.
+        nwires: usize,
+    },
+    /// The file has more gates, wires, or bytes than its
+    /// [`ParseLimits`](crate::circuit::ParseLimits) allow.
+    LimitExceeded {
+        /// What is limited: `"gates"`, `"wires"`, or `"bytes"`.
+        what: &'static str,
+        /// The limit.
+        max: u64,
+    },
 }
 
+#[cfg(feature = "std")]
 impl Display for CircuitParserError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
             CircuitParserError::IoError(e) => write!(f, "io error: {}", e),
+            #[cfg(feature = "regex")]
             CircuitParserError::RegexError(e) => write!(f, "regex error: {}", e),
             CircuitParserError::ParseIntError => write!(f, "unable to parse integer"),
             CircuitParserError::ParseLineError(s) => write!(f, "unable to parse line '{}'", s),
             CircuitParserError::ParseGateError(s) => write!(f, "unable to parse gate '{}'", s),
+            CircuitParserError::InvalidHeader(s) => write!(f, "invalid header: {}", s),
+            CircuitParserError::InvalidCircuit(e) => write!(f, "invalid circuit: {}", e),
+            CircuitParserError::GateCountMismatch { header, found } => write!(
+                f,
+                "the header counts {} gates, but the file has {}",
+                header, found
+            ),
+            CircuitParserError::WireOutOfRange { wire, nwires } => write!(
+                f,
+                "wire {} is past the {} wires in the header",
+                wire, nwires
+            ),
+            CircuitParserError::LimitExceeded { what, max } => {
+                write!(f, "the circuit exceeds the limit of {} {}", max, what)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for CircuitParserError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CircuitParserError::IoError(e) => Some(e),
+            #[cfg(feature = "regex")]
+            CircuitParserError::RegexError(e) => Some(e),
+            CircuitParserError::InvalidCircuit(e) => Some(e),
+            _ => None,
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl From<std::io::Error> for CircuitParserError {
     fn from(e: std::io::Error) -> CircuitParserError {
         CircuitParserError::IoError(e)
     }
 }
 
+#[cfg(feature = "regex")]
 impl From<regex::Error> for CircuitParserError {
     fn from(e: regex::Error) -> CircuitParserError {
         CircuitParserError::RegexError(e)
     }
 }
 
+#[cfg(feature = "std")]
+impl From<CircuitValidationError> for CircuitParserError {
+    fn from(e: CircuitValidationError) -> CircuitParserError {
+        CircuitParserError::InvalidCircuit(e)
+    }
+}
+
+#[cfg(feature = "std")]
 impl From<std::num::ParseIntError> for CircuitParserError {
     fn from(_: std::num::ParseIntError) -> CircuitParserError {
         CircuitParserError::ParseIntError
     }
 }
 
+/// Errors emitted when writing a circuit in Bristol Fashion.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CircuitWriteError {
+    /// An I/O error occurred.
+    IoError(std::io::Error),
+    /// The circuit is not well-formed.
+    InvalidCircuit(CircuitValidationError),
+    /// The gate of the given index, a public input or a custom gate, has no
+    /// counterpart in Bristol Fashion.
+    UnsupportedGate(usize),
+    /// A gate has a modulus other than 2.
+    NonBinaryModulus {
+        /// The index of the gate.
+        gate: usize,
+        /// The modulus of the gate.
+        modulus: Modulus,
+    },
+}
+
+#[cfg(feature = "std")]
+impl Display for CircuitWriteError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            CircuitWriteError::IoError(e) => write!(f, "io error: {}", e),
+            CircuitWriteError::InvalidCircuit(e) => write!(f, "invalid circuit: {}", e),
+            CircuitWriteError::UnsupportedGate(gate) => {
+                write!(f, "gate {} cannot be written in Bristol Fashion", gate)
+            }
+            CircuitWriteError::NonBinaryModulus { gate, modulus } => write!(
+                f,
+                "gate {} has modulus {}, but Bristol Fashion has only binary wires",
+                gate, modulus
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for CircuitWriteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CircuitWriteError::IoError(e) => Some(e),
+            CircuitWriteError::InvalidCircuit(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for CircuitWriteError {
+    fn from(e: std::io::Error) -> CircuitWriteError {
+        CircuitWriteError::IoError(e)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<CircuitValidationError> for CircuitWriteError {
+    fn from(e: CircuitValidationError) -> CircuitWriteError {
+        CircuitWriteError::InvalidCircuit(e)
+    }
+}
+
+/// Errors emitted when composing two circuits.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CircuitComposeError {
+    /// A wiring names an output past the outputs of the first circuit.
+    OutputOutOfRange {
+        /// The output named.
+        output: usize,
+        /// The number of outputs of the first circuit.
+        noutputs: usize,
+    },
+    /// A wiring names an input past the garbler and evaluator inputs of the
+    /// second circuit.
+    InputOutOfRange {
+        /// The input named.
+        input: usize,
+        /// The number of garbler and evaluator inputs of the second circuit.
+        ninputs: usize,
+    },
+    /// The wiring feeds the given input of the second circuit more than once.
+    InputWiredTwice(usize),
+    /// A wiring connects an output and an input of different moduli.
+    ModulusMismatch {
+        /// The output of the first circuit.
+        output: usize,
+        /// The input of the second circuit.
+        input: usize,
+        /// The modulus of the output.
+        output_modulus: Modulus,
+        /// The modulus of the input.
+        input_modulus: Modulus,
+    },
+    /// Building the composed circuit failed, as it does on a circuit that is
+    /// not well-formed.
+    BuilderError(CircuitBuilderError),
+}
+
+impl Display for CircuitComposeError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            CircuitComposeError::OutputOutOfRange { output, noutputs } => write!(
+                f,
+                "output {} is past the {} outputs of the first circuit",
+                output, noutputs
+            ),
+            CircuitComposeError::InputOutOfRange { input, ninputs } => write!(
+                f,
+                "input {} is past the {} inputs of the second circuit",
+                input, ninputs
+            ),
+            CircuitComposeError::InputWiredTwice(input) => {
+                write!(f, "input {} of the second circuit is wired twice", input)
+            }
+            CircuitComposeError::ModulusMismatch {
+                output,
+                input,
+                output_modulus,
+                input_modulus,
+            } => write!(
+                f,
+                "output {} has modulus {}, but input {} has modulus {}",
+                output, output_modulus, input, input_modulus
+            ),
+            CircuitComposeError::BuilderError(e) => write!(f, "circuit builder error: {}", e),
+        }
+    }
+}
+
+impl Error for CircuitComposeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CircuitComposeError::BuilderError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<CircuitBuilderError> for CircuitComposeError {
+    fn from(e: CircuitBuilderError) -> CircuitComposeError {
+        CircuitComposeError::BuilderError(e)
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // 2PC errors
 
 /// Errors produced by `twopac`.
+#[cfg(feature = "std")]
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum TwopacError {
     /// An I/O error has occurred.
     IoError(std::io::Error),
@@ -376,40 +1580,195 @@ pub enum TwopacError {
     EvaluatorError(EvaluatorError),
     /// Processing the garbled circuit produced an error.
     FancyError(FancyError),
+    /// The peer speaks a different version of the protocol; see
+    /// `twopac::semihonest::PROTOCOL_VERSION`.
+    VersionMismatch {
+        /// The version of this party.
+        ours: u32,
+        /// The version the peer sent.
+        theirs: u32,
+    },
+    /// This party was cancelled by its hooks; see
+    /// `twopac::semihonest::SemiHonestBuilder::hooks`.
+    Cancelled {
+        /// The number of gates done before it stopped.
+        gates_done: usize,
+    },
+    /// The garbler was cancelled by its hooks, and told the evaluator so.
+    PeerCancelled {
+        /// The number of gates done before it stopped.
+        gates_done: usize,
+    },
+    /// The parties encoded different public inputs; see
+    /// `twopac::semihonest::Garbler::encode_public`.
+    PublicInputMismatch,
+    /// The verifier of a zero-knowledge proof rejected it; see `twopac::zk`.
+    ProofRejected,
+    /// The verifier of a zero-knowledge proof did not garble the circuit
+    /// honestly; see `twopac::zk`.
+    InvalidGarbling,
+    /// The outputs of the two executions of a dual execution differ, so that
+    /// the peer did not garble or evaluate the circuit honestly; see
+    /// `twopac::dualex`.
+    EqualityCheckFailed,
+    /// The evaluator sent a label of output `index` that is none of the
+    /// output's; see `twopac::semihonest::Garbler::receive_output_labels`.
+    InvalidOutputLabel {
+        /// The index of the output among those received.
+        index: usize,
+    },
+    /// A party has no circuit of the name proposed; see
+    /// `twopac::semihonest::propose`.
+    UnknownCircuit(String),
+    /// The parties' circuits of the name proposed differ.
+    CircuitMismatch {
+        /// The digest of this party's circuit.
+        ours: CircuitDigest,
+        /// The digest of the peer's circuit.
+        theirs: CircuitDigest,
+    },
+    /// The parties split the inputs of the circuit proposed into numbers of
+    /// different widths.
+    InputWidthMismatch,
+    /// The parties are configured with different options of the protocol.
+    OptionsMismatch,
+    /// The peer's share of the session id tossed when the parties were built
+    /// does not match its commitment.
+    CoinTossFailed,
+    /// An audited garbler departs from its recorded transcript, first at byte
+    /// `offset` of what it sent; see
+    /// `twopac::semihonest::audit::verify_transcript`.
+    TranscriptMismatch {
+        /// The offset of the first byte sent that differs from the recording.
+        offset: usize,
+    },
+    /// This party was closed, and can no longer be used; see
+    /// `twopac::semihonest::Garbler::close`.
+    Closed,
+    /// The call does not fit the output mode of the party, such as the
+    /// evaluator decoding outputs only the garbler learns; see
+    /// `twopac::semihonest::OutputMode`.
+    WrongOutputMode,
+    /// The security parameters do not fit the wire labels, or the call; see
+    /// `twopac::semihonest::SemiHonestBuilder::security`.
+    Security(SecurityParamsError),
+}
+
+#[cfg(feature = "std")]
+impl Error for TwopacError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TwopacError::IoError(e) => Some(e),
+            TwopacError::OtError(e) => Some(e),
+            TwopacError::GarblerError(e) => Some(e),
+            TwopacError::EvaluatorError(e) => Some(e),
+            TwopacError::FancyError(e) => Some(e),
+            TwopacError::Security(e) => Some(e),
+            TwopacError::VersionMismatch { .. }
+            | TwopacError::Cancelled { .. }
+            | TwopacError::PeerCancelled { .. }
+            | TwopacError::PublicInputMismatch
+            | TwopacError::ProofRejected
+            | TwopacError::InvalidGarbling
+            | TwopacError::EqualityCheckFailed
+            | TwopacError::InvalidOutputLabel { .. }
+            | TwopacError::UnknownCircuit(_)
+            | TwopacError::CircuitMismatch { .. }
+            | TwopacError::InputWidthMismatch
+            | TwopacError::OptionsMismatch
+            | TwopacError::CoinTossFailed
+            | TwopacError::TranscriptMismatch { .. }
+            | TwopacError::Closed
+            | TwopacError::WrongOutputMode => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl TwopacError {
+    /// How the channel failed, if this error came from a failed read or write
+    /// on it.
+    pub fn channel_error(&self) -> Option<scuttlebutt::ChannelError> {
+        match self {
+            TwopacError::IoError(e) => Some(e.into()),
+            TwopacError::OtError(e) => e.channel_error(),
+            TwopacError::GarblerError(e) => e.channel_error(),
+            TwopacError::EvaluatorError(e) => e.channel_error(),
+            TwopacError::FancyError(_)
+            | TwopacError::Security(_)
+            | TwopacError::VersionMismatch { .. }
+            | TwopacError::Cancelled { .. }
+            | TwopacError::PeerCancelled { .. }
+            | TwopacError::PublicInputMismatch
+            | TwopacError::ProofRejected
+            | TwopacError::InvalidGarbling
+            | TwopacError::EqualityCheckFailed
+            | TwopacError::InvalidOutputLabel { .. }
+            | TwopacError::UnknownCircuit(_)
+            | TwopacError::CircuitMismatch { .. }
+            | TwopacError::InputWidthMismatch
+            | TwopacError::OptionsMismatch
+            | TwopacError::CoinTossFailed
+            | TwopacError::TranscriptMismatch { .. }
+            | TwopacError::Closed
+            | TwopacError::WrongOutputMode => None,
+        }
+    }
 }
 
-impl std::error::Error for TwopacError {}
+#[cfg(feature = "std")]
+impl From<scuttlebutt::cointoss::Error> for TwopacError {
+    fn from(e: scuttlebutt::cointoss::Error) -> TwopacError {
+        match e {
+            scuttlebutt::cointoss::Error::IoError(e) => TwopacError::IoError(e),
+            _ => TwopacError::CoinTossFailed,
+        }
+    }
+}
 
+#[cfg(feature = "std")]
 impl From<ocelot::Error> for TwopacError {
     fn from(e: ocelot::Error) -> TwopacError {
         TwopacError::OtError(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<std::io::Error> for TwopacError {
     fn from(e: std::io::Error) -> TwopacError {
         TwopacError::IoError(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<EvaluatorError> for TwopacError {
     fn from(e: EvaluatorError) -> TwopacError {
         TwopacError::EvaluatorError(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<GarblerError> for TwopacError {
     fn from(e: GarblerError) -> TwopacError {
         TwopacError::GarblerError(e)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<FancyError> for TwopacError {
     fn from(e: FancyError) -> TwopacError {
         TwopacError::FancyError(e)
     }
 }
 
+#[cfg(feature = "std")]
+impl From<SecurityParamsError> for TwopacError {
+    fn from(e: SecurityParamsError) -> TwopacError {
+        TwopacError::Security(e)
+    }
+}
+
+#[cfg(feature = "std")]
 impl std::fmt::Display for TwopacError {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -418,18 +1777,86 @@ impl std::fmt::Display for TwopacError {
             TwopacError::EvaluatorError(e) => write!(f, "evaluator error: {}", e),
             TwopacError::GarblerError(e) => write!(f, "garbler error: {}", e),
             TwopacError::FancyError(e) => write!(f, "fancy error: {}", e),
+            TwopacError::VersionMismatch { ours, theirs } => write!(
+                f,
+                "protocol version mismatch: this party speaks version {} but its peer version {}",
+                ours, theirs
+            ),
+            TwopacError::Cancelled { gates_done } => {
+                write!(f, "cancelled after {} gates", gates_done)
+            }
+            TwopacError::PeerCancelled { gates_done } => {
+                write!(f, "the garbler cancelled after {} gates", gates_done)
+            }
+            TwopacError::PublicInputMismatch => {
+                "the parties encoded different public inputs".fmt(f)
+            }
+            TwopacError::ProofRejected => "the verifier rejected the proof".fmt(f),
+            TwopacError::InvalidGarbling => {
+                "the verifier did not garble the circuit honestly".fmt(f)
+            }
+            TwopacError::EqualityCheckFailed => "the outputs of the two executions differ".fmt(f),
+            TwopacError::InvalidOutputLabel { index } => {
+                write!(f, "the label of output {} is invalid", index)
+            }
+            TwopacError::UnknownCircuit(name) => {
+                write!(f, "a party has no circuit named {:?}", name)
+            }
+            TwopacError::CircuitMismatch { ours, theirs } => write!(
+                f,
+                "circuit mismatch: this party's circuit has digest {} but its peer's {}",
+                ours, theirs
+            ),
+            TwopacError::InputWidthMismatch => {
+                "the parties split the inputs of the circuit differently".fmt(f)
+            }
+            TwopacError::OptionsMismatch => {
+                "the parties are configured with different protocol options".fmt(f)
+            }
+            TwopacError::CoinTossFailed => {
+                "the peer's share of the session id does not match its commitment".fmt(f)
+            }
+            TwopacError::TranscriptMismatch { offset } => write!(
+                f,
+                "the garbler departs from its transcript at byte {} of what it sent",
+                offset
+            ),
+            TwopacError::Closed => "the party has been closed".fmt(f),
+            TwopacError::WrongOutputMode => {
+                "the call does not fit the output mode of the party".fmt(f)
+            }
+            TwopacError::Security(e) => write!(f, "security parameters: {}", e),
         }
     }
 }
 
+// Unwrap the cause where the target type can hold it, so that `source()` and
+// `channel_error()` still reach the original I/O error.
+#[cfg(feature = "std")]
 impl From<TwopacError> for GarblerError {
     fn from(e: TwopacError) -> GarblerError {
-        GarblerError::CommunicationError(e.to_string())
+        match e {
+            TwopacError::IoError(e) => GarblerError::IoError(e),
+            TwopacError::OtError(e) => GarblerError::OtError(e),
+            TwopacError::GarblerError(e) => e,
+            TwopacError::FancyError(e) => GarblerError::FancyError(e),
+            TwopacError::Cancelled { gates_done } => GarblerError::Cancelled { gates_done },
+            e => GarblerError::CommunicationError(e.to_string()),
+        }
     }
 }
 
+#[cfg(feature = "std")]
 impl From<TwopacError> for EvaluatorError {
     fn from(e: TwopacError) -> EvaluatorError {
-        EvaluatorError::CommunicationError(e.to_string())
+        match e {
+            TwopacError::IoError(e) => EvaluatorError::IoError(e),
+            TwopacError::OtError(e) => EvaluatorError::OtError(e),
+            TwopacError::EvaluatorError(e) => e,
+            TwopacError::FancyError(e) => EvaluatorError::FancyError(e),
+            TwopacError::Cancelled { gates_done } => EvaluatorError::Cancelled { gates_done },
+            TwopacError::Security(e) => EvaluatorError::Security(e),
+            e => EvaluatorError::CommunicationError(e.to_string()),
+        }
     }
 }
@@ -3,8 +3,13 @@
 //! An implementer must be able to create inputs, constants, do modular arithmetic, and
 //! create projections.
 
-use crate::errors::FancyError;
+use crate::{
+    circuit::{lower_custom_gate, CustomGate},
+    errors::FancyError,
+    Modulus,
+};
 use itertools::Itertools;
+use std::sync::Arc;
 
 mod binary;
 mod bundle;
@@ -15,13 +20,14 @@ mod reveal;
 pub use binary::{BinaryBundle, BinaryGadgets};
 pub use bundle::{ArithmeticBundleGadgets, BinaryBundleGadgets, Bundle, BundleGadgets};
 pub use crt::{CrtBundle, CrtGadgets};
+pub(crate) use input::check_inputs;
 pub use input::FancyInput;
 pub use reveal::FancyReveal;
 
 /// An object that has some modulus. Basic object of `Fancy` computations.
 pub trait HasModulus {
     /// The modulus of the wire.
-    fn modulus(&self) -> u16;
+    fn modulus(&self) -> Modulus;
 }
 
 /// Fancy DSL providing binary operations
@@ -130,6 +136,19 @@ pub trait FancyBinary: Fancy {
         let ysel = self.and(b, y)?;
         self.xor(&xsel, &ysel)
     }
+
+    /// Apply a custom gate to as many wires as it has inputs.
+    ///
+    /// By default, this lowers the gate to AND and XOR gates following its
+    /// truth table, with [`lower_custom_gate`]; implementations that can
+    /// garble or record the gate natively override it.
+    fn custom(
+        &mut self,
+        gate: &Arc<dyn CustomGate>,
+        inputs: &[Self::Item],
+    ) -> Result<Self::Item, Self::Error> {
+        lower_custom_gate(self, gate.as_ref(), inputs)
+    }
 }
 
 /// DSL for the basic computations supported by `fancy-garbling`.
@@ -144,14 +163,14 @@ pub trait Fancy {
     type Error: std::fmt::Debug + std::fmt::Display + std::convert::From<FancyError>;
 
     /// Create a constant `x` with modulus `q`.
-    fn constant(&mut self, x: u16, q: u16) -> Result<Self::Item, Self::Error>;
+    fn constant(&mut self, x: Modulus, q: Modulus) -> Result<Self::Item, Self::Error>;
 
     /// Process this wire as output. Some `Fancy` implementers don't actually *return*
     /// output, but they need to be involved in the process, so they can return `None`.
-    fn output(&mut self, x: &Self::Item) -> Result<Option<u16>, Self::Error>;
+    fn output(&mut self, x: &Self::Item) -> Result<Option<Modulus>, Self::Error>;
 
     /// Output a slice of wires.
-    fn outputs(&mut self, xs: &[Self::Item]) -> Result<Option<Vec<u16>>, Self::Error> {
+    fn outputs(&mut self, xs: &[Self::Item]) -> Result<Option<Vec<Modulus>>, Self::Error> {
         let mut zs = Vec::with_capacity(xs.len());
         for x in xs.iter() {
             zs.push(self.output(x)?);
@@ -163,15 +182,21 @@ pub trait Fancy {
 /// DSL for arithmetic computation.
 pub trait FancyArithmetic: Fancy {
     /// Add `x` and `y`.
+    ///
+    /// Implementations return [`FancyError::UnequalModuli`] if `x` and `y`
+    /// have different moduli, as they do for `sub`.
     fn add(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error>;
 
     /// Subtract `x` and `y`.
     fn sub(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error>;
 
     /// Multiply `x` times the constant `c`.
-    fn cmul(&mut self, x: &Self::Item, c: u16) -> Result<Self::Item, Self::Error>;
+    fn cmul(&mut self, x: &Self::Item, c: Modulus) -> Result<Self::Item, Self::Error>;
 
     /// Multiply `x` and `y`.
+    ///
+    /// The moduli of `x` and `y` may differ, in which case the result has the
+    /// larger of the two.
     fn mul(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error>;
 
     /// Project `x` according to the truth table `tt`. Resulting wire has modulus `q`.
@@ -180,8 +205,8 @@ pub trait FancyArithmetic: Fancy {
     fn proj(
         &mut self,
         x: &Self::Item,
-        q: u16,
-        tt: Option<Vec<u16>>,
+        q: Modulus,
+        tt: Option<Vec<Modulus>>,
     ) -> Result<Self::Item, Self::Error>;
 
     ////////////////////////////////////////////////////////////////////////////////
@@ -202,7 +227,11 @@ pub trait FancyArithmetic: Fancy {
         Ok(z)
     }
     /// Change the modulus of `x` to `to_modulus` using a projection gate.
-    fn mod_change(&mut self, x: &Self::Item, to_modulus: u16) -> Result<Self::Item, Self::Error> {
+    fn mod_change(
+        &mut self,
+        x: &Self::Item,
+        to_modulus: Modulus,
+    ) -> Result<Self::Item, Self::Error> {
         let from_modulus = x.modulus();
         if from_modulus == to_modulus {
             return Ok(x.clone());
@@ -212,6 +241,33 @@ pub trait FancyArithmetic: Fancy {
     }
 }
 
+/// Check that `x` and `y`, the arguments of `op`, have the same modulus.
+pub(crate) fn check_moduli(op: &'static str, x: Modulus, y: Modulus) -> Result<(), FancyError> {
+    if x != y {
+        return Err(FancyError::UnequalModuli { op, x, y });
+    }
+    Ok(())
+}
+
+/// Check that bundles `xs` and `ys`, the arguments of `op`, have the same
+/// moduli, naming the first wires that differ.
+pub(crate) fn check_bundle_moduli(
+    op: &'static str,
+    xs: &[Modulus],
+    ys: &[Modulus],
+) -> Result<(), FancyError> {
+    if xs.len() != ys.len() {
+        return Err(FancyError::InvalidArgNum {
+            got: ys.len(),
+            needed: xs.len(),
+        });
+    }
+    for (&x, &y) in xs.iter().zip(ys.iter()) {
+        check_moduli(op, x, y)?;
+    }
+    Ok(())
+}
+
 macro_rules! check_binary {
     ($x:ident) => {
         if $x.modulus() != 2 {
@@ -2,9 +2,10 @@ use crate::{
     errors::FancyError,
     fancy::{
         bundle::{Bundle, BundleGadgets},
-        HasModulus,
+        check_bundle_moduli, HasModulus,
     },
-    util, FancyBinary,
+    util::{self, BitOrder},
+    FancyBinary, Modulus,
 };
 use itertools::Itertools;
 use std::ops::{Deref, DerefMut};
@@ -19,10 +20,29 @@ impl<W: Clone + HasModulus> BinaryBundle<W> {
         BinaryBundle(Bundle::new(ws))
     }
 
+    /// Create a new binary bundle from wires holding the bits of a number in
+    /// `order`.
+    ///
+    /// Binary bundles hold their bits least significant first, so this
+    /// reverses wires given most significant first.
+    pub fn from_order(ws: Vec<W>, order: BitOrder) -> BinaryBundle<W> {
+        BinaryBundle::new(order.arrange(ws))
+    }
+
     /// Extract the underlying bundle from this binary bundle.
     pub fn extract(self) -> Bundle<W> {
         self.0
     }
+
+    /// Return the wires of this bundle with its bits in `order`.
+    pub fn wires_in(&self, order: BitOrder) -> Vec<W> {
+        order.arrange(self.wires().to_vec())
+    }
+
+    /// Return a binary bundle of the wires in reverse order.
+    pub fn reversed(&self) -> BinaryBundle<W> {
+        BinaryBundle(self.0.reversed())
+    }
 }
 
 impl<W: Clone + HasModulus> Deref for BinaryBundle<W> {
@@ -126,9 +146,7 @@ pub trait BinaryGadgets: FancyBinary + BundleGadgets {
         xs: &BinaryBundle<Self::Item>,
         ys: &BinaryBundle<Self::Item>,
     ) -> Result<(BinaryBundle<Self::Item>, Self::Item), Self::Error> {
-        if xs.moduli() != ys.moduli() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
-        }
+        check_bundle_moduli("bin_addition", &xs.moduli(), &ys.moduli())?;
         let xwires = xs.wires();
         let ywires = ys.wires();
         let (mut z, mut c) = self.adder(&xwires[0], &ywires[0], None)?;
@@ -148,9 +166,7 @@ pub trait BinaryGadgets: FancyBinary + BundleGadgets {
         xs: &BinaryBundle<Self::Item>,
         ys: &BinaryBundle<Self::Item>,
     ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
-        if xs.moduli() != ys.moduli() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
-        }
+        check_bundle_moduli("bin_addition_no_carry", &xs.moduli(), &ys.moduli())?;
         let xwires = xs.wires();
         let ywires = ys.wires();
         let (mut z, mut c) = self.adder(&xwires[0], &ywires[0], None)?;
@@ -180,9 +196,7 @@ pub trait BinaryGadgets: FancyBinary + BundleGadgets {
         xs: &BinaryBundle<Self::Item>,
         ys: &BinaryBundle<Self::Item>,
     ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
-        if xs.moduli() != ys.moduli() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
-        }
+        check_bundle_moduli("bin_multiplication_lower_half", &xs.moduli(), &ys.moduli())?;
 
         let xwires = xs.wires();
         let ywires = ys.wires();
@@ -212,9 +226,7 @@ pub trait BinaryGadgets: FancyBinary + BundleGadgets {
         xs: &BinaryBundle<Self::Item>,
         ys: &BinaryBundle<Self::Item>,
     ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
-        if xs.moduli() != ys.moduli() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
-        }
+        check_bundle_moduli("bin_mul", &xs.moduli(), &ys.moduli())?;
 
         let xwires = xs.wires();
         let ywires = ys.wires();
@@ -249,9 +261,7 @@ pub trait BinaryGadgets: FancyBinary + BundleGadgets {
         xs: &BinaryBundle<Self::Item>,
         ys: &BinaryBundle<Self::Item>,
     ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
-        if xs.moduli() != ys.moduli() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
-        }
+        check_bundle_moduli("bin_div", &xs.moduli(), &ys.moduli())?;
         let ys_neg = self.bin_twos_complement(ys)?;
         let mut acc = self.bin_constant_bundle(0, xs.size())?;
         let mut qs = BinaryBundle::new(Vec::new());
@@ -303,11 +313,11 @@ pub trait BinaryGadgets: FancyBinary + BundleGadgets {
     ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
         let c1_bs = util::u128_to_bits(c1, nbits)
             .into_iter()
-            .map(|x: u16| x > 0)
+            .map(|x: Modulus| x > 0)
             .collect_vec();
         let c2_bs = util::u128_to_bits(c2, nbits)
             .into_iter()
-            .map(|x: u16| x > 0)
+            .map(|x: Modulus| x > 0)
             .collect_vec();
         c1_bs
             .into_iter()
@@ -491,6 +501,10 @@ pub trait BinaryGadgets: FancyBinary + BundleGadgets {
     }
 
     /// arithmetic right shift (shifts the sign of the MSB into the new spaces)
+    ///
+    /// On fixed-point numbers in two's complement, this rescales a product.
+    /// Their widths are best chosen by probing the products and sums of a run
+    /// on a [`DummyProfiler`](crate::dummy::DummyProfiler).
     fn bin_rsa(
         &mut self,
         x: &BinaryBundle<Self::Item>,
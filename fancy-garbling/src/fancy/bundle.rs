@@ -1,7 +1,7 @@
 use crate::{
     errors::FancyError,
-    fancy::{Fancy, HasModulus},
-    FancyArithmetic, FancyBinary,
+    fancy::{check_bundle_moduli, Fancy, HasModulus},
+    FancyArithmetic, FancyBinary, Modulus,
 };
 use itertools::Itertools;
 use std::ops::Index;
@@ -17,7 +17,7 @@ impl<W: Clone + HasModulus> Bundle<W> {
     }
 
     /// Return the moduli of all the wires in the bundle.
-    pub fn moduli(&self) -> Vec<u16> {
+    pub fn moduli(&self) -> Vec<Modulus> {
         self.0.iter().map(HasModulus::modulus).collect()
     }
 
@@ -37,7 +37,7 @@ impl<W: Clone + HasModulus> Bundle<W> {
     }
 
     /// Returns a new bundle only containing wires with matching moduli.
-    pub fn with_moduli(&self, moduli: &[u16]) -> Bundle<W> {
+    pub fn with_moduli(&self, moduli: &[Modulus]) -> Bundle<W> {
         let old_ws = self.wires();
         let mut new_ws = Vec::with_capacity(moduli.len());
         for &p in moduli {
@@ -86,6 +86,11 @@ impl<W: Clone + HasModulus> Bundle<W> {
     pub fn reverse(&mut self) {
         self.0.reverse();
     }
+
+    /// Return a bundle of the wires in reverse order.
+    pub fn reversed(&self) -> Bundle<W> {
+        Bundle(self.0.iter().rev().cloned().collect())
+    }
 }
 
 impl<W: Clone + HasModulus> Index<usize> for Bundle<W> {
@@ -177,8 +182,8 @@ pub trait ArithmeticBundleGadgets: FancyArithmetic {
         }
 
         let n = xs[0].wires().len();
-        if !xs.iter().all(|x| x.moduli() == xs[0].moduli()) {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
+        for x in xs.iter().skip(1) {
+            check_bundle_moduli("mixed_radix_addition", &xs[0].moduli(), &x.moduli())?;
         }
 
         let mut digit_carry = None;
@@ -199,7 +204,7 @@ pub trait ArithmeticBundleGadgets: FancyArithmetic {
                 // compute the carries
                 let q = xs[0].wires()[i].modulus();
                 // max_carry currently contains the max carry from the previous iteration
-                let max_val = nargs as u16 * (q - 1) + max_carry;
+                let max_val = nargs as Modulus * (q - 1) + max_carry;
                 // now it is the max carry of this iteration
                 max_carry = max_val / q;
 
@@ -219,7 +224,7 @@ pub trait ArithmeticBundleGadgets: FancyArithmetic {
                 let tt = (0..=max_val).map(|i| (i / q) % next_mod).collect_vec();
                 digit_carry = Some(self.proj(&carry, next_mod, Some(tt))?);
 
-                let next_max_val = nargs as u16 * (next_mod - 1) + max_carry;
+                let next_max_val = nargs as Modulus * (next_mod - 1) + max_carry;
 
                 if i < n - 2 {
                     if max_carry < next_mod {
@@ -256,8 +261,12 @@ pub trait ArithmeticBundleGadgets: FancyArithmetic {
         }
 
         let n = xs[0].wires().len();
-        if !xs.iter().all(|x| x.moduli() == xs[0].moduli()) {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
+        for x in xs.iter().skip(1) {
+            check_bundle_moduli(
+                "mixed_radix_addition_msb_only",
+                &xs[0].moduli(),
+                &x.moduli(),
+            )?;
         }
 
         let mut opt_carry = None;
@@ -269,7 +278,7 @@ pub trait ArithmeticBundleGadgets: FancyArithmetic {
             // compute the carry
             let q = xs[0].moduli()[i];
             // max_carry currently contains the max carry from the previous iteration
-            let max_val = nargs as u16 * (q - 1) + max_carry;
+            let max_val = nargs as Modulus * (q - 1) + max_carry;
             // now it is the max carry of this iteration
             max_carry = max_val / q;
 
@@ -291,7 +300,7 @@ pub trait ArithmeticBundleGadgets: FancyArithmetic {
             // compute the next carry, if i < n-2, or it will be used to compute the
             // output MSB, in which case it should be the modulus of the SB
             let next_mod = if i < n - 2 {
-                nargs as u16 * (xs[0].moduli()[i + 1] - 1) + max_carry + 1
+                nargs as Modulus * (xs[0].moduli()[i + 1] - 1) + max_carry + 1
             } else {
                 xs[0].moduli()[i + 1] // we will be adding the carry to the MSB
             };
@@ -327,10 +336,8 @@ pub trait ArithmeticBundleGadgets: FancyArithmetic {
         x: &Bundle<Self::Item>,
         y: &Bundle<Self::Item>,
     ) -> Result<Self::Item, Self::Error> {
-        if x.moduli() != y.moduli() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
-        }
-        let wlen = x.wires().len() as u16;
+        check_bundle_moduli("eq_bundles", &x.moduli(), &y.moduli())?;
+        let wlen = x.wires().len() as Modulus;
         let zs = x
             .wires()
             .iter()
@@ -377,8 +384,8 @@ pub trait BundleGadgets: Fancy {
     /// Creates a bundle of constant wires using moduli `ps`.
     fn constant_bundle(
         &mut self,
-        xs: &[u16],
-        ps: &[u16],
+        xs: &[Modulus],
+        ps: &[Modulus],
     ) -> Result<Bundle<Self::Item>, Self::Error> {
         xs.iter()
             .zip(ps.iter())
@@ -388,7 +395,10 @@ pub trait BundleGadgets: Fancy {
     }
 
     /// Output the wires that make up a bundle.
-    fn output_bundle(&mut self, x: &Bundle<Self::Item>) -> Result<Option<Vec<u16>>, Self::Error> {
+    fn output_bundle(
+        &mut self,
+        x: &Bundle<Self::Item>,
+    ) -> Result<Option<Vec<Modulus>>, Self::Error> {
         let ws = x.wires();
         let mut outputs = Vec::with_capacity(ws.len());
         for w in ws.iter() {
@@ -401,7 +411,7 @@ pub trait BundleGadgets: Fancy {
     fn output_bundles(
         &mut self,
         xs: &[Bundle<Self::Item>],
-    ) -> Result<Option<Vec<Vec<u16>>>, Self::Error> {
+    ) -> Result<Option<Vec<Vec<Modulus>>>, Self::Error> {
         let mut zs = Vec::with_capacity(xs.len());
         for x in xs.iter() {
             let z = self.output_bundle(x)?;
@@ -444,3 +454,173 @@ pub trait BundleGadgets: Fancy {
         Ok(Bundle(ws))
     }
 }
+
+#[cfg(all(test, unix, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::{check, gadget, util};
+    use rand::thread_rng;
+
+    const TRIALS: usize = 8;
+
+    /// Split `xs` into bundles of `n` wires.
+    fn bundles<W: Clone + HasModulus>(xs: &[W], n: usize) -> Vec<Bundle<W>> {
+        xs.chunks(n).map(|c| Bundle::new(c.to_vec())).collect()
+    }
+
+    #[test]
+    fn add_sub_mul_bundles() {
+        let moduli = [3, 5, 7, 3, 5, 7];
+        let pairwise = |op: fn(Modulus, Modulus) -> Modulus| {
+            move |xs: &[Modulus]| {
+                (0..3)
+                    .map(|i| op(xs[i], xs[i + 3]) % moduli[i])
+                    .collect::<Vec<_>>()
+            }
+        };
+        let mut rng = thread_rng();
+        check::gadget(
+            gadget!(|f, xs| {
+                let bs = bundles(xs, 3);
+                Ok(f.add_bundles(&bs[0], &bs[1])?.wires().to_vec())
+            }),
+            pairwise(|x, y| x + y),
+            &moduli,
+            TRIALS,
+            &mut rng,
+        );
+        check::gadget(
+            gadget!(|f, xs| {
+                let bs = bundles(xs, 3);
+                Ok(f.sub_bundles(&bs[0], &bs[1])?.wires().to_vec())
+            }),
+            |xs| {
+                (0..3)
+                    .map(|i| (xs[i] + moduli[i] - xs[i + 3]) % moduli[i])
+                    .collect()
+            },
+            &moduli,
+            TRIALS,
+            &mut rng,
+        );
+        check::gadget(
+            gadget!(|f, xs| {
+                let bs = bundles(xs, 3);
+                Ok(f.mul_bundles(&bs[0], &bs[1])?.wires().to_vec())
+            }),
+            pairwise(|x, y| x * y),
+            &moduli,
+            TRIALS,
+            &mut rng,
+        );
+    }
+
+    #[test]
+    fn mixed_radix_addition() {
+        let radii = [2, 3, 5, 4];
+        let moduli = radii.repeat(3);
+        let sum = |xs: &[Modulus]| {
+            let total = xs
+                .chunks(radii.len())
+                .map(|x| util::from_mixed_radix(x, &radii))
+                .sum::<u128>();
+            util::as_mixed_radix(total % 120, &radii)
+        };
+        let mut rng = thread_rng();
+        check::gadget(
+            gadget!(|f, xs| {
+                let z = f.mixed_radix_addition(&bundles(xs, 4))?;
+                Ok(z.wires().to_vec())
+            }),
+            sum,
+            &moduli,
+            TRIALS,
+            &mut rng,
+        );
+        check::gadget(
+            gadget!(|f, xs| Ok(vec![f.mixed_radix_addition_msb_only(&bundles(xs, 4))?])),
+            |xs| vec![sum(xs)[3]],
+            &moduli,
+            TRIALS,
+            &mut rng,
+        );
+    }
+
+    #[test]
+    fn mask_and_multiplex() {
+        let mut rng = thread_rng();
+        check::gadget(
+            gadget!(|f, xs| Ok(f
+                .mask(&xs[0], &Bundle::new(xs[1..].to_vec()))?
+                .wires()
+                .to_vec())),
+            |xs| xs[1..].iter().map(|&x| x * xs[0]).collect(),
+            &[2, 5, 5, 5],
+            TRIALS,
+            &mut rng,
+        );
+        check::gadget(
+            gadget!(|f, xs| {
+                let bs = bundles(&xs[1..], 3);
+                Ok(f.multiplex(&xs[0], &bs[0], &bs[1])?.wires().to_vec())
+            }),
+            |xs| {
+                let i = 1 + 3 * xs[0] as usize;
+                xs[i..i + 3].to_vec()
+            },
+            &[2; 7],
+            TRIALS,
+            &mut rng,
+        );
+    }
+
+    #[test]
+    fn eq_bundles() {
+        // Small moduli, so that equal bundles come up.
+        let mut rng = thread_rng();
+        check::gadget(
+            gadget!(|f, xs| {
+                let bs = bundles(xs, 2);
+                Ok(vec![f.eq_bundles(&bs[0], &bs[1])?])
+            }),
+            |xs| vec![(xs[..2] == xs[2..]) as Modulus],
+            &[2, 3, 2, 3],
+            4 * TRIALS,
+            &mut rng,
+        );
+    }
+
+    #[test]
+    fn constants_and_shifts() {
+        let mut rng = thread_rng();
+        check::gadget(
+            gadget!(|f, xs| {
+                let c = f.constant_bundle(&[1, 4, 2], &[3, 5, 7])?;
+                Ok(f.add_bundles(&Bundle::new(xs.to_vec()), &c)?
+                    .wires()
+                    .to_vec())
+            }),
+            |xs| vec![(xs[0] + 1) % 3, (xs[1] + 4) % 5, (xs[2] + 2) % 7],
+            &[3, 5, 7],
+            TRIALS,
+            &mut rng,
+        );
+        check::gadget(
+            gadget!(|f, xs| Ok(f.shift(&Bundle::new(xs.to_vec()), 2)?.wires().to_vec())),
+            |xs| [&[0, 0], &xs[..2]].concat(),
+            &[5; 4],
+            TRIALS,
+            &mut rng,
+        );
+        check::gadget(
+            gadget!(|f, xs| Ok(f
+                .shift_extend(&Bundle::new(xs.to_vec()), 2)?
+                .wires()
+                .to_vec())),
+            |xs| [&[0, 0], xs].concat(),
+            &[5; 4],
+            TRIALS,
+            &mut rng,
+        );
+    }
+}
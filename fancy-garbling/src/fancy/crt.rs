@@ -1,10 +1,14 @@
 //! Module containing `CrtGadgets`, which are the CRT-based gadgets for `Fancy`.
 
-use super::{bundle::ArithmeticBundleGadgets, HasModulus};
+use super::{
+    binary::{BinaryBundle, BinaryGadgets},
+    bundle::ArithmeticBundleGadgets,
+    check_bundle_moduli, HasModulus,
+};
 use crate::{
     errors::FancyError,
     fancy::bundle::{Bundle, BundleGadgets},
-    util, FancyArithmetic, FancyBinary,
+    util, FancyArithmetic, FancyBinary, Modulus,
 };
 use itertools::Itertools;
 use std::ops::Deref;
@@ -19,15 +23,31 @@ impl<W: Clone + HasModulus> CrtBundle<W> {
         CrtBundle(Bundle::new(ws))
     }
 
+    /// Create a new CRT bundle from a vector of wires, failing if their moduli
+    /// are not pairwise coprime or their product overflows a `u128`.
+    pub fn new_checked(ws: Vec<W>) -> Result<CrtBundle<W>, FancyError> {
+        util::crt_modulus_checked(&ws.iter().map(HasModulus::modulus).collect_vec())?;
+        Ok(CrtBundle::new(ws))
+    }
+
     /// Extract the underlying bundle from this CRT bundle.
     pub fn extract(self) -> Bundle<W> {
         self.0
     }
 
     /// Return the product of all the wires' moduli.
+    ///
+    /// Panics in debug builds, and wraps in release, if the product overflows
+    /// a `u128`; see [`CrtBundle::moduli_product_checked`].
     pub fn composite_modulus(&self) -> u128 {
         util::product(&self.iter().map(HasModulus::modulus).collect_vec())
     }
+
+    /// Return the product of all the wires' moduli, or `None` if it overflows
+    /// a `u128`.
+    pub fn moduli_product_checked(&self) -> Option<u128> {
+        util::product_checked(&self.iter().map(HasModulus::modulus).collect_vec())
+    }
 }
 
 impl<W: Clone + HasModulus> Deref for CrtBundle<W> {
@@ -47,27 +67,39 @@ impl<W: Clone + HasModulus> From<Bundle<W>> for CrtBundle<W> {
 impl<F: FancyArithmetic + FancyBinary> CrtGadgets for F {}
 
 /// Extension trait for `Fancy` providing advanced CRT gadgets based on bundles of wires.
+///
+/// The modulus of a bundle must hold every value it takes, negative ones
+/// included, for gadgets such as [`crt_relu`](CrtGadgets::crt_relu) and
+/// [`crt_sign`](CrtGadgets::crt_sign) to be exact. To choose the moduli of a
+/// neural network's layers, run it on a
+/// [`DummyProfiler`](crate::dummy::DummyProfiler) with representative inputs,
+/// probing the outputs of each layer, and read their ranges off the report.
 pub trait CrtGadgets:
-    FancyArithmetic + FancyBinary + ArithmeticBundleGadgets + BundleGadgets
+    FancyArithmetic + FancyBinary + ArithmeticBundleGadgets + BundleGadgets + BinaryGadgets
 {
     /// Creates a bundle of constant wires for the CRT representation of `x` under
     /// composite modulus `q`.
+    ///
+    /// `x` is reduced mod `q`. Fails if `q` is not a product of distinct
+    /// primes from `util::PRIMES`.
     fn crt_constant_bundle(
         &mut self,
         x: u128,
         q: u128,
     ) -> Result<CrtBundle<Self::Item>, Self::Error> {
-        let ps = util::factor(q);
-        let xs = ps.iter().map(|&p| (x % p as u128) as u16).collect_vec();
+        let ps = util::factor_checked(q)?;
+        let xs = ps.iter().map(|&p| (x % p as u128) as Modulus).collect_vec();
         self.constant_bundle(&xs, &ps).map(CrtBundle)
     }
 
     /// Output a CRT bundle and interpret it mod Q.
+    ///
+    /// Fails if the bundle's moduli are not pairwise coprime, or Q overflows a
+    /// `u128`.
     fn crt_output(&mut self, x: &CrtBundle<Self::Item>) -> Result<Option<u128>, Self::Error> {
-        let q = x.composite_modulus();
-        Ok(self
-            .output_bundle(x)?
-            .map(|xs| util::crt_inv_factor(&xs, q)))
+        let ps = x.moduli();
+        util::crt_modulus_checked(&ps)?;
+        Ok(self.output_bundle(x)?.map(|xs| util::crt_inv(&xs, &ps)))
     }
 
     /// Output a slice of CRT bundles and interpret the outputs mod Q.
@@ -132,15 +164,13 @@ pub trait CrtGadgets:
     fn crt_cexp(
         &mut self,
         x: &CrtBundle<Self::Item>,
-        c: u16,
+        c: Modulus,
     ) -> Result<CrtBundle<Self::Item>, Self::Error> {
         x.wires()
             .iter()
             .map(|x| {
                 let p = x.modulus();
-                let tab = (0..p)
-                    .map(|x| ((x as u64).pow(c as u32) % p as u64) as u16)
-                    .collect_vec();
+                let tab = (0..p).map(|x| util::pow_mod(x, c, p)).collect_vec();
                 self.proj(x, p, Some(tab))
             })
             .collect::<Result<Vec<Self::Item>, Self::Error>>()
@@ -151,7 +181,7 @@ pub trait CrtGadgets:
     fn crt_rem(
         &mut self,
         x: &CrtBundle<Self::Item>,
-        p: u16,
+        p: Modulus,
     ) -> Result<CrtBundle<Self::Item>, Self::Error> {
         let i = x.moduli().iter().position(|&q| p == q).ok_or_else(|| {
             Self::Error::from(FancyError::InvalidArg(
@@ -174,7 +204,7 @@ pub trait CrtGadgets:
     fn crt_fractional_mixed_radix(
         &mut self,
         bun: &CrtBundle<Self::Item>,
-        ms: &[u16],
+        ms: &[Modulus],
     ) -> Result<Self::Item, Self::Error> {
         let ndigits = ms.len();
 
@@ -216,14 +246,14 @@ pub trait CrtGadgets:
         &mut self,
         x: &CrtBundle<Self::Item>,
         accuracy: &str,
-        output_moduli: Option<&[u16]>,
+        output_moduli: Option<&[Modulus]>,
     ) -> Result<CrtBundle<Self::Item>, Self::Error> {
         let factors_of_m = &get_ms(x, accuracy);
         let res = self.crt_fractional_mixed_radix(x, factors_of_m)?;
 
         // project the MSB to 0/1, whether or not it is less than p/2
         let p = *factors_of_m.last().unwrap();
-        let mask_tt = (0..p).map(|x| (x < p / 2) as u16).collect_vec();
+        let mask_tt = (0..p).map(|x| (x < p / 2) as Modulus).collect_vec();
         let mask = self.proj(&res, 2, Some(mask_tt))?;
 
         // use the mask to either output x or 0
@@ -247,7 +277,7 @@ pub trait CrtGadgets:
         let factors_of_m = &get_ms(x, accuracy);
         let res = self.crt_fractional_mixed_radix(x, factors_of_m)?;
         let p = *factors_of_m.last().unwrap();
-        let tt = (0..p).map(|x| (x >= p / 2) as u16).collect_vec();
+        let tt = (0..p).map(|x| (x >= p / 2) as Modulus).collect_vec();
         self.proj(&res, 2, Some(tt))
     }
 
@@ -258,7 +288,7 @@ pub trait CrtGadgets:
         &mut self,
         x: &CrtBundle<Self::Item>,
         accuracy: &str,
-        output_moduli: Option<&[u16]>,
+        output_moduli: Option<&[Modulus]>,
     ) -> Result<CrtBundle<Self::Item>, Self::Error> {
         let sign = self.crt_sign(x, accuracy)?;
         output_moduli
@@ -329,14 +359,14 @@ pub trait CrtGadgets:
         &mut self,
         xs: &CrtBundle<Self::Item>,
     ) -> Result<Bundle<Self::Item>, Self::Error> {
-        let gadget_projection_tt = |p: u16, q: u16| -> Vec<u16> {
-            let pq = p as u32 + q as u32 - 1;
+        let gadget_projection_tt = |p: Modulus, q: Modulus| -> Vec<Modulus> {
+            let pq = p as u64 + q as u64 - 1;
             let mut tab = Vec::with_capacity(pq as usize);
             for z in 0..pq {
                 let mut x = 0;
                 let mut y = 0;
-                'outer: for i in 0..p as u32 {
-                    for j in 0..q as u32 {
+                'outer: for i in 0..p as u64 {
+                    for j in 0..q as u64 {
                         if (i + pq - j) % pq == z {
                             x = i;
                             y = j;
@@ -346,10 +376,10 @@ pub trait CrtGadgets:
                 }
                 debug_assert_eq!((x + pq - y) % pq, z);
                 tab.push(
-                    (((x * q as u32 * util::inv(q as i128, p as i128) as u32
-                        + y * p as u32 * util::inv(p as i128, q as i128) as u32)
-                        / p as u32)
-                        % q as u32) as u16,
+                    (((x * q as u64 * util::inv(q as i128, p as i128) as u64
+                        + y * p as u64 * util::inv(p as i128, q as i128) as u64)
+                        / p as u64)
+                        % q as u64) as Modulus,
                 );
             }
             tab
@@ -424,9 +454,7 @@ pub trait CrtGadgets:
         x: &CrtBundle<Self::Item>,
         y: &CrtBundle<Self::Item>,
     ) -> Result<CrtBundle<Self::Item>, Self::Error> {
-        if x.moduli() != y.moduli() {
-            return Err(Self::Error::from(FancyError::UnequalModuli));
-        }
+        check_bundle_moduli("crt_div", &x.moduli(), &y.moduli())?;
 
         let q = x.composite_modulus();
 
@@ -470,13 +498,123 @@ pub trait CrtGadgets:
 
         Ok(quotient)
     }
+
+    ////////////////////////////////////////////////////////////////////////////////
+    // Conversions between the CRT and binary representations.
+
+    /// Convert the unsigned binary bundle `x` to a CRT bundle under composite
+    /// modulus `q`, giving `x mod q`.
+    ///
+    /// This takes one ciphertext for each bit of `x` and odd prime of `q`.
+    /// Fails if `q` is not a product of distinct primes from `util::PRIMES`.
+    fn bin_to_crt(
+        &mut self,
+        x: &BinaryBundle<Self::Item>,
+        q: u128,
+    ) -> Result<CrtBundle<Self::Item>, Self::Error> {
+        let ps = util::factor_checked(q)?;
+        let mut ws = Vec::with_capacity(ps.len());
+        for p in ps {
+            // Bit `i` of `x` is worth `2^i mod p`; mod 2, only the first bit counts.
+            if p == 2 {
+                match x.wires().first() {
+                    Some(w) => ws.push(w.clone()),
+                    None => ws.push(self.constant(0, 2)?),
+                }
+                continue;
+            }
+            let mut z = self.constant(0, p)?;
+            let mut c = 1;
+            for w in x.wires() {
+                let y = self.proj(w, p, Some(vec![0, c]))?;
+                z = self.add(&z, &y)?;
+                c = c * 2 % p;
+            }
+            ws.push(z);
+        }
+        Ok(CrtBundle::new(ws))
+    }
+
+    /// Convert the CRT bundle `x` to an unsigned binary bundle of `nbits`
+    /// bits, giving `x mod 2^nbits`.
+    ///
+    /// Each residue is projected to the bits of its term in the CRT
+    /// reconstruction of `x`, and the terms are added in binary mod `Q`. This
+    /// costs about `(p - 1) log Q` ciphertexts for each prime `p` of `Q`, and
+    /// an addition and a reduction of `log Q`-bit numbers for each prime but
+    /// the first.
+    fn crt_to_bin(
+        &mut self,
+        x: &CrtBundle<Self::Item>,
+        nbits: usize,
+    ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
+        let ps = x.moduli();
+        let q = util::crt_modulus_checked(&ps)?;
+        // The number of bits of values mod `q`.
+        let k = (u128::BITS - (q - 1).leading_zeros()) as usize;
+        let mut acc: Option<BinaryBundle<Self::Item>> = None;
+        for (i, (w, &p)) in x.wires().iter().zip(ps.iter()).enumerate() {
+            // The term of residue `v` is the value with residue `v` mod `p` and
+            // zero mod the other primes.
+            let terms = (0..p)
+                .map(|v| {
+                    let mut residues = vec![0; ps.len()];
+                    residues[i] = v;
+                    util::crt_inv(&residues, &ps)
+                })
+                .collect_vec();
+            let bits = (0..k)
+                .map(|j| {
+                    let tt = terms
+                        .iter()
+                        .map(|t| ((t >> j) & 1) as Modulus)
+                        .collect_vec();
+                    if tt.iter().all(|&b| b == tt[0]) {
+                        self.constant(tt[0], 2)
+                    } else if p == 2 {
+                        self.mux_constant_bits(w, tt[0] == 1, tt[1] == 1)
+                    } else {
+                        self.proj(w, 2, Some(tt))
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map(BinaryBundle::new)?;
+            acc = Some(match acc {
+                None => bits,
+                Some(acc) => {
+                    // The sum is less than `2q`: subtract `q` if it is at
+                    // least `q`, which the carry of adding `2^(k+1) - q` tells.
+                    // Its low `k + 1` bits are those of `2^128 - q`.
+                    let (mut sum, carry) = self.bin_addition(&acc, &bits)?;
+                    sum.push(carry);
+                    let neg_q = self.bin_constant_bundle(q.wrapping_neg(), k + 1)?;
+                    let (diff, geq) = self.bin_addition(&sum, &neg_q)?;
+                    let mut z = self.bin_multiplex(&geq, &sum, &diff)?;
+                    z.pop();
+                    z
+                }
+            });
+        }
+        let mut z = match acc {
+            Some(acc) => acc,
+            None => self.bin_constant_bundle(0, 0)?,
+        };
+        if z.size() > nbits {
+            z = BinaryBundle::new(z.wires()[..nbits].to_vec());
+        } else {
+            let zero = self.constant(0, 2)?;
+            let n = nbits - z.size();
+            z.pad(&zero, n);
+        }
+        Ok(z)
+    }
 }
 
 /// Compute the `ms` needed for the number of CRT primes in `x`, with accuracy
 /// `accuracy`.
 ///
 /// Supported accuracy: ["100%", "99.9%", "99%"]
-fn get_ms<W: Clone + HasModulus>(x: &Bundle<W>, accuracy: &str) -> Vec<u16> {
+fn get_ms<W: Clone + HasModulus>(x: &Bundle<W>, accuracy: &str) -> Vec<Modulus> {
     match accuracy {
         "100%" => match x.moduli().len() {
             3 => vec![2; 5],
@@ -1,5 +1,5 @@
 use super::*;
-use crate::util;
+use crate::{util, Modulus};
 use itertools::Itertools;
 
 /// Convenience functions for encoding input to Fancy objects.
@@ -16,15 +16,16 @@ pub trait FancyInput {
     /// Encode many values where the actual input is known.
     ///
     /// When writing a garbler, the return value must correspond to the zero
-    /// wire label.
+    /// wire label. Implementations return [`FancyError::InputOutOfRange`] for a
+    /// value that is not less than its modulus.
     fn encode_many(
         &mut self,
-        values: &[u16],
-        moduli: &[u16],
+        values: &[Modulus],
+        moduli: &[Modulus],
     ) -> Result<Vec<Self::Item>, Self::Error>;
 
     /// Receive many values where the input is not known.
-    fn receive_many(&mut self, moduli: &[u16]) -> Result<Vec<Self::Item>, Self::Error>;
+    fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<Self::Item>, Self::Error>;
 
     ////////////////////////////////////////////////////////////////////////////////
     // optional methods
@@ -33,13 +34,13 @@ pub trait FancyInput {
     ///
     /// When writing a garbler, the return value must correspond to the zero
     /// wire label.
-    fn encode(&mut self, value: u16, modulus: u16) -> Result<Self::Item, Self::Error> {
+    fn encode(&mut self, value: Modulus, modulus: Modulus) -> Result<Self::Item, Self::Error> {
         let mut xs = self.encode_many(&[value], &[modulus])?;
         Ok(xs.remove(0))
     }
 
     /// Receive a single value.
-    fn receive(&mut self, modulus: u16) -> Result<Self::Item, Self::Error> {
+    fn receive(&mut self, modulus: Modulus) -> Result<Self::Item, Self::Error> {
         let mut xs = self.receive_many(&[modulus])?;
         Ok(xs.remove(0))
     }
@@ -47,22 +48,22 @@ pub trait FancyInput {
     /// Encode a bundle.
     fn encode_bundle(
         &mut self,
-        values: &[u16],
-        moduli: &[u16],
+        values: &[Modulus],
+        moduli: &[Modulus],
     ) -> Result<Bundle<Self::Item>, Self::Error> {
         self.encode_many(values, moduli).map(Bundle::new)
     }
 
     /// Receive a bundle.
-    fn receive_bundle(&mut self, moduli: &[u16]) -> Result<Bundle<Self::Item>, Self::Error> {
+    fn receive_bundle(&mut self, moduli: &[Modulus]) -> Result<Bundle<Self::Item>, Self::Error> {
         self.receive_many(moduli).map(Bundle::new)
     }
 
     /// Encode many input bundles.
     fn encode_bundles(
         &mut self,
-        values: &[Vec<u16>],
-        moduli: &[Vec<u16>],
+        values: &[Vec<Modulus>],
+        moduli: &[Vec<Modulus>],
     ) -> Result<Vec<Bundle<Self::Item>>, Self::Error> {
         let qs = moduli.iter().flatten().cloned().collect_vec();
         let xs = values.iter().flatten().cloned().collect_vec();
@@ -85,7 +86,7 @@ pub trait FancyInput {
     /// Receive many input bundles.
     fn receive_many_bundles(
         &mut self,
-        moduli: &[Vec<u16>],
+        moduli: &[Vec<Modulus>],
     ) -> Result<Vec<Bundle<Self::Item>>, Self::Error> {
         let qs = moduli.iter().flatten().cloned().collect_vec();
         let mut wires = self.receive_many(&qs)?;
@@ -100,35 +101,39 @@ pub trait FancyInput {
     }
 
     /// Encode a CRT input bundle.
+    ///
+    /// Fails if `modulus` is not a product of distinct primes from
+    /// `util::PRIMES`, or `value` is not less than it.
     fn crt_encode(
         &mut self,
         value: u128,
         modulus: u128,
     ) -> Result<CrtBundle<Self::Item>, Self::Error> {
-        let qs = util::factor(modulus);
-        let xs = util::crt(value, &qs);
+        let qs = util::factor_checked(modulus)?;
+        let xs = util::crt_checked(value, &qs)?;
         self.encode_bundle(&xs, &qs).map(CrtBundle::from)
     }
 
     /// Receive an CRT input bundle.
     fn crt_receive(&mut self, modulus: u128) -> Result<CrtBundle<Self::Item>, Self::Error> {
-        let qs = util::factor(modulus);
+        let qs = util::factor_checked(modulus)?;
         self.receive_bundle(&qs).map(CrtBundle::from)
     }
 
     /// Encode many CRT input bundles.
+    ///
+    /// Fails as [`crt_encode`](FancyInput::crt_encode) does.
     fn crt_encode_many(
         &mut self,
         values: &[u128],
         modulus: u128,
     ) -> Result<Vec<CrtBundle<Self::Item>>, Self::Error> {
-        let mods = util::factor(modulus);
+        let mods = util::factor_checked(modulus)?;
         let nmods = mods.len();
-        let xs = values
-            .iter()
-            .map(|x| util::crt(*x, &mods))
-            .flatten()
-            .collect_vec();
+        let mut xs = Vec::with_capacity(values.len() * nmods);
+        for &x in values.iter() {
+            xs.extend(util::crt_checked(x, &mods)?);
+        }
         let qs = itertools::repeat_n(mods, values.len())
             .flatten()
             .collect_vec();
@@ -148,7 +153,7 @@ pub trait FancyInput {
         n: usize,
         modulus: u128,
     ) -> Result<Vec<CrtBundle<Self::Item>>, Self::Error> {
-        let mods = util::factor(modulus);
+        let mods = util::factor_checked(modulus)?;
         let nmods = mods.len();
         let qs = itertools::repeat_n(mods, n).flatten().collect_vec();
         let mut wires = self.receive_many(&qs)?;
@@ -214,3 +219,24 @@ pub trait FancyInput {
         Ok(buns)
     }
 }
+
+/// Check that each of `values` is less than the corresponding modulus, naming
+/// the first that is not.
+///
+/// This is done once, where inputs are encoded, so that gates can assume their
+/// inputs are reduced.
+pub(crate) fn check_inputs(
+    values: &[Modulus],
+    moduli: impl IntoIterator<Item = Modulus>,
+) -> Result<(), FancyError> {
+    for (index, (&value, modulus)) in values.iter().zip(moduli).enumerate() {
+        if value >= modulus {
+            return Err(FancyError::InputOutOfRange {
+                index,
+                value,
+                modulus,
+            });
+        }
+    }
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use super::*;
-use crate::util;
+use crate::{util, Modulus};
 
 /// Trait to describe Fancy objects which can reveal outputs to both parties. For many
 /// simple Fancy objects in this library such as Dummy, this is simply output. For Garbler
@@ -7,10 +7,10 @@ use crate::util;
 /// Evaluator only.
 pub trait FancyReveal: Fancy {
     /// Reveal the contents of `x` to all parties.
-    fn reveal(&mut self, x: &Self::Item) -> Result<u16, Self::Error>;
+    fn reveal(&mut self, x: &Self::Item) -> Result<Modulus, Self::Error>;
 
     /// Reveal a slice of items to all parties.
-    fn reveal_many(&mut self, xs: &[Self::Item]) -> Result<Vec<u16>, Self::Error> {
+    fn reveal_many(&mut self, xs: &[Self::Item]) -> Result<Vec<Modulus>, Self::Error> {
         let mut zs = Vec::with_capacity(xs.len());
         for x in xs.iter() {
             zs.push(self.reveal(x)?);
@@ -19,7 +19,7 @@ pub trait FancyReveal: Fancy {
     }
 
     /// Reveal a bundle to all parties.
-    fn reveal_bundle(&mut self, x: &Bundle<Self::Item>) -> Result<Vec<u16>, Self::Error> {
+    fn reveal_bundle(&mut self, x: &Bundle<Self::Item>) -> Result<Vec<Modulus>, Self::Error> {
         self.reveal_many(x.wires())
     }
 
@@ -27,7 +27,7 @@ pub trait FancyReveal: Fancy {
     fn reveal_many_bundles(
         &mut self,
         xs: &[Bundle<Self::Item>],
-    ) -> Result<Vec<Vec<u16>>, Self::Error> {
+    ) -> Result<Vec<Vec<Modulus>>, Self::Error> {
         let mut zs = Vec::with_capacity(xs.len());
         for x in xs.iter() {
             zs.push(self.reveal_bundle(x)?);
@@ -36,10 +36,14 @@ pub trait FancyReveal: Fancy {
     }
 
     /// Reveal a CRT bundle to all parties.
+    ///
+    /// Fails if the bundle's moduli are not pairwise coprime, or their product
+    /// overflows a `u128`.
     fn crt_reveal(&mut self, x: &CrtBundle<Self::Item>) -> Result<u128, Self::Error> {
-        let q = x.composite_modulus();
+        let ps = x.moduli();
+        util::crt_modulus_checked(&ps)?;
         let xs = self.reveal_many(x.wires())?;
-        Ok(util::crt_inv_factor(&xs, q))
+        Ok(util::crt_inv(&xs, &ps))
     }
 
     /// Reveal many CRT bundles to all parties.
@@ -57,6 +61,31 @@ pub trait FancyReveal: Fancy {
         Ok(util::u128_from_bits(&bits))
     }
 
+    /// Reveal the bits of `x` where `mask` is `true` to all parties, keeping
+    /// the others hidden, least significant bit first.
+    ///
+    /// Hidden bits are `None`, and are never output, so no decoding
+    /// information is sent or kept for them: revealing the top 8 bits of a
+    /// 64-bit bundle costs what revealing an 8-bit bundle does. Fails with
+    /// [`FancyError::InvalidArgNum`] if `mask` is not as long as `x`.
+    fn reveal_masked(
+        &mut self,
+        x: &BinaryBundle<Self::Item>,
+        mask: &[bool],
+    ) -> Result<Vec<Option<Modulus>>, Self::Error> {
+        if mask.len() != x.size() {
+            return Err(Self::Error::from(FancyError::InvalidArgNum {
+                got: mask.len(),
+                needed: x.size(),
+            }));
+        }
+        x.wires()
+            .iter()
+            .zip(mask)
+            .map(|(w, &shown)| shown.then(|| self.reveal(w)).transpose())
+            .collect()
+    }
+
     /// Reveal many binary bundles to all parties.
     fn bin_reveal_many(
         &mut self,
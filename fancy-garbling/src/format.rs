@@ -0,0 +1,414 @@
+//! Framing of the artifacts this crate persists.
+//!
+//! Garbled circuits, encoders, and circuits are written as a 16-byte header
+//! followed by a payload:
+//!
+//! | Bytes     | Field                                        |
+//! |-----------|----------------------------------------------|
+//! | `0..4`    | The magic bytes [`MAGIC`], `FNCY`.           |
+//! | `4..6`    | The [`FormatId`], a little-endian `u16`.     |
+//! | `6..8`    | The format version, a little-endian `u16`.   |
+//! | `8..16`   | The payload length, a little-endian `u64`.   |
+//!
+//! Readers check the header before they look at the payload, so that a file
+//! of another kind, or of a version this build does not read, is reported as
+//! such (e.g., "this is a garbled-circuit file v3 but this build only reads
+//! v1") rather than as a payload that fails to decode. Each format's version
+//! is bumped whenever its payload changes, and a new kind of artifact gets a
+//! new [`FormatId`]. As the payload length is in the header, frames can be
+//! concatenated and read back with [`split`].
+//!
+//! The payloads of circuits and encoders are `bincode` encodings, which need
+//! the `serde` feature. They contain moduli and wire values, whose size
+//! depends on the `wide-moduli` feature, so they are only readable by builds
+//! that agree on it.
+
+use crate::errors::FormatError;
+use std::{fmt, ops::RangeInclusive};
+
+/// The bytes at the start of every framed artifact.
+pub const MAGIC: [u8; 4] = *b"FNCY";
+
+/// The length of the header preceding the payload.
+pub const HEADER_LEN: usize = 16;
+
+/// The kinds of framed artifact.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[repr(u16)]
+pub enum FormatId {
+    /// A [`BinaryCircuit`](crate::circuit::BinaryCircuit).
+    BinaryCircuit = 1,
+    /// An [`ArithmeticCircuit`](crate::circuit::ArithmeticCircuit).
+    ArithmeticCircuit = 2,
+    /// A [`GarbledCircuit`](crate::classic::GarbledCircuit).
+    GarbledCircuit = 3,
+    /// An [`Encoder`](crate::classic::Encoder).
+    Encoder = 4,
+}
+
+impl FormatId {
+    /// Every format, in order of id.
+    pub const ALL: [FormatId; 4] = [
+        FormatId::BinaryCircuit,
+        FormatId::ArithmeticCircuit,
+        FormatId::GarbledCircuit,
+        FormatId::Encoder,
+    ];
+
+    /// The format with the given id, if there is one.
+    pub fn from_u16(id: u16) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|format| *format as u16 == id)
+    }
+}
+
+impl fmt::Display for FormatId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatId::BinaryCircuit => "binary-circuit",
+            FormatId::ArithmeticCircuit => "arithmetic-circuit",
+            FormatId::GarbledCircuit => "garbled-circuit",
+            FormatId::Encoder => "encoder",
+        }
+        .fmt(f)
+    }
+}
+
+/// The header of a framed artifact.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Header {
+    /// The kind of artifact.
+    pub format: FormatId,
+    /// The version of its format.
+    pub version: u16,
+    /// The length of its payload in bytes.
+    pub len: u64,
+}
+
+impl Header {
+    /// Encode the header.
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0; HEADER_LEN];
+        bytes[..4].copy_from_slice(&MAGIC);
+        bytes[4..6].copy_from_slice(&(self.format as u16).to_le_bytes());
+        bytes[6..8].copy_from_slice(&self.version.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.len.to_le_bytes());
+        bytes
+    }
+}
+
+/// Frame `payload` as version `version` of `format`.
+pub fn encode(format: FormatId, version: u16, payload: &[u8]) -> Vec<u8> {
+    let header = Header {
+        format,
+        version,
+        len: payload.len() as u64,
+    };
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(&header.to_bytes());
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+/// Read the frame at the start of `bytes`, returning its header, its payload,
+/// and the bytes following it.
+pub fn split(bytes: &[u8]) -> Result<(Header, &[u8], &[u8]), FormatError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(FormatError::Truncated(bytes.len()));
+    }
+    let (header, rest) = bytes.split_at(HEADER_LEN);
+    let mut magic = [0; 4];
+    magic.copy_from_slice(&header[..4]);
+    if magic != MAGIC {
+        return Err(FormatError::BadMagic(magic));
+    }
+    let id = u16::from_le_bytes([header[4], header[5]]);
+    let format = FormatId::from_u16(id).ok_or(FormatError::UnknownFormat(id))?;
+    let version = u16::from_le_bytes([header[6], header[7]]);
+    let mut len = [0; 8];
+    len.copy_from_slice(&header[8..]);
+    let len = u64::from_le_bytes(len);
+    if (rest.len() as u64) < len {
+        return Err(FormatError::LengthMismatch {
+            expected: len,
+            got: rest.len(),
+        });
+    }
+    let (payload, rest) = rest.split_at(len as usize);
+    let header = Header {
+        format,
+        version,
+        len,
+    };
+    Ok((header, payload, rest))
+}
+
+/// Check that `bytes` is a single frame of `format`, of one of the versions
+/// in `versions`, and return its version and payload.
+pub fn decode(
+    bytes: &[u8],
+    format: FormatId,
+    versions: RangeInclusive<u16>,
+) -> Result<(u16, &[u8]), FormatError> {
+    let (header, payload, rest) = split(bytes)?;
+    if header.format != format {
+        return Err(FormatError::WrongFormat {
+            expected: format,
+            found: header.format,
+        });
+    }
+    if !versions.contains(&header.version) {
+        return Err(FormatError::UnsupportedVersion {
+            format,
+            found: header.version,
+            oldest: *versions.start(),
+            newest: *versions.end(),
+        });
+    }
+    if !rest.is_empty() {
+        return Err(FormatError::LengthMismatch {
+            expected: header.len,
+            got: bytes.len() - HEADER_LEN,
+        });
+    }
+    Ok((header.version, payload))
+}
+
+/// Frame the `bincode` encoding of `value` as version `version` of `format`.
+#[cfg(feature = "serde")]
+pub(crate) fn serialize<T: serde::Serialize>(format: FormatId, version: u16, value: &T) -> Vec<u8> {
+    let payload = bincode::serialize(value).expect("serializing to memory cannot fail");
+    encode(format, version, &payload)
+}
+
+/// Decode a frame written by [`serialize`].
+#[cfg(feature = "serde")]
+pub(crate) fn deserialize<T: serde::de::DeserializeOwned>(
+    bytes: &[u8],
+    format: FormatId,
+    versions: RangeInclusive<u16>,
+) -> Result<T, FormatError> {
+    let (_, payload) = decode(bytes, format, versions)?;
+    deserialize_payload(payload, format)
+}
+
+/// Decode the payload of a frame of `format` written by [`serialize`].
+#[cfg(feature = "serde")]
+pub(crate) fn deserialize_payload<T: serde::de::DeserializeOwned>(
+    payload: &[u8],
+    format: FormatId,
+) -> Result<T, FormatError> {
+    use bincode::Options;
+
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_limit(payload.len() as u64)
+        .reject_trailing_bytes()
+        .deserialize(payload)
+        .map_err(|e| FormatError::InvalidPayload {
+            format,
+            reason: e.to_string(),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header() {
+        let a = encode(FormatId::Encoder, 3, b"payload");
+        let b = encode(FormatId::GarbledCircuit, 1, b"");
+        assert_eq!(&a[..8], b"FNCY\x04\x00\x03\x00");
+        assert_eq!(a.len(), HEADER_LEN + 7);
+
+        // Frames can be concatenated.
+        let both = [a.clone(), b].concat();
+        let (header, payload, rest) = split(&both).unwrap();
+        assert_eq!(
+            header,
+            Header {
+                format: FormatId::Encoder,
+                version: 3,
+                len: 7
+            }
+        );
+        assert_eq!(payload, b"payload");
+        assert_eq!(
+            decode(rest, FormatId::GarbledCircuit, 1..=1).unwrap(),
+            (1, &b""[..])
+        );
+        assert!(matches!(
+            decode(&both, FormatId::Encoder, 1..=3),
+            Err(FormatError::LengthMismatch {
+                expected: 7,
+                got: 23
+            })
+        ));
+
+        assert!(matches!(
+            decode(&a[..15], FormatId::Encoder, 3..=3),
+            Err(FormatError::Truncated(15))
+        ));
+        assert!(matches!(
+            decode(&a[..a.len() - 1], FormatId::Encoder, 3..=3),
+            Err(FormatError::LengthMismatch {
+                expected: 7,
+                got: 6
+            })
+        ));
+        let mut bad = a.clone();
+        bad[0] = b'f';
+        assert!(matches!(
+            decode(&bad, FormatId::Encoder, 3..=3),
+            Err(FormatError::BadMagic(magic)) if &magic == b"fNCY"
+        ));
+        let mut bad = a;
+        bad[4] = 0xff;
+        assert!(matches!(
+            decode(&bad, FormatId::Encoder, 3..=3),
+            Err(FormatError::UnknownFormat(0xff))
+        ));
+    }
+
+    #[test]
+    fn version_messages() {
+        let bytes = encode(FormatId::GarbledCircuit, 3, &[]);
+        let e = decode(&bytes, FormatId::GarbledCircuit, 2..=2).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "this is a garbled-circuit file v3 but this build only reads v2"
+        );
+        let e = decode(&bytes, FormatId::GarbledCircuit, 4..=5).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "this is a garbled-circuit file v3 but this build only reads v4 to v5"
+        );
+        let e = decode(&bytes, FormatId::BinaryCircuit, 3..=3).unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "this is a garbled-circuit file but a binary-circuit file was expected"
+        );
+    }
+
+    /// Feed each loader the files of every format, and of other versions of
+    /// its own.
+    #[cfg(feature = "serde")]
+    #[test]
+    fn compatibility() {
+        use crate::{
+            circuit::{ArithmeticCircuit, BinaryCircuit, CircuitBuilder},
+            classic::{garble, Encoder, GarbledCircuit},
+            errors::GarbledCircuitDecodingError,
+            Fancy, FancyArithmetic, FancyBinary, WireMod2,
+        };
+
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.garbler_input(2);
+        let y = b.evaluator_input(2);
+        let z = b.and(&x, &y).unwrap();
+        b.output(&z).unwrap();
+        let binary = b.finish();
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(5);
+        let y = b.evaluator_input(5);
+        let z = b.mul(&x, &y).unwrap();
+        b.output(&z).unwrap();
+        let arithmetic = b.finish();
+        let (encoder, gc) = garble::<WireMod2, _>(&binary).unwrap();
+
+        type Loader = fn(&[u8]) -> Result<(), FormatError>;
+        let loaders: [(FormatId, Loader); 4] = [
+            (FormatId::BinaryCircuit, |bytes| {
+                BinaryCircuit::from_bytes(bytes).map(drop)
+            }),
+            (FormatId::ArithmeticCircuit, |bytes| {
+                ArithmeticCircuit::from_bytes(bytes).map(drop)
+            }),
+            (FormatId::GarbledCircuit, |bytes| {
+                match GarbledCircuit::<WireMod2, BinaryCircuit>::from_bytes(bytes) {
+                    Ok(_) => Ok(()),
+                    Err(GarbledCircuitDecodingError::Format(e)) => Err(e),
+                    // The payload is decoded outside the framing.
+                    Err(e) => Err(FormatError::InvalidPayload {
+                        format: FormatId::GarbledCircuit,
+                        reason: e.to_string(),
+                    }),
+                }
+            }),
+            (FormatId::Encoder, |bytes| {
+                Encoder::<WireMod2>::from_bytes(bytes).map(drop)
+            }),
+        ];
+        let files = [
+            binary.to_bytes(),
+            arithmetic.to_bytes(),
+            gc.to_bytes(),
+            encoder.to_bytes(),
+        ];
+        assert_eq!(BinaryCircuit::from_bytes(&files[0]).unwrap(), binary);
+        assert_eq!(
+            ArithmeticCircuit::from_bytes(&files[1]).unwrap(),
+            arithmetic
+        );
+
+        for (expected, load) in loaders.iter() {
+            for file in files.iter() {
+                let (header, _, _) = split(file).unwrap();
+                match load(file) {
+                    Ok(()) => assert_eq!(header.format, *expected),
+                    Err(FormatError::WrongFormat { expected: e, found }) => {
+                        assert_eq!(e, *expected);
+                        assert_eq!(found, header.format);
+                        assert_ne!(found, *expected);
+                    }
+                    Err(e) => panic!("{} loader: unexpected error: {}", expected, e),
+                }
+            }
+
+            let file = &files[*expected as usize - 1];
+            // Each loader reads the version it writes, circuits also read
+            // version 1, which lacked output groups, and garbled circuits
+            // version 2, which lacked the security parameters.
+            let (current, _) = decode(file, *expected, 0..=u16::MAX).unwrap();
+            let oldest = match expected {
+                FormatId::BinaryCircuit | FormatId::ArithmeticCircuit => 1,
+                FormatId::GarbledCircuit => 2,
+                _ => current,
+            };
+            for version in [oldest - 1, current + 1, u16::MAX] {
+                let mut file = file.clone();
+                file[6..8].copy_from_slice(&u16::to_le_bytes(version));
+                assert!(
+                    matches!(
+                        load(&file),
+                        Err(FormatError::UnsupportedVersion {
+                            format,
+                            found,
+                            oldest: oldest_,
+                            newest,
+                        }) if format == *expected
+                            && found == version
+                            && oldest_ == oldest
+                            && newest == current
+                    ),
+                    "{} loader, version {}",
+                    expected,
+                    version
+                );
+            }
+
+            // A valid header around a payload that does not decode.
+            let mut file = file.clone();
+            file.truncate(HEADER_LEN + 4);
+            file[8..16].copy_from_slice(&4u64.to_le_bytes());
+            assert!(matches!(
+                load(&file),
+                Err(FormatError::InvalidPayload { format, .. }) if format == *expected
+            ));
+        }
+    }
+}
@@ -1,9 +1,66 @@
 //! Structs and functions for creating, streaming, and evaluating garbled circuits.
+//!
+//! # Binary wire format
+//!
+//! Binary gates, on `WireMod2` or on the `Mod2` case of `AllWire`, are garbled
+//! with the half-gates scheme of Zahur, Rosulek, and Evans. An implementation
+//! following this section can evaluate what [`Garbler`] sends, or garble for
+//! [`Evaluator`]; the `interop` tests pin it down with frozen vectors.
+//!
+//! **Labels.** A label is 16 bytes, sent as is. Its color is the least
+//! significant bit of its first byte. The garbler picks a global offset `Δ` of
+//! color 1 and, for each wire, a uniformly random zero label `W`; the label of
+//! the value `v` is `W ⊕ v·Δ`.
+//!
+//! **Hash.** `H(x, t) = π(π(x) ⊕ t) ⊕ π(x)`, where `π` is AES-128 under the
+//! fixed key `bd2400c11241ce33ed3d7dc7a8564025` (in hex, in byte order), and
+//! the tweak `t` is a little-endian `u128`. The `j`-th AND gate uses the tweak
+//! `j << 64`, and the `k`-th ciphertext of the `j`-th output uses
+//! `(j << 64) | k`, where gates and outputs are counted separately from 0.
+//! The `blake3-hash` feature replaces this hash with BLAKE3, and so changes
+//! the format.
+//!
+//! **Gates.** XOR is free: `C = A ⊕ B` for both parties. NOT is free too: the
+//! garbler sets `C = A ⊕ Δ` and the evaluator keeps its label. A constant `v`
+//! is sent as the label `W ⊕ v·Δ` of a fresh wire. An AND gate sends two
+//! ciphertexts, `G0` then `G1`. With `H(x)` for `H(x, j << 64)`, `pa` and `pb`
+//! for the colors of `A` and `B`, and `Ac` for the label of `A` with color `c`,
+//! the garbler computes
+//!
+//! ```text
+//! G0 = H(A0) ⊕ H(A1) ⊕ pb·Δ
+//! G1 = H(B0) ⊕ H(B1) ⊕ A
+//! C  = H(A0) ⊕ H(B0) ⊕ pa·pb·Δ
+//! ```
+//!
+//! and the evaluator, holding `X` and `Y` of colors `sx` and `sy`, computes
+//!
+//! ```text
+//! Z = H(X) ⊕ sx·G0 ⊕ H(Y) ⊕ sy·(G1 ⊕ X)
+//! ```
+//!
+//! **Custom gates.** A [`CustomGate`](crate::circuit::CustomGate) is numbered
+//! along with the AND gates, so that a custom gate between the `j`-th and
+//! the next AND gate is the `j + 1`-th gate, and sends the ciphertexts its
+//! `garble` returns for that number, in order.
+//!
+//! **Outputs.** The `j`-th output, of a wire with zero label `W`, sends
+//! `H(W, j << 64)` then `H(W ⊕ Δ, (j << 64) | 1)`. The evaluator decodes its
+//! label `X` as the `k` for which `H(X, (j << 64) | k)` is the `k`-th of these.
+//!
+//! **Order.** Blocks are sent in the order the circuit calls `constant`,
+//! `and`, `custom`, and `output`. Input labels are not part of this stream:
+//! the evaluator gets them from the caller, e.g. through
+//! [`Garbler::send_wire`] or oblivious transfer.
 
 mod evaluator;
 mod garbler;
 
 pub use crate::garble::{evaluator::Evaluator, garbler::Garbler};
+pub(crate) use crate::garble::{
+    evaluator::{decode_output, evaluate_and_gate, evaluate_and_gates},
+    garbler::{garble_and_gate, garble_and_gates, truncate},
+};
 
 ////////////////////////////////////////////////////////////////////////////////
 // tests
@@ -15,7 +72,7 @@ mod nonstreaming {
         classic::garble,
         fancy::{ArithmeticBundleGadgets, Bundle, BundleGadgets, Fancy},
         util::{self, RngExt},
-        AllWire, FancyArithmetic, FancyBinary,
+        AllWire, FancyArithmetic, FancyBinary, Modulus,
     };
     use itertools::Itertools;
     use rand::{thread_rng, SeedableRng};
@@ -24,7 +81,7 @@ mod nonstreaming {
     // helper
     fn garble_test_helper<F>(f: F)
     where
-        F: Fn(u16) -> ArithmeticCircuit,
+        F: Fn(Modulus) -> ArithmeticCircuit,
     {
         let mut rng = thread_rng();
         for _ in 0..16 {
@@ -35,12 +92,14 @@ mod nonstreaming {
                 let mut inps = Vec::new();
                 for i in 0..c.num_evaluator_inputs() {
                     let q = c.evaluator_input_mod(i);
-                    let x = rng.gen_u16() % q;
+                    let x = rng.gen_residue(q);
                     inps.push(x);
                 }
                 // Run the garbled circuit evaluator.
-                let xs = &en.encode_evaluator_inputs(&inps);
-                let decoded = &ev.eval(c, &[], xs).unwrap();
+                let xs = &en.encode_evaluator_inputs(&inps).unwrap();
+                let decoded = &ev
+                    .eval(c, &en.encode_garbler_inputs(&[]).unwrap(), xs)
+                    .unwrap();
 
                 // Run the dummy evaluator.
                 let should_be = eval_plain(c, &[], &inps).unwrap();
@@ -132,7 +191,7 @@ mod nonstreaming {
             let mut rng = thread_rng();
             let mut tab = Vec::new();
             for _ in 0..q {
-                tab.push(rng.gen_u16() % q);
+                tab.push(rng.gen_residue(q));
             }
             let mut b = CircuitBuilder::new();
             let x = b.evaluator_input(q);
@@ -142,6 +201,77 @@ mod nonstreaming {
         });
     }
 
+    #[test]
+    fn proj_shared_tables() {
+        // Projections reusing a handful of truth tables, as a CRT circuit
+        // does, each garbled with its own tweak.
+        let mut rng = thread_rng();
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let xs = b.evaluator_inputs(&[3, 5, 7, 11]);
+        let mut outputs = vec![];
+        for x in xs.iter() {
+            let q = x.modulus;
+            let tt = (0..q).map(|_| rng.gen_residue(q)).collect_vec();
+            let mut z = *x;
+            for _ in 0..8 {
+                z = b.proj(&z, q, Some(tt.clone())).unwrap();
+                outputs.push(b.proj(x, q, Some(tt.clone())).unwrap());
+                outputs.push(
+                    b.proj(x, 2, Some(tt.iter().map(|y| y % 2).collect()))
+                        .unwrap(),
+                );
+            }
+            outputs.push(z);
+        }
+        b.outputs(&outputs).unwrap();
+        let c = b.finish();
+        let (en, gc) = garble::<AllWire, _>(&c).unwrap();
+        let gb = en.encode_garbler_inputs(&[]).unwrap();
+        for _ in 0..16 {
+            let inps = [3, 5, 7, 11].map(|q| rng.gen_residue(q));
+            let ev = en.encode_evaluator_inputs(&inps).unwrap();
+            assert_eq!(
+                gc.eval(&c, &gb, &ev).unwrap(),
+                eval_plain(&c, &[], &inps).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn batched_and_gates() {
+        use crate::{
+            garble::{evaluate_and_gate, evaluate_and_gates, garble_and_gate, garble_and_gates},
+            WireLabel, WireMod2,
+        };
+
+        let mut rng = thread_rng();
+        let delta = WireMod2::rand_delta(&mut rng, 2);
+        let wires = (0..8).map(|_| WireMod2::rand(&mut rng, 2)).collect_vec();
+        let nums = [3, 17, 17, 1 << 40];
+        let garbled = [0, 1, 2, 3]
+            .map(|k| garble_and_gate(&wires[2 * k], &wires[2 * k + 1], &delta, nums[k]));
+        let pairs = [0, 2].map(|k| {
+            garble_and_gates(
+                [
+                    (&wires[2 * k], &wires[2 * k + 1], nums[k]),
+                    (&wires[2 * k + 2], &wires[2 * k + 3], nums[k + 1]),
+                ],
+                &delta,
+            )
+        });
+        assert_eq!(pairs.concat(), garbled);
+
+        let evaluated = [0, 1, 2, 3].map(|k| {
+            let (gate0, gate1, _) = &garbled[k];
+            evaluate_and_gate(&wires[2 * k], &wires[2 * k + 1], gate0, gate1, nums[k])
+        });
+        let batch = evaluate_and_gates(std::array::from_fn(|k| {
+            let (gate0, gate1, _) = &garbled[k];
+            (&wires[2 * k], &wires[2 * k + 1], gate0, gate1, nums[k])
+        }));
+        assert_eq!(batch, evaluated);
+    }
+
     #[test] // mod_change
     fn mod_change() {
         garble_test_helper(|q| {
@@ -169,7 +299,7 @@ mod nonstreaming {
     fn half_gate_unequal_mods() {
         let mut rng = AesRng::from_seed(Block::from(0 as u128));
         for q in 3..16 {
-            let ymod = 2 + rng.gen_u16() % 6; // lower mod is capped at 8 for now
+            let ymod = 2 + rng.gen_residue(6); // lower mod is capped at 8 for now
             println!("\nTESTING MOD q={} ymod={}", q, ymod);
 
             let mut b = CircuitBuilder::new();
@@ -184,8 +314,10 @@ mod nonstreaming {
             for x in 0..q {
                 for y in 0..ymod {
                     println!("TEST x={} y={}", x, y);
-                    let xs = &en.encode_evaluator_inputs(&[x, y]);
-                    let decoded = &ev.eval(&mut c, &[], xs).unwrap();
+                    let xs = &en.encode_evaluator_inputs(&[x, y]).unwrap();
+                    let decoded = &ev
+                        .eval(&mut c, &en.encode_garbler_inputs(&[]).unwrap(), xs)
+                        .unwrap();
                     let should_be = eval_plain(&c, &[], &[x, y]).unwrap();
                     assert_eq!(decoded[0], should_be[0]);
                 }
@@ -222,8 +354,10 @@ mod nonstreaming {
                 should_be = (should_be + x) % Q;
                 ds.extend(util::as_mixed_radix(x, &mods).iter());
             }
-            let X = en.encode_evaluator_inputs(&ds);
-            let outputs = ev.eval(&mut circ, &[], &X).unwrap();
+            let X = en.encode_evaluator_inputs(&ds).unwrap();
+            let outputs = ev
+                .eval(&mut circ, &en.encode_garbler_inputs(&[]).unwrap(), &X)
+                .unwrap();
             assert_eq!(util::from_mixed_radix(&outputs, &mods), should_be);
         }
     }
@@ -234,18 +368,24 @@ mod nonstreaming {
         let mut rng = thread_rng();
 
         let q = rng.gen_modulus();
-        let c = rng.gen_u16() % q;
+        let c = rng.gen_residue(q);
 
         let y = b.constant(c, q).unwrap();
         b.output(&y).unwrap();
 
         let mut circ: ArithmeticCircuit = b.finish();
-        let (_, ev) = garble::<AllWire, _>(&mut circ).unwrap();
+        let (en, ev) = garble::<AllWire, _>(&mut circ).unwrap();
 
         for _ in 0..64 {
             let outputs = eval_plain(&circ, &[], &[]).unwrap();
             assert_eq!(outputs[0], c, "plaintext eval failed");
-            let outputs = ev.eval(&mut circ, &[], &[]).unwrap();
+            let outputs = ev
+                .eval(
+                    &mut circ,
+                    &en.encode_garbler_inputs(&[]).unwrap(),
+                    &en.encode_evaluator_inputs(&[]).unwrap(),
+                )
+                .unwrap();
             assert_eq!(outputs[0], c, "garbled eval failed");
         }
     }
@@ -256,7 +396,7 @@ mod nonstreaming {
         let mut rng = thread_rng();
 
         let q = rng.gen_modulus();
-        let c = rng.gen_u16() % q;
+        let c = rng.gen_residue(q);
 
         let x = b.evaluator_input(q);
         let y = b.constant(c, q).unwrap();
@@ -267,12 +407,14 @@ mod nonstreaming {
         let (en, ev) = garble::<AllWire, _>(&mut circ).unwrap();
 
         for _ in 0..64 {
-            let x = rng.gen_u16() % q;
+            let x = rng.gen_residue(q);
             let outputs = eval_plain(&circ, &[], &[x]).unwrap();
             assert_eq!(outputs[0], (x + c) % q, "plaintext");
 
-            let X = en.encode_evaluator_inputs(&[x]);
-            let Y = ev.eval(&mut circ, &[], &X).unwrap();
+            let X = en.encode_evaluator_inputs(&[x]).unwrap();
+            let Y = ev
+                .eval(&mut circ, &en.encode_garbler_inputs(&[]).unwrap(), &X)
+                .unwrap();
             assert_eq!(Y[0], (x + c) % q, "garbled");
         }
     }
@@ -283,7 +425,7 @@ mod streaming {
     use crate::{
         dummy::{Dummy, DummyVal},
         util::RngExt,
-        AllWire, Evaluator, FancyArithmetic, FancyInput, Garbler, WireLabel,
+        AllWire, Evaluator, FancyArithmetic, FancyInput, Garbler, Modulus, WireLabel,
     };
     use itertools::Itertools;
     use rand::thread_rng;
@@ -295,18 +437,18 @@ mod streaming {
         mut f_gb: FGB,
         mut f_ev: FEV,
         mut f_du: FDU,
-        input_mods: &[u16],
+        input_mods: &[Modulus],
     ) where
         Wire: WireLabel,
-        FGB: FnMut(&mut Garbler<UnixChannel, AesRng, Wire>, &[Wire]) -> Option<u16>
+        FGB: FnMut(&mut Garbler<UnixChannel, AesRng, Wire>, &[Wire]) -> Option<Modulus>
             + Send
             + Sync
             + 'static,
-        FEV: FnMut(&mut Evaluator<UnixChannel, Wire>, &[Wire]) -> Option<u16>,
-        FDU: FnMut(&mut Dummy, &[DummyVal]) -> Option<u16>,
+        FEV: FnMut(&mut Evaluator<UnixChannel, Wire>, &[Wire]) -> Option<Modulus>,
+        FDU: FnMut(&mut Dummy, &[DummyVal]) -> Option<Modulus>,
     {
         let mut rng = AesRng::new();
-        let inputs = input_mods.iter().map(|q| rng.gen_u16() % q).collect_vec();
+        let inputs = input_mods.iter().map(|&q| rng.gen_residue(q)).collect_vec();
 
         // evaluate f_gb as a dummy
         let mut dummy = Dummy::new();
@@ -337,7 +479,7 @@ mod streaming {
 
     #[test]
     fn addition() {
-        fn fancy_addition<F: FancyArithmetic>(b: &mut F, xs: &[F::Item]) -> Option<u16> {
+        fn fancy_addition<F: FancyArithmetic>(b: &mut F, xs: &[F::Item]) -> Option<Modulus> {
             let z = b.add(&xs[0], &xs[1]).unwrap();
             b.output(&z).unwrap()
         }
@@ -356,7 +498,7 @@ mod streaming {
 
     #[test]
     fn subtraction() {
-        fn fancy_subtraction<F: FancyArithmetic>(b: &mut F, xs: &[F::Item]) -> Option<u16> {
+        fn fancy_subtraction<F: FancyArithmetic>(b: &mut F, xs: &[F::Item]) -> Option<Modulus> {
             let z = b.sub(&xs[0], &xs[1]).unwrap();
             b.output(&z).unwrap()
         }
@@ -375,7 +517,7 @@ mod streaming {
 
     #[test]
     fn multiplication() {
-        fn fancy_multiplication<F: FancyArithmetic>(b: &mut F, xs: &[F::Item]) -> Option<u16> {
+        fn fancy_multiplication<F: FancyArithmetic>(b: &mut F, xs: &[F::Item]) -> Option<Modulus> {
             let z = b.mul(&xs[0], &xs[1]).unwrap();
             b.output(&z).unwrap()
         }
@@ -394,7 +536,7 @@ mod streaming {
 
     #[test]
     fn cmul() {
-        fn fancy_cmul<F: FancyArithmetic>(b: &mut F, xs: &[F::Item]) -> Option<u16> {
+        fn fancy_cmul<F: FancyArithmetic>(b: &mut F, xs: &[F::Item]) -> Option<Modulus> {
             let z = b.cmul(&xs[0], 5).unwrap();
             b.output(&z).unwrap()
         }
@@ -413,7 +555,11 @@ mod streaming {
 
     #[test]
     fn proj() {
-        fn fancy_projection<F: FancyArithmetic>(b: &mut F, xs: &[F::Item], q: u16) -> Option<u16> {
+        fn fancy_projection<F: FancyArithmetic>(
+            b: &mut F,
+            xs: &[F::Item],
+            q: Modulus,
+        ) -> Option<Modulus> {
             let tab = (0..q).map(|i| (i + 1) % q).collect_vec();
             let z = b.proj(&xs[0], q, Some(tab)).unwrap();
             b.output(&z).unwrap()
@@ -430,13 +576,46 @@ mod streaming {
             );
         }
     }
+
+    #[test]
+    fn unequal_moduli() {
+        use crate::errors::{EvaluatorError, FancyError, GarblerError};
+
+        let (sender, receiver) = unix_channel_pair();
+        let mut gb = Garbler::<UnixChannel, AesRng, AllWire>::new(sender, AesRng::new());
+        let mut ev = Evaluator::<UnixChannel, AllWire>::new(receiver);
+        let mut rng = AesRng::new();
+        let (x, y) = (AllWire::rand(&mut rng, 3), AllWire::rand(&mut rng, 5));
+
+        for (op, res) in [("add", gb.add(&x, &y)), ("sub", gb.sub(&y, &x))] {
+            match res {
+                Err(GarblerError::FancyError(FancyError::UnequalModuli { op: o, x, y })) => {
+                    assert_eq!(o, op);
+                    assert_eq!((x, y), if op == "add" { (3, 5) } else { (5, 3) });
+                }
+                r => panic!("{} of mod 3 and mod 5 succeeded: {:?}", op, r.map(|_| ())),
+            }
+        }
+        for (op, res) in [("add", ev.add(&x, &y)), ("sub", ev.sub(&x, &y))] {
+            match res {
+                Err(EvaluatorError::FancyError(FancyError::UnequalModuli {
+                    op: o,
+                    x: 3,
+                    y: 5,
+                })) => {
+                    assert_eq!(o, op)
+                }
+                r => panic!("{} of mod 3 and mod 5 succeeded: {:?}", op, r.map(|_| ())),
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod complex {
     use crate::{
         dummy::Dummy, util::RngExt, AllWire, CrtBundle, CrtGadgets, Evaluator, FancyArithmetic,
-        FancyBinary, FancyInput, Garbler,
+        FancyBinary, FancyInput, Garbler, SecurityParams,
     };
     use itertools::Itertools;
     use rand::thread_rng;
@@ -458,6 +637,17 @@ mod complex {
 
     #[test]
     fn test_complex_gadgets() {
+        check_complex_gadgets(SecurityParams::DEFAULT);
+    }
+
+    #[test]
+    fn test_complex_gadgets_narrow_labels() {
+        // The gadgets mix binary wires, whose labels are narrowed, with wires
+        // of other moduli, whose labels are not.
+        check_complex_gadgets(SecurityParams::SIMULATION);
+    }
+
+    fn check_complex_gadgets(security: SecurityParams) {
         let mut rng = thread_rng();
         let N = 10;
         let qs = crate::util::primes_with_width(10);
@@ -480,7 +670,8 @@ mod complex {
             let (sender, receiver) = unix_channel_pair();
 
             std::thread::spawn(move || {
-                let mut garbler = Garbler::<_, _, AllWire>::new(sender, AesRng::new());
+                let mut garbler =
+                    Garbler::<_, _, AllWire>::with_security(sender, AesRng::new(), security);
 
                 // encode input and send it to the evaluator
                 let mut gb_inp = Vec::with_capacity(N);
@@ -494,7 +685,7 @@ mod complex {
                 complex_gadget(&mut garbler, &gb_inp).unwrap();
             });
 
-            let mut evaluator = Evaluator::<_, AllWire>::new(receiver);
+            let mut evaluator = Evaluator::<_, AllWire>::with_security(receiver, security);
 
             // receive encoded wires from the garbler thread
             let mut ev_inp = Vec::with_capacity(N);
@@ -511,3 +702,474 @@ mod complex {
         }
     }
 }
+
+#[cfg(all(test, feature = "wide-moduli"))]
+mod wide_moduli {
+    use crate::{
+        circuit::{eval_plain, ArithmeticCircuit, CircuitBuilder},
+        classic::garble,
+        util::RngExt,
+        AllWire, Fancy, FancyArithmetic, Modulus,
+    };
+    use rand::SeedableRng;
+    use scuttlebutt::{AesRng, Block};
+
+    const Q: Modulus = 65537;
+
+    fn circuit() -> ArithmeticCircuit {
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(Q);
+        let y = b.evaluator_input(Q);
+        let z = b.mul(&x, &y).unwrap();
+        let s = b.add(&x, &y).unwrap();
+        let c = b.cmul(&x, Q - 1).unwrap();
+        b.outputs(&[z, s, c]).unwrap();
+        b.finish()
+    }
+
+    fn native(x: Modulus, y: Modulus) -> Vec<Modulus> {
+        let (x, y, q) = (x as u64, y as u64, Q as u64);
+        [x * y % q, (x + y) % q, x * (q - 1) % q]
+            .iter()
+            .map(|&z| z as Modulus)
+            .collect()
+    }
+
+    #[test]
+    fn mod_65537() {
+        let mut rng = AesRng::from_seed(Block::from(0x65537_u128));
+        let mut c = circuit();
+        let (en, ev) = garble::<AllWire, _>(&mut c).unwrap();
+        let mut inputs = vec![(0, 0), (Q - 1, Q - 1), (Q - 1, 1), (1 << 16, 2)];
+        inputs.extend((0..8).map(|_| (rng.gen_residue(Q), rng.gen_residue(Q))));
+        for (x, y) in inputs {
+            let should_be = native(x, y);
+            assert_eq!(eval_plain(&c, &[x], &[y]).unwrap(), should_be);
+            let xs = en.encode_garbler_inputs(&[x]).unwrap();
+            let ys = en.encode_evaluator_inputs(&[y]).unwrap();
+            assert_eq!(
+                ev.eval(&mut c, &xs, &ys).unwrap(),
+                should_be,
+                "x={} y={}",
+                x,
+                y
+            );
+        }
+    }
+}
+
+// The digests are for `u16` moduli: with `wide-moduli` the garbler draws its
+// random digits as `u32`s, and so sends different labels for the same seed.
+// They are also for the AES hash, which `blake3-hash` replaces.
+#[cfg(all(test, not(feature = "wide-moduli"), not(feature = "blake3-hash")))]
+mod kat {
+    use crate::{
+        circuit::{ArithmeticCircuit, CircuitBuilder, CircuitType, EvaluableCircuit},
+        util, AllWire, BundleGadgets, CrtGadgets, Fancy, FancyArithmetic, Garbler,
+    };
+    use itertools::Itertools;
+    use rand::SeedableRng;
+    use scuttlebutt::{AbstractChannel, AesRng, Block, Channel};
+    use sha2::{Digest, Sha256};
+    use std::rc::Rc;
+
+    fn circuit() -> ArithmeticCircuit {
+        let mut b = CircuitBuilder::new();
+        for q in [3, 5, 7, 11] {
+            let x = b.garbler_input(q);
+            let y = b.evaluator_input(q);
+            let z = b.mul(&x, &y).unwrap();
+            let c = b.constant(2, q).unwrap();
+            let z = b.mul(&z, &c).unwrap();
+            let tt = (0..q).map(|i| i * i % q).collect_vec();
+            let w = b.proj(&z, q, Some(tt)).unwrap();
+            b.output(&z).unwrap();
+            b.output(&w).unwrap();
+        }
+        let x = b.garbler_input(11);
+        let y = b.evaluator_input(3);
+        let z = b.mul(&x, &y).unwrap();
+        b.output(&z).unwrap();
+
+        let q = util::modulus_with_width(16);
+        let x = b.crt_garbler_input(q);
+        let y = b.crt_evaluator_input(q);
+        let z = b.crt_mul(&x, &y).unwrap();
+        b.output_bundle(&z).unwrap();
+        b.finish()
+    }
+
+    /// Projections sharing truth tables, across moduli and with unused outputs.
+    fn projections() -> ArithmeticCircuit {
+        let mut b = CircuitBuilder::new();
+        let sbox = (0..16).map(|i| (7 * i + 3) % 16).collect_vec();
+        let xs = b.garbler_inputs(&[16; 4]);
+        for _ in 0..8 {
+            for x in xs.iter() {
+                let z = b.proj(x, 16, Some(sbox.clone())).unwrap();
+                b.output(&z).unwrap();
+            }
+        }
+        for (q_in, q_out) in [(5, 3), (3, 7), (17, 2)] {
+            let x = b.evaluator_input(q_in);
+            let tt = (0..q_in).map(|i| (i / 2) % q_out).collect_vec();
+            for _ in 0..3 {
+                let z = b.proj(&x, q_out, Some(tt.clone())).unwrap();
+                b.output(&z).unwrap();
+            }
+        }
+        b.finish()
+    }
+
+    /// Garble `c` with a fixed seed, returning a digest of what the garbler sends.
+    fn garbling_digest(c: &ArithmeticCircuit) -> String {
+        let channel = Channel::new(std::io::empty(), Vec::new());
+        let rng = AesRng::from_seed(Block::from(0x5eed_u128));
+        let mut gb = Garbler::<_, _, AllWire>::new(channel.clone(), rng);
+        let xs = (0..c.num_garbler_inputs())
+            .map(|i| gb.encode_wire(0, c.garbler_input_mod(i)).0)
+            .collect_vec();
+        let ys = (0..c.num_evaluator_inputs())
+            .map(|i| gb.encode_wire(0, c.evaluator_input_mod(i)).0)
+            .collect_vec();
+        c.eval(&mut gb, &xs, &ys).unwrap();
+        drop(gb);
+
+        let bytes = Rc::try_unwrap(channel.writer()).unwrap().into_inner();
+        format!("{:x}", Sha256::digest(&bytes))
+    }
+
+    // Changes to the garbler must not change what it sends for a given seed.
+    #[test]
+    fn seeded_garbling() {
+        assert_eq!(
+            garbling_digest(&circuit()),
+            "17060612625fdd62bcade77cf612bbc698308fb72c9523c6bf459f98b8ef5cf8"
+        );
+    }
+
+    #[test]
+    fn seeded_projections() {
+        assert_eq!(
+            garbling_digest(&projections()),
+            "463dc0c6b23cf2e84b744fbb8b34331bd5b5b9bca0597d9371742f51b0b9e55d"
+        );
+    }
+}
+
+// The vectors are for the AES hash of the module docs.
+#[cfg(all(test, not(feature = "blake3-hash")))]
+mod interop {
+    //! Frozen vectors for the binary wire format in the module docs, checked
+    //! against `Garbler` and `Evaluator` and against a reference written from
+    //! the docs alone. Changing a vector changes the scheme, which breaks
+    //! interoperability with other implementations and with garbled circuits
+    //! that already exist, so it must only be done deliberately.
+
+    use crate::{
+        errors::{EvaluatorError, FancyError},
+        Evaluator, Fancy, FancyBinary, Garbler, Modulus, WireLabel, WireMod2,
+    };
+    use itertools::Itertools;
+    use rand::SeedableRng;
+    use scuttlebutt::{AbstractChannel, Aes128, AesRng, Block, Channel};
+    use std::{io::Cursor, rc::Rc};
+
+    const SEED: u128 = 0x1e7e_0b;
+
+    /// The key of `π`, as given in the module docs.
+    const FIXED_KEY: &str = "bd2400c11241ce33ed3d7dc7a8564025";
+
+    const DELTA: &str = "d792587800a9ec3e35cc828db8728de1";
+
+    /// The garbler's zero labels of the wires returned by `circuit`.
+    const ZERO_LABELS: [&str; 8] = [
+        "2dcf07dc433a19d97c37d9b4849ed7ae",
+        "8d11f951ba59c2abf40dc6100571c630",
+        "3f422086708a918c5c3fe9ab82d7cd42",
+        "a0defe8df963db72883a1fa481ef119e",
+        "e8d078fe70237db269f36b263aa540a3",
+        "bd3bb6fbc034a57155bcbd67764b8a96",
+        "59bec4ab7a596beb3f5ee5e4705a9604",
+        "49aa2d70efe380baec7d205b398e40fe",
+    ];
+
+    /// What the garbler sends: a constant, three AND gates, and five outputs.
+    const SENT: [&str; 17] = [
+        "e2ed5f172b192abe37c0013fba11cd9b",
+        "1ae13d23168b20414fc6884cc389de45",
+        "6aa9ee83c09d494f60703feace390777",
+        "5ea615e0f1cf9cfcad7bd09cb2f4b1e5",
+        "1a711262b3d3e2cb05190dceb7bfc623",
+        "84c2320d202823c82cd7aad89ba8d169",
+        "f3f54e994f1bbe14e1dc61fc7b66671e",
+        "6a7d35469403460089d45542cae1b057",
+        "8e97bf7d2f02c773c6835a94f13b983c",
+        "0227057d53ab8bff37477bdc49f29808",
+        "86f394941bbdf5a50074f942cbfe53b0",
+        "0dc93510f0f24ac0caca020d52533249",
+        "9489c8263d1975e250eb2c141c4afc1e",
+        "15336500952acad39b626193378f5d53",
+        "6b58f32ba24bbbebe7703828a376c6fc",
+        "794bd06c1942912b52d4d56b53d7f285",
+        "94afa6ffd018d1f98e4787070e82d3a4",
+    ];
+
+    const INPUTS: [Modulus; 2] = [1, 0];
+
+    /// The evaluator's labels of the wires returned by `circuit`, on `INPUTS`.
+    const ACTIVE_LABELS: [&str; 8] = [
+        "fa5d5fa44393f5e749fb5b393cec5a4f",
+        "8d11f951ba59c2abf40dc6100571c630",
+        "3f422086708a918c5c3fe9ab82d7cd42",
+        "774ca6f5f9ca374cbdf69d29399d9c7f",
+        "3f422086708a918c5c3fe9ab82d7cd42",
+        "6aa9ee83c09d494f60703feace390777",
+        "8e2c9cd37af087d50a926769c8281be5",
+        "9e387508ef4a6c84d9b1a2d681fccd1f",
+    ];
+
+    /// The indices of the output wires among those returned by `circuit`.
+    const OUTPUT_WIRES: [usize; 5] = [2, 3, 4, 6, 7];
+
+    /// Run a garbler input `a` and an evaluator input `b` through every kind
+    /// of binary gate, returning every wire and the outputs.
+    fn circuit<F: FancyBinary>(f: &mut F, a: F::Item, b: F::Item) -> (Vec<F::Item>, Vec<Modulus>) {
+        let c = f.and(&a, &b).unwrap();
+        let d = f.xor(&a, &b).unwrap();
+        let e = f.negate(&c).unwrap();
+        let k = f.constant(1, 2).unwrap();
+        let g = f.and(&d, &k).unwrap();
+        let h = f.and(&e, &g).unwrap();
+        let wires = vec![a, b, c, d, e, k, g, h];
+        let outputs = OUTPUT_WIRES
+            .iter()
+            .map(|&i| f.output(&wires[i]).unwrap().unwrap_or(0))
+            .collect();
+        (wires, outputs)
+    }
+
+    /// The values of the wires returned by `circuit`.
+    fn plain(a: Modulus, b: Modulus) -> [Modulus; 8] {
+        let c = a & b;
+        [a, b, c, a ^ b, 1 - c, 1, a ^ b, (1 - c) & (a ^ b)]
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn block(hex: &str) -> Block {
+        let mut bytes = [0; 16];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap();
+        }
+        Block::from(bytes)
+    }
+
+    fn wire(hex: &str) -> WireMod2 {
+        WireMod2::from_block(block(hex), 2)
+    }
+
+    fn hexes(wires: &[WireMod2]) -> Vec<String> {
+        wires.iter().map(|w| hex(w.as_block().as_ref())).collect()
+    }
+
+    /// The evaluator's labels of `INPUTS`.
+    fn active_inputs(xs: [Modulus; 2]) -> [WireMod2; 2] {
+        let delta = wire(DELTA);
+        [0, 1].map(|i| wire(ZERO_LABELS[i]).plus_mov(&delta.cmul(xs[i])))
+    }
+
+    #[test]
+    fn garbler() {
+        let channel = Channel::new(std::io::empty(), Vec::new());
+        let rng = AesRng::from_seed(Block::from(SEED));
+        let mut gb = Garbler::<_, _, WireMod2>::new(channel.clone(), rng);
+        let a = gb.encode_wire(INPUTS[0], 2).0;
+        let b = gb.encode_wire(INPUTS[1], 2).0;
+        let (wires, _) = circuit(&mut gb, a, b);
+        let delta = gb.get_deltas()[&2];
+
+        let sent = Rc::try_unwrap(channel.writer()).unwrap().into_inner();
+        let sent = sent.chunks(16).map(hex).collect_vec();
+        assert_eq!(hex(delta.as_block().as_ref()), DELTA);
+        assert_eq!(hexes(&wires), ZERO_LABELS);
+        assert_eq!(sent, SENT);
+    }
+
+    #[test]
+    fn evaluator() {
+        let sent = SENT
+            .iter()
+            .flat_map(|s| block(s).as_ref().to_vec())
+            .collect_vec();
+        let delta = wire(DELTA);
+        for (a, b) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            let channel = Channel::new(Cursor::new(sent.clone()), std::io::sink());
+            let mut ev = Evaluator::<_, WireMod2>::new(channel);
+            let [x, y] = active_inputs([a, b]);
+            let (wires, outputs) = circuit(&mut ev, x, y);
+
+            let values = plain(a, b);
+            let expected = ZERO_LABELS
+                .iter()
+                .zip(values)
+                .map(|(w, v)| wire(w).plus_mov(&delta.cmul(v)))
+                .collect_vec();
+            assert_eq!(wires, expected);
+            assert_eq!(outputs, OUTPUT_WIRES.map(|i| values[i]));
+            if [a, b] == INPUTS {
+                assert_eq!(hexes(&wires), ACTIVE_LABELS);
+            }
+        }
+    }
+
+    ////////////////////////////////////////////////////////////////////////////
+    // reference implementation, written from the module docs
+
+    /// `H(x, t)`, with its own copy of the fixed key.
+    fn hash(x: Block, t: u128) -> Block {
+        let aes = Aes128::new(block(FIXED_KEY));
+        let y = aes.encrypt(x);
+        aes.encrypt(y ^ Block::from(t.to_le_bytes())) ^ y
+    }
+
+    fn color(x: Block) -> bool {
+        x.as_ref()[0] & 1 == 1
+    }
+
+    /// `x` if `bit` is set, and zero otherwise.
+    fn times(bit: bool, x: Block) -> Block {
+        if bit {
+            x
+        } else {
+            Block::default()
+        }
+    }
+
+    struct ReferenceGarbler {
+        delta: Block,
+        /// The zero labels to give constants, in order.
+        fresh: std::vec::IntoIter<Block>,
+        gates: u128,
+        outputs: u128,
+        sent: Vec<Block>,
+    }
+
+    impl Fancy for ReferenceGarbler {
+        type Item = WireMod2;
+        type Error = FancyError;
+
+        fn constant(&mut self, x: Modulus, _: Modulus) -> Result<WireMod2, FancyError> {
+            let w = self.fresh.next().unwrap();
+            self.sent.push(w ^ times(x == 1, self.delta));
+            Ok(WireMod2::from_block(w, 2))
+        }
+
+        fn output(&mut self, x: &WireMod2) -> Result<Option<Modulus>, FancyError> {
+            let w = x.as_block();
+            let j = self.outputs << 64;
+            self.sent.push(hash(w, j));
+            self.sent.push(hash(w ^ self.delta, j | 1));
+            self.outputs += 1;
+            Ok(None)
+        }
+    }
+
+    impl FancyBinary for ReferenceGarbler {
+        fn xor(&mut self, x: &WireMod2, y: &WireMod2) -> Result<WireMod2, FancyError> {
+            Ok(WireMod2::from_block(x.as_block() ^ y.as_block(), 2))
+        }
+
+        fn negate(&mut self, x: &WireMod2) -> Result<WireMod2, FancyError> {
+            Ok(WireMod2::from_block(x.as_block() ^ self.delta, 2))
+        }
+
+        fn and(&mut self, x: &WireMod2, y: &WireMod2) -> Result<WireMod2, FancyError> {
+            let (a, b, d) = (x.as_block(), y.as_block(), self.delta);
+            let t = self.gates << 64;
+            let (pa, pb) = (color(a), color(b));
+            let (a0, b0) = (a ^ times(pa, d), b ^ times(pb, d));
+            let (a1, b1) = (a0 ^ d, b0 ^ d);
+            self.sent.push(hash(a0, t) ^ hash(a1, t) ^ times(pb, d));
+            self.sent.push(hash(b0, t) ^ hash(b1, t) ^ a);
+            self.gates += 1;
+            let c = hash(a0, t) ^ hash(b0, t) ^ times(pa && pb, d);
+            Ok(WireMod2::from_block(c, 2))
+        }
+    }
+
+    struct ReferenceEvaluator {
+        sent: std::array::IntoIter<Block, { SENT.len() }>,
+        gates: u128,
+        outputs: u128,
+    }
+
+    impl Fancy for ReferenceEvaluator {
+        type Item = WireMod2;
+        type Error = EvaluatorError;
+
+        fn constant(&mut self, _: Modulus, _: Modulus) -> Result<WireMod2, EvaluatorError> {
+            Ok(WireMod2::from_block(self.sent.next().unwrap(), 2))
+        }
+
+        fn output(&mut self, x: &WireMod2) -> Result<Option<Modulus>, EvaluatorError> {
+            let j = self.outputs << 64;
+            self.outputs += 1;
+            let cts = [self.sent.next().unwrap(), self.sent.next().unwrap()];
+            (0..2)
+                .find(|&k| hash(x.as_block(), j | k as u128) == cts[k as usize])
+                .map(Some)
+                .ok_or(EvaluatorError::DecodingFailed)
+        }
+    }
+
+    impl FancyBinary for ReferenceEvaluator {
+        fn xor(&mut self, x: &WireMod2, y: &WireMod2) -> Result<WireMod2, EvaluatorError> {
+            Ok(WireMod2::from_block(x.as_block() ^ y.as_block(), 2))
+        }
+
+        fn negate(&mut self, x: &WireMod2) -> Result<WireMod2, EvaluatorError> {
+            Ok(*x)
+        }
+
+        fn and(&mut self, x: &WireMod2, y: &WireMod2) -> Result<WireMod2, EvaluatorError> {
+            let (x, y) = (x.as_block(), y.as_block());
+            let t = self.gates << 64;
+            self.gates += 1;
+            let g0 = self.sent.next().unwrap();
+            let g1 = self.sent.next().unwrap();
+            let z = hash(x, t) ^ times(color(x), g0) ^ hash(y, t) ^ times(color(y), g1 ^ x);
+            Ok(WireMod2::from_block(z, 2))
+        }
+    }
+
+    #[test]
+    fn reference_garbler() {
+        let mut gb = ReferenceGarbler {
+            delta: block(DELTA),
+            fresh: vec![block(ZERO_LABELS[5])].into_iter(),
+            gates: 0,
+            outputs: 0,
+            sent: Vec::new(),
+        };
+        let (wires, _) = circuit(&mut gb, wire(ZERO_LABELS[0]), wire(ZERO_LABELS[1]));
+        assert_eq!(hexes(&wires), ZERO_LABELS);
+        assert_eq!(gb.sent.iter().map(|b| hex(b.as_ref())).collect_vec(), SENT);
+    }
+
+    #[test]
+    fn reference_evaluator() {
+        let mut ev = ReferenceEvaluator {
+            sent: SENT.map(block).into_iter(),
+            gates: 0,
+            outputs: 0,
+        };
+        let [x, y] = active_inputs(INPUTS);
+        let (wires, outputs) = circuit(&mut ev, x, y);
+        assert_eq!(hexes(&wires), ACTIVE_LABELS);
+        let values = plain(INPUTS[0], INPUTS[1]);
+        assert_eq!(outputs, OUTPUT_WIRES.map(|i| values[i]));
+    }
+}
@@ -1,15 +1,18 @@
-use std::marker::PhantomData;
+use std::{marker::PhantomData, sync::Arc};
 
 use crate::{
     check_binary,
+    circuit::{check_arity, CustomGate},
     errors::{EvaluatorError, FancyError},
-    fancy::{Fancy, FancyReveal},
-    hash_wires,
+    fancy::{check_moduli, Fancy, FancyReveal},
+    garble::garbler::truncate,
+    hash_wires, hash_wires_tweaked,
     util::{output_tweak, tweak, tweak2},
     wire::WireLabel,
-    AllWire, ArithmeticWire, FancyArithmetic, FancyBinary, HasModulus, WireMod2,
+    AllWire, ArithmeticWire, FancyArithmetic, FancyBinary, HasModulus, Modulus, SecurityParams,
+    WideWireMod2, WireMod2,
 };
-use scuttlebutt::{AbstractChannel, Block};
+use scuttlebutt::{AbstractChannel, Block, Block512};
 use subtle::ConditionallySelectable;
 
 /// Streaming evaluator using a callback to receive ciphertexts as needed.
@@ -20,20 +23,47 @@ pub struct Evaluator<C, Wire> {
     channel: C,
     current_gate: usize,
     current_output: usize,
+    security: SecurityParams,
     _phantom: PhantomData<Wire>,
 }
 
 impl<C: AbstractChannel, Wire: WireLabel> Evaluator<C, Wire> {
     /// Create a new `Evaluator`.
     pub fn new(channel: C) -> Self {
+        Self::with_security(channel, SecurityParams::DEFAULT)
+    }
+
+    /// Create a new `Evaluator` using the security parameters `security`,
+    /// which must be those of the garbler.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the labels of `security` are wider than `Wire` holds; see
+    /// [`WireLabel::MAX_LABEL_BITS`].
+    pub fn with_security(channel: C, security: SecurityParams) -> Self {
+        if let Err(e) = security.check_label_bits(Wire::MAX_LABEL_BITS) {
+            panic!("{}", e);
+        }
         Evaluator {
             channel,
             current_gate: 0,
             current_output: 0,
+            security,
             _phantom: PhantomData,
         }
     }
 
+    /// The security parameters of this evaluator.
+    pub fn security(&self) -> SecurityParams {
+        self.security
+    }
+
+    /// Clear the bits of a binary label above the label width of the
+    /// security parameters. Labels of other moduli are left as they are.
+    pub(crate) fn truncate(&self, wire: Wire) -> Wire {
+        truncate(self.security, wire)
+    }
+
     /// The current non-free gate index of the garbling computation.
     fn current_gate(&mut self) -> usize {
         let current = self.current_gate;
@@ -49,43 +79,150 @@ impl<C: AbstractChannel, Wire: WireLabel> Evaluator<C, Wire> {
     }
 
     /// Read a Wire from the reader.
-    pub fn read_wire(&mut self, modulus: u16) -> Result<Wire, EvaluatorError> {
-        let block = self.channel.read_block()?;
+    pub fn read_wire(&mut self, modulus: Modulus) -> Result<Wire, EvaluatorError> {
+        if modulus == 2 && Wire::MAX_LABEL_BITS > 128 {
+            return Ok(Wire::from_block512(self.read_wide_label()?, modulus));
+        }
+        let block = if modulus == 2 {
+            self.read_label()?
+        } else {
+            self.channel.read_block()?
+        };
         Ok(Wire::from_block(block, modulus))
     }
 
-    /// Evaluates an 'and' gate given two inputs wires and two half-gates from the garbler.
-    ///
-    /// Outputs C = A & B
-    ///
-    /// Used internally as a subroutine to implement 'and' gates for `FancyBinary`.
-    fn evaluate_and_gate(
-        &mut self,
-        A: &WireMod2,
-        B: &WireMod2,
-        gate0: &Block,
-        gate1: &Block,
-    ) -> WireMod2 {
-        let gate_num = self.current_gate();
-        let g = tweak2(gate_num as u64, 0);
+    /// Read a binary label or a ciphertext of the label width.
+    fn read_label(&mut self) -> Result<Block, EvaluatorError> {
+        self.read_prefix(self.security.label_bytes())
+    }
 
-        let [hashA, hashB] = hash_wires([A, B], g);
+    /// Read a binary label or a ciphertext of the label width into the start
+    /// of an otherwise zero `Block512`.
+    fn read_wide_label(&mut self) -> Result<Block512, EvaluatorError> {
+        let mut label = Block512::default();
+        let len = self.security.label_bytes();
+        self.channel.read_bytes(label.prefix_mut(len))?;
+        Ok(label)
+    }
 
-        // garbler's half gate
-        let L = WireMod2::from_block(
-            Block::conditional_select(&hashA, &(hashA ^ *gate0), (A.color() as u8).into()),
-            2,
-        );
+    /// Read `len` bytes into the start of an otherwise zero block.
+    fn read_prefix(&mut self, len: usize) -> Result<Block, EvaluatorError> {
+        if len == std::mem::size_of::<Block>() {
+            return Ok(self.channel.read_block()?);
+        }
+        let mut block = Block::default();
+        self.channel.read_bytes(&mut block.as_mut()[..len])?;
+        Ok(block)
+    }
+}
 
-        // evaluator's half gate
-        let R = WireMod2::from_block(
-            Block::conditional_select(&hashB, &(hashB ^ *gate1), (B.color() as u8).into()),
-            2,
-        );
+/// Evaluates the `gate_num`-th 'and' gate given two inputs wires and two half-gates from the garbler.
+///
+/// Outputs C = A & B
+///
+/// Used internally as a subroutine to implement 'and' gates for `FancyBinary`.
+pub(crate) fn evaluate_and_gate(
+    A: &WireMod2,
+    B: &WireMod2,
+    gate0: &Block,
+    gate1: &Block,
+    gate_num: usize,
+) -> WireMod2 {
+    let g = tweak2(gate_num as u64, 0);
+    let hashes = hash_wires([A, B], g);
+    finish_and_gate(A, B, gate0, gate1, hashes)
+}
+
+/// Evaluates four 'and' gates as [`evaluate_and_gate`] does, given the input
+/// wires, half-gates, and number of each, hashing the labels of all four at
+/// once.
+pub(crate) fn evaluate_and_gates(
+    gates: [(&WireMod2, &WireMod2, &Block, &Block, usize); 4],
+) -> [WireMod2; 4] {
+    let [(A0, B0, _, _, n0), (A1, B1, _, _, n1), (A2, B2, _, _, n2), (A3, B3, _, _, n3)] = gates;
+    let [g0, g1, g2, g3] = [n0, n1, n2, n3].map(|n| tweak2(n as u64, 0));
+    let [h0, h1, h2, h3, h4, h5, h6, h7] = hash_wires_tweaked(
+        [A0, B0, A1, B1, A2, B2, A3, B3],
+        [g0, g0, g1, g1, g2, g2, g3, g3],
+    );
+    let hashes = [[h0, h1], [h2, h3], [h4, h5], [h6, h7]];
+    std::array::from_fn(|k| {
+        let (A, B, gate0, gate1, _) = gates[k];
+        finish_and_gate(A, B, gate0, gate1, hashes[k])
+    })
+}
 
-        let res = L.plus_mov(&R.plus_mov(&A.cmul(B.color())));
-        res
+/// Decode `x`, the label of the `i`-th output, with the output ciphertexts
+/// `ct`, of which the first `len` bytes are compared.
+pub(crate) fn decode_output<Wire: WireLabel>(
+    x: &Wire,
+    i: usize,
+    ct: &[Block],
+    len: usize,
+) -> Result<Modulus, EvaluatorError> {
+    // Attempt to brute force x using the output ciphertext
+    for (k, ct) in (0..x.modulus()).zip(ct) {
+        let hashed_wire = x.hash(output_tweak(i, k));
+        if hashed_wire.as_ref()[..len] == ct.as_ref()[..len] {
+            return Ok(k);
+        }
     }
+    Err(EvaluatorError::DecodingFailed)
+}
+
+/// Finish evaluating an 'and' gate given the hashes of its input wires.
+fn finish_and_gate(
+    A: &WireMod2,
+    B: &WireMod2,
+    gate0: &Block,
+    gate1: &Block,
+    hashes: [Block; 2],
+) -> WireMod2 {
+    let [hashA, hashB] = hashes;
+
+    // garbler's half gate
+    let L = WireMod2::from_block(
+        Block::conditional_select(&hashA, &(hashA ^ *gate0), (A.color() as u8).into()),
+        2,
+    );
+
+    // evaluator's half gate
+    let R = WireMod2::from_block(
+        Block::conditional_select(&hashB, &(hashB ^ *gate1), (B.color() as u8).into()),
+        2,
+    );
+
+    L.plus_mov(&R.plus_mov(&A.cmul(B.color())))
+}
+
+/// Evaluates the `gate_num`-th 'and' gate of labels wider than 128 bits as
+/// [`evaluate_and_gate`] does, hashing with [`WideWireMod2::hash_wide`].
+pub(crate) fn evaluate_wide_and_gate(
+    A: &WideWireMod2,
+    B: &WideWireMod2,
+    gate0: &Block512,
+    gate1: &Block512,
+    gate_num: usize,
+) -> WideWireMod2 {
+    let g = tweak2(gate_num as u64, 0);
+    let hashA = WideWireMod2::from_block512(A.hash_wide(g), 2);
+    let hashB = WideWireMod2::from_block512(B.hash_wide(g), 2);
+
+    // garbler's half gate
+    let L = WideWireMod2::conditional_select(
+        &hashA,
+        &hashA.plus(&WideWireMod2::from_block512(*gate0, 2)),
+        (A.color() as u8).into(),
+    );
+
+    // evaluator's half gate
+    let R = WideWireMod2::conditional_select(
+        &hashB,
+        &hashB.plus(&WideWireMod2::from_block512(*gate1, 2)),
+        (B.color() as u8).into(),
+    );
+
+    L.plus_mov(&R.plus_mov(&A.cmul(B.color())))
 }
 
 impl<C: AbstractChannel> FancyBinary for Evaluator<C, WireMod2> {
@@ -99,21 +236,68 @@ impl<C: AbstractChannel> FancyBinary for Evaluator<C, WireMod2> {
     }
 
     fn and(&mut self, A: &Self::Item, B: &Self::Item) -> Result<Self::Item, Self::Error> {
-        let gate0 = self.channel.read_block()?;
-        let gate1 = self.channel.read_block()?;
-        Ok(self.evaluate_and_gate(A, B, &gate0, &gate1))
+        let gate0 = self.read_label()?;
+        let gate1 = self.read_label()?;
+        let C = evaluate_and_gate(A, B, &gate0, &gate1, self.current_gate());
+        Ok(self.truncate(C))
+    }
+
+    /// Evaluate the gate as it says, as the next gate.
+    fn custom(
+        &mut self,
+        gate: &Arc<dyn CustomGate>,
+        inputs: &[Self::Item],
+    ) -> Result<Self::Item, Self::Error> {
+        check_arity(gate.as_ref(), inputs.len())?;
+        let ciphertexts = (0..gate.num_ciphertexts())
+            .map(|_| self.read_label())
+            .collect::<Result<Vec<_>, _>>()?;
+        let z = gate.evaluate(inputs, &ciphertexts, self.current_gate());
+        Ok(self.truncate(z))
     }
 }
 
 impl<C: AbstractChannel, Wire: WireLabel> FancyReveal for Evaluator<C, Wire> {
-    fn reveal(&mut self, x: &Wire) -> Result<u16, EvaluatorError> {
-        let val = self.output(x)?.expect("Evaluator always outputs Some(u16)");
-        self.channel.write_u16(val)?;
+    fn reveal(&mut self, x: &Wire) -> Result<Modulus, EvaluatorError> {
+        let val = self
+            .output(x)?
+            .expect("Evaluator always outputs Some(Modulus)");
+        self.channel.write_bytes(&val.to_le_bytes())?;
         self.channel.flush()?;
         Ok(val)
     }
 }
 
+impl<C: AbstractChannel> FancyBinary for Evaluator<C, WideWireMod2> {
+    /// Negate is a noop for the evaluator
+    fn negate(&mut self, x: &Self::Item) -> Result<Self::Item, Self::Error> {
+        Ok(*x)
+    }
+
+    fn xor(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        Ok(x.plus(y))
+    }
+
+    fn and(&mut self, A: &Self::Item, B: &Self::Item) -> Result<Self::Item, Self::Error> {
+        let gate0 = self.read_wide_label()?;
+        let gate1 = self.read_wide_label()?;
+        let C = evaluate_wide_and_gate(A, B, &gate0, &gate1, self.current_gate());
+        Ok(self.truncate(C))
+    }
+
+    /// Refuse the gate: custom gates garble labels of 128 bits.
+    fn custom(
+        &mut self,
+        gate: &Arc<dyn CustomGate>,
+        _inputs: &[Self::Item],
+    ) -> Result<Self::Item, Self::Error> {
+        Err(EvaluatorError::FancyError(FancyError::InvalidArg(format!(
+            "custom gate {} cannot evaluate labels wider than 128 bits",
+            gate.name()
+        ))))
+    }
+}
+
 impl<C: AbstractChannel> FancyBinary for Evaluator<C, AllWire> {
     /// Overriding `negate` to be a noop: entirely handled on garbler's end
     fn negate(&mut self, x: &Self::Item) -> Result<Self::Item, Self::Error> {
@@ -131,9 +315,10 @@ impl<C: AbstractChannel> FancyBinary for Evaluator<C, AllWire> {
 
     fn and(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
         if let (AllWire::Mod2(ref A), AllWire::Mod2(ref B)) = (x, y) {
-            let gate0 = self.channel.read_block()?;
-            let gate1 = self.channel.read_block()?;
-            return Ok(AllWire::Mod2(self.evaluate_and_gate(A, B, &gate0, &gate1)));
+            let gate0 = self.read_label()?;
+            let gate1 = self.read_label()?;
+            let C = evaluate_and_gate(A, B, &gate0, &gate1, self.current_gate());
+            return Ok(AllWire::Mod2(truncate(self.security, C)));
         }
 
         // If we got here, one of the wires isn't binary
@@ -147,20 +332,16 @@ impl<C: AbstractChannel> FancyBinary for Evaluator<C, AllWire> {
 
 impl<C: AbstractChannel, Wire: WireLabel + ArithmeticWire> FancyArithmetic for Evaluator<C, Wire> {
     fn add(&mut self, x: &Wire, y: &Wire) -> Result<Wire, EvaluatorError> {
-        if x.modulus() != y.modulus() {
-            return Err(EvaluatorError::FancyError(FancyError::UnequalModuli));
-        }
+        check_moduli("add", x.modulus(), y.modulus())?;
         Ok(x.plus(y))
     }
 
     fn sub(&mut self, x: &Wire, y: &Wire) -> Result<Wire, EvaluatorError> {
-        if x.modulus() != y.modulus() {
-            return Err(EvaluatorError::FancyError(FancyError::UnequalModuli));
-        }
+        check_moduli("sub", x.modulus(), y.modulus())?;
         Ok(x.minus(y))
     }
 
-    fn cmul(&mut self, x: &Wire, c: u16) -> Result<Wire, EvaluatorError> {
+    fn cmul(&mut self, x: &Wire, c: Modulus) -> Result<Wire, EvaluatorError> {
         Ok(x.cmul(c))
     }
 
@@ -196,26 +377,31 @@ impl<C: AbstractChannel, Wire: WireLabel + ArithmeticWire> FancyArithmetic for E
         let R = if B.color() == 0 {
             Wire::hash_to_mod(hashB, q)
         } else {
-            let ct_right = gate[(q + B.color()) as usize - 2];
+            let ct_right = gate[q as usize + B.color() as usize - 2];
             Wire::from_block(ct_right ^ hashB, q)
         };
 
         // hack for unequal mods
         // TODO: Batch this with original hash if unequal.
         let new_b_color = if unequal {
-            let minitable = *gate.last().unwrap();
+            let minitable = gate[ngates - 1];
             let ct = u128::from(minitable) >> (B.color() * 16);
             let pt = u128::from(B.hash(tweak2(gate_num as u64, 1))) ^ ct;
-            pt as u16
+            (pt & 0xFFFF) as Modulus
         } else {
             B.color()
         };
 
         let res = L.plus_mov(&R.plus_mov(&A.cmul(new_b_color)));
-        Ok(res)
+        Ok(self.truncate(res))
     }
 
-    fn proj(&mut self, x: &Wire, q: u16, _: Option<Vec<u16>>) -> Result<Wire, EvaluatorError> {
+    fn proj(
+        &mut self,
+        x: &Wire,
+        q: Modulus,
+        _: Option<Vec<Modulus>>,
+    ) -> Result<Wire, EvaluatorError> {
         let ngates = (x.modulus() - 1) as usize;
         let mut gate = Vec::with_capacity(ngates);
         for _ in 0..ngates {
@@ -224,7 +410,7 @@ impl<C: AbstractChannel, Wire: WireLabel + ArithmeticWire> FancyArithmetic for E
         }
         let t = tweak(self.current_gate());
         if x.color() == 0 {
-            Ok(x.hashback(t, q))
+            Ok(self.truncate(x.hashback(t, q)))
         } else {
             let ct = gate[x.color() as usize - 1];
             Ok(Wire::from_block(ct ^ x.hash(t), q))
@@ -236,31 +422,20 @@ impl<C: AbstractChannel, Wire: WireLabel> Fancy for Evaluator<C, Wire> {
     type Item = Wire;
     type Error = EvaluatorError;
 
-    fn constant(&mut self, _: u16, q: u16) -> Result<Wire, EvaluatorError> {
+    fn constant(&mut self, _: Modulus, q: Modulus) -> Result<Wire, EvaluatorError> {
         self.read_wire(q)
     }
 
-    fn output(&mut self, x: &Wire) -> Result<Option<u16>, EvaluatorError> {
+    fn output(&mut self, x: &Wire) -> Result<Option<Modulus>, EvaluatorError> {
         let q = x.modulus();
         let i = self.current_output();
 
         // Receive the output ciphertext from the garbler
-        let ct = self.channel.read_blocks(q as usize)?;
-
-        // Attempt to brute force x using the output ciphertext
-        let mut decoded = None;
-        for k in 0..q {
-            let hashed_wire = x.hash(output_tweak(i, k));
-            if hashed_wire == ct[k as usize] {
-                decoded = Some(k);
-                break;
-            }
-        }
+        let len = self.security.hash_bytes();
+        let ct = (0..q)
+            .map(|_| self.read_prefix(len))
+            .collect::<Result<Vec<_>, _>>()?;
 
-        if let Some(output) = decoded {
-            Ok(Some(output))
-        } else {
-            Err(EvaluatorError::DecodingFailed)
-        }
+        decode_output(x, i, &ct, len).map(Some)
     }
 }
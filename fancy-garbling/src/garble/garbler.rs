@@ -1,28 +1,125 @@
 use crate::{
     check_binary,
+    circuit::{check_arity, CustomGate},
     errors::{FancyError, GarblerError},
-    fancy::{BinaryBundle, CrtBundle, Fancy, FancyReveal},
-    hash_wires,
-    util::{output_tweak, tweak, tweak2, RngExt},
-    AllWire, ArithmeticWire, FancyArithmetic, FancyBinary, HasModulus, WireLabel, WireMod2,
+    fancy::{check_inputs, check_moduli, BinaryBundle, CrtBundle, Fancy, FancyReveal},
+    hash_wires, hash_wires_tweaked,
+    util::{self, output_tweak, tweak, tweak2, RngExt},
+    AllWire, ArithmeticWire, FancyArithmetic, FancyBinary, HasModulus, Modulus, SecurityParams,
+    WideWireMod2, WireLabel, WireMod2,
 };
+use itertools::Itertools;
 use rand::{CryptoRng, RngCore};
-use scuttlebutt::{AbstractChannel, Block};
+use scuttlebutt::{AbstractChannel, Block, Block512};
 #[cfg(feature = "serde")]
 use serde::de::DeserializeOwned;
-use std::collections::HashMap;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    sync::Arc,
+};
 use subtle::ConditionallySelectable;
 
+/// Largest modulus for which the garbler caches the multiples of its delta.
+const DELTA_TABLE_MAX_MODULUS: Modulus = 256;
+
+/// The parts of garbling a projection gate that depend only on its truth table.
+struct ProjTable {
+    /// The distinct outputs of the truth table, in increasing order.
+    outputs: Vec<Modulus>,
+    /// For each input `x`, the index of `tt[x]` in `outputs`.
+    slots: Vec<usize>,
+}
+
+impl ProjTable {
+    /// Check that `tt` is a valid projection from `q_in` to `q_out` and collect its
+    /// outputs.
+    fn new(q_in: Modulus, q_out: Modulus, tt: &[Modulus]) -> Result<Self, GarblerError> {
+        if tt.len() < q_in as usize {
+            return Err(GarblerError::FancyError(FancyError::InvalidTruthTable));
+        }
+        let tt = &tt[..q_in as usize];
+        if tt.iter().any(|&y| y >= q_out) {
+            return Err(GarblerError::FancyError(FancyError::InvalidTruthTable));
+        }
+        let mut outputs = tt.to_vec();
+        outputs.sort_unstable();
+        outputs.dedup();
+        let slots = tt
+            .iter()
+            .map(|y| outputs.partition_point(|z| z < y))
+            .collect();
+        Ok(ProjTable { outputs, slots })
+    }
+}
+
 /// Streams garbled circuit ciphertexts through a callback.
-pub struct Garbler<C, RNG, Wire> {
+pub struct Garbler<C, RNG, Wire: WireLabel> {
     channel: C,
-    deltas: HashMap<u16, Wire>, // map from modulus to associated delta wire-label.
+    deltas: HashMap<Modulus, Wire>, // map from modulus to associated delta wire-label.
+    // the delta of modulus 2 in `deltas`, once there is one, which every binary
+    // 'and' and negation reads.
+    binary_delta: Option<Wire>,
+    // map from modulus to the multiples `[0 * delta, 1 * delta, ..., (q - 1) * delta]`,
+    // created lazily on first use.
+    delta_tables: HashMap<Modulus, Arc<[Wire]>>,
+    // map from `(q_in, q_out, truth table)` to the analysis of that table, shared by
+    // all projection gates using it.
+    proj_tables: HashMap<(Modulus, Modulus, Vec<Modulus>), Arc<ProjTable>>,
     current_output: usize,
     current_gate: usize,
+    bytes_written: usize,
+    security: SecurityParams,
     rng: RNG,
 }
 
-#[cfg(feature = "serde")]
+impl<C, RNG, Wire: WireLabel> Garbler<C, RNG, Wire> {
+    /// Wipe the deltas and their multiples; see [`WireLabel::wipe`].
+    #[cfg(any(feature = "std", feature = "zeroize"))]
+    fn wipe_deltas(&mut self) {
+        self.deltas.values_mut().for_each(WireLabel::wipe);
+        self.deltas.clear();
+        if let Some(delta) = self.binary_delta.as_mut() {
+            delta.wipe();
+        }
+        self.binary_delta = None;
+        // The multiples are only shared while a gate is garbled.
+        for table in self.delta_tables.values_mut() {
+            if let Some(table) = Arc::get_mut(table) {
+                table.iter_mut().for_each(WireLabel::wipe);
+            }
+        }
+        self.delta_tables.clear();
+    }
+}
+
+// Used by `twopac::semihonest::Garbler::close`.
+#[cfg(feature = "std")]
+impl<C, RNG: rand::Rng + rand::SeedableRng<Seed = Block>, Wire: WireLabel> Garbler<C, RNG, Wire> {
+    /// Wipe the deltas, and replace the RNG the labels were drawn from with
+    /// one seeded from it, ending the garbling.
+    pub(crate) fn wipe(&mut self) {
+        self.wipe_deltas();
+        let seed = self.rng.gen::<Block>();
+        self.rng = RNG::from_seed(seed);
+    }
+}
+
+// With the `zeroize` feature, a garbler wipes its deltas when dropped. Its RNG
+// overwrites itself if it is an `AesRng`.
+#[cfg(feature = "zeroize")]
+impl<C, RNG, Wire: WireLabel> Drop for Garbler<C, RNG, Wire> {
+    fn drop(&mut self) {
+        self.wipe_deltas();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<C, RNG: zeroize::ZeroizeOnDrop, Wire: WireLabel> zeroize::ZeroizeOnDrop
+    for Garbler<C, RNG, Wire>
+{
+}
+
+#[cfg(all(feature = "serde", feature = "std"))]
 impl<C: AbstractChannel, RNG: CryptoRng + RngCore, Wire: WireLabel + DeserializeOwned>
     Garbler<C, RNG, Wire>
 {
@@ -30,8 +127,14 @@ impl<C: AbstractChannel, RNG: CryptoRng + RngCore, Wire: WireLabel + Deserialize
     pub fn load_deltas(&mut self, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
         let f = std::fs::File::open(filename)?;
         let reader = std::io::BufReader::new(f);
-        let deltas: HashMap<u16, Wire> = serde_json::from_reader(reader)?;
-        self.deltas.extend(deltas.into_iter());
+        let deltas: HashMap<Modulus, Wire> = serde_json::from_reader(reader)?;
+        let deltas = deltas
+            .into_iter()
+            .map(|(q, delta)| (q, self.truncate(delta)))
+            .collect_vec();
+        self.deltas.extend(deltas);
+        self.binary_delta = self.deltas.get(&2).cloned();
+        self.delta_tables.clear();
         Ok(())
     }
 }
@@ -39,15 +142,45 @@ impl<C: AbstractChannel, RNG: CryptoRng + RngCore, Wire: WireLabel + Deserialize
 impl<C: AbstractChannel, RNG: CryptoRng + RngCore, Wire: WireLabel> Garbler<C, RNG, Wire> {
     /// Create a new garbler.
     pub fn new(channel: C, rng: RNG) -> Self {
+        Self::with_security(channel, rng, SecurityParams::DEFAULT)
+    }
+
+    /// Create a new garbler using the security parameters `security`, which
+    /// the evaluator must use too.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the labels of `security` are wider than `Wire` holds; see
+    /// [`WireLabel::MAX_LABEL_BITS`].
+    pub fn with_security(channel: C, rng: RNG, security: SecurityParams) -> Self {
+        if let Err(e) = security.check_label_bits(Wire::MAX_LABEL_BITS) {
+            panic!("{}", e);
+        }
         Garbler {
             channel,
             deltas: HashMap::new(),
+            binary_delta: None,
+            delta_tables: HashMap::new(),
+            proj_tables: HashMap::new(),
             current_gate: 0,
             current_output: 0,
+            bytes_written: 0,
+            security,
             rng,
         }
     }
 
+    /// The security parameters of this garbler.
+    pub fn security(&self) -> SecurityParams {
+        self.security
+    }
+
+    /// Clear the bits of a binary label above the label width of the
+    /// security parameters. Labels of other moduli are left as they are.
+    pub(crate) fn truncate(&self, wire: Wire) -> Wire {
+        truncate(self.security, wire)
+    }
+
     /// The current non-free gate index of the garbling computation
     fn current_gate(&mut self) -> usize {
         let current = self.current_gate;
@@ -57,15 +190,55 @@ impl<C: AbstractChannel, RNG: CryptoRng + RngCore, Wire: WireLabel> Garbler<C, R
 
     /// Create a delta if it has not been created yet for this modulus, otherwise just
     /// return the existing one.
-    pub fn delta(&mut self, q: u16) -> Wire {
+    pub fn delta(&mut self, q: Modulus) -> Wire {
+        if q == 2 {
+            if let Some(delta) = &self.binary_delta {
+                return delta.clone();
+            }
+        }
         if let Some(delta) = self.deltas.get(&q) {
             return delta.clone();
         }
         let w = Wire::rand_delta(&mut self.rng, q);
+        let w = self.truncate(w);
         self.deltas.insert(q, w.clone());
+        if q == 2 {
+            self.binary_delta = Some(w.clone());
+        }
         w
     }
 
+    /// Get the multiples `[0 * delta, 1 * delta, ..., (q - 1) * delta]` of the
+    /// delta for modulus `q`, computing them on first use.
+    ///
+    /// Returns `None` if `q` is too large for the multiples to be cached.
+    fn delta_table(&mut self, q: Modulus) -> Option<Arc<[Wire]>> {
+        if q > DELTA_TABLE_MAX_MODULUS {
+            return None;
+        }
+        if let Some(table) = self.delta_tables.get(&q) {
+            return Some(table.clone());
+        }
+        let delta = self.delta(q);
+        let mut table = Vec::with_capacity(q as usize);
+        let mut multiple = delta.cmul(0);
+        for _ in 0..q {
+            table.push(multiple.clone());
+            multiple.plus_eq(&delta);
+        }
+        let table: Arc<[Wire]> = table.into();
+        self.delta_tables.insert(q, table.clone());
+        Some(table)
+    }
+
+    /// Compute `c * delta` for the delta of modulus `q`.
+    fn delta_cmul(&mut self, q: Modulus, c: Modulus) -> Wire {
+        match self.delta_table(q) {
+            Some(table) => table[(c % q) as usize].clone(),
+            None => self.delta(q).cmul(c),
+        }
+    }
+
     /// The current output index of the garbling computation.
     fn current_output(&mut self) -> usize {
         let current = self.current_output;
@@ -76,41 +249,94 @@ impl<C: AbstractChannel, RNG: CryptoRng + RngCore, Wire: WireLabel> Garbler<C, R
     /// Get the deltas, consuming the Garbler.
     ///
     /// This is useful for reusing wires in multiple garbled circuit instances.
-    pub fn get_deltas(self) -> HashMap<u16, Wire> {
-        self.deltas
+    pub fn get_deltas(mut self) -> HashMap<Modulus, Wire> {
+        std::mem::take(&mut self.deltas)
+    }
+
+    /// The number of bytes of wire labels, garbled gates, and output decoding
+    /// information written to the channel so far.
+    pub fn bytes_written(&self) -> usize {
+        self.bytes_written
+    }
+
+    /// Write a block to the channel, counting it in `bytes_written`.
+    fn write_block(&mut self, block: &Block) -> std::io::Result<()> {
+        self.bytes_written += std::mem::size_of::<Block>();
+        self.channel.write_block(block)
+    }
+
+    /// Write a binary label or a ciphertext of the label width to the channel.
+    fn write_label(&mut self, block: &Block) -> std::io::Result<()> {
+        self.write_prefix(block, self.security.label_bytes())
+    }
+
+    /// Write a binary label or a ciphertext of the label width, held in a
+    /// `Block512`, to the channel.
+    fn write_wide_label(&mut self, label: &Block512) -> std::io::Result<()> {
+        let len = self.security.label_bytes();
+        self.bytes_written += len;
+        self.channel.write_bytes(label.prefix(len))
+    }
+
+    /// Write an output hash of the hash width to the channel.
+    fn write_hash(&mut self, block: &Block) -> std::io::Result<()> {
+        self.write_prefix(block, self.security.hash_bytes())
+    }
+
+    /// Write the first `len` bytes of `block` to the channel, counting them in
+    /// `bytes_written`.
+    fn write_prefix(&mut self, block: &Block, len: usize) -> std::io::Result<()> {
+        if len == std::mem::size_of::<Block>() {
+            return self.write_block(block);
+        }
+        self.bytes_written += len;
+        self.channel.write_bytes(&block.as_ref()[..len])
     }
 
     /// Send a wire over the established channel.
     pub fn send_wire(&mut self, wire: &Wire) -> Result<(), GarblerError> {
-        self.channel.write_block(&wire.as_block())?;
+        if wire.modulus() == 2 && Wire::MAX_LABEL_BITS > 128 {
+            self.write_wide_label(&wire.as_block512())?;
+        } else if wire.modulus() == 2 {
+            self.write_label(&wire.as_block())?;
+        } else {
+            self.write_block(&wire.as_block())?;
+        }
         Ok(())
     }
 
     /// Encode a wire, producing the zero wire as well as the encoded value.
-    pub fn encode_wire(&mut self, val: u16, modulus: u16) -> (Wire, Wire) {
+    pub fn encode_wire(&mut self, val: Modulus, modulus: Modulus) -> (Wire, Wire) {
         let zero = Wire::rand(&mut self.rng, modulus);
+        let zero = self.truncate(zero);
         let delta = self.delta(modulus);
         let enc = zero.plus(&delta.cmul(val));
         (zero, enc)
     }
 
     /// Encode many wires, producing zero wires as well as encoded values.
+    ///
+    /// With the `rayon` feature enabled, large inputs are encoded in parallel.
     pub fn encode_many_wires(
         &mut self,
-        vals: &[u16],
-        moduli: &[u16],
+        vals: &[Modulus],
+        moduli: &[Modulus],
     ) -> Result<(Vec<Wire>, Vec<Wire>), GarblerError> {
         if vals.len() != moduli.len() {
             return Err(GarblerError::EncodingError);
         }
-        assert!(vals.len() == moduli.len());
+        check_inputs(vals, moduli.iter().copied())?;
+        // Sample the zero wires serially so that the RNG stream matches
+        // `encode_wire`; only the label arithmetic is done in parallel.
         let mut gbs = Vec::with_capacity(vals.len());
-        let mut evs = Vec::with_capacity(vals.len());
-        for (x, q) in vals.iter().zip(moduli.iter()) {
-            let (gb, ev) = self.encode_wire(*x, *q);
-            gbs.push(gb);
-            evs.push(ev);
+        let mut deltas = Vec::with_capacity(vals.len());
+        for q in moduli.iter() {
+            let zero = Wire::rand(&mut self.rng, *q);
+            gbs.push(self.truncate(zero));
+            deltas.push(self.delta(*q));
         }
+        let inputs = gbs.iter().zip(deltas.iter()).zip(vals.iter()).collect_vec();
+        let evs = util::par_map(&inputs, |((zero, delta), &x)| zero.plus(&delta.cmul(x)));
         Ok((gbs, evs))
     }
 
@@ -120,8 +346,8 @@ impl<C: AbstractChannel, RNG: CryptoRng + RngCore, Wire: WireLabel> Garbler<C, R
         val: u128,
         modulus: u128,
     ) -> Result<(CrtBundle<Wire>, CrtBundle<Wire>), GarblerError> {
-        let ms = crate::util::factor(modulus);
-        let xs = crate::util::crt(val, &ms);
+        let ms = crate::util::factor_checked(modulus)?;
+        let xs = crate::util::crt_checked(val, &ms)?;
         let (gbs, evs) = self.encode_many_wires(&xs, &ms)?;
         Ok((CrtBundle::new(gbs), CrtBundle::new(evs)))
     }
@@ -137,27 +363,80 @@ impl<C: AbstractChannel, RNG: CryptoRng + RngCore, Wire: WireLabel> Garbler<C, R
         let (gbs, evs) = self.encode_many_wires(&xs, &ms)?;
         Ok((BinaryBundle::new(gbs), BinaryBundle::new(evs)))
     }
+}
 
-    /// Garbles an 'and' gate given two input wires and the delta.
-    ///
-    /// Outputs a tuple consisting of the two gates (that should be transfered to the evaluator)
-    /// and the next wire label for the garbler.
-    ///
-    /// Used internally as a subroutine to implement 'and' gates for `FancyBinary`.
-    fn garble_and_gate(
-        &mut self,
-        A: &WireMod2,
-        B: &WireMod2,
-        delta: &WireMod2,
-    ) -> (Block, Block, WireMod2) {
+/// Clear the bits of `wire` above the label width of `security` if it is
+/// binary.
+pub(crate) fn truncate<Wire: WireLabel>(security: SecurityParams, wire: Wire) -> Wire {
+    if security.label_bits() == Wire::MAX_LABEL_BITS || wire.modulus() != 2 {
+        return wire;
+    }
+    if Wire::MAX_LABEL_BITS > 128 {
+        let mut label = wire.as_block512();
+        <&mut [u8; 64]>::from(&mut label)[security.label_bytes()..].fill(0);
+        return Wire::from_block512(label, 2);
+    }
+    let block = Block::from(u128::from(wire.as_block()) & security.label_mask());
+    Wire::from_block(block, 2)
+}
+
+/// Garbles the `gate_num`-th 'and' gate given two input wires and the delta.
+///
+/// Outputs a tuple consisting of the two gates (that should be transfered to the evaluator)
+/// and the next wire label for the garbler.
+///
+/// Used internally as a subroutine to implement 'and' gates for `FancyBinary`.
+pub(crate) fn garble_and_gate(
+    A: &WireMod2,
+    B: &WireMod2,
+    delta: &WireMod2,
+    gate_num: usize,
+) -> (Block, Block, WireMod2) {
+    let gate = AndGate::new(A, B, delta);
+    let [a, b, x, y] = &gate.hashed;
+    let hashes = hash_wires([a, b, x, y], tweak2(gate_num as u64, 0));
+    gate.finish(hashes)
+}
+
+/// Garbles two 'and' gates as [`garble_and_gate`] does, given the input wires
+/// and number of each, hashing the labels of both at once.
+pub(crate) fn garble_and_gates(
+    gates: [(&WireMod2, &WireMod2, usize); 2],
+    delta: &WireMod2,
+) -> [(Block, Block, WireMod2); 2] {
+    let [(A0, B0, n0), (A1, B1, n1)] = gates;
+    let (gate0, gate1) = (AndGate::new(A0, B0, delta), AndGate::new(A1, B1, delta));
+    let [a0, b0, x0, y0] = &gate0.hashed;
+    let [a1, b1, x1, y1] = &gate1.hashed;
+    let (g0, g1) = (tweak2(n0 as u64, 0), tweak2(n1 as u64, 0));
+    let [h0, h1, h2, h3, h4, h5, h6, h7] = hash_wires_tweaked(
+        [a0, b0, x0, y0, a1, b1, x1, y1],
+        [g0, g0, g0, g0, g1, g1, g1, g1],
+    );
+    [
+        gate0.finish([h0, h1, h2, h3]),
+        gate1.finish([h4, h5, h6, h7]),
+    ]
+}
+
+/// An 'and' gate being garbled: the labels it hashes, and what finishing it
+/// takes besides their hashes, which do not depend on the gate number and
+/// so can be computed for several gates before hashing them together.
+struct AndGate<'a> {
+    A: &'a WireMod2,
+    D: &'a WireMod2,
+    alpha: Modulus,
+    r: Modulus,
+    idx: u8,
+    hashed: [WireMod2; 4],
+}
+
+impl<'a> AndGate<'a> {
+    fn new(A: &'a WireMod2, B: &WireMod2, D: &'a WireMod2) -> Self {
         let q = A.modulus();
-        let D = delta;
-        let gate_num = self.current_gate();
 
         let r = B.color(); // secret value known only to the garbler (ev knows r+b)
 
-        let g = tweak2(gate_num as u64, 0);
-
         // X = H(A+aD) + arD such that a + A.color == 0
         let alpha = A.color(); // alpha = -A.color
         let X1 = A.plus(&D.cmul(alpha));
@@ -166,50 +445,101 @@ impl<C: AbstractChannel, RNG: CryptoRng + RngCore, Wire: WireLabel> Garbler<C, R
         let beta = (q - B.color()) % q;
         let Y1 = B.plus(&D.cmul(beta));
 
-        let AD = A.plus(&D);
-        let BD = B.plus(&D);
+        let AD = A.plus(D);
+        let BD = B.plus(D);
 
         // idx is always boolean for binary gates, so it can be represented as a `u8`
         let a_selector = (A.color() as u8).into();
         let b_selector = (B.color() as u8).into();
 
-        let B = WireMod2::conditional_select(&BD, &B, b_selector);
+        let B = WireMod2::conditional_select(&BD, B, b_selector);
         let newA = WireMod2::conditional_select(&AD, A, a_selector);
         let idx = u8::conditional_select(&(r as u8), &0u8, a_selector);
 
-        let [hashA, hashB, hashX, hashY] = hash_wires([&newA, &B, &X1, &Y1], g);
+        AndGate {
+            A,
+            D,
+            alpha,
+            r,
+            idx,
+            hashed: [newA, B, X1, Y1],
+        }
+    }
 
-        let X = WireMod2::hash_to_mod(hashX, q).plus_mov(&D.cmul(alpha * r % q));
+    /// Finish the gate given the hashes of its labels.
+    fn finish(&self, hashes: [Block; 4]) -> (Block, Block, WireMod2) {
+        let (A, D) = (self.A, self.D);
+        let q = A.modulus();
+        let [hashA, hashB, hashX, hashY] = hashes;
+
+        let X = WireMod2::hash_to_mod(hashX, q).plus_mov(&D.cmul(self.alpha * self.r % q));
         let Y = WireMod2::hash_to_mod(hashY, q);
 
-        let gate0 =
-            hashA ^ Block::conditional_select(&X.as_block(), &X.plus(&D).as_block(), idx.into());
-        let gate1 = hashB ^ Y.plus(&A).as_block();
+        let gate0 = hashA
+            ^ Block::conditional_select(&X.as_block(), &X.plus(D).as_block(), self.idx.into());
+        let gate1 = hashB ^ Y.plus(A).as_block();
 
         (gate0, gate1, X.plus_mov(&Y))
     }
 }
 
+/// Garbles the `gate_num`-th 'and' gate of labels wider than 128 bits as
+/// [`garble_and_gate`] does, hashing with [`WideWireMod2::hash_wide`].
+pub(crate) fn garble_wide_and_gate(
+    A: &WideWireMod2,
+    B: &WideWireMod2,
+    delta: &WideWireMod2,
+    gate_num: usize,
+) -> (Block512, Block512, WideWireMod2) {
+    let D = delta;
+    let r = B.color();
+    let g = tweak2(gate_num as u64, 0);
+
+    // As in `garble_and_gate`, where `-1 = 1` mod 2.
+    let alpha = A.color();
+    let X1 = A.plus(&D.cmul(alpha));
+    let Y1 = B.plus(&D.cmul(B.color()));
+
+    let AD = A.plus(D);
+    let BD = B.plus(D);
+    let a_selector = (A.color() as u8).into();
+    let b_selector = (B.color() as u8).into();
+    let B = WideWireMod2::conditional_select(&BD, B, b_selector);
+    let newA = WideWireMod2::conditional_select(&AD, A, a_selector);
+    let idx = u8::conditional_select(&(r as u8), &0u8, a_selector);
+
+    let [hashA, hashB, hashX, hashY] = [&newA, &B, &X1, &Y1].map(|w| w.hash_wide(g));
+
+    let X = WideWireMod2::from_block512(hashX, 2).plus_mov(&D.cmul(alpha * r));
+    let Y = WideWireMod2::from_block512(hashY, 2);
+
+    let gate0 = hashA ^ WideWireMod2::conditional_select(&X, &X.plus(D), idx.into()).as_block512();
+    let gate1 = hashB ^ Y.plus(A).as_block512();
+
+    (gate0, gate1, X.plus_mov(&Y))
+}
+
 impl<C: AbstractChannel, RNG: RngCore + CryptoRng, Wire: WireLabel> FancyReveal
     for Garbler<C, RNG, Wire>
 {
-    fn reveal(&mut self, x: &Wire) -> Result<u16, GarblerError> {
+    fn reveal(&mut self, x: &Wire) -> Result<Modulus, GarblerError> {
         // The evaluator needs our cooperation in order to see the output.
         // Hence, we call output() ourselves.
         self.output(x)?;
         self.channel.flush()?;
-        let val = self.channel.read_u16()?;
-        Ok(val)
+        let mut val = [0; std::mem::size_of::<Modulus>()];
+        self.channel.read_bytes(&mut val)?;
+        Ok(Modulus::from_le_bytes(val))
     }
 }
 
 impl<C: AbstractChannel, RNG: RngCore + CryptoRng> FancyBinary for Garbler<C, RNG, WireMod2> {
     fn and(&mut self, A: &Self::Item, B: &Self::Item) -> Result<Self::Item, Self::Error> {
         let delta = self.delta(2);
-        let (gate0, gate1, C) = self.garble_and_gate(A, B, &delta);
-        self.channel.write_block(&gate0)?;
-        self.channel.write_block(&gate1)?;
-        Ok(C)
+        let (gate0, gate1, C) = garble_and_gate(A, B, &delta, self.current_gate());
+        self.write_label(&gate0)?;
+        self.write_label(&gate1)?;
+        Ok(self.truncate(C))
     }
 
     fn xor(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
@@ -224,6 +554,62 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng> FancyBinary for Garbler<C, RN
         let delta = self.delta(2);
         self.xor(&delta, x)
     }
+
+    /// Garble the gate as it says, as the next gate.
+    fn custom(
+        &mut self,
+        gate: &Arc<dyn CustomGate>,
+        inputs: &[Self::Item],
+    ) -> Result<Self::Item, Self::Error> {
+        check_arity(gate.as_ref(), inputs.len())?;
+        let delta = self.delta(2);
+        let gate_num = self.current_gate();
+        let (z, ciphertexts) = gate.garble(inputs, &delta, gate_num);
+        if ciphertexts.len() != gate.num_ciphertexts() {
+            return Err(GarblerError::FancyError(FancyError::InvalidArg(format!(
+                "custom gate {} made {} ciphertexts rather than {}",
+                gate.name(),
+                ciphertexts.len(),
+                gate.num_ciphertexts()
+            ))));
+        }
+        for ct in ciphertexts.iter() {
+            self.write_label(ct)?;
+        }
+        Ok(self.truncate(z))
+    }
+}
+
+impl<C: AbstractChannel, RNG: RngCore + CryptoRng> FancyBinary for Garbler<C, RNG, WideWireMod2> {
+    fn and(&mut self, A: &Self::Item, B: &Self::Item) -> Result<Self::Item, Self::Error> {
+        let delta = self.delta(2);
+        let (gate0, gate1, C) = garble_wide_and_gate(A, B, &delta, self.current_gate());
+        self.write_wide_label(&gate0)?;
+        self.write_wide_label(&gate1)?;
+        Ok(self.truncate(C))
+    }
+
+    fn xor(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        Ok(x.plus(y))
+    }
+
+    /// We can negate by having garbler xor wire with Delta
+    fn negate(&mut self, x: &Self::Item) -> Result<Self::Item, Self::Error> {
+        let delta = self.delta(2);
+        self.xor(&delta, x)
+    }
+
+    /// Refuse the gate: custom gates garble labels of 128 bits.
+    fn custom(
+        &mut self,
+        gate: &Arc<dyn CustomGate>,
+        _inputs: &[Self::Item],
+    ) -> Result<Self::Item, Self::Error> {
+        Err(GarblerError::FancyError(FancyError::InvalidArg(format!(
+            "custom gate {} cannot garble labels wider than 128 bits",
+            gate.name()
+        ))))
+    }
 }
 
 impl<C: AbstractChannel, RNG: RngCore + CryptoRng> FancyBinary for Garbler<C, RNG, AllWire> {
@@ -251,10 +637,10 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng> FancyBinary for Garbler<C, RN
         if let (AllWire::Mod2(ref A), AllWire::Mod2(ref B), AllWire::Mod2(ref delta)) =
             (x, y, self.delta(2))
         {
-            let (gate0, gate1, C) = self.garble_and_gate(A, B, delta);
-            self.channel.write_block(&gate0)?;
-            self.channel.write_block(&gate1)?;
-            return Ok(AllWire::Mod2(C));
+            let (gate0, gate1, C) = garble_and_gate(A, B, delta, self.current_gate());
+            self.write_label(&gate0)?;
+            self.write_label(&gate1)?;
+            return Ok(AllWire::Mod2(truncate(self.security, C)));
         }
         // If we got here, one of the wires isn't binary
         check_binary!(x);
@@ -269,20 +655,16 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng, Wire: WireLabel + ArithmeticW
     for Garbler<C, RNG, Wire>
 {
     fn add(&mut self, x: &Wire, y: &Wire) -> Result<Wire, GarblerError> {
-        if x.modulus() != y.modulus() {
-            return Err(GarblerError::FancyError(FancyError::UnequalModuli));
-        }
+        check_moduli("add", x.modulus(), y.modulus())?;
         Ok(x.plus(y))
     }
 
     fn sub(&mut self, x: &Wire, y: &Wire) -> Result<Wire, GarblerError> {
-        if x.modulus() != y.modulus() {
-            return Err(GarblerError::FancyError(FancyError::UnequalModuli));
-        }
+        check_moduli("sub", x.modulus(), y.modulus())?;
         Ok(x.minus(y))
     }
 
-    fn cmul(&mut self, x: &Wire, c: u16) -> Result<Wire, GarblerError> {
+    fn cmul(&mut self, x: &Wire, c: Modulus) -> Result<Wire, GarblerError> {
         Ok(x.cmul(c))
     }
 
@@ -308,7 +690,7 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng, Wire: WireLabel + ArithmeticW
                 return Err(GarblerError::AsymmetricHalfGateModuliMax8(qb));
             }
 
-            r = self.rng.gen_u16() % q;
+            r = self.rng.gen_residue(q);
             let t = tweak2(gate_num as u64, 1);
 
             let mut minitable = vec![u128::default(); qb as usize];
@@ -317,7 +699,7 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng, Wire: WireLabel + ArithmeticW
                 if b > 0 {
                     B_.plus_eq(&Db);
                 }
-                let new_color = ((r + b) % q) as u128;
+                let new_color = util::add_mod(r, b, q) as u128;
                 let ct = (u128::from(B_.hash(t)) & 0xFFFF) ^ new_color;
                 minitable[B_.color() as usize] = ct;
             }
@@ -335,16 +717,17 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng, Wire: WireLabel + ArithmeticW
 
         // X = H(A+aD) + arD such that a + A.color == 0
         let alpha = (q - A.color()) % q; // alpha = -A.color
-        let X1 = A.plus(&D.cmul(alpha));
+        let X1 = A.plus(&self.delta_cmul(q, alpha));
 
         // Y = H(B + bD) + (b + r)A such that b + B.color == 0
         let beta = (qb - B.color()) % qb;
-        let Y1 = B.plus(&Db.cmul(beta));
+        let Y1 = B.plus(&self.delta_cmul(qb, beta));
 
         let [hashX, hashY] = hash_wires([&X1, &Y1], g);
 
-        let X = Wire::hash_to_mod(hashX, q).plus_mov(&D.cmul(alpha * r % q));
-        let Y = Wire::hash_to_mod(hashY, q).plus_mov(&A.cmul((beta + r) % q));
+        let X =
+            Wire::hash_to_mod(hashX, q).plus_mov(&self.delta_cmul(q, util::mul_mod(alpha, r, q)));
+        let Y = Wire::hash_to_mod(hashY, q).plus_mov(&A.cmul(util::add_mod(beta, r, q)));
 
         let mut precomp = Vec::with_capacity(q as usize);
         // precompute a lookup table of X.minus(&D_cmul[(a * r % q)])
@@ -368,7 +751,7 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng, Wire: WireLabel + ArithmeticW
             // G = H(A+aD) ^ X+a(-r)D = H(A+aD) ^ X-arD
             if A_.color() != 0 {
                 gate[A_.color() as usize - 1] =
-                    A_.hash(g) ^ precomp[((q - (a * r % q)) % q) as usize];
+                    A_.hash(g) ^ precomp[((q - util::mul_mod(a, r, q)) % q) as usize];
             }
         }
         precomp.clear();
@@ -392,21 +775,32 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng, Wire: WireLabel + ArithmeticW
             // G = H(B+bD) + Y-(b+r)A
             if B_.color() != 0 {
                 gate[q as usize - 1 + B_.color() as usize - 1] =
-                    B_.hash(g) ^ precomp[((q - ((b + r) % q)) % q) as usize];
+                    B_.hash(g) ^ precomp[((q - util::add_mod(b, r, q)) % q) as usize];
             }
         }
 
         for block in gate.iter() {
-            self.channel.write_block(block)?;
+            self.write_block(block)?;
         }
-        Ok(X.plus_mov(&Y))
+        Ok(self.truncate(X.plus_mov(&Y)))
     }
 
-    fn proj(&mut self, A: &Wire, q_out: u16, tt: Option<Vec<u16>>) -> Result<Wire, GarblerError> {
+    fn proj(
+        &mut self,
+        A: &Wire,
+        q_out: Modulus,
+        tt: Option<Vec<Modulus>>,
+    ) -> Result<Wire, GarblerError> {
         let tt = tt.ok_or(GarblerError::TruthTableRequired)?;
 
         let q_in = A.modulus();
-        let mut gate = vec![Block::default(); q_in as usize - 1];
+        let table = match self.proj_tables.entry((q_in, q_out, tt)) {
+            Entry::Occupied(entry) => entry.get().clone(),
+            Entry::Vacant(entry) => {
+                let table = ProjTable::new(q_in, q_out, &entry.key().2)?;
+                entry.insert(Arc::new(table)).clone()
+            }
+        };
 
         let tao = A.color();
         let g = tweak(self.current_gate());
@@ -414,43 +808,41 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng, Wire: WireLabel + ArithmeticW
         let Din = self.delta(q_in);
         let Dout = self.delta(q_out);
 
+        // the input whose label has color zero
+        let x0 = (q_in - tao) % q_in;
+        let mut A_ = A.plus(&self.delta_cmul(q_in, x0));
+
         // output zero-wire
         // W_g^0 <- -H(g, W_{a_1}^0 - \tao\Delta_m) - \phi(-\tao)\Delta_n
-        let C = A
-            .plus(&Din.cmul((q_in - tao) % q_in))
+        let y0 = table.outputs[table.slots[x0 as usize]];
+        let C = A_
             .hashback(g, q_out)
-            .plus_mov(&Dout.cmul((q_out - tt[((q_in - tao) % q_in) as usize]) % q_out));
+            .plus_mov(&self.delta_cmul(q_out, (q_out - y0) % q_out));
+        let C = self.truncate(C);
 
-        // precompute `let C_ = C.plus(&Dout.cmul(tt[x as usize]))`
+        // precompute `C.plus(&Dout.cmul(y))` for the outputs `y` of the table
         let C_precomputed = {
             let mut C_ = C.clone();
-            (0..q_out)
-                .map(|x| {
-                    if x > 0 {
+            let mut y_ = 0;
+            table
+                .outputs
+                .iter()
+                .map(|&y| {
+                    for _ in y_..y {
                         C_.plus_eq(&Dout);
                     }
+                    y_ = y;
                     C_.as_block()
                 })
                 .collect::<Vec<Block>>()
         };
 
-        let mut A_ = A.clone();
-        for x in 0..q_in {
-            if x > 0 {
-                A_.plus_eq(&Din); // avoiding expensive cmul for `A_ = A.plus(&Din.cmul(x))`
-            }
-
-            let ix = (tao as usize + x as usize) % q_in as usize;
-            if ix == 0 {
-                continue;
-            }
-
-            let ct = A_.hash(g) ^ C_precomputed[tt[x as usize] as usize];
-            gate[ix - 1] = ct;
-        }
-
-        for block in gate.iter() {
-            self.channel.write_block(block)?;
+        // the ciphertext for color `ix` encrypts the output of input `x0 + ix`
+        for ix in 1..q_in {
+            A_.plus_eq(&Din); // avoiding expensive cmul for `A_ = A.plus(&Din.cmul(x))`
+            let x = util::add_mod(x0, ix, q_in);
+            let ct = A_.hash(g) ^ C_precomputed[table.slots[x as usize]];
+            self.write_block(&ct)?;
         }
         Ok(C)
     }
@@ -462,20 +854,31 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng, Wire: WireLabel> Fancy
     type Item = Wire;
     type Error = GarblerError;
 
-    fn constant(&mut self, x: u16, q: u16) -> Result<Wire, GarblerError> {
+    fn constant(&mut self, x: Modulus, q: Modulus) -> Result<Wire, GarblerError> {
         let zero = Wire::rand(&mut self.rng, q);
-        let wire = zero.plus(&self.delta(q).cmul_eq(x));
+        let zero = self.truncate(zero);
+        let wire = zero.plus(&self.delta_cmul(q, x));
         self.send_wire(&wire)?;
         Ok(zero)
     }
 
-    fn output(&mut self, X: &Wire) -> Result<Option<u16>, GarblerError> {
+    fn output(&mut self, X: &Wire) -> Result<Option<Modulus>, GarblerError> {
         let q = X.modulus();
         let i = self.current_output();
-        let D = self.delta(q);
-        for k in 0..q {
-            let block = X.plus(&D.cmul(k)).hash(output_tweak(i, k));
-            self.channel.write_block(&block)?;
+        match self.delta_table(q) {
+            Some(table) => {
+                for (k, kD) in table.iter().enumerate() {
+                    let block = X.plus(kD).hash(output_tweak(i, k as Modulus));
+                    self.write_hash(&block)?;
+                }
+            }
+            None => {
+                let D = self.delta(q);
+                for k in 0..q {
+                    let block = X.plus(&D.cmul(k)).hash(output_tweak(i, k));
+                    self.write_hash(&block)?;
+                }
+            }
         }
         Ok(None)
     }
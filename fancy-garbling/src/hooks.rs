@@ -0,0 +1,171 @@
+//! Progress reports and cooperative cancellation for long garblings and
+//! evaluations.
+//!
+//! [`Hooks`] carry a progress callback and a cancellation check, which are
+//! called every [`Hooks::interval`] gates of a circuit, at the checkpoints of
+//! [`EvaluableCircuit::eval_with_checkpoints`](crate::circuit::EvaluableCircuit::eval_with_checkpoints).
+//! [`classic::garble_with_hooks`](crate::classic::garble_with_hooks) and
+//! [`GarbledCircuit::eval_with_hooks`](crate::classic::GarbledCircuit::eval_with_hooks)
+//! take them, as does the `twopac` builder:
+//!
+//! ```ignore
+//! let token = CancelToken::new();
+//! let hooks = Hooks::new()
+//!     .on_progress(|done, total| eprintln!("{}/{} gates", done, total))
+//!     .cancel_token(&token);
+//! // Another thread may call `token.cancel()` to stop the garbling, which
+//! // then fails with `GarblerError::Cancelled`.
+//! let (en, gc) = classic::garble_with_hooks::<WireMod2, _>(&circ, AesRng::new(), &hooks)?;
+//! ```
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// The number of gates between checkpoints of [`Hooks::new`].
+pub const DEFAULT_INTERVAL: usize = 4096;
+
+type ProgressFn = dyn Fn(usize, usize) + Send + Sync;
+type CancelFn = dyn Fn() -> bool + Send + Sync;
+
+/// A progress callback and a cancellation check, called every
+/// [`interval`](Hooks::interval) gates.
+///
+/// Hooks are cheap to clone, sharing their callbacks, and are `Send` and
+/// `Sync`, so that a configuration holding them can be used from several
+/// threads.
+#[derive(Clone)]
+pub struct Hooks {
+    interval: usize,
+    on_progress: Option<Arc<ProgressFn>>,
+    should_cancel: Option<Arc<CancelFn>>,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Hooks::new()
+    }
+}
+
+impl std::fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Hooks")
+            .field("interval", &self.interval)
+            .field("on_progress", &self.on_progress.is_some())
+            .field("should_cancel", &self.should_cancel.is_some())
+            .finish()
+    }
+}
+
+impl Hooks {
+    /// Make hooks that do nothing, with checkpoints every
+    /// [`DEFAULT_INTERVAL`] gates.
+    pub fn new() -> Self {
+        Hooks {
+            interval: DEFAULT_INTERVAL,
+            on_progress: None,
+            should_cancel: None,
+        }
+    }
+
+    /// Call the hooks every `gates` gates rather than every
+    /// [`DEFAULT_INTERVAL`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `gates` is zero.
+    pub fn every(mut self, gates: usize) -> Self {
+        assert!(gates > 0, "the checkpoint interval must be positive");
+        self.interval = gates;
+        self
+    }
+
+    /// Call `f` with the number of gates done and the total number of gates
+    /// at every checkpoint, and once more when every gate is done.
+    pub fn on_progress(mut self, f: impl Fn(usize, usize) + Send + Sync + 'static) -> Self {
+        self.on_progress = Some(Arc::new(f));
+        self
+    }
+
+    /// Stop at the first checkpoint at which `f` returns `true`.
+    pub fn should_cancel(mut self, f: impl Fn() -> bool + Send + Sync + 'static) -> Self {
+        self.should_cancel = Some(Arc::new(f));
+        self
+    }
+
+    /// Stop at the first checkpoint after `token` is cancelled.
+    pub fn cancel_token(self, token: &CancelToken) -> Self {
+        let token = token.clone();
+        self.should_cancel(move || token.is_cancelled())
+    }
+
+    /// The number of gates between checkpoints.
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    /// Report the progress of `done` gates of `total`, without checking for
+    /// cancellation.
+    pub(crate) fn progress(&self, done: usize, total: usize) {
+        if let Some(f) = &self.on_progress {
+            f(done, total);
+        }
+    }
+
+    /// Report the progress of `done` gates of `total`, and return whether to
+    /// cancel.
+    pub(crate) fn checkpoint(&self, done: usize, total: usize) -> bool {
+        self.progress(done, total);
+        matches!(&self.should_cancel, Some(f) if f())
+    }
+}
+
+/// A flag that cancels the garblings and evaluations whose [`Hooks`] check
+/// it, set from any thread.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// Make a token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancel the garblings and evaluations checking this token, at their
+    /// next checkpoint.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether [`CancelToken::cancel`] has been called.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn checkpoint() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let calls_ = calls.clone();
+        let token = CancelToken::new();
+        let hooks = Hooks::new()
+            .every(10)
+            .on_progress(move |done, total| {
+                assert_eq!(total, 100);
+                calls_.store(done, Ordering::Relaxed);
+            })
+            .cancel_token(&token);
+        assert!(!hooks.checkpoint(10, 100));
+        assert_eq!(calls.load(Ordering::Relaxed), 10);
+        token.cancel();
+        assert!(hooks.checkpoint(20, 100));
+        assert_eq!(calls.load(Ordering::Relaxed), 20);
+        assert!(!Hooks::new().checkpoint(1, 1));
+    }
+}
@@ -1,10 +1,14 @@
 //! `Informer` runs a fancy computation and learns information from it.
 
 use crate::{
+    circuit::CustomGate,
     fancy::{Fancy, FancyInput, FancyReveal, HasModulus},
-    FancyArithmetic, FancyBinary,
+    FancyArithmetic, FancyBinary, Modulus,
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
 };
-use std::collections::{HashMap, HashSet};
 
 /// Implements `Fancy`. Used to learn information about a `Fancy` computation in
 /// a lightweight way.
@@ -12,22 +16,48 @@ pub struct Informer<F: Fancy> {
     /// The underlying fancy object.
     pub underlying: F,
     stats: InformerStats,
+    // The distinct `(value, modulus)` constants seen so far.
+    constants: HashSet<(Modulus, Modulus)>,
+    // The distinct `(q_in, q_out, truth table)` projections seen so far.
+    proj_tables: HashSet<(Modulus, Modulus, Vec<Modulus>)>,
 }
 
 /// The statistics revealed by the informer.
-#[derive(Clone, Debug)]
+///
+/// With the `serde` feature, this serializes with the field names below, which
+/// are kept stable so that tools can track the cost of a computation; the
+/// `Display` output is for people and may change.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct InformerStats {
-    garbler_input_moduli: Vec<u16>,
-    evaluator_input_moduli: Vec<u16>,
-    constants: HashSet<(u16, u16)>,
-    outputs: Vec<u16>,
-    nadds: usize,
-    nsubs: usize,
-    ncmuls: usize,
-    nmuls: usize,
-    nprojs: usize,
-    nciphertexts: usize,
-    moduli: HashMap<u16, usize>,
+    /// Moduli of garbler inputs, in order.
+    pub garbler_input_moduli: Vec<Modulus>,
+    /// Moduli of evaluator inputs, in order.
+    pub evaluator_input_moduli: Vec<Modulus>,
+    /// Moduli of outputs, in order, not counting the outputs of constant
+    /// gates, which are public.
+    pub output_moduli: Vec<Modulus>,
+    /// Number of distinct constants.
+    pub nconsts: usize,
+    /// Number of additions, including binary XORs and negations.
+    pub nadds: usize,
+    /// Number of subtractions.
+    pub nsubs: usize,
+    /// Number of scalar multiplications.
+    pub ncmuls: usize,
+    /// Number of multiplications, including binary ANDs.
+    pub nmuls: usize,
+    /// Number of projections.
+    pub nprojs: usize,
+    /// Number of projections whose input modulus, output modulus and truth
+    /// table match an earlier projection.
+    pub nproj_table_hits: usize,
+    /// Number of ciphertexts in garbled gates, not counting outputs.
+    pub nciphertexts: usize,
+    /// Number of custom gates.
+    pub ncustoms: usize,
+    /// The number of gates producing a wire of each modulus.
+    pub moduli: BTreeMap<Modulus, usize>,
 }
 
 impl InformerStats {
@@ -37,7 +67,7 @@ impl InformerStats {
     }
 
     /// Moduli of garbler inputs in the fancy computation.
-    pub fn garbler_input_moduli(&self) -> Vec<u16> {
+    pub fn garbler_input_moduli(&self) -> Vec<Modulus> {
         self.garbler_input_moduli.clone()
     }
 
@@ -47,23 +77,23 @@ impl InformerStats {
     }
 
     /// Moduli of evaluator inputs in the fancy computation.
-    pub fn evaluator_input_moduli(&self) -> Vec<u16> {
+    pub fn evaluator_input_moduli(&self) -> Vec<Modulus> {
         self.evaluator_input_moduli.clone()
     }
 
     /// Number of constants in the fancy computation.
     pub fn num_consts(&self) -> usize {
-        self.constants.len()
+        self.nconsts
     }
 
     /// Number of outputs in the fancy computation.
     pub fn num_outputs(&self) -> usize {
-        self.outputs.len()
+        self.output_moduli.len()
     }
 
     /// Number of output ciphertexts.
     pub fn num_output_ciphertexts(&self) -> usize {
-        self.outputs.iter().map(|&m| m as usize).sum()
+        self.output_moduli.iter().map(|&m| m as usize).sum()
     }
 
     /// Number of additions in the fancy computation.
@@ -91,10 +121,28 @@ impl InformerStats {
         self.nprojs
     }
 
+    /// Number of projections whose input modulus, output modulus and truth table
+    /// match an earlier projection. The garbler reuses its analysis of the truth
+    /// table for these.
+    pub fn num_proj_table_hits(&self) -> usize {
+        self.nproj_table_hits
+    }
+
     /// Number of ciphertexts in the fancy computation.
     pub fn num_ciphertexts(&self) -> usize {
         self.nciphertexts
     }
+
+    /// Number of custom gates in the fancy computation.
+    pub fn num_customs(&self) -> usize {
+        self.ncustoms
+    }
+
+    /// Serialize the statistics as JSON.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("statistics always serialize")
+    }
 }
 
 impl std::fmt::Display for InformerStats {
@@ -166,7 +214,11 @@ impl std::fmt::Display for InformerStats {
         writeln!(f, "  subtractions:       {:16}", self.num_subs())?;
         writeln!(f, "  cmuls:              {:16}", self.num_cmuls())?;
         writeln!(f, "  projections:        {:16}", self.num_projs())?;
+        writeln!(f, "  proj table hits:    {:16}", self.num_proj_table_hits())?;
         writeln!(f, "  multiplications:    {:16}", self.num_muls())?;
+        if self.num_customs() > 0 {
+            writeln!(f, "  custom gates:       {:16}", self.num_customs())?;
+        }
         let cs = self.num_ciphertexts();
         let kb = cs as f64 * 128.0 / 1000.0;
         let mb = kb / 1000.0;
@@ -189,19 +241,9 @@ impl<F: Fancy> Informer<F> {
     pub fn new(underlying: F) -> Informer<F> {
         Informer {
             underlying,
-            stats: InformerStats {
-                garbler_input_moduli: Vec::new(),
-                evaluator_input_moduli: Vec::new(),
-                constants: HashSet::new(),
-                outputs: Vec::new(),
-                nadds: 0,
-                nsubs: 0,
-                ncmuls: 0,
-                nmuls: 0,
-                nprojs: 0,
-                nciphertexts: 0,
-                moduli: HashMap::new(),
-            },
+            stats: InformerStats::default(),
+            constants: HashSet::new(),
+            proj_tables: HashSet::new(),
         }
     }
 
@@ -210,7 +252,7 @@ impl<F: Fancy> Informer<F> {
         self.stats.clone()
     }
 
-    fn update_moduli(&mut self, q: u16) {
+    fn update_moduli(&mut self, q: Modulus) {
         let entry = self.stats.moduli.entry(q).or_insert(0);
         *entry += 1;
     }
@@ -222,7 +264,7 @@ impl<F: Fancy + FancyInput<Item = <F as Fancy>::Item, Error = <F as Fancy>::Erro
     type Item = <F as Fancy>::Item;
     type Error = <F as Fancy>::Error;
 
-    fn receive_many(&mut self, moduli: &[u16]) -> Result<Vec<Self::Item>, Self::Error> {
+    fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<Self::Item>, Self::Error> {
         self.stats
             .garbler_input_moduli
             .extend(moduli.iter().cloned());
@@ -231,8 +273,8 @@ impl<F: Fancy + FancyInput<Item = <F as Fancy>::Item, Error = <F as Fancy>::Erro
 
     fn encode_many(
         &mut self,
-        values: &[u16],
-        moduli: &[u16],
+        values: &[Modulus],
+        moduli: &[Modulus],
     ) -> Result<Vec<Self::Item>, Self::Error> {
         self.stats
             .garbler_input_moduli
@@ -265,6 +307,20 @@ impl<F: FancyBinary> FancyBinary for Informer<F> {
         self.update_moduli(x.modulus());
         Ok(result)
     }
+
+    /// Count the gate and the ciphertexts of its garbled form, whether or not
+    /// the underlying object garbles it natively.
+    fn custom(
+        &mut self,
+        gate: &Arc<dyn CustomGate>,
+        inputs: &[Self::Item],
+    ) -> Result<Self::Item, Self::Error> {
+        let result = self.underlying.custom(gate, inputs)?;
+        self.stats.ncustoms += 1;
+        self.stats.nciphertexts += gate.num_ciphertexts();
+        self.update_moduli(2);
+        Ok(result)
+    }
 }
 
 impl<F: FancyArithmetic> FancyArithmetic for Informer<F> {
@@ -286,7 +342,7 @@ impl<F: FancyArithmetic> FancyArithmetic for Informer<F> {
         Ok(result)
     }
 
-    fn cmul(&mut self, x: &Self::Item, y: u16) -> Result<Self::Item, Self::Error> {
+    fn cmul(&mut self, x: &Self::Item, y: Modulus) -> Result<Self::Item, Self::Error> {
         let result = self.underlying.cmul(x, y)?;
         self.stats.ncmuls += 1;
         self.update_moduli(x.modulus());
@@ -311,11 +367,17 @@ impl<F: FancyArithmetic> FancyArithmetic for Informer<F> {
     fn proj(
         &mut self,
         x: &Self::Item,
-        q: u16,
-        tt: Option<Vec<u16>>,
+        q: Modulus,
+        tt: Option<Vec<Modulus>>,
     ) -> Result<Self::Item, Self::Error> {
+        let key = tt.as_ref().map(|tt| (x.modulus(), q, tt.clone()));
         let result = self.underlying.proj(x, q, tt)?;
         self.stats.nprojs += 1;
+        if let Some(key) = key {
+            if !self.proj_tables.insert(key) {
+                self.stats.nproj_table_hits += 1;
+            }
+        }
         self.stats.nciphertexts += x.modulus() as usize - 1;
         self.update_moduli(q);
         Ok(result)
@@ -326,21 +388,95 @@ impl<F: Fancy> Fancy for Informer<F> {
     type Item = F::Item;
     type Error = F::Error;
 
-    fn constant(&mut self, val: u16, q: u16) -> Result<Self::Item, Self::Error> {
-        self.stats.constants.insert((val, q));
+    fn constant(&mut self, val: Modulus, q: Modulus) -> Result<Self::Item, Self::Error> {
+        if self.constants.insert((val, q)) {
+            self.stats.nconsts += 1;
+        }
         self.update_moduli(q);
         self.underlying.constant(val, q)
     }
 
-    fn output(&mut self, x: &Self::Item) -> Result<Option<u16>, Self::Error> {
+    fn output(&mut self, x: &Self::Item) -> Result<Option<Modulus>, Self::Error> {
         let result = self.underlying.output(x)?;
-        self.stats.outputs.push(x.modulus());
+        self.stats.output_moduli.push(x.modulus());
         Ok(result)
     }
 }
 
 impl<F: Fancy + FancyReveal> FancyReveal for Informer<F> {
-    fn reveal(&mut self, x: &Self::Item) -> Result<u16, Self::Error> {
+    fn reveal(&mut self, x: &Self::Item) -> Result<Modulus, Self::Error> {
         self.underlying.reveal(x)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dummy::Dummy, BinaryGadgets, BundleGadgets};
+
+    #[test]
+    fn proj_table_hits() {
+        let mut informer = Informer::new(Dummy::new());
+        let x = informer.encode(0, 5).unwrap();
+        let tt = vec![1, 0, 2, 2, 1];
+        for _ in 0..3 {
+            informer.proj(&x, 3, Some(tt.clone())).unwrap();
+        }
+        informer.proj(&x, 4, Some(tt)).unwrap();
+        informer.proj(&x, 3, Some(vec![0; 5])).unwrap();
+        let stats = informer.stats();
+        assert_eq!(stats.num_projs(), 5);
+        assert_eq!(stats.num_proj_table_hits(), 2);
+    }
+
+    #[test]
+    fn unequal_moduli() {
+        use crate::errors::{DummyError, FancyError};
+
+        let mut informer = Informer::new(Dummy::new());
+        let x = informer.encode(0, 3).unwrap();
+        let y = informer.encode(0, 5).unwrap();
+        assert!(matches!(
+            informer.add(&x, &y),
+            Err(DummyError::FancyError(FancyError::UnequalModuli {
+                op: "add",
+                x: 3,
+                y: 5
+            }))
+        ));
+        assert!(informer.sub(&x, &y).is_err());
+        let stats = informer.stats();
+        assert_eq!((stats.nadds, stats.nsubs), (0, 0));
+    }
+
+    #[test]
+    fn adder64_stats() {
+        let mut informer = Informer::new(Dummy::new());
+        let x = informer.bin_encode(0, 64).unwrap();
+        let y = informer.bin_encode(0, 64).unwrap();
+        let z = informer.bin_addition_no_carry(&x, &y).unwrap();
+        informer.output_bundle(&z).unwrap();
+        let stats = informer.stats();
+        assert_eq!(stats.garbler_input_moduli, vec![2; 128]);
+        assert_eq!(stats.output_moduli, vec![2; 64]);
+        assert_eq!(stats.nmuls, 63);
+        assert_eq!(stats.nadds, 251);
+        assert_eq!(stats.nciphertexts, 126);
+        assert_eq!(stats.moduli, BTreeMap::from([(2, 314)]));
+        assert!(stats
+            .to_string()
+            .contains("multiplications:                  63"));
+
+        #[cfg(feature = "serde")]
+        {
+            let json: serde_json::Value = serde_json::from_str(&stats.to_json()).unwrap();
+            assert_eq!(json["nmuls"], 63);
+            assert_eq!(json["nadds"], 251);
+            assert_eq!(json["nciphertexts"], 126);
+            assert_eq!(json["nconsts"], 0);
+            assert_eq!(json["evaluator_input_moduli"], serde_json::json!([]));
+            assert_eq!(json["output_moduli"].as_array().unwrap().len(), 64);
+            assert_eq!(json["moduli"], serde_json::json!({ "2": 314 }));
+        }
+    }
+}
@@ -12,17 +12,53 @@
 #![deny(missing_docs)]
 // TODO: when https://git.io/JYTnW gets stabilized add the readme as module docs.
 
+#[cfg(feature = "std")]
+#[cfg_attr(not(test), deny(clippy::unwrap_used))]
+pub mod apps;
+#[cfg(all(unix, feature = "std", any(test, feature = "test-utils")))]
+pub mod check;
+// The modules below that read circuits, garbled circuits, and protocol
+// messages from outside must fail on bad input rather than panic.
+#[cfg_attr(not(test), deny(clippy::unwrap_used))]
 pub mod circuit;
+#[cfg_attr(not(test), deny(clippy::unwrap_used))]
 pub mod classic;
 pub mod depth_informer;
 pub mod dummy;
 pub mod errors;
 mod fancy;
+#[cfg_attr(not(test), deny(clippy::unwrap_used))]
+pub mod format;
+#[cfg_attr(not(test), deny(clippy::unwrap_used))]
 mod garble;
+pub mod hooks;
 pub mod informer;
+#[cfg(feature = "std")]
+#[cfg_attr(not(test), deny(clippy::unwrap_used))]
 mod parser;
+#[cfg_attr(not(test), deny(clippy::unwrap_used))]
+pub mod plan;
+mod security;
+#[cfg(feature = "stdlib")]
+pub mod stdlib;
+#[cfg(feature = "std")]
+#[cfg_attr(not(test), deny(clippy::unwrap_used))]
 pub mod twopac;
 pub mod util;
 mod wire;
 
-pub use crate::{errors::FancyError, fancy::*, garble::*, wire::*};
+pub use crate::{
+    errors::FancyError, fancy::*, garble::*, security::SecurityParams, util::BitOrder, wire::*,
+};
+
+/// The type of moduli, and of the values on wires.
+///
+/// This is `u16`, or `u32` with the `wide-moduli` feature.
+#[cfg(not(feature = "wide-moduli"))]
+pub type Modulus = u16;
+
+/// The type of moduli, and of the values on wires.
+///
+/// This is `u16`, or `u32` with the `wide-moduli` feature.
+#[cfg(feature = "wide-moduli")]
+pub type Modulus = u32;
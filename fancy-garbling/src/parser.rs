@@ -5,19 +5,313 @@ use crate::{
     circuit::{BinaryCircuit, BinaryGate, CircuitRef, CircuitType},
     errors::CircuitParserError as Error,
 };
+#[cfg(feature = "regex")]
 use regex::{Captures, Regex};
-use std::str::FromStr;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    str::FromStr,
+};
+
+/// The most gates to reserve space for up front. The gate count in the header
+/// is not trusted beyond this, since the file may not contain as many gates.
+const MAX_RESERVED_GATES: usize = 1 << 20;
+
+/// The default of each of the [`ParseLimits`].
+pub const DEFAULT_PARSE_LIMIT: usize = 1 << 31;
+
+/// Limits on the size of the circuit files [`BinaryCircuit::parse_with_limits`]
+/// reads, so that a hostile file cannot make the parser exhaust memory.
+///
+/// The counts in the header are checked against the limits before anything is
+/// allocated for them, and the file is read no further than its limit. Each
+/// limit is [`DEFAULT_PARSE_LIMIT`] by default, which is well beyond the
+/// circuits in use, and is what [`BinaryCircuit::parse`] enforces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseLimits {
+    max_gates: usize,
+    max_wires: usize,
+    max_bytes: u64,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits {
+            max_gates: DEFAULT_PARSE_LIMIT,
+            max_wires: DEFAULT_PARSE_LIMIT,
+            max_bytes: DEFAULT_PARSE_LIMIT as u64,
+        }
+    }
+}
+
+impl ParseLimits {
+    /// The default limits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept at most `n` gates, as counted in the header and as found in
+    /// the file, not counting inputs. A `MAND` line counts as its AND gates.
+    pub fn max_gates(mut self, n: usize) -> Self {
+        self.max_gates = n;
+        self
+    }
+
+    /// Accept at most `n` wires, as counted in the header.
+    pub fn max_wires(mut self, n: usize) -> Self {
+        self.max_wires = n;
+        self
+    }
+
+    /// Read at most `n` bytes of the file.
+    pub fn max_bytes(mut self, n: u64) -> Self {
+        self.max_bytes = n;
+        self
+    }
+
+    /// Fail if `got` exceeds the limit `max` on `what`.
+    fn check(what: &'static str, got: usize, max: usize) -> Result<(), Error> {
+        if got > max {
+            return Err(Error::LimitExceeded {
+                what,
+                max: max as u64,
+            });
+        }
+        Ok(())
+    }
+}
 
 enum GateType {
     AndGate,
     XorGate,
 }
 
+enum UnaryType {
+    Inv,
+    Eq,
+    Eqw,
+}
+
+/// Matches the lines of a circuit file by splitting them into
+/// whitespace-separated tokens.
+#[cfg(not(feature = "regex"))]
+struct LineParser;
+
+#[cfg(not(feature = "regex"))]
+impl LineParser {
+    fn new() -> Result<Self, Error> {
+        Ok(LineParser)
+    }
+
+    /// Parse the first `N` tokens of `line` as integers.
+    fn header<const N: usize>(&self, line: &str) -> Result<[usize; N], Error> {
+        let tokens = tokens::<N>(line)?;
+        let mut out = [0; N];
+        for (x, t) in out.iter_mut().zip(tokens) {
+            *x = token2int(line, t)?;
+        }
+        Ok(out)
+    }
+
+    fn blank(&self, line: &str) -> Result<(), Error> {
+        if line.ends_with('\n') {
+            Ok(())
+        } else {
+            Err(Error::ParseLineError(line.to_string()))
+        }
+    }
+
+    /// Parse the whitespace-separated integers of `line`.
+    fn ints(&self, line: &str) -> Result<Vec<usize>, Error> {
+        line.split_whitespace()
+            .map(|t| token2int(line, t))
+            .collect()
+    }
+
+    /// Parse `1 1 x out INV`, `1 1 c out EQ` or `1 1 x out EQW`.
+    fn unary(&self, line: &str) -> Result<([usize; 2], UnaryType), Error> {
+        let (refs, typ) = match tokens::<5>(line)? {
+            ["1", "1", x, out, "INV"] => ([x, out], UnaryType::Inv),
+            ["1", "1", c, out, "EQ"] => ([c, out], UnaryType::Eq),
+            ["1", "1", x, out, "EQW"] => ([x, out], UnaryType::Eqw),
+            _ => return Err(Error::ParseLineError(line.to_string())),
+        };
+        Ok(([token2int(line, refs[0])?, token2int(line, refs[1])?], typ))
+    }
+
+    /// Parse `2 1 x y out AND` or `2 1 x y out XOR`.
+    fn binary(&self, line: &str) -> Result<([usize; 3], GateType), Error> {
+        let (refs, typ) = match tokens::<6>(line)? {
+            ["2", "1", x, y, out, "AND"] => ([x, y, out], GateType::AndGate),
+            ["2", "1", x, y, out, "XOR"] => ([x, y, out], GateType::XorGate),
+            _ => return Err(Error::ParseLineError(line.to_string())),
+        };
+        Ok((
+            [
+                token2int(line, refs[0])?,
+                token2int(line, refs[1])?,
+                token2int(line, refs[2])?,
+            ],
+            typ,
+        ))
+    }
+
+    /// Parse `2k k x_1 .. x_k y_1 .. y_k out_1 .. out_k MAND`.
+    fn mand(&self, line: &str) -> Result<Vec<[usize; 3]>, Error> {
+        let mut tokens = line.split_whitespace();
+        if tokens.next_back() != Some("MAND") {
+            return Err(Error::ParseLineError(line.to_string()));
+        }
+        let ints = tokens
+            .map(|t| token2int(line, t))
+            .collect::<Result<Vec<_>, _>>()?;
+        mand_gates(line, &ints)
+    }
+}
+
+/// The first `N` whitespace-separated tokens of `line`.
+#[cfg(not(feature = "regex"))]
+fn tokens<const N: usize>(line: &str) -> Result<[&str; N], Error> {
+    let mut iter = line.split_whitespace();
+    let mut out = [""; N];
+    for t in out.iter_mut() {
+        *t = iter
+            .next()
+            .ok_or_else(|| Error::ParseLineError(line.to_string()))?;
+    }
+    Ok(out)
+}
+
+/// Parse a token of `line` that must consist of decimal digits.
+#[cfg(not(feature = "regex"))]
+fn token2int(line: &str, token: &str) -> Result<usize, Error> {
+    if token.is_empty() || !token.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(Error::ParseLineError(line.to_string()));
+    }
+    token.parse().map_err(Error::from)
+}
+
+/// Matches the lines of a circuit file with regular expressions.
+#[cfg(feature = "regex")]
+struct LineParser {
+    ints: Regex,
+    unary: Regex,
+    binary: Regex,
+    mand: Regex,
+}
+
+#[cfg(feature = "regex")]
+impl LineParser {
+    fn new() -> Result<Self, Error> {
+        Ok(LineParser {
+            ints: Regex::new(r"^\s*((?:\d+\s+)*\d+)\s*$")?,
+            unary: Regex::new(r"1 1 (\d+) (\d+) (INV|EQW|EQ)\b")?,
+            binary: Regex::new(r"2 1 (\d+) (\d+) (\d+) ((AND|XOR))")?,
+            mand: Regex::new(r"^((?:\d+ )+)MAND$")?,
+        })
+    }
+
+    /// Parse the first `N` whitespace-separated integers of `line`.
+    fn header<const N: usize>(&self, line: &str) -> Result<[usize; N], Error> {
+        let re = Regex::new(&vec![r"(\d+)"; N].join(r"\s+"))?;
+        let cap = regex2captures(&re, line)?;
+        let mut out = [0; N];
+        for (i, x) in out.iter_mut().enumerate() {
+            *x = cap2int(&cap, i + 1)?;
+        }
+        Ok(out)
+    }
+
+    fn blank(&self, line: &str) -> Result<(), Error> {
+        #[allow(clippy::trivial_regex)]
+        let re = Regex::new(r"\n")?;
+        regex2captures(&re, line).map(|_| ())
+    }
+
+    fn ints(&self, line: &str) -> Result<Vec<usize>, Error> {
+        let cap = regex2captures(&self.ints, line)?;
+        cap.get(1)
+            .ok_or(Error::ParseIntError)?
+            .as_str()
+            .split_whitespace()
+            .map(|t| FromStr::from_str(t).map_err(Error::from))
+            .collect()
+    }
+
+    fn unary(&self, line: &str) -> Result<([usize; 2], UnaryType), Error> {
+        let cap = regex2captures(&self.unary, line)?;
+        Ok(([cap2int(&cap, 1)?, cap2int(&cap, 2)?], cap2unary(&cap, 3)?))
+    }
+
+    fn binary(&self, line: &str) -> Result<([usize; 3], GateType), Error> {
+        let cap = regex2captures(&self.binary, line)?;
+        Ok((
+            [cap2int(&cap, 1)?, cap2int(&cap, 2)?, cap2int(&cap, 3)?],
+            cap2typ(&cap, 4)?,
+        ))
+    }
+
+    fn mand(&self, line: &str) -> Result<Vec<[usize; 3]>, Error> {
+        let cap = regex2captures(&self.mand, line)?;
+        let ints = cap
+            .get(1)
+            .ok_or(Error::ParseIntError)?
+            .as_str()
+            .split_whitespace()
+            .map(|t| FromStr::from_str(t).map_err(Error::from))
+            .collect::<Result<Vec<_>, _>>()?;
+        mand_gates(line, &ints)
+    }
+}
+
+/// Split the integers of a `MAND` line of `line`, `2k k` followed by `3k`
+/// wires, into its `k` AND gates `[x_i, y_i, out_i]`.
+fn mand_gates(line: &str, ints: &[usize]) -> Result<Vec<[usize; 3]>, Error> {
+    match ints {
+        [nin, k, wires @ ..]
+            if *k > 0
+                && k.checked_mul(2) == Some(*nin)
+                && k.checked_mul(3) == Some(wires.len()) =>
+        {
+            Ok((0..*k)
+                .map(|i| [wires[i], wires[k + i], wires[2 * k + i]])
+                .collect())
+        }
+        _ => Err(Error::ParseLineError(line.to_string())),
+    }
+}
+
+/// Split a header line of Bristol Fashion of `line`, `n w_1 .. w_n`, into the
+/// widths `w_1 .. w_n` of its `n` values.
+fn value_widths(line: &str, ints: &[usize]) -> Result<Vec<usize>, Error> {
+    match ints {
+        [n, widths @ ..] if *n > 0 && *n == widths.len() => Ok(widths.to_vec()),
+        _ => Err(Error::ParseLineError(line.to_string())),
+    }
+}
+
+/// Check that the wires `ixs` of a gate are among the `nwires` wires of the
+/// header.
+fn check_wires(ixs: &[usize], nwires: usize) -> Result<(), Error> {
+    match ixs.iter().find(|&&ix| ix >= nwires) {
+        Some(&wire) => Err(Error::WireOutOfRange { wire, nwires }),
+        None => Ok(()),
+    }
+}
+
+/// The number of wires of values of the given widths, if it fits a `usize`.
+fn total_width(widths: &[usize]) -> Option<usize> {
+    widths.iter().try_fold(0usize, |sum, &w| sum.checked_add(w))
+}
+
+#[cfg(feature = "regex")]
 fn cap2int(cap: &Captures, idx: usize) -> Result<usize, Error> {
     let s = cap.get(idx).ok_or(Error::ParseIntError)?;
     FromStr::from_str(s.as_str()).map_err(Error::from)
 }
 
+#[cfg(feature = "regex")]
 fn cap2typ(cap: &Captures, idx: usize) -> Result<GateType, Error> {
     let s = cap.get(idx).ok_or(Error::ParseIntError)?;
     let s = s.as_str();
@@ -28,6 +322,19 @@ fn cap2typ(cap: &Captures, idx: usize) -> Result<GateType, Error> {
     }
 }
 
+#[cfg(feature = "regex")]
+fn cap2unary(cap: &Captures, idx: usize) -> Result<UnaryType, Error> {
+    let s = cap.get(idx).ok_or(Error::ParseIntError)?;
+    let s = s.as_str();
+    match s {
+        "INV" => Ok(UnaryType::Inv),
+        "EQ" => Ok(UnaryType::Eq),
+        "EQW" => Ok(UnaryType::Eqw),
+        s => Err(Error::ParseGateError(s.to_string())),
+    }
+}
+
+#[cfg(feature = "regex")]
 fn regex2captures<'t>(re: &Regex, line: &'t str) -> Result<Captures<'t>, Error> {
     re.captures(&line)
         .ok_or_else(|| Error::ParseLineError(line.to_string()))
@@ -38,35 +345,126 @@ impl BinaryCircuit {
     /// format given here: <https://homes.esat.kuleuven.be/~nsmart/MPC/old-circuits.html>,
     /// (Bristol Format---the OLD format---not Bristol Fashion---the NEW format) otherwise
     /// a `CircuitParserError` is returned.
-    pub fn parse(mut reader: impl std::io::BufRead) -> Result<Self, Error> {
+    ///
+    /// The gates may also be the `MAND` gates of Bristol Fashion, `2k k x_1 ..
+    /// x_k y_1 .. y_k out_1 .. out_k MAND`, each of which is parsed as the `k`
+    /// AND gates `out_i = x_i & y_i`, in order. The `EQ` gates `1 1 c out EQ`,
+    /// which set `out` to the constant bit `c`, and the `EQW` gates `1 1 x out
+    /// EQW`, which copy `x` to `out`, are parsed as free XOR and negation
+    /// gates writing `out`.
+    ///
+    /// The header may also be that of Bristol Fashion, the number of input
+    /// values followed by their widths on the second line, and the same for
+    /// the outputs on the third. The first input value is the garbler's and
+    /// the rest are the evaluator's, and each output value is an output group
+    /// (see [`CircuitType::output_widths`]).
+    ///
+    /// The header must count the lines of gates in the file, and every wire
+    /// of a gate must be among the wires it counts. The circuit is then
+    /// checked with [`BinaryCircuit::validate`], so that no gate reads a wire
+    /// before an input or an earlier gate assigns it.
+    ///
+    /// The file is parsed under the default [`ParseLimits`]; see
+    /// [`BinaryCircuit::parse_with_limits`] to change them.
+    pub fn parse(reader: impl std::io::BufRead) -> Result<Self, Error> {
+        Self::parse_with_limits(reader, ParseLimits::default())
+    }
+
+    /// Parse the circuit file at `path` as [`BinaryCircuit::parse`] does.
+    pub fn parse_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::parse(BufReader::new(File::open(path)?))
+    }
+
+    /// Parse a circuit file as [`BinaryCircuit::parse`] does, failing with
+    /// [`CircuitParserError::LimitExceeded`](Error::LimitExceeded) as soon as
+    /// it exceeds one of `limits`.
+    pub fn parse_with_limits(
+        reader: impl std::io::BufRead,
+        limits: ParseLimits,
+    ) -> Result<Self, Error> {
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!(
+            "parse",
+            ngates = tracing::field::Empty,
+            ninputs = tracing::field::Empty,
+            noutputs = tracing::field::Empty
+        )
+        .entered();
+        let lp = LineParser::new()?;
+        // Reading one byte past the limit tells a file at the limit from a
+        // longer one.
+        let mut reader = reader.take(limits.max_bytes.saturating_add(1));
+        let read_line =
+            |reader: &mut std::io::Take<_>, line: &mut String| -> Result<usize, Error> {
+                let n = reader.read_line(line)?;
+                if reader.limit() == 0 {
+                    return Err(Error::LimitExceeded {
+                        what: "bytes",
+                        max: limits.max_bytes,
+                    });
+                }
+                Ok(n)
+            };
+
         // Parse first line: ngates nwires\n
         let mut line = String::new();
-        reader.read_line(&mut line)?;
-        let re = Regex::new(r"(\d+)\s+(\d+)")?;
-        let cap = regex2captures(&re, &line)?;
-        let ngates = cap2int(&cap, 1)?;
-        let nwires = cap2int(&cap, 2)?;
+        read_line(&mut reader, &mut line)?;
+        let [ngates, nwires] = lp.header(&line)?;
 
-        // Parse second line: n1 n2 n3\n
+        // Parse second line: n1 n2 n3\n, or niv ni_1 .. ni_niv\n
         let mut line = String::new();
-        reader.read_line(&mut line)?;
-        let re = Regex::new(r"(\d+)\s+(\d+)\s+(\d+)")?;
-        let cap = regex2captures(&re, &line)?;
-        let n1 = cap2int(&cap, 1)?; // Number of garbler inputs
-        let n2 = cap2int(&cap, 2)?; // Number of evaluator inputs
-        let n3 = cap2int(&cap, 3)?; // Number of outputs
-
-        // Parse third line: \n
-        let mut line = String::new();
-        reader.read_line(&mut line)?;
-        #[allow(clippy::trivial_regex)]
-        let re = Regex::new(r"\n")?;
-        let _ = regex2captures(&re, &line)?;
+        read_line(&mut reader, &mut line)?;
+        // The third line is blank, unless it holds the outputs of Bristol
+        // Fashion.
+        let fashion = matches!(
+            reader.fill_buf()?.iter().find(|b| !matches!(b, b' ' | b'\t')),
+            Some(b) if b.is_ascii_digit()
+        );
+        // Number of garbler inputs, evaluator inputs, and outputs.
+        let (n1, n2, n3, output_groups) = if fashion {
+            // The counts are checked before the third line is read on.
+            ParseLimits::check("gates", ngates, limits.max_gates)?;
+            ParseLimits::check("wires", nwires, limits.max_wires)?;
+            let inputs = value_widths(&line, &lp.ints(&line)?)?;
+            // Parse third line: nov no_1 .. no_nov\n
+            let mut line = String::new();
+            read_line(&mut reader, &mut line)?;
+            let outputs = value_widths(&line, &lp.ints(&line)?)?;
+            let sums = total_width(&inputs[1..]).zip(total_width(&outputs));
+            let (n2, n3) = sums.ok_or_else(|| {
+                Error::InvalidHeader(format!("inputs or outputs exceed {} wires", nwires))
+            })?;
+            (inputs[0], n2, n3, outputs)
+        } else {
+            let [n1, n2, n3] = lp.header(&line)?;
+            (n1, n2, n3, Vec::new())
+        };
+        // Every input and output is a wire.
+        match n1.checked_add(n2) {
+            Some(n) if n <= nwires && n3 <= nwires => (),
+            _ => {
+                return Err(Error::InvalidHeader(format!(
+                    "{} garbler inputs, {} evaluator inputs, or {} outputs exceed {} wires",
+                    n1, n2, n3, nwires
+                )))
+            }
+        }
+        // Nothing has been allocated for the counts yet, and the inputs and
+        // outputs are bounded by the wires.
+        ParseLimits::check("gates", ngates, limits.max_gates)?;
+        ParseLimits::check("wires", nwires, limits.max_wires)?;
+        #[cfg(feature = "tracing")]
+        span.record("ngates", ngates)
+            .record("ninputs", n1 + n2)
+            .record("noutputs", n3);
 
-        let mut circ = Self::new(Some(ngates));
+        // Parse the blank line: \n
+        let mut line = String::new();
+        read_line(&mut reader, &mut line)?;
+        lp.blank(&line)?;
 
-        let re1 = Regex::new(r"1 1 (\d+) (\d+) INV")?;
-        let re2 = Regex::new(r"2 1 (\d+) (\d+) (\d+) ((AND|XOR))")?;
+        let mut circ = Self::new(Some(ngates.min(MAX_RESERVED_GATES)));
+        circ.output_groups = output_groups;
 
         let mut id = 0;
 
@@ -100,28 +498,107 @@ impl BinaryCircuit {
                 modulus: 2,
             });
         }
-        for line in reader.lines() {
-            let line = line?;
+        // The gates read, counting each of the AND gates of a `MAND` line, and
+        // the lines of gates.
+        let mut ngates_read = 0;
+        let mut nlines = 0;
+        loop {
+            line.clear();
+            if read_line(&mut reader, &mut line)? == 0 {
+                break;
+            }
+            let line = line.strip_suffix('\n').unwrap_or(&line);
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            if !line.is_empty() {
+                nlines += 1;
+                ngates_read += 1;
+                ParseLimits::check("gates", ngates_read, limits.max_gates)?;
+            }
             match line.chars().next() {
+                // A multiple AND gate of Bristol Fashion, which is as many AND
+                // gates.
+                Some(_) if line.ends_with(" MAND") => {
+                    let gates = lp.mand(line)?;
+                    ngates_read += gates.len() - 1;
+                    ParseLimits::check("gates", ngates_read, limits.max_gates)?;
+                    for wires in &gates {
+                        check_wires(wires, nwires)?;
+                    }
+                    for [xref, yref, out] in gates {
+                        circ.gates.push(BinaryGate::And {
+                            xref: CircuitRef {
+                                ix: xref,
+                                modulus: 2,
+                            },
+                            yref: CircuitRef {
+                                ix: yref,
+                                modulus: 2,
+                            },
+                            id,
+                            out: Some(out),
+                        });
+                        id += 1;
+                    }
+                }
                 Some('1') => {
-                    let cap = regex2captures(&re1, &line)?;
-                    let yref = cap2int(&cap, 1)?;
-                    let out = cap2int(&cap, 2)?;
-                    let yref = CircuitRef {
-                        ix: yref,
+                    let ([xref, out], typ) = lp.unary(line)?;
+                    // The first integer of an `EQ` gate is a constant.
+                    match typ {
+                        UnaryType::Eq => check_wires(&[out], nwires)?,
+                        _ => check_wires(&[xref, out], nwires)?,
+                    }
+                    let x = CircuitRef {
+                        ix: xref,
                         modulus: 2,
                     };
-                    circ.gates.push(BinaryGate::Inv {
-                        xref: yref,
-                        out: Some(out),
-                    })
+                    let o = CircuitRef {
+                        ix: out,
+                        modulus: 2,
+                    };
+                    match typ {
+                        UnaryType::Inv => circ.gates.push(BinaryGate::Inv {
+                            xref: x,
+                            out: Some(out),
+                        }),
+                        // Constant gates write the wire of their own index, so
+                        // the constant is made in `out` from any wire, here the
+                        // constant one, as `x ^ x`, negated if it is one.
+                        UnaryType::Eq => {
+                            if xref > 1 {
+                                return Err(Error::ParseLineError(line.to_string()));
+                            }
+                            circ.gates.push(BinaryGate::Xor {
+                                xref: oneref,
+                                yref: oneref,
+                                out: Some(out),
+                            });
+                            if xref == 1 {
+                                circ.gates.push(BinaryGate::Inv {
+                                    xref: o,
+                                    out: Some(out),
+                                });
+                            }
+                        }
+                        // The copy is `x ^ (x ^ x)`, built in `out`; copying a
+                        // wire to itself does nothing.
+                        UnaryType::Eqw if xref != out => {
+                            circ.gates.push(BinaryGate::Xor {
+                                xref: x,
+                                yref: x,
+                                out: Some(out),
+                            });
+                            circ.gates.push(BinaryGate::Xor {
+                                xref: x,
+                                yref: o,
+                                out: Some(out),
+                            });
+                        }
+                        UnaryType::Eqw => {}
+                    }
                 }
                 Some('2') => {
-                    let cap = regex2captures(&re2, &line)?;
-                    let xref = cap2int(&cap, 1)?;
-                    let yref = cap2int(&cap, 2)?;
-                    let out = cap2int(&cap, 3)?;
-                    let typ = cap2typ(&cap, 4)?;
+                    let ([xref, yref, out], typ) = lp.binary(line)?;
+                    check_wires(&[xref, yref, out], nwires)?;
                     let xref = CircuitRef {
                         ix: xref,
                         modulus: 2,
@@ -155,48 +632,583 @@ impl BinaryCircuit {
                 }
             }
         }
+        if nlines != ngates {
+            return Err(Error::GateCountMismatch {
+                header: ngates,
+                found: nlines,
+            });
+        }
+        circ.num_nonfree_gates = id;
+        circ.validate()?;
         Ok(circ)
     }
 }
 
+/// Parse a circuit from its text, as [`BinaryCircuit::parse`] does, e.g. one
+/// embedded with `include_str!`.
+impl FromStr for BinaryCircuit {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        Self::parse(s.as_bytes())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
-        circuit::{eval_plain, BinaryCircuit as Circuit},
+        circuit::{
+            eval_plain, eval_plain_bytes, BinaryCircuit as Circuit, CircuitType, ParseLimits,
+        },
         classic::garble,
+        errors::{CircuitParserError, CircuitValidationError},
+        util::{BitOrder, RngExt},
         WireMod2,
     };
+    use std::io::Read;
+
+    /// The bytes of the hex string `s`.
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
 
     #[test]
     fn test_parser() {
+        // The garbler provides the plaintext and the evaluator the key.
         let circ = Circuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
             "../circuits/AES-non-expanded.txt"
         )))
         .unwrap();
-        let key = vec![0u16; 128];
-        let pt = vec![0u16; 128];
-        let output = eval_plain(&circ, &pt, &key).unwrap();
-        assert_eq!(output.iter().map(|i| i.to_string()).collect::<String>(),
-                   "01100110111010010100101111010100111011111000101000101100001110111000100001001100111110100101100111001010001101000010101100101110");
-        let key = vec![1u16; 128];
-        let pt = vec![0u16; 128];
-        let output = eval_plain(&circ, &pt, &key).unwrap();
-        assert_eq!(output.iter().map(|i| i.to_string()).collect::<String>(),
-                   "10100001111101100010010110001100100001110111110101011111110011011000100101100100010010000100010100111000101111111100100100101100");
-        let mut key = vec![0u16; 128];
-        for i in 0..8 {
-            key[i] = 1;
-        }
-        let pt = vec![0u16; 128];
-        let output = eval_plain(&circ, &pt, &key).unwrap();
-        assert_eq!(output.iter().map(|i| i.to_string()).collect::<String>(),
-                   "10110001110101110101100000100101011010110010100011111101100001010000101011010100100101000100001000001000110011110001000101010101");
-        let mut key = vec![0u16; 128];
-        key[7] = 1;
-        let pt = vec![0u16; 128];
-        let output = eval_plain(&circ, &pt, &key).unwrap();
-        assert_eq!(output.iter().map(|i| i.to_string()).collect::<String>(),
-                   "11011100000011101101100001011101111110010110000100011010101110110111001001001001110011011101000101101000110001010100011001111110");
+        for (pt, key, ct) in [
+            // AES-128 known-answer vector from FIPS-197, appendix C.1.
+            (
+                "00112233445566778899aabbccddeeff",
+                "000102030405060708090a0b0c0d0e0f",
+                "69c4e0d86a7b0430d8cdb78070b4c55a",
+            ),
+            // FIPS-197, appendix B.
+            (
+                "3243f6a8885a308d313198a2e0370734",
+                "2b7e151628aed2a6abf7158809cf4f3c",
+                "3925841d02dc09fbdc118597196a0b32",
+            ),
+            (
+                "00000000000000000000000000000000",
+                "00000000000000000000000000000000",
+                "66e94bd4ef8a2c3b884cfa59ca342b2e",
+            ),
+        ] {
+            let out = eval_plain_bytes(&circ, &hex(pt), &hex(key), BitOrder::MsbFirst).unwrap();
+            assert_eq!(out, hex(ct));
+        }
+    }
+
+    #[test]
+    fn test_sha256() {
+        // The circuit compresses one padded block, without the padding.
+        let circ = Circuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../circuits/sha-256.txt"
+        )))
+        .unwrap();
+        let digest = |msg: &[u8]| {
+            let mut block = [0u8; 64];
+            block[..msg.len()].copy_from_slice(msg);
+            block[msg.len()] = 0x80;
+            block[56..].copy_from_slice(&(8 * msg.len() as u64).to_be_bytes());
+            eval_plain_bytes(&circ, &block, &[], BitOrder::MsbFirst).unwrap()
+        };
+        // The one-block example of FIPS 180-2, appendix B.1.
+        assert_eq!(
+            digest(b"abc"),
+            hex("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        assert_eq!(
+            digest(b""),
+            hex("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+    }
+
+    #[test]
+    fn test_typed_inputs() {
+        let circ = Circuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap();
+        // The garbler provides the plaintext and the evaluator the key.
+        let aes = |pt: [u8; 16], key: [u8; 16]| -> String {
+            let gb = circ.assemble_garbler_inputs(&[&pt]).unwrap();
+            let ev = circ.assemble_evaluator_inputs(&[&key]).unwrap();
+            let out = eval_plain(&circ, &gb, &ev).unwrap();
+            BitOrder::MsbFirst
+                .bits_to_bytes(&out)
+                .unwrap()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect()
+        };
+        assert_eq!(aes([0; 16], [0; 16]), "66e94bd4ef8a2c3b884cfa59ca342b2e");
+        assert_eq!(aes([0; 16], [0xff; 16]), "a1f6258c877d5fcd8964484538bfc92c");
+        let mut key = [0; 16];
+        key[0] = 0xff;
+        assert_eq!(aes([0; 16], key), "b1d758256b28fd850ad4944208cf1155");
+        key[0] = 0x01;
+        assert_eq!(aes([0; 16], key), "dc0ed85df9611abb7249cdd168c5467e");
+        // AES-128 known-answer vector from FIPS-197, appendix C.1.
+        let pt = 0x00112233445566778899aabbccddeeffu128.to_be_bytes();
+        let key = 0x000102030405060708090a0b0c0d0e0fu128.to_be_bytes();
+        assert_eq!(aes(pt, key), "69c4e0d86a7b0430d8cdb78070b4c55a");
+
+        assert!(circ.assemble_garbler_inputs(&[&[0u8; 15]]).is_err());
+    }
+
+    #[test]
+    fn test_adder64() {
+        // The Bristol adder takes and returns its numbers least significant
+        // bit first.
+        let circ = Circuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../circuits/adder64.txt"
+        )))
+        .unwrap();
+        let order = BitOrder::LsbFirst;
+        let mut rng = rand::thread_rng();
+        for _ in 0..16 {
+            let (x, y) = (rng.gen_u64(), rng.gen_u64());
+            let out = eval_plain(&circ, &order.bits(x.into(), 64), &order.bits(y.into(), 64));
+            assert_eq!(order.value(&out.unwrap()), u128::from(x.wrapping_add(y)));
+        }
+    }
+
+    fn parse(s: &str) -> Result<Circuit, CircuitParserError> {
+        Circuit::parse(std::io::Cursor::new(s))
+    }
+
+    #[test]
+    fn test_output_overwrites_constant() {
+        // The parser puts a constant on the first wire after the inputs, which
+        // this circuit's only gate overwrites with its output.
+        let circ = parse("1 3\n1 1 1\n\n2 1 0 1 2 XOR\n").unwrap();
+        assert_eq!(circ.constant_outputs(), [None]);
+        for (x, y) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            assert_eq!(eval_plain(&circ, &[x], &[y]).unwrap(), [x ^ y]);
+        }
+    }
+
+    #[test]
+    fn test_output_groups() {
+        // A Bristol Fashion header: two 2-bit inputs, and the outputs `x ^ y`
+        // and `x_0 & y_0` as values of 2 bits and 1 bit.
+        let s = "3 7\n2 2 2\n2 2 1\n\n2 1 0 2 4 XOR\n2 1 1 3 5 XOR\n2 1 0 2 6 AND\n";
+        let circ = parse(s).unwrap();
+        assert_eq!(circ.get_garbler_input_refs().len(), 2);
+        assert_eq!(circ.get_evaluator_input_refs().len(), 2);
+        assert_eq!(circ.output_widths(), [2, 1]);
+        for x in 0..4 {
+            for y in 0..4 {
+                let (xs, ys) = ([x & 1, x >> 1], [y & 1, y >> 1]);
+                let out = eval_plain(&circ, &xs, &ys).unwrap();
+                assert_eq!(
+                    circ.group_outputs(&out),
+                    [vec![xs[0] ^ ys[0], xs[1] ^ ys[1]], vec![xs[0] & ys[0]]]
+                );
+            }
+        }
+
+        // The input values after the first are the evaluator's.
+        let circ = parse("1 4\n3 1 1 1\n1 1\n\n2 1 1 2 3 AND\n").unwrap();
+        assert_eq!(circ.get_garbler_input_refs().len(), 1);
+        assert_eq!(circ.get_evaluator_input_refs().len(), 2);
+        assert_eq!(eval_plain(&circ, &[0], &[1, 1]).unwrap(), [1]);
+
+        // Circuits in the old format keep a single group of all outputs.
+        let adder: Circuit = include_str!("../circuits/adder64.txt").parse().unwrap();
+        assert!(adder.get_output_groups().is_empty());
+        assert_eq!(adder.output_widths(), [64]);
+        let nand = parse("3 6\n1 1 1\n\n2 1 0 1 3 XOR\n2 1 0 1 4 AND\n1 1 4 5 INV\n").unwrap();
+        assert_eq!(nand.output_widths(), [1]);
+
+        for bad in [
+            "1 3\n2 1 1\n2 1\n\n2 1 0 1 2 AND\n",
+            "1 3\n0\n1 1\n\n2 1 0 1 2 AND\n",
+        ] {
+            assert!(
+                matches!(parse(bad), Err(CircuitParserError::ParseLineError(_))),
+                "{:?}",
+                bad
+            );
+        }
+        assert!(matches!(
+            parse("1 3\n2 1 1\n2 2 2\n\n2 1 0 1 2 AND\n"),
+            Err(CircuitParserError::InvalidHeader(_))
+        ));
+        assert!(matches!(
+            parse(&format!("1 3\n2 1 1\n2 {} 2\n\n", usize::MAX)),
+            Err(CircuitParserError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn test_line_formats() {
+        // NAND, with extra whitespace in the header and CRLF line endings.
+        for s in [
+            "3 6\n1 1 1\n\n2 1 0 1 3 XOR\n2 1 0 1 4 AND\n1 1 4 5 INV\n",
+            "  3\t6  \r\n1  1 1\r\n\r\n2 1 0 1 3 XOR\r\n2 1 0 1 4 AND\r\n1 1 4 5 INV\r\n",
+        ] {
+            let circ = parse(s).unwrap();
+            assert_eq!(circ.get_num_nonfree_gates(), 1);
+            for (x, y) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+                assert_eq!(eval_plain(&circ, &[x], &[y]).unwrap(), [1 - x * y]);
+            }
+        }
+
+        let err = |s| parse(s).unwrap_err().to_string();
+        assert_eq!(err("3 6\n1 1 1\n"), "unable to parse line ''");
+        assert_eq!(err("3\n1 1 1\n\n"), "unable to parse line '3\n'");
+        assert_eq!(err("3 x\n1 1 1\n\n"), "unable to parse line '3 x\n'");
+        assert_eq!(
+            err("99999999999999999999999 6\n1 1 1\n\n"),
+            "unable to parse integer"
+        );
+        assert_eq!(
+            err("3 6\n1 1 1\n\n2 1 0 1 3 OR\n"),
+            "unable to parse line '2 1 0 1 3 OR'"
+        );
+        assert_eq!(
+            err("3 6\n1 1 1\n\n1 1 4 INV\n"),
+            "unable to parse line '1 1 4 INV'"
+        );
+        assert_eq!(
+            err("3 6\n1 1 1\n\n3 1 0 1 2 3 MAJ\n"),
+            "unable to parse line '3 1 0 1 2 3 MAJ'"
+        );
+    }
+
+    #[test]
+    fn test_malformed_circuits() {
+        // Each of these used to panic or abort while parsing or evaluating.
+        assert!(matches!(
+            parse("1 0\n0 0 1\n\n"),
+            Err(CircuitParserError::InvalidHeader(_))
+        ));
+        assert!(matches!(
+            parse("2 3\n18446744073709551615 1 1\n\n"),
+            Err(CircuitParserError::InvalidHeader(_))
+        ));
+        assert!(matches!(
+            parse("1 18446744073709551615\n18446744073709551615 1 1\n\n"),
+            Err(CircuitParserError::InvalidHeader(_))
+        ));
+        // A header claiming far more wires than the file holds.
+        assert!(matches!(
+            parse("1 18446744073709551615\n1 1 1\n\n"),
+            Err(CircuitParserError::LimitExceeded { what: "wires", .. })
+        ));
+        assert!(matches!(
+            parse("0 9\n1 1 1\n\n"),
+            Err(CircuitParserError::InvalidCircuit(_))
+        ));
+        // Gates reading or writing past the last wire.
+        for s in [
+            "1 3\n1 1 1\n\n2 1 0 9 2 AND\n",
+            "1 3\n1 1 1\n\n2 1 0 1 9 XOR\n",
+            "1 3\n1 1 1\n\n1 1 0 9 INV\n",
+        ] {
+            assert!(matches!(
+                parse(s),
+                Err(CircuitParserError::WireOutOfRange { wire: 9, nwires: 3 })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_wiring() {
+        let nand = "3 6\n1 1 1\n\n2 1 0 1 3 XOR\n2 1 0 1 4 AND\n1 1 4 5 INV\n";
+        assert!(parse(nand).is_ok());
+
+        // Truncated files, and files with more gates than their header.
+        let err = |s: &str| parse(s).unwrap_err().to_string();
+        assert_eq!(
+            err(&nand[..nand.len() - 12]),
+            "the header counts 3 gates, but the file has 2"
+        );
+        assert_eq!(
+            err(&nand.replacen('3', "4", 1)),
+            "the header counts 4 gates, but the file has 3"
+        );
+        assert_eq!(
+            err(&format!("{}2 1 3 5 5 XOR\n", nand)),
+            "the header counts 3 gates, but the file has 4"
+        );
+        // A MAND line is one line of gates.
+        assert!(parse("1 6\n2 2 2\n\n4 2 0 1 2 3 4 5 MAND\n").is_ok());
+
+        // A gate reading a wire before any gate writes it.
+        assert!(matches!(
+            parse("3 6\n1 1 1\n\n2 1 0 4 3 XOR\n2 1 0 1 4 AND\n1 1 4 5 INV\n"),
+            Err(CircuitParserError::InvalidCircuit(
+                CircuitValidationError::UndefinedRef(4)
+            ))
+        ));
+        // An output that no gate writes.
+        assert!(matches!(
+            parse("3 6\n1 1 1\n\n2 1 0 1 3 XOR\n2 1 0 1 4 AND\n2 1 3 4 2 XOR\n"),
+            Err(CircuitParserError::InvalidCircuit(
+                CircuitValidationError::UndefinedRef(5)
+            ))
+        ));
+
+        // Wires past those in the header, in every kind of gate.
+        for gate in [
+            "2 1 0 6 3 XOR",
+            "2 1 0 1 6 AND",
+            "1 1 6 5 INV",
+            "1 1 4 7 EQW",
+            "1 1 1 8 EQ",
+            "2 1 0 1 9 MAND",
+        ] {
+            let s = format!("1 6\n1 1 1\n\n{}\n", gate);
+            assert!(
+                matches!(
+                    parse(&s),
+                    Err(CircuitParserError::WireOutOfRange { nwires: 6, .. })
+                ),
+                "{:?}",
+                gate
+            );
+        }
+        assert_eq!(
+            err("1 6\n1 1 1\n\n2 1 0 1 6 AND\n"),
+            "wire 6 is past the 6 wires in the header"
+        );
+    }
+
+    #[test]
+    fn test_parse_limits() {
+        // Headers with absurd counts are rejected before the parser reads on,
+        // here into an endless stream.
+        let hostile = |header: &'static str| {
+            let reader = std::io::BufReader::new(header.as_bytes().chain(std::io::repeat(b'2')));
+            Circuit::parse(reader).unwrap_err()
+        };
+        assert!(matches!(
+            hostile("1152921504606846976 3\n1 1 1\n"),
+            CircuitParserError::LimitExceeded {
+                what: "gates",
+                max: 2147483648
+            }
+        ));
+        assert!(matches!(
+            hostile("1 1152921504606846976\n1152921504606846975 1 1\n"),
+            CircuitParserError::LimitExceeded { what: "wires", .. }
+        ));
+        assert_eq!(
+            hostile("1 1152921504606846976\n1 1 1\n").to_string(),
+            "the circuit exceeds the limit of 2147483648 wires"
+        );
+
+        // A line without end is read no further than the limit.
+        let reader = std::io::BufReader::new(std::io::repeat(b' '));
+        let limits = ParseLimits::new().max_bytes(1 << 20);
+        assert!(matches!(
+            Circuit::parse_with_limits(reader, limits),
+            Err(CircuitParserError::LimitExceeded {
+                what: "bytes",
+                max: 1048576
+            })
+        ));
+
+        // A file at the byte limit parses, and one past it does not.
+        let nand = "3 6\n1 1 1\n\n2 1 0 1 3 XOR\n2 1 0 1 4 AND\n1 1 4 5 INV\n";
+        let with = |limits| Circuit::parse_with_limits(std::io::Cursor::new(nand), limits);
+        let len = nand.len() as u64;
+        assert!(with(ParseLimits::new().max_bytes(len)).is_ok());
+        assert!(matches!(
+            with(ParseLimits::new().max_bytes(len - 1)),
+            Err(CircuitParserError::LimitExceeded { what: "bytes", .. })
+        ));
+        // The gates in the file count, even past those in the header.
+        assert!(with(ParseLimits::new().max_gates(3)).is_ok());
+        let undercounted = nand.replacen('3', "2", 1);
+        assert!(matches!(
+            Circuit::parse_with_limits(
+                std::io::Cursor::new(undercounted),
+                ParseLimits::new().max_gates(2)
+            ),
+            Err(CircuitParserError::LimitExceeded { what: "gates", .. })
+        ));
+        assert!(matches!(
+            with(ParseLimits::new().max_wires(5)),
+            Err(CircuitParserError::LimitExceeded { what: "wires", .. })
+        ));
+
+        // The largest circuits shipped parse under the default limits.
+        for bytes in [
+            &include_bytes!("../circuits/sha-256.txt")[..],
+            &include_bytes!("../circuits/sha-1.txt")[..],
+        ] {
+            let limits = ParseLimits::default();
+            let circ = Circuit::parse_with_limits(std::io::Cursor::new(bytes), limits).unwrap();
+            assert_eq!(circ, Circuit::parse(std::io::Cursor::new(bytes)).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_adder64_from_str() {
+        let text: Circuit = include_str!("../circuits/adder64.txt").parse().unwrap();
+        let file =
+            Circuit::parse_file(concat!(env!("CARGO_MANIFEST_DIR"), "/circuits/adder64.txt"))
+                .unwrap();
+        assert_eq!(text.gates(), file.gates());
+        assert_eq!(text, file);
+        assert!(matches!(
+            Circuit::parse_file("circuits/no-such-circuit.txt"),
+            Err(CircuitParserError::IoError(_))
+        ));
+        match "1 3\n1 1 1\n\n2 1 0 1 2 XAND\n".parse::<Circuit>() {
+            Err(CircuitParserError::ParseLineError(line)) => assert_eq!(line, "2 1 0 1 2 XAND"),
+            other => panic!("parsed as {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_malformed_gate_lines() {
+        // Each line follows a valid header, and fails as a line whichever
+        // parser reads it.
+        for gate in [
+            "2 1 0 1 3 OR",
+            "2 1 0 1 AND",
+            "2 1 0 x 3 XOR",
+            "2 1 0 -1 3 XOR",
+            "2 1 0 1 3 and",
+            "1 1 4 INV",
+            "1 1 4 5",
+            "1 1 4 5 NOT",
+            "3 1 0 1 2 3 MAJ",
+            " 2 1 0 1 3 AND",
+            "x",
+        ] {
+            let s = format!("3 6\n1 1 1\n\n{}\n", gate);
+            match parse(&s) {
+                Err(CircuitParserError::ParseLineError(line)) => assert_eq!(line, gate),
+                other => panic!("{:?} parsed as {:?}", gate, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_mand() {
+        // Two ANDs as one MAND line, and as two AND lines.
+        let mand = parse("1 6\n2 2 2\n\n4 2 0 1 2 3 4 5 MAND\n").unwrap();
+        let flat = parse("2 6\n2 2 2\n\n2 1 0 2 4 AND\n2 1 1 3 5 AND\n").unwrap();
+        assert_eq!(mand, flat);
+        assert_eq!(mand.get_num_nonfree_gates(), 2);
+        for x in 0..4 {
+            for y in 0..4 {
+                let (xs, ys) = ([x & 1, x >> 1], [y & 1, y >> 1]);
+                let out = eval_plain(&mand, &xs, &ys).unwrap();
+                assert_eq!(out, [xs[0] & ys[0], xs[1] & ys[1]]);
+            }
+        }
+        let (en, gc) = garble::<WireMod2, _>(&mand).unwrap();
+        let gb = en.encode_garbler_inputs(&[1, 1]).unwrap();
+        let ev = en.encode_evaluator_inputs(&[1, 0]).unwrap();
+        assert_eq!(gc.eval(&mand, &gb, &ev).unwrap(), [1, 0]);
+
+        // A single AND may be a MAND too.
+        let one = parse("1 3\n1 1 1\n\n2 1 0 1 2 MAND\n").unwrap();
+        assert_eq!(one, parse("1 3\n1 1 1\n\n2 1 0 1 2 AND\n").unwrap());
+
+        for bad in [
+            "1 6\n2 2 2\n\n4 2 0 1 2 3 4 MAND\n",
+            "1 6\n2 2 2\n\n3 2 0 1 2 3 4 5 MAND\n",
+            "1 6\n2 2 2\n\n0 0 MAND\n",
+            "1 6\n2 2 2\n\n4 2 0 1 2 x 4 5 MAND\n",
+        ] {
+            assert!(
+                matches!(parse(bad), Err(CircuitParserError::ParseLineError(_))),
+                "{:?}",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_eq_eqw() {
+        // Wire 4, the first after the inputs, is overwritten before the
+        // constants are set, and wire 8 is copied to itself.
+        let circ: Circuit = include_str!("../circuits/eq_eqw.txt").parse().unwrap();
+        assert_eq!(circ.get_num_nonfree_gates(), 1);
+        for x in 0..4 {
+            for y in 0..4 {
+                let (xs, ys) = ([x & 1, x >> 1], [y & 1, y >> 1]);
+                let out = eval_plain(&circ, &xs, &ys).unwrap();
+                let xor = xs[1] ^ ys[1];
+                assert_eq!(out, [xs[0] & ys[0], 1 - xor, xor, 1]);
+            }
+        }
+        let (en, gc) = garble::<WireMod2, _>(&circ).unwrap();
+        let gb = en.encode_garbler_inputs(&[1, 1]).unwrap();
+        let ev = en.encode_evaluator_inputs(&[1, 0]).unwrap();
+        assert_eq!(gc.eval(&circ, &gb, &ev).unwrap(), [1, 0, 1, 1]);
+
+        for bad in [
+            "1 3\n1 1 1\n\n1 1 2 2 EQ\n",
+            "1 3\n1 1 1\n\n1 1 0 EQW\n",
+            "1 3\n1 1 1\n\n1 1 0 2 EQV\n",
+        ] {
+            assert!(parse(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    /// Join the runs of AND lines of `text` whose outputs none of the run
+    /// uses into MAND lines, and count the lines anew in the header.
+    fn to_mand(text: &str) -> String {
+        let mut lines = text.lines().map(String::from).collect::<Vec<_>>();
+        let gates = lines.split_off(3);
+        let mut run: Vec<[&str; 3]> = Vec::new();
+        let flush = |run: &mut Vec<[&str; 3]>, lines: &mut Vec<String>| {
+            if !run.is_empty() {
+                let k = run.len();
+                let wires = (0..3).flat_map(|j| run.iter().map(move |g| g[j]));
+                let wires = wires.collect::<Vec<_>>().join(" ");
+                lines.push(format!("{} {} {} MAND", 2 * k, k, wires));
+                run.clear();
+            }
+        };
+        for gate in &gates {
+            let tokens = gate.split_whitespace().collect::<Vec<_>>();
+            if let ["2", "1", x, y, out, "AND"] = tokens[..] {
+                if run.iter().any(|g| g[2] == x || g[2] == y) {
+                    flush(&mut run, &mut lines);
+                }
+                run.push([x, y, out]);
+            } else {
+                flush(&mut run, &mut lines);
+                lines.push(gate.clone());
+            }
+        }
+        flush(&mut run, &mut lines);
+        // The header counts the lines of gates.
+        let ngates = lines[3..].iter().filter(|l| !l.is_empty()).count();
+        let nwires = lines[0].split_whitespace().nth(1).unwrap().to_string();
+        lines[0] = format!("{} {}", ngates, nwires);
+        lines.join("\n") + "\n"
+    }
+
+    #[test]
+    fn test_mand_aes() {
+        let text = include_str!("../circuits/AES-non-expanded.txt");
+        let flat: Circuit = text.parse().unwrap();
+        let text = to_mand(text);
+        assert!(text.lines().filter(|l| l.ends_with("MAND")).count() > 100);
+        let mand: Circuit = text.parse().unwrap();
+        assert_eq!(mand, flat);
+        assert_eq!(
+            eval_plain(&mand, &[0; 128], &[0; 128]).unwrap(),
+            eval_plain(&flat, &[0; 128], &[0; 128]).unwrap()
+        );
     }
 
     #[test]
@@ -205,9 +1217,10 @@ mod tests {
             "../circuits/AES-non-expanded.txt"
         )))
         .unwrap();
+        assert_eq!(circ.get_num_nonfree_gates(), 6800);
         let (en, gc) = garble::<WireMod2, _>(&circ).unwrap();
-        let gb = en.encode_garbler_inputs(&vec![0u16; 128]);
-        let ev = en.encode_evaluator_inputs(&vec![0u16; 128]);
+        let gb = en.encode_garbler_inputs(&vec![0; 128]).unwrap();
+        let ev = en.encode_evaluator_inputs(&vec![0; 128]).unwrap();
         gc.eval(&circ, &gb, &ev).unwrap();
     }
 }
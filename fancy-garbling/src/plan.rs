@@ -0,0 +1,771 @@
+//! Choosing between the binary and CRT representations of integer arithmetic.
+//!
+//! Comparisons are cheap on [`BinaryBundle`]s and multiplications are cheap on
+//! [`CrtBundle`]s, so a computation mixing the two is cheapest in one or the
+//! other, or in both with conversions between them. [`choose_representation`]
+//! counts the ciphertexts of a small program of [`ArithOp`]s in either
+//! representation, and of converting values from one to the other with
+//! [`CrtGadgets::bin_to_crt`] and [`CrtGadgets::crt_to_bin`], running each
+//! gadget on an [`Informer`]. The cheapest [`Plan`] it finds then runs against
+//! any `Fancy` object with [`Plan::execute`].
+//!
+//! ```
+//! use fancy_garbling::{
+//!     dummy::Dummy,
+//!     plan::{choose_representation, ArithOp::*, Repr},
+//! };
+//!
+//! // (x * y * y) < z
+//! let ops = [Input, Input, Input, Mul(0, 1), Mul(3, 1), Lt(4, 2)];
+//! let plan = choose_representation(&ops, 16).unwrap();
+//! assert!(plan.cost() <= plan.binary_cost().min(plan.crt_cost()));
+//!
+//! let mut f = Dummy::new();
+//! let inputs = plan.encode(&mut f, &[3, 5, 100]).unwrap();
+//! let z = plan.execute(&mut f, &inputs).unwrap();
+//! assert_eq!(z.output(&mut f).unwrap(), Some(1));
+//! ```
+//!
+//! The planner is greedy: it computes each operation in the representation
+//! that is cheapest given where its operands already are, and falls back to
+//! computing everything in binary or everything in CRT when either is cheaper.
+
+use crate::{
+    dummy::Dummy,
+    errors::{FancyError, PlanError},
+    fancy::{BinaryBundle, CrtBundle, CrtGadgets, FancyInput, HasModulus},
+    informer::Informer,
+    util,
+};
+use itertools::Itertools;
+use std::collections::HashMap;
+
+/// The widest arithmetic, in bits, that can be planned.
+pub const MAX_BITWIDTH: usize = 32;
+
+/// An operation of a program to plan.
+///
+/// Operation `i` of a program computes value `i`, and the operands of an
+/// operation are the indices of values computed before it. Values are unsigned
+/// integers less than `2^bitwidth`: additions, subtractions and
+/// multiplications must not overflow or underflow, since the binary
+/// representation computes modulo `2^bitwidth` and the CRT representation
+/// modulo its composite modulus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ArithOp {
+    /// The next input.
+    Input,
+    /// `x + y`.
+    Add(usize, usize),
+    /// `x - y`, for `x >= y`.
+    Sub(usize, usize),
+    /// `x * y`.
+    Mul(usize, usize),
+    /// `x * c` for a constant `c`.
+    Cmul(usize, u128),
+    /// 1 if `x < y`, and 0 otherwise.
+    Lt(usize, usize),
+}
+
+impl ArithOp {
+    /// The indices of the values the operation uses.
+    pub fn operands(&self) -> Vec<usize> {
+        match *self {
+            ArithOp::Input => vec![],
+            ArithOp::Cmul(x, _) => vec![x],
+            ArithOp::Add(x, y) | ArithOp::Sub(x, y) | ArithOp::Mul(x, y) | ArithOp::Lt(x, y) => {
+                vec![x, y]
+            }
+        }
+    }
+
+    /// The operation on values `0` and `1`, whose cost is that of `self`.
+    fn shape(&self) -> ArithOp {
+        match *self {
+            ArithOp::Input => ArithOp::Input,
+            ArithOp::Add(..) => ArithOp::Add(0, 1),
+            ArithOp::Sub(..) => ArithOp::Sub(0, 1),
+            ArithOp::Mul(..) => ArithOp::Mul(0, 1),
+            ArithOp::Cmul(_, c) => ArithOp::Cmul(0, c),
+            ArithOp::Lt(..) => ArithOp::Lt(0, 1),
+        }
+    }
+}
+
+/// A representation of integers as bundles of wires.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Repr {
+    /// A [`BinaryBundle`] of as many bits as the arithmetic is wide.
+    Binary,
+    /// A [`CrtBundle`] under the composite modulus of the plan.
+    Crt,
+}
+
+/// A value of a program, in either representation.
+#[derive(Clone)]
+pub enum Value<W> {
+    /// A value in binary.
+    Binary(BinaryBundle<W>),
+    /// A value in CRT.
+    Crt(CrtBundle<W>),
+}
+
+impl<W: Clone + HasModulus> Value<W> {
+    /// The representation of the value.
+    pub fn repr(&self) -> Repr {
+        match self {
+            Value::Binary(_) => Repr::Binary,
+            Value::Crt(_) => Repr::Crt,
+        }
+    }
+
+    /// Output the value, and interpret it as an integer.
+    pub fn output<F: CrtGadgets<Item = W>>(&self, f: &mut F) -> Result<Option<u128>, F::Error> {
+        match self {
+            Value::Binary(x) => f.bin_output(x),
+            Value::Crt(x) => f.crt_output(x),
+        }
+    }
+}
+
+/// The number of ciphertexts of the operations of a program, and of the
+/// conversions between representations, for arithmetic of a given width.
+///
+/// Every number is counted by an [`Informer`] running the gadget
+/// [`Plan::execute`] runs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CostModel {
+    bitwidth: usize,
+    modulus: u128,
+    to_binary: usize,
+    to_crt: usize,
+    ops: HashMap<(ArithOp, Repr), usize>,
+}
+
+impl CostModel {
+    /// Count the costs for arithmetic on `bitwidth` bits, from 2 to
+    /// [`MAX_BITWIDTH`].
+    ///
+    /// The CRT representation holds one more bit than the binary one, so that
+    /// comparisons can take the sign of a difference.
+    pub fn new(bitwidth: usize) -> Result<Self, PlanError> {
+        if !(2..=MAX_BITWIDTH).contains(&bitwidth) {
+            return Err(PlanError::Bitwidth(bitwidth));
+        }
+        let modulus = util::modulus_with_width(bitwidth as u32 + 1);
+        let mut model = CostModel {
+            bitwidth,
+            modulus,
+            to_binary: 0,
+            to_crt: 0,
+            ops: HashMap::new(),
+        };
+        model.to_binary = model.measure(|f| {
+            let x = f.crt_encode(0, modulus)?;
+            f.crt_to_bin(&x, bitwidth)?;
+            Ok(())
+        });
+        model.to_crt = model.measure(|f| {
+            let x = f.bin_encode(0, bitwidth)?;
+            f.bin_to_crt(&x, modulus)?;
+            Ok(())
+        });
+        for op in [
+            ArithOp::Add(0, 1),
+            ArithOp::Sub(0, 1),
+            ArithOp::Mul(0, 1),
+            ArithOp::Lt(0, 1),
+        ] {
+            for repr in [Repr::Binary, Repr::Crt] {
+                let cost = model.measure_op(op, repr);
+                model.ops.insert((op, repr), cost);
+            }
+        }
+        Ok(model)
+    }
+
+    /// The width of the arithmetic in bits.
+    pub fn bitwidth(&self) -> usize {
+        self.bitwidth
+    }
+
+    /// The composite modulus of the CRT representation.
+    pub fn crt_modulus(&self) -> u128 {
+        self.modulus
+    }
+
+    /// The number of ciphertexts of converting a value to `repr` from the
+    /// other representation.
+    pub fn conversion_cost(&self, repr: Repr) -> usize {
+        match repr {
+            Repr::Binary => self.to_binary,
+            Repr::Crt => self.to_crt,
+        }
+    }
+
+    /// The number of ciphertexts of computing `op` in `repr`.
+    pub fn op_cost(&self, op: &ArithOp, repr: Repr) -> usize {
+        let op = op.shape();
+        match op {
+            ArithOp::Input => 0,
+            // The cost of a binary `cmul` depends on its constant.
+            ArithOp::Cmul(..) => self.measure_op(op, repr),
+            _ => self.ops[&(op, repr)],
+        }
+    }
+
+    /// Plan `ops` in whichever of binary, CRT, or a mix of the two is
+    /// cheapest.
+    pub fn plan(&self, ops: &[ArithOp]) -> Result<Plan, PlanError> {
+        check_ops(ops)?;
+        let costs = ops
+            .iter()
+            .map(|op| [self.op_cost(op, Repr::Binary), self.op_cost(op, Repr::Crt)])
+            .collect_vec();
+        let binary = self.plan_in_with(ops, Repr::Binary, &costs);
+        let crt = self.plan_in_with(ops, Repr::Crt, &costs);
+        let mixed = self.plan_greedy(ops, &costs);
+        let mut plan = if binary.cost <= crt.cost.min(mixed.cost) {
+            binary
+        } else if crt.cost <= mixed.cost {
+            crt
+        } else {
+            mixed
+        };
+        plan.binary_cost = plan_cost(&costs, Repr::Binary);
+        plan.crt_cost = plan_cost(&costs, Repr::Crt);
+        Ok(plan)
+    }
+
+    /// Plan `ops` with every operation in `repr`.
+    pub fn plan_in(&self, ops: &[ArithOp], repr: Repr) -> Result<Plan, PlanError> {
+        check_ops(ops)?;
+        let costs = ops
+            .iter()
+            .map(|op| [self.op_cost(op, Repr::Binary), self.op_cost(op, Repr::Crt)])
+            .collect_vec();
+        let mut plan = self.plan_in_with(ops, repr, &costs);
+        plan.binary_cost = plan_cost(&costs, Repr::Binary);
+        plan.crt_cost = plan_cost(&costs, Repr::Crt);
+        Ok(plan)
+    }
+
+    fn plan_in_with(&self, ops: &[ArithOp], repr: Repr, costs: &[[usize; 2]]) -> Plan {
+        Plan {
+            ops: ops.to_vec(),
+            reprs: vec![repr; ops.len()],
+            conversions: Vec::new(),
+            bitwidth: self.bitwidth,
+            modulus: self.modulus,
+            cost: plan_cost(costs, repr),
+            binary_cost: 0,
+            crt_cost: 0,
+        }
+    }
+
+    /// Compute each operation in the representation that is cheapest given
+    /// where its operands are. An input takes the representation of the first
+    /// operation using it.
+    fn plan_greedy(&self, ops: &[ArithOp], costs: &[[usize; 2]]) -> Plan {
+        let mut reprs: Vec<Option<Repr>> = vec![None; ops.len()];
+        let mut available = vec![[false; 2]; ops.len()];
+        let mut conversions = Vec::new();
+        let mut total = 0;
+        for (i, op) in ops.iter().enumerate() {
+            if let ArithOp::Input = op {
+                continue;
+            }
+            let operands = op.operands().into_iter().unique().collect_vec();
+            let cost = |repr: Repr| {
+                let converting = operands
+                    .iter()
+                    .filter(|&&j| reprs[j].is_some() && !available[j][repr as usize])
+                    .count();
+                costs[i][repr as usize] + converting * self.conversion_cost(repr)
+            };
+            let (repr, cost) = [Repr::Binary, Repr::Crt]
+                .into_iter()
+                .map(|repr| (repr, cost(repr)))
+                .min_by_key(|&(_, cost)| cost)
+                .expect("there are two representations");
+            for j in operands {
+                if reprs[j].is_none() {
+                    reprs[j] = Some(repr);
+                } else if !available[j][repr as usize] {
+                    conversions.push((j, repr));
+                }
+                available[j][repr as usize] = true;
+            }
+            reprs[i] = Some(repr);
+            available[i][repr as usize] = true;
+            total += cost;
+        }
+        Plan {
+            ops: ops.to_vec(),
+            reprs: reprs
+                .into_iter()
+                .map(|repr| repr.unwrap_or(Repr::Binary))
+                .collect(),
+            conversions,
+            bitwidth: self.bitwidth,
+            modulus: self.modulus,
+            cost: total,
+            binary_cost: 0,
+            crt_cost: 0,
+        }
+    }
+
+    fn measure_op(&self, op: ArithOp, repr: Repr) -> usize {
+        self.measure(|f| {
+            let xs = op
+                .operands()
+                .iter()
+                .map(|_| encode(f, repr, 0, self.bitwidth, self.modulus))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut slots = xs.into_iter().map(Slot::from).collect_vec();
+            apply(f, op, repr, &mut slots, self.bitwidth, self.modulus)?;
+            Ok(())
+        })
+    }
+
+    fn measure(
+        &self,
+        gadget: impl FnOnce(&mut Informer<Dummy>) -> Result<(), <Dummy as FancyInput>::Error>,
+    ) -> usize {
+        let mut informer = Informer::new(Dummy::new());
+        gadget(&mut informer).expect("the gadgets run on zeros, which are in range");
+        informer.stats().num_ciphertexts()
+    }
+}
+
+/// Plan `ops` on `bitwidth`-bit integers in whichever of binary, CRT, or a
+/// mix of the two takes the fewest ciphertexts; see [`CostModel::plan`].
+pub fn choose_representation(ops: &[ArithOp], bitwidth: usize) -> Result<Plan, PlanError> {
+    CostModel::new(bitwidth)?.plan(ops)
+}
+
+fn check_ops(ops: &[ArithOp]) -> Result<(), PlanError> {
+    if ops.is_empty() {
+        return Err(PlanError::Empty);
+    }
+    for (op, x) in ops.iter().enumerate() {
+        if let Some(operand) = x.operands().into_iter().find(|&j| j >= op) {
+            return Err(PlanError::Operand { op, operand });
+        }
+    }
+    Ok(())
+}
+
+fn plan_cost(costs: &[[usize; 2]], repr: Repr) -> usize {
+    costs.iter().map(|cost| cost[repr as usize]).sum()
+}
+
+/// The representations to compute a program in, chosen by
+/// [`choose_representation`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Plan {
+    ops: Vec<ArithOp>,
+    reprs: Vec<Repr>,
+    conversions: Vec<(usize, Repr)>,
+    bitwidth: usize,
+    modulus: u128,
+    cost: usize,
+    binary_cost: usize,
+    crt_cost: usize,
+}
+
+impl Plan {
+    /// The program.
+    pub fn ops(&self) -> &[ArithOp] {
+        &self.ops
+    }
+
+    /// The representation each operation computes its value in. An input is
+    /// given in its representation.
+    pub fn reprs(&self) -> &[Repr] {
+        &self.reprs
+    }
+
+    /// The representations the inputs are given in, in order.
+    pub fn input_reprs(&self) -> Vec<Repr> {
+        self.ops
+            .iter()
+            .zip(self.reprs.iter())
+            .filter(|(op, _)| **op == ArithOp::Input)
+            .map(|(_, &repr)| repr)
+            .collect()
+    }
+
+    /// The values converted, each by the index of its operation, with the
+    /// representation it is converted to, in the order they are converted.
+    pub fn conversions(&self) -> &[(usize, Repr)] {
+        &self.conversions
+    }
+
+    /// The width of the arithmetic in bits.
+    pub fn bitwidth(&self) -> usize {
+        self.bitwidth
+    }
+
+    /// The composite modulus of values in CRT.
+    pub fn crt_modulus(&self) -> u128 {
+        self.modulus
+    }
+
+    /// The number of ciphertexts of the plan, conversions included.
+    pub fn cost(&self) -> usize {
+        self.cost
+    }
+
+    /// The number of ciphertexts of computing the program in binary.
+    pub fn binary_cost(&self) -> usize {
+        self.binary_cost
+    }
+
+    /// The number of ciphertexts of computing the program in CRT.
+    pub fn crt_cost(&self) -> usize {
+        self.crt_cost
+    }
+
+    /// Encode `values`, one for each input, in the representations of
+    /// [`input_reprs`](Plan::input_reprs).
+    ///
+    /// Each value must be less than `2^bitwidth`.
+    pub fn encode<F: FancyInput>(
+        &self,
+        f: &mut F,
+        values: &[u128],
+    ) -> Result<Vec<Value<F::Item>>, F::Error> {
+        let reprs = self.input_reprs();
+        if values.len() != reprs.len() {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: values.len(),
+                needed: reprs.len(),
+            }));
+        }
+        values
+            .iter()
+            .zip(reprs)
+            .map(|(&x, repr)| encode(f, repr, x, self.bitwidth, self.modulus))
+            .collect()
+    }
+
+    /// Receive the inputs, in the representations of
+    /// [`input_reprs`](Plan::input_reprs).
+    pub fn receive<F: FancyInput>(&self, f: &mut F) -> Result<Vec<Value<F::Item>>, F::Error> {
+        self.input_reprs()
+            .into_iter()
+            .map(|repr| match repr {
+                Repr::Binary => f.bin_receive(self.bitwidth).map(Value::Binary),
+                Repr::Crt => f.crt_receive(self.modulus).map(Value::Crt),
+            })
+            .collect()
+    }
+
+    /// Run the program on `inputs`, converting values between representations
+    /// as planned, and return the value of its last operation.
+    ///
+    /// An input given in the other representation than planned is converted.
+    pub fn execute<F: CrtGadgets>(
+        &self,
+        f: &mut F,
+        inputs: &[Value<F::Item>],
+    ) -> Result<Value<F::Item>, F::Error> {
+        let ninputs = self.ops.iter().filter(|&&op| op == ArithOp::Input).count();
+        if inputs.len() != ninputs {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: inputs.len(),
+                needed: ninputs,
+            }));
+        }
+        let mut inputs = inputs.iter();
+        let mut slots: Vec<Slot<F::Item>> = Vec::with_capacity(self.ops.len());
+        for (&op, &repr) in self.ops.iter().zip(self.reprs.iter()) {
+            let slot = match op {
+                ArithOp::Input => {
+                    let x = inputs.next().expect("inputs were counted");
+                    self.check_input(x)?;
+                    Slot::from(x.clone())
+                }
+                _ => apply(f, op, repr, &mut slots, self.bitwidth, self.modulus)?,
+            };
+            slots.push(slot);
+        }
+        let last = slots.pop().expect("plans are not empty");
+        let repr = self.reprs[self.reprs.len() - 1];
+        Ok(match repr {
+            Repr::Binary => Value::Binary(last.binary.expect("computed in binary")),
+            Repr::Crt => Value::Crt(last.crt.expect("computed in CRT")),
+        })
+    }
+
+    fn check_input<W: Clone + HasModulus>(&self, x: &Value<W>) -> Result<(), FancyError> {
+        match x {
+            Value::Binary(x) if x.size() != self.bitwidth || !x.is_binary() => Err(
+                FancyError::InvalidArg(format!("binary inputs must have {} bits", self.bitwidth)),
+            ),
+            Value::Crt(x) if x.moduli_product_checked() != Some(self.modulus) => Err(
+                FancyError::InvalidArg(format!("CRT inputs must have modulus {}", self.modulus)),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A value in the representations it has been computed or converted to.
+struct Slot<W> {
+    binary: Option<BinaryBundle<W>>,
+    crt: Option<CrtBundle<W>>,
+}
+
+impl<W> From<Value<W>> for Slot<W> {
+    fn from(x: Value<W>) -> Self {
+        match x {
+            Value::Binary(x) => Slot {
+                binary: Some(x),
+                crt: None,
+            },
+            Value::Crt(x) => Slot {
+                binary: None,
+                crt: Some(x),
+            },
+        }
+    }
+}
+
+fn encode<F: FancyInput>(
+    f: &mut F,
+    repr: Repr,
+    value: u128,
+    bitwidth: usize,
+    modulus: u128,
+) -> Result<Value<F::Item>, F::Error> {
+    match repr {
+        Repr::Binary => f.bin_encode(value, bitwidth).map(Value::Binary),
+        Repr::Crt => f.crt_encode(value, modulus).map(Value::Crt),
+    }
+}
+
+/// Compute `op` in `repr` on the values in `slots`, converting its operands
+/// to `repr` first if they are not there yet.
+fn apply<F: CrtGadgets>(
+    f: &mut F,
+    op: ArithOp,
+    repr: Repr,
+    slots: &mut [Slot<F::Item>],
+    bitwidth: usize,
+    modulus: u128,
+) -> Result<Slot<F::Item>, F::Error> {
+    let operands = op.operands();
+    for &j in &operands {
+        let slot = &mut slots[j];
+        match (repr, &slot.binary, &slot.crt) {
+            (Repr::Binary, None, Some(x)) => slot.binary = Some(f.crt_to_bin(x, bitwidth)?),
+            (Repr::Crt, Some(x), None) => slot.crt = Some(f.bin_to_crt(x, modulus)?),
+            _ => (),
+        }
+    }
+    let z = match repr {
+        Repr::Binary => {
+            let xs = operands
+                .iter()
+                .map(|&j| slots[j].binary.as_ref().expect("converted to binary"))
+                .collect_vec();
+            Value::Binary(match op {
+                ArithOp::Input => unreachable!("inputs are not computed"),
+                ArithOp::Add(..) => f.bin_addition_no_carry(xs[0], xs[1])?,
+                ArithOp::Sub(..) => f.bin_subtraction(xs[0], xs[1])?.0,
+                ArithOp::Mul(..) => f.bin_multiplication_lower_half(xs[0], xs[1])?,
+                ArithOp::Cmul(_, c) => f.bin_cmul(xs[0], c, bitwidth)?,
+                ArithOp::Lt(..) => {
+                    let lt = f.bin_lt(xs[0], xs[1])?;
+                    let zero = f.constant(0, 2)?;
+                    let mut z = BinaryBundle::new(vec![lt]);
+                    z.pad(&zero, bitwidth - 1);
+                    z
+                }
+            })
+        }
+        Repr::Crt => {
+            let xs = operands
+                .iter()
+                .map(|&j| slots[j].crt.as_ref().expect("converted to CRT"))
+                .collect_vec();
+            Value::Crt(match op {
+                ArithOp::Input => unreachable!("inputs are not computed"),
+                ArithOp::Add(..) => f.crt_add(xs[0], xs[1])?,
+                ArithOp::Sub(..) => f.crt_sub(xs[0], xs[1])?,
+                ArithOp::Mul(..) => f.crt_mul(xs[0], xs[1])?,
+                ArithOp::Cmul(_, c) => f.crt_cmul(xs[0], c)?,
+                ArithOp::Lt(..) => {
+                    let lt = f.crt_lt(xs[0], xs[1], "100%")?;
+                    f.bin_to_crt(&BinaryBundle::new(vec![lt]), modulus)?
+                }
+            })
+        }
+    };
+    Ok(Slot::from(z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArithOp::*, *};
+    use rand::{thread_rng, Rng};
+
+    fn eval_plain(ops: &[ArithOp], inputs: &[u128]) -> u128 {
+        let mut inputs = inputs.iter();
+        let mut xs: Vec<u128> = Vec::new();
+        for op in ops {
+            let z = match *op {
+                Input => *inputs.next().unwrap(),
+                Add(x, y) => xs[x] + xs[y],
+                Sub(x, y) => xs[x] - xs[y],
+                Mul(x, y) => xs[x] * xs[y],
+                Cmul(x, c) => xs[x] * c,
+                Lt(x, y) => (xs[x] < xs[y]) as u128,
+            };
+            xs.push(z);
+        }
+        xs.pop().unwrap()
+    }
+
+    /// Check that `plan`, and the plans all in binary and all in CRT, compute
+    /// `ops` on random inputs below `2^bound`.
+    fn check(ops: &[ArithOp], bitwidth: usize, bound: u32) {
+        let model = CostModel::new(bitwidth).unwrap();
+        let plans = [
+            model.plan(ops).unwrap(),
+            model.plan_in(ops, Repr::Binary).unwrap(),
+            model.plan_in(ops, Repr::Crt).unwrap(),
+        ];
+        let ninputs = ops.iter().filter(|&&op| op == Input).count();
+        let mut rng = thread_rng();
+        for _ in 0..16 {
+            let inputs = (0..ninputs)
+                .map(|_| rng.gen_range(0..1 << bound))
+                .collect_vec();
+            let should_be = eval_plain(ops, &inputs);
+            for plan in &plans {
+                let mut f = Dummy::new();
+                let xs = plan.encode(&mut f, &inputs).unwrap();
+                let z = plan.execute(&mut f, &xs).unwrap();
+                assert_eq!(z.repr(), *plan.reprs().last().unwrap());
+                assert_eq!(z.output(&mut f).unwrap(), Some(should_be), "{:?}", plan);
+            }
+        }
+    }
+
+    #[test]
+    fn test_multiplications_choose_crt() {
+        // x * y * y * y + x
+        let ops = [Input, Input, Mul(0, 1), Mul(2, 1), Mul(3, 1), Add(4, 0)];
+        let plan = choose_representation(&ops, 16).unwrap();
+        assert!(plan.reprs().iter().all(|&r| r == Repr::Crt), "{:?}", plan);
+        assert!(plan.conversions().is_empty());
+        assert_eq!(plan.cost(), plan.crt_cost());
+        assert!(plan.crt_cost() < plan.binary_cost());
+        check(&ops, 16, 4);
+    }
+
+    #[test]
+    fn test_comparisons_choose_binary() {
+        // (a < b) + (c < d) + (b < c)
+        let ops = [
+            Input,
+            Input,
+            Input,
+            Input,
+            Lt(0, 1),
+            Lt(2, 3),
+            Lt(1, 2),
+            Add(4, 5),
+            Add(7, 6),
+        ];
+        let plan = choose_representation(&ops, 16).unwrap();
+        assert!(
+            plan.reprs().iter().all(|&r| r == Repr::Binary),
+            "{:?}",
+            plan
+        );
+        assert_eq!(plan.cost(), plan.binary_cost());
+        assert!(plan.binary_cost() < plan.crt_cost());
+        check(&ops, 16, 16);
+    }
+
+    #[test]
+    fn test_mixed() {
+        // Comparisons of inputs, whose results are multiplied together and
+        // with an input: the comparisons are cheapest in binary, and the
+        // multiplications in CRT.
+        let ops = [
+            Input,
+            Input,
+            Input,
+            Input,
+            Lt(0, 1),
+            Lt(2, 3),
+            Lt(0, 2),
+            Lt(1, 3),
+            Mul(4, 5),
+            Mul(6, 7),
+            Mul(8, 9),
+            Mul(10, 0),
+            Cmul(11, 3),
+            Add(12, 3),
+        ];
+        let plan = choose_representation(&ops, 16).unwrap();
+        assert!(plan.cost() < plan.binary_cost().min(plan.crt_cost()));
+        assert!(plan.input_reprs().iter().all(|&r| r == Repr::Binary));
+        assert_eq!(plan.reprs()[4..8], [Repr::Binary; 4]);
+        assert_eq!(plan.reprs()[8..], [Repr::Crt; 6]);
+        // The comparisons, and the inputs used in CRT, are converted once.
+        assert_eq!(
+            plan.conversions(),
+            [
+                (4, Repr::Crt),
+                (5, Repr::Crt),
+                (6, Repr::Crt),
+                (7, Repr::Crt),
+                (0, Repr::Crt),
+                (3, Repr::Crt)
+            ]
+        );
+        check(&ops, 16, 13);
+    }
+
+    #[test]
+    fn test_costs_from_informer() {
+        let model = CostModel::new(8).unwrap();
+        // An 8-bit addition has 7 full adders of one AND each; CRT addition
+        // is free.
+        assert_eq!(model.op_cost(&Add(0, 1), Repr::Binary), 14);
+        assert_eq!(model.op_cost(&Add(3, 5), Repr::Crt), 0);
+        assert_eq!(model.op_cost(&Cmul(0, 6), Repr::Crt), 0);
+        assert!(model.op_cost(&Cmul(0, 6), Repr::Binary) < model.op_cost(&Mul(0, 1), Repr::Binary));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            choose_representation(&[Input], 1),
+            Err(PlanError::Bitwidth(1))
+        );
+        assert_eq!(
+            choose_representation(&[Input], MAX_BITWIDTH + 1),
+            Err(PlanError::Bitwidth(MAX_BITWIDTH + 1))
+        );
+        assert_eq!(choose_representation(&[], 8), Err(PlanError::Empty));
+        assert_eq!(
+            choose_representation(&[Input, Add(0, 1)], 8),
+            Err(PlanError::Operand { op: 1, operand: 1 })
+        );
+
+        let plan = choose_representation(&[Input, Input, Add(0, 1)], 8).unwrap();
+        let mut f = Dummy::new();
+        assert!(plan.encode(&mut f, &[1]).is_err());
+        let xs = plan.encode(&mut f, &[1, 2]).unwrap();
+        assert!(plan.execute(&mut f, &xs[..1]).is_err());
+        let wide = Value::Binary(f.bin_encode(1, 9).unwrap());
+        assert!(plan.execute(&mut f, &[wide, xs[1].clone()]).is_err());
+    }
+}
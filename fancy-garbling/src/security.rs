@@ -0,0 +1,217 @@
+//! The security parameters of garbling and oblivious transfer.
+
+use crate::errors::SecurityParamsError;
+
+/// The security parameters used by a garbler and evaluator.
+///
+/// The parameters are:
+///
+/// - The width of the labels of binary wires, and of the ciphertexts of
+///   binary `and` gates and custom gates, in bits. Narrower labels are the
+///   labels of the default width with their upper bits cleared, and are sent
+///   in as many bytes as they take. Labels wider than 128 bits are held in a
+///   [`WideWireMod2`](crate::WideWireMod2), which garbles binary circuits
+///   with a hash as wide as its labels. Labels of other moduli, and the
+///   ciphertexts of multiplication and projection gates, keep their 128 bits.
+/// - The statistical security parameter of the consistency check of
+///   oblivious transfer extensions that have one, such as KOS, in bits.
+/// - The width of the hashes that decode the outputs, in bits.
+///
+/// [`SecurityParams::DEFAULT`] is what every garbler and evaluator used
+/// before these parameters could be set, and is what they use unless told
+/// otherwise. Both parties must use the same parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawSecurityParams"))]
+pub struct SecurityParams {
+    label_bits: usize,
+    statistical_bits: usize,
+    hash_bits: usize,
+}
+
+impl SecurityParams {
+    /// 128-bit labels and output hashes, and 40 bits of statistical security.
+    pub const DEFAULT: SecurityParams = SecurityParams {
+        label_bits: 128,
+        statistical_bits: 40,
+        hash_bits: 128,
+    };
+
+    /// 64-bit labels, 32-bit output hashes, and 16 bits of statistical
+    /// security, for simulating large computations cheaply.
+    ///
+    /// This is not secure, and is meant for testing and benchmarking only.
+    pub const SIMULATION: SecurityParams = SecurityParams {
+        label_bits: 64,
+        statistical_bits: 16,
+        hash_bits: 32,
+    };
+
+    /// 256-bit labels, for computations that must stay secret after 128-bit
+    /// labels no longer keep them so, with the statistical security and
+    /// output hashes of [`SecurityParams::DEFAULT`].
+    ///
+    /// The labels are [`WideWireMod2`](crate::WideWireMod2)s, and garbling
+    /// with them is several times slower than with the default.
+    pub const LONG_TERM: SecurityParams = SecurityParams {
+        label_bits: 256,
+        statistical_bits: 40,
+        hash_bits: 128,
+    };
+
+    /// Check and make the parameters.
+    ///
+    /// The label width must be a multiple of 8 from 32 to 256, the
+    /// statistical security a multiple of 8 from 8 to 128, and the hash width
+    /// a multiple of 8 from 16 to 128.
+    pub fn new(
+        label_bits: usize,
+        statistical_bits: usize,
+        hash_bits: usize,
+    ) -> Result<Self, SecurityParamsError> {
+        // The widths from `min` to `max` bits that fill whole bytes.
+        let whole_bytes =
+            |bits: usize, min: usize, max: usize| (min..=max).step_by(8).any(|b| b == bits);
+        if !whole_bytes(label_bits, 32, 256) {
+            return Err(SecurityParamsError::LabelBits(label_bits));
+        }
+        if !whole_bytes(statistical_bits, 8, 128) {
+            return Err(SecurityParamsError::StatisticalBits(statistical_bits));
+        }
+        if !whole_bytes(hash_bits, 16, 128) {
+            return Err(SecurityParamsError::HashBits(hash_bits));
+        }
+        Ok(SecurityParams {
+            label_bits,
+            statistical_bits,
+            hash_bits,
+        })
+    }
+
+    /// The width of the labels of binary wires, in bits.
+    pub fn label_bits(&self) -> usize {
+        self.label_bits
+    }
+
+    /// The statistical security parameter of oblivious transfer, in bits.
+    pub fn statistical_bits(&self) -> usize {
+        self.statistical_bits
+    }
+
+    /// The width of the hashes that decode the outputs, in bits.
+    pub fn hash_bits(&self) -> usize {
+        self.hash_bits
+    }
+
+    /// The number of bytes a label of a binary wire is sent in.
+    pub(crate) fn label_bytes(&self) -> usize {
+        self.label_bits / 8
+    }
+
+    /// The number of bytes an output hash is sent in.
+    pub(crate) fn hash_bytes(&self) -> usize {
+        self.hash_bits / 8
+    }
+
+    /// The number of blocks a label of a binary wire spans, each of which
+    /// takes an oblivious transfer of its own.
+    #[cfg(feature = "std")]
+    pub(crate) fn label_blocks(&self) -> usize {
+        (self.label_bytes() + 15) / 16
+    }
+
+    /// The mask clearing the bits of a binary label of at most 128 bits
+    /// above the label width.
+    pub(crate) fn label_mask(&self) -> u128 {
+        u128::MAX >> (128 - self.label_bits.min(128))
+    }
+
+    /// Fail unless the labels are at most `max` bits wide, the widest that
+    /// the wire labels, or the call, can take.
+    pub(crate) fn check_label_bits(&self, max: usize) -> Result<(), SecurityParamsError> {
+        if self.label_bits > max {
+            return Err(SecurityParamsError::UnsupportedLabelBits {
+                label_bits: self.label_bits,
+                max,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Default for SecurityParams {
+    fn default() -> Self {
+        SecurityParams::DEFAULT
+    }
+}
+
+/// The unchecked fields of serialized parameters.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawSecurityParams {
+    label_bits: usize,
+    statistical_bits: usize,
+    hash_bits: usize,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawSecurityParams> for SecurityParams {
+    type Error = SecurityParamsError;
+
+    fn try_from(raw: RawSecurityParams) -> Result<Self, SecurityParamsError> {
+        SecurityParams::new(raw.label_bits, raw.statistical_bits, raw.hash_bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        assert_eq!(
+            SecurityParams::new(128, 40, 128),
+            Ok(SecurityParams::DEFAULT)
+        );
+        assert_eq!(
+            SecurityParams::new(64, 16, 32),
+            Ok(SecurityParams::SIMULATION)
+        );
+        assert_eq!(
+            SecurityParams::new(256, 40, 128),
+            Ok(SecurityParams::LONG_TERM)
+        );
+        assert_eq!(
+            SecurityParams::new(264, 40, 128),
+            Err(SecurityParamsError::LabelBits(264))
+        );
+        assert_eq!(
+            SecurityParams::new(24, 40, 128),
+            Err(SecurityParamsError::LabelBits(24))
+        );
+        assert_eq!(
+            SecurityParams::new(128, 41, 128),
+            Err(SecurityParamsError::StatisticalBits(41))
+        );
+        assert_eq!(
+            SecurityParams::new(128, 40, 8),
+            Err(SecurityParamsError::HashBits(8))
+        );
+        assert_eq!(SecurityParams::DEFAULT.label_mask(), u128::MAX);
+        assert_eq!(SecurityParams::SIMULATION.label_mask(), u64::MAX as u128);
+        assert_eq!(SecurityParams::LONG_TERM.label_mask(), u128::MAX);
+        #[cfg(feature = "std")]
+        {
+            assert_eq!(SecurityParams::SIMULATION.label_blocks(), 1);
+            assert_eq!(SecurityParams::LONG_TERM.label_blocks(), 2);
+        }
+        assert_eq!(
+            SecurityParams::LONG_TERM.check_label_bits(128),
+            Err(SecurityParamsError::UnsupportedLabelBits {
+                label_bits: 256,
+                max: 128
+            })
+        );
+        assert_eq!(SecurityParams::LONG_TERM.check_label_bits(256), Ok(()));
+    }
+}
@@ -0,0 +1,365 @@
+//! Ready-made binary circuits for common functions.
+//!
+//! These are built with a [`CircuitBuilder`] and the binary gadgets rather
+//! than parsed from Bristol files.
+//!
+//! # Wire layout
+//!
+//! Apart from [`sha256_1block`] and [`sha256_compress`], each circuit takes an unsigned integer `x`
+//! from the garbler and an unsigned integer `y` of the same width from the
+//! evaluator. Numbers are written in [`BIT_ORDER`], most significant bit
+//! first, in both the inputs and the outputs. This is the order of
+//! [`CircuitInput`](crate::circuit::CircuitInput), so the inputs of, e.g.,
+//! [`cmp64`] are `c.assemble_garbler_inputs(&[&x])` and
+//! `c.assemble_evaluator_inputs(&[&y])` for `x, y: u64`, and its output is
+//! `BIT_ORDER.value(&outputs)`.
+
+use crate::{
+    circuit::{BinaryCircuit, CircuitBuilder, CircuitRef},
+    errors::CircuitBuilderError,
+    util::BitOrder,
+    BinaryBundle, BinaryGadgets, Fancy,
+};
+
+/// The order of the bits of the numbers input to and output from the
+/// circuits here.
+pub const BIT_ORDER: BitOrder = BitOrder::MsbFirst;
+
+type Builder = CircuitBuilder<BinaryCircuit>;
+type Word = BinaryBundle<CircuitRef>;
+
+/// `x < y` for 32-bit `x` and `y`, as a single output bit.
+pub fn cmp32() -> BinaryCircuit {
+    cmp(32)
+}
+
+/// `x < y` for 64-bit `x` and `y`, as a single output bit.
+pub fn cmp64() -> BinaryCircuit {
+    cmp(64)
+}
+
+/// `x == y` for 32-bit `x` and `y`, as a single output bit.
+pub fn eq32() -> BinaryCircuit {
+    eq(32)
+}
+
+/// `x == y` for 64-bit `x` and `y`, as a single output bit.
+pub fn eq64() -> BinaryCircuit {
+    eq(64)
+}
+
+/// The full 64-bit product of 32-bit `x` and `y`.
+pub fn mul32() -> BinaryCircuit {
+    mul(32)
+}
+
+/// The full 128-bit product of 64-bit `x` and `y`.
+pub fn mul64() -> BinaryCircuit {
+    mul(64)
+}
+
+/// The larger of 32-bit `x` and `y`, in 32 bits.
+pub fn max32() -> BinaryCircuit {
+    max(32)
+}
+
+/// The larger of 64-bit `x` and `y`, in 64 bits.
+pub fn max64() -> BinaryCircuit {
+    max(64)
+}
+
+/// The SHA-256 compression function applied once to the initial hash value.
+///
+/// The garbler inputs a single 512-bit message block, and there are no
+/// evaluator inputs. The 256 outputs are the resulting hash value. Both are
+/// bytes in order, each most significant bit first, so for a message of at
+/// most 55 bytes padded to one block as SHA-256 specifies, the inputs are
+/// `c.assemble_garbler_inputs(&[&block])` for `block: [u8; 64]`, and the
+/// outputs are the bits of the message's digest.
+pub fn sha256_1block() -> BinaryCircuit {
+    build(|b| {
+        let h = SHA256_H
+            .iter()
+            .map(|&h| b.bin_constant_bundle(h as u128, 32))
+            .collect::<Result<Vec<_>, _>>()?;
+        let w = (0..16).map(|_| input(b, 32, true)).collect();
+        for z in sha256_compress_words(b, &h, w)? {
+            output(b, &z)?;
+        }
+        Ok(())
+    })
+}
+
+/// The SHA-256 compression function.
+///
+/// The garbler inputs the 256-bit hash value so far, and the evaluator a
+/// 512-bit message block. The 256 outputs are the next hash value. All three
+/// are bytes in order, each most significant bit first, as in
+/// [`sha256_1block`], so the outputs can be the garbler inputs of the next
+/// block; [`twopac::streaming_hash`](crate::twopac::streaming_hash) chains
+/// them that way.
+pub fn sha256_compress() -> BinaryCircuit {
+    build(|b| {
+        let h = (0..8).map(|_| input(b, 32, true)).collect::<Vec<_>>();
+        let w = (0..16).map(|_| input(b, 32, false)).collect();
+        for z in sha256_compress_words(b, &h, w)? {
+            output(b, &z)?;
+        }
+        Ok(())
+    })
+}
+
+/// The initial hash value of SHA-256, as bytes, each most significant bit
+/// first.
+#[cfg(any(test, feature = "std"))]
+pub(crate) fn sha256_initial_value() -> Vec<crate::Modulus> {
+    SHA256_H
+        .iter()
+        .flat_map(|&h| BIT_ORDER.bits(h.into(), 32))
+        .collect()
+}
+
+/// Compress the message block `w`, sixteen words, into the hash value `h`,
+/// eight words, returning the next hash value.
+fn sha256_compress_words(
+    b: &mut Builder,
+    h: &[Word],
+    mut w: Vec<Word>,
+) -> Result<Vec<Word>, CircuitBuilderError> {
+    let zero = b.constant(0, 2)?;
+    for t in 16..64 {
+        let s0 = sigma(b, &w[t - 15], [7, 18], 3, zero)?;
+        let s1 = sigma(b, &w[t - 2], [17, 19], 10, zero)?;
+        let x = b.bin_addition_no_carry(&w[t - 16], &s0)?;
+        let y = b.bin_addition_no_carry(&w[t - 7], &s1)?;
+        w.push(b.bin_addition_no_carry(&x, &y)?);
+    }
+
+    let mut v = h.to_vec();
+    for t in 0..64 {
+        let k = b.bin_constant_bundle(SHA256_K[t] as u128, 32)?;
+        let s1 = big_sigma(b, &v[4], [6, 11, 25])?;
+        let ch = choose(b, &v[4], &v[5], &v[6])?;
+        let x = b.bin_addition_no_carry(&v[7], &s1)?;
+        let y = b.bin_addition_no_carry(&ch, &k)?;
+        let z = b.bin_addition_no_carry(&x, &y)?;
+        let t1 = b.bin_addition_no_carry(&z, &w[t])?;
+        let s0 = big_sigma(b, &v[0], [2, 13, 22])?;
+        let maj = majority(b, &v[0], &v[1], &v[2])?;
+        let t2 = b.bin_addition_no_carry(&s0, &maj)?;
+
+        v.rotate_right(1);
+        v[4] = b.bin_addition_no_carry(&v[4], &t1)?;
+        v[0] = b.bin_addition_no_carry(&t1, &t2)?;
+    }
+
+    h.iter()
+        .zip(v.iter())
+        .map(|(h, v)| b.bin_addition_no_carry(h, v))
+        .collect()
+}
+
+fn cmp(nbits: usize) -> BinaryCircuit {
+    build(|b| {
+        let (x, y) = inputs(b, nbits);
+        let z = b.bin_lt(&x, &y)?;
+        b.output(&z).map(|_| ())
+    })
+}
+
+fn eq(nbits: usize) -> BinaryCircuit {
+    build(|b| {
+        let (x, y) = inputs(b, nbits);
+        let z = b.bin_eq_bundles(&x, &y)?;
+        b.output(&z).map(|_| ())
+    })
+}
+
+fn mul(nbits: usize) -> BinaryCircuit {
+    build(|b| {
+        let (x, y) = inputs(b, nbits);
+        let z = b.bin_mul(&x, &y)?;
+        output(b, &z)
+    })
+}
+
+fn max(nbits: usize) -> BinaryCircuit {
+    build(|b| {
+        let (x, y) = inputs(b, nbits);
+        let z = b.bin_max(&[x, y])?;
+        output(b, &z)
+    })
+}
+
+/// Build a circuit with `f`. The circuits here have fixed shapes, so this
+/// cannot fail.
+fn build(f: impl FnOnce(&mut Builder) -> Result<(), CircuitBuilderError>) -> BinaryCircuit {
+    let mut b = Builder::new();
+    f(&mut b).expect("stdlib circuits are well-formed");
+    b.finish()
+}
+
+/// Take `nbits` inputs from the garbler or the evaluator, in [`BIT_ORDER`].
+fn input(b: &mut Builder, nbits: usize, garbler: bool) -> Word {
+    if garbler {
+        b.bin_garbler_input_ordered(nbits, BIT_ORDER)
+    } else {
+        b.bin_evaluator_input_ordered(nbits, BIT_ORDER)
+    }
+}
+
+/// Take `x` from the garbler and `y` from the evaluator.
+fn inputs(b: &mut Builder, nbits: usize) -> (Word, Word) {
+    (input(b, nbits, true), input(b, nbits, false))
+}
+
+/// Output `x` in [`BIT_ORDER`].
+fn output(b: &mut Builder, x: &Word) -> Result<(), CircuitBuilderError> {
+    b.bin_output_ordered(x, BIT_ORDER)
+}
+
+fn rotr(x: &Word, n: usize) -> Word {
+    let ws = x.wires();
+    BinaryBundle::new((0..ws.len()).map(|i| ws[(i + n) % ws.len()]).collect())
+}
+
+fn shr(x: &Word, n: usize, zero: CircuitRef) -> Word {
+    let ws = x.wires();
+    BinaryBundle::new(
+        (0..ws.len())
+            .map(|i| ws.get(i + n).copied().unwrap_or(zero))
+            .collect(),
+    )
+}
+
+/// SHA-256's `σ`: two rotations and a shift of `x`, xored together.
+fn sigma(
+    b: &mut Builder,
+    x: &Word,
+    rotations: [usize; 2],
+    shift: usize,
+    zero: CircuitRef,
+) -> Result<Word, CircuitBuilderError> {
+    let y = b.bin_xor(&rotr(x, rotations[0]), &rotr(x, rotations[1]))?;
+    b.bin_xor(&y, &shr(x, shift, zero))
+}
+
+/// SHA-256's `Σ`: three rotations of `x`, xored together.
+fn big_sigma(
+    b: &mut Builder,
+    x: &Word,
+    rotations: [usize; 3],
+) -> Result<Word, CircuitBuilderError> {
+    let y = b.bin_xor(&rotr(x, rotations[0]), &rotr(x, rotations[1]))?;
+    b.bin_xor(&y, &rotr(x, rotations[2]))
+}
+
+/// `(e & f) ^ (!e & g)`, with one AND per bit.
+fn choose(b: &mut Builder, e: &Word, f: &Word, g: &Word) -> Result<Word, CircuitBuilderError> {
+    let fg = b.bin_xor(f, g)?;
+    let z = b.bin_and(e, &fg)?;
+    b.bin_xor(g, &z)
+}
+
+/// The bitwise majority of `x`, `y`, and `z`, with one AND per bit.
+fn majority(b: &mut Builder, x: &Word, y: &Word, z: &Word) -> Result<Word, CircuitBuilderError> {
+    let xy = b.bin_xor(x, y)?;
+    let yz = b.bin_xor(y, z)?;
+    let w = b.bin_and(&xy, &yz)?;
+    b.bin_xor(&w, y)
+}
+
+const SHA256_H: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+const SHA256_K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{eval_plain, CircuitType},
+        util::RngExt,
+    };
+    use rand::thread_rng;
+    use sha2::{Digest, Sha256};
+
+    fn eval(c: &BinaryCircuit, x: u64, y: u64, nbits: usize) -> u128 {
+        let bits = |v: u64| BIT_ORDER.bits(v.into(), nbits);
+        BIT_ORDER.value(&eval_plain(c, &bits(x), &bits(y)).unwrap())
+    }
+
+    fn check(c: &BinaryCircuit, nbits: usize, f: impl Fn(u64, u64) -> u128) {
+        let mut rng = thread_rng();
+        let max = u64::MAX >> (64 - nbits);
+        let mut xs = vec![0, 1, max, max - 1, 1 << (nbits - 1)];
+        xs.extend((0..16).map(|_| rng.gen_u64() & max));
+        for &x in &xs {
+            for &y in &xs {
+                assert_eq!(eval(c, x, y, nbits), f(x, y), "x={} y={}", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn arithmetic() {
+        for (nbits, c) in [(32, cmp32()), (64, cmp64())] {
+            check(&c, nbits, |x, y| (x < y) as u128);
+        }
+        for (nbits, c) in [(32, eq32()), (64, eq64())] {
+            check(&c, nbits, |x, y| (x == y) as u128);
+        }
+        for (nbits, c) in [(32, mul32()), (64, mul64())] {
+            check(&c, nbits, |x, y| x as u128 * y as u128);
+        }
+        for (nbits, c) in [(32, max32()), (64, max64())] {
+            check(&c, nbits, |x, y| x.max(y) as u128);
+        }
+    }
+
+    #[test]
+    fn sha256() {
+        let c = sha256_1block();
+        let mut rng = thread_rng();
+        for len in [0, 3, 32, 55] {
+            let msg = (0..len).map(|_| rng.gen_u16() as u8).collect::<Vec<_>>();
+            let mut block = [0u8; 64];
+            block[..len].copy_from_slice(&msg);
+            block[len] = 0x80;
+            block[56..].copy_from_slice(&(8 * len as u64).to_be_bytes());
+
+            let gb = c.assemble_garbler_inputs(&[&block]).unwrap();
+            let out = eval_plain(&c, &gb, &[]).unwrap();
+            let digest = BIT_ORDER.bits_to_bytes(&out).unwrap();
+            assert_eq!(digest, Sha256::digest(&msg).to_vec(), "len={}", len);
+        }
+    }
+
+    #[test]
+    fn sha256_chained() {
+        let c = sha256_compress();
+        let mut rng = thread_rng();
+        let msg = (0..100).map(|_| rng.gen_u16() as u8).collect::<Vec<_>>();
+        let mut padded = [0u8; 128];
+        padded[..100].copy_from_slice(&msg);
+        padded[100] = 0x80;
+        padded[120..].copy_from_slice(&800u64.to_be_bytes());
+
+        let mut h = sha256_initial_value();
+        for block in padded.chunks(64) {
+            h = eval_plain(&c, &h, &BIT_ORDER.bytes_to_bits(block)).unwrap();
+        }
+        let digest = BIT_ORDER.bits_to_bytes(&h).unwrap();
+        assert_eq!(digest, Sha256::digest(&msg).to_vec());
+    }
+}
@@ -0,0 +1,442 @@
+//! Two-party computation secure against a malicious party up to one bit of
+//! leakage, by dual execution, after Mohassel and Franklin, and Huang, Katz,
+//! and Evans.
+//!
+//! Each [`Party`] holds the inputs of one side of a binary circuit, its
+//! garbler inputs or its evaluator inputs, and both garble and evaluate it:
+//!
+//! 1. The party holding the garbler inputs garbles the circuit with the same
+//!    half-gates garbler as [`semihonest`](super::semihonest), and the other
+//!    evaluates it, learning the labels of its inputs by oblivious transfer
+//!    and decoding the outputs.
+//! 2. The roles are swapped: the party holding the evaluator inputs garbles
+//!    the circuit, and the other evaluates it.
+//! 3. Each party hashes, for each output, the label it garbled of the value
+//!    it decoded and the label it evaluated. The party holding the garbler
+//!    inputs commits to its hash, the other sends its own, and the first
+//!    opens its commitment. The outputs are returned only if the hashes are
+//!    equal, and otherwise [`TwopacError::EqualityCheckFailed`] is.
+//!
+//! If both parties are honest, they decode the same outputs, and hash the
+//! same labels. A malicious party cannot make the honest one accept other
+//! outputs, since it would have to guess a label of the honest party's
+//! garbling that it did not evaluate.
+//!
+//! # Leakage
+//!
+//! A malicious party learns one bit more than the outputs: whether the
+//! equality check passes. By garbling another function, or by corrupting the
+//! labels of one value of an input it sends by oblivious transfer, it can
+//! make the check pass only on inputs of the honest party of its choice, say
+//! those with a given first bit, and learn that bit. The honest party goes
+//! on to the check when it cannot decode an output, so that a failure shows
+//! no earlier than any other.
+//!
+//! Each party decodes its outputs before the check, and a malicious party
+//! can abort once it has them, so dual execution is not fair.
+//!
+//! The oblivious transfers must be secure against a malicious receiver, such
+//! as those of [`KosSender`](ocelot::ot::KosSender) and
+//! [`KosReceiver`](ocelot::ot::KosReceiver).
+
+use super::zk::read_array;
+use crate::{
+    circuit::{BinaryCircuit, CircuitDigest, CircuitType, EvaluableCircuit},
+    errors::{EvaluatorError, FancyError, TwopacError},
+    fancy::check_inputs,
+    Evaluator as Ev, Fancy, Garbler as Gb, Modulus, WireLabel, WireMod2,
+};
+use ocelot::ot::{Receiver as OtReceiver, Sender as OtSender};
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{
+    commitment::{Commitment, ShaCommitment},
+    AbstractChannel, AesRng, Block, Malicious,
+};
+use sha2::{Digest, Sha256};
+use std::{cmp::Ordering, marker::PhantomData};
+
+/// Which inputs of the circuit a [`Party`] holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    /// The party holding the garbler inputs, which garbles first.
+    Garbler,
+    /// The party holding the evaluator inputs, which evaluates first.
+    Evaluator,
+}
+
+/// A party of a dual execution.
+pub struct Party<C, RNG, OTS, OTR> {
+    channel: C,
+    rng: RNG,
+    role: Role,
+    _ot: PhantomData<(OTS, OTR)>,
+}
+
+impl<
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+        OTS: OtSender<Msg = Block> + Malicious,
+        OTR: OtReceiver<Msg = Block> + Malicious,
+    > Party<C, RNG, OTS, OTR>
+{
+    /// Make a new `Party`, holding the inputs of `role`.
+    pub fn new(channel: C, rng: RNG, role: Role) -> Self {
+        Party {
+            channel,
+            rng,
+            role,
+            _ot: PhantomData,
+        }
+    }
+
+    /// Compute `circuit` on `inputs`, the inputs of this party's role, and
+    /// the inputs of the peer, returning its outputs.
+    ///
+    /// Fails with [`TwopacError::CircuitMismatch`] if the peer has another
+    /// circuit, and with [`TwopacError::EqualityCheckFailed`] if the two
+    /// executions give different outputs, as they do when the peer cheats.
+    pub fn execute(
+        &mut self,
+        circuit: &BinaryCircuit,
+        inputs: &[Modulus],
+    ) -> Result<Vec<Modulus>, TwopacError> {
+        self.run(circuit, circuit, inputs)
+    }
+
+    /// Get a reference to the channel.
+    pub fn get_channel(&mut self) -> &mut C {
+        &mut self.channel
+    }
+
+    /// Execute `circuit`, garbling `garbled`, which is `circuit` unless a
+    /// test makes this party cheat.
+    fn run(
+        &mut self,
+        garbled: &BinaryCircuit,
+        circuit: &BinaryCircuit,
+        inputs: &[Modulus],
+    ) -> Result<Vec<Modulus>, TwopacError> {
+        check_own_inputs(circuit, self.role, inputs)?;
+        let ours = circuit.digest();
+        self.channel.write_bytes(ours.as_bytes())?;
+        self.channel.flush()?;
+        let theirs = CircuitDigest::from_bytes(read_array(&mut self.channel)?);
+        if theirs != ours {
+            return Err(TwopacError::CircuitMismatch { ours, theirs });
+        }
+
+        let ((zeros, delta), (labels, outputs)) = match self.role {
+            Role::Garbler => {
+                let garbling = self.garble(garbled, inputs)?;
+                (garbling, self.evaluate(circuit, inputs)?)
+            }
+            Role::Evaluator => {
+                let evaluation = self.evaluate(circuit, inputs)?;
+                (self.garble(garbled, inputs)?, evaluation)
+            }
+        };
+
+        // Hash the labels of each output in the garbling of the party holding
+        // the garbler inputs and then in that of the other, so that both
+        // parties hash them in the same order.
+        let mut hash = Sha256::new();
+        for ((zero, label), &output) in zeros.iter().zip(&labels).zip(&outputs) {
+            let garbled = zero.plus(&delta.cmul(output.unwrap_or(0)));
+            let (first, second) = match self.role {
+                Role::Garbler => (garbled, *label),
+                Role::Evaluator => (*label, garbled),
+            };
+            hash.update(<[u8; 16]>::from(first.as_block()));
+            hash.update(<[u8; 16]>::from(second.as_block()));
+        }
+        let equal = self.check_equal(hash.finalize().into())?;
+        if !equal || zeros.len() != outputs.len() {
+            return Err(TwopacError::EqualityCheckFailed);
+        }
+        outputs
+            .into_iter()
+            .map(|output| output.ok_or(TwopacError::EqualityCheckFailed))
+            .collect()
+    }
+
+    /// Garble `circuit` for the peer to evaluate, returning the zero labels
+    /// of its outputs and the delta.
+    fn garble(
+        &mut self,
+        circuit: &BinaryCircuit,
+        inputs: &[Modulus],
+    ) -> Result<(Vec<WireMod2>, WireMod2), TwopacError> {
+        let seed = self.rng.gen::<Block>();
+        let mut gb = Gb::<_, AesRng, WireMod2>::new(self.channel.clone(), AesRng::from_seed(seed));
+        let (own, labels) = gb.encode_many_wires(inputs, &vec![2; inputs.len()])?;
+        for label in &labels {
+            self.channel.write_block(&label.as_block())?;
+        }
+        let n = peer_inputs(circuit, self.role);
+        let (peer, _) = gb.encode_many_wires(&vec![0; n], &vec![2; n])?;
+        let delta = gb.delta(2);
+        let pairs = peer
+            .iter()
+            .map(|zero| (zero.as_block(), zero.plus(&delta).as_block()))
+            .collect::<Vec<_>>();
+        let mut ot = OTS::init(&mut self.channel, &mut self.rng)?;
+        ot.send(&mut self.channel, &pairs, &mut self.rng)?;
+
+        let (garbler_inputs, evaluator_inputs) = match self.role {
+            Role::Garbler => (own, peer),
+            Role::Evaluator => (peer, own),
+        };
+        let zeros = circuit.eval_output_wires(&mut gb, &garbler_inputs, &evaluator_inputs)?;
+        for zero in &zeros {
+            gb.output(zero)?;
+        }
+        self.channel.flush()?;
+        Ok((zeros, delta))
+    }
+
+    /// Evaluate the peer's garbling of `circuit`, returning the labels of its
+    /// outputs and their values, or `None` for those that do not decode.
+    fn evaluate(
+        &mut self,
+        circuit: &BinaryCircuit,
+        inputs: &[Modulus],
+    ) -> Result<(Vec<WireMod2>, Vec<Option<Modulus>>), TwopacError> {
+        let peer = (0..peer_inputs(circuit, self.role))
+            .map(|_| Ok(WireMod2::from_block(self.channel.read_block()?, 2)))
+            .collect::<Result<Vec<_>, TwopacError>>()?;
+        let choices = inputs.iter().map(|&x| x == 1).collect::<Vec<_>>();
+        let mut ot = OTR::init(&mut self.channel, &mut self.rng)?;
+        let own = ot
+            .receive(&mut self.channel, &choices, &mut self.rng)?
+            .into_iter()
+            .map(|label| WireMod2::from_block(label, 2))
+            .collect::<Vec<_>>();
+
+        let (garbler_inputs, evaluator_inputs) = match self.role {
+            Role::Garbler => (own, peer),
+            Role::Evaluator => (peer, own),
+        };
+        let mut ev = Ev::<_, WireMod2>::new(self.channel.clone());
+        let labels = circuit.eval_output_wires(&mut ev, &garbler_inputs, &evaluator_inputs)?;
+        // An output that does not decode fails the equality check rather than
+        // the execution, so that the peer learns no more from it.
+        let outputs = labels
+            .iter()
+            .map(|label| match ev.output(label) {
+                Ok(output) => Ok(output),
+                Err(EvaluatorError::DecodingFailed) => Ok(None),
+                Err(e) => Err(e),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((labels, outputs))
+    }
+
+    /// Check that the peer's hash of the output labels is `hash`, the party
+    /// holding the garbler inputs committing to its hash before it learns the
+    /// other's.
+    fn check_equal(&mut self, hash: [u8; 32]) -> Result<bool, TwopacError> {
+        match self.role {
+            Role::Garbler => {
+                let seed = self.rng.gen::<[u8; 32]>();
+                let mut commitment = ShaCommitment::new(seed);
+                commitment.input(&hash);
+                self.channel.write_bytes(&commitment.finish())?;
+                self.channel.flush()?;
+                let theirs = read_array::<_, 32>(&mut self.channel)?;
+                self.channel.write_bytes(&hash)?;
+                self.channel.write_bytes(&seed)?;
+                self.channel.flush()?;
+                Ok(theirs == hash)
+            }
+            Role::Evaluator => {
+                let commitment = read_array::<_, 32>(&mut self.channel)?;
+                self.channel.write_bytes(&hash)?;
+                self.channel.flush()?;
+                let theirs = read_array::<_, 32>(&mut self.channel)?;
+                let seed = read_array(&mut self.channel)?;
+                let mut opened = ShaCommitment::new(seed);
+                opened.input(&theirs);
+                Ok(ShaCommitment::check(&opened.finish(), &commitment) && theirs == hash)
+            }
+        }
+    }
+}
+
+/// The number of inputs of `circuit` that the peer of a party of `role`
+/// holds.
+fn peer_inputs(circuit: &BinaryCircuit, role: Role) -> usize {
+    match role {
+        Role::Garbler => circuit.num_evaluator_inputs(),
+        Role::Evaluator => circuit.num_garbler_inputs(),
+    }
+}
+
+/// Check that `inputs` are the bits of the inputs of `circuit` that a party
+/// of `role` holds.
+fn check_own_inputs(
+    circuit: &BinaryCircuit,
+    role: Role,
+    inputs: &[Modulus],
+) -> Result<(), FancyError> {
+    let got = inputs.len();
+    let error = match role {
+        Role::Garbler => {
+            let needed = circuit.num_garbler_inputs();
+            match got.cmp(&needed) {
+                Ordering::Less => Some(FancyError::NotEnoughGarblerInputs { got, needed }),
+                Ordering::Greater => Some(FancyError::TooManyGarblerInputs { got, needed }),
+                Ordering::Equal => None,
+            }
+        }
+        Role::Evaluator => {
+            let needed = circuit.num_evaluator_inputs();
+            match got.cmp(&needed) {
+                Ordering::Less => Some(FancyError::NotEnoughEvaluatorInputs { got, needed }),
+                Ordering::Greater => Some(FancyError::TooManyEvaluatorInputs { got, needed }),
+                Ordering::Equal => None,
+            }
+        }
+    };
+    match error {
+        Some(e) => Err(e),
+        None => check_inputs(inputs, std::iter::repeat(2)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit::CircuitBuilder, FancyBinary};
+    use itertools::Itertools;
+    use ocelot::ot::{KosReceiver, KosSender};
+    use scuttlebutt::{unix_channel_pair, UnixChannel};
+    use std::sync::Arc;
+
+    type P = Party<UnixChannel, AesRng, KosSender, KosReceiver>;
+
+    fn aes() -> BinaryCircuit {
+        include_str!("../../circuits/AES-non-expanded.txt")
+            .parse()
+            .unwrap()
+    }
+
+    /// `circuit` with its first output negated.
+    fn negate_first_output(circuit: &BinaryCircuit) -> BinaryCircuit {
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.garbler_inputs(&vec![2; circuit.num_garbler_inputs()]);
+        let ys = b.evaluator_inputs(&vec![2; circuit.num_evaluator_inputs()]);
+        let mut zs = circuit.eval_output_wires(&mut b, &xs, &ys).unwrap();
+        zs[0] = b.negate(&zs[0]).unwrap();
+        b.outputs(&zs).unwrap();
+        b.finish()
+    }
+
+    /// Run a dual execution of `circuit` on `xs` and `ys`, the party of
+    /// `cheater`, if any, garbling `garbled` instead, and return the results
+    /// of the party holding the garbler inputs and of the other.
+    #[allow(clippy::type_complexity)]
+    fn run(
+        circuit: BinaryCircuit,
+        garbled: BinaryCircuit,
+        cheater: Option<Role>,
+        xs: Vec<Modulus>,
+        ys: Vec<Modulus>,
+    ) -> (
+        Result<Vec<Modulus>, TwopacError>,
+        Result<Vec<Modulus>, TwopacError>,
+    ) {
+        let (circuit, garbled) = (Arc::new(circuit), Arc::new(garbled));
+        let garbling = |role| {
+            if cheater == Some(role) {
+                garbled.clone()
+            } else {
+                circuit.clone()
+            }
+        };
+        let (gb_circuit, ev_circuit) = (garbling(Role::Garbler), garbling(Role::Evaluator));
+        let circuit_ = circuit.clone();
+        let (a, b) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            P::new(b, AesRng::new(), Role::Evaluator).run(&ev_circuit, &circuit_, &ys)
+        });
+        let result = P::new(a, AesRng::new(), Role::Garbler).run(&gb_circuit, &circuit, &xs);
+        (result, handle.join().unwrap())
+    }
+
+    fn random_bits(n: usize) -> Vec<Modulus> {
+        let mut rng = AesRng::new();
+        (0..n).map(|_| rng.gen::<bool>() as Modulus).collect_vec()
+    }
+
+    #[test]
+    fn test_aes() {
+        let circuit = aes();
+        let (xs, ys) = (random_bits(128), random_bits(128));
+        let expected = crate::circuit::eval_plain(&circuit, &xs, &ys).unwrap();
+        let (a, b) = run(circuit.clone(), circuit, None, xs, ys);
+        assert_eq!(a.unwrap(), expected);
+        assert_eq!(b.unwrap(), expected);
+    }
+
+    #[test]
+    fn test_tampered_garbling() {
+        let circuit = aes();
+        let tampered = negate_first_output(&circuit);
+        for cheater in [Role::Garbler, Role::Evaluator] {
+            let (xs, ys) = (random_bits(128), random_bits(128));
+            let (a, b) = run(circuit.clone(), tampered.clone(), Some(cheater), xs, ys);
+            for result in [a, b] {
+                assert!(
+                    matches!(result, Err(TwopacError::EqualityCheckFailed)),
+                    "{:?}",
+                    result
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_circuit_mismatch() {
+        let circuit = aes();
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.garbler_inputs(&[2; 128]);
+        let ys = b.evaluator_inputs(&[2; 128]);
+        let zs = xs
+            .iter()
+            .zip(&ys)
+            .map(|(x, y)| b.xor(x, y).unwrap())
+            .collect_vec();
+        b.outputs(&zs).unwrap();
+        let other = b.finish();
+
+        let (sender, receiver) = unix_channel_pair();
+        let ys = random_bits(128);
+        let handle = std::thread::spawn(move || {
+            P::new(receiver, AesRng::new(), Role::Evaluator).execute(&other, &ys)
+        });
+        let result =
+            P::new(sender, AesRng::new(), Role::Garbler).execute(&circuit, &random_bits(128));
+        assert!(matches!(result, Err(TwopacError::CircuitMismatch { .. })));
+        assert!(matches!(
+            handle.join().unwrap(),
+            Err(TwopacError::CircuitMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_inputs() {
+        let mut p = P::new(unix_channel_pair().0, AesRng::new(), Role::Evaluator);
+        assert!(matches!(
+            p.execute(&aes(), &[0; 127]),
+            Err(TwopacError::FancyError(
+                FancyError::NotEnoughEvaluatorInputs { .. }
+            ))
+        ));
+        let mut inputs = vec![0; 128];
+        inputs[5] = 2;
+        assert!(matches!(
+            p.execute(&aes(), &inputs),
+            Err(TwopacError::FancyError(_))
+        ));
+    }
+}
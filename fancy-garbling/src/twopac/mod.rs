@@ -1,3 +1,11 @@
 //! Implementations of two-party secure computation.
 
+pub mod dualex;
 pub mod semihonest;
+#[cfg(feature = "stdlib")]
+pub mod streaming_hash;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod testing;
+pub mod zk;
+
+pub use semihonest::{estimate, CostEstimate};
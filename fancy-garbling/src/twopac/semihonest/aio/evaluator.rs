@@ -0,0 +1,128 @@
+use super::{
+    receive_frame, replay, AsyncOtReceiver, Config, EvaluatorCore, FrameChannel, QueuedReceiver,
+};
+use crate::{
+    circuit::EvaluableCircuit, errors::TwopacError, twopac::semihonest::CountingChannel,
+    wire::WireLabel, FancyInput, Modulus,
+};
+use ocelot::ot::Receiver as OtReceiver;
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, AsyncAbstractChannel, Block, SemiHonest};
+use std::time::Instant;
+
+/// Semi-honest evaluator over an asynchronous channel.
+///
+/// Each call must be matched by the call of the same name of a
+/// [`Garbler`](super::Garbler) over the other end of the channel, as for a
+/// blocking [`Evaluator`](crate::twopac::semihonest::Evaluator). The OT `OT`
+/// is run by an [`AsyncOtReceiver`], and must thus be cloned.
+pub struct Evaluator<C, RNG, OT, Wire> {
+    core: EvaluatorCore<RNG, Wire>,
+    frames: FrameChannel,
+    channel: C,
+    ot: Option<AsyncOtReceiver<OT>>,
+    rng: RNG,
+    statistical_bits: usize,
+}
+
+impl<
+        C: AsyncAbstractChannel,
+        RNG: CryptoRng + Rng + SeedableRng<Seed = Block>,
+        OT: OtReceiver<Msg = Block> + SemiHonest + Clone,
+        Wire: WireLabel,
+    > Evaluator<C, RNG, OT, Wire>
+{
+    /// Make a new `Evaluator`, exchanging protocol versions with the garbler
+    /// as [`Evaluator::new`](crate::twopac::semihonest::Evaluator::new) does.
+    pub async fn new(mut channel: C, mut rng: RNG) -> Result<Self, TwopacError> {
+        let started = Instant::now();
+        let config = Config::default();
+        config.check_labels::<Wire>()?;
+        replay(&mut channel, super::super::handshake).await?;
+        let frames = FrameChannel::default();
+        let statistical_bits = config.security.statistical_bits();
+        let core = EvaluatorCore::start(
+            CountingChannel::new(frames.clone(), config.stats),
+            RNG::from_seed(rng.gen()),
+            config,
+            None,
+            started,
+        );
+        Ok(Evaluator {
+            core,
+            frames,
+            channel,
+            ot: None,
+            rng,
+            statistical_bits,
+        })
+    }
+
+    /// Get a reference to the internal channel.
+    pub fn get_channel(&mut self) -> &mut C {
+        &mut self.channel
+    }
+
+    /// Receive the evaluator's labels of the garbler's inputs, of the given
+    /// `moduli`.
+    pub async fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<Wire>, TwopacError> {
+        let frame = receive_frame(&mut self.channel).await?;
+        self.frames.run(frame, || self.core.receive_many(moduli))
+    }
+
+    /// Encode the evaluator's inputs `values`, of the given `moduli`, with
+    /// oblivious transfer.
+    ///
+    /// The core's encoding is replayed once for each batch of its choices
+    /// the OT receives, which costs no more than decomposing the inputs into
+    /// bits again.
+    pub async fn encode_many(
+        &mut self,
+        values: &[Modulus],
+        moduli: &[Modulus],
+    ) -> Result<Vec<Wire>, TwopacError> {
+        loop {
+            let result = self.core.encode_many(values, moduli);
+            let pending = self.core.ot_mut().and_then(QueuedReceiver::take_pending);
+            let Some(choices) = pending else {
+                if let Some(ot) = self.core.ot_mut() {
+                    ot.clear();
+                }
+                return result;
+            };
+            drop(result);
+            let blocks = self.receive_ots(&choices).await?;
+            if let Some(ot) = self.core.ot_mut() {
+                ot.push(choices, blocks);
+            }
+        }
+    }
+
+    /// Evaluate `circuit` on the given input wires, as garbled and sent by
+    /// the garbler, returning its outputs.
+    pub async fn eval_circuit<Circuit: EvaluableCircuit<EvaluatorCore<RNG, Wire>>>(
+        &mut self,
+        circuit: &Circuit,
+        garbler_inputs: &[Wire],
+        evaluator_inputs: &[Wire],
+    ) -> Result<Vec<Modulus>, TwopacError> {
+        let frame = receive_frame(&mut self.channel).await?;
+        self.frames.run(frame, || {
+            self.core
+                .eval_circuit(circuit, garbler_inputs, evaluator_inputs)
+        })
+    }
+
+    /// Run the OT receiver on `choices`, initializing OT first if necessary.
+    async fn receive_ots(&mut self, choices: &[bool]) -> Result<Vec<Block>, TwopacError> {
+        let ot = match self.ot {
+            Some(ref mut ot) => ot,
+            None => {
+                let mut ot = AsyncOtReceiver::init(&mut self.channel, &mut self.rng).await?;
+                ot.set_statistical_security(self.statistical_bits);
+                self.ot.insert(ot)
+            }
+        };
+        ot.receive(&mut self.channel, choices, &mut self.rng).await
+    }
+}
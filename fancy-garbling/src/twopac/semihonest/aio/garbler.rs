@@ -0,0 +1,126 @@
+use super::{replay, send_frame, AsyncOtSender, Config, DeferredSender, FrameChannel, GarblerCore};
+use crate::{
+    circuit::EvaluableCircuit, errors::TwopacError, twopac::semihonest::CountingChannel,
+    wire::WireLabel, FancyInput, Modulus,
+};
+use ocelot::ot::Sender as OtSender;
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, AsyncAbstractChannel, Block, SemiHonest};
+use std::time::Instant;
+
+/// Semi-honest garbler over an asynchronous channel.
+///
+/// Each call must be matched by the call of the same name of an
+/// [`Evaluator`](super::Evaluator) over the other end of the channel, as for
+/// a blocking [`Garbler`](crate::twopac::semihonest::Garbler). The OT `OT` is
+/// run by an [`AsyncOtSender`], and must thus be cloned.
+pub struct Garbler<C, RNG, OT, Wire: WireLabel> {
+    core: GarblerCore<RNG, Wire>,
+    frames: FrameChannel,
+    channel: C,
+    ot: Option<AsyncOtSender<OT>>,
+    rng: RNG,
+    statistical_bits: usize,
+}
+
+impl<
+        C: AsyncAbstractChannel,
+        RNG: CryptoRng + Rng + SeedableRng<Seed = Block>,
+        OT: OtSender<Msg = Block> + SemiHonest + Clone,
+        Wire: WireLabel,
+    > Garbler<C, RNG, OT, Wire>
+{
+    /// Make a new `Garbler`, exchanging protocol versions with the evaluator
+    /// as [`Garbler::new`](crate::twopac::semihonest::Garbler::new) does.
+    pub async fn new(mut channel: C, mut rng: RNG) -> Result<Self, TwopacError> {
+        let started = Instant::now();
+        let config = Config::default();
+        config.check_labels::<Wire>()?;
+        replay(&mut channel, super::super::handshake).await?;
+        let frames = FrameChannel::default();
+        let statistical_bits = config.security.statistical_bits();
+        let core = GarblerCore::start(
+            CountingChannel::new(frames.clone(), config.stats),
+            RNG::from_seed(rng.gen()),
+            config,
+            None,
+            started,
+        );
+        Ok(Garbler {
+            core,
+            frames,
+            channel,
+            ot: None,
+            rng,
+            statistical_bits,
+        })
+    }
+
+    /// Get a reference to the internal channel.
+    pub fn get_channel(&mut self) -> &mut C {
+        &mut self.channel
+    }
+
+    /// Encode the garbler's inputs `values`, of the given `moduli`, and send
+    /// the evaluator its labels of them.
+    pub async fn encode_many(
+        &mut self,
+        values: &[Modulus],
+        moduli: &[Modulus],
+    ) -> Result<Vec<Wire>, TwopacError> {
+        let result = self.core.encode_many(values, moduli);
+        self.send(result).await
+    }
+
+    /// Encode the evaluator's inputs, of the given `moduli`, with oblivious
+    /// transfer.
+    pub async fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<Wire>, TwopacError> {
+        let wires = self.core.receive_many(moduli)?;
+        let batches = self
+            .core
+            .ot_mut()
+            .map(DeferredSender::take_batches)
+            .unwrap_or_default();
+        for batch in batches {
+            self.send_ots(&batch).await?;
+        }
+        Ok(wires)
+    }
+
+    /// Garble `circuit` on the given input wires, and send it to the
+    /// evaluator, which learns the outputs.
+    pub async fn eval_circuit<Circuit: EvaluableCircuit<GarblerCore<RNG, Wire>>>(
+        &mut self,
+        circuit: &Circuit,
+        garbler_inputs: &[Wire],
+        evaluator_inputs: &[Wire],
+    ) -> Result<(), TwopacError> {
+        let result = self
+            .core
+            .eval_circuit(circuit, garbler_inputs, evaluator_inputs)
+            .map(|_| ());
+        self.send(result).await
+    }
+
+    /// Send what the core wrote in a call as a frame, if the call returned
+    /// `result` successfully.
+    async fn send<T>(&mut self, result: Result<T, TwopacError>) -> Result<T, TwopacError> {
+        let written = self.frames.take_written();
+        let value = result?;
+        send_frame(&mut self.channel, &written).await?;
+        Ok(value)
+    }
+
+    /// Run the OT sender on `inputs`, initializing OT first if necessary.
+    async fn send_ots(&mut self, inputs: &[(Block, Block)]) -> Result<(), TwopacError> {
+        let ot = match self.ot {
+            Some(ref mut ot) => ot,
+            None => {
+                let mut ot = AsyncOtSender::init(&mut self.channel, &mut self.rng).await?;
+                ot.set_statistical_security(self.statistical_bits);
+                self.ot.insert(ot)
+            }
+        };
+        ot.send(&mut self.channel, inputs, &mut self.rng).await
+    }
+}
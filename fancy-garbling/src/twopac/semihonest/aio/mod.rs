@@ -0,0 +1,404 @@
+//! Semi-honest two-party computation over asynchronous channels.
+//!
+//! [`Garbler`] and [`Evaluator`] compute as their blocking counterparts in
+//! [`semihonest`](super) do, over an [`AsyncAbstractChannel`] such as a tokio
+//! `TcpStream`. A party holds no thread while it waits for its peer, so that
+//! a runtime can serve many sessions on few threads:
+//!
+//! ```ignore
+//! use fancy_garbling::{twopac::semihonest::aio, WireMod2};
+//! use ocelot::ot::{AlszReceiver, AlszSender};
+//! use scuttlebutt::AesRng;
+//!
+//! let (a, b) = tokio::io::duplex(1 << 16);
+//! let garbler = tokio::spawn(async move {
+//!     let mut gb = aio::Garbler::<_, _, AlszSender, WireMod2>::new(a, AesRng::new()).await?;
+//!     let xs = gb.encode_many(&key, &[2; 128]).await?;
+//!     let ys = gb.receive_many(&[2; 128]).await?;
+//!     gb.eval_circuit(&circ, &xs, &ys).await
+//! });
+//! let mut ev = aio::Evaluator::<_, _, AlszReceiver, WireMod2>::new(b, AesRng::new()).await?;
+//! let xs = ev.receive_many(&[2; 128]).await?;
+//! let ys = ev.encode_many(&block, &[2; 128]).await?;
+//! let outputs = ev.eval_circuit(&circ, &xs, &ys).await?;
+//! ```
+//!
+//! Each party runs its calls with a blocking garbler or evaluator, its
+//! [`GarblerCore`] or [`EvaluatorCore`], over an in-memory [`FrameChannel`],
+//! on the task of the call. What the core of one party writes in a call is
+//! sent whole, as a frame: its length as a little-endian `u64`, and the
+//! bytes. The peer receives the frame before its core runs the matching call
+//! over it, and fails with `ErrorKind::InvalidData` unless the call reads the
+//! whole frame and no more, so every call of one party must be matched by one
+//! call of the other, with the same moduli. A message is thus held whole in
+//! memory, the garbled gates of a circuit included, and the core computes
+//! without yielding to the runtime in between.
+//!
+//! Oblivious transfer goes back and forth within a call, which a frame cannot
+//! hold. [`AsyncOtSender`] and [`AsyncOtReceiver`] run it by replaying each
+//! call of a blocking OT, from a copy of its state, over the frames its peer
+//! has sent during the call until it needs no more; the cores only hand them
+//! the transfers to run. Exchanging protocol versions is replayed in the same
+//! way. The frames make the messages differ from those of
+//! [`PROTOCOL_VERSION`](super::PROTOCOL_VERSION), so an asynchronous party
+//! only computes with another one. Only the default configuration is
+//! supported.
+//!
+//! This module needs the `tokio` feature.
+
+mod evaluator;
+mod garbler;
+mod ot;
+
+pub use evaluator::Evaluator;
+pub use garbler::Garbler;
+pub use ot::{AsyncOtReceiver, AsyncOtSender, DeferredSender, QueuedReceiver};
+
+use super::config::Config;
+use crate::errors::TwopacError;
+use scuttlebutt::{AbstractChannel, AsyncAbstractChannel};
+use std::{
+    io::{self, Error, ErrorKind},
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+/// The blocking garbler a [`Garbler`] runs its calls with.
+pub type GarblerCore<RNG, Wire> = super::Garbler<FrameChannel, RNG, DeferredSender, Wire>;
+
+/// The blocking evaluator an [`Evaluator`] runs its calls with.
+pub type EvaluatorCore<RNG, Wire> = super::Evaluator<FrameChannel, RNG, QueuedReceiver, Wire>;
+
+/// The most bytes of a frame read at once, so that the memory held for a
+/// frame grows with the bytes received rather than the length announced.
+const FRAME_CHUNK: usize = 1 << 16;
+
+/// The in-memory channel the blocking parts of a party run over.
+///
+/// Reads come from the frames received from the peer, and fail with
+/// `ErrorKind::UnexpectedEof` past their end; writes are kept until the party
+/// sends them as a frame.
+#[derive(Debug, Default)]
+pub struct FrameChannel(Arc<Mutex<Frames>>);
+
+/// The buffers of a [`FrameChannel`].
+#[derive(Debug, Default)]
+struct Frames {
+    /// The frames received from the peer.
+    received: Vec<u8>,
+    /// How much of them has been read.
+    read: usize,
+    /// What has been written since it was last sent.
+    written: Vec<u8>,
+    /// Whether a read went past the end of what was received.
+    starved: bool,
+}
+
+impl FrameChannel {
+    fn new(received: Vec<u8>) -> Self {
+        FrameChannel(Arc::new(Mutex::new(Frames {
+            received,
+            ..Frames::default()
+        })))
+    }
+
+    fn frames(&self) -> MutexGuard<'_, Frames> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Take what has been written since it was last taken.
+    fn take_written(&self) -> Vec<u8> {
+        std::mem::take(&mut self.frames().written)
+    }
+
+    /// Whether a read went past the end of what was received.
+    fn is_starved(&self) -> bool {
+        self.frames().starved
+    }
+
+    /// Run `step` over `frame`, the message of the peer's matching call, as
+    /// [`FrameChannel::check_read`] does.
+    fn run<T>(
+        &self,
+        frame: Vec<u8>,
+        step: impl FnOnce() -> Result<T, TwopacError>,
+    ) -> Result<T, TwopacError> {
+        *self.frames() = Frames {
+            received: frame,
+            ..Frames::default()
+        };
+        let result = step();
+        self.check_read(result)
+    }
+
+    /// Return `result`, of a run over the channel, if the run read all it
+    /// received and no more.
+    fn check_read<T, E>(&self, result: Result<T, E>) -> Result<T, TwopacError>
+    where
+        TwopacError: From<E>,
+    {
+        let frames = self.frames();
+        if frames.starved {
+            return Err(mismatch("the peer's message ended early"));
+        }
+        let value = result?;
+        if frames.read < frames.received.len() {
+            return Err(mismatch("the peer's message went on past its end"));
+        }
+        Ok(value)
+    }
+}
+
+impl AbstractChannel for FrameChannel {
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> io::Result<()> {
+        let mut frames = self.frames();
+        let start = frames.read;
+        let Some(unread) = frames.received.get(start..start + bytes.len()) else {
+            frames.starved = true;
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "read past the frames received",
+            ));
+        };
+        bytes.copy_from_slice(unread);
+        frames.read += bytes.len();
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.frames().written.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn clone(&self) -> Self {
+        FrameChannel(Arc::clone(&self.0))
+    }
+}
+
+/// The error of a peer whose messages do not match the calls of the party.
+fn mismatch(what: &str) -> TwopacError {
+    TwopacError::IoError(Error::new(
+        ErrorKind::InvalidData,
+        format!("{}; do the calls of the parties match?", what),
+    ))
+}
+
+/// Send `bytes` as a frame over `channel`.
+async fn send_frame<C: AsyncAbstractChannel>(channel: &mut C, bytes: &[u8]) -> io::Result<()> {
+    // One write, so that the length and the bytes share a packet.
+    let mut frame = Vec::with_capacity(8 + bytes.len());
+    frame.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+    frame.extend_from_slice(bytes);
+    channel.write_bytes(&frame).await?;
+    channel.flush().await
+}
+
+/// Receive a frame sent with [`send_frame`] over `channel`.
+async fn receive_frame<C: AsyncAbstractChannel>(channel: &mut C) -> io::Result<Vec<u8>> {
+    let mut len = [0; 8];
+    channel.read_bytes(&mut len).await?;
+    let len = usize::try_from(u64::from_le_bytes(len))
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "frame too long"))?;
+    let mut frame = Vec::new();
+    while frame.len() < len {
+        let start = frame.len();
+        frame.resize(len.min(start + FRAME_CHUNK), 0);
+        channel.read_bytes(&mut frame[start..]).await?;
+    }
+    Ok(frame)
+}
+
+/// Run `attempt`, a party's side of a blocking exchange with its peer, over
+/// `channel`, returning what it returns.
+///
+/// Each attempt runs over a [`FrameChannel`] holding the frames the peer has
+/// sent during the exchange. Once an attempt needs more, what it wrote past
+/// what the last one did is sent as a frame, and the next attempt waits for
+/// the peer's next frame. The attempts must write the same bytes given the
+/// same frames, failing with `ErrorKind::InvalidData` otherwise, and read all
+/// the frames at the end.
+async fn replay<C, T, E>(
+    channel: &mut C,
+    mut attempt: impl FnMut(&mut FrameChannel) -> Result<T, E>,
+) -> Result<T, TwopacError>
+where
+    C: AsyncAbstractChannel,
+    TwopacError: From<E>,
+{
+    let mut received = Vec::new();
+    let mut sent = Vec::new();
+    loop {
+        let mut frames = FrameChannel::new(received.clone());
+        let result = attempt(&mut frames);
+        let written = frames.take_written();
+        if !written.starts_with(&sent) {
+            return Err(TwopacError::IoError(Error::new(
+                ErrorKind::InvalidData,
+                "an exchange wrote different bytes when replayed; is its randomness seeded?",
+            )));
+        }
+        if written.len() > sent.len() {
+            send_frame(channel, &written[sent.len()..]).await?;
+            sent = written;
+        }
+        if result.is_ok() || !frames.is_starved() {
+            return frames.check_read(result);
+        }
+        drop(result);
+        received.extend(receive_frame(channel).await?);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{eval_plain, BinaryCircuit, CircuitBuilder, CircuitType},
+        Fancy, FancyBinary, Modulus, WireMod2,
+    };
+    use itertools::Itertools;
+    use ocelot::ot::{AlszReceiver, AlszSender, KosReceiver, KosSender};
+    use rand::Rng;
+    use scuttlebutt::AesRng;
+
+    fn aes() -> Arc<BinaryCircuit> {
+        Arc::new(
+            BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+                "../../../../circuits/AES-non-expanded.txt"
+            )))
+            .unwrap(),
+        )
+    }
+
+    fn random_bits(n: usize) -> Vec<Modulus> {
+        let mut rng = AesRng::new();
+        (0..n).map(|_| rng.gen_bool(0.5) as Modulus).collect()
+    }
+
+    /// Run `circ` between a garbler task on `a` and an evaluator on `b`, with
+    /// the OTs `S` and `R`, and check the evaluator's outputs.
+    async fn run_circuit<C, S, R>(circ: Arc<BinaryCircuit>, a: C, b: C)
+    where
+        C: AsyncAbstractChannel + 'static,
+        S: ocelot::ot::Sender<Msg = scuttlebutt::Block>
+            + scuttlebutt::SemiHonest
+            + Clone
+            + Send
+            + 'static,
+        R: ocelot::ot::Receiver<Msg = scuttlebutt::Block> + scuttlebutt::SemiHonest + Clone + Send,
+    {
+        let (ngb, nev) = (circ.num_garbler_inputs(), circ.num_evaluator_inputs());
+        let (gb_inputs, ev_inputs) = (random_bits(ngb), random_bits(nev));
+        let garbler = tokio::spawn({
+            let (circ, gb_inputs) = (Arc::clone(&circ), gb_inputs.clone());
+            async move {
+                let mut gb = Garbler::<_, _, S, WireMod2>::new(a, AesRng::new()).await?;
+                let xs = gb.encode_many(&gb_inputs, &vec![2; ngb]).await?;
+                let ys = gb.receive_many(&vec![2; nev]).await?;
+                gb.eval_circuit(circ.as_ref(), &xs, &ys).await
+            }
+        });
+        let mut ev = Evaluator::<_, _, R, WireMod2>::new(b, AesRng::new())
+            .await
+            .unwrap();
+        let xs = ev.receive_many(&vec![2; ngb]).await.unwrap();
+        let ys = ev.encode_many(&ev_inputs, &vec![2; nev]).await.unwrap();
+        let outputs = ev.eval_circuit(circ.as_ref(), &xs, &ys).await.unwrap();
+        garbler.await.unwrap().unwrap();
+        assert_eq!(
+            outputs,
+            eval_plain(circ.as_ref(), &gb_inputs, &ev_inputs).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_aes_duplex() {
+        let (a, b) = tokio::io::duplex(1 << 16);
+        run_circuit::<_, AlszSender, AlszReceiver>(aes(), a, b).await;
+        let (a, b) = tokio::io::duplex(1 << 16);
+        run_circuit::<_, KosSender, KosReceiver>(aes(), a, b).await;
+    }
+
+    #[tokio::test]
+    async fn test_aes_tcp() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (a, b) = tokio::join!(tokio::net::TcpStream::connect(addr), listener.accept());
+        run_circuit::<_, AlszSender, AlszReceiver>(aes(), a.unwrap(), b.unwrap().0).await;
+    }
+
+    /// An AND of each of 8 garbler inputs with the evaluator input of the
+    /// same index, or with the next garbler input past the `nev` of them.
+    fn and_circuit(nev: usize) -> Arc<BinaryCircuit> {
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.garbler_inputs(&[2; 8]);
+        let ys = b.evaluator_inputs(&vec![2; nev]);
+        for (i, x) in xs.iter().enumerate() {
+            let y = ys.get(i).unwrap_or(&xs[(i + 1) % xs.len()]);
+            let z = b.and(x, y).unwrap();
+            b.output(&z).unwrap();
+        }
+        Arc::new(b.finish())
+    }
+
+    #[tokio::test]
+    async fn test_many_sessions() {
+        // All of them on the one thread of the test's runtime, with small
+        // buffers, so that the sessions wait on each other's channels. This
+        // is more sessions than the blocking pool of a runtime has threads.
+        // Every session waits on its peer in the handshake and each call;
+        // only some run oblivious transfer too, as its base OT is slow in
+        // debug builds.
+        let (plain, with_ot) = (and_circuit(0), and_circuit(8));
+        let sessions = (0..1024)
+            .map(|i| {
+                let circ = if i % 32 == 0 { &with_ot } else { &plain };
+                let (a, b) = tokio::io::duplex(1 << 12);
+                tokio::spawn(run_circuit::<_, AlszSender, AlszReceiver>(
+                    Arc::clone(circ),
+                    a,
+                    b,
+                ))
+            })
+            .collect_vec();
+        for session in sessions {
+            session.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mismatched_calls() {
+        // The garbler encodes its inputs in two calls, the evaluator receives
+        // them in one.
+        let (a, b) = tokio::io::duplex(1 << 16);
+        let garbler = tokio::spawn(async move {
+            let mut gb = Garbler::<_, _, AlszSender, WireMod2>::new(a, AesRng::new()).await?;
+            gb.encode_many(&[0, 1], &[2, 2]).await?;
+            gb.encode_many(&[1], &[2]).await
+        });
+        let mut ev = Evaluator::<_, _, AlszReceiver, WireMod2>::new(b, AesRng::new())
+            .await
+            .unwrap();
+        let err = ev.receive_many(&[2, 2, 2]).await.unwrap_err();
+        assert!(
+            matches!(&err, TwopacError::IoError(e) if e.kind() == ErrorKind::InvalidData),
+            "{}",
+            err
+        );
+        garbler.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_closed_channel() {
+        let (a, b) = tokio::io::duplex(1 << 16);
+        drop(b);
+        let err = Garbler::<_, _, AlszSender, WireMod2>::new(a, AesRng::new())
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, TwopacError::IoError(_)), "{}", err);
+    }
+}
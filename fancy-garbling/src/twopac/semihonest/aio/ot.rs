@@ -0,0 +1,213 @@
+//! Oblivious transfer for the asynchronous parties.
+
+use super::replay;
+use crate::errors::TwopacError;
+use ocelot::{
+    ot::{Receiver as OtReceiver, Sender as OtSender},
+    Error,
+};
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, AesRng, AsyncAbstractChannel, Block, SemiHonest};
+
+/// An OT sender over an [`AsyncAbstractChannel`].
+///
+/// Each call replays a call of the blocking sender `OT` from a copy of its
+/// state, with randomness seeded once per call, until the receiver, an
+/// [`AsyncOtReceiver`], has sent all the call needs; the state is kept once
+/// the call succeeds.
+pub struct AsyncOtSender<OT> {
+    ot: OT,
+}
+
+impl<OT: OtSender<Msg = Block> + Clone> AsyncOtSender<OT> {
+    /// Run the one-time initialization of `OT`.
+    pub async fn init<C: AsyncAbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, TwopacError> {
+        let seed = rng.gen::<Block>();
+        let ot = replay(channel, |frames| {
+            OT::init(frames, &mut AesRng::from_seed(seed))
+        })
+        .await?;
+        Ok(AsyncOtSender { ot })
+    }
+
+    /// Send `inputs`, of each pair of which the receiver learns one message.
+    pub async fn send<C: AsyncAbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        inputs: &[(Block, Block)],
+        rng: &mut RNG,
+    ) -> Result<(), TwopacError> {
+        let (seed, start) = (rng.gen::<Block>(), self.ot.clone());
+        self.ot = replay(channel, move |frames| {
+            let mut ot = start.clone();
+            ot.send(frames, inputs, &mut AesRng::from_seed(seed))?;
+            Ok::<_, Error>(ot)
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Set the statistical security parameter of `OT`, in bits.
+    pub fn set_statistical_security(&mut self, bits: usize) {
+        self.ot.set_statistical_security(bits);
+    }
+}
+
+/// An OT receiver over an [`AsyncAbstractChannel`], replaying the blocking
+/// receiver `OT` as [`AsyncOtSender`] does the sender.
+pub struct AsyncOtReceiver<OT> {
+    ot: OT,
+}
+
+impl<OT: OtReceiver<Msg = Block> + Clone> AsyncOtReceiver<OT> {
+    /// Run the one-time initialization of `OT`.
+    pub async fn init<C: AsyncAbstractChannel, RNG: CryptoRng + Rng>(
+        channel: &mut C,
+        rng: &mut RNG,
+    ) -> Result<Self, TwopacError> {
+        let seed = rng.gen::<Block>();
+        let ot = replay(channel, |frames| {
+            OT::init(frames, &mut AesRng::from_seed(seed))
+        })
+        .await?;
+        Ok(AsyncOtReceiver { ot })
+    }
+
+    /// Receive one message of each pair of the sender, the second one where
+    /// `choices` is true.
+    pub async fn receive<C: AsyncAbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        channel: &mut C,
+        choices: &[bool],
+        rng: &mut RNG,
+    ) -> Result<Vec<Block>, TwopacError> {
+        let (seed, start) = (rng.gen::<Block>(), self.ot.clone());
+        let (ot, blocks) = replay(channel, move |frames| {
+            let mut ot = start.clone();
+            let blocks = ot.receive(frames, choices, &mut AesRng::from_seed(seed))?;
+            Ok::<_, Error>((ot, blocks))
+        })
+        .await?;
+        self.ot = ot;
+        Ok(blocks)
+    }
+
+    /// Set the statistical security parameter of `OT`, in bits.
+    pub fn set_statistical_security(&mut self, bits: usize) {
+        self.ot.set_statistical_security(bits);
+    }
+}
+
+/// The OT sender of a [`GarblerCore`](super::GarblerCore).
+///
+/// It sends nothing, but keeps the inputs of each call for the garbler to
+/// send with its [`AsyncOtSender`].
+#[derive(Default)]
+pub struct DeferredSender {
+    batches: Vec<Vec<(Block, Block)>>,
+}
+
+impl DeferredSender {
+    /// Take the inputs of the calls since the last time.
+    pub(super) fn take_batches(&mut self) -> Vec<Vec<(Block, Block)>> {
+        std::mem::take(&mut self.batches)
+    }
+}
+
+impl OtSender for DeferredSender {
+    type Msg = Block;
+
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        _: &mut C,
+        _: &mut RNG,
+    ) -> Result<Self, Error> {
+        Ok(Self::default())
+    }
+
+    fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        _: &mut C,
+        inputs: &[(Block, Block)],
+        _: &mut RNG,
+    ) -> Result<(), Error> {
+        self.batches.push(inputs.to_vec());
+        Ok(())
+    }
+}
+
+impl SemiHonest for DeferredSender {}
+
+/// The OT receiver of an [`EvaluatorCore`](super::EvaluatorCore).
+///
+/// It receives nothing. The evaluator replays each encoding of its inputs:
+/// the receiver returns what the evaluator's [`AsyncOtReceiver`] received for
+/// the calls it has run, and fails at the first call it has not, keeping the
+/// choices for the evaluator to receive.
+#[derive(Default)]
+pub struct QueuedReceiver {
+    /// The choices and messages of the calls run so far.
+    received: Vec<(Vec<bool>, Vec<Block>)>,
+    /// How many of them have been returned in this attempt.
+    returned: usize,
+    /// The choices of the first call that has not been run.
+    pending: Option<Vec<bool>>,
+}
+
+impl QueuedReceiver {
+    /// Take the choices of the first call that has not been run, if any, for
+    /// the next attempt to start over.
+    pub(super) fn take_pending(&mut self) -> Option<Vec<bool>> {
+        self.returned = 0;
+        self.pending.take()
+    }
+
+    /// Keep the messages received for `choices`.
+    pub(super) fn push(&mut self, choices: Vec<bool>, blocks: Vec<Block>) {
+        self.received.push((choices, blocks));
+    }
+
+    /// Forget the calls of the last encoding.
+    pub(super) fn clear(&mut self) {
+        self.received.clear();
+        self.returned = 0;
+    }
+}
+
+impl OtReceiver for QueuedReceiver {
+    type Msg = Block;
+
+    fn init<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        _: &mut C,
+        _: &mut RNG,
+    ) -> Result<Self, Error> {
+        Ok(Self::default())
+    }
+
+    fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
+        &mut self,
+        _: &mut C,
+        inputs: &[bool],
+        _: &mut RNG,
+    ) -> Result<Vec<Block>, Error> {
+        match self.received.get(self.returned) {
+            Some((choices, blocks)) if choices == inputs => {
+                self.returned += 1;
+                Ok(blocks.clone())
+            }
+            Some(_) => Err(Error::Other(
+                "the choices of an oblivious transfer changed when replayed".to_string(),
+            )),
+            None => {
+                self.pending = Some(inputs.to_vec());
+                Err(Error::Other(
+                    "waiting for the messages of an oblivious transfer".to_string(),
+                ))
+            }
+        }
+    }
+}
+
+impl SemiHonest for QueuedReceiver {}
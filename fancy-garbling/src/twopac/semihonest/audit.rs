@@ -0,0 +1,381 @@
+//! Auditing semi-honest sessions after the fact.
+//!
+//! A garbler and evaluator built with
+//! [`build_garbler_from_seed`](super::SemiHonestBuilder::build_garbler_from_seed)
+//! and
+//! [`build_evaluator_from_seed`](super::SemiHonestBuilder::build_evaluator_from_seed)
+//! toss coins for a session id when they are made, and each draws all its
+//! randomness, the garbler's deltas and wire labels as well as both parties'
+//! oblivious transfers, from an [`AesRng`] keyed by the SHA-256 hash of its
+//! party seed and the session id. Neither party alone picks the session id,
+//! yet given the garbler's party seed, everything the garbler sent can be
+//! derived anew from what it received.
+//!
+//! [`verify_transcript`] does so from a [`Recording`] of the garbler's
+//! channel, as kept by a [`RecordingChannel`]:
+//!
+//! ```ignore
+//! let channel = RecordingChannel::new(channel);
+//! let mut gb = SemiHonest::builder()
+//!     .build_garbler_from_seed::<_, WireMod2>(channel.clone(), party_seed)?;
+//! // ... garble `circuit` ...
+//! let inputs = audit::verify_transcript(&channel.recording(), party_seed, &circuit)?;
+//! ```
+
+use super::{Garbler, SemiHonest};
+use crate::{
+    circuit::{BinaryCircuit, CircuitType},
+    errors::TwopacError,
+    wire::WireLabel,
+    FancyInput, Modulus, WireMod2,
+};
+use scuttlebutt::{AbstractChannel, AesRng, Block};
+use std::{
+    cell::RefCell,
+    io,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
+
+/// The bytes a party sent and received over a [`RecordingChannel`], in the
+/// order it wrote and read them.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recording {
+    /// The bytes written to the channel.
+    pub sent: Vec<u8>,
+    /// The bytes read from the channel.
+    pub received: Vec<u8>,
+}
+
+/// A channel that keeps a copy of every byte written to and read from the
+/// channel it wraps.
+///
+/// Clones of a recording channel share their recording.
+pub struct RecordingChannel<C> {
+    channel: C,
+    recording: Arc<Mutex<Recording>>,
+}
+
+impl<C: AbstractChannel> RecordingChannel<C> {
+    /// Record what goes through `channel`.
+    pub fn new(channel: C) -> Self {
+        RecordingChannel {
+            channel,
+            recording: Arc::default(),
+        }
+    }
+
+    /// A copy of what has been recorded so far.
+    pub fn recording(&self) -> Recording {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Recording> {
+        // A recording is left whole by a panic, as it only ever grows.
+        self.recording
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl<C: AbstractChannel> AbstractChannel for RecordingChannel<C> {
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> io::Result<()> {
+        self.channel.read_bytes(bytes)?;
+        self.lock().received.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.channel.write_bytes(bytes)?;
+        self.lock().sent.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.channel.flush()
+    }
+
+    fn clone(&self) -> Self {
+        RecordingChannel {
+            channel: self.channel.clone(),
+            recording: self.recording.clone(),
+        }
+    }
+
+    fn set_timeouts(
+        &mut self,
+        read: Option<std::time::Duration>,
+        write: Option<std::time::Duration>,
+    ) -> io::Result<()> {
+        self.channel.set_timeouts(read, write)
+    }
+}
+
+/// Check that `recording`, made on the channel of a garbler built from
+/// `garbler_seed`, is the transcript of an honest session garbling
+/// `circuit`, returning the garbler's inputs.
+///
+/// The session checked is that of a garbler built with the default
+/// configuration of [`SemiHonest::builder`], which encodes its inputs with
+/// `encode_many`, receives the evaluator's with `receive_many`, and garbles
+/// the circuit with `eval_circuit`. The garbler is run again on the bytes it
+/// received, and must send every byte it sent, and read every byte it
+/// received; its inputs are recovered from the wire labels it sent them
+/// with. Fails with [`TwopacError::TranscriptMismatch`] at the first byte it
+/// departs from.
+pub fn verify_transcript(
+    recording: &Recording,
+    garbler_seed: Block,
+    circuit: &BinaryCircuit,
+) -> Result<Vec<Modulus>, TwopacError> {
+    let garbler_moduli = circuit
+        .get_garbler_input_refs()
+        .iter()
+        .map(|r| r.modulus)
+        .collect::<Vec<_>>();
+    let evaluator_moduli = circuit
+        .get_evaluator_input_refs()
+        .iter()
+        .map(|r| r.modulus)
+        .collect::<Vec<_>>();
+
+    // The labels of the garbler's inputs are the first it sends once made,
+    // each the label of zero plus a multiple of the delta.
+    let replay = Replay::new(recording);
+    let inputs = replay.check(|| {
+        let mut gb = garbler(replay.clone(), garbler_seed)?;
+        let offset = replay.position();
+        let zeros = vec![0; garbler_moduli.len()];
+        let (zeros, _) = gb.encode_many_wires(&zeros, &garbler_moduli)?;
+        let mut inputs = Vec::with_capacity(zeros.len());
+        for (i, (zero, &q)) in zeros.iter().zip(&garbler_moduli).enumerate() {
+            let delta = gb.delta(q);
+            let offset = offset + i * std::mem::size_of::<Block>();
+            let sent = recording
+                .sent
+                .get(offset..offset + std::mem::size_of::<Block>())
+                .ok_or(TwopacError::TranscriptMismatch {
+                    offset: recording.sent.len(),
+                })?;
+            let x = (0..q)
+                .find(|&x| zero.plus(&delta.cmul(x)).as_block().as_ref() == sent)
+                .ok_or(TwopacError::TranscriptMismatch { offset })?;
+            inputs.push(x);
+        }
+        Ok(inputs)
+    })?;
+
+    let replay = Replay::new(recording);
+    replay.check(|| {
+        let mut gb = garbler(replay.clone(), garbler_seed)?;
+        let xs = gb.encode_many(&inputs, &garbler_moduli)?;
+        let ys = gb.receive_many(&evaluator_moduli)?;
+        gb.eval_circuit(circuit, &xs, &ys)?;
+        gb.get_channel().flush()?;
+        Ok(())
+    })?;
+    replay.check_consumed()?;
+    Ok(inputs)
+}
+
+type AuditedGarbler = Garbler<Replay, AesRng, ocelot::ot::AlszSender, WireMod2>;
+
+fn garbler(channel: Replay, garbler_seed: Block) -> Result<AuditedGarbler, TwopacError> {
+    SemiHonest::builder().build_garbler_from_seed(channel, garbler_seed)
+}
+
+/// A channel playing back the bytes a garbler received, and checking the
+/// bytes it sends against those it sent.
+struct Replay(Rc<RefCell<ReplayState>>);
+
+struct ReplayState {
+    recording: Recording,
+    sent: usize,
+    received: usize,
+    // The offset of the first byte sent that differs from the recording.
+    mismatch: Option<usize>,
+}
+
+impl Replay {
+    fn new(recording: &Recording) -> Self {
+        Replay(Rc::new(RefCell::new(ReplayState {
+            recording: recording.clone(),
+            sent: 0,
+            received: 0,
+            mismatch: None,
+        })))
+    }
+
+    /// The number of bytes sent so far.
+    fn position(&self) -> usize {
+        self.0.borrow().sent
+    }
+
+    /// Run `f`, blaming any failure on the transcript if the garbler departed
+    /// from it.
+    fn check<T>(&self, f: impl FnOnce() -> Result<T, TwopacError>) -> Result<T, TwopacError> {
+        let result = f();
+        match self.0.borrow().mismatch {
+            Some(offset) => Err(TwopacError::TranscriptMismatch { offset }),
+            None => result,
+        }
+    }
+
+    /// Check that the garbler sent and received all of the recording.
+    fn check_consumed(&self) -> Result<(), TwopacError> {
+        let state = self.0.borrow();
+        if state.sent < state.recording.sent.len()
+            || state.received < state.recording.received.len()
+        {
+            return Err(TwopacError::TranscriptMismatch { offset: state.sent });
+        }
+        Ok(())
+    }
+}
+
+impl AbstractChannel for Replay {
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> io::Result<()> {
+        let mut state = self.0.borrow_mut();
+        let start = state.received;
+        let recorded = state
+            .recording
+            .received
+            .get(start..start + bytes.len())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "the garbler reads past the end of its transcript",
+                )
+            })?;
+        bytes.copy_from_slice(recorded);
+        state.received += bytes.len();
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let mut state = self.0.borrow_mut();
+        let start = state.sent;
+        let recorded = &state.recording.sent[start.min(state.recording.sent.len())..];
+        if let Some(i) = (0..bytes.len()).find(|&i| recorded.get(i) != Some(&bytes[i])) {
+            state.mismatch = Some(start + i);
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the garbler departs from its transcript",
+            ));
+        }
+        state.sent += bytes.len();
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn clone(&self) -> Self {
+        Replay(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{BinaryCircuit, CircuitBuilder},
+        twopac::semihonest::SemiHonest,
+        util, BinaryGadgets, Fancy,
+    };
+    use scuttlebutt::unix_channel_pair;
+
+    const N: usize = 16;
+
+    fn adder() -> BinaryCircuit {
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.bin_garbler_input(N);
+        let y = b.bin_evaluator_input(N);
+        let (z, _) = b.bin_addition(&x, &y).unwrap();
+        b.outputs(z.wires()).unwrap();
+        b.finish()
+    }
+
+    /// Run the adder on `x` and `y`, returning the garbler's recording, its
+    /// session id, and the evaluator's.
+    fn session(garbler_seed: Block, x: u128, y: u128) -> (Recording, Block, Block) {
+        let circuit = adder();
+        let (sender, receiver) = unix_channel_pair();
+        std::thread::scope(|s| {
+            let garbler = s.spawn(|| {
+                let channel = RecordingChannel::new(sender);
+                let mut gb = SemiHonest::builder()
+                    .stats(true)
+                    .build_garbler_from_seed::<_, WireMod2>(channel.clone(), garbler_seed)
+                    .unwrap();
+                let xs = gb.encode_many(&util::u128_to_bits(x, N), &[2; N]).unwrap();
+                let ys = gb.receive_many(&[2; N]).unwrap();
+                gb.eval_circuit(&circuit, &xs, &ys).unwrap();
+                gb.get_channel().flush().unwrap();
+                assert_eq!(gb.stats().unwrap().session_id, gb.session_id());
+                (channel.recording(), gb.session_id().unwrap())
+            });
+            let mut ev = SemiHonest::builder()
+                .build_evaluator_from_seed::<_, WireMod2>(receiver, Block::from(7))
+                .unwrap();
+            let xs = ev.receive_many(&[2; N]).unwrap();
+            let ys = ev.encode_many(&util::u128_to_bits(y, N), &[2; N]).unwrap();
+            let outputs = ev.eval_circuit(&circuit, &xs, &ys).unwrap();
+            assert_eq!(util::u128_from_bits(&outputs), (x + y) % (1 << N));
+            let (recording, session_id) = garbler.join().unwrap();
+            (recording, session_id, ev.session_id().unwrap())
+        })
+    }
+
+    #[test]
+    fn test_session_id() {
+        let (_, gb_id, ev_id) = session(Block::from(1), 3, 4);
+        assert_eq!(gb_id, ev_id);
+        let (_, other, _) = session(Block::from(2), 3, 4);
+        assert_ne!(gb_id, other);
+    }
+
+    #[test]
+    fn test_audit_honest() {
+        let seed = Block::from(1);
+        let (recording, _, _) = session(seed, 0xbeef, 0x1234);
+        let inputs = verify_transcript(&recording, seed, &adder()).unwrap();
+        assert_eq!(inputs, util::u128_to_bits(0xbeef, N));
+        // The garbler's randomness derives from its seed.
+        assert!(matches!(
+            verify_transcript(&recording, Block::from(2), &adder()),
+            Err(TwopacError::TranscriptMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_audit_tampered() {
+        let seed = Block::from(1);
+        let (recording, _, _) = session(seed, 0xbeef, 0x1234);
+        let n = recording.sent.len();
+
+        let mut tampered = recording.clone();
+        tampered.sent[n - 1] ^= 1;
+        match verify_transcript(&tampered, seed, &adder()) {
+            Err(TwopacError::TranscriptMismatch { offset }) => assert_eq!(offset, n - 1),
+            r => panic!("expected a transcript mismatch: {:?}", r),
+        }
+
+        // What the garbler received changes what it sends.
+        let mut tampered = recording.clone();
+        let m = tampered.received.len();
+        tampered.received[m - 1] ^= 1;
+        assert!(matches!(
+            verify_transcript(&tampered, seed, &adder()),
+            Err(TwopacError::TranscriptMismatch { .. })
+        ));
+
+        let mut tampered = recording;
+        tampered.sent.truncate(n - 16);
+        assert!(matches!(
+            verify_transcript(&tampered, seed, &adder()),
+            Err(TwopacError::TranscriptMismatch { offset }) if offset == n - 16
+        ));
+    }
+}
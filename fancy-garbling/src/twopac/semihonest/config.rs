@@ -0,0 +1,414 @@
+//! Configuration of semi-honest two-party computation.
+
+use super::{Evaluator, Garbler};
+use crate::{
+    errors::{SecurityParamsError, TwopacError},
+    hooks::Hooks,
+    wire::WireLabel,
+    SecurityParams,
+};
+use ocelot::ot::{AlszReceiver, AlszSender, Receiver as OtReceiver, Sender as OtSender};
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, AesRng, Block};
+use std::{marker::PhantomData, time::Duration};
+
+/// Which parties learn the outputs of a circuit run with `eval_circuit`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Only the evaluator learns the outputs.
+    #[default]
+    Evaluator,
+    /// The evaluator decodes the outputs and sends them to the garbler, in a
+    /// single flight once the circuit has been evaluated.
+    Both,
+    /// Only the garbler learns the outputs. It withholds what decodes them,
+    /// and the evaluator, which calls
+    /// [`Evaluator::output_labels`](super::Evaluator::output_labels) rather
+    /// than `eval_circuit`, sends it the labels of the outputs instead, which
+    /// the garbler decodes with
+    /// [`Garbler::decode_outputs`](super::Garbler::decode_outputs).
+    Garbler,
+}
+
+/// Counters kept by a party whose configuration enables
+/// [`stats`](SemiHonestBuilder::stats).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TwopacStats {
+    /// The number of oblivious transfers run, one per bit of the evaluator's
+    /// inputs and 128 bits of their labels.
+    pub ots: usize,
+    /// The number of batches of OT extension the transfers were run in.
+    pub ot_batches: usize,
+    /// The number of times the garbler flushed its channel because the flush
+    /// threshold was reached.
+    pub flushes: usize,
+    /// The session id tossed with the peer, if the party was built from a
+    /// party seed, as by [`SemiHonestBuilder::build_garbler_from_seed`].
+    pub session_id: Option<Block>,
+    /// The number of bytes the party wrote to its channel, as counted by the
+    /// [`CountingChannel`](super::CountingChannel) wrapping it.
+    pub bytes_written: usize,
+    /// The number of bytes the party read from its channel.
+    pub bytes_read: usize,
+    /// The number of gates garbled or evaluated, not counting inputs and
+    /// outputs.
+    pub gates: usize,
+    /// The time spent making the party, exchanging versions and, if it was
+    /// built from a party seed, tossing the session id.
+    pub init_time: Duration,
+    /// The time spent encoding inputs, oblivious transfer and its setup
+    /// included.
+    pub input_time: Duration,
+    /// The time spent garbling or evaluating circuits, outputs included.
+    pub eval_time: Duration,
+}
+
+impl TwopacStats {
+    /// Serialize the counters as JSON.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("stats always serialize")
+    }
+
+    /// Count `elapsed` as spent in `phase`.
+    pub(super) fn add_time(&mut self, phase: Phase, elapsed: Duration) {
+        match phase {
+            Phase::Input => self.input_time += elapsed,
+            Phase::Eval => self.eval_time += elapsed,
+        }
+    }
+}
+
+/// The phases of a computation after the party is made, whose times
+/// [`TwopacStats`] keeps.
+#[derive(Clone, Copy, Debug)]
+pub(super) enum Phase {
+    /// Encoding inputs.
+    Input,
+    /// Garbling or evaluating circuits.
+    Eval,
+}
+
+impl std::fmt::Display for TwopacStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "two-party computation stats:")?;
+        writeln!(f, "  oblivious transfers: {:16}", self.ots)?;
+        writeln!(f, "  OT batches:          {:16}", self.ot_batches)?;
+        writeln!(f, "  flushes:             {:16}", self.flushes)?;
+        writeln!(f, "  bytes written:       {:16}", self.bytes_written)?;
+        writeln!(f, "  bytes read:          {:16}", self.bytes_read)?;
+        writeln!(f, "  gates:               {:16}", self.gates)?;
+        writeln!(f, "  init time:           {:16.3?}", self.init_time)?;
+        writeln!(f, "  input time:          {:16.3?}", self.input_time)?;
+        writeln!(f, "  eval time:           {:16.3?}", self.eval_time)?;
+        if let Some(id) = self.session_id {
+            writeln!(f, "  session id: {}", id)?;
+        }
+        Ok(())
+    }
+}
+
+/// The options set through a [`SemiHonestBuilder`].
+#[derive(Clone, Debug, Default)]
+pub(super) struct Config {
+    pub(super) flush_threshold: Option<usize>,
+    pub(super) ot_chunk_size: Option<usize>,
+    pub(super) stats: bool,
+    pub(super) seed: Option<Block>,
+    pub(super) output_mode: OutputMode,
+    pub(super) timeout: Option<Duration>,
+    pub(super) hooks: Option<Hooks>,
+    pub(super) stream_outputs: bool,
+    pub(super) security: SecurityParams,
+}
+
+impl Config {
+    /// Check that `Wire` holds labels of the width of the security
+    /// parameters, and that those fit in a block in the output mode
+    /// [`OutputMode::Garbler`], in which the evaluator sends the garbler a
+    /// block of each output label.
+    pub(super) fn check_labels<Wire: WireLabel>(&self) -> Result<(), SecurityParamsError> {
+        self.security.check_label_bits(Wire::MAX_LABEL_BITS)?;
+        if self.output_mode == OutputMode::Garbler {
+            self.security.check_label_bits(128)?;
+        }
+        Ok(())
+    }
+}
+
+/// Semi-honest two-party computation.
+///
+/// [`SemiHonest::builder`] configures a garbler and evaluator beyond the
+/// defaults used by [`Garbler::new`] and [`Evaluator::new`].
+#[derive(Clone, Copy, Debug)]
+pub struct SemiHonest;
+
+impl SemiHonest {
+    /// Start configuring a garbler or evaluator, from the defaults used by
+    /// [`Garbler::new`] and [`Evaluator::new`].
+    pub fn builder() -> SemiHonestBuilder {
+        SemiHonestBuilder::default()
+    }
+}
+
+/// Configuration of a semi-honest garbler or evaluator, using `S` and `R` as
+/// the OT sender and receiver.
+///
+/// Both parties of a computation must be built from equal configurations:
+/// options such as the OT protocol and the chunk size change the messages
+/// exchanged.
+///
+/// ```
+/// use fancy_garbling::twopac::semihonest::{OutputMode, SemiHonest};
+/// use ocelot::ot::{KosReceiver, KosSender};
+///
+/// let config = SemiHonest::builder()
+///     .ot::<KosSender, KosReceiver>()
+///     .flush_threshold(1 << 16)
+///     .output_mode(OutputMode::Both);
+/// ```
+pub struct SemiHonestBuilder<S = AlszSender, R = AlszReceiver> {
+    pub(super) config: Config,
+    _ot: PhantomData<fn() -> (S, R)>,
+}
+
+impl Default for SemiHonestBuilder {
+    fn default() -> Self {
+        SemiHonestBuilder {
+            config: Config::default(),
+            _ot: PhantomData,
+        }
+    }
+}
+
+impl<S, R> Clone for SemiHonestBuilder<S, R> {
+    fn clone(&self) -> Self {
+        SemiHonestBuilder {
+            config: self.config.clone(),
+            _ot: PhantomData,
+        }
+    }
+}
+
+impl<S, R> std::fmt::Debug for SemiHonestBuilder<S, R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SemiHonestBuilder")
+            .field("ot_sender", &std::any::type_name::<S>())
+            .field("ot_receiver", &std::any::type_name::<R>())
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl<S, R> SemiHonestBuilder<S, R> {
+    /// Use `S2` and `R2` for oblivious transfer of the evaluator's inputs,
+    /// rather than ALSZ OT extension.
+    pub fn ot<S2, R2>(self) -> SemiHonestBuilder<S2, R2> {
+        SemiHonestBuilder {
+            config: self.config,
+            _ot: PhantomData,
+        }
+    }
+
+    /// Have the garbler flush its channel whenever it has written at least
+    /// `bytes` of wire labels and garbled gates since the last flush, so that
+    /// the evaluator can start on them sooner.
+    ///
+    /// By default the garbler leaves this to the channel's own buffering, and
+    /// flushes only before it reads.
+    pub fn flush_threshold(mut self, bytes: usize) -> Self {
+        self.config.flush_threshold = Some(bytes);
+        self
+    }
+
+    /// Run the oblivious transfers for the evaluator's inputs in batches of at
+    /// most `n`, bounding the memory they take, rather than in one batch per
+    /// call to `encode_many`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn ot_chunk_size(mut self, n: usize) -> Self {
+        assert!(n > 0, "the OT chunk size must be positive");
+        self.config.ot_chunk_size = Some(n);
+        self
+    }
+
+    /// Keep the counters returned by `Garbler::stats` and `Evaluator::stats`.
+    pub fn stats(mut self, enabled: bool) -> Self {
+        self.config.stats = enabled;
+        self
+    }
+
+    /// Derive the garbler's wire labels and deltas from `seed` rather than from
+    /// the RNG it is built with, so that they are the same from one run to the
+    /// next. The RNG is still used for oblivious transfer.
+    ///
+    /// This is meant for debugging and test vectors: reusing a seed across
+    /// computations with different inputs leaks those inputs. It has no effect
+    /// on the evaluator.
+    pub fn seed(mut self, seed: Block) -> Self {
+        self.config.seed = Some(seed);
+        self
+    }
+
+    /// Choose which parties learn the outputs of `eval_circuit`.
+    pub fn output_mode(mut self, mode: OutputMode) -> Self {
+        self.config.output_mode = mode;
+        self
+    }
+
+    /// Fail any read or write on the channel that blocks for longer than
+    /// `timeout`, so that a party whose peer stalls returns an error for which
+    /// `channel_error` is `ChannelError::Timeout` rather than waiting forever.
+    ///
+    /// The timeout is set on the channel when the party is built, which fails
+    /// if the channel does not support timeouts; see
+    /// `AbstractChannel::set_timeouts`. A party that has timed out is left
+    /// partway through the protocol and cannot continue. Unlike the other
+    /// options, the parties' timeouts may differ.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.config.timeout = Some(timeout);
+        self
+    }
+
+    /// Call `hooks` every [`Hooks::interval`] gates of the circuits run with
+    /// `eval_circuit`, cancelling the run with [`TwopacError::Cancelled`] if
+    /// they say so.
+    ///
+    /// At each checkpoint the garbler tells the evaluator whether it goes on,
+    /// so a cancelled garbler makes its evaluator fail with
+    /// [`TwopacError::PeerCancelled`] rather than wait for gates that never
+    /// come. Both parties must set hooks of the same interval, though their
+    /// callbacks may differ. A cancelled evaluator cannot tell the garbler,
+    /// which only writes while it garbles; dropping the evaluator's channel
+    /// makes the garbler fail once it next writes to it.
+    pub fn hooks(mut self, hooks: Hooks) -> Self {
+        self.config.hooks = Some(hooks);
+        self
+    }
+
+    /// Output each output of the circuits run with `eval_circuit` as soon as
+    /// the gate computing it has been garbled, rather than once the whole
+    /// circuit has been, so that
+    /// [`Evaluator::eval_circuit_streaming`] reports it to its callback
+    /// before evaluating the rest of the circuit.
+    ///
+    /// The garbler then sends the ciphertexts decoding each output right after
+    /// the gate computing it, flushing its channel after them, and both
+    /// parties output in the order of those gates, so both must set this
+    /// alike. Outputs are still returned in order at the end. This pays off
+    /// for circuits computing some outputs early, such as pipelined designs;
+    /// on circuits computing every output in the last few gates it only costs
+    /// the flushes.
+    pub fn stream_outputs(mut self, enabled: bool) -> Self {
+        self.config.stream_outputs = enabled;
+        self
+    }
+
+    /// Garble with the security parameters `security`, and run oblivious
+    /// transfer with their statistical security, rather than with
+    /// [`SecurityParams::DEFAULT`].
+    ///
+    /// The statistical security is that of the OT extension's consistency
+    /// check, so it changes nothing with ALSZ, which has none; KOS checks
+    /// with it. Audits with [`verify_transcript`](super::audit::verify_transcript)
+    /// replay the default parameters only.
+    ///
+    /// Labels wider than 128 bits, as with [`SecurityParams::LONG_TERM`],
+    /// need parties of [`WideWireMod2`](crate::WideWireMod2), take an
+    /// oblivious transfer per 128 bits of each evaluator input's label, and
+    /// cannot be sent as blocks: building the parties fails with
+    /// [`TwopacError::Security`] with other wires or in the output mode
+    /// [`OutputMode::Garbler`], and so do the features that send labels, such
+    /// as [`Garbler::preprocess`].
+    pub fn security(mut self, security: SecurityParams) -> Self {
+        self.config.security = security;
+        self
+    }
+
+    /// Make a garbler with this configuration.
+    ///
+    /// Like [`Garbler::new`], this blocks until the evaluator is made too.
+    pub fn build_garbler<C, RNG, Wire>(
+        &self,
+        mut channel: C,
+        rng: RNG,
+    ) -> Result<Garbler<C, RNG, S, Wire>, TwopacError>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng + SeedableRng<Seed = Block>,
+        S: OtSender<Msg = Block> + scuttlebutt::SemiHonest,
+        Wire: WireLabel,
+    {
+        self.set_timeouts(&mut channel)?;
+        Garbler::with_config(channel, rng, self.config.clone())
+    }
+
+    /// Make an evaluator with this configuration.
+    ///
+    /// Like [`Evaluator::new`], this blocks until the garbler is made too.
+    pub fn build_evaluator<C, RNG, Wire>(
+        &self,
+        mut channel: C,
+        rng: RNG,
+    ) -> Result<Evaluator<C, RNG, R, Wire>, TwopacError>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+        R: OtReceiver<Msg = Block> + scuttlebutt::SemiHonest,
+        Wire: WireLabel,
+    {
+        self.set_timeouts(&mut channel)?;
+        Evaluator::with_config(channel, rng, self.config.clone())
+    }
+
+    /// Make a garbler with this configuration that derives all its
+    /// randomness from `party_seed` and a session id tossed with the
+    /// evaluator, so that the session can later be audited; see the
+    /// [`audit`](super::audit) module.
+    ///
+    /// The evaluator must be built with
+    /// [`build_evaluator_from_seed`](Self::build_evaluator_from_seed). Each
+    /// party seed must be fresh: one reused with a peer that does the same
+    /// gives the same labels in both sessions, leaking the inputs.
+    pub fn build_garbler_from_seed<C, Wire>(
+        &self,
+        mut channel: C,
+        party_seed: Block,
+    ) -> Result<Garbler<C, AesRng, S, Wire>, TwopacError>
+    where
+        C: AbstractChannel,
+        S: OtSender<Msg = Block> + scuttlebutt::SemiHonest,
+        Wire: WireLabel,
+    {
+        self.set_timeouts(&mut channel)?;
+        Garbler::with_session(channel, party_seed, self.config.clone())
+    }
+
+    /// Make an evaluator with this configuration that derives all its
+    /// randomness from `party_seed` and a session id tossed with the
+    /// garbler, which must be built with
+    /// [`build_garbler_from_seed`](Self::build_garbler_from_seed).
+    pub fn build_evaluator_from_seed<C, Wire>(
+        &self,
+        mut channel: C,
+        party_seed: Block,
+    ) -> Result<Evaluator<C, AesRng, R, Wire>, TwopacError>
+    where
+        C: AbstractChannel,
+        R: OtReceiver<Msg = Block> + scuttlebutt::SemiHonest,
+        Wire: WireLabel,
+    {
+        self.set_timeouts(&mut channel)?;
+        Evaluator::with_session(channel, party_seed, self.config.clone())
+    }
+
+    fn set_timeouts<C: AbstractChannel>(&self, channel: &mut C) -> Result<(), TwopacError> {
+        if let Some(timeout) = self.config.timeout {
+            channel.set_timeouts(Some(timeout), Some(timeout))?;
+        }
+        Ok(())
+    }
+}
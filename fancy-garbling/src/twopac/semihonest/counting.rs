@@ -0,0 +1,89 @@
+//! The channel the parties count their communication with.
+
+use scuttlebutt::AbstractChannel;
+use std::{
+    io::Result,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// A channel counting the bytes written to and read from the channel it wraps.
+///
+/// [`Garbler`](super::Garbler) and [`Evaluator`](super::Evaluator) wrap their
+/// channels in one, so that [`TwopacStats`](super::TwopacStats) counts all of
+/// their communication, that of oblivious transfer included, whatever the
+/// channel. Its clones share the counts. It only counts if the party's
+/// configuration enables [`stats`](super::SemiHonestBuilder::stats).
+pub struct CountingChannel<C> {
+    channel: C,
+    counts: Option<Arc<Counts>>,
+}
+
+#[derive(Default)]
+struct Counts {
+    written: AtomicUsize,
+    read: AtomicUsize,
+}
+
+impl<C> CountingChannel<C> {
+    /// Wrap `channel`, counting its bytes if `enabled`.
+    pub(super) fn new(channel: C, enabled: bool) -> Self {
+        CountingChannel {
+            channel,
+            counts: enabled.then(Arc::default),
+        }
+    }
+
+    /// The channel wrapped, whose bytes are not counted.
+    pub(super) fn inner_mut(&mut self) -> &mut C {
+        &mut self.channel
+    }
+
+    /// The number of bytes written, if they are counted.
+    pub fn bytes_written(&self) -> Option<usize> {
+        self.counts
+            .as_ref()
+            .map(|counts| counts.written.load(Ordering::Relaxed))
+    }
+
+    /// The number of bytes read, if they are counted.
+    pub fn bytes_read(&self) -> Option<usize> {
+        self.counts
+            .as_ref()
+            .map(|counts| counts.read.load(Ordering::Relaxed))
+    }
+}
+
+impl<C: AbstractChannel> AbstractChannel for CountingChannel<C> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        if let Some(counts) = &self.counts {
+            counts.written.fetch_add(bytes.len(), Ordering::Relaxed);
+        }
+        self.channel.write_bytes(bytes)
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> Result<()> {
+        if let Some(counts) = &self.counts {
+            counts.read.fetch_add(bytes.len(), Ordering::Relaxed);
+        }
+        self.channel.read_bytes(bytes)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.channel.flush()
+    }
+
+    fn clone(&self) -> Self {
+        CountingChannel {
+            channel: self.channel.clone(),
+            counts: self.counts.clone(),
+        }
+    }
+
+    fn set_timeouts(&mut self, read: Option<Duration>, write: Option<Duration>) -> Result<()> {
+        self.channel.set_timeouts(read, write)
+    }
+}
@@ -0,0 +1,570 @@
+//! Estimates of the cost of semi-honest two-party computation.
+
+use super::{config::SemiHonestBuilder, OutputMode};
+use crate::{
+    circuit::{outputs_size, CircuitInfo, CircuitType},
+    errors::DummyError,
+    Modulus,
+};
+use ocelot::ot::{AlszSender, ChouOrlandiSender, KosSender};
+use std::time::Duration;
+
+/// The evaluator's time per non-free gate that [`estimate`] assumes until
+/// [`CostEstimate::calibrate`] is given a measured one: roughly that of a
+/// release build on a recent x86-64 machine, with both parties on it.
+pub const DEFAULT_TIME_PER_NONFREE_GATE: Duration = Duration::from_micros(3);
+
+/// A party of a semi-honest computation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Party {
+    /// The garbler, which is the OT sender.
+    Garbler,
+    /// The evaluator, which is the OT receiver.
+    Evaluator,
+}
+
+/// The messages of an OT protocol, for [`estimate`], where the garbler is the
+/// OT sender.
+///
+/// This is implemented for the OT senders in `ocelot` that a
+/// [`SemiHonestBuilder`] is most often configured with.
+pub trait OtCost {
+    /// The sender and length in bytes of each message setting up OT, in
+    /// order.
+    fn setup_messages() -> Vec<(Party, u64)>;
+
+    /// The sender and length in bytes of each message of a batch of `n`
+    /// transfers, in order.
+    fn transfer_messages(n: usize) -> Vec<(Party, u64)>;
+}
+
+// A compressed Ristretto point.
+const POINT: u64 = 32;
+const BLOCK: u64 = 16;
+
+impl OtCost for ChouOrlandiSender {
+    fn setup_messages() -> Vec<(Party, u64)> {
+        vec![(Party::Garbler, POINT)]
+    }
+
+    fn transfer_messages(n: usize) -> Vec<(Party, u64)> {
+        let n = n as u64;
+        vec![
+            (Party::Evaluator, n * POINT),
+            (Party::Garbler, 2 * n * BLOCK),
+        ]
+    }
+}
+
+/// The 128 base OTs of the OT extensions, run by the evaluator as the
+/// Chou–Orlandi sender.
+fn base_ot_messages() -> Vec<(Party, u64)> {
+    vec![
+        (Party::Evaluator, POINT),
+        (Party::Garbler, 128 * POINT),
+        (Party::Evaluator, 128 * 2 * BLOCK),
+    ]
+}
+
+/// `n` rounded up to a multiple of 8.
+fn pad_to_byte(n: usize) -> usize {
+    (n + 7) & !7
+}
+
+/// The bytes of the matrix the receiver of an OT extension sends for `ncols`
+/// transfers, 128 rows of `ncols` bits padded to a whole number of bytes.
+fn extension_matrix(ncols: usize) -> u64 {
+    (128 / 8 * pad_to_byte(ncols)) as u64
+}
+
+impl OtCost for AlszSender {
+    fn setup_messages() -> Vec<(Party, u64)> {
+        base_ot_messages()
+    }
+
+    fn transfer_messages(n: usize) -> Vec<(Party, u64)> {
+        vec![
+            (Party::Evaluator, extension_matrix(n)),
+            (Party::Garbler, 2 * n as u64 * BLOCK),
+        ]
+    }
+}
+
+impl OtCost for KosSender {
+    fn setup_messages() -> Vec<(Party, u64)> {
+        base_ot_messages()
+    }
+
+    fn transfer_messages(n: usize) -> Vec<(Party, u64)> {
+        // The transfers are padded to a byte, plus 128 + 40 for the check,
+        // whose seed is tossed with a commitment before the receiver sends
+        // its part of it.
+        let ncols = pad_to_byte(n) + 128 + 40;
+        vec![
+            (Party::Evaluator, extension_matrix(ncols)),
+            (Party::Garbler, BLOCK),
+            (Party::Evaluator, BLOCK),
+            (Party::Garbler, BLOCK),
+            (Party::Evaluator, 3 * BLOCK),
+            (Party::Garbler, 2 * n as u64 * BLOCK),
+        ]
+    }
+}
+
+/// The number of flights of a computation, by the phase each starts in.
+///
+/// A flight is a run of messages from one party not interrupted by a message
+/// from the other, so that the flights of a computation take at least as many
+/// network latencies.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Rounds {
+    /// The exchange of protocol versions, a flight from each party.
+    pub handshake: usize,
+    /// Sending the garbler's inputs.
+    pub garbler_inputs: usize,
+    /// Oblivious transfer of the evaluator's inputs, including its setup.
+    pub ot: usize,
+    /// Sending the garbled circuit.
+    pub circuit: usize,
+    /// Sending the outputs, or their labels, back to the garbler.
+    pub outputs: usize,
+}
+
+impl Rounds {
+    /// The number of flights of all phases.
+    pub fn total(&self) -> usize {
+        self.handshake + self.garbler_inputs + self.ot + self.circuit + self.outputs
+    }
+}
+
+/// The predicted cost of running a circuit between a semi-honest garbler and
+/// evaluator. Produced by [`estimate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CostEstimate {
+    /// The bytes sent by the garbler.
+    pub garbler_bytes: u64,
+    /// The bytes sent by the evaluator.
+    pub evaluator_bytes: u64,
+    /// The bytes of garbled gates and outputs, part of `garbler_bytes`.
+    pub garbled_circuit_bytes: u64,
+    /// The number of oblivious transfers, one per bit of the evaluator's
+    /// inputs and 128 bits of their labels.
+    pub ots: usize,
+    /// The number of batches of OT extension the transfers are run in.
+    pub ot_batches: usize,
+    /// The flights of each phase.
+    pub rounds: Rounds,
+    /// The number of non-free gates in the circuit.
+    pub nonfree_gates: usize,
+    /// The evaluator's time per non-free gate the estimate assumes.
+    pub time_per_nonfree_gate: Duration,
+    /// The evaluator's time spent on the circuit, ignoring the network.
+    pub cpu_time: Duration,
+}
+
+impl CostEstimate {
+    /// The bytes sent by both parties.
+    pub fn total_bytes(&self) -> u64 {
+        self.garbler_bytes + self.evaluator_bytes
+    }
+
+    /// Replace the time per non-free gate, e.g., with
+    /// [`TwopacProfile::time_per_nonfree_gate`](super::TwopacProfile::time_per_nonfree_gate)
+    /// measured once on the machine that will run the computation.
+    pub fn calibrate(mut self, time_per_nonfree_gate: Duration) -> Self {
+        self.time_per_nonfree_gate = time_per_nonfree_gate;
+        self.cpu_time = time_per_nonfree_gate
+            .checked_mul(self.nonfree_gates.try_into().unwrap_or(u32::MAX))
+            .unwrap_or(Duration::MAX);
+        self
+    }
+
+    /// Serialize the estimate as JSON.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("estimates always serialize")
+    }
+}
+
+impl std::fmt::Display for CostEstimate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "two-party computation estimate:")?;
+        writeln!(f, "  garbler bytes:       {:16}", self.garbler_bytes)?;
+        writeln!(f, "  evaluator bytes:     {:16}", self.evaluator_bytes)?;
+        writeln!(
+            f,
+            "  garbled circuit:     {:16}",
+            self.garbled_circuit_bytes
+        )?;
+        writeln!(f, "  oblivious transfers: {:16}", self.ots)?;
+        writeln!(f, "  OT batches:          {:16}", self.ot_batches)?;
+        writeln!(f, "  flights:             {:16}", self.rounds.total())?;
+        writeln!(f, "  non-free gates:      {:16}", self.nonfree_gates)?;
+        writeln!(f, "  CPU time:         {:16.3?}", self.cpu_time)?;
+        Ok(())
+    }
+}
+
+/// The phases of a computation, in the order they run.
+#[derive(Clone, Copy)]
+enum Phase {
+    GarblerInputs,
+    Ot,
+    Circuit,
+    Outputs,
+}
+
+/// Estimate the cost of running `circ` between a garbler and an evaluator
+/// built from `config`, as [`profile`](super::profile) and `eval_circuit`
+/// do: the garbler encodes all of its inputs, the evaluator all of its
+/// inputs, and then the circuit is run.
+///
+/// The bytes and flights follow the messages of
+/// [`PROTOCOL_VERSION`](super::PROTOCOL_VERSION) for `S`; the CPU time is a
+/// rough guess until the estimate is [calibrated](CostEstimate::calibrate).
+/// Fails if the circuit cannot be evaluated.
+pub fn estimate<C, S, R>(
+    circ: &C,
+    config: &SemiHonestBuilder<S, R>,
+) -> Result<CostEstimate, DummyError>
+where
+    C: CircuitType + CircuitInfo,
+    S: OtCost,
+{
+    let config = &config.config;
+    let info = circ.info()?;
+    let ots = info
+        .evaluator_input_moduli
+        .iter()
+        .map(|&q| f64::from(q).log2().ceil() as usize)
+        .sum::<usize>()
+        * config.security.label_blocks();
+    let mut garbled_circuit_bytes = circ.garbled_size_estimate(config.security) as u64;
+    // A garbler that learns the outputs alone sends nothing to decode them.
+    if config.output_mode == OutputMode::Garbler {
+        garbled_circuit_bytes -= outputs_size(circ, config.security) as u64;
+    }
+
+    // Binary labels are sent at the label width, the others as blocks.
+    let garbler_inputs = info
+        .garbler_input_moduli
+        .iter()
+        .map(|&q| match q {
+            2 => config.security.label_bytes() as u64,
+            _ => BLOCK,
+        })
+        .sum();
+    let mut messages = vec![(Phase::GarblerInputs, Party::Garbler, garbler_inputs)];
+    // The garbler runs the transfers in chunks, as `run_ots` does.
+    let chunk = config.ot_chunk_size.unwrap_or(ots).max(1);
+    let batches = (0..ots)
+        .step_by(chunk)
+        .map(|i| chunk.min(ots - i))
+        .collect::<Vec<_>>();
+    if !batches.is_empty() {
+        let setup = S::setup_messages().into_iter();
+        let transfers = batches.iter().flat_map(|&n| S::transfer_messages(n));
+        messages.extend(setup.chain(transfers).map(|(p, n)| (Phase::Ot, p, n)));
+    }
+    // With hooks, the garbler sends a byte at every checkpoint.
+    let checkpoints = config
+        .hooks
+        .as_ref()
+        .map_or(0, |hooks| circ.get_gates().len() / hooks.interval());
+    messages.push((
+        Phase::Circuit,
+        Party::Garbler,
+        garbled_circuit_bytes + checkpoints as u64,
+    ));
+    match config.output_mode {
+        OutputMode::Evaluator => {}
+        OutputMode::Both => {
+            let n = circ.get_output_refs().len() * std::mem::size_of::<Modulus>();
+            messages.push((Phase::Outputs, Party::Evaluator, n as u64));
+        }
+        OutputMode::Garbler => {
+            let labels = circ
+                .constant_outputs()
+                .iter()
+                .filter(|c| c.is_none())
+                .count();
+            messages.push((Phase::Outputs, Party::Evaluator, BLOCK * labels as u64));
+        }
+    }
+
+    // Both parties send their versions before reading the other's.
+    let version = std::mem::size_of::<u32>() as u64;
+    let (mut garbler_bytes, mut evaluator_bytes) = (version, version);
+    let mut rounds = Rounds {
+        handshake: 2,
+        ..Rounds::default()
+    };
+    let mut last = None;
+    for (phase, party, n) in messages {
+        if n == 0 {
+            continue;
+        }
+        match party {
+            Party::Garbler => garbler_bytes += n,
+            Party::Evaluator => evaluator_bytes += n,
+        }
+        if last != Some(party) {
+            *match phase {
+                Phase::GarblerInputs => &mut rounds.garbler_inputs,
+                Phase::Ot => &mut rounds.ot,
+                Phase::Circuit => &mut rounds.circuit,
+                Phase::Outputs => &mut rounds.outputs,
+            } += 1;
+            last = Some(party);
+        }
+    }
+
+    let nonfree_gates = circ.get_num_nonfree_gates();
+    Ok(CostEstimate {
+        garbler_bytes,
+        evaluator_bytes,
+        garbled_circuit_bytes,
+        ots,
+        ot_batches: batches.len(),
+        rounds,
+        nonfree_gates,
+        time_per_nonfree_gate: Duration::ZERO,
+        cpu_time: Duration::ZERO,
+    }
+    .calibrate(DEFAULT_TIME_PER_NONFREE_GATE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{ArithmeticCircuit, BinaryCircuit, CircuitBuilder, EvaluableCircuit},
+        hooks::Hooks,
+        twopac::semihonest::{Evaluator, Garbler, SemiHonest},
+        AllWire, Fancy, FancyArithmetic, FancyInput, SecurityParams, WideWireMod2, WireLabel,
+        WireMod2,
+    };
+    use ocelot::ot::{KosReceiver, Receiver as OtReceiver, Sender as OtSender};
+    use scuttlebutt::{track_unix_channel_pair, AbstractChannel, AesRng, Block, TrackUnixChannel};
+
+    fn aes() -> BinaryCircuit {
+        BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap()
+    }
+
+    /// Run `circ` on zero inputs between parties built from `config`, and
+    /// return the bytes written by the garbler and by the evaluator, and
+    /// those the garbler wrote evaluating the circuit.
+    fn measure<C, S, R, Wire>(circ: &C, config: &SemiHonestBuilder<S, R>) -> (u64, u64, u64)
+    where
+        C: CircuitType
+            + EvaluableCircuit<Garbler<TrackUnixChannel, AesRng, S, Wire>>
+            + EvaluableCircuit<Evaluator<TrackUnixChannel, AesRng, R, Wire>>
+            + Sync,
+        S: OtSender<Msg = Block> + scuttlebutt::SemiHonest,
+        R: OtReceiver<Msg = Block> + scuttlebutt::SemiHonest,
+        Wire: WireLabel,
+    {
+        let gb_moduli = (0..circ.num_garbler_inputs())
+            .map(|i| circ.garbler_input_mod(i))
+            .collect::<Vec<_>>();
+        let ev_moduli = (0..circ.num_evaluator_inputs())
+            .map(|i| circ.evaluator_input_mod(i))
+            .collect::<Vec<_>>();
+        let (sender, receiver) = track_unix_channel_pair();
+        let bytes = |c: &TrackUnixChannel| (c.kilobits_written() * 1000.0 / 8.0).round() as u64;
+        std::thread::scope(|s| {
+            let handle = s.spawn(|| {
+                let mut gb = config
+                    .build_garbler::<_, _, Wire>(sender, AesRng::new())
+                    .unwrap();
+                let xs = gb
+                    .encode_many(&vec![0; gb_moduli.len()], &gb_moduli)
+                    .unwrap();
+                let ys = gb.receive_many(&ev_moduli).unwrap();
+                gb.get_channel().flush().unwrap();
+                let before = bytes(gb.get_channel());
+                gb.eval_circuit(circ, &xs, &ys).unwrap();
+                gb.get_channel().flush().unwrap();
+                let after = bytes(gb.get_channel());
+                (after, after - before)
+            });
+            let mut ev = config
+                .build_evaluator::<_, _, Wire>(receiver, AesRng::new())
+                .unwrap();
+            let xs = ev.receive_many(&gb_moduli).unwrap();
+            let ys = ev
+                .encode_many(&vec![0; ev_moduli.len()], &ev_moduli)
+                .unwrap();
+            if config.config.output_mode == OutputMode::Garbler {
+                ev.output_labels(circ, &xs, &ys).unwrap();
+            } else {
+                ev.eval_circuit(circ, &xs, &ys).unwrap();
+            }
+            let (gb, circuit) = handle.join().unwrap();
+            (gb, bytes(ev.get_channel()), circuit)
+        })
+    }
+
+    /// Check that `actual` is within 1% of `estimated`.
+    fn assert_close(estimated: u64, actual: u64, what: &str) {
+        let diff = estimated.abs_diff(actual);
+        assert!(
+            diff * 100 <= actual,
+            "estimated {} {} bytes, measured {}",
+            estimated,
+            what,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_hooks_bandwidth() {
+        let circ = aes();
+        let plain = estimate(&circ, &SemiHonest::builder()).unwrap();
+        let config = SemiHonest::builder().hooks(Hooks::new().every(100));
+        let est = estimate(&circ, &config).unwrap();
+        // One byte from the garbler at each checkpoint.
+        let checkpoints = (circ.get_gates().len() / 100) as u64;
+        assert_eq!(est.garbler_bytes, plain.garbler_bytes + checkpoints);
+        assert_eq!(est.evaluator_bytes, plain.evaluator_bytes);
+        let (gb, ev, _) = measure::<_, _, _, WireMod2>(&circ, &config);
+        assert_close(est.garbler_bytes, gb, "garbler");
+        assert_close(est.evaluator_bytes, ev, "evaluator");
+    }
+
+    #[test]
+    fn test_aes_128_bandwidth() {
+        let circ = aes();
+        let config = SemiHonest::builder();
+        let est = estimate(&circ, &config).unwrap();
+        let (gb, ev, _) = measure::<_, _, _, WireMod2>(&circ, &config);
+        assert_close(est.garbler_bytes, gb, "garbler");
+        assert_close(est.evaluator_bytes, ev, "evaluator");
+        assert_eq!(est.ots, 128);
+        assert_eq!(est.ot_batches, 1);
+        assert_eq!(est.nonfree_gates, 6800);
+        // As counted by the `test_aes_rounds` test of the parties.
+        assert_eq!(
+            est.rounds,
+            Rounds {
+                handshake: 2,
+                garbler_inputs: 1,
+                ot: 4,
+                circuit: 0,
+                outputs: 0,
+            }
+        );
+        assert_eq!(est.rounds.total(), 7);
+    }
+
+    #[test]
+    fn test_wide_label_bandwidth() {
+        let circ = aes();
+        let config = SemiHonest::builder().security(SecurityParams::LONG_TERM);
+        let est = estimate(&circ, &config).unwrap();
+        let (gb, ev, circuit) = measure::<_, _, _, WideWireMod2>(&circ, &config);
+        assert_close(est.garbler_bytes, gb, "garbler");
+        assert_close(est.evaluator_bytes, ev, "evaluator");
+        assert_eq!(est.garbled_circuit_bytes, circuit);
+        // An oblivious transfer per 128 bits of each label.
+        assert_eq!(est.ots, 2 * 128);
+    }
+
+    #[test]
+    fn test_configured_bandwidth() {
+        let circ = aes();
+        let config = SemiHonest::builder()
+            .ot::<KosSender, KosReceiver>()
+            .ot_chunk_size(50)
+            .output_mode(OutputMode::Both);
+        let est = estimate(&circ, &config).unwrap();
+        let (gb, ev, _) = measure::<_, _, _, WireMod2>(&circ, &config);
+        assert_close(est.garbler_bytes, gb, "garbler");
+        assert_close(est.evaluator_bytes, ev, "evaluator");
+        assert_eq!(est.ot_batches, 3);
+        assert_eq!(est.rounds.outputs, 1);
+
+        let config =
+            SemiHonest::builder().ot::<ChouOrlandiSender, ocelot::ot::ChouOrlandiReceiver>();
+        let est = estimate(&circ, &config).unwrap();
+        let (gb, ev, _) = measure::<_, _, _, WireMod2>(&circ, &config);
+        assert_close(est.garbler_bytes, gb, "garbler");
+        assert_close(est.evaluator_bytes, ev, "evaluator");
+    }
+
+    #[test]
+    fn test_arithmetic_bandwidth() {
+        // Projections, multiplications of unequal moduli, constants, and
+        // inputs of several OTs each.
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(5);
+        let y = b.evaluator_input(5);
+        let z = b.evaluator_input(7);
+        let c = b.constant(3, 5).unwrap();
+        let c_ = b.constant(3, 5).unwrap();
+        let s = b.add_many(&[x, y, c, c_]).unwrap();
+        let p = b.proj(&s, 7, Some(vec![1, 2, 3, 4, 5])).unwrap();
+        let m = b.mul(&p, &z).unwrap();
+        let m_ = b.mul(&s, &z).unwrap();
+        b.outputs(&[m, m_]).unwrap();
+        let circ = b.finish();
+        let config = SemiHonest::builder().output_mode(OutputMode::Both);
+        let est = estimate(&circ, &config).unwrap();
+        assert_eq!(est.ots, 3 + 3);
+        let (gb, ev, _) = measure::<_, _, _, AllWire>(&circ, &config);
+        assert_eq!((est.garbler_bytes, est.evaluator_bytes), (gb, ev));
+    }
+
+    #[test]
+    fn test_garbler_output_bandwidth() {
+        // A constant output, which needs no label.
+        let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+        let x = b.garbler_input(5);
+        let y = b.evaluator_input(5);
+        let c = b.constant(3, 5).unwrap();
+        let s = b.add(&x, &y).unwrap();
+        let m = b.mul(&s, &y).unwrap();
+        b.outputs(&[s, m, c]).unwrap();
+        let circ = b.finish();
+        let plain = estimate(&circ, &SemiHonest::builder()).unwrap();
+        let config = SemiHonest::builder().output_mode(OutputMode::Garbler);
+        let est = estimate(&circ, &config).unwrap();
+        assert_eq!(est.evaluator_bytes, plain.evaluator_bytes + 2 * 16);
+        assert_eq!(est.rounds.outputs, 1);
+        let (gb, ev, circuit) = measure::<_, _, _, AllWire>(&circ, &config);
+        assert_eq!((est.garbler_bytes, est.evaluator_bytes), (gb, ev));
+        assert_eq!(est.garbled_circuit_bytes, circuit);
+    }
+
+    #[test]
+    fn test_garbled_circuit_bytes() {
+        let sha = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/sha-256.txt"
+        )))
+        .unwrap();
+        for circ in [aes(), sha] {
+            for security in [SecurityParams::DEFAULT, SecurityParams::SIMULATION] {
+                let config = SemiHonest::builder().security(security);
+                let est = estimate(&circ, &config).unwrap();
+                let bytes = circ.garbled_size_estimate(security) as u64;
+                assert_eq!(est.garbled_circuit_bytes, bytes);
+                let (_, _, circuit) = measure::<_, _, _, WireMod2>(&circ, &config);
+                assert_eq!(circuit, bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn test_calibrate() {
+        let est = estimate(&aes(), &SemiHonest::builder()).unwrap();
+        assert_eq!(est.time_per_nonfree_gate, DEFAULT_TIME_PER_NONFREE_GATE);
+        let est = est.calibrate(Duration::from_micros(1));
+        assert_eq!(est.cpu_time, Duration::from_micros(6800));
+        assert!(est.to_string().contains("flights"));
+    }
+}
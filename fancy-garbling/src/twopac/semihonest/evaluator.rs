@@ -1,20 +1,119 @@
+use super::{
+    config::{Config, OutputMode, Phase, TwopacStats},
+    counting::CountingChannel,
+    input_moduli, session, Party, CHECKPOINT_CANCEL, CHECKPOINT_CONTINUE,
+};
 use crate::{
-    errors::TwopacError, wire::WireLabel, AllWire, ArithmeticWire, Evaluator as Ev, Fancy,
-    FancyArithmetic, FancyBinary, FancyInput, FancyReveal, WireMod2,
+    circuit::{CustomGate, EvaluableCircuit, OnOutput},
+    classic::{self, EncodedInputs, GarbledCircuit, GarblingId, GcEvalContext},
+    errors::{EvaluatorError, FancyError, TwopacError},
+    fancy::check_inputs,
+    hooks::Hooks,
+    wire::WireLabel,
+    AllWire, ArithmeticWire, Evaluator as Ev, Fancy, FancyArithmetic, FancyBinary, FancyInput,
+    FancyReveal, Modulus, WideWireMod2, WireMod2,
 };
 use ocelot::ot::Receiver as OtReceiver;
-use rand::{CryptoRng, Rng};
-use scuttlebutt::{AbstractChannel, Block, SemiHonest};
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, AesRng, Block, Block512, SemiHonest};
+use std::{sync::Arc, time::Instant};
 
 /// Semi-honest evaluator.
+///
+/// An `Evaluator` is `Send` whenever its channel, RNG, and OT receiver are, so a
+/// session can be started on one thread and finished on another. Of the
+/// `scuttlebutt` channels, `SyncChannel`, `UnixChannel`, and `TrackChannel` are
+/// `Send`; `Channel` is not, as it shares its reader and writer through `Rc`.
 pub struct Evaluator<C, RNG, OT, Wire> {
-    evaluator: Ev<C, Wire>,
-    channel: C,
-    ot: OT,
+    evaluator: Ev<CountingChannel<C>, Wire>,
+    channel: CountingChannel<C>,
+    ot: Option<OT>,
     rng: RNG,
+    config: Config,
+    stats: Option<TwopacStats>,
+    session_id: Option<Block>,
+    // With `OutputMode::Garbler`, the outputs evaluated so far that are not
+    // constant.
+    pending_outputs: Vec<Wire>,
+    closed: bool,
 }
 
-impl<C, RNG, OT, Wire> Evaluator<C, RNG, OT, Wire> {}
+impl<C, RNG, OT, Wire> Evaluator<C, RNG, OT, Wire> {
+    /// Whether the evaluator has been [closed](Evaluator::close).
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Fail with [`TwopacError::Closed`] once the evaluator has been closed.
+    fn check_open(&self) -> Result<(), TwopacError> {
+        if self.closed {
+            return Err(TwopacError::Closed);
+        }
+        Ok(())
+    }
+
+    /// Fail as [`Evaluator::check_open`] does, and count a gate otherwise.
+    fn check_gate(&mut self) -> Result<(), TwopacError> {
+        self.check_open()?;
+        if let Some(stats) = self.stats.as_mut() {
+            stats.gates += 1;
+        }
+        Ok(())
+    }
+
+    /// Count the time since `started` as spent in `phase`.
+    fn add_time(&mut self, phase: Phase, started: Instant) {
+        if let Some(stats) = self.stats.as_mut() {
+            stats.add_time(phase, started.elapsed());
+        }
+    }
+}
+
+impl<
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng + SeedableRng<Seed = Block>,
+        OT: OtReceiver<Msg = Block> + SemiHonest,
+        Wire: WireLabel,
+    > Evaluator<C, RNG, OT, Wire>
+{
+    /// End the session: flush the channel, and wipe the evaluator's secrets.
+    ///
+    /// The OT and the RNG it drew its randomness from are dropped; with the
+    /// `zeroize` feature, they are overwritten first. Every later use of the
+    /// evaluator fails with [`TwopacError::Closed`], and closing it again
+    /// does nothing. The channel is closed when the evaluator is dropped, and
+    /// the wires it returned are the caller's to wipe, with
+    /// [`WireLabel::wipe`].
+    pub fn close(&mut self) -> Result<(), TwopacError> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+        let flushed = self.channel.flush();
+        self.ot = None;
+        let seed = self.rng.gen::<Block>();
+        self.rng = RNG::from_seed(seed);
+        Ok(flushed?)
+    }
+}
+
+impl<C: AbstractChannel, OT: OtReceiver<Msg = Block> + SemiHonest, Wire: WireLabel>
+    Evaluator<C, AesRng, OT, Wire>
+{
+    pub(super) fn with_session(
+        channel: C,
+        party_seed: Block,
+        config: Config,
+    ) -> Result<Self, TwopacError> {
+        config.check_labels::<Wire>()?;
+        let started = Instant::now();
+        let mut channel = CountingChannel::new(channel, config.stats);
+        super::handshake(&mut channel)?;
+        let session_id = session::toss(&mut channel, party_seed, Party::Evaluator)?;
+        let rng = session::rng(party_seed, session_id);
+        Ok(Self::start(channel, rng, config, Some(session_id), started))
+    }
+}
 
 impl<
         C: AbstractChannel,
@@ -24,26 +123,509 @@ impl<
     > Evaluator<C, RNG, OT, Wire>
 {
     /// Make a new `Evaluator`.
-    pub fn new(mut channel: C, mut rng: RNG) -> Result<Self, TwopacError> {
-        let ot = OT::init(&mut channel, &mut rng)?;
-        let evaluator = Ev::new(channel.clone());
-        Ok(Self {
+    ///
+    /// Oblivious transfer is initialized lazily, the first time the evaluator
+    /// encodes its inputs, so that the base OT messages share a flight with the
+    /// OT extension setup.
+    ///
+    /// The evaluator first exchanges [`PROTOCOL_VERSION`](super::PROTOCOL_VERSION)
+    /// with the garbler, so this blocks until the garbler is made too, and
+    /// fails with [`TwopacError::VersionMismatch`] if the versions differ.
+    ///
+    /// This uses the default configuration; see
+    /// [`SemiHonest::builder`](super::SemiHonest::builder) for the others.
+    pub fn new(channel: C, rng: RNG) -> Result<Self, TwopacError> {
+        Self::with_config(channel, rng, Config::default())
+    }
+
+    pub(super) fn with_config(channel: C, rng: RNG, config: Config) -> Result<Self, TwopacError> {
+        config.check_labels::<Wire>()?;
+        let started = Instant::now();
+        let mut channel = CountingChannel::new(channel, config.stats);
+        super::handshake(&mut channel)?;
+        Ok(Self::start(channel, rng, config, None, started))
+    }
+
+    pub(super) fn start(
+        channel: CountingChannel<C>,
+        rng: RNG,
+        config: Config,
+        session_id: Option<Block>,
+        started: Instant,
+    ) -> Self {
+        let evaluator = Ev::with_security(channel.clone(), config.security);
+        let stats = config.stats.then(|| TwopacStats {
+            session_id,
+            init_time: started.elapsed(),
+            ..TwopacStats::default()
+        });
+        Self {
             evaluator,
             channel,
-            ot,
+            ot: None,
             rng,
+            config,
+            stats,
+            session_id,
+            pending_outputs: Vec::new(),
+            closed: false,
+        }
+    }
+
+    /// The OT of this evaluator, once it has run one.
+    #[cfg(feature = "tokio")]
+    pub(super) fn ot_mut(&mut self) -> Option<&mut OT> {
+        self.ot.as_mut()
+    }
+
+    /// The session id tossed with the garbler, if this evaluator was built
+    /// with [`build_evaluator_from_seed`](super::SemiHonestBuilder::build_evaluator_from_seed).
+    pub fn session_id(&self) -> Option<Block> {
+        self.session_id
+    }
+
+    /// The counters kept since this evaluator was made, as of now, if its
+    /// configuration enables them.
+    pub fn stats(&self) -> Option<TwopacStats> {
+        let stats = self.stats.as_ref()?;
+        Some(TwopacStats {
+            bytes_written: self.channel.bytes_written().unwrap_or_default(),
+            bytes_read: self.channel.bytes_read().unwrap_or_default(),
+            ..stats.clone()
         })
     }
 
     /// Get a reference to the internal channel.
+    ///
+    /// What is written to or read from it directly is not counted in the
+    /// [`stats`](Evaluator::stats).
     pub fn get_channel(&mut self) -> &mut C {
-        &mut self.channel
+        self.channel.inner_mut()
+    }
+
+    /// Evaluate `circuit` on the given input wires, returning its outputs.
+    ///
+    /// The circuit is only borrowed, so a single copy of it (e.g., behind an
+    /// `Arc`) can be shared with a garbler running in another thread.
+    ///
+    /// If the output mode is [`OutputMode::Both`], the outputs are also sent
+    /// to the garbler. Fails with [`TwopacError::Cancelled`] if the
+    /// [hooks](super::SemiHonestBuilder::hooks) cancel the run, with
+    /// [`TwopacError::PeerCancelled`] if the garbler's do, and with
+    /// [`TwopacError::WrongOutputMode`] if the output mode is
+    /// [`OutputMode::Garbler`]; see [`Evaluator::output_labels`].
+    pub fn eval_circuit<Circuit: EvaluableCircuit<Self>>(
+        &mut self,
+        circuit: &Circuit,
+        garbler_inputs: &[Wire],
+        evaluator_inputs: &[Wire],
+    ) -> Result<Vec<Modulus>, TwopacError> {
+        self.eval_circuit_with_public(circuit, garbler_inputs, evaluator_inputs, &[])
+    }
+
+    /// Evaluate a circuit with public inputs as [`Evaluator::eval_circuit`]
+    /// does, given the wires of the public inputs, as made by
+    /// [`Evaluator::encode_public`].
+    pub fn eval_circuit_with_public<Circuit: EvaluableCircuit<Self>>(
+        &mut self,
+        circuit: &Circuit,
+        garbler_inputs: &[Wire],
+        evaluator_inputs: &[Wire],
+        public_inputs: &[Wire],
+    ) -> Result<Vec<Modulus>, TwopacError> {
+        self.eval(
+            circuit,
+            garbler_inputs,
+            evaluator_inputs,
+            public_inputs,
+            &mut |_, _| {},
+        )
+    }
+
+    /// Evaluate `circuit` as [`Evaluator::eval_circuit`] does, calling
+    /// `on_output` with the index and value of each output as soon as it is
+    /// decoded.
+    ///
+    /// If both parties are configured to
+    /// [stream outputs](super::SemiHonestBuilder::stream_outputs), each output
+    /// is decoded right after the gate computing it, before the rest of the
+    /// circuit is evaluated; otherwise they are all decoded, and `on_output`
+    /// called for each, once the whole circuit has been. The outputs are
+    /// returned in order at the end either way.
+    pub fn eval_circuit_streaming<Circuit: EvaluableCircuit<Self>>(
+        &mut self,
+        circuit: &Circuit,
+        garbler_inputs: &[Wire],
+        evaluator_inputs: &[Wire],
+        mut on_output: impl FnMut(usize, Modulus),
+    ) -> Result<Vec<Modulus>, TwopacError> {
+        self.eval(
+            circuit,
+            garbler_inputs,
+            evaluator_inputs,
+            &[],
+            &mut on_output,
+        )
+    }
+
+    fn eval<Circuit: EvaluableCircuit<Self>>(
+        &mut self,
+        circuit: &Circuit,
+        garbler_inputs: &[Wire],
+        evaluator_inputs: &[Wire],
+        public_inputs: &[Wire],
+        on_output: &mut OnOutput<'_>,
+    ) -> Result<Vec<Modulus>, TwopacError> {
+        self.check_open()?;
+        if self.config.output_mode == OutputMode::Garbler {
+            return Err(TwopacError::WrongOutputMode);
+        }
+        let started = Instant::now();
+        let outputs = self.run_circuit(
+            circuit,
+            garbler_inputs,
+            evaluator_inputs,
+            public_inputs,
+            on_output,
+        )?;
+        let outputs = outputs.expect("evaluator outputs always are Some(Modulus)");
+        if !self.config.stream_outputs {
+            for (i, &val) in outputs.iter().enumerate() {
+                on_output(i, val);
+            }
+        }
+        if self.config.output_mode == OutputMode::Both {
+            for val in outputs.iter() {
+                self.channel.write_bytes(&val.to_le_bytes())?;
+            }
+            self.channel.flush()?;
+        }
+        self.add_time(Phase::Eval, started);
+        Ok(outputs)
+    }
+
+    /// Evaluate `circuit` with the hooks and streaming of the configuration,
+    /// returning its outputs if the evaluator decodes them.
+    fn run_circuit<Circuit: EvaluableCircuit<Self>>(
+        &mut self,
+        circuit: &Circuit,
+        garbler_inputs: &[Wire],
+        evaluator_inputs: &[Wire],
+        public_inputs: &[Wire],
+        on_output: &mut OnOutput<'_>,
+    ) -> Result<Option<Vec<Modulus>>, TwopacError> {
+        let streaming = self.config.stream_outputs;
+        let outputs = {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::info_span!("evaluate", party = "evaluator").entered();
+            match self.config.hooks.clone() {
+                None if streaming => circuit.eval_streaming(
+                    self,
+                    garbler_inputs,
+                    evaluator_inputs,
+                    public_inputs,
+                    None,
+                    on_output,
+                )?,
+                None => circuit.eval_with_public(
+                    self,
+                    garbler_inputs,
+                    evaluator_inputs,
+                    public_inputs,
+                )?,
+                Some(hooks) if streaming => {
+                    let total = circuit.get_gates().len();
+                    let outputs = circuit.eval_streaming(
+                        self,
+                        garbler_inputs,
+                        evaluator_inputs,
+                        public_inputs,
+                        Some((hooks.interval(), &mut |ev, done| {
+                            ev.checkpoint(&hooks, done, total)
+                        })),
+                        on_output,
+                    )?;
+                    hooks.progress(total, total);
+                    outputs
+                }
+                Some(hooks) => {
+                    let total = circuit.get_gates().len();
+                    let outputs = circuit.eval_with_checkpoints(
+                        self,
+                        garbler_inputs,
+                        evaluator_inputs,
+                        public_inputs,
+                        hooks.interval(),
+                        &mut |ev, done| ev.checkpoint(&hooks, done, total),
+                    )?;
+                    hooks.progress(total, total);
+                    outputs
+                }
+            }
+        };
+        Ok(outputs)
+    }
+
+    /// Evaluate `circuit` on the given input wires without decoding its
+    /// outputs, and send the garbler the label of each output that is not
+    /// constant, as [`Evaluator::send_output_labels`] does, returning those
+    /// labels.
+    ///
+    /// This is how the evaluator runs a circuit in the output mode
+    /// [`OutputMode::Garbler`], in which the garbler calls
+    /// [`Garbler::eval_circuit`](super::Garbler::eval_circuit), withholds
+    /// what decodes the outputs, and decodes the labels itself: the evaluator
+    /// cannot tell which value a label stands for. Fails with
+    /// [`TwopacError::WrongOutputMode`] in the other output modes.
+    pub fn output_labels<Circuit: EvaluableCircuit<Self>>(
+        &mut self,
+        circuit: &Circuit,
+        garbler_inputs: &[Wire],
+        evaluator_inputs: &[Wire],
+    ) -> Result<Vec<Block>, TwopacError> {
+        self.check_open()?;
+        if self.config.output_mode != OutputMode::Garbler {
+            return Err(TwopacError::WrongOutputMode);
+        }
+        let started = Instant::now();
+        self.pending_outputs.clear();
+        self.run_circuit(
+            circuit,
+            garbler_inputs,
+            evaluator_inputs,
+            &[],
+            &mut |_, _| {},
+        )?;
+        self.add_time(Phase::Eval, started);
+        let outputs = std::mem::take(&mut self.pending_outputs);
+        self.send_output_labels(&outputs)?;
+        Ok(outputs.iter().map(|x| x.as_block()).collect())
+    }
+
+    /// Evaluate `circuit` as [`Evaluator::eval_circuit`] does, returning its
+    /// outputs divided into its output groups.
+    ///
+    /// See [`CircuitType::output_widths`](crate::circuit::CircuitType::output_widths).
+    pub fn eval_circuit_grouped<Circuit: EvaluableCircuit<Self>>(
+        &mut self,
+        circuit: &Circuit,
+        garbler_inputs: &[Wire],
+        evaluator_inputs: &[Wire],
+    ) -> Result<Vec<Vec<Modulus>>, TwopacError> {
+        let outputs = self.eval_circuit(circuit, garbler_inputs, evaluator_inputs)?;
+        Ok(circuit.group_outputs(&outputs))
+    }
+
+    /// Decode `outputs`, wires the parties computed without decoding them, as
+    /// with [`EvaluableCircuit::eval_output_wires`], and send their values to
+    /// the garbler, returning them.
+    ///
+    /// The garbler must call
+    /// [`Garbler::receive_outputs`](super::Garbler::receive_outputs) with its
+    /// wires of the same outputs, so that both parties learn them.
+    pub fn reveal_outputs(&mut self, outputs: &[Wire]) -> Result<Vec<Modulus>, TwopacError> {
+        self.check_open()?;
+        let started = Instant::now();
+        let values = outputs
+            .iter()
+            .map(|x| {
+                let val = self.evaluator.output(x)?;
+                Ok(val.expect("evaluator outputs always are Some(Modulus)"))
+            })
+            .collect::<Result<Vec<_>, TwopacError>>()?;
+        for val in values.iter() {
+            self.channel.write_bytes(&val.to_le_bytes())?;
+        }
+        self.channel.flush()?;
+        self.add_time(Phase::Eval, started);
+        Ok(values)
     }
 
+    /// Send the labels of `outputs`, wires the parties computed without
+    /// decoding them, to the garbler, without decoding them.
+    ///
+    /// The garbler must call
+    /// [`Garbler::receive_output_labels`](super::Garbler::receive_output_labels)
+    /// with its wires of the same outputs, so that it learns them and the
+    /// evaluator learns nothing. Fails with [`TwopacError::Security`] if the
+    /// labels are wider than the block each is sent as.
+    pub fn send_output_labels(&mut self, outputs: &[Wire]) -> Result<(), TwopacError> {
+        self.check_open()?;
+        self.config.security.check_label_bits(128)?;
+        let started = Instant::now();
+        for x in outputs {
+            self.channel.write_block(&x.as_block())?;
+        }
+        self.channel.flush()?;
+        self.add_time(Phase::Eval, started);
+        Ok(())
+    }
+
+    /// Run `circuit` on the garbler's inputs and the evaluator's `inputs`, as
+    /// an execution of its own, returning its outputs.
+    ///
+    /// The garbler must call [`Garbler::execute`](super::Garbler::execute)
+    /// with the same circuit, which garbles it with fresh deltas and wire
+    /// labels while the oblivious transfer set up by earlier executions is
+    /// reused. Fails with [`TwopacError::WrongOutputMode`] before anything is
+    /// sent if the output mode is [`OutputMode::Garbler`].
+    pub fn execute<Circuit: EvaluableCircuit<Self>>(
+        &mut self,
+        circuit: &Circuit,
+        inputs: &[Modulus],
+    ) -> Result<Vec<Modulus>, TwopacError> {
+        self.check_open()?;
+        if self.config.output_mode == OutputMode::Garbler {
+            return Err(TwopacError::WrongOutputMode);
+        }
+        self.evaluator = Ev::with_security(self.channel.clone(), self.config.security);
+        let xs = self.receive_many(&input_moduli(circuit.get_garbler_input_refs()))?;
+        let ys = self.encode_many(inputs, &input_moduli(circuit.get_evaluator_input_refs()))?;
+        self.eval_circuit(circuit, &xs, &ys)
+    }
+
+    /// Receive `circuit` garbled by the garbler before the inputs are known,
+    /// returning a handle that evaluates it once they are.
+    ///
+    /// See [`Garbler::preprocess`](super::Garbler::preprocess), which the
+    /// garbler must call with the same circuit. The evaluator receives a
+    /// random label of each of its inputs, for a random choice bit, so that
+    /// [`EvaluatorHandle::online`] sends no more than a bit of each input.
+    /// Fails with [`EvaluatorError::MismatchedCircuit`] if the garbler
+    /// garbled another circuit, with [`TwopacError::WrongOutputMode`] if the
+    /// output mode is [`OutputMode::Garbler`], and with
+    /// [`TwopacError::Security`] if the labels are wider than the block each
+    /// is sent as.
+    pub fn preprocess<'a, Circuit: EvaluableCircuit<classic::Ev<Wire>>>(
+        &'a mut self,
+        circuit: &'a Circuit,
+    ) -> Result<EvaluatorHandle<'a, C, RNG, OT, Wire, Circuit>, TwopacError> {
+        self.check_open()?;
+        self.config.security.check_label_bits(128)?;
+        if self.config.output_mode == OutputMode::Garbler {
+            return Err(TwopacError::WrongOutputMode);
+        }
+        let moduli = input_moduli(circuit.get_evaluator_input_refs());
+        if moduli.iter().any(|&q| q != 2) {
+            return Err(FancyError::ArgNotBinary.into());
+        }
+        let started = Instant::now();
+        let len = self.channel.read_u64()?;
+        let mut bytes = vec![0; len as usize];
+        self.channel.read_bytes(&mut bytes)?;
+        let gc = GarbledCircuit::<Wire, Circuit>::from_bytes(&bytes).map_err(|e| {
+            EvaluatorError::CommunicationError(format!("invalid garbled circuit: {}", e))
+        })?;
+        let context = gc.evaluator(circuit)?;
+        self.add_time(Phase::Eval, started);
+        let started = Instant::now();
+        let choices = (0..moduli.len())
+            .map(|_| self.rng.gen())
+            .collect::<Vec<bool>>();
+        let masks = if choices.is_empty() {
+            Vec::new()
+        } else {
+            self.run_ots(&choices)?
+        };
+        self.add_time(Phase::Input, started);
+        let gates = self.stats.as_ref().map(|_| circuit.stats().ngates());
+        Ok(EvaluatorHandle {
+            evaluator: self,
+            context,
+            garbling: gc.garbling(),
+            garbler_moduli: input_moduli(circuit.get_garbler_input_refs()),
+            choices,
+            masks: masks.into_iter().map(|b| Wire::from_block(b, 2)).collect(),
+            gates,
+        })
+    }
+
+    /// Encode public inputs, whose values both parties know, as wires.
+    ///
+    /// See [`Garbler::encode_public`](super::Garbler::encode_public), which
+    /// the garbler must call with the same values and moduli.
+    pub fn encode_public(
+        &mut self,
+        values: &[Modulus],
+        moduli: &[Modulus],
+    ) -> Result<Vec<Wire>, TwopacError> {
+        self.check_open()?;
+        let started = Instant::now();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!(
+            "encode_inputs",
+            party = "evaluator",
+            owner = "public",
+            ninputs = values.len()
+        )
+        .entered();
+        let digest = super::public_digest(values, moduli)?;
+        self.channel.write_bytes(&digest)?;
+        self.channel.flush()?;
+        let mut theirs = [0; 32];
+        self.channel.read_bytes(&mut theirs)?;
+        let seed = self.channel.read_block()?;
+        if theirs != digest {
+            return Err(TwopacError::PublicInputMismatch);
+        }
+        let mut labels = AesRng::from_seed(seed);
+        let wires = moduli
+            .iter()
+            .map(|&q| self.evaluator.truncate(Wire::rand(&mut labels, q)))
+            .collect();
+        self.add_time(Phase::Input, started);
+        Ok(wires)
+    }
+
+    /// Call `hooks` after `done` gates of `total`, and learn whether the
+    /// garbler goes on.
+    fn checkpoint(&mut self, hooks: &Hooks, done: usize, total: usize) -> Result<(), TwopacError> {
+        if hooks.checkpoint(done, total) {
+            return Err(TwopacError::Cancelled { gates_done: done });
+        }
+        match self.channel.read_u8()? {
+            CHECKPOINT_CONTINUE => Ok(()),
+            CHECKPOINT_CANCEL => Err(TwopacError::PeerCancelled { gates_done: done }),
+            b => Err(EvaluatorError::CommunicationError(format!(
+                "expected a checkpoint after {} gates but read {:#04x}; do both parties \
+                 have hooks of the same interval?",
+                done, b
+            ))
+            .into()),
+        }
+    }
+
+    /// Run the OT receiver on `inputs`, in chunks if the configuration says so.
+    fn run_ots(&mut self, inputs: &[bool]) -> Result<Vec<Block>, TwopacError> {
+        let chunk_size = self.config.ot_chunk_size.unwrap_or(inputs.len());
+        let mut blocks = Vec::with_capacity(inputs.len());
+        for chunk in inputs.chunks(chunk_size) {
+            blocks.extend(self.run_ot(chunk)?);
+        }
+        Ok(blocks)
+    }
+
+    /// Run the OT receiver on `inputs`, initializing OT first if necessary.
     fn run_ot(&mut self, inputs: &[bool]) -> Result<Vec<Block>, TwopacError> {
-        self.ot
-            .receive(&mut self.channel, &inputs, &mut self.rng)
-            .map_err(TwopacError::from)
+        let ot = match self.ot {
+            Some(ref mut ot) => ot,
+            None => {
+                #[cfg(feature = "tracing")]
+                let _span = tracing::info_span!("base_ot", party = "evaluator").entered();
+                let mut ot = OT::init(&mut self.channel, &mut self.rng)?;
+                ot.set_statistical_security(self.config.security.statistical_bits());
+                self.ot.insert(ot)
+            }
+        };
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::info_span!("ot_extension", party = "evaluator", nots = inputs.len()).entered();
+        let blocks = ot.receive(&mut self.channel, inputs, &mut self.rng)?;
+        if let Some(stats) = self.stats.as_mut() {
+            stats.ots += inputs.len();
+            stats.ot_batches += 1;
+        }
+        Ok(blocks)
     }
 }
 
@@ -58,73 +640,167 @@ impl<
     type Error = TwopacError;
 
     /// Receive a garbler input wire.
-    fn receive(&mut self, modulus: u16) -> Result<Wire, TwopacError> {
+    fn receive(&mut self, modulus: Modulus) -> Result<Wire, TwopacError> {
+        self.check_open()?;
+        let started = Instant::now();
         let w = self.evaluator.read_wire(modulus)?;
+        self.add_time(Phase::Input, started);
         Ok(w)
     }
 
     /// Receive garbler input wires.
-    fn receive_many(&mut self, moduli: &[u16]) -> Result<Vec<Wire>, TwopacError> {
+    fn receive_many(&mut self, moduli: &[Modulus]) -> Result<Vec<Wire>, TwopacError> {
+        self.check_open()?;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!(
+            "encode_inputs",
+            party = "evaluator",
+            owner = "garbler",
+            ninputs = moduli.len(),
+            bytes = moduli.len() * std::mem::size_of::<Block>()
+        )
+        .entered();
         moduli.iter().map(|q| self.receive(*q)).collect()
     }
 
     /// Perform OT and obtain wires for the evaluator's inputs.
-    fn encode_many(&mut self, inputs: &[u16], moduli: &[u16]) -> Result<Vec<Wire>, TwopacError> {
+    fn encode_many(
+        &mut self,
+        inputs: &[Modulus],
+        moduli: &[Modulus],
+    ) -> Result<Vec<Wire>, TwopacError> {
+        self.check_open()?;
+        let started = Instant::now();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!(
+            "encode_inputs",
+            party = "evaluator",
+            owner = "evaluator",
+            ninputs = inputs.len()
+        )
+        .entered();
+        if inputs.len() != moduli.len() {
+            return Err(TwopacError::from(FancyError::InvalidArgNum {
+                got: inputs.len(),
+                needed: moduli.len(),
+            }));
+        }
+        check_inputs(inputs, moduli.iter().copied())?;
+        // Each bit takes an oblivious transfer per 128 bits of its labels.
+        let lanes = self.config.security.label_blocks();
         let mut lens = Vec::new();
         let mut bs = Vec::new();
         for (x, q) in inputs.iter().zip(moduli.iter()) {
-            let len = f32::from(*q).log(2.0).ceil() as usize;
+            let len = f64::from(*q).log2().ceil() as usize;
             for b in (0..len).map(|i| x & (1 << i) != 0) {
-                bs.push(b);
+                bs.extend(std::iter::repeat(b).take(lanes));
             }
-            lens.push(len);
+            lens.push(len * lanes);
         }
-        let wires = self.run_ot(&bs)?;
+        let wires = if bs.is_empty() {
+            Vec::new()
+        } else {
+            self.run_ots(&bs)?
+        };
         let mut start = 0;
-        Ok(lens
+        let wires = lens
             .into_iter()
             .zip(moduli.iter())
             .map(|(len, q)| {
                 let range = start..start + len;
                 let chunk = &wires[range];
                 start += len;
-                combine(chunk, *q)
+                combine(chunk, lanes, *q)
             })
-            .collect::<Vec<Wire>>())
+            .collect::<Vec<Wire>>();
+        self.add_time(Phase::Input, started);
+        Ok(wires)
     }
 }
 
-fn combine<Wire: WireLabel>(wires: &[Block], q: u16) -> Wire {
-    wires.iter().enumerate().fold(Wire::zero(q), |acc, (i, w)| {
-        let w = Wire::from_block(*w, q);
-        acc.plus(&w.cmul(1 << i))
-    })
+/// Combine the labels of the bits of an input of modulus `q`, each received
+/// as `lanes` blocks, into its wire.
+fn combine<Wire: WireLabel>(wires: &[Block], lanes: usize, q: Modulus) -> Wire {
+    wires
+        .chunks(lanes)
+        .enumerate()
+        .fold(Wire::zero(q), |acc, (i, w)| {
+            let mut label = [Block::default(); 4];
+            label[..lanes].copy_from_slice(w);
+            let w = Wire::from_block512(Block512::from(label), q);
+            acc.plus(&w.cmul(1 << i))
+        })
 }
 
 impl<C: AbstractChannel, RNG, OT> FancyBinary for Evaluator<C, RNG, OT, WireMod2> {
     fn and(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
+        self.evaluator.and(x, y).map_err(Self::Error::from)
+    }
+
+    fn xor(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
+        self.evaluator.xor(x, y).map_err(Self::Error::from)
+    }
+
+    fn negate(&mut self, x: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
+        self.evaluator.negate(x).map_err(Self::Error::from)
+    }
+
+    fn custom(
+        &mut self,
+        gate: &Arc<dyn CustomGate>,
+        inputs: &[Self::Item],
+    ) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
+        self.evaluator
+            .custom(gate, inputs)
+            .map_err(Self::Error::from)
+    }
+}
+
+impl<C: AbstractChannel, RNG, OT> FancyBinary for Evaluator<C, RNG, OT, WideWireMod2> {
+    fn and(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.evaluator.and(x, y).map_err(Self::Error::from)
     }
 
     fn xor(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.evaluator.xor(x, y).map_err(Self::Error::from)
     }
 
     fn negate(&mut self, x: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.evaluator.negate(x).map_err(Self::Error::from)
     }
+
+    fn custom(
+        &mut self,
+        gate: &Arc<dyn CustomGate>,
+        inputs: &[Self::Item],
+    ) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
+        self.evaluator
+            .custom(gate, inputs)
+            .map_err(Self::Error::from)
+    }
 }
 
 impl<C: AbstractChannel, RNG, OT> FancyBinary for Evaluator<C, RNG, OT, AllWire> {
     fn and(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.evaluator.and(x, y).map_err(Self::Error::from)
     }
 
     fn xor(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.evaluator.xor(x, y).map_err(Self::Error::from)
     }
 
     fn negate(&mut self, x: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.evaluator.negate(x).map_err(Self::Error::from)
     }
 }
@@ -133,22 +809,32 @@ impl<C: AbstractChannel, RNG, OT, Wire: WireLabel + ArithmeticWire> FancyArithme
     for Evaluator<C, RNG, OT, Wire>
 {
     fn add(&mut self, x: &Wire, y: &Wire) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.evaluator.add(&x, &y).map_err(Self::Error::from)
     }
 
     fn sub(&mut self, x: &Wire, y: &Wire) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.evaluator.sub(&x, &y).map_err(Self::Error::from)
     }
 
-    fn cmul(&mut self, x: &Wire, c: u16) -> Result<Self::Item, Self::Error> {
+    fn cmul(&mut self, x: &Wire, c: Modulus) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.evaluator.cmul(&x, c).map_err(Self::Error::from)
     }
 
     fn mul(&mut self, x: &Wire, y: &Wire) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.evaluator.mul(&x, &y).map_err(Self::Error::from)
     }
 
-    fn proj(&mut self, x: &Wire, q: u16, tt: Option<Vec<u16>>) -> Result<Self::Item, Self::Error> {
+    fn proj(
+        &mut self,
+        x: &Wire,
+        q: Modulus,
+        tt: Option<Vec<Modulus>>,
+    ) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.evaluator.proj(&x, q, tt).map_err(Self::Error::from)
     }
 }
@@ -157,11 +843,17 @@ impl<C: AbstractChannel, RNG, OT, Wire: WireLabel> Fancy for Evaluator<C, RNG, O
     type Item = Wire;
     type Error = TwopacError;
 
-    fn constant(&mut self, x: u16, q: u16) -> Result<Self::Item, Self::Error> {
+    fn constant(&mut self, x: Modulus, q: Modulus) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.evaluator.constant(x, q).map_err(Self::Error::from)
     }
 
-    fn output(&mut self, x: &Wire) -> Result<Option<u16>, Self::Error> {
+    fn output(&mut self, x: &Wire) -> Result<Option<Modulus>, Self::Error> {
+        self.check_open()?;
+        if self.config.output_mode == OutputMode::Garbler {
+            self.pending_outputs.push(x.clone());
+            return Ok(None);
+        }
         self.evaluator.output(&x).map_err(Self::Error::from)
     }
 }
@@ -169,9 +861,98 @@ impl<C: AbstractChannel, RNG, OT, Wire: WireLabel> Fancy for Evaluator<C, RNG, O
 impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT, Wire: WireLabel> FancyReveal
     for Evaluator<C, RNG, OT, Wire>
 {
-    fn reveal(&mut self, x: &Self::Item) -> Result<u16, Self::Error> {
+    fn reveal(&mut self, x: &Self::Item) -> Result<Modulus, Self::Error> {
+        self.check_open()?;
         self.evaluator.reveal(x).map_err(Self::Error::from)
     }
 }
 
+/// A garbled circuit received by [`Evaluator::preprocess`], waiting for the
+/// inputs.
+///
+/// The handle holds the garbled circuit and the labels of the oblivious
+/// transfers, and borrows the evaluator and the circuit until it is run.
+pub struct EvaluatorHandle<'a, C, RNG, OT, Wire, Circuit> {
+    evaluator: &'a mut Evaluator<C, RNG, OT, Wire>,
+    context: GcEvalContext<'a, Wire, Circuit>,
+    garbling: GarblingId,
+    garbler_moduli: Vec<Modulus>,
+    choices: Vec<bool>,
+    masks: Vec<Wire>,
+    gates: Option<usize>,
+}
+
+impl<
+        C: AbstractChannel,
+        RNG,
+        OT,
+        Wire: WireLabel,
+        Circuit: EvaluableCircuit<classic::Ev<Wire>>,
+    > EvaluatorHandle<'_, C, RNG, OT, Wire, Circuit>
+{
+    /// Evaluate the preprocessed circuit on the garbler's inputs and the
+    /// evaluator's `inputs`, returning its outputs.
+    ///
+    /// The garbler must call
+    /// [`GarblerHandle::online`](super::GarblerHandle::online). The evaluator
+    /// sends each input masked with the choice bit of its oblivious transfer,
+    /// packed eight to a byte, and reads the labels of the garbler's inputs
+    /// and a correction of each of its own.
+    pub fn online(mut self, inputs: &[Modulus]) -> Result<Vec<Modulus>, TwopacError> {
+        let evaluator = self.evaluator;
+        evaluator.check_open()?;
+        let started = Instant::now();
+        if inputs.len() != self.masks.len() {
+            return Err(TwopacError::from(FancyError::InvalidArgNum {
+                got: inputs.len(),
+                needed: self.masks.len(),
+            }));
+        }
+        check_inputs(inputs, std::iter::repeat(2))?;
+        let mut bits = vec![0; (inputs.len() + 7) / 8];
+        for (i, (&x, &choice)) in inputs.iter().zip(self.choices.iter()).enumerate() {
+            if (x == 1) != choice {
+                bits[i / 8] |= 1 << (i % 8);
+            }
+        }
+        evaluator.channel.write_bytes(&bits)?;
+        evaluator.channel.flush()?;
+        let xs = self
+            .garbler_moduli
+            .iter()
+            .map(|&q| Ok(Wire::from_block(evaluator.channel.read_block()?, q)))
+            .collect::<Result<Vec<_>, TwopacError>>()?;
+        let ys = self
+            .masks
+            .iter()
+            .map(|mask| Ok(mask.plus(&Wire::from_block(evaluator.channel.read_block()?, 2))))
+            .collect::<Result<Vec<_>, TwopacError>>()?;
+        evaluator.add_time(Phase::Input, started);
+        let started = Instant::now();
+        let outputs = self.context.eval(
+            &EncodedInputs::from_wires(self.garbling, xs),
+            &EncodedInputs::from_wires(self.garbling, ys),
+        )?;
+        if evaluator.config.output_mode == OutputMode::Both {
+            for val in outputs.iter() {
+                evaluator.channel.write_bytes(&val.to_le_bytes())?;
+            }
+            evaluator.channel.flush()?;
+        }
+        if let (Some(stats), Some(gates)) = (evaluator.stats.as_mut(), self.gates) {
+            stats.gates += gates;
+        }
+        evaluator.add_time(Phase::Eval, started);
+        Ok(outputs)
+    }
+}
+
 impl<C: AbstractChannel, RNG, OT, Wire> SemiHonest for Evaluator<C, RNG, OT, Wire> {}
+
+// With the `zeroize` feature, the OT and the RNG of an evaluator overwrite
+// themselves when it is dropped.
+#[cfg(feature = "zeroize")]
+impl<C, RNG: zeroize::ZeroizeOnDrop, OT: zeroize::ZeroizeOnDrop, Wire> zeroize::ZeroizeOnDrop
+    for Evaluator<C, RNG, OT, Wire>
+{
+}
@@ -1,28 +1,55 @@
+use super::{
+    config::{Config, OutputMode, Phase, TwopacStats},
+    counting::CountingChannel,
+    input_moduli, session, Party, CHECKPOINT_CANCEL, CHECKPOINT_CONTINUE,
+};
 use crate::{
-    errors::TwopacError, wire::WireLabel, AllWire, ArithmeticWire, Fancy, FancyArithmetic,
-    FancyBinary, FancyInput, FancyReveal, Garbler as Gb, WireMod2,
+    circuit::{CustomGate, EvaluableCircuit, Liveness},
+    classic::{self, Encoder},
+    errors::{FancyError, TwopacError},
+    fancy::check_inputs,
+    hooks::Hooks,
+    wire::WireLabel,
+    AllWire, ArithmeticWire, Fancy, FancyArithmetic, FancyBinary, FancyInput, FancyReveal,
+    Garbler as Gb, Modulus, WideWireMod2, WireMod2,
 };
 use ocelot::ot::Sender as OtSender;
 use rand::{CryptoRng, Rng, SeedableRng};
-use scuttlebutt::{AbstractChannel, Block, SemiHonest};
+use scuttlebutt::{AbstractChannel, AesRng, Block, SemiHonest};
+use std::{sync::Arc, time::Instant};
 
 /// Semi-honest garbler.
-pub struct Garbler<C, RNG, OT, Wire> {
-    garbler: Gb<C, RNG, Wire>,
-    channel: C,
-    ot: OT,
+///
+/// A `Garbler` is `Send` whenever its channel, RNG, and OT sender are, so a
+/// session can be started on one thread and finished on another. Of the
+/// `scuttlebutt` channels, `SyncChannel`, `UnixChannel`, and `TrackChannel` are
+/// `Send`; `Channel` is not, as it shares its reader and writer through `Rc`.
+pub struct Garbler<C, RNG, OT, Wire: WireLabel> {
+    garbler: Gb<CountingChannel<C>, RNG, Wire>,
+    channel: CountingChannel<C>,
+    ot: Option<OT>,
     rng: RNG,
+    config: Config,
+    stats: Option<TwopacStats>,
+    session_id: Option<Block>,
+    // The value of `garbler.bytes_written()` at the last flush.
+    flushed_at: usize,
+    // With `OutputMode::Garbler`, the zero labels of the outputs of the last
+    // circuit that are not constant, and the values of those that are.
+    output_zeros: Vec<Wire>,
+    output_constants: Vec<Option<Modulus>>,
+    closed: bool,
 }
 
-impl<C, OT, RNG, Wire> std::ops::Deref for Garbler<C, RNG, OT, Wire> {
-    type Target = Gb<C, RNG, Wire>;
+impl<C, OT, RNG, Wire: WireLabel> std::ops::Deref for Garbler<C, RNG, OT, Wire> {
+    type Target = Gb<CountingChannel<C>, RNG, Wire>;
     fn deref(&self) -> &Self::Target {
         &self.garbler
     }
 }
 
-impl<C, OT, RNG, Wire> std::ops::DerefMut for Garbler<C, RNG, OT, Wire> {
-    fn deref_mut(&mut self) -> &mut Gb<C, RNG, Wire> {
+impl<C, OT, RNG, Wire: WireLabel> std::ops::DerefMut for Garbler<C, RNG, OT, Wire> {
+    fn deref_mut(&mut self) -> &mut Gb<CountingChannel<C>, RNG, Wire> {
         &mut self.garbler
     }
 }
@@ -35,38 +62,673 @@ impl<
     > Garbler<C, RNG, OT, Wire>
 {
     /// Make a new `Garbler`.
-    pub fn new(mut channel: C, mut rng: RNG) -> Result<Self, TwopacError> {
-        let ot = OT::init(&mut channel, &mut rng)?;
+    ///
+    /// Oblivious transfer is initialized lazily, the first time the evaluator's
+    /// inputs are needed, so that the base OT messages share flights with the
+    /// input-encoding messages.
+    ///
+    /// The garbler first exchanges [`PROTOCOL_VERSION`](super::PROTOCOL_VERSION)
+    /// with the evaluator, so this blocks until the evaluator is made too, and
+    /// fails with [`TwopacError::VersionMismatch`] if the versions differ.
+    ///
+    /// This uses the default configuration; see
+    /// [`SemiHonest::builder`](super::SemiHonest::builder) for the others.
+    pub fn new(channel: C, rng: RNG) -> Result<Self, TwopacError> {
+        Self::with_config(channel, rng, Config::default())
+    }
 
-        let garbler = Gb::new(channel.clone(), RNG::from_seed(rng.gen()));
-        Ok(Garbler {
+    pub(super) fn with_config(channel: C, rng: RNG, config: Config) -> Result<Self, TwopacError> {
+        config.check_labels::<Wire>()?;
+        let started = Instant::now();
+        let mut channel = CountingChannel::new(channel, config.stats);
+        super::handshake(&mut channel)?;
+        Ok(Self::start(channel, rng, config, None, started))
+    }
+
+    pub(super) fn start(
+        channel: CountingChannel<C>,
+        mut rng: RNG,
+        config: Config,
+        session_id: Option<Block>,
+        started: Instant,
+    ) -> Self {
+        let seed = config.seed.unwrap_or_else(|| rng.gen());
+        let garbler = Gb::with_security(channel.clone(), RNG::from_seed(seed), config.security);
+        let stats = config.stats.then(|| TwopacStats {
+            session_id,
+            init_time: started.elapsed(),
+            ..TwopacStats::default()
+        });
+        Garbler {
             garbler,
             channel,
-            ot,
+            ot: None,
             rng,
+            config,
+            stats,
+            session_id,
+            flushed_at: 0,
+            output_zeros: Vec::new(),
+            output_constants: Vec::new(),
+            closed: false,
+        }
+    }
+
+    /// The OT of this garbler, once it has run one.
+    #[cfg(feature = "tokio")]
+    pub(super) fn ot_mut(&mut self) -> Option<&mut OT> {
+        self.ot.as_mut()
+    }
+
+    /// The session id tossed with the evaluator, if this garbler was built
+    /// with [`build_garbler_from_seed`](super::SemiHonestBuilder::build_garbler_from_seed).
+    pub fn session_id(&self) -> Option<Block> {
+        self.session_id
+    }
+
+    /// The counters kept since this garbler was made, as of now, if its
+    /// configuration enables them.
+    pub fn stats(&self) -> Option<TwopacStats> {
+        let stats = self.stats.as_ref()?;
+        Some(TwopacStats {
+            bytes_written: self.channel.bytes_written().unwrap_or_default(),
+            bytes_read: self.channel.bytes_read().unwrap_or_default(),
+            ..stats.clone()
         })
     }
 
     /// Get a reference to the internal channel.
+    ///
+    /// What is written to or read from it directly is not counted in the
+    /// [`stats`](Garbler::stats).
     pub fn get_channel(&mut self) -> &mut C {
-        &mut self.channel
+        self.channel.inner_mut()
     }
 
-    fn _evaluator_input(&mut self, delta: &Wire, q: u16) -> (Wire, Vec<(Block, Block)>) {
-        let len = f32::from(q).log(2.0).ceil() as u16;
-        let mut wire = Wire::zero(q);
-        let inputs = (0..len)
-            .map(|i| {
-                let zero = Wire::rand(&mut self.rng, q);
-                let one = zero.plus(&delta);
-                wire = wire.plus(&zero.cmul(1 << i));
-                (zero.as_block(), one.as_block())
+    /// End the session: flush the channel, and wipe the garbler's secrets.
+    ///
+    /// The deltas and the zero labels of the outputs are wiped, and the OT and
+    /// the RNGs the labels were drawn from are dropped; with the `zeroize` feature, all of them are
+    /// overwritten first. Every later use of the garbler fails with
+    /// [`TwopacError::Closed`], and closing it again does nothing. The
+    /// channel is closed when the garbler is dropped, and the wires it
+    /// returned are the caller's to wipe, with [`WireLabel::wipe`].
+    pub fn close(&mut self) -> Result<(), TwopacError> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+        let flushed = self.channel.flush();
+        self.garbler.wipe();
+        for zero in self.output_zeros.iter_mut() {
+            zero.wipe();
+        }
+        self.output_zeros.clear();
+        self.ot = None;
+        let seed = self.rng.gen::<Block>();
+        self.rng = RNG::from_seed(seed);
+        Ok(flushed?)
+    }
+
+    /// Whether the garbler has been [closed](Garbler::close).
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Garble `circuit` on the given input wires.
+    ///
+    /// The circuit is only borrowed, so a single copy of it (e.g., behind an
+    /// `Arc`) can be shared with an evaluator running in another thread.
+    ///
+    /// Returns the outputs as decoded by the evaluator if the output mode is
+    /// [`OutputMode::Both`], as decoded by the garbler from the labels the
+    /// evaluator sends if it is [`OutputMode::Garbler`], and `None`
+    /// otherwise. Fails with
+    /// [`TwopacError::Cancelled`] if the [hooks](super::SemiHonestBuilder::hooks)
+    /// cancel the run, once the evaluator has been told.
+    pub fn eval_circuit<Circuit: EvaluableCircuit<Self>>(
+        &mut self,
+        circuit: &Circuit,
+        garbler_inputs: &[Wire],
+        evaluator_inputs: &[Wire],
+    ) -> Result<Option<Vec<Modulus>>, TwopacError> {
+        self.eval_circuit_with_public(circuit, garbler_inputs, evaluator_inputs, &[])
+    }
+
+    /// Garble a circuit with public inputs as [`Garbler::eval_circuit`] does,
+    /// given the wires of the public inputs, as made by
+    /// [`Garbler::encode_public`].
+    pub fn eval_circuit_with_public<Circuit: EvaluableCircuit<Self>>(
+        &mut self,
+        circuit: &Circuit,
+        garbler_inputs: &[Wire],
+        evaluator_inputs: &[Wire],
+        public_inputs: &[Wire],
+    ) -> Result<Option<Vec<Modulus>>, TwopacError> {
+        self.run_circuit(
+            circuit,
+            garbler_inputs,
+            evaluator_inputs,
+            public_inputs,
+            None,
+        )
+    }
+
+    /// Garble a circuit as [`Garbler::eval_circuit_with_public`] does,
+    /// holding only the values of live wires if given the `liveness` of the
+    /// circuit, and no hooks, public inputs, or streamed outputs need the
+    /// other evaluations.
+    pub(super) fn run_circuit<Circuit: EvaluableCircuit<Self>>(
+        &mut self,
+        circuit: &Circuit,
+        garbler_inputs: &[Wire],
+        evaluator_inputs: &[Wire],
+        public_inputs: &[Wire],
+        liveness: Option<&Liveness>,
+    ) -> Result<Option<Vec<Modulus>>, TwopacError> {
+        self.check_open()?;
+        let started = Instant::now();
+        if self.config.output_mode == OutputMode::Garbler {
+            self.output_zeros.clear();
+            self.output_constants = circuit.constant_outputs();
+        }
+        {
+            #[cfg(feature = "tracing")]
+            let _span = tracing::info_span!("stream_gates", party = "garbler").entered();
+            match self.config.hooks.clone() {
+                None if self.config.stream_outputs => {
+                    circuit.eval_streaming(
+                        self,
+                        garbler_inputs,
+                        evaluator_inputs,
+                        public_inputs,
+                        None,
+                        &mut |_, _| {},
+                    )?;
+                }
+                None => match liveness {
+                    Some(liveness) if public_inputs.is_empty() => {
+                        circuit.eval_bounded(self, garbler_inputs, evaluator_inputs, liveness)?;
+                    }
+                    _ => {
+                        circuit.eval_with_public(
+                            self,
+                            garbler_inputs,
+                            evaluator_inputs,
+                            public_inputs,
+                        )?;
+                    }
+                },
+                Some(hooks) if self.config.stream_outputs => {
+                    let total = circuit.get_gates().len();
+                    circuit.eval_streaming(
+                        self,
+                        garbler_inputs,
+                        evaluator_inputs,
+                        public_inputs,
+                        Some((hooks.interval(), &mut |gb, done| {
+                            gb.checkpoint(&hooks, done, total)
+                        })),
+                        &mut |_, _| {},
+                    )?;
+                    hooks.progress(total, total);
+                }
+                Some(hooks) => {
+                    let total = circuit.get_gates().len();
+                    circuit.eval_with_checkpoints(
+                        self,
+                        garbler_inputs,
+                        evaluator_inputs,
+                        public_inputs,
+                        hooks.interval(),
+                        &mut |gb, done| gb.checkpoint(&hooks, done, total),
+                    )?;
+                    hooks.progress(total, total);
+                }
+            }
+        }
+        let outputs = match self.config.output_mode {
+            OutputMode::Evaluator => None,
+            OutputMode::Both => {
+                self.channel.flush()?;
+                let mut outputs = Vec::with_capacity(circuit.noutputs());
+                for _ in 0..circuit.noutputs() {
+                    let mut val = [0; std::mem::size_of::<Modulus>()];
+                    self.channel.read_bytes(&mut val)?;
+                    outputs.push(Modulus::from_le_bytes(val));
+                }
+                Some(outputs)
+            }
+            OutputMode::Garbler => {
+                let zeros = self.output_zeros.clone();
+                let values = self.read_output_labels(&zeros)?;
+                Some(self.with_constant_outputs(values))
+            }
+        };
+        self.add_time(Phase::Eval, started);
+        Ok(outputs)
+    }
+
+    /// Garble `circuit` as [`Garbler::eval_circuit`] does, returning the
+    /// outputs, if the evaluator shares them, divided into the output groups
+    /// of the circuit.
+    ///
+    /// See [`CircuitType::output_widths`](crate::circuit::CircuitType::output_widths).
+    pub fn eval_circuit_grouped<Circuit: EvaluableCircuit<Self>>(
+        &mut self,
+        circuit: &Circuit,
+        garbler_inputs: &[Wire],
+        evaluator_inputs: &[Wire],
+    ) -> Result<Option<Vec<Vec<Modulus>>>, TwopacError> {
+        let outputs = self.eval_circuit(circuit, garbler_inputs, evaluator_inputs)?;
+        Ok(outputs.map(|outputs| circuit.group_outputs(&outputs)))
+    }
+
+    /// Send the evaluator what it needs to decode `outputs`, wires the parties
+    /// computed without decoding them, as with
+    /// [`EvaluableCircuit::eval_output_wires`], and return their values once
+    /// it has sent them back.
+    ///
+    /// The evaluator must call
+    /// [`Evaluator::reveal_outputs`](super::Evaluator::reveal_outputs) with
+    /// its wires of the same outputs, so that both parties learn them.
+    pub fn receive_outputs(&mut self, outputs: &[Wire]) -> Result<Vec<Modulus>, TwopacError> {
+        self.check_open()?;
+        let started = Instant::now();
+        for x in outputs {
+            self.garbler.output(x)?;
+        }
+        self.channel.flush()?;
+        self.flushed_at = self.garbler.bytes_written();
+        let mut values = Vec::with_capacity(outputs.len());
+        for _ in outputs {
+            let mut val = [0; std::mem::size_of::<Modulus>()];
+            self.channel.read_bytes(&mut val)?;
+            values.push(Modulus::from_le_bytes(val));
+        }
+        self.add_time(Phase::Eval, started);
+        Ok(values)
+    }
+
+    /// Receive the evaluator's labels of `outputs`, wires the parties computed
+    /// without decoding them, and decode them.
+    ///
+    /// The evaluator must call
+    /// [`Evaluator::send_output_labels`](super::Evaluator::send_output_labels)
+    /// with its wires of the same outputs, so that the garbler learns them and
+    /// the evaluator learns nothing. Fails with
+    /// [`TwopacError::InvalidOutputLabel`] if a label is none of those of its
+    /// output, and with [`TwopacError::Security`] if the labels are wider
+    /// than the block each is sent as.
+    pub fn receive_output_labels(&mut self, outputs: &[Wire]) -> Result<Vec<Modulus>, TwopacError> {
+        self.check_open()?;
+        self.config.security.check_label_bits(128)?;
+        let started = Instant::now();
+        let values = self.read_output_labels(outputs)?;
+        self.add_time(Phase::Eval, started);
+        Ok(values)
+    }
+
+    /// Decode `labels`, the evaluator's labels of the outputs of the last
+    /// circuit garbled with the output mode [`OutputMode::Garbler`], one for
+    /// each output that is not constant, returning the values of all the
+    /// outputs.
+    ///
+    /// [`Garbler::eval_circuit`] receives and decodes the labels the
+    /// evaluator sends as [`Garbler::receive_output_labels`] does; call this
+    /// for labels that reach the garbler otherwise. Fails with
+    /// [`TwopacError::InvalidOutputLabel`] if a label is none of those of its
+    /// output, and with [`TwopacError::WrongOutputMode`] in the other output
+    /// modes.
+    pub fn decode_outputs(&mut self, labels: &[Block]) -> Result<Vec<Modulus>, TwopacError> {
+        self.check_open()?;
+        if self.config.output_mode != OutputMode::Garbler {
+            return Err(TwopacError::WrongOutputMode);
+        }
+        if labels.len() != self.output_zeros.len() {
+            return Err(FancyError::InvalidArgNum {
+                got: labels.len(),
+                needed: self.output_zeros.len(),
+            }
+            .into());
+        }
+        let zeros = self.output_zeros.clone();
+        let values = self.decode_labels(&zeros, labels)?;
+        Ok(self.with_constant_outputs(values))
+    }
+
+    /// Flush the channel, and receive and decode the evaluator's labels of
+    /// `outputs`.
+    fn read_output_labels(&mut self, outputs: &[Wire]) -> Result<Vec<Modulus>, TwopacError> {
+        self.channel.flush()?;
+        self.flushed_at = self.garbler.bytes_written();
+        let labels = outputs
+            .iter()
+            .map(|_| self.channel.read_block())
+            .collect::<Result<Vec<_>, _>>()?;
+        self.decode_labels(outputs, &labels)
+    }
+
+    /// Decode the evaluator's `labels` of the outputs whose zero labels are
+    /// `zeros`.
+    fn decode_labels(
+        &mut self,
+        zeros: &[Wire],
+        labels: &[Block],
+    ) -> Result<Vec<Modulus>, TwopacError> {
+        zeros
+            .iter()
+            .zip(labels)
+            .enumerate()
+            .map(|(index, (zero, &label))| self.decode_label(zero, label, index))
+            .collect()
+    }
+
+    /// Put the constant outputs of the last circuit garbled with the output
+    /// mode [`OutputMode::Garbler`] among `values`, those of its other
+    /// outputs.
+    fn with_constant_outputs(&self, values: Vec<Modulus>) -> Vec<Modulus> {
+        let mut values = values.into_iter();
+        self.output_constants
+            .iter()
+            .map(|constant| {
+                constant.unwrap_or_else(|| {
+                    values
+                        .next()
+                        .expect("one value per output that is not constant")
+                })
             })
-            .collect::<Vec<(Block, Block)>>();
+            .collect()
+    }
+
+    /// Decode the evaluator's `label` of output `index`, whose zero label is
+    /// `zero`.
+    fn decode_label(
+        &mut self,
+        zero: &Wire,
+        label: Block,
+        index: usize,
+    ) -> Result<Modulus, TwopacError> {
+        let q = zero.modulus();
+        let delta = self.garbler.delta(q);
+        (0..q)
+            .find(|&k| zero.plus(&delta.cmul(k)).as_block() == label)
+            .ok_or(TwopacError::InvalidOutputLabel { index })
+    }
+
+    /// Run `circuit` on the garbler's `inputs` and the evaluator's, as an
+    /// execution of its own, returning what [`Garbler::eval_circuit`] does.
+    ///
+    /// The evaluator must call
+    /// [`Evaluator::execute`](super::Evaluator::execute) with the same
+    /// circuit. Each execution garbles with fresh deltas and wire labels,
+    /// drawn from a seed of the garbler's RNG, so that no wire of another
+    /// one can be used in it, but reuses the oblivious transfer: only the
+    /// first execution of a garbler pays for the base OTs, and the others
+    /// for the garbling and the OTs of the evaluator's inputs alone. Fails
+    /// with [`TwopacError::WrongOutputMode`] before anything is sent if the
+    /// output mode is [`OutputMode::Garbler`].
+    pub fn execute<Circuit: EvaluableCircuit<Self>>(
+        &mut self,
+        circuit: &Circuit,
+        inputs: &[Modulus],
+    ) -> Result<Option<Vec<Modulus>>, TwopacError> {
+        self.check_open()?;
+        if self.config.output_mode == OutputMode::Garbler {
+            return Err(TwopacError::WrongOutputMode);
+        }
+        self.garbler.wipe();
+        let seed = self.rng.gen();
+        self.garbler = Gb::with_security(
+            self.channel.clone(),
+            RNG::from_seed(seed),
+            self.config.security,
+        );
+        self.flushed_at = 0;
+        let xs = self.encode_many(inputs, &input_moduli(circuit.get_garbler_input_refs()))?;
+        let ys = self.receive_many(&input_moduli(circuit.get_evaluator_input_refs()))?;
+        let outputs = self.eval_circuit(circuit, &xs, &ys)?;
+        self.channel.flush()?;
+        Ok(outputs)
+    }
+
+    /// Garble `circuit` and send it to the evaluator before the inputs are
+    /// known, returning a handle that runs it once they are.
+    ///
+    /// The evaluator must call
+    /// [`Evaluator::preprocess`](super::Evaluator::preprocess) with the same
+    /// circuit. The circuit is garbled whole, with fresh deltas and wire
+    /// labels, and the garbler runs an oblivious transfer of random labels
+    /// for each evaluator input, so that [`GarblerHandle::online`] sends no
+    /// more than a label of each input. Fails with [`FancyError::ArgNotBinary`]
+    /// before sending anything if an evaluator input is not binary, and with
+    /// [`TwopacError::WrongOutputMode`] if the output mode is
+    /// [`OutputMode::Garbler`], as the garbled circuit decodes its outputs,
+    /// and with [`TwopacError::Security`] if the labels are wider than the
+    /// block each is sent as.
+    pub fn preprocess<Circuit: EvaluableCircuit<classic::Gb<Wire>>>(
+        &mut self,
+        circuit: &Circuit,
+    ) -> Result<GarblerHandle<'_, C, RNG, OT, Wire>, TwopacError> {
+        self.check_open()?;
+        self.config.security.check_label_bits(128)?;
+        if self.config.output_mode == OutputMode::Garbler {
+            return Err(TwopacError::WrongOutputMode);
+        }
+        let moduli = input_moduli(circuit.get_evaluator_input_refs());
+        if moduli.iter().any(|&q| q != 2) {
+            return Err(FancyError::ArgNotBinary.into());
+        }
+        let started = Instant::now();
+        let seed = self.rng.gen();
+        let (encoder, gc) = classic::garble_with_security::<Wire, _>(
+            circuit,
+            AesRng::from_seed(seed),
+            self.config.security,
+        )?;
+        let bytes = gc.to_bytes();
+        self.channel.write_u64(bytes.len() as u64)?;
+        self.channel.write_bytes(&bytes)?;
+        if let Some(stats) = self.stats.as_mut() {
+            stats.gates += circuit.stats().ngates();
+        }
+        self.add_time(Phase::Eval, started);
+        let started = Instant::now();
+        let mut masks = Vec::with_capacity(moduli.len());
+        let mut pairs = Vec::with_capacity(moduli.len());
+        for i in 0..moduli.len() {
+            let zero = encoder.encode_evaluator_input(0, i)?;
+            let delta = encoder.encode_evaluator_input(1, i)?.minus(&zero);
+            let mask = Wire::rand(&mut self.rng, 2);
+            pairs.push((mask.as_block(), mask.plus(&delta).as_block()));
+            masks.push(mask);
+        }
+        if !pairs.is_empty() {
+            self.run_ots(&pairs)?;
+        }
+        self.channel.flush()?;
+        self.add_time(Phase::Input, started);
+        Ok(GarblerHandle {
+            garbler: self,
+            encoder,
+            masks,
+            noutputs: circuit.noutputs(),
+        })
+    }
+
+    /// Encode public inputs, whose values both parties know, as wires.
+    ///
+    /// The evaluator must call
+    /// [`Evaluator::encode_public`](super::Evaluator::encode_public) with the
+    /// same values and moduli. The parties compare a hash of them, failing
+    /// with [`TwopacError::PublicInputMismatch`] if they differ, and the
+    /// evaluator's labels are drawn from a seed the garbler sends, so public
+    /// inputs need neither oblivious transfer nor a label per wire.
+    pub fn encode_public(
+        &mut self,
+        values: &[Modulus],
+        moduli: &[Modulus],
+    ) -> Result<Vec<Wire>, TwopacError> {
+        self.check_open()?;
+        let started = Instant::now();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!(
+            "encode_inputs",
+            party = "garbler",
+            owner = "public",
+            ninputs = values.len()
+        )
+        .entered();
+        let digest = super::public_digest(values, moduli)?;
+        let seed = self.rng.gen::<Block>();
+        self.channel.write_bytes(&digest)?;
+        self.channel.write_block(&seed)?;
+        self.channel.flush()?;
+        let mut theirs = [0; 32];
+        self.channel.read_bytes(&mut theirs)?;
+        if theirs != digest {
+            return Err(TwopacError::PublicInputMismatch);
+        }
+        // The evaluator's label of each input is drawn from the seed, so the
+        // zero label is that less the value times delta.
+        let mut labels = AesRng::from_seed(seed);
+        let wires = values
+            .iter()
+            .zip(moduli)
+            .map(|(&x, &q)| {
+                let label = self.garbler.truncate(Wire::rand(&mut labels, q));
+                label.minus(&self.garbler.delta(q).cmul(x))
+            })
+            .collect();
+        self.add_time(Phase::Input, started);
+        Ok(wires)
+    }
+
+    /// Run the OT sender on `inputs`, in chunks if the configuration says so.
+    fn run_ots(&mut self, inputs: &[(Block, Block)]) -> Result<(), TwopacError> {
+        let chunk_size = self.config.ot_chunk_size.unwrap_or(inputs.len());
+        for chunk in inputs.chunks(chunk_size) {
+            self.run_ot(chunk)?;
+        }
+        Ok(())
+    }
+
+    /// Run the OT sender on `inputs`, initializing OT first if necessary.
+    fn run_ot(&mut self, inputs: &[(Block, Block)]) -> Result<(), TwopacError> {
+        let ot = match self.ot {
+            Some(ref mut ot) => ot,
+            None => {
+                #[cfg(feature = "tracing")]
+                let _span = tracing::info_span!("base_ot", party = "garbler").entered();
+                let mut ot = OT::init(&mut self.channel, &mut self.rng)?;
+                ot.set_statistical_security(self.config.security.statistical_bits());
+                self.ot.insert(ot)
+            }
+        };
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::info_span!("ot_extension", party = "garbler", nots = inputs.len()).entered();
+        ot.send(&mut self.channel, inputs, &mut self.rng)?;
+        if let Some(stats) = self.stats.as_mut() {
+            stats.ots += inputs.len();
+            stats.ot_batches += 1;
+        }
+        Ok(())
+    }
+
+    /// Draw the labels of an evaluator input of modulus `q`, returning its
+    /// zero wire and the pairs of blocks of each bit to transfer: one pair
+    /// per 128 bits of the labels.
+    fn _evaluator_input(&mut self, delta: &Wire, q: Modulus) -> (Wire, Vec<(Block, Block)>) {
+        let len = f64::from(q).log2().ceil() as Modulus;
+        let lanes = self.config.security.label_blocks();
+        let mut wire = Wire::zero(q);
+        let mut inputs = Vec::with_capacity(len as usize * lanes);
+        for i in 0..len {
+            let zero = Wire::rand(&mut self.rng, q);
+            let zero = self.garbler.truncate(zero);
+            let one = zero.plus(delta);
+            wire = wire.plus(&zero.cmul(1 << i));
+            let zero = <[Block; 4]>::from(zero.as_block512());
+            let one = <[Block; 4]>::from(one.as_block512());
+            inputs.extend(zero.into_iter().zip(one).take(lanes));
+        }
         (wire, inputs)
     }
 }
 
+impl<C: AbstractChannel, OT: OtSender<Msg = Block> + SemiHonest, Wire: WireLabel>
+    Garbler<C, AesRng, OT, Wire>
+{
+    pub(super) fn with_session(
+        channel: C,
+        party_seed: Block,
+        config: Config,
+    ) -> Result<Self, TwopacError> {
+        config.check_labels::<Wire>()?;
+        let started = Instant::now();
+        let mut channel = CountingChannel::new(channel, config.stats);
+        super::handshake(&mut channel)?;
+        let session_id = session::toss(&mut channel, party_seed, Party::Garbler)?;
+        let rng = session::rng(party_seed, session_id);
+        Ok(Self::start(channel, rng, config, Some(session_id), started))
+    }
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT, Wire: WireLabel> Garbler<C, RNG, OT, Wire> {
+    /// Fail with [`TwopacError::Closed`] once the garbler has been closed.
+    fn check_open(&self) -> Result<(), TwopacError> {
+        if self.closed {
+            return Err(TwopacError::Closed);
+        }
+        Ok(())
+    }
+
+    /// Fail as [`Garbler::check_open`] does, and count a gate otherwise.
+    fn check_gate(&mut self) -> Result<(), TwopacError> {
+        self.check_open()?;
+        if let Some(stats) = self.stats.as_mut() {
+            stats.gates += 1;
+        }
+        Ok(())
+    }
+
+    /// Count the time since `started` as spent in `phase`.
+    fn add_time(&mut self, phase: Phase, started: Instant) {
+        if let Some(stats) = self.stats.as_mut() {
+            stats.add_time(phase, started.elapsed());
+        }
+    }
+
+    /// Call `hooks` after `done` gates of `total`, and tell the evaluator
+    /// whether to go on.
+    fn checkpoint(&mut self, hooks: &Hooks, done: usize, total: usize) -> Result<(), TwopacError> {
+        if hooks.checkpoint(done, total) {
+            self.channel.write_u8(CHECKPOINT_CANCEL)?;
+            self.channel.flush()?;
+            return Err(TwopacError::Cancelled { gates_done: done });
+        }
+        self.channel.write_u8(CHECKPOINT_CONTINUE)?;
+        Ok(())
+    }
+
+    /// Flush the channel if the flush threshold has been reached since the
+    /// last flush.
+    fn check_flush(&mut self) -> Result<(), TwopacError> {
+        if let Some(threshold) = self.config.flush_threshold {
+            let written = self.garbler.bytes_written();
+            if written - self.flushed_at >= threshold {
+                self.channel.flush()?;
+                self.flushed_at = written;
+                if let Some(stats) = self.stats.as_mut() {
+                    stats.flushes += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 impl<
         C: AbstractChannel,
         RNG: CryptoRng + Rng + SeedableRng<Seed = Block>,
@@ -77,33 +739,62 @@ impl<
     type Item = Wire;
     type Error = TwopacError;
 
-    fn encode(&mut self, val: u16, modulus: u16) -> Result<Wire, TwopacError> {
+    // Encoded garbler inputs are not flushed here: they ride along with the
+    // next flush, which happens at the latest before the garbler reads from
+    // the channel.
+
+    fn encode(&mut self, val: Modulus, modulus: Modulus) -> Result<Wire, TwopacError> {
+        self.check_open()?;
+        let started = Instant::now();
+        check_inputs(&[val], [modulus])?;
         let (mine, theirs) = self.garbler.encode_wire(val, modulus);
         self.garbler.send_wire(&theirs)?;
-        self.channel.flush()?;
+        self.add_time(Phase::Input, started);
         Ok(mine)
     }
 
-    fn encode_many(&mut self, vals: &[u16], moduli: &[u16]) -> Result<Vec<Wire>, TwopacError> {
-        let ws = vals
-            .iter()
-            .zip(moduli.iter())
-            .map(|(x, q)| {
-                let (mine, theirs) = self.garbler.encode_wire(*x, *q);
-                self.garbler.send_wire(&theirs)?;
-                Ok(mine)
-            })
-            .collect();
-        self.channel.flush()?;
-        ws
+    fn encode_many(
+        &mut self,
+        vals: &[Modulus],
+        moduli: &[Modulus],
+    ) -> Result<Vec<Wire>, TwopacError> {
+        self.check_open()?;
+        let started = Instant::now();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!(
+            "encode_inputs",
+            party = "garbler",
+            owner = "garbler",
+            ninputs = vals.len(),
+            bytes = vals.len() * std::mem::size_of::<Block>()
+        )
+        .entered();
+        check_inputs(vals, moduli.iter().copied())?;
+        let (mine, theirs) = self.garbler.encode_many_wires(vals, moduli)?;
+        for w in theirs.iter() {
+            self.garbler.send_wire(w)?;
+        }
+        self.add_time(Phase::Input, started);
+        Ok(mine)
     }
 
-    fn receive_many(&mut self, qs: &[u16]) -> Result<Vec<Wire>, TwopacError> {
+    fn receive_many(&mut self, qs: &[Modulus]) -> Result<Vec<Wire>, TwopacError> {
+        self.check_open()?;
+        let started = Instant::now();
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!(
+            "encode_inputs",
+            party = "garbler",
+            owner = "evaluator",
+            ninputs = qs.len()
+        )
+        .entered();
         self.channel.flush()?;
         let n = qs.len();
-        let lens = qs.iter().map(|q| f32::from(*q).log(2.0).ceil() as usize);
+        let lens = qs.iter().map(|q| f64::from(*q).log2().ceil() as usize);
         let mut wires = Vec::with_capacity(n);
-        let mut inputs = Vec::with_capacity(lens.sum());
+        let mut inputs =
+            Vec::with_capacity(lens.sum::<usize>() * self.config.security.label_blocks());
 
         for q in qs.iter() {
             let delta = self.garbler.delta(*q);
@@ -113,36 +804,92 @@ impl<
                 inputs.push(i);
             }
         }
-        self.ot.send(&mut self.channel, &inputs, &mut self.rng)?;
+        if !inputs.is_empty() {
+            self.run_ots(&inputs)?;
+        }
+        self.add_time(Phase::Input, started);
         Ok(wires)
     }
 }
 
 impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT> FancyBinary for Garbler<C, RNG, OT, WireMod2> {
     fn negate(&mut self, x: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
+        self.garbler.negate(x).map_err(Self::Error::from)
+    }
+
+    fn xor(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
+        self.garbler.xor(x, y).map_err(Self::Error::from)
+    }
+
+    fn and(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
+        let z = self.garbler.and(x, y)?;
+        self.check_flush()?;
+        Ok(z)
+    }
+
+    fn custom(
+        &mut self,
+        gate: &Arc<dyn CustomGate>,
+        inputs: &[Self::Item],
+    ) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
+        let z = self.garbler.custom(gate, inputs)?;
+        self.check_flush()?;
+        Ok(z)
+    }
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT> FancyBinary
+    for Garbler<C, RNG, OT, WideWireMod2>
+{
+    fn negate(&mut self, x: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.garbler.negate(x).map_err(Self::Error::from)
     }
 
     fn xor(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.garbler.xor(x, y).map_err(Self::Error::from)
     }
 
     fn and(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
-        self.garbler.and(x, y).map_err(Self::Error::from)
+        self.check_gate()?;
+        let z = self.garbler.and(x, y)?;
+        self.check_flush()?;
+        Ok(z)
+    }
+
+    fn custom(
+        &mut self,
+        gate: &Arc<dyn CustomGate>,
+        inputs: &[Self::Item],
+    ) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
+        let z = self.garbler.custom(gate, inputs)?;
+        self.check_flush()?;
+        Ok(z)
     }
 }
 
 impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT> FancyBinary for Garbler<C, RNG, OT, AllWire> {
     fn negate(&mut self, x: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.garbler.negate(x).map_err(Self::Error::from)
     }
 
     fn xor(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.garbler.xor(x, y).map_err(Self::Error::from)
     }
 
     fn and(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
-        self.garbler.and(x, y).map_err(Self::Error::from)
+        self.check_gate()?;
+        let z = self.garbler.and(x, y)?;
+        self.check_flush()?;
+        Ok(z)
     }
 }
 
@@ -150,23 +897,37 @@ impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT, Wire: WireLabel + ArithmeticW
     for Garbler<C, RNG, OT, Wire>
 {
     fn add(&mut self, x: &Wire, y: &Wire) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.garbler.add(x, y).map_err(Self::Error::from)
     }
 
     fn sub(&mut self, x: &Wire, y: &Wire) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.garbler.sub(x, y).map_err(Self::Error::from)
     }
 
-    fn cmul(&mut self, x: &Wire, c: u16) -> Result<Self::Item, Self::Error> {
+    fn cmul(&mut self, x: &Wire, c: Modulus) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.garbler.cmul(x, c).map_err(Self::Error::from)
     }
 
     fn mul(&mut self, x: &Wire, y: &Wire) -> Result<Self::Item, Self::Error> {
-        self.garbler.mul(x, y).map_err(Self::Error::from)
+        self.check_gate()?;
+        let z = self.garbler.mul(x, y)?;
+        self.check_flush()?;
+        Ok(z)
     }
 
-    fn proj(&mut self, x: &Wire, q: u16, tt: Option<Vec<u16>>) -> Result<Self::Item, Self::Error> {
-        self.garbler.proj(x, q, tt).map_err(Self::Error::from)
+    fn proj(
+        &mut self,
+        x: &Wire,
+        q: Modulus,
+        tt: Option<Vec<Modulus>>,
+    ) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
+        let z = self.garbler.proj(x, q, tt)?;
+        self.check_flush()?;
+        Ok(z)
     }
 }
 
@@ -176,21 +937,100 @@ impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT, Wire: WireLabel> Fancy
     type Item = Wire;
     type Error = TwopacError;
 
-    fn constant(&mut self, x: u16, q: u16) -> Result<Self::Item, Self::Error> {
+    fn constant(&mut self, x: Modulus, q: Modulus) -> Result<Self::Item, Self::Error> {
+        self.check_gate()?;
         self.garbler.constant(x, q).map_err(Self::Error::from)
     }
 
-    fn output(&mut self, x: &Self::Item) -> Result<Option<u16>, Self::Error> {
-        self.garbler.output(x).map_err(Self::Error::from)
+    fn output(&mut self, x: &Self::Item) -> Result<Option<Modulus>, Self::Error> {
+        self.check_open()?;
+        if self.config.output_mode == OutputMode::Garbler {
+            self.output_zeros.push(x.clone());
+            return Ok(None);
+        }
+        self.garbler.output(x)?;
+        if self.config.stream_outputs {
+            self.channel.flush()?;
+            self.flushed_at = self.garbler.bytes_written();
+        } else {
+            self.check_flush()?;
+        }
+        Ok(None)
     }
 }
 
 impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT, Wire: WireLabel> FancyReveal
     for Garbler<C, RNG, OT, Wire>
 {
-    fn reveal(&mut self, x: &Self::Item) -> Result<u16, Self::Error> {
+    fn reveal(&mut self, x: &Self::Item) -> Result<Modulus, Self::Error> {
+        self.check_open()?;
         self.garbler.reveal(x).map_err(Self::Error::from)
     }
 }
 
-impl<C, RNG, OT, Wire> SemiHonest for Garbler<C, RNG, OT, Wire> {}
+/// A circuit garbled and sent by [`Garbler::preprocess`], waiting for the
+/// inputs.
+///
+/// The handle holds the encoder of the garbling and the labels of the
+/// oblivious transfers, and borrows the garbler until it is run.
+pub struct GarblerHandle<'a, C, RNG, OT, Wire: WireLabel> {
+    garbler: &'a mut Garbler<C, RNG, OT, Wire>,
+    encoder: Encoder<Wire>,
+    masks: Vec<Wire>,
+    noutputs: usize,
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT, Wire: WireLabel>
+    GarblerHandle<'_, C, RNG, OT, Wire>
+{
+    /// Run the preprocessed circuit on the garbler's `inputs` and the
+    /// evaluator's, which must call
+    /// [`EvaluatorHandle::online`](super::EvaluatorHandle::online).
+    ///
+    /// The garbler sends the label of each of its inputs, reads a bit of each
+    /// evaluator input, the value masked with the choice of its oblivious
+    /// transfer, and answers with the label of the value masked with the
+    /// label the evaluator received. Returns what [`Garbler::eval_circuit`]
+    /// does.
+    pub fn online(self, inputs: &[Modulus]) -> Result<Option<Vec<Modulus>>, TwopacError> {
+        let garbler = self.garbler;
+        garbler.check_open()?;
+        let started = Instant::now();
+        let xs = self.encoder.encode_garbler_inputs(inputs)?;
+        for x in xs.iter() {
+            garbler.channel.write_block(&x.as_block())?;
+        }
+        garbler.channel.flush()?;
+        let mut bits = vec![0; (self.masks.len() + 7) / 8];
+        garbler.channel.read_bytes(&mut bits)?;
+        for (i, mask) in self.masks.iter().enumerate() {
+            let bit = (bits[i / 8] >> (i % 8)) & 1;
+            let label = self.encoder.encode_evaluator_input(Modulus::from(bit), i)?;
+            garbler.channel.write_block(&label.minus(mask).as_block())?;
+        }
+        garbler.channel.flush()?;
+        garbler.add_time(Phase::Input, started);
+        if garbler.config.output_mode != OutputMode::Both {
+            return Ok(None);
+        }
+        let started = Instant::now();
+        let mut outputs = Vec::with_capacity(self.noutputs);
+        for _ in 0..self.noutputs {
+            let mut val = [0; std::mem::size_of::<Modulus>()];
+            garbler.channel.read_bytes(&mut val)?;
+            outputs.push(Modulus::from_le_bytes(val));
+        }
+        garbler.add_time(Phase::Eval, started);
+        Ok(Some(outputs))
+    }
+}
+
+impl<C, RNG, OT, Wire: WireLabel> SemiHonest for Garbler<C, RNG, OT, Wire> {}
+
+// With the `zeroize` feature, the deltas, the OT, and the RNGs of a garbler
+// overwrite themselves when it is dropped.
+#[cfg(feature = "zeroize")]
+impl<C, RNG: zeroize::ZeroizeOnDrop, OT: zeroize::ZeroizeOnDrop, Wire: WireLabel>
+    zeroize::ZeroizeOnDrop for Garbler<C, RNG, OT, Wire>
+{
+}
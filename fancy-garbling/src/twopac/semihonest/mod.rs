@@ -1,30 +1,181 @@
 //! Implementation of semi-honest two-party computation.
 
+#[cfg(feature = "tokio")]
+pub mod aio;
+pub mod audit;
+mod config;
+mod counting;
+mod estimate;
 mod evaluator;
 mod garbler;
+mod negotiate;
+mod pool;
+mod profile;
+mod session;
 
-pub use evaluator::Evaluator;
-pub use garbler::Garbler;
+pub use config::{OutputMode, SemiHonest, SemiHonestBuilder, TwopacStats};
+pub use counting::CountingChannel;
+pub use estimate::{estimate, CostEstimate, OtCost, Party, Rounds, DEFAULT_TIME_PER_NONFREE_GATE};
+pub use evaluator::{Evaluator, EvaluatorHandle};
+pub use garbler::{Garbler, GarblerHandle};
+pub use negotiate::{accept, propose, Agreement};
+pub use pool::GarblerPool;
+pub use profile::{profile, TwopacProfile};
+
+use crate::{
+    circuit::CircuitRef,
+    errors::{FancyError, TwopacError},
+    fancy::check_inputs,
+    Modulus,
+};
+use scuttlebutt::AbstractChannel;
+use sha2::{Digest, Sha256};
+
+/// The version of the wire protocol spoken by [`Garbler`] and [`Evaluator`].
+///
+/// The parties exchange their versions when they are made, and refuse to run
+/// with a peer of another version with [`TwopacError::VersionMismatch`]. The
+/// version covers everything a peer sees:
+///
+/// * **Handshake.** Each party sends its version as a little-endian `u32`,
+///   flushes, and reads the version of its peer.
+/// * **Garbler inputs.** The evaluator's label of each garbler input is sent
+///   as is, 16 bytes per wire, in the order of encoding.
+/// * **Evaluator inputs.** Each input of modulus `q` takes `⌈log2 q⌉`
+///   oblivious transfers of 16-byte messages, least significant bit first,
+///   run in batches of the configured chunk size. The OT is set up, with its
+///   base OTs, the first time the evaluator's inputs are needed; by default it
+///   is the ALSZ extension over Chou–Orlandi base OTs.
+/// * **Gates.** The garbled gates and outputs are streamed in the order the
+///   circuit is evaluated, as described in the [`garble`](crate::garble)
+///   module.
+/// * **Public inputs.** At each call of `encode_public`, the garbler sends
+///   the SHA-256 hash of the values and moduli, as little-endian `u64`s
+///   following their number, and a 16-byte seed, and the evaluator sends its
+///   own hash. The evaluator's labels of the inputs are drawn from an
+///   `AesRng` seeded with the seed.
+/// * **Checkpoints.** Only if both parties are built with
+///   [`hooks`](SemiHonestBuilder::hooks): after every interval of gates of a
+///   circuit, the garbler sends one byte, 0 to go on or 1 to cancel.
+/// * **Outputs.** With [`OutputMode::Both`], the evaluator sends each output
+///   as a little-endian [`Modulus`](crate::Modulus) once the circuit has been
+///   evaluated. With [`OutputMode::Garbler`], the garbler sends no decoding
+///   ciphertexts of the outputs, and the evaluator sends the 16-byte label of
+///   each output that is not constant once the circuit has been evaluated.
+/// * **Revealed outputs.** At [`Garbler::receive_outputs`], the garbler sends
+///   the decoding ciphertexts of each wire, as for an output of a circuit,
+///   and the evaluator sends back each value as a little-endian
+///   [`Modulus`](crate::Modulus). At [`Garbler::receive_output_labels`], the
+///   evaluator sends the 16-byte label of each wire.
+/// * **Preprocessing.** At [`Garbler::preprocess`], the garbler sends the
+///   length of the garbled circuit as a little-endian `u64` and the circuit
+///   as written by [`GarbledCircuit::to_bytes`](crate::classic::GarbledCircuit::to_bytes),
+///   and runs an oblivious transfer of a random label and that label plus
+///   the delta for each evaluator input. Online, the garbler sends the label
+///   of each of its inputs, the evaluator each of its inputs XORed with its
+///   choice bit, packed eight to a byte, least significant bit first, and the
+///   garbler the label of that bit minus the random label of each input. The
+///   outputs are then sent as above.
+///
+/// Apart from the garbled circuit, nothing is framed: each party knows the
+/// length of every message from the circuit and the moduli. The hash of the `blake3-hash` feature changes the
+/// gates without changing the version, so both parties must agree on the
+/// feature as well.
+///
+/// The asynchronous parties of the `aio` module send the same messages, each in
+/// a frame of its own.
+///
+/// Any change to the above must bump the version, and regenerate the golden
+/// transcripts of the `transcripts` tests.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The byte the garbler sends at a checkpoint to go on.
+const CHECKPOINT_CONTINUE: u8 = 0;
+
+/// The byte the garbler sends at a checkpoint to cancel.
+const CHECKPOINT_CANCEL: u8 = 1;
+
+/// Send our protocol version over `channel` and check the version of the
+/// peer against it.
+fn handshake<C: AbstractChannel>(channel: &mut C) -> Result<(), TwopacError> {
+    channel.write_u32(PROTOCOL_VERSION)?;
+    channel.flush()?;
+    let theirs = channel.read_u32()?;
+    if theirs != PROTOCOL_VERSION {
+        return Err(TwopacError::VersionMismatch {
+            ours: PROTOCOL_VERSION,
+            theirs,
+        });
+    }
+    Ok(())
+}
+
+/// The moduli of the inputs `refs` of a circuit.
+fn input_moduli(refs: &[CircuitRef]) -> Vec<Modulus> {
+    refs.iter().map(|r| r.modulus).collect()
+}
+
+/// Check public inputs and hash them, for the parties to compare in
+/// `encode_public`.
+fn public_digest(values: &[Modulus], moduli: &[Modulus]) -> Result<[u8; 32], FancyError> {
+    if values.len() != moduli.len() {
+        return Err(FancyError::InvalidArgNum {
+            got: values.len(),
+            needed: moduli.len(),
+        });
+    }
+    check_inputs(values, moduli.iter().copied())?;
+    let mut hash = Sha256::new();
+    hash.update((values.len() as u64).to_le_bytes());
+    for (&x, &q) in values.iter().zip(moduli) {
+        hash.update(u64::from(x).to_le_bytes());
+        hash.update(u64::from(q).to_le_bytes());
+    }
+    Ok(hash.finalize().into())
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
-        circuit::{eval_plain, BinaryCircuit, CircuitInfo, EvaluableCircuit},
+        circuit::{
+            eval_plain, eval_plain_grouped, ArithmeticCircuit, BinaryCircuit, CircuitBuilder,
+            CircuitInfo, CircuitRef, CircuitType, EvaluableCircuit,
+        },
         dummy::Dummy,
-        util::RngExt,
-        AllWire, CrtBundle, CrtGadgets, FancyArithmetic, FancyBinary, FancyInput, WireLabel,
-        WireMod2,
+        errors::{FancyError, TwopacError},
+        hooks::Hooks,
+        twopac::testing::{run_local, run_parties, LocalEvaluator, LocalGarbler},
+        util::{self, RngExt},
+        AllWire, BinaryBundle, BinaryGadgets, CrtBundle, CrtGadgets, Fancy, FancyArithmetic,
+        FancyBinary, FancyInput, FancyReveal, HasModulus, Modulus, SecurityParams, WideWireMod2,
+        WireLabel, WireMod2,
     };
     use itertools::Itertools;
-    use ocelot::ot::{ChouOrlandiReceiver, ChouOrlandiSender};
-    use scuttlebutt::{unix_channel_pair, AesRng, UnixChannel};
+    use ocelot::ot::{
+        AlszReceiver, AlszSender, ChouOrlandiReceiver, ChouOrlandiSender, KosReceiver, KosSender,
+        Receiver as OtReceiver, Sender as OtSender,
+    };
+    use rand::SeedableRng;
+    use scuttlebutt::{
+        unix_channel_pair, AbstractChannel, AesRng, Block, ChannelError, SyncChannel,
+        TrackUnixChannel, UnixChannel,
+    };
+    use std::{
+        error::Error,
+        io,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+        time::{Duration, Instant},
+    };
 
     fn addition<F: FancyArithmetic>(
         f: &mut F,
         a: &F::Item,
         b: &F::Item,
-    ) -> Result<Option<u16>, F::Error> {
+    ) -> Result<Option<Modulus>, F::Error> {
         let c = f.add(&a, &b)?;
         f.output(&c)
     }
@@ -108,58 +259,1647 @@ mod tests {
         assert_eq!(target, result);
     }
 
+    /// Multiply CRT bundles and add binary bundles of the garbler's `xs` and
+    /// the evaluator's `ys`, returning the products and the sums.
+    fn crt_mul_bin_add<F: FancyArithmetic + FancyBinary>(
+        f: &mut F,
+        (xs, ys): (&[CrtBundle<F::Item>], &[CrtBundle<F::Item>]),
+        (us, vs): (&[BinaryBundle<F::Item>], &[BinaryBundle<F::Item>]),
+    ) -> Result<(Option<Vec<u128>>, Option<Vec<u128>>), F::Error> {
+        let zs = xs
+            .iter()
+            .zip(ys)
+            .map(|(x, y)| f.crt_mul(x, y))
+            .collect::<Result<Vec<_>, _>>()?;
+        let ws = us
+            .iter()
+            .zip(vs)
+            .map(|(u, v)| f.bin_addition_no_carry(u, v))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((f.crt_outputs(&zs)?, f.bin_outputs(&ws)?))
+    }
+
+    #[test]
+    fn test_bundle_inputs() {
+        let n = 4;
+        let q = util::modulus_with_width(64);
+        let mut rng = AesRng::new();
+        let mut values = || (0..n).map(|_| rng.gen_u64() as u128).collect_vec();
+        let (xs, ys) = (values(), values());
+        let config = SemiHonest::builder().stats(true);
+        let (sender, receiver) = unix_channel_pair();
+        let (gb_config, xs_) = (config.clone(), xs.clone());
+        let garbler = std::thread::spawn(move || {
+            let mut gb = gb_config
+                .build_garbler::<_, _, AllWire>(sender, AesRng::new())
+                .unwrap();
+            let crt = (
+                gb.crt_encode_many(&xs_, q).unwrap(),
+                gb.crt_receive_many(n, q).unwrap(),
+            );
+            let bin = (
+                gb.bin_encode_many(&xs_, 64).unwrap(),
+                gb.bin_receive_many(n, 64).unwrap(),
+            );
+            crt_mul_bin_add(&mut gb, (&crt.0, &crt.1), (&bin.0, &bin.1)).unwrap();
+            gb.get_channel().flush().unwrap();
+        });
+        let mut ev = config
+            .build_evaluator::<_, _, AllWire>(receiver, AesRng::new())
+            .unwrap();
+        let crt = (
+            ev.crt_receive_many(n, q).unwrap(),
+            ev.crt_encode_many(&ys, q).unwrap(),
+        );
+        let bin = (
+            ev.bin_receive_many(n, 64).unwrap(),
+            ev.bin_encode_many(&ys, 64).unwrap(),
+        );
+        let (products, sums) =
+            crt_mul_bin_add(&mut ev, (&crt.0, &crt.1), (&bin.0, &bin.1)).unwrap();
+        garbler.join().unwrap();
+
+        let expected = xs.iter().zip(&ys).map(|(x, y)| x * y % q).collect_vec();
+        assert_eq!(products.unwrap(), expected);
+        let expected = xs
+            .iter()
+            .zip(&ys)
+            .map(|(x, y)| (x + y) % (1 << 64))
+            .collect_vec();
+        assert_eq!(sums.unwrap(), expected);
+        // The bits of all the residues of the CRT bundles are sent in one
+        // batch of OT extension, and those of the binary bundles in another.
+        let bits = util::factor(q)
+            .iter()
+            .map(|&p| f64::from(p).log2().ceil() as usize)
+            .sum::<usize>();
+        let stats = ev.stats().unwrap();
+        assert_eq!(stats.ots, n * (bits + 64));
+        assert_eq!(stats.ot_batches, 2);
+    }
+
     type GB<Wire> = Garbler<UnixChannel, AesRng, ChouOrlandiSender, Wire>;
     type EV<Wire> = Evaluator<UnixChannel, AesRng, ChouOrlandiReceiver, Wire>;
 
-    fn test_circuit<CIRC, Wire: WireLabel>(circ: CIRC)
+    /// Run `circ` on random inputs with [`run_local`], checking the
+    /// evaluator's outputs, and that the run depends only on its seed.
+    fn test_circuit<Wire: WireLabel>(circ: &BinaryCircuit, seed: u128)
+    where
+        BinaryCircuit: EvaluableCircuit<LocalGarbler<AlszSender, Wire>>
+            + EvaluableCircuit<LocalEvaluator<AlszReceiver, Wire>>,
+    {
+        let mut rng = AesRng::from_seed(Block::from(seed));
+        let gb_inputs = (0..circ.num_garbler_inputs())
+            .map(|_| Modulus::from(rng.gen_bool()))
+            .collect_vec();
+        let ev_inputs = (0..circ.num_evaluator_inputs())
+            .map(|_| Modulus::from(rng.gen_bool()))
+            .collect_vec();
+        let run = || run_local::<_, Wire>(circ, &gb_inputs, &ev_inputs, Block::from(seed)).unwrap();
+        let (gb, ev) = run();
+        let target = eval_plain(circ, &gb_inputs, &ev_inputs).unwrap();
+        assert_eq!(gb.outputs, None);
+        assert_eq!(ev.outputs, Some(target));
+        assert_eq!(run(), (gb, ev));
+    }
+
+    #[test]
+    fn test_aes_arithmetic() {
+        let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap();
+        test_circuit::<AllWire>(&circ, 1);
+    }
+
+    #[test]
+    fn test_aes_binary() {
+        let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap();
+        test_circuit::<WireMod2>(&circ, 2);
+    }
+
+    #[test]
+    fn test_adder64() {
+        let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/adder64.txt"
+        )))
+        .unwrap();
+        test_circuit::<WireMod2>(&circ, 3);
+        test_circuit::<AllWire>(&circ, 4);
+    }
+
+    /// A circuit outputting a constant, the sum of its inputs computed by
+    /// `add`, and another constant.
+    fn constant_outputs<C: crate::circuit::CircuitType>(
+        q: Modulus,
+        add: fn(&mut CircuitBuilder<C>, &CircuitRef, &CircuitRef) -> CircuitRef,
+    ) -> C {
+        let mut b = CircuitBuilder::<C>::new();
+        let x = b.garbler_input(q);
+        let y = b.evaluator_input(q);
+        let c = b.constant(q - 1, q).unwrap();
+        b.output(&c).unwrap();
+        let z = add(&mut b, &x, &y);
+        b.output(&z).unwrap();
+        let c = b.constant(1, q).unwrap();
+        b.output(&c).unwrap();
+        b.finish()
+    }
+
+    /// Evaluate `circ` with each of the ways of evaluating a circuit, and
+    /// check they agree on every input.
+    fn check_all_paths<CIRC, Wire: WireLabel>(circ: CIRC, q: Modulus)
     where
         CIRC: EvaluableCircuit<Dummy>
+            + EvaluableCircuit<crate::classic::Gb<Wire>>
+            + EvaluableCircuit<crate::classic::Ev<Wire>>
             + EvaluableCircuit<GB<Wire>>
             + EvaluableCircuit<EV<Wire>>
             + CircuitInfo
             + Send
+            + Sync
             + 'static,
     {
-        circ.print_info().unwrap();
+        assert_eq!(circ.constant_outputs(), [Some(q - 1), None, Some(1)]);
+        // Only the computed output is decoded.
+        assert_eq!(circ.info().unwrap().output_moduli, [q]);
+
+        let circ = Arc::new(circ);
+        for x in 0..q {
+            for y in 0..q {
+                let target = eval_plain(&*circ, &[x], &[y]).unwrap();
+                assert_eq!(target, [q - 1, (x + y) % q, 1]);
+
+                let (en, gc) = crate::classic::garble::<Wire, _>(&*circ).unwrap();
+                let xs = en.encode_garbler_inputs(&[x]).unwrap();
+                let ys = en.encode_evaluator_inputs(&[y]).unwrap();
+                assert_eq!(gc.eval(&*circ, &xs, &ys).unwrap(), target);
+
+                let (sender, receiver) = unix_channel_pair();
+                let circ_ = Arc::clone(&circ);
+                let handle = std::thread::spawn(move || {
+                    let mut gb = GB::<Wire>::new(sender, AesRng::new()).unwrap();
+                    let xs = gb.encode_many(&[x], &[q]).unwrap();
+                    let ys = gb.receive_many(&[q]).unwrap();
+                    gb.eval_circuit(&*circ_, &xs, &ys).unwrap();
+                });
+                let mut ev = EV::<Wire>::new(receiver, AesRng::new()).unwrap();
+                let xs = ev.receive_many(&[q]).unwrap();
+                let ys = ev.encode_many(&[y], &[q]).unwrap();
+                assert_eq!(ev.eval_circuit(&*circ, &xs, &ys).unwrap(), target);
+                handle.join().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_constant_outputs() {
+        let xor = constant_outputs::<BinaryCircuit>(2, |b, x, y| b.xor(x, y).unwrap());
+        check_all_paths::<_, WireMod2>(xor, 2);
+        let add = constant_outputs::<ArithmeticCircuit>(5, |b, x, y| b.add(x, y).unwrap());
+        check_all_paths::<_, AllWire>(add, 5);
+    }
+
+    /// Add two 64-bit numbers between the parties, revealing the bits of the
+    /// sum where `mask` is set, and return what each party learns and how
+    /// many bytes each sends.
+    fn reveal_masked_sum(
+        x: u64,
+        y: u64,
+        mask: &[bool],
+    ) -> ((Vec<Option<Modulus>>, u64), (Vec<Option<Modulus>>, u64)) {
+        let (sender, receiver) = scuttlebutt::track_unix_channel_pair();
+        let bytes = |c: &TrackUnixChannel| (c.kilobits_written() * 1000.0 / 8.0).round() as u64;
+        let xbits = util::u128_to_bits(x.into(), 64);
+        let ybits = util::u128_to_bits(y.into(), 64);
+        let mask_ = mask.to_vec();
+        let handle = std::thread::spawn(move || {
+            let mut gb =
+                Garbler::<_, AesRng, AlszSender, WireMod2>::new(sender, AesRng::new()).unwrap();
+            let xs = BinaryBundle::new(gb.encode_many(&xbits, &[2; 64]).unwrap());
+            let ys = BinaryBundle::new(gb.receive_many(&[2; 64]).unwrap());
+            let z = gb.bin_addition_no_carry(&xs, &ys).unwrap();
+            let revealed = gb.reveal_masked(&z, &mask_).unwrap();
+            (revealed, bytes(gb.get_channel()))
+        });
+        let mut ev =
+            Evaluator::<_, AesRng, AlszReceiver, WireMod2>::new(receiver, AesRng::new()).unwrap();
+        let xs = BinaryBundle::new(ev.receive_many(&[2; 64]).unwrap());
+        let ys = BinaryBundle::new(ev.encode_many(&ybits, &[2; 64]).unwrap());
+        let z = ev.bin_addition_no_carry(&xs, &ys).unwrap();
+        let revealed = ev.reveal_masked(&z, mask).unwrap();
+        let gb = handle.join().unwrap();
+        (gb, (revealed, bytes(ev.get_channel())))
+    }
+
+    #[test]
+    fn test_reveal_masked() {
+        let mut rng = AesRng::new();
+        let (x, y) = (rng.gen_u64(), rng.gen_u64());
+        let sum = util::u128_to_bits(x.wrapping_add(y).into(), 64);
+        let top8 = (0..64).map(|i| i >= 56).collect_vec();
+        let ((gb, gb_bytes), (ev, ev_bytes)) = reveal_masked_sum(x, y, &top8);
+        let expected = (0..64).map(|i| top8[i].then(|| sum[i])).collect_vec();
+        assert_eq!(gb, expected);
+        assert_eq!(ev, expected);
+
+        // Hidden bits cost nothing: each revealed bit costs the garbler two
+        // decoding ciphertexts and the evaluator its value.
+        let ((_, gb_none), (none, ev_none)) = reveal_masked_sum(x, y, &[false; 64]);
+        assert_eq!(none, vec![None; 64]);
+        assert_eq!(gb_bytes - gb_none, 8 * 2 * 16);
+        assert_eq!(
+            ev_bytes - ev_none,
+            8 * std::mem::size_of::<Modulus>() as u64
+        );
+        let ((gb_all, gb_all_bytes), (_, ev_all_bytes)) = reveal_masked_sum(x, y, &[true; 64]);
+        assert_eq!(gb_all, sum.into_iter().map(Some).collect_vec());
+        assert_eq!(gb_all_bytes - gb_none, 64 * 2 * 16);
+        assert_eq!(
+            ev_all_bytes - ev_none,
+            64 * std::mem::size_of::<Modulus>() as u64
+        );
+
+        let mut dummy = Dummy::new();
+        let z = dummy.bin_encode(5, 4).unwrap();
+        assert!(dummy.reveal_masked(&z, &[true; 3]).is_err());
+    }
+
+    /// Compute AES between the parties, keeping the wires of its outputs, and
+    /// return what each party learns of them with `garbler` and `evaluator`,
+    /// and the ciphertext.
+    #[allow(clippy::type_complexity)]
+    fn aes_output_phase<G, E>(
+        garbler: G,
+        evaluator: E,
+    ) -> (
+        Result<Vec<Modulus>, TwopacError>,
+        Result<Vec<Modulus>, TwopacError>,
+        Vec<Modulus>,
+    )
+    where
+        G: FnOnce(&mut UnixGarbler, &[WireMod2]) -> Result<Vec<Modulus>, TwopacError> + Send,
+        E: FnOnce(&mut UnixEvaluator, &[WireMod2]) -> Result<Vec<Modulus>, TwopacError>,
+    {
+        let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap();
+        let mut rng = AesRng::new();
+        let key = (0..128).map(|_| rng.gen_bool() as Modulus).collect_vec();
+        let block = (0..128).map(|_| rng.gen_bool() as Modulus).collect_vec();
+        let expected = eval_plain(&circ, &key, &block).unwrap();
+        let (sender, receiver) = unix_channel_pair();
+        let (gb, ev) = std::thread::scope(|s| {
+            let handle = s.spawn(|| {
+                let mut gb = UnixGarbler::new(sender, AesRng::new()).unwrap();
+                let xs = gb.encode_many(&key, &[2; 128]).unwrap();
+                let ys = gb.receive_many(&[2; 128]).unwrap();
+                let zs = circ.eval_output_wires(&mut gb, &xs, &ys).unwrap();
+                garbler(&mut gb, &zs)
+            });
+            let mut ev = UnixEvaluator::new(receiver, AesRng::new()).unwrap();
+            let xs = ev.receive_many(&[2; 128]).unwrap();
+            let ys = ev.encode_many(&block, &[2; 128]).unwrap();
+            let zs = circ.eval_output_wires(&mut ev, &xs, &ys).unwrap();
+            let ev = evaluator(&mut ev, &zs);
+            (handle.join().unwrap(), ev)
+        });
+        (gb, ev, expected)
+    }
+
+    #[test]
+    fn test_output_phase() {
+        let (gb, ev, expected) = aes_output_phase(
+            |gb, zs| gb.receive_outputs(zs),
+            |ev, zs| ev.reveal_outputs(zs),
+        );
+        assert_eq!(gb.unwrap(), expected);
+        assert_eq!(ev.unwrap(), expected);
+
+        // Only the garbler learns the outputs from their labels.
+        let (gb, ev, expected) = aes_output_phase(
+            |gb, zs| gb.receive_output_labels(zs),
+            |ev, zs| ev.send_output_labels(zs).map(|()| Vec::new()),
+        );
+        assert_eq!(gb.unwrap(), expected);
+        assert!(ev.unwrap().is_empty());
+
+        // A label that is neither of an output's fails to decode.
+        let (gb, _, _) = aes_output_phase(
+            |gb, zs| gb.receive_output_labels(zs),
+            |ev, zs| {
+                let mut zs = zs.to_vec();
+                zs[7] = WireMod2::rand(&mut AesRng::new(), 2);
+                ev.send_output_labels(&zs).map(|()| Vec::new())
+            },
+        );
+        assert!(
+            matches!(gb, Err(TwopacError::InvalidOutputLabel { index: 7 })),
+            "{:?}",
+            gb
+        );
+    }
+
+    /// Run `circ` on `x` and `y` with the garbler learning the outputs alone,
+    /// returning the garbler, the outputs it learns, and the labels the
+    /// evaluator sends.
+    fn garbler_outputs<CIRC, Wire: WireLabel>(
+        circ: &CIRC,
+        x: &[Modulus],
+        y: &[Modulus],
+    ) -> (GB<Wire>, Vec<Modulus>, Vec<Block>)
+    where
+        CIRC: EvaluableCircuit<GB<Wire>> + EvaluableCircuit<EV<Wire>> + CircuitType + Sync,
+    {
+        let config = SemiHonest::builder()
+            .ot::<ChouOrlandiSender, ChouOrlandiReceiver>()
+            .output_mode(OutputMode::Garbler);
+        let gb_moduli = input_moduli(circ.get_garbler_input_refs());
+        let ev_moduli = input_moduli(circ.get_evaluator_input_refs());
+        let (sender, receiver) = unix_channel_pair();
+        std::thread::scope(|s| {
+            let handle = s.spawn(|| {
+                let mut gb = config
+                    .build_garbler::<_, _, Wire>(sender, AesRng::new())
+                    .unwrap();
+                let xs = gb.encode_many(x, &gb_moduli).unwrap();
+                let ys = gb.receive_many(&ev_moduli).unwrap();
+                let outputs = gb.eval_circuit(circ, &xs, &ys).unwrap();
+                (gb, outputs.unwrap())
+            });
+            let mut ev = config
+                .build_evaluator::<_, _, Wire>(receiver, AesRng::new())
+                .unwrap();
+            let xs = ev.receive_many(&gb_moduli).unwrap();
+            let ys = ev.encode_many(y, &ev_moduli).unwrap();
+            // The evaluator cannot decode the outputs.
+            assert!(matches!(
+                ev.eval_circuit(circ, &xs, &ys),
+                Err(TwopacError::WrongOutputMode)
+            ));
+            let labels = ev.output_labels(circ, &xs, &ys).unwrap();
+            let (gb, outputs) = handle.join().unwrap();
+            (gb, outputs, labels)
+        })
+    }
+
+    #[test]
+    fn test_garbler_output_mode() {
+        let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap();
+        let mut rng = AesRng::new();
+        let key = (0..128).map(|_| rng.gen_bool() as Modulus).collect_vec();
+        let block = (0..128).map(|_| rng.gen_bool() as Modulus).collect_vec();
+        let expected = eval_plain(&circ, &key, &block).unwrap();
+        let (mut gb, outputs, labels) = garbler_outputs::<_, WireMod2>(&circ, &key, &block);
+        assert_eq!(outputs, expected);
+        assert_eq!(labels.len(), 128);
+        assert_eq!(gb.decode_outputs(&labels).unwrap(), expected);
+
+        // A label that is neither of an output's fails to decode, as do too
+        // few labels.
+        let mut wrong = labels.clone();
+        wrong[7] = WireMod2::rand(&mut rng, 2).as_block();
+        assert!(matches!(
+            gb.decode_outputs(&wrong),
+            Err(TwopacError::InvalidOutputLabel { index: 7 })
+        ));
+        assert!(matches!(
+            gb.decode_outputs(&labels[1..]),
+            Err(TwopacError::FancyError(FancyError::InvalidArgNum {
+                got: 127,
+                needed: 128
+            }))
+        ));
+
+        // Constant outputs need no label.
+        let circ = constant_outputs::<ArithmeticCircuit>(5, |b, x, y| b.add(x, y).unwrap());
+        let (_, outputs, labels) = garbler_outputs::<_, AllWire>(&circ, &[3], &[4]);
+        assert_eq!(outputs, [4, 2, 1]);
+        assert_eq!(labels.len(), 1);
+    }
+
+    #[test]
+    fn test_garbler_output_mode_mismatch() {
+        let circ = Arc::new(
+            BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+                "../../../circuits/adder64.txt"
+            )))
+            .unwrap(),
+        );
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut gb = GB::<WireMod2>::new(sender, AesRng::new()).unwrap();
+            let err = gb.decode_outputs(&[]).unwrap_err();
+            assert!(matches!(err, TwopacError::WrongOutputMode), "{:?}", err);
+        });
+        let mut ev = EV::<WireMod2>::new(receiver, AesRng::new()).unwrap();
+        let err = ev.output_labels(&*circ, &[], &[]).unwrap_err();
+        assert!(matches!(err, TwopacError::WrongOutputMode), "{:?}", err);
+        handle.join().unwrap();
+
+        // Preprocessed circuits decode their outputs, so neither party
+        // preprocesses when the garbler learns them alone.
+        let config = SemiHonest::builder()
+            .ot::<ChouOrlandiSender, ChouOrlandiReceiver>()
+            .output_mode(OutputMode::Garbler);
+        let (sender, receiver) = unix_channel_pair();
+        let (config_, circ_) = (config.clone(), Arc::clone(&circ));
+        let handle = std::thread::spawn(move || {
+            let mut gb = config_
+                .build_garbler::<_, _, WireMod2>(sender, AesRng::new())
+                .unwrap();
+            assert!(matches!(
+                gb.preprocess(&*circ_),
+                Err(TwopacError::WrongOutputMode)
+            ));
+            assert!(matches!(
+                gb.execute(&*circ_, &[0; 64]),
+                Err(TwopacError::WrongOutputMode)
+            ));
+        });
+        let mut ev = config
+            .build_evaluator::<_, _, WireMod2>(receiver, AesRng::new())
+            .unwrap();
+        assert!(matches!(
+            ev.preprocess(&*circ),
+            Err(TwopacError::WrongOutputMode)
+        ));
+        assert!(matches!(
+            ev.execute(&*circ, &[0; 64]),
+            Err(TwopacError::WrongOutputMode)
+        ));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_repeated_executions() {
+        let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/adder64.txt"
+        )))
+        .unwrap();
+        let n = 100;
+        let mut rng = AesRng::new();
+        let inputs = (0..n)
+            .map(|_| {
+                let mut bits = || (0..64).map(|_| rng.gen_bool() as Modulus).collect_vec();
+                (bits(), bits())
+            })
+            .collect_vec();
+        let bytes = |c: &TrackUnixChannel| (c.kilobits_written() * 1000.0 / 8.0).round() as u64;
+        let config = SemiHonest::builder().output_mode(OutputMode::Both);
+        let (sender, receiver) = scuttlebutt::track_unix_channel_pair();
+        let (gb, ev) = std::thread::scope(|s| {
+            let handle = s.spawn(|| {
+                let mut gb = config
+                    .build_garbler::<_, _, WireMod2>(sender, AesRng::new())
+                    .unwrap();
+                let mut runs = Vec::new();
+                for (xs, _) in &inputs {
+                    let outputs = gb.execute(&circ, xs).unwrap().unwrap();
+                    runs.push((outputs, gb.delta(2), bytes(gb.get_channel())));
+                }
+                runs
+            });
+            let mut ev = config
+                .build_evaluator::<_, _, WireMod2>(receiver, AesRng::new())
+                .unwrap();
+            let mut runs = Vec::new();
+            for (_, ys) in &inputs {
+                let outputs = ev.execute(&circ, ys).unwrap();
+                runs.push((outputs, bytes(ev.get_channel())));
+            }
+            (handle.join().unwrap(), runs)
+        });
+
+        for (((xs, ys), (gb_outputs, _, _)), (ev_outputs, _)) in inputs.iter().zip(&gb).zip(&ev) {
+            let expected = eval_plain(&circ, xs, ys).unwrap();
+            assert_eq!(gb_outputs, &expected);
+            assert_eq!(ev_outputs, &expected);
+        }
+        // Each execution has deltas of its own.
+        let deltas = gb
+            .iter()
+            .map(|(_, delta, _)| delta.as_block())
+            .collect_vec();
+        assert_eq!(deltas.iter().unique().count(), n);
+        // Only the first execution sets up the oblivious transfer, and every
+        // later one costs the same.
+        for sent in [
+            gb.iter().map(|&(_, _, sent)| sent).collect_vec(),
+            ev.iter().map(|&(_, sent)| sent).collect_vec(),
+        ] {
+            let costs = std::iter::once(sent[0])
+                .chain(sent.windows(2).map(|w| w[1] - w[0]))
+                .collect_vec();
+            assert!(
+                costs[1..].iter().all(|&cost| cost == costs[1]),
+                "{:?}",
+                costs
+            );
+            assert!(costs[0] > costs[1], "{:?}", costs);
+        }
+    }
+
+    #[test]
+    fn test_preprocess() {
+        let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap();
+        let mut rng = AesRng::new();
+        let key = (0..128).map(|_| rng.gen_bool() as Modulus).collect_vec();
+        let block = (0..128).map(|_| rng.gen_bool() as Modulus).collect_vec();
+        let expected = eval_plain(&circ, &key, &block).unwrap();
+        let bytes = |c: &TrackUnixChannel| (c.kilobits_written() * 1000.0 / 8.0).round() as u64;
+        for mode in [OutputMode::Evaluator, OutputMode::Both] {
+            let config = SemiHonest::builder().output_mode(mode);
+            let (sender, receiver) = scuttlebutt::track_unix_channel_pair();
+            let (gb_track, ev_track) = (sender.clone(), receiver.clone());
+            let (gb, ev) = std::thread::scope(|s| {
+                let garbler = s.spawn(|| {
+                    let mut gb = config
+                        .build_garbler::<_, _, WireMod2>(sender, AesRng::new())
+                        .unwrap();
+                    let start = bytes(&gb_track);
+                    let handle = gb.preprocess(&circ).unwrap();
+                    let offline = bytes(&gb_track) - start;
+                    let outputs = handle.online(&key).unwrap();
+                    (outputs, offline, bytes(&gb_track) - start - offline)
+                });
+                let mut ev = config
+                    .build_evaluator::<_, _, WireMod2>(receiver, AesRng::new())
+                    .unwrap();
+                let start = bytes(&ev_track);
+                let handle = ev.preprocess(&circ).unwrap();
+                let offline = bytes(&ev_track) - start;
+                let outputs = handle.online(&block).unwrap();
+                let online = bytes(&ev_track) - start - offline;
+                (garbler.join().unwrap(), (outputs, offline, online))
+            });
+            let (gb_outputs, gb_offline, gb_online) = gb;
+            let (ev_outputs, ev_offline, ev_online) = ev;
+            assert_eq!(ev_outputs, expected);
+            match mode {
+                OutputMode::Evaluator => assert_eq!(gb_outputs, None),
+                OutputMode::Both => assert_eq!(gb_outputs, Some(expected.clone())),
+                OutputMode::Garbler => unreachable!(),
+            }
+            // Online, the garbler sends a label of each of the 256 inputs, and
+            // the evaluator a bit of each of its 128, and the outputs if the
+            // garbler learns them.
+            let outputs = match mode {
+                OutputMode::Evaluator => 0,
+                OutputMode::Both => expected.len() * std::mem::size_of::<Modulus>(),
+                OutputMode::Garbler => unreachable!(),
+            };
+            assert_eq!(gb_online, 256 * 16);
+            assert_eq!(ev_online, 16 + outputs as u64);
+            let (offline, online) = (gb_offline + ev_offline, gb_online + ev_online);
+            assert!(offline > 50 * online, "{} vs {}", offline, online);
+        }
+    }
+
+    /// A channel counting the number of flights it sends, i.e., the number of
+    /// maximal runs of writes not interrupted by a read.
+    struct RoundChannel {
+        channel: UnixChannel,
+        writing: Arc<AtomicBool>,
+        flights: Arc<AtomicUsize>,
+    }
+
+    impl RoundChannel {
+        fn new(channel: UnixChannel) -> Self {
+            Self {
+                channel,
+                writing: Arc::new(AtomicBool::new(false)),
+                flights: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+    }
 
-        let circ_ = circ.clone();
+    impl AbstractChannel for RoundChannel {
+        fn write_bytes(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+            if !self.writing.swap(true, Ordering::SeqCst) {
+                self.flights.fetch_add(1, Ordering::SeqCst);
+            }
+            self.channel.write_bytes(bytes)
+        }
+
+        fn read_bytes(&mut self, bytes: &mut [u8]) -> std::io::Result<()> {
+            self.writing.store(false, Ordering::SeqCst);
+            self.channel.read_bytes(bytes)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.channel.flush()
+        }
+
+        fn clone(&self) -> Self {
+            Self {
+                channel: self.channel.clone(),
+                writing: self.writing.clone(),
+                flights: self.flights.clone(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_aes_rounds() {
+        let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap();
+        let circ = Arc::new(circ);
+        let circ_ = Arc::clone(&circ);
         let (sender, receiver) = unix_channel_pair();
+        let sender = RoundChannel::new(sender);
+        let receiver = RoundChannel::new(receiver);
+        let gb_flights = sender.flights.clone();
+        let ev_flights = receiver.flights.clone();
         let handle = std::thread::spawn(move || {
             let rng = AesRng::new();
             let mut gb =
-                Garbler::<UnixChannel, AesRng, ChouOrlandiSender, Wire>::new(sender, rng).unwrap();
-            let xs = gb.encode_many(&vec![0_u16; 128], &vec![2; 128]).unwrap();
-            let ys = gb.receive_many(&vec![2; 128]).unwrap();
-            circ_.eval(&mut gb, &xs, &ys).unwrap();
+                Garbler::<RoundChannel, AesRng, AlszSender, WireMod2>::new(sender, rng).unwrap();
+            let xs = gb.encode_many(&[0; 128], &[2; 128]).unwrap();
+            let ys = gb.receive_many(&[2; 128]).unwrap();
+            gb.eval_circuit(&*circ_, &xs, &ys).unwrap();
+            gb.get_channel().flush().unwrap();
         });
         let rng = AesRng::new();
         let mut ev =
-            Evaluator::<UnixChannel, AesRng, ChouOrlandiReceiver, Wire>::new(receiver, rng)
-                .unwrap();
-        let xs = ev.receive_many(&vec![2; 128]).unwrap();
-        let ys = ev.encode_many(&vec![0_u16; 128], &vec![2; 128]).unwrap();
-        let out = circ.eval(&mut ev, &xs, &ys).unwrap().unwrap();
+            Evaluator::<RoundChannel, AesRng, AlszReceiver, WireMod2>::new(receiver, rng).unwrap();
+        let xs = ev.receive_many(&[2; 128]).unwrap();
+        let ys = ev.encode_many(&[0; 128], &[2; 128]).unwrap();
+        ev.eval_circuit(&*circ, &xs, &ys).unwrap();
+        handle.join().unwrap();
+
+        // Both: the handshake. Garbler: its inputs, the base OT, and the OT
+        // extension response together with the garbled circuit. Evaluator:
+        // the base OT, and the remainder of the base OT together with the OT
+        // extension setup. Initializing OT eagerly used to cost one more
+        // evaluator flight.
+        assert_eq!(gb_flights.load(Ordering::SeqCst), 4);
+        assert_eq!(ev_flights.load(Ordering::SeqCst), 3);
+    }
+
+    /// A channel whose reads time out once `budget` bytes have been read.
+    struct TimeoutChannel {
+        channel: UnixChannel,
+        budget: Arc<AtomicUsize>,
+    }
+
+    impl AbstractChannel for TimeoutChannel {
+        fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+            self.channel.write_bytes(bytes)
+        }
+
+        fn read_bytes(&mut self, bytes: &mut [u8]) -> io::Result<()> {
+            let n = bytes.len();
+            self.budget
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |b| b.checked_sub(n))
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "injected timeout"))?;
+            self.channel.read_bytes(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.channel.flush()
+        }
+
+        fn clone(&self) -> Self {
+            Self {
+                channel: self.channel.clone(),
+                budget: self.budget.clone(),
+            }
+        }
+    }
+
+    /// Run AES with the evaluator's reads timing out after `budget` bytes, and
+    /// return the evaluator's error.
+    fn aes_timeout(budget: usize) -> TwopacError {
+        let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap();
+        let circ = Arc::new(circ);
+        let circ_ = Arc::clone(&circ);
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            // The garbler fails too once the evaluator hangs up.
+            let _ = (|| -> Result<(), TwopacError> {
+                let rng = AesRng::new();
+                let mut gb =
+                    Garbler::<UnixChannel, AesRng, AlszSender, WireMod2>::new(sender, rng)?;
+                let xs = gb.encode_many(&[0; 128], &[2; 128])?;
+                let ys = gb.receive_many(&[2; 128])?;
+                gb.eval_circuit(&*circ_, &xs, &ys)?;
+                Ok(gb.get_channel().flush()?)
+            })();
+        });
+        let receiver = TimeoutChannel {
+            channel: receiver,
+            budget: Arc::new(AtomicUsize::new(budget)),
+        };
+        let err = (|| -> Result<(), TwopacError> {
+            let rng = AesRng::new();
+            let mut ev =
+                Evaluator::<TimeoutChannel, AesRng, AlszReceiver, WireMod2>::new(receiver, rng)?;
+            let xs = ev.receive_many(&[2; 128])?;
+            let ys = ev.encode_many(&[0; 128], &[2; 128])?;
+            ev.eval_circuit(&*circ, &xs, &ys)?;
+            Ok(())
+        })()
+        .unwrap_err();
         handle.join().unwrap();
+        err
+    }
+
+    #[test]
+    fn test_timeout_source() {
+        // Fail during the handshake, while receiving the garbler's inputs,
+        // during OT, and while evaluating the garbled circuit.
+        for budget in [0, 4, 4 + 128 * 16, 100_000] {
+            let err = aes_timeout(budget);
+            let io = std::iter::successors(Some(&err as &dyn Error), |&e| e.source())
+                .find_map(|e| e.downcast_ref::<io::Error>())
+                .unwrap_or_else(|| panic!("no I/O error in the chain of `{}`", err));
+            assert_eq!(io.kind(), io::ErrorKind::TimedOut);
+            if budget == 4 + 128 * 16 {
+                assert!(matches!(err, TwopacError::OtError(_)), "{:?}", err);
+            }
+        }
+    }
+
+    /// Make a garbler with `garbler` on a thread of its own and an evaluator
+    /// with `evaluator`, as each waits for the other to exchange the protocol
+    /// version.
+    fn connect<G: Send, E>(
+        garbler: impl FnOnce() -> G + Send,
+        evaluator: impl FnOnce() -> E,
+    ) -> (G, E) {
+        std::thread::scope(|s| {
+            let handle = s.spawn(garbler);
+            let ev = evaluator();
+            (handle.join().unwrap(), ev)
+        })
+    }
+
+    #[test]
+    fn test_version_mismatch() {
+        let (sender, mut receiver) = unix_channel_pair();
+        let garbler = std::thread::spawn(move || {
+            Garbler::<UnixChannel, AesRng, AlszSender, WireMod2>::new(sender, AesRng::new())
+        });
+        receiver.write_u32(PROTOCOL_VERSION + 1).unwrap();
+        receiver.flush().unwrap();
+        assert_eq!(receiver.read_u32().unwrap(), PROTOCOL_VERSION);
+        match garbler.join().unwrap() {
+            Err(TwopacError::VersionMismatch { ours, theirs }) => {
+                assert_eq!((ours, theirs), (PROTOCOL_VERSION, PROTOCOL_VERSION + 1))
+            }
+            r => panic!("expected a version mismatch: {:?}", r.err()),
+        }
+    }
+
+    type UnixGarbler = Garbler<UnixChannel, AesRng, AlszSender, WireMod2>;
+    type UnixEvaluator = Evaluator<UnixChannel, AesRng, AlszReceiver, WireMod2>;
+
+    /// Set up both parties over a Unix channel pair and send the garbler's
+    /// 128 inputs.
+    fn parties() -> (UnixGarbler, UnixEvaluator, Vec<WireMod2>, Vec<WireMod2>) {
+        let (sender, receiver) = unix_channel_pair();
+        let (mut gb, mut ev) = connect(
+            || UnixGarbler::new(sender, AesRng::new()).unwrap(),
+            || UnixEvaluator::new(receiver, AesRng::new()).unwrap(),
+        );
+        let gb_xs = gb.encode_many(&[0; 128], &[2; 128]).unwrap();
+        gb.get_channel().flush().unwrap();
+        let ev_xs = ev.receive_many(&[2; 128]).unwrap();
+        (gb, ev, gb_xs, ev_xs)
+    }
+
+    /// Run `f` on a thread of its own and return its error, failing if it
+    /// takes more than ten seconds.
+    fn survivor<T: Send + 'static>(
+        f: impl FnOnce() -> Result<T, TwopacError> + Send + 'static,
+    ) -> TwopacError {
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || tx.send(f().err()).unwrap());
+        rx.recv_timeout(std::time::Duration::from_secs(10))
+            .expect("the survivor hung after its peer went away")
+            .expect("the survivor succeeded without its peer")
+    }
+
+    #[test]
+    fn test_peer_closed_encode_many() {
+        let (gb, mut ev, _, _) = parties();
+        drop(gb);
+        let err = survivor(move || ev.encode_many(&[0; 128], &[2; 128]));
+        assert!(matches!(err, TwopacError::OtError(_)), "{:?}", err);
+        assert_eq!(err.channel_error(), Some(ChannelError::PeerClosed));
+    }
+
+    #[test]
+    fn test_peer_closed_receive_many() {
+        let (mut gb, ev, _, _) = parties();
+        drop(ev);
+        let err = survivor(move || gb.receive_many(&[2; 128]));
+        assert!(matches!(err, TwopacError::OtError(_)), "{:?}", err);
+        assert_eq!(err.channel_error(), Some(ChannelError::PeerClosed));
+    }
+
+    #[test]
+    fn test_peer_closed_eval() {
+        let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap();
+        let (mut gb, mut ev, gb_xs, ev_xs) = parties();
+        // The garbler sends a few garbled gates of the circuit and then goes
+        // away, leaving the evaluator partway through it.
+        let garbler = std::thread::spawn(move || {
+            let ys = gb.receive_many(&[2; 128]).unwrap();
+            for (x, y) in gb_xs.iter().zip(&ys).take(8) {
+                gb.and(x, y).unwrap();
+            }
+            gb.get_channel().flush().unwrap();
+        });
+        let err = survivor(move || {
+            let ys = ev.encode_many(&[0; 128], &[2; 128])?;
+            ev.eval_circuit(&circ, &ev_xs, &ys)
+        });
+        garbler.join().unwrap();
+        assert_eq!(err.channel_error(), Some(ChannelError::PeerClosed));
+    }
+
+    /// Run an 8-bit adder with the garbler, if `garbler_fails`, or else the
+    /// evaluator, passing the input wires changed by `tamper`, which is given
+    /// both slices of input wires and a wire of modulus 3. Return the error of
+    /// the party given the changed wires and that of its peer.
+    fn bad_inputs(
+        garbler_fails: bool,
+        tamper: fn(&mut Vec<AllWire>, &mut Vec<AllWire>, &AllWire),
+    ) -> (TwopacError, TwopacError) {
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.bin_garbler_input(8);
+        let y = b.bin_evaluator_input(8);
+        let (z, _) = b.bin_addition(&x, &y).unwrap();
+        b.outputs(z.wires()).unwrap();
+        let circ = Arc::new(b.finish());
+        let circ_ = Arc::clone(&circ);
+
+        // The garbler sends its inputs and a wire of modulus 3 to go with them.
+        let moduli = [[2; 8].as_slice(), &[3]].concat();
+        let config = SemiHonest::builder().output_mode(OutputMode::Both);
+        let (sender, receiver) = unix_channel_pair();
+        let (mut gb, mut ev) = connect(
+            || {
+                config
+                    .build_garbler::<_, _, AllWire>(sender, AesRng::new())
+                    .unwrap()
+            },
+            || {
+                config
+                    .build_evaluator::<_, _, AllWire>(receiver, AesRng::new())
+                    .unwrap()
+            },
+        );
+        let mut garbler = move || {
+            let mut xs = gb.encode_many(&[0; 9], &moduli)?;
+            let mut ys = gb.receive_many(&[2; 8])?;
+            let q3 = xs.pop().unwrap();
+            if garbler_fails {
+                tamper(&mut xs, &mut ys, &q3);
+            }
+            gb.eval_circuit(&*circ_, &xs, &ys)
+        };
+        let mut evaluator = move || {
+            let mut xs = ev.receive_many(&[[2; 8].as_slice(), &[3]].concat())?;
+            let mut ys = ev.encode_many(&[0; 8], &[2; 8])?;
+            let q3 = xs.pop().unwrap();
+            if !garbler_fails {
+                tamper(&mut xs, &mut ys, &q3);
+            }
+            ev.eval_circuit(&*circ, &xs, &ys)
+        };
+        if garbler_fails {
+            let handle = std::thread::spawn(move || survivor(evaluator));
+            let err = garbler().unwrap_err();
+            drop(garbler);
+            (err, handle.join().unwrap())
+        } else {
+            let handle = std::thread::spawn(move || survivor(garbler));
+            let err = evaluator().unwrap_err();
+            drop(evaluator);
+            (err, handle.join().unwrap())
+        }
+    }
+
+    #[test]
+    fn test_bad_input_wires() {
+        let cases: [(
+            fn(&mut Vec<AllWire>, &mut Vec<AllWire>, &AllWire),
+            FancyError,
+        ); 6] = [
+            (
+                |xs, _, _| drop(xs.pop()),
+                FancyError::NotEnoughGarblerInputs { got: 7, needed: 8 },
+            ),
+            (
+                |_, ys, _| ys.truncate(2),
+                FancyError::NotEnoughEvaluatorInputs { got: 2, needed: 8 },
+            ),
+            (
+                |xs, _, q3| xs.push(q3.clone()),
+                FancyError::TooManyGarblerInputs { got: 9, needed: 8 },
+            ),
+            (
+                |_, ys, _| ys.push(ys[0].clone()),
+                FancyError::TooManyEvaluatorInputs { got: 9, needed: 8 },
+            ),
+            (
+                |xs, _, q3| xs[3] = q3.clone(),
+                FancyError::GarblerInputModulus {
+                    id: 3,
+                    got: 3,
+                    needed: 2,
+                },
+            ),
+            (
+                |_, ys, q3| ys[7] = q3.clone(),
+                FancyError::EvaluatorInputModulus {
+                    id: 7,
+                    got: 3,
+                    needed: 2,
+                },
+            ),
+        ];
+        for garbler_fails in [true, false] {
+            for (tamper, expected) in cases.iter() {
+                let (err, peer_err) = bad_inputs(garbler_fails, *tamper);
+                match err {
+                    TwopacError::FancyError(e) => assert_eq!(e.to_string(), expected.to_string()),
+                    e => panic!("expected {:?}, got {:?}", expected, e),
+                }
+                // Its peer finds the channel closed rather than waiting on it.
+                assert_eq!(
+                    peer_err.channel_error(),
+                    Some(ChannelError::PeerClosed),
+                    "{:?}",
+                    peer_err
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_timeout_silent_peer() {
+        let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap();
+        let timeout = Duration::from_millis(500);
+        let config = SemiHonest::builder().timeout(timeout);
+        let (sender, receiver) = unix_channel_pair();
+        let (hang_up, silent) = std::sync::mpsc::channel::<()>();
+        // The garbler sends its inputs and runs OT, and then goes silent
+        // without closing the channel.
+        let gb_config = config.clone();
+        let garbler = std::thread::spawn(move || {
+            let mut gb = gb_config
+                .build_garbler::<_, _, WireMod2>(sender, AesRng::new())
+                .unwrap();
+            gb.encode_many(&[0; 128], &[2; 128]).unwrap();
+            gb.receive_many(&[2; 128]).unwrap();
+            gb.get_channel().flush().unwrap();
+            let _ = silent.recv();
+        });
+        let mut ev = config
+            .build_evaluator::<_, _, WireMod2>(receiver, AesRng::new())
+            .unwrap();
+        let xs = ev.receive_many(&[2; 128]).unwrap();
+        let ys = ev.encode_many(&[0; 128], &[2; 128]).unwrap();
+        let start = Instant::now();
+        let err = ev.eval_circuit(&circ, &xs, &ys).unwrap_err();
+        let elapsed = start.elapsed();
+        hang_up.send(()).unwrap();
+        garbler.join().unwrap();
+        assert_eq!(
+            err.channel_error(),
+            Some(ChannelError::Timeout),
+            "{:?}",
+            err
+        );
+        assert!(elapsed < 2 * timeout, "timed out after {:?}", elapsed);
+
+        // Channels that cannot time out are refused rather than left without one.
+        let channel = SyncChannel::new(io::empty(), io::sink());
+        assert!(config
+            .build_garbler::<_, AesRng, AllWire>(channel, AesRng::new())
+            .is_err());
+    }
+
+    fn assert_out_of_range<T: std::fmt::Debug>(
+        result: Result<T, TwopacError>,
+        index: usize,
+        modulus: Modulus,
+    ) {
+        match result {
+            Err(TwopacError::FancyError(FancyError::InputOutOfRange {
+                index: i,
+                modulus: q,
+                ..
+            })) => assert_eq!((i, q), (index, modulus)),
+            r => panic!("expected input {} to be out of range: {:?}", index, r),
+        }
+    }
+
+    #[test]
+    fn test_out_of_range_inputs() {
+        // The peer of each party only sends the protocol version.
+        type Sync = SyncChannel<io::Cursor<[u8; 4]>, io::Sink>;
+        let channel = || Sync::new(io::Cursor::new(PROTOCOL_VERSION.to_le_bytes()), io::sink());
+        let mut gb =
+            Garbler::<Sync, AesRng, AlszSender, AllWire>::new(channel(), AesRng::new()).unwrap();
+        let mut ev =
+            Evaluator::<Sync, AesRng, AlszReceiver, AllWire>::new(channel(), AesRng::new())
+                .unwrap();
+
+        assert_out_of_range(gb.encode(2, 2), 0, 2);
+        assert_out_of_range(gb.encode_many(&[1, 0, 5, 1], &[2, 3, 5, 2]), 2, 5);
+        assert_out_of_range(ev.encode(3, 3), 0, 3);
+        assert_out_of_range(ev.encode_many(&[1, 2, 3], &[2, 3, 3]), 2, 3);
+        assert_out_of_range(ev.encode_many(&[2, 0], &[2, 2]), 0, 2);
+
+        // In range, the garbler only writes the labels, so it gets by without
+        // an evaluator.
+        let xs = gb.encode_many(&[1, 2, 4, 0], &[2, 3, 5, 2]).unwrap();
+        assert_eq!(xs.iter().map(|x| x.modulus()).collect_vec(), [2, 3, 5, 2]);
+    }
+
+    fn assert_send<T: Send>() {}
+
+    #[test]
+    fn test_send() {
+        type Sync = SyncChannel<io::Empty, io::Sink>;
+        assert_send::<Garbler<UnixChannel, AesRng, AlszSender, WireMod2>>();
+        assert_send::<Evaluator<UnixChannel, AesRng, AlszReceiver, WireMod2>>();
+        assert_send::<Garbler<TrackUnixChannel, AesRng, KosSender, AllWire>>();
+        assert_send::<Evaluator<TrackUnixChannel, AesRng, KosReceiver, AllWire>>();
+        assert_send::<Garbler<Sync, AesRng, ChouOrlandiSender, AllWire>>();
+        assert_send::<Evaluator<Sync, AesRng, ChouOrlandiReceiver, AllWire>>();
+    }
+
+    #[test]
+    fn test_move_session() {
+        let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/adder64.txt"
+        )))
+        .unwrap();
+        let circ = Arc::new(circ);
+        let mut rng = AesRng::new();
+        let gb_inputs = (0..64).map(|_| Modulus::from(rng.gen_bool())).collect_vec();
+        let ev_inputs = (0..64).map(|_| Modulus::from(rng.gen_bool())).collect_vec();
+        let target = eval_plain(&*circ, &gb_inputs, &ev_inputs).unwrap();
+
+        // Start both parties on this thread and send the garbler's inputs, then
+        // move each party to a thread of its own to run OT and the circuit.
+        let (sender, receiver) = unix_channel_pair();
+        let (mut gb, mut ev) = connect(
+            || {
+                Garbler::<UnixChannel, AesRng, AlszSender, WireMod2>::new(sender, AesRng::new())
+                    .unwrap()
+            },
+            || {
+                Evaluator::<UnixChannel, AesRng, AlszReceiver, WireMod2>::new(
+                    receiver,
+                    AesRng::new(),
+                )
+                .unwrap()
+            },
+        );
+        let gb_xs = gb.encode_many(&gb_inputs, &[2; 64]).unwrap();
+        gb.get_channel().flush().unwrap();
+        let ev_xs = ev.receive_many(&[2; 64]).unwrap();
+
+        let circ_ = Arc::clone(&circ);
+        let garbler = std::thread::spawn(move || {
+            let ys = gb.receive_many(&[2; 64]).unwrap();
+            gb.eval_circuit(&*circ_, &gb_xs, &ys).unwrap();
+        });
+        let evaluator = std::thread::spawn(move || {
+            let ys = ev.encode_many(&ev_inputs, &[2; 64]).unwrap();
+            ev.eval_circuit(&*circ, &ev_xs, &ys).unwrap()
+        });
+        garbler.join().unwrap();
+        let out = evaluator.join().unwrap();
+        assert_eq!(out, target);
+    }
+
+    /// The outputs of the garbler and evaluator, and their stats.
+    type ConfiguredRun = (
+        Option<Vec<Modulus>>,
+        Vec<Modulus>,
+        Option<TwopacStats>,
+        Option<TwopacStats>,
+    );
+
+    /// Run AES on random inputs between parties built from `config`, checking
+    /// the evaluator's output.
+    fn run_configured<S, R>(config: SemiHonestBuilder<S, R>) -> ConfiguredRun
+    where
+        S: OtSender<Msg = Block> + scuttlebutt::SemiHonest + Send + 'static,
+        R: OtReceiver<Msg = Block> + scuttlebutt::SemiHonest + 'static,
+    {
+        run_configured_with::<_, _, WireMod2, _>(config, unix_channel_pair())
+    }
+
+    /// Run AES as [`run_configured`] does, with parties of `Wire` on the
+    /// garbler's and evaluator's `channels`.
+    fn run_configured_with<S, R, Wire, C>(
+        config: SemiHonestBuilder<S, R>,
+        channels: (C, C),
+    ) -> ConfiguredRun
+    where
+        S: OtSender<Msg = Block> + scuttlebutt::SemiHonest + Send + 'static,
+        R: OtReceiver<Msg = Block> + scuttlebutt::SemiHonest + 'static,
+        Wire: WireLabel + Send + 'static,
+        C: AbstractChannel + Send + 'static,
+        BinaryCircuit: EvaluableCircuit<Garbler<C, AesRng, S, Wire>>
+            + EvaluableCircuit<Evaluator<C, AesRng, R, Wire>>,
+    {
+        let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap();
+        let circ = Arc::new(circ);
+        let mut rng = AesRng::new();
+        let gb_inputs = (0..128)
+            .map(|_| Modulus::from(rng.gen_bool()))
+            .collect_vec();
+        let ev_inputs = (0..128)
+            .map(|_| Modulus::from(rng.gen_bool()))
+            .collect_vec();
+        let target = eval_plain(&*circ, &gb_inputs, &ev_inputs).unwrap();
 
-        let target = eval_plain(&circ, &vec![0_u16; 128], &vec![0_u16; 128]).unwrap();
+        let circ_ = Arc::clone(&circ);
+        let (sender, receiver) = channels;
+        let gb_config = config.clone();
+        let handle = std::thread::spawn(move || {
+            let mut gb = gb_config
+                .build_garbler::<_, _, Wire>(sender, AesRng::new())
+                .unwrap();
+            let xs = gb.encode_many(&gb_inputs, &[2; 128]).unwrap();
+            let ys = gb.receive_many(&[2; 128]).unwrap();
+            let out = gb.eval_circuit(&*circ_, &xs, &ys).unwrap();
+            gb.get_channel().flush().unwrap();
+            (out, gb.stats())
+        });
+        let mut ev = config
+            .build_evaluator::<_, _, Wire>(receiver, AesRng::new())
+            .unwrap();
+        let xs = ev.receive_many(&[2; 128]).unwrap();
+        let ys = ev.encode_many(&ev_inputs, &[2; 128]).unwrap();
+        let out = ev.eval_circuit(&*circ, &xs, &ys).unwrap();
+        let (gb_out, gb_stats) = handle.join().unwrap();
         assert_eq!(out, target);
+        (gb_out, out, gb_stats, ev.stats())
     }
 
     #[test]
-    fn test_aes_arithmetic() {
+    fn test_config_defaults() {
+        let (gb_out, _, gb_stats, ev_stats) = run_configured(SemiHonest::builder());
+        assert_eq!(gb_out, None);
+        assert_eq!(gb_stats, None);
+        assert_eq!(ev_stats, None);
+    }
+
+    #[test]
+    fn test_config_flush_threshold() {
+        let config = SemiHonest::builder().flush_threshold(1).stats(true);
+        let (_, _, gb_stats, _) = run_configured(config);
+        // A flush after every AND gate and every output.
+        assert_eq!(gb_stats.unwrap().flushes, 6800 + 128);
+    }
+
+    #[test]
+    fn test_config_ot() {
+        let config = SemiHonest::builder()
+            .ot::<KosSender, KosReceiver>()
+            .ot_chunk_size(7)
+            .stats(true);
+        let (_, _, gb_stats, ev_stats) = run_configured(config);
+        for stats in [gb_stats.unwrap(), ev_stats.unwrap()] {
+            assert_eq!(
+                (stats.ots, stats.ot_batches, stats.flushes, stats.session_id),
+                (128, 19, 0, None)
+            );
+        }
+    }
+
+    #[test]
+    fn test_stats() {
         let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
             "../../../circuits/AES-non-expanded.txt"
         )))
         .unwrap();
-        test_circuit::<_, AllWire>(circ);
+        let ngates = circ.stats().ngates();
+        for mode in [OutputMode::Evaluator, OutputMode::Both] {
+            let config = SemiHonest::builder().output_mode(mode).stats(true);
+            let (_, _, gb_stats, ev_stats) = run_configured(config);
+            let (gb_stats, ev_stats) = (gb_stats.unwrap(), ev_stats.unwrap());
+            // Every byte one party sends, the other reads.
+            assert_eq!(gb_stats.bytes_written, ev_stats.bytes_read);
+            assert_eq!(ev_stats.bytes_written, gb_stats.bytes_read);
+            // The garbled gates and the output ciphertexts are most of it.
+            assert!(gb_stats.bytes_written > 16 * 2 * 6800);
+            for stats in [&gb_stats, &ev_stats] {
+                assert_eq!(stats.ots, 128);
+                assert_eq!(stats.gates, ngates);
+                assert!(stats.input_time > Duration::ZERO);
+                assert!(stats.eval_time > Duration::ZERO);
+            }
+        }
     }
 
     #[test]
-    fn test_aes_binary() {
+    fn test_config_security() {
+        let config = SemiHonest::builder()
+            .ot::<KosSender, KosReceiver>()
+            .security(SecurityParams::SIMULATION)
+            .output_mode(OutputMode::Both);
+        let (gb_out, ev_out, _, _) = run_configured(config);
+        assert_eq!(gb_out, Some(ev_out));
+        let security = SecurityParams::new(96, 80, 64).unwrap();
+        run_configured(SemiHonest::builder().security(security));
+    }
+
+    #[test]
+    fn test_config_simulation_sends_less() {
+        let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap();
+        // The bytes each party writes under a profile, counted on the channel.
+        let written = |security: SecurityParams| {
+            let (sender, receiver) = scuttlebutt::track_unix_channel_pair();
+            let (gb, ev) = (sender.clone(), receiver.clone());
+            run_configured_with::<_, _, WireMod2, _>(
+                SemiHonest::builder().security(security),
+                (sender, receiver),
+            );
+            let bytes = |c: &TrackUnixChannel| (c.kilobits_written() * 1000.0 / 8.0).round() as u64;
+            (bytes(&gb), bytes(&ev))
+        };
+        // The labels of the garbler's inputs and the garbled circuit, which
+        // the profile sizes.
+        let garbled = |security: SecurityParams| {
+            (128 * security.label_bits() / 8 + circ.garbled_size_estimate(security)) as u64
+        };
+        let (default_gb, default_ev) = written(SecurityParams::DEFAULT);
+        let (simulation_gb, simulation_ev) = written(SecurityParams::SIMULATION);
+        // The handshake and the oblivious transfers cost the same under both.
+        assert_eq!(
+            default_gb - garbled(SecurityParams::DEFAULT),
+            simulation_gb - garbled(SecurityParams::SIMULATION)
+        );
+        assert_eq!(default_ev, simulation_ev);
+        // Each label, of an input, an AND gate or the circuit's one constant,
+        // takes half the bytes, and each output hash a quarter.
+        assert_eq!(
+            default_gb - simulation_gb,
+            (128 + 2 * 6800 + 1) * 8 + 128 * 2 * 12
+        );
+    }
+
+    #[test]
+    fn test_config_long_term_security() {
+        let config = SemiHonest::builder()
+            .security(SecurityParams::LONG_TERM)
+            .output_mode(OutputMode::Both)
+            .stats(true);
+        let (gb_out, ev_out, gb_stats, ev_stats) =
+            run_configured_with::<_, _, WideWireMod2, _>(config.clone(), unix_channel_pair());
+        assert_eq!(gb_out, Some(ev_out));
+        // Two oblivious transfers of each evaluator input, one per half of
+        // its labels.
+        assert_eq!(gb_stats.unwrap().ots, 2 * 128);
+        assert_eq!(ev_stats.unwrap().ots, 2 * 128);
+
+        // Parties whose wires cannot hold the labels, or that would send them
+        // as blocks, are not made.
+        let (sender, receiver) = unix_channel_pair();
+        assert!(matches!(
+            config.build_garbler::<_, _, WireMod2>(sender, AesRng::new()),
+            Err(TwopacError::Security(_))
+        ));
+        let config = config.output_mode(OutputMode::Garbler);
+        assert!(matches!(
+            config.build_evaluator::<_, _, WideWireMod2>(receiver, AesRng::new()),
+            Err(TwopacError::Security(_))
+        ));
+    }
+
+    #[test]
+    fn test_config_output_mode() {
+        let config = SemiHonest::builder().output_mode(OutputMode::Both);
+        let (gb_out, ev_out, _, _) = run_configured(config);
+        assert_eq!(gb_out, Some(ev_out));
+    }
+
+    #[test]
+    fn test_config_stream_outputs() {
+        let config = SemiHonest::builder()
+            .stream_outputs(true)
+            .output_mode(OutputMode::Both);
+        let (gb_out, ev_out, _, _) = run_configured(config.clone());
+        assert_eq!(gb_out, Some(ev_out));
+        run_configured(config.hooks(Hooks::new().every(100)));
+    }
+
+    /// Run a circuit outputting `x & y` first and then the end of a long chain
+    /// of gates, recording the gates the evaluator has done when each output
+    /// reaches its callback.
+    fn stream_outputs(config: SemiHonestBuilder) -> (usize, Vec<(usize, Modulus, usize)>) {
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.garbler_input(2);
+        let y = b.evaluator_input(2);
+        let early = b.and(&x, &y).unwrap();
+        b.output(&early).unwrap();
+        let mut z = early;
+        for _ in 0..500 {
+            z = b.xor(&z, &x).unwrap();
+            z = b.and(&z, &y).unwrap();
+        }
+        b.output(&z).unwrap();
+        let circ = Arc::new(b.finish());
+        let total = circ.get_gates().len();
+        let target = eval_plain(&*circ, &[1], &[1]).unwrap();
+
+        let circ_ = Arc::clone(&circ);
+        let (sender, receiver) = unix_channel_pair();
+        let gb_config = config.clone().hooks(Hooks::new().every(1));
+        let handle = std::thread::spawn(move || {
+            let mut gb = gb_config
+                .build_garbler::<_, _, WireMod2>(sender, AesRng::new())
+                .unwrap();
+            let xs = gb.encode_many(&[1], &[2]).unwrap();
+            let ys = gb.receive_many(&[2]).unwrap();
+            gb.eval_circuit(&*circ_, &xs, &ys).unwrap();
+            gb.get_channel().flush().unwrap();
+        });
+        let done = Arc::new(AtomicUsize::new(0));
+        let done_ = Arc::clone(&done);
+        let hooks = Hooks::new()
+            .every(1)
+            .on_progress(move |n, _| done_.store(n, Ordering::SeqCst));
+        let mut ev = config
+            .hooks(hooks)
+            .build_evaluator::<_, _, WireMod2>(receiver, AesRng::new())
+            .unwrap();
+        let xs = ev.receive_many(&[2]).unwrap();
+        let ys = ev.encode_many(&[1], &[2]).unwrap();
+        let mut streamed = Vec::new();
+        let out = ev
+            .eval_circuit_streaming(&*circ, &xs, &ys, |i, v| {
+                streamed.push((i, v, done.load(Ordering::SeqCst)))
+            })
+            .unwrap();
+        handle.join().unwrap();
+        assert_eq!(out, target);
+        (total, streamed)
+    }
+
+    #[test]
+    fn test_stream_outputs() {
+        // The first output is decoded right after the third gate, long before
+        // the others are evaluated.
+        let (total, streamed) = stream_outputs(SemiHonest::builder().stream_outputs(true));
+        assert_eq!(streamed, [(0, 1, 2), (1, 1, total - 1)]);
+
+        // Without streaming, both are decoded once every gate is done.
+        let (total, streamed) = stream_outputs(SemiHonest::builder());
+        assert_eq!(streamed, [(0, 1, total), (1, 1, total)]);
+    }
+
+    #[test]
+    fn test_grouped_outputs() {
+        // An adder outputting its sum and its carry.
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.bin_garbler_input(8);
+        let y = b.bin_evaluator_input(8);
+        let (z, carry) = b.bin_addition(&x, &y).unwrap();
+        b.output_group(z.wires()).unwrap();
+        b.output_group(&[carry]).unwrap();
+        let circ = Arc::new(b.finish());
+        assert_eq!(circ.output_widths(), [8, 1]);
+
+        let mut rng = AesRng::new();
+        let gb_inputs = (0..8).map(|_| rng.gen_residue(2)).collect_vec();
+        let ev_inputs = (0..8).map(|_| rng.gen_residue(2)).collect_vec();
+        let target = eval_plain_grouped(&*circ, &gb_inputs, &ev_inputs).unwrap();
+        assert_eq!(
+            target.concat(),
+            eval_plain(&*circ, &gb_inputs, &ev_inputs).unwrap()
+        );
+
+        let (en, gc) = crate::classic::garble::<WireMod2, _>(&*circ).unwrap();
+        let xs = en.encode_garbler_inputs(&gb_inputs).unwrap();
+        let ys = en.encode_evaluator_inputs(&ev_inputs).unwrap();
+        assert_eq!(gc.eval_grouped(&*circ, &xs, &ys).unwrap(), target);
+        assert_eq!(gc.eval(&*circ, &xs, &ys).unwrap(), target.concat());
+
+        let config = SemiHonest::builder().output_mode(OutputMode::Both);
+        let circ_ = Arc::clone(&circ);
+        let (sender, receiver) = unix_channel_pair();
+        let gb_config = config.clone();
+        let handle = std::thread::spawn(move || {
+            let mut gb = gb_config
+                .build_garbler::<_, _, WireMod2>(sender, AesRng::new())
+                .unwrap();
+            let xs = gb.encode_many(&gb_inputs, &[2; 8]).unwrap();
+            let ys = gb.receive_many(&[2; 8]).unwrap();
+            gb.eval_circuit_grouped(&*circ_, &xs, &ys).unwrap()
+        });
+        let mut ev = config
+            .build_evaluator::<_, _, WireMod2>(receiver, AesRng::new())
+            .unwrap();
+        let xs = ev.receive_many(&[2; 8]).unwrap();
+        let ys = ev.encode_many(&ev_inputs, &[2; 8]).unwrap();
+        assert_eq!(ev.eval_circuit_grouped(&*circ, &xs, &ys).unwrap(), target);
+        assert_eq!(handle.join().unwrap(), Some(target));
+    }
+
+    #[test]
+    fn test_close() {
         let circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/adder64.txt"
+        )))
+        .unwrap();
+        let config = SemiHonest::builder();
+        let closed = |e: TwopacError| matches!(e, TwopacError::Closed);
+        let (_, (out, _)) = run_parties(
+            |channel| {
+                let mut gb =
+                    config.build_garbler_from_seed::<_, WireMod2>(channel, Block::from(1))?;
+                let xs = gb.encode_many(&[1; 64], &[2; 64])?;
+                let ys = gb.receive_many(&[2; 64])?;
+                gb.eval_circuit(&circ, &xs, &ys)?;
+                assert!(!gb.is_closed());
+                gb.close()?;
+                gb.close()?;
+                assert!(gb.is_closed());
+                assert!(closed(gb.encode(1, 2).unwrap_err()));
+                assert!(closed(gb.receive_many(&[2]).unwrap_err()));
+                assert!(closed(gb.xor(&xs[0], &ys[0]).unwrap_err()));
+                assert!(closed(gb.and(&xs[0], &ys[0]).unwrap_err()));
+                assert!(closed(gb.eval_circuit(&circ, &xs, &ys).unwrap_err()));
+                assert!(closed(gb.encode_public(&[1], &[2]).unwrap_err()));
+                Ok(())
+            },
+            |channel| {
+                let mut ev =
+                    config.build_evaluator_from_seed::<_, WireMod2>(channel, Block::from(2))?;
+                let xs = ev.receive_many(&[2; 64])?;
+                let ys = ev.encode_many(&[0; 64], &[2; 64])?;
+                let out = ev.eval_circuit(&circ, &xs, &ys)?;
+                ev.close()?;
+                ev.close()?;
+                assert!(ev.is_closed());
+                assert!(closed(ev.receive_many(&[2]).unwrap_err()));
+                assert!(closed(ev.encode_many(&[1], &[2]).unwrap_err()));
+                assert!(closed(ev.and(&xs[0], &ys[0]).unwrap_err()));
+                assert!(closed(ev.output(&xs[0]).unwrap_err()));
+                assert!(closed(ev.eval_circuit(&circ, &xs, &ys).unwrap_err()));
+                Ok(out)
+            },
+        )
+        .unwrap();
+        assert_eq!(out, eval_plain(&circ, &[1; 64], &[0; 64]).unwrap());
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_zeroize_on_drop() {
+        fn assert_zeroize_on_drop<T: zeroize::ZeroizeOnDrop>() {}
+        assert_zeroize_on_drop::<Garbler<UnixChannel, AesRng, AlszSender, WireMod2>>();
+        assert_zeroize_on_drop::<Evaluator<UnixChannel, AesRng, AlszReceiver, WireMod2>>();
+        assert_zeroize_on_drop::<Garbler<UnixChannel, AesRng, KosSender, AllWire>>();
+        assert_zeroize_on_drop::<Evaluator<UnixChannel, AesRng, KosReceiver, AllWire>>();
+    }
+
+    #[test]
+    fn test_config_seed() {
+        type Sync = SyncChannel<io::Cursor<[u8; 4]>, io::Sink>;
+        let inputs = |seed| {
+            let config = SemiHonest::builder().seed(seed);
+            let channel = Sync::new(io::Cursor::new(PROTOCOL_VERSION.to_le_bytes()), io::sink());
+            let mut gb = config
+                .build_garbler::<_, _, AllWire>(channel, AesRng::new())
+                .unwrap();
+            gb.encode_many(&[0, 1, 2, 3], &[2, 3, 5, 7])
+                .unwrap()
+                .iter()
+                .map(|w| w.as_block())
+                .collect_vec()
+        };
+        assert_eq!(inputs(Block::from(1)), inputs(Block::from(1)));
+        assert_ne!(inputs(Block::from(1)), inputs(Block::from(2)));
+
+        // The seed fixes the garbling, not the outcome.
+        let config = SemiHonest::builder().seed(Block::from(1));
+        run_configured(config.clone());
+        run_configured(config);
+    }
+
+    #[test]
+    fn test_public_inputs() {
+        // The plaintext of AES is public, and only the key is the garbler's.
+        let mut circ = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
             "../../../circuits/AES-non-expanded.txt"
         )))
         .unwrap();
-        test_circuit::<_, WireMod2>(circ);
+        let plain = circ.clone();
+        circ.make_inputs_public(&[], &(0..128).collect_vec())
+            .unwrap();
+        assert_eq!(circ.num_evaluator_inputs(), 0);
+        assert_eq!(circ.num_public_inputs(), 128);
+        let circ = Arc::new(circ);
+        let mut rng = AesRng::new();
+        let key = (0..128)
+            .map(|_| Modulus::from(rng.gen_bool()))
+            .collect_vec();
+        let text = (0..128)
+            .map(|_| Modulus::from(rng.gen_bool()))
+            .collect_vec();
+        let target = eval_plain(&plain, &key, &text).unwrap();
+
+        let circ_ = Arc::clone(&circ);
+        let text_ = text.clone();
+        let config = SemiHonest::builder().stats(true);
+        let gb_config = config.clone();
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut gb = gb_config
+                .build_garbler::<_, _, WireMod2>(sender, AesRng::new())
+                .unwrap();
+            let xs = gb.encode_many(&key, &[2; 128]).unwrap();
+            let ps = gb.encode_public(&text_, &[2; 128]).unwrap();
+            gb.eval_circuit_with_public(&*circ_, &xs, &[], &ps).unwrap();
+            gb.get_channel().flush().unwrap();
+        });
+        let mut ev = config
+            .build_evaluator::<_, _, WireMod2>(receiver, AesRng::new())
+            .unwrap();
+        let xs = ev.receive_many(&[2; 128]).unwrap();
+        let ps = ev.encode_public(&text, &[2; 128]).unwrap();
+        let out = ev.eval_circuit_with_public(&*circ, &xs, &[], &ps).unwrap();
+        handle.join().unwrap();
+        assert_eq!(out, target);
+        assert_eq!(ev.stats().unwrap().ots, 0);
+    }
+
+    #[test]
+    fn test_public_inputs_mismatch() {
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut gb = GB::<AllWire>::new(sender, AesRng::new()).unwrap();
+            gb.encode_public(&[1, 2], &[2, 3]).map(drop)
+        });
+        let mut ev = EV::<AllWire>::new(receiver, AesRng::new()).unwrap();
+        let ev_result = ev.encode_public(&[1, 1], &[2, 3]).map(drop);
+        assert!(
+            matches!(ev_result, Err(TwopacError::PublicInputMismatch)),
+            "{:?}",
+            ev_result
+        );
+        let gb_result = handle.join().unwrap();
+        assert!(
+            matches!(gb_result, Err(TwopacError::PublicInputMismatch)),
+            "{:?}",
+            gb_result
+        );
+
+        // Values out of range are caught before anything is sent.
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut gb = GB::<AllWire>::new(sender, AesRng::new()).unwrap();
+            gb.encode_public(&[3], &[3]).map(drop)
+        });
+        let mut ev = EV::<AllWire>::new(receiver, AesRng::new()).unwrap();
+        assert!(matches!(
+            ev.encode_public(&[1], &[]),
+            Err(TwopacError::FancyError(FancyError::InvalidArgNum { .. }))
+        ));
+        assert!(matches!(
+            handle.join().unwrap(),
+            Err(TwopacError::FancyError(FancyError::InputOutOfRange { .. }))
+        ));
     }
 }
@@ -0,0 +1,421 @@
+//! Agreeing on the circuit to run, before the parties are made.
+//!
+//! The garbler [`propose`]s a circuit of its [`CircuitRegistry`] by name, and
+//! the evaluator [`accept`]s it if its own registry has a circuit of that
+//! name with the same digest and input widths, and its configuration matches
+//! the garbler's. Both then make their parties over the same channel, from
+//! their own copy of the circuit:
+//!
+//! ```ignore
+//! // The garbler.
+//! let agreed = propose(&mut channel, &registry, "cmp64", &config)?;
+//! let mut gb = config.build_garbler::<_, _, WireMod2>(channel, AesRng::new())?;
+//!
+//! // The evaluator.
+//! let agreed = accept(&mut channel, &registry, &config)?;
+//! let mut ev = config.build_evaluator::<_, _, WireMod2>(channel, AesRng::new())?;
+//! ```
+//!
+//! # Messages
+//!
+//! The garbler sends, as little-endian integers, the length of the name as a
+//! `u64` and its UTF-8 bytes, the 32 bytes of the digest, the number of
+//! garbler widths and then each, the same for the evaluator widths, all as
+//! `u64`s, and the options: the [`PROTOCOL_VERSION`](super::PROTOCOL_VERSION)
+//! as a `u32`, the output mode and whether outputs are streamed as a byte
+//! each, the OT chunk size and the hooks interval as `u64`s, 0 if unset, and
+//! the label width, statistical security, and hash width of the
+//! [`SecurityParams`](crate::SecurityParams) as `u16`s.
+//! The evaluator answers with a status byte, 0 if it accepts, and the 32
+//! bytes of the digest of its circuit of that name, all zero if it has none.
+//!
+//! Neither the OT protocol nor the `blake3-hash` feature is part of the
+//! options: parties disagreeing on them fail once they run.
+
+use super::{config::Config, OutputMode, SemiHonestBuilder, PROTOCOL_VERSION};
+use crate::{
+    circuit::{CircuitDigest, CircuitRegistry, RegisteredCircuit},
+    errors::{FancyError, TwopacError},
+};
+use scuttlebutt::AbstractChannel;
+
+/// The longest name of a circuit the evaluator reads.
+const MAX_NAME_LEN: usize = 1024;
+
+const ACCEPT: u8 = 0;
+const UNKNOWN_CIRCUIT: u8 = 1;
+const CIRCUIT_MISMATCH: u8 = 2;
+const INPUT_WIDTH_MISMATCH: u8 = 3;
+const OPTIONS_MISMATCH: u8 = 4;
+
+/// A circuit both parties agreed to run.
+#[derive(Clone, Debug)]
+pub struct Agreement {
+    /// The name of the circuit.
+    pub name: String,
+    /// This party's copy of the circuit, with its input widths.
+    pub circuit: RegisteredCircuit,
+}
+
+/// Propose the circuit `name` of `registry` to the evaluator, as the garbler
+/// configured by `config`.
+///
+/// Fails with [`TwopacError::UnknownCircuit`] if either party has no circuit
+/// of that name, [`TwopacError::CircuitMismatch`] if their circuits differ,
+/// [`TwopacError::InputWidthMismatch`] if they split the inputs differently,
+/// and [`TwopacError::OptionsMismatch`] if their configurations differ.
+pub fn propose<C: AbstractChannel, S, R>(
+    channel: &mut C,
+    registry: &CircuitRegistry,
+    name: &str,
+    config: &SemiHonestBuilder<S, R>,
+) -> Result<Agreement, TwopacError> {
+    let circuit = registry
+        .get(name)
+        .ok_or_else(|| TwopacError::UnknownCircuit(name.to_string()))?;
+    channel.write_u64(name.len() as u64)?;
+    channel.write_bytes(name.as_bytes())?;
+    channel.write_bytes(circuit.digest().as_bytes())?;
+    write_widths(channel, circuit.garbler_widths())?;
+    write_widths(channel, circuit.evaluator_widths())?;
+    channel.write_bytes(&options(&config.config))?;
+    channel.flush()?;
+
+    let status = channel.read_u8()?;
+    let mut theirs = [0; 32];
+    channel.read_bytes(&mut theirs)?;
+    match status {
+        ACCEPT => Ok(Agreement {
+            name: name.to_string(),
+            circuit: circuit.clone(),
+        }),
+        UNKNOWN_CIRCUIT => Err(TwopacError::UnknownCircuit(name.to_string())),
+        CIRCUIT_MISMATCH => Err(TwopacError::CircuitMismatch {
+            ours: circuit.digest(),
+            theirs: CircuitDigest::from_bytes(theirs),
+        }),
+        INPUT_WIDTH_MISMATCH => Err(TwopacError::InputWidthMismatch),
+        OPTIONS_MISMATCH => Err(TwopacError::OptionsMismatch),
+        _ => Err(invalid(format!("unknown status {}", status))),
+    }
+}
+
+/// Accept the circuit the garbler proposes if `registry` has a circuit of
+/// its name, digest, and input widths, as the evaluator configured by
+/// `config`.
+///
+/// Fails as [`propose`] does, telling the garbler why.
+pub fn accept<C: AbstractChannel, S, R>(
+    channel: &mut C,
+    registry: &CircuitRegistry,
+    config: &SemiHonestBuilder<S, R>,
+) -> Result<Agreement, TwopacError> {
+    let len = channel.read_u64()?;
+    if len > MAX_NAME_LEN as u64 {
+        return Err(invalid(format!("a name of {} bytes", len)));
+    }
+    let name = String::from_utf8(channel.read_vec(len as usize)?)
+        .map_err(|_| invalid("a name that is not UTF-8".to_string()))?;
+    let mut digest = [0; 32];
+    channel.read_bytes(&mut digest)?;
+    let theirs = CircuitDigest::from_bytes(digest);
+
+    let circuit = match registry.get(&name) {
+        Some(circuit) => circuit,
+        None => {
+            reply(channel, UNKNOWN_CIRCUIT, &[0; 32])?;
+            return Err(TwopacError::UnknownCircuit(name));
+        }
+    };
+    let ours = circuit.digest();
+    if ours != theirs {
+        reply(channel, CIRCUIT_MISMATCH, ours.as_bytes())?;
+        return Err(TwopacError::CircuitMismatch { ours, theirs });
+    }
+    if !read_widths(channel, circuit.garbler_widths())?
+        || !read_widths(channel, circuit.evaluator_widths())?
+    {
+        reply(channel, INPUT_WIDTH_MISMATCH, ours.as_bytes())?;
+        return Err(TwopacError::InputWidthMismatch);
+    }
+    let mut their_options = [0; OPTIONS_LEN];
+    channel.read_bytes(&mut their_options)?;
+    if their_options != options(&config.config) {
+        reply(channel, OPTIONS_MISMATCH, ours.as_bytes())?;
+        return Err(TwopacError::OptionsMismatch);
+    }
+    reply(channel, ACCEPT, ours.as_bytes())?;
+    Ok(Agreement {
+        name,
+        circuit: circuit.clone(),
+    })
+}
+
+const OPTIONS_LEN: usize = 4 + 1 + 1 + 8 + 8 + 3 * 2;
+
+/// The options of `config` that both parties must share.
+fn options(config: &Config) -> [u8; OPTIONS_LEN] {
+    let mut bytes = [0; OPTIONS_LEN];
+    bytes[..4].copy_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+    bytes[4] = match config.output_mode {
+        OutputMode::Evaluator => 0,
+        OutputMode::Both => 1,
+        OutputMode::Garbler => 2,
+    };
+    bytes[5] = config.stream_outputs as u8;
+    let chunk = config.ot_chunk_size.unwrap_or(0) as u64;
+    bytes[6..14].copy_from_slice(&chunk.to_le_bytes());
+    let interval = config.hooks.as_ref().map_or(0, |h| h.interval()) as u64;
+    bytes[14..22].copy_from_slice(&interval.to_le_bytes());
+    let security = config.security;
+    for (i, bits) in [
+        security.label_bits(),
+        security.statistical_bits(),
+        security.hash_bits(),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        bytes[22 + 2 * i..][..2].copy_from_slice(&(bits as u16).to_le_bytes());
+    }
+    bytes
+}
+
+fn write_widths<C: AbstractChannel>(channel: &mut C, widths: &[usize]) -> Result<(), TwopacError> {
+    channel.write_u64(widths.len() as u64)?;
+    for &w in widths {
+        channel.write_u64(w as u64)?;
+    }
+    Ok(())
+}
+
+/// Read widths, returning whether they are `ours`.
+///
+/// Reads no further than the first difference, so that a garbler cannot make
+/// the evaluator read more widths than it has.
+fn read_widths<C: AbstractChannel>(channel: &mut C, ours: &[usize]) -> Result<bool, TwopacError> {
+    if channel.read_u64()? != ours.len() as u64 {
+        return Ok(false);
+    }
+    for &w in ours {
+        if channel.read_u64()? != w as u64 {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+fn reply<C: AbstractChannel>(
+    channel: &mut C,
+    status: u8,
+    digest: &[u8; 32],
+) -> Result<(), TwopacError> {
+    channel.write_u8(status)?;
+    channel.write_bytes(digest)?;
+    channel.flush()?;
+    Ok(())
+}
+
+fn invalid(what: String) -> TwopacError {
+    FancyError::InvalidArg(format!("invalid circuit proposal: {}", what)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{eval_plain, BinaryCircuit, CircuitBuilder},
+        hooks::Hooks,
+        twopac::semihonest::SemiHonest,
+        util, Fancy, FancyBinary, FancyInput, SecurityParams, WireMod2,
+    };
+    use scuttlebutt::{unix_channel_pair, AesRng, UnixChannel};
+
+    type Outcome = (
+        Result<Agreement, TwopacError>,
+        Result<Agreement, TwopacError>,
+    );
+
+    /// Negotiate between a garbler proposing `name` from `gb_registry` and an
+    /// evaluator with `ev_registry`, returning what each gets, and the
+    /// channels to go on with.
+    fn negotiate(
+        gb_registry: CircuitRegistry,
+        ev_registry: CircuitRegistry,
+        name: &str,
+        gb_config: SemiHonestBuilder,
+        ev_config: SemiHonestBuilder,
+    ) -> (Outcome, UnixChannel, UnixChannel) {
+        let (mut sender, mut receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let agreed = accept(&mut receiver, &ev_registry, &ev_config);
+            (agreed, receiver)
+        });
+        let proposed = propose(&mut sender, &gb_registry, name, &gb_config);
+        let (accepted, receiver) = handle.join().unwrap();
+        ((proposed, accepted), sender, receiver)
+    }
+
+    #[test]
+    fn test_agreement() {
+        let registry = CircuitRegistry::embedded();
+        let config = SemiHonest::builder();
+        let ((proposed, accepted), sender, receiver) = negotiate(
+            registry.clone(),
+            registry,
+            "adder64",
+            config.clone(),
+            config.clone(),
+        );
+        let (proposed, accepted) = (proposed.unwrap(), accepted.unwrap());
+        assert_eq!(accepted.name, "adder64");
+        assert_eq!(proposed.circuit.digest(), accepted.circuit.digest());
+
+        // the parties go on over the same channel
+        let (x, y) = (123_456_789u128, 987_654_321u128);
+        let gb_config = config.clone();
+        let handle = std::thread::spawn(move || {
+            let c = proposed.circuit.circuit();
+            let mut gb = gb_config
+                .build_garbler::<_, _, WireMod2>(sender, AesRng::new())
+                .unwrap();
+            let xs = gb
+                .encode_many(&util::u128_to_bits(x, 64), &[2; 64])
+                .unwrap();
+            let ys = gb.receive_many(&[2; 64]).unwrap();
+            gb.eval_circuit(c.as_ref(), &xs, &ys).unwrap();
+            gb.get_channel().flush().unwrap();
+        });
+        let c = accepted.circuit.circuit();
+        let mut ev = config
+            .build_evaluator::<_, _, WireMod2>(receiver, AesRng::new())
+            .unwrap();
+        let xs = ev.receive_many(&[2; 64]).unwrap();
+        let ys = ev
+            .encode_many(&util::u128_to_bits(y, 64), &[2; 64])
+            .unwrap();
+        let out = ev.eval_circuit(c.as_ref(), &xs, &ys).unwrap();
+        handle.join().unwrap();
+        assert_eq!(util::u128_from_bits(&out), x + y);
+        assert_eq!(
+            out,
+            eval_plain(
+                c.as_ref(),
+                &util::u128_to_bits(x, 64),
+                &util::u128_to_bits(y, 64)
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_circuit_mismatch() {
+        let gb_registry = CircuitRegistry::embedded();
+        // the evaluator's "adder64" is another circuit of the same inputs
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.garbler_inputs(&[2; 64]);
+        let ys = b.evaluator_inputs(&[2; 64]);
+        for (x, y) in xs.iter().zip(&ys) {
+            let z = b.xor(x, y).unwrap();
+            b.output(&z).unwrap();
+        }
+        let other = b.finish();
+        let mut ev_registry = CircuitRegistry::new();
+        ev_registry
+            .insert("adder64", other, vec![64], vec![64])
+            .unwrap();
+        let config = SemiHonest::builder();
+        let ((proposed, accepted), _, _) = negotiate(
+            gb_registry.clone(),
+            ev_registry.clone(),
+            "adder64",
+            config.clone(),
+            config.clone(),
+        );
+        let ours = gb_registry.get("adder64").unwrap().digest();
+        let theirs = ev_registry.get("adder64").unwrap().digest();
+        assert!(matches!(
+            proposed,
+            Err(TwopacError::CircuitMismatch { ours: a, theirs: b }) if a == ours && b == theirs
+        ));
+        assert!(matches!(
+            accepted,
+            Err(TwopacError::CircuitMismatch { ours: a, theirs: b }) if a == theirs && b == ours
+        ));
+
+        // a name the evaluator does not know
+        let ((proposed, accepted), _, _) = negotiate(
+            gb_registry.clone(),
+            ev_registry,
+            "aes128",
+            config.clone(),
+            config.clone(),
+        );
+        assert!(matches!(proposed, Err(TwopacError::UnknownCircuit(name)) if name == "aes128"));
+        assert!(matches!(accepted, Err(TwopacError::UnknownCircuit(name)) if name == "aes128"));
+
+        // a name the garbler does not know fails before anything is sent
+        let (mut sender, _receiver) = unix_channel_pair();
+        assert!(matches!(
+            propose(&mut sender, &gb_registry, "sha-256", &config),
+            Err(TwopacError::UnknownCircuit(_))
+        ));
+    }
+
+    #[test]
+    fn test_width_mismatch() {
+        let gb_registry = CircuitRegistry::embedded();
+        let adder = gb_registry.get("adder64").unwrap().circuit().clone();
+        let mut ev_registry = CircuitRegistry::new();
+        ev_registry
+            .insert("adder64", adder, vec![32, 32], vec![64])
+            .unwrap();
+        let config = SemiHonest::builder();
+        let ((proposed, accepted), _, _) =
+            negotiate(gb_registry, ev_registry, "adder64", config.clone(), config);
+        assert!(matches!(proposed, Err(TwopacError::InputWidthMismatch)));
+        assert!(matches!(accepted, Err(TwopacError::InputWidthMismatch)));
+    }
+
+    #[test]
+    fn test_options_mismatch() {
+        let registry = CircuitRegistry::embedded();
+        for ev_config in [
+            SemiHonest::builder().output_mode(OutputMode::Both),
+            SemiHonest::builder().ot_chunk_size(16),
+            SemiHonest::builder().hooks(Hooks::new().every(100)),
+            SemiHonest::builder().security(SecurityParams::new(80, 40, 80).unwrap()),
+        ] {
+            let ((proposed, accepted), _, _) = negotiate(
+                registry.clone(),
+                registry.clone(),
+                "adder64",
+                SemiHonest::builder(),
+                ev_config,
+            );
+            assert!(matches!(proposed, Err(TwopacError::OptionsMismatch)));
+            assert!(matches!(accepted, Err(TwopacError::OptionsMismatch)));
+        }
+        // options local to a party may differ
+        let ((proposed, accepted), _, _) = negotiate(
+            registry.clone(),
+            registry,
+            "adder64",
+            SemiHonest::builder().flush_threshold(1 << 12).stats(true),
+            SemiHonest::builder(),
+        );
+        assert!(proposed.is_ok() && accepted.is_ok());
+    }
+
+    #[test]
+    fn test_hostile_proposal() {
+        let registry = CircuitRegistry::embedded();
+        let (mut sender, mut receiver) = unix_channel_pair();
+        sender.write_u64(u64::MAX).unwrap();
+        sender.flush().unwrap();
+        assert!(matches!(
+            accept(&mut receiver, &registry, &SemiHonest::builder()),
+            Err(TwopacError::FancyError(FancyError::InvalidArg(_)))
+        ));
+    }
+}
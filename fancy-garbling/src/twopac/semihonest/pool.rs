@@ -0,0 +1,351 @@
+//! A garbler running one circuit against many evaluators.
+//!
+//! A [`GarblerPool`] analyzes its circuit once, when it is made: it computes
+//! the liveness of the wires, the digest, the [`Informer`](crate::informer)
+//! statistics, including the projections sharing truth tables, and the
+//! [`estimate`] of a session. Each session against an evaluator then only
+//! pays for its own garbler, with fresh deltas, wire labels, and oblivious
+//! transfer, and holds no more wire labels at once than the circuit has live
+//! wires:
+//!
+//! ```ignore
+//! let pool = GarblerPool::new(circuit, SemiHonest::builder())?;
+//! let outputs = pool.run_parallel::<_, WireMod2>(vec![
+//!     (alice, my_inputs.clone()),
+//!     (bob, my_inputs),
+//! ]);
+//! ```
+//!
+//! Each evaluator runs an ordinary [`Evaluator`](super::Evaluator), built from
+//! [`GarblerPool::config`], receiving the garbler's inputs and encoding its
+//! own before evaluating [`GarblerPool::circuit`].
+//!
+//! The sessions share nothing secret: the garbler of each draws its seed from
+//! the RNG it is given, so a pool refuses a configuration with a fixed
+//! [`seed`](super::SemiHonestBuilder::seed), which would give every session
+//! the same labels and leak the garbler's inputs across them.
+
+use super::{estimate, CostEstimate, Garbler, OtCost, SemiHonestBuilder};
+use crate::{
+    circuit::{CircuitDigest, CircuitInfo, CircuitType, EvaluableCircuit, Liveness},
+    errors::{DummyError, FancyError, TwopacError},
+    informer::InformerStats,
+    wire::WireLabel,
+    FancyInput, Modulus,
+};
+use ocelot::ot::{AlszReceiver, AlszSender, Sender as OtSender};
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, AesRng, Block, SemiHonest};
+use std::sync::Arc;
+
+/// A circuit analyzed once, and garbled anew in each session against an
+/// evaluator.
+///
+/// A pool is `Sync` whenever its circuit is, so sessions can run on as many
+/// threads as there are evaluators; see [`GarblerPool::run_parallel`].
+pub struct GarblerPool<Circuit, S = AlszSender, R = AlszReceiver> {
+    circuit: Arc<Circuit>,
+    config: SemiHonestBuilder<S, R>,
+    liveness: Liveness,
+    digest: CircuitDigest,
+    info: InformerStats,
+    estimate: CostEstimate,
+    garbler_moduli: Vec<Modulus>,
+    evaluator_moduli: Vec<Modulus>,
+}
+
+impl<Circuit, S, R> GarblerPool<Circuit, S, R>
+where
+    Circuit: CircuitType + CircuitInfo,
+    S: OtCost,
+{
+    /// Analyze `circuit` for sessions built from `config`.
+    ///
+    /// Fails if the circuit cannot be evaluated, or if `config` fixes the
+    /// garbler's seed.
+    pub fn new(
+        circuit: impl Into<Arc<Circuit>>,
+        config: SemiHonestBuilder<S, R>,
+    ) -> Result<Self, TwopacError> {
+        if config.config.seed.is_some() {
+            return Err(FancyError::InvalidArg(
+                "the sessions of a pool cannot share a seed".to_string(),
+            )
+            .into());
+        }
+        let circuit = circuit.into();
+        let info = circuit.info().map_err(fancy_error)?;
+        let estimate = estimate(circuit.as_ref(), &config).map_err(fancy_error)?;
+        let garbler_moduli = circuit
+            .get_garbler_input_refs()
+            .iter()
+            .map(|r| r.modulus)
+            .collect();
+        let evaluator_moduli = circuit
+            .get_evaluator_input_refs()
+            .iter()
+            .map(|r| r.modulus)
+            .collect();
+        Ok(GarblerPool {
+            liveness: circuit.liveness(),
+            digest: circuit.digest(),
+            info,
+            estimate,
+            garbler_moduli,
+            evaluator_moduli,
+            circuit,
+            config,
+        })
+    }
+}
+
+impl<Circuit, S, R> GarblerPool<Circuit, S, R> {
+    /// The circuit garbled in each session.
+    pub fn circuit(&self) -> &Arc<Circuit> {
+        &self.circuit
+    }
+
+    /// The configuration of each session, which the evaluators must share.
+    pub fn config(&self) -> &SemiHonestBuilder<S, R> {
+        &self.config
+    }
+
+    /// The liveness of the wires of the circuit.
+    pub fn liveness(&self) -> &Liveness {
+        &self.liveness
+    }
+
+    /// The digest of the circuit.
+    pub fn digest(&self) -> CircuitDigest {
+        self.digest
+    }
+
+    /// The statistics of the circuit, as an `Informer` finds them.
+    pub fn info(&self) -> &InformerStats {
+        &self.info
+    }
+
+    /// The estimated cost of one session.
+    pub fn estimate(&self) -> &CostEstimate {
+        &self.estimate
+    }
+}
+
+impl<Circuit, S, R> GarblerPool<Circuit, S, R>
+where
+    Circuit: CircuitType,
+    S: OtSender<Msg = Block> + SemiHonest,
+{
+    /// Start a session against the evaluator at the other end of `channel`,
+    /// with a garbler drawing its deltas and wire labels from `rng`.
+    ///
+    /// Like [`Garbler::new`], this blocks until the evaluator is made too.
+    /// Every session must have an RNG of its own, seeded independently of
+    /// the others.
+    pub fn session<C, RNG, Wire>(
+        &self,
+        channel: C,
+        rng: RNG,
+    ) -> Result<Garbler<C, RNG, S, Wire>, TwopacError>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng + SeedableRng<Seed = Block>,
+        Wire: WireLabel,
+    {
+        self.config.build_garbler(channel, rng)
+    }
+
+    /// Garble the circuit in a session, on the garbler's `inputs` and the
+    /// evaluator's.
+    ///
+    /// The garbler encodes its inputs, the evaluator its own, and the circuit
+    /// is run, as [`Garbler::eval_circuit`] does. Returns the outputs if the
+    /// garbler learns them, and `None` otherwise.
+    pub fn garble<C, RNG, Wire>(
+        &self,
+        gb: &mut Garbler<C, RNG, S, Wire>,
+        inputs: &[Modulus],
+    ) -> Result<Option<Vec<Modulus>>, TwopacError>
+    where
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng + SeedableRng<Seed = Block>,
+        Wire: WireLabel,
+        Circuit: EvaluableCircuit<Garbler<C, RNG, S, Wire>>,
+    {
+        let xs = gb.encode_many(inputs, &self.garbler_moduli)?;
+        let ys = gb.receive_many(&self.evaluator_moduli)?;
+        let outputs = gb.run_circuit(self.circuit.as_ref(), &xs, &ys, &[], Some(&self.liveness))?;
+        gb.get_channel().flush()?;
+        Ok(outputs)
+    }
+
+    /// Run a whole session against the evaluator at the other end of
+    /// `channel`, with a garbler seeded from the operating system.
+    ///
+    /// See [`GarblerPool::garble`].
+    pub fn run<C, Wire>(
+        &self,
+        channel: C,
+        inputs: &[Modulus],
+    ) -> Result<Option<Vec<Modulus>>, TwopacError>
+    where
+        C: AbstractChannel,
+        Wire: WireLabel,
+        Circuit: EvaluableCircuit<Garbler<C, AesRng, S, Wire>>,
+    {
+        let mut gb = self.session::<_, _, Wire>(channel, AesRng::new())?;
+        self.garble(&mut gb, inputs)
+    }
+
+    /// Run a session against each evaluator of `sessions`, on its own thread,
+    /// with the garbler's inputs for that session.
+    ///
+    /// Returns what each session returns, in order, once all are done; a
+    /// session failing does not stop the others.
+    pub fn run_parallel<C, Wire>(
+        &self,
+        sessions: Vec<(C, Vec<Modulus>)>,
+    ) -> Vec<Result<Option<Vec<Modulus>>, TwopacError>>
+    where
+        Self: Sync,
+        C: AbstractChannel + Send,
+        Wire: WireLabel,
+        Circuit: EvaluableCircuit<Garbler<C, AesRng, S, Wire>>,
+    {
+        std::thread::scope(|scope| {
+            // Every session is spawned before any is joined.
+            let mut handles = Vec::with_capacity(sessions.len());
+            for (channel, inputs) in sessions {
+                handles.push(scope.spawn(move || self.run::<_, Wire>(channel, &inputs)));
+            }
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap_or_else(|e| std::panic::resume_unwind(e)))
+                .collect()
+        })
+    }
+}
+
+fn fancy_error(e: DummyError) -> TwopacError {
+    match e {
+        DummyError::FancyError(e) => e.into(),
+        e => FancyError::InvalidArg(e.to_string()).into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{eval_plain, BinaryCircuit},
+        twopac::semihonest::{Evaluator, OutputMode, SemiHonest},
+        util::{self, RngExt},
+        WireMod2,
+    };
+    use scuttlebutt::{unix_channel_pair, UnixChannel};
+
+    fn adder64() -> BinaryCircuit {
+        BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../../circuits/adder64.txt"
+        )))
+        .unwrap()
+    }
+
+    /// Run the evaluator of a session of `pool` with `inputs`, returning the
+    /// garbler's input labels it receives and the outputs.
+    fn evaluate(
+        pool: &GarblerPool<BinaryCircuit>,
+        channel: UnixChannel,
+        inputs: &[Modulus],
+    ) -> (Vec<WireMod2>, Vec<Modulus>) {
+        let c = pool.circuit();
+        let mut ev: Evaluator<_, _, _, WireMod2> = pool
+            .config()
+            .build_evaluator(channel, AesRng::new())
+            .unwrap();
+        let xs = ev.receive_many(&vec![2; c.num_garbler_inputs()]).unwrap();
+        let ys = ev
+            .encode_many(inputs, &vec![2; c.num_evaluator_inputs()])
+            .unwrap();
+        let outputs = ev.eval_circuit(c.as_ref(), &xs, &ys).unwrap();
+        ev.get_channel().flush().unwrap();
+        (xs, outputs)
+    }
+
+    #[test]
+    fn test_parallel_sessions() {
+        let config = SemiHonest::builder().output_mode(OutputMode::Both);
+        let pool = Arc::new(GarblerPool::new(adder64(), config).unwrap());
+        assert_eq!(
+            pool.info().num_muls(),
+            pool.circuit().get_num_nonfree_gates()
+        );
+        assert_eq!(pool.digest(), pool.circuit().digest());
+
+        let mut rng = AesRng::new();
+        let x = rng.gen_u64() as u128;
+        let ys = (0..3).map(|_| rng.gen_u64() as u128).collect::<Vec<_>>();
+        let (channels, peers): (Vec<_>, Vec<_>) = (0..3).map(|_| unix_channel_pair()).unzip();
+        let mut handles = Vec::new();
+        for (channel, &y) in peers.into_iter().zip(&ys) {
+            let pool = pool.clone();
+            handles.push(std::thread::spawn(move || {
+                evaluate(&pool, channel, &util::u128_to_bits(y, 64)).1
+            }));
+        }
+        let sessions = channels
+            .into_iter()
+            .map(|channel| (channel, util::u128_to_bits(x, 64)))
+            .collect();
+        let gb_outputs = pool.run_parallel::<_, WireMod2>(sessions);
+        for ((handle, gb), y) in handles.into_iter().zip(gb_outputs).zip(ys) {
+            let ev = handle.join().unwrap();
+            let expected = eval_plain(
+                pool.circuit().as_ref(),
+                &util::u128_to_bits(x, 64),
+                &util::u128_to_bits(y, 64),
+            )
+            .unwrap();
+            assert_eq!(ev, expected);
+            assert_eq!(util::u128_from_bits(&ev), (x + y) % (1 << 64));
+            assert_eq!(gb.unwrap(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn test_independent_sessions() {
+        let pool = Arc::new(GarblerPool::new(adder64(), SemiHonest::builder()).unwrap());
+        let x = util::u128_to_bits(12345, 64);
+        let y = util::u128_to_bits(678, 64);
+        let mut labels = Vec::new();
+        let mut deltas = Vec::new();
+        for _ in 0..2 {
+            let (sender, receiver) = unix_channel_pair();
+            let ev_pool = pool.clone();
+            let ev_y = y.clone();
+            let handle = std::thread::spawn(move || evaluate(&ev_pool, receiver, &ev_y));
+            let mut gb = pool
+                .session::<_, _, WireMod2>(sender, AesRng::new())
+                .unwrap();
+            deltas.push(gb.delta(2));
+            assert_eq!(pool.garble(&mut gb, &x).unwrap(), None);
+            let (xs, outputs) = handle.join().unwrap();
+            assert_eq!(util::u128_from_bits(&outputs), 12345 + 678);
+            labels.push(xs);
+        }
+        // the same inputs, under labels and deltas nothing in common
+        assert_ne!(deltas[0], deltas[1]);
+        for a in &labels[0] {
+            assert!(labels[1].iter().all(|b| a.as_block() != b.as_block()));
+        }
+    }
+
+    #[test]
+    fn test_seed_refused() {
+        let config = SemiHonest::builder().seed(Block::default());
+        assert!(matches!(
+            GarblerPool::<BinaryCircuit>::new(adder64(), config),
+            Err(TwopacError::FancyError(FancyError::InvalidArg(_)))
+        ));
+    }
+}
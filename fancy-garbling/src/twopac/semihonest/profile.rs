@@ -0,0 +1,158 @@
+//! Throughput measurements of semi-honest two-party computation.
+
+use super::{Evaluator, Garbler};
+use crate::{circuit::EvaluableCircuit, errors::TwopacError, FancyInput, Modulus, WireLabel};
+use itertools::Itertools;
+use ocelot::ot::{AlszReceiver, AlszSender};
+use rand::{Rng, SeedableRng};
+use scuttlebutt::{unix_channel_pair, AesRng, UnixChannel};
+use std::time::{Duration, Instant};
+
+type Gb<Wire> = Garbler<UnixChannel, AesRng, AlszSender, Wire>;
+type Ev<Wire> = Evaluator<UnixChannel, AesRng, AlszReceiver, Wire>;
+
+/// Per-phase wall time of a semi-honest two-party computation, as observed by
+/// the evaluator. Produced by [`profile`].
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TwopacProfile {
+    /// The number of non-free gates in the circuit.
+    pub nonfree_gates: usize,
+    /// Wall time spent exchanging inputs, including oblivious transfer.
+    pub input_time: Duration,
+    /// Wall time spent evaluating the circuit and decoding its outputs.
+    pub circuit_time: Duration,
+}
+
+impl TwopacProfile {
+    /// Total wall time of the computation.
+    pub fn total_time(&self) -> Duration {
+        self.input_time + self.circuit_time
+    }
+
+    /// The number of non-free gates evaluated per second.
+    pub fn gates_per_sec(&self) -> f64 {
+        self.nonfree_gates as f64 / self.circuit_time.as_secs_f64()
+    }
+
+    /// The wall time per non-free gate, with which to
+    /// [calibrate](super::CostEstimate::calibrate) cost estimates.
+    pub fn time_per_nonfree_gate(&self) -> Duration {
+        self.circuit_time
+            .checked_div(self.nonfree_gates.try_into().unwrap_or(u32::MAX))
+            .unwrap_or(self.circuit_time)
+    }
+
+    /// Serialize the profile as JSON.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("profile always serializes")
+    }
+}
+
+impl std::fmt::Display for TwopacProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "two-party computation profile:")?;
+        writeln!(f, "  non-free gates:     {:16}", self.nonfree_gates)?;
+        writeln!(f, "  inputs:          {:16.3?}", self.input_time)?;
+        writeln!(
+            f,
+            "  circuit:         {:16.3?} // {:.0} gates/s",
+            self.circuit_time,
+            self.gates_per_sec()
+        )?;
+        writeln!(f, "  total:           {:16.3?}", self.total_time())?;
+        Ok(())
+    }
+}
+
+/// Measure the time taken to run `c` between a semi-honest garbler and
+/// evaluator connected by a local socket, on random inputs drawn from `rng`.
+///
+/// A warmup iteration is run before the timed one.
+pub fn profile<Wire, Circuit, R>(c: &Circuit, rng: &mut R) -> Result<TwopacProfile, TwopacError>
+where
+    Wire: WireLabel,
+    Circuit: EvaluableCircuit<Gb<Wire>> + EvaluableCircuit<Ev<Wire>> + Sync,
+    R: Rng,
+{
+    let gb_moduli = (0..c.num_garbler_inputs())
+        .map(|i| c.garbler_input_mod(i))
+        .collect_vec();
+    let ev_moduli = (0..c.num_evaluator_inputs())
+        .map(|i| c.evaluator_input_mod(i))
+        .collect_vec();
+    let gb_inps = gb_moduli
+        .iter()
+        .map(|q| rng.gen::<Modulus>() % q)
+        .collect_vec();
+    let ev_inps = ev_moduli
+        .iter()
+        .map(|q| rng.gen::<Modulus>() % q)
+        .collect_vec();
+
+    let run = |rng: &mut R| -> Result<TwopacProfile, TwopacError> {
+        let (sender, receiver) = unix_channel_pair();
+        let gb_rng = AesRng::from_seed(rng.gen());
+        let ev_rng = AesRng::from_seed(rng.gen());
+        std::thread::scope(|s| {
+            let handle = s.spawn(|| -> Result<(), TwopacError> {
+                let mut gb = Gb::<Wire>::new(sender, gb_rng)?;
+                let xs = gb.encode_many(&gb_inps, &gb_moduli)?;
+                let ys = gb.receive_many(&ev_moduli)?;
+                c.eval(&mut gb, &xs, &ys)?;
+                Ok(())
+            });
+
+            let start = Instant::now();
+            let mut ev = Ev::<Wire>::new(receiver, ev_rng)?;
+            let xs = ev.receive_many(&gb_moduli)?;
+            let ys = ev.encode_many(&ev_inps, &ev_moduli)?;
+            let input_time = start.elapsed();
+
+            let start = Instant::now();
+            c.eval(&mut ev, &xs, &ys)?;
+            let circuit_time = start.elapsed();
+
+            handle.join().expect("garbler thread panicked")?;
+            Ok(TwopacProfile {
+                nonfree_gates: c.get_num_nonfree_gates(),
+                input_time,
+                circuit_time,
+            })
+        })
+    };
+
+    // Warm up caches, the allocator, and the socket before measuring.
+    run(rng)?;
+    run(rng)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{BinaryCircuit, CircuitBuilder, CircuitType},
+        BinaryGadgets, BundleGadgets, Fancy, WireMod2,
+    };
+
+    #[test]
+    fn profile_adder64() {
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.bin_garbler_input(64);
+        let y = b.bin_evaluator_input(64);
+        let (z, carry) = b.bin_addition(&x, &y).unwrap();
+        b.output_bundle(&z).unwrap();
+        b.output(&carry).unwrap();
+        let circ = b.finish();
+
+        let mut rng = AesRng::new();
+        let p = profile::<WireMod2, _, _>(&circ, &mut rng).unwrap();
+        assert_eq!(p.nonfree_gates, circ.get_num_nonfree_gates());
+        assert!(p.input_time > Duration::ZERO);
+        assert!(p.circuit_time > Duration::ZERO);
+        assert_eq!(p.total_time(), p.input_time + p.circuit_time);
+        assert!(p.gates_per_sec().is_finite());
+        assert!(p.to_string().contains("non-free gates"));
+    }
+}
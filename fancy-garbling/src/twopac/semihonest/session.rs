@@ -0,0 +1,38 @@
+//! Session ids tossed by the parties, and the randomness derived from them.
+
+use super::Party;
+use crate::errors::TwopacError;
+use rand::SeedableRng;
+use scuttlebutt::{cointoss, AbstractChannel, AesRng, Block};
+use sha2::{Digest, Sha256};
+
+/// Toss a session id with the peer, the garbler committing to its share
+/// first. Each party's share is derived from its `party_seed`.
+pub(super) fn toss<C: AbstractChannel>(
+    channel: &mut C,
+    party_seed: Block,
+    party: Party,
+) -> Result<Block, TwopacError> {
+    let share = [hash(b"session share", party_seed, Block::default())];
+    let ids = match party {
+        Party::Garbler => cointoss::send(channel, &share)?,
+        Party::Evaluator => cointoss::receive(channel, &share)?,
+    };
+    Ok(ids[0])
+}
+
+/// The working RNG of a party of the session `session_id`.
+pub(super) fn rng(party_seed: Block, session_id: Block) -> AesRng {
+    AesRng::from_seed(hash(b"session rng", party_seed, session_id))
+}
+
+// The first 16 bytes of the SHA-256 hash of `label`, `seed`, and `id`.
+fn hash(label: &[u8], seed: Block, id: Block) -> Block {
+    let mut hash = Sha256::new();
+    hash.update(label);
+    hash.update(seed);
+    hash.update(id);
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&hash.finalize()[..16]);
+    Block::from(bytes)
+}
@@ -0,0 +1,230 @@
+//! SHA-256 of long messages in two-party computation, one block at a time.
+//!
+//! A [`Sha256Session`] runs the [`sha256_compress`] circuit once per 512-bit
+//! block of the padded message, on a garbler or an evaluator, and keeps the
+//! wires of the hash value between blocks. The hash value is thus never
+//! decoded or encoded again until [`Sha256Session::finalize`], and the only
+//! inputs of each block are the bits of the block itself. When the garbler
+//! holds the message, hashing it needs no oblivious transfer at all; when the
+//! evaluator does, it needs one for each bit of the message and none for the
+//! hash value.
+//!
+//! Both parties run a session, encoding or receiving each block as the party
+//! holding the message dictates:
+//!
+//! ```ignore
+//! // The garbler, holding `msg`.
+//! let mut session = Sha256Session::new(&mut gb)?;
+//! for block in blocks(msg) {
+//!     let xs = gb.encode_many(&block, &[2; 512])?;
+//!     session.update(&mut gb, &xs)?;
+//! }
+//! session.finalize(&mut gb)?;
+//!
+//! // The evaluator, knowing only the number of blocks, `n`.
+//! let mut session = Sha256Session::new(&mut ev)?;
+//! for _ in 0..n {
+//!     let xs = ev.receive_many(&[2; 512])?;
+//!     session.update(&mut ev, &xs)?;
+//! }
+//! let digest = session.finalize(&mut ev)?;
+//! ```
+
+use crate::{
+    circuit::{BinaryCircuit, EvaluableCircuit},
+    errors::FancyError,
+    stdlib::{sha256_compress, sha256_initial_value, BIT_ORDER},
+    FancyBinary, Modulus,
+};
+
+/// The padded blocks of `message`, as SHA-256 specifies, each as the 512 bits
+/// that [`Sha256Session::update`] takes the wires of.
+pub fn blocks(message: &[u8]) -> Vec<Vec<Modulus>> {
+    let mut padded = message.to_vec();
+    padded.push(0x80);
+    padded.resize((message.len() + 8) / 64 * 64 + 56, 0);
+    padded.extend((8 * message.len() as u64).to_be_bytes());
+    padded
+        .chunks(64)
+        .map(|block| BIT_ORDER.bytes_to_bits(block))
+        .collect()
+}
+
+/// A SHA-256 hash in progress, holding the wires of the hash value of the
+/// blocks so far.
+///
+/// The methods of a session must all be called on the same garbler or
+/// evaluator, the peer of which runs a session of its own in step.
+pub struct Sha256Session<W> {
+    circuit: BinaryCircuit,
+    state: Vec<W>,
+}
+
+impl<W: Clone> Sha256Session<W> {
+    /// Start a hash on `f`, with the initial hash value as constants.
+    pub fn new<F: FancyBinary<Item = W>>(f: &mut F) -> Result<Self, F::Error> {
+        let state = sha256_initial_value()
+            .into_iter()
+            .map(|bit| f.constant(bit, 2))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
+            circuit: sha256_compress(),
+            state,
+        })
+    }
+
+    /// Compress the next block of the padded message, given as the wires of
+    /// its 512 bits, in the order of [`blocks`].
+    pub fn update<F: FancyBinary<Item = W>>(
+        &mut self,
+        f: &mut F,
+        block_bits: &[W],
+    ) -> Result<(), F::Error> {
+        self.state = self.circuit.eval_output_wires(f, &self.state, block_bits)?;
+        Ok(())
+    }
+
+    /// Output the hash value, returning the digest if `f` learns outputs, as
+    /// an evaluator does.
+    pub fn finalize<F: FancyBinary<Item = W>>(
+        self,
+        f: &mut F,
+    ) -> Result<Option<[u8; 32]>, F::Error> {
+        // Output every wire before looking at the results, which a garbler
+        // does not learn.
+        let bits = self
+            .state
+            .iter()
+            .map(|w| f.output(w))
+            .collect::<Result<Vec<_>, _>>()?;
+        let Some(bits) = bits.into_iter().collect::<Option<Vec<_>>>() else {
+            return Ok(None);
+        };
+        let bytes = BIT_ORDER.bits_to_bytes(&bits)?;
+        let mut digest = [0; 32];
+        if bytes.len() != digest.len() {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: bytes.len(),
+                needed: digest.len(),
+            }));
+        }
+        digest.copy_from_slice(&bytes);
+        Ok(Some(digest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dummy::Dummy,
+        errors::TwopacError,
+        twopac::semihonest::{SemiHonest, TwopacStats},
+        util::RngExt,
+        FancyInput, WireMod2,
+    };
+    use scuttlebutt::{unix_channel_pair, AbstractChannel, AesRng};
+    use sha2::{Digest, Sha256};
+
+    fn message(len: usize) -> Vec<u8> {
+        let mut rng = AesRng::new();
+        (0..len).map(|_| rng.gen_u16() as u8).collect()
+    }
+
+    #[test]
+    fn test_blocks() {
+        for (len, n) in [(0, 1), (55, 1), (56, 2), (64, 2), (119, 2), (120, 3)] {
+            let padded = blocks(&message(len));
+            assert_eq!(padded.len(), n, "len={}", len);
+            assert!(padded.iter().all(|b| b.len() == 512));
+        }
+    }
+
+    #[test]
+    fn test_dummy() {
+        for len in [0, 55, 56, 64, 300] {
+            let msg = message(len);
+            let mut f = Dummy::new();
+            let mut session = Sha256Session::new(&mut f).unwrap();
+            for block in blocks(&msg) {
+                let xs = f.encode_many(&block, &[2; 512]).unwrap();
+                session.update(&mut f, &xs).unwrap();
+            }
+            let digest = session.finalize(&mut f).unwrap().unwrap();
+            assert_eq!(
+                digest.to_vec(),
+                Sha256::digest(&msg).to_vec(),
+                "len={}",
+                len
+            );
+        }
+    }
+
+    /// Hash `msg`, held by the garbler or, if `evaluator_holds`, the
+    /// evaluator, between two parties, returning the digest and the stats of
+    /// the evaluator.
+    fn twopac_hash(msg: &[u8], evaluator_holds: bool) -> ([u8; 32], TwopacStats) {
+        let padded = blocks(msg);
+        let gb_padded = padded.clone();
+        let config = SemiHonest::builder().stats(true);
+        let (sender, receiver) = unix_channel_pair();
+        let gb_config = config.clone();
+        let handle = std::thread::spawn(move || {
+            let mut gb = gb_config.build_garbler::<_, _, WireMod2>(sender, AesRng::new())?;
+            let mut session = Sha256Session::new(&mut gb)?;
+            for block in gb_padded {
+                let xs = if evaluator_holds {
+                    gb.receive_many(&[2; 512])?
+                } else {
+                    gb.encode_many(&block, &[2; 512])?
+                };
+                session.update(&mut gb, &xs)?;
+            }
+            assert_eq!(session.finalize(&mut gb)?, None);
+            gb.get_channel().flush()?;
+            Ok::<_, TwopacError>(())
+        });
+        let mut ev = config
+            .build_evaluator::<_, _, WireMod2>(receiver, AesRng::new())
+            .unwrap();
+        let mut session = Sha256Session::new(&mut ev).unwrap();
+        for block in &padded {
+            let xs = if evaluator_holds {
+                ev.encode_many(block, &[2; 512]).unwrap()
+            } else {
+                ev.receive_many(&[2; 512]).unwrap()
+            };
+            session.update(&mut ev, &xs).unwrap();
+        }
+        let digest = session.finalize(&mut ev).unwrap().unwrap();
+        handle.join().unwrap().unwrap();
+        (digest, ev.stats().unwrap())
+    }
+
+    #[test]
+    fn test_twopac() {
+        // One, two, and five blocks.
+        for len in [40, 100, 300] {
+            let msg = message(len);
+            let (digest, stats) = twopac_hash(&msg, false);
+            assert_eq!(
+                digest.to_vec(),
+                Sha256::digest(&msg).to_vec(),
+                "len={}",
+                len
+            );
+            // The hash value carries over without oblivious transfer.
+            assert_eq!(stats.ots, 0);
+        }
+    }
+
+    #[test]
+    fn test_twopac_evaluator_message() {
+        let msg = message(100);
+        let (digest, stats) = twopac_hash(&msg, true);
+        assert_eq!(digest.to_vec(), Sha256::digest(&msg).to_vec());
+        // One oblivious transfer per bit of the message, and none for the
+        // hash value.
+        assert_eq!(stats.ots, 2 * 512);
+    }
+}
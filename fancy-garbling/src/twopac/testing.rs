@@ -0,0 +1,384 @@
+//! A deterministic, single-threaded harness for tests of two-party
+//! computation.
+//!
+//! [`run_local`] runs a semi-honest [`Garbler`] and [`Evaluator`] on the
+//! calling thread over an in-memory channel, with all their randomness
+//! derived from one seed, so that a test of the protocol needs no threads or
+//! sockets, and runs the same way every time:
+//!
+//! ```ignore
+//! use fancy_garbling::{circuit::eval_plain, twopac::testing, WireMod2};
+//! use scuttlebutt::Block;
+//!
+//! let (gb, ev) = testing::run_local::<_, WireMod2>(&circ, &xs, &ys, Block::from(7))?;
+//! assert_eq!(ev.outputs, eval_plain(&circ, &xs, &ys)?);
+//! ```
+//!
+//! The parties block on their reads, so they cannot simply take turns on one
+//! thread. Instead, each turn runs one party from its start, over everything
+//! its peer has flushed so far, until it needs a byte that has not been
+//! flushed yet or finishes; then the peer takes its turn. The parties are
+//! deterministic, so each turn replays the last one and goes further, and
+//! the run ends once both have finished. This costs one replay of each party
+//! per round trip of the protocol, of which there are few.
+//!
+//! Only the bytes a party flushes reach its peer, so a missing flush shows up
+//! as it would over a socket, as a deadlock: the run fails once a turn of each
+//! party makes no progress. It also fails if a party sends different bytes
+//! when replayed, which means its randomness is not derived from the seed.
+//!
+//! This module needs the `test-utils` feature.
+
+use super::semihonest::{Evaluator, Garbler, SemiHonest, SemiHonestBuilder, TwopacStats};
+use crate::{circuit::EvaluableCircuit, errors::TwopacError, FancyInput, Modulus, WireLabel};
+use ocelot::ot::{AlszReceiver, AlszSender, Receiver as OtReceiver, Sender as OtSender};
+use rand::{Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, AesRng, Block};
+use std::{
+    cell::RefCell,
+    io::{self, Error, ErrorKind},
+    rc::Rc,
+};
+
+/// A garbler of a local run.
+pub type LocalGarbler<OT, Wire> = Garbler<LocalChannel, AesRng, OT, Wire>;
+
+/// An evaluator of a local run.
+pub type LocalEvaluator<OT, Wire> = Evaluator<LocalChannel, AesRng, OT, Wire>;
+
+/// What one party of a local run did.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PartyView {
+    /// The outputs the party learned: the evaluator's always, and the
+    /// garbler's with [`OutputMode::Both`](super::semihonest::OutputMode::Both).
+    pub outputs: Option<Vec<Modulus>>,
+    /// Every byte the party sent, in order.
+    pub sent: Vec<u8>,
+    /// The counters the party kept, if its configuration enables them.
+    pub stats: Option<TwopacStats>,
+}
+
+/// The buffers of one party's turn.
+#[derive(Debug, Default)]
+struct Buffers {
+    /// What the peer had flushed when the turn started.
+    inbox: Vec<u8>,
+    /// How much of the inbox has been read.
+    read: usize,
+    /// What the party has written this turn.
+    written: Vec<u8>,
+    /// How much of what it has written the party has flushed.
+    flushed: usize,
+    /// Whether the party has read past the end of the inbox.
+    starved: bool,
+}
+
+/// The in-memory channel of a party of a local run.
+///
+/// A read past what the peer has flushed fails with
+/// [`ErrorKind::WouldBlock`], ending the party's turn.
+#[derive(Debug)]
+pub struct LocalChannel(Rc<RefCell<Buffers>>);
+
+impl LocalChannel {
+    fn new(inbox: Vec<u8>) -> Self {
+        LocalChannel(Rc::new(RefCell::new(Buffers {
+            inbox,
+            ..Buffers::default()
+        })))
+    }
+}
+
+impl AbstractChannel for LocalChannel {
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> io::Result<()> {
+        let mut buffers = self.0.borrow_mut();
+        let start = buffers.read;
+        let Some(unread) = buffers.inbox.get(start..start + bytes.len()) else {
+            buffers.starved = true;
+            return Err(Error::new(
+                ErrorKind::WouldBlock,
+                "waiting for the peer's turn",
+            ));
+        };
+        bytes.copy_from_slice(unread);
+        buffers.read += bytes.len();
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.0.borrow_mut().written.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let mut buffers = self.0.borrow_mut();
+        buffers.flushed = buffers.written.len();
+        Ok(())
+    }
+
+    fn clone(&self) -> Self {
+        LocalChannel(Rc::clone(&self.0))
+    }
+}
+
+/// The progress of one party over its turns.
+struct Party<T> {
+    name: &'static str,
+    /// What the party flushed in its last turn.
+    sent: Vec<u8>,
+    /// What the party returned, once it has finished.
+    result: Option<T>,
+}
+
+impl<T> Party<T> {
+    fn new(name: &'static str) -> Self {
+        Party {
+            name,
+            sent: Vec::new(),
+            result: None,
+        }
+    }
+
+    /// Replay the party over what its peer has sent, returning whether it
+    /// finished or sent more than in its last turn.
+    fn take_turn(
+        &mut self,
+        run: &mut impl FnMut(LocalChannel) -> Result<T, TwopacError>,
+        inbox: &[u8],
+    ) -> Result<bool, TwopacError> {
+        if self.result.is_some() {
+            return Ok(false);
+        }
+        let channel = LocalChannel::new(inbox.to_vec());
+        let buffers = Rc::clone(&channel.0);
+        let result = run(channel);
+        let mut buffers = buffers.borrow_mut();
+        let result = match result {
+            Ok(result) => Some(result),
+            Err(_) if buffers.starved => None,
+            Err(e) => return Err(e),
+        };
+        let flushed = buffers.flushed;
+        buffers.written.truncate(flushed);
+        let sent = std::mem::take(&mut buffers.written);
+        if !sent.starts_with(&self.sent) {
+            return Err(TwopacError::IoError(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "the {} sent different bytes when replayed; is its randomness seeded?",
+                    self.name
+                ),
+            )));
+        }
+        let progress = result.is_some() || sent.len() > self.sent.len();
+        self.sent = sent;
+        self.result = result;
+        Ok(progress)
+    }
+}
+
+/// Run `garbler` and `evaluator` against each other, in turns over in-memory
+/// channels, until both finish, returning what each returned and sent.
+///
+/// Each turn calls the closure of a party with a fresh channel holding what
+/// the peer has flushed so far, so the closures must be deterministic: given
+/// the same bytes, they must send the same bytes. [`run_local`] is this with
+/// closures that run a circuit.
+pub fn run_parties<G, E>(
+    mut garbler: impl FnMut(LocalChannel) -> Result<G, TwopacError>,
+    mut evaluator: impl FnMut(LocalChannel) -> Result<E, TwopacError>,
+) -> Result<((G, Vec<u8>), (E, Vec<u8>)), TwopacError> {
+    let mut gb = Party::new("garbler");
+    let mut ev = Party::new("evaluator");
+    loop {
+        let gb_progress = gb.take_turn(&mut garbler, &ev.sent)?;
+        let ev_progress = ev.take_turn(&mut evaluator, &gb.sent)?;
+        if let (Some(_), Some(_)) = (&gb.result, &ev.result) {
+            break;
+        }
+        if !gb_progress && !ev_progress {
+            return Err(TwopacError::IoError(Error::new(
+                ErrorKind::WouldBlock,
+                "both parties are waiting for the other",
+            )));
+        }
+    }
+    let gb_result = gb.result.expect("the garbler has finished");
+    let ev_result = ev.result.expect("the evaluator has finished");
+    Ok(((gb_result, gb.sent), (ev_result, ev.sent)))
+}
+
+/// Run `circuit` between a garbler with inputs `gb_inputs` and an evaluator
+/// with inputs `ev_inputs` on this thread, with the default configuration,
+/// and return what each party did.
+///
+/// The parties' randomness, and so everything they send, is derived from
+/// `seed`. See [`run_local_with`] for other configurations.
+pub fn run_local<Circuit, Wire>(
+    circuit: &Circuit,
+    gb_inputs: &[Modulus],
+    ev_inputs: &[Modulus],
+    seed: Block,
+) -> Result<(PartyView, PartyView), TwopacError>
+where
+    Circuit: EvaluableCircuit<LocalGarbler<AlszSender, Wire>>
+        + EvaluableCircuit<LocalEvaluator<AlszReceiver, Wire>>,
+    Wire: WireLabel,
+{
+    run_local_with(&SemiHonest::builder(), circuit, gb_inputs, ev_inputs, seed)
+}
+
+/// Run `circuit` as [`run_local`] does, between parties built from `config`.
+///
+/// The OT of the configuration must draw all its randomness from the RNG it
+/// is given, as the default ALSZ and Chou–Orlandi OTs do, and the
+/// configuration must not set a timeout, which the in-memory channel does
+/// not support.
+pub fn run_local_with<S, R, Circuit, Wire>(
+    config: &SemiHonestBuilder<S, R>,
+    circuit: &Circuit,
+    gb_inputs: &[Modulus],
+    ev_inputs: &[Modulus],
+    seed: Block,
+) -> Result<(PartyView, PartyView), TwopacError>
+where
+    S: OtSender<Msg = Block> + scuttlebutt::SemiHonest,
+    R: OtReceiver<Msg = Block> + scuttlebutt::SemiHonest,
+    Circuit: EvaluableCircuit<LocalGarbler<S, Wire>> + EvaluableCircuit<LocalEvaluator<R, Wire>>,
+    Wire: WireLabel,
+{
+    let mut rng = AesRng::from_seed(seed);
+    let gb_seed = rng.gen::<Block>();
+    let ev_seed = rng.gen::<Block>();
+    let gb_moduli = (0..gb_inputs.len())
+        .map(|i| circuit.garbler_input_mod(i))
+        .collect::<Vec<_>>();
+    let ev_moduli = (0..ev_inputs.len())
+        .map(|i| circuit.evaluator_input_mod(i))
+        .collect::<Vec<_>>();
+
+    let ((gb, gb_sent), (ev, ev_sent)) = run_parties(
+        |channel| {
+            let mut gb = config.build_garbler_from_seed::<_, Wire>(channel, gb_seed)?;
+            let xs = gb.encode_many(gb_inputs, &gb_moduli)?;
+            let ys = gb.receive_many(&ev_moduli)?;
+            let outputs = gb.eval_circuit(circuit, &xs, &ys)?;
+            gb.get_channel().flush()?;
+            Ok((outputs, gb.stats()))
+        },
+        |channel| {
+            let mut ev = config.build_evaluator_from_seed::<_, Wire>(channel, ev_seed)?;
+            let xs = ev.receive_many(&gb_moduli)?;
+            let ys = ev.encode_many(ev_inputs, &ev_moduli)?;
+            let outputs = ev.eval_circuit(circuit, &xs, &ys)?;
+            ev.get_channel().flush()?;
+            Ok((outputs, ev.stats()))
+        },
+    )?;
+    Ok((
+        PartyView {
+            outputs: gb.0,
+            sent: gb_sent,
+            stats: gb.1,
+        },
+        PartyView {
+            outputs: Some(ev.0),
+            sent: ev_sent,
+            stats: ev.1,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::{eval_plain, BinaryCircuit},
+        twopac::semihonest::OutputMode,
+        WireMod2,
+    };
+
+    fn adder64() -> BinaryCircuit {
+        BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../circuits/adder64.txt"
+        )))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let circ = adder64();
+        let xs = [1; 64];
+        let ys = [0; 64];
+        let run = |seed| run_local::<_, WireMod2>(&circ, &xs, &ys, Block::from(seed)).unwrap();
+        let (gb, ev) = run(1);
+        assert_eq!(gb.outputs, None);
+        assert_eq!(ev.outputs, Some(eval_plain(&circ, &xs, &ys).unwrap()));
+        assert_eq!(run(1), (gb.clone(), ev.clone()));
+        let (other_gb, other_ev) = run(2);
+        assert_ne!(other_gb.sent, gb.sent);
+        assert_ne!(other_ev.sent, ev.sent);
+        assert_eq!(other_ev.outputs, ev.outputs);
+    }
+
+    #[test]
+    fn test_output_mode_both() {
+        let circ = adder64();
+        let config = SemiHonest::builder()
+            .output_mode(OutputMode::Both)
+            .stats(true);
+        let (gb, ev) =
+            run_local_with::<_, _, _, WireMod2>(&config, &circ, &[1; 64], &[1; 64], Block::from(3))
+                .unwrap();
+        assert_eq!(gb.outputs, ev.outputs);
+        assert_eq!(gb.stats.unwrap().ots, 64);
+        assert_eq!(ev.stats.unwrap().ots, 64);
+    }
+
+    #[test]
+    fn test_deadlock() {
+        // Each party waits for the other before sending anything.
+        let read_first = |mut channel: LocalChannel| {
+            let x = channel.read_u8()?;
+            channel.write_u8(x)?;
+            channel.flush()?;
+            Ok(())
+        };
+        assert!(matches!(
+            run_parties(read_first, read_first),
+            Err(TwopacError::IoError(e)) if e.kind() == ErrorKind::WouldBlock
+        ));
+
+        // A byte written but never flushed does not reach the peer.
+        let unflushed = |mut channel: LocalChannel| {
+            channel.write_u8(1)?;
+            channel.read_u8()?;
+            Ok(())
+        };
+        assert!(matches!(
+            run_parties(unflushed, read_first),
+            Err(TwopacError::IoError(e)) if e.kind() == ErrorKind::WouldBlock
+        ));
+    }
+
+    #[test]
+    fn test_replay_mismatch() {
+        // A garbler sending a different byte in each turn.
+        let mut turns = 0;
+        let garbler = |mut channel: LocalChannel| {
+            turns += 1;
+            channel.write_u8(turns)?;
+            channel.flush()?;
+            channel.read_u8()?;
+            Ok(())
+        };
+        let evaluator = |mut channel: LocalChannel| {
+            channel.read_u8()?;
+            Ok(channel.read_u8()?)
+        };
+        assert!(matches!(
+            run_parties(garbler, evaluator),
+            Err(TwopacError::IoError(e)) if e.kind() == ErrorKind::InvalidData
+        ));
+    }
+}
@@ -0,0 +1,597 @@
+//! Zero-knowledge proofs from garbled circuits, after Jawurek, Kerschbaum, and
+//! Orlandi.
+//!
+//! A [`Prover`] proves to a [`Verifier`] that it knows a witness `x` such that
+//! `C(x) = 1`, for a binary circuit `C` whose inputs are all the evaluator's
+//! and which has a single output, without revealing anything else about `x`:
+//!
+//! 1. The verifier garbles `C` from a random seed, as a garbler would, and
+//!    the prover learns the labels of `x` by oblivious transfer, whose sender
+//!    randomness the verifier also draws from the seed.
+//! 2. The prover evaluates the garbled circuit and commits to the label of
+//!    the output it gets.
+//! 3. The verifier reveals the seed. The prover garbles `C` again from it,
+//!    and checks that the garbled circuit it evaluated is that of an honest
+//!    garbling. It also plays the sender of the oblivious transfer again from
+//!    the seed, against the messages it sent and received, so that it checks
+//!    both labels of every input and not only those it chose. The verifier
+//!    therefore cannot have learned anything from the output label, nor from
+//!    whether the prover goes on.
+//! 4. The prover opens the commitment, and the verifier accepts if the label
+//!    is that of the output `1`, which the prover cannot have guessed.
+//!
+//! The verifier garbles with the same half-gates garbler as
+//! [`semihonest`](super::semihonest), so each AND gate costs two ciphertexts
+//! where a privacy-free garbling would do with one.
+//!
+//! Replaying the sender needs it to draw all its randomness from the random
+//! number generator it is given, as the oblivious transfers of `ocelot` do, so
+//! that the prover takes the types of both the sender and the receiver.
+
+use crate::{
+    circuit::{BinaryCircuit, CircuitDigest, CircuitType, EvaluableCircuit},
+    errors::{EvaluatorError, FancyError, TwopacError},
+    fancy::check_inputs,
+    Evaluator as Ev, Garbler as Gb, Modulus, WireLabel, WireMod2,
+};
+use ocelot::ot::{Receiver as OtReceiver, Sender as OtSender};
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{
+    commitment::{Commitment, ShaCommitment},
+    AbstractChannel, AesRng, Block, Channel, Malicious,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    cell::{Cell, RefCell},
+    io,
+    marker::PhantomData,
+    rc::Rc,
+};
+
+/// The prover of a zero-knowledge proof, playing the evaluator.
+///
+/// `OTR` receives the input labels, and `OTS` is the sender the verifier
+/// uses, which the prover plays again to check the labels it did not choose.
+pub struct Prover<C, RNG, OTS, OTR> {
+    channel: C,
+    rng: RNG,
+    _ot: PhantomData<(OTS, OTR)>,
+}
+
+/// The verifier of a zero-knowledge proof, playing the garbler.
+pub struct Verifier<C, RNG, OT> {
+    channel: C,
+    rng: RNG,
+    _ot: PhantomData<OT>,
+}
+
+impl<
+        C: AbstractChannel,
+        RNG: CryptoRng + Rng,
+        OTS: OtSender<Msg = Block> + Malicious,
+        OTR: OtReceiver<Msg = Block> + Malicious,
+    > Prover<C, RNG, OTS, OTR>
+{
+    /// Make a new `Prover`.
+    pub fn new(channel: C, rng: RNG) -> Self {
+        Prover {
+            channel,
+            rng,
+            _ot: PhantomData,
+        }
+    }
+
+    /// Prove that `witness`, the evaluator inputs of `circuit`, makes it
+    /// output `1`.
+    ///
+    /// Fails with [`TwopacError::ProofRejected`] if the verifier rejects the
+    /// proof, as it does when the output is `0`, and with
+    /// [`TwopacError::InvalidGarbling`], before opening its commitment, if the
+    /// verifier did not garble `circuit` or send the input labels honestly.
+    pub fn prove(
+        &mut self,
+        circuit: &BinaryCircuit,
+        witness: &[Modulus],
+    ) -> Result<(), TwopacError> {
+        check_circuit(circuit)?;
+        let needed = circuit.num_evaluator_inputs();
+        if witness.len() < needed {
+            return Err(FancyError::NotEnoughEvaluatorInputs {
+                got: witness.len(),
+                needed,
+            }
+            .into());
+        }
+        if witness.len() > needed {
+            return Err(FancyError::TooManyEvaluatorInputs {
+                got: witness.len(),
+                needed,
+            }
+            .into());
+        }
+        check_inputs(witness, std::iter::repeat(2))?;
+        let expected = circuit.digest();
+        let found = CircuitDigest::from_bytes(read_array(&mut self.channel)?);
+        if found != expected {
+            return Err(EvaluatorError::MismatchedCircuit { expected, found }.into());
+        }
+
+        let choices = witness.iter().map(|&x| x == 1).collect::<Vec<_>>();
+        let mut recorder = Recorder::new(self.channel.clone());
+        let mut ot = OTR::init(&mut recorder, &mut self.rng)?;
+        let labels = ot.receive(&mut recorder, &choices, &mut self.rng)?;
+        let inputs = labels
+            .iter()
+            .map(|&label| WireMod2::from_block(label, 2))
+            .collect::<Vec<_>>();
+        let received = Transcript::new(self.channel.clone());
+        let mut ev = Ev::<_, WireMod2>::new(received.clone());
+        let output = output_wire(circuit.eval_output_wires(&mut ev, &[], &inputs)?)?;
+
+        let commitment_seed = self.rng.gen::<[u8; 32]>();
+        let mut commitment = ShaCommitment::new(commitment_seed);
+        commitment.input(&<[u8; 16]>::from(output.as_block()));
+        self.channel.write_bytes(&commitment.finish())?;
+        self.channel.flush()?;
+
+        // Garble the circuit and send the labels again, and check that the
+        // verifier sent what it would have.
+        let seed = self.channel.read_block()?;
+        let (gb_rng, mut ot_rng) = verifier_rngs(seed);
+        let garbled = Transcript::new(Channel::new(io::empty(), io::sink()));
+        let mut gb = Gb::<_, AesRng, WireMod2>::new(garbled.clone(), gb_rng);
+        let (zeros, _) = gb.encode_many_wires(&vec![0; needed], &vec![2; needed])?;
+        let pairs = label_pairs(&zeros, &gb.delta(2));
+        let mut replay = Replay::new(recorder.finish());
+        let honest_inputs = OTS::init(&mut replay, &mut ot_rng)
+            .and_then(|mut ot| ot.send(&mut replay, &pairs, &mut ot_rng))
+            .is_ok()
+            && replay.done();
+        circuit.eval_output_wires(&mut gb, &[], &zeros)?;
+        if !honest_inputs || garbled.hash() != received.hash() {
+            return Err(TwopacError::InvalidGarbling);
+        }
+
+        self.channel.write_block(&output.as_block())?;
+        self.channel.write_bytes(&commitment_seed)?;
+        self.channel.flush()?;
+        match self.channel.read_u8()? {
+            1 => Ok(()),
+            _ => Err(TwopacError::ProofRejected),
+        }
+    }
+
+    /// Get a reference to the channel.
+    pub fn get_channel(&mut self) -> &mut C {
+        &mut self.channel
+    }
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT: OtSender<Msg = Block> + Malicious>
+    Verifier<C, RNG, OT>
+{
+    /// Make a new `Verifier`.
+    pub fn new(channel: C, rng: RNG) -> Self {
+        Verifier {
+            channel,
+            rng,
+            _ot: PhantomData,
+        }
+    }
+
+    /// Verify the prover's proof that it knows evaluator inputs making
+    /// `circuit` output `1`.
+    ///
+    /// Fails with [`TwopacError::ProofRejected`], once the prover has been
+    /// told, if the proof does not hold.
+    pub fn verify(&mut self, circuit: &BinaryCircuit) -> Result<(), TwopacError> {
+        check_circuit(circuit)?;
+        let n = circuit.num_evaluator_inputs();
+        self.channel.write_bytes(circuit.digest().as_bytes())?;
+
+        let seed = self.rng.gen::<Block>();
+        let (gb_rng, mut ot_rng) = verifier_rngs(seed);
+        let mut gb = Gb::<_, AesRng, WireMod2>::new(self.channel.clone(), gb_rng);
+        let (zeros, _) = gb.encode_many_wires(&vec![0; n], &vec![2; n])?;
+        let delta = gb.delta(2);
+        let pairs = label_pairs(&zeros, &delta);
+        let mut ot = OT::init(&mut self.channel, &mut ot_rng)?;
+        ot.send(&mut self.channel, &pairs, &mut ot_rng)?;
+        let output = output_wire(circuit.eval_output_wires(&mut gb, &[], &zeros)?)?;
+        self.channel.flush()?;
+
+        let commitment = read_array::<_, 32>(&mut self.channel)?;
+        self.channel.write_block(&seed)?;
+        self.channel.flush()?;
+
+        let label = self.channel.read_block()?;
+        let commitment_seed = read_array(&mut self.channel)?;
+        let mut opened = ShaCommitment::new(commitment_seed);
+        opened.input(&<[u8; 16]>::from(label));
+        let accept = ShaCommitment::check(&opened.finish(), &commitment)
+            && label == output.plus(&delta).as_block();
+        self.channel.write_u8(accept.into())?;
+        self.channel.flush()?;
+        if accept {
+            Ok(())
+        } else {
+            Err(TwopacError::ProofRejected)
+        }
+    }
+
+    /// Get a reference to the channel.
+    pub fn get_channel(&mut self) -> &mut C {
+        &mut self.channel
+    }
+}
+
+/// Check that the circuit has no garbler inputs and a single output.
+fn check_circuit(circuit: &BinaryCircuit) -> Result<(), FancyError> {
+    if circuit.num_garbler_inputs() > 0 {
+        return Err(FancyError::TooManyGarblerInputs {
+            got: circuit.num_garbler_inputs(),
+            needed: 0,
+        });
+    }
+    let outputs = circuit.get_output_refs().len();
+    if outputs != 1 {
+        return Err(FancyError::InvalidArgNum {
+            got: outputs,
+            needed: 1,
+        });
+    }
+    Ok(())
+}
+
+/// The random number generators of the verifier's garbler and oblivious
+/// transfer sender, both drawn from `seed` so that the prover can replay them.
+fn verifier_rngs(seed: Block) -> (AesRng, AesRng) {
+    let mut rng = AesRng::from_seed(seed);
+    (AesRng::from_seed(rng.gen()), AesRng::from_seed(rng.gen()))
+}
+
+/// The labels of `0` and `1` of each input, given the labels of `0`.
+fn label_pairs(zeros: &[WireMod2], delta: &WireMod2) -> Vec<(Block, Block)> {
+    zeros
+        .iter()
+        .map(|zero| (zero.as_block(), zero.plus(delta).as_block()))
+        .collect()
+}
+
+/// The wire of the single output, which `check_circuit` has checked for.
+fn output_wire(mut outputs: Vec<WireMod2>) -> Result<WireMod2, FancyError> {
+    outputs
+        .pop()
+        .ok_or(FancyError::InvalidArgNum { got: 0, needed: 1 })
+}
+
+pub(super) fn read_array<C: AbstractChannel, const N: usize>(
+    channel: &mut C,
+) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    channel.read_bytes(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// A channel hashing the bytes read from and written to it, shared by its
+/// clones.
+struct Transcript<C> {
+    channel: C,
+    hash: Rc<RefCell<Sha256>>,
+}
+
+impl<C: AbstractChannel> Transcript<C> {
+    fn new(channel: C) -> Self {
+        Transcript {
+            channel,
+            hash: Rc::new(RefCell::new(Sha256::new())),
+        }
+    }
+
+    /// The hash of the bytes so far.
+    fn hash(&self) -> [u8; 32] {
+        self.hash.borrow().clone().finalize().into()
+    }
+}
+
+impl<C: AbstractChannel> AbstractChannel for Transcript<C> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.hash.borrow_mut().update(bytes);
+        self.channel.write_bytes(bytes)
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> io::Result<()> {
+        self.channel.read_bytes(bytes)?;
+        self.hash.borrow_mut().update(&*bytes);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.channel.flush()
+    }
+
+    fn clone(&self) -> Self {
+        Transcript {
+            channel: self.channel.clone(),
+            hash: Rc::clone(&self.hash),
+        }
+    }
+}
+
+/// The bytes read from and written to a channel, in order.
+#[derive(Default)]
+struct Recording {
+    read: Vec<u8>,
+    written: Vec<u8>,
+}
+
+/// A channel recording the bytes read from and written to it, shared by its
+/// clones.
+struct Recorder<C> {
+    channel: C,
+    recording: Rc<RefCell<Recording>>,
+}
+
+impl<C: AbstractChannel> Recorder<C> {
+    fn new(channel: C) -> Self {
+        Recorder {
+            channel,
+            recording: Default::default(),
+        }
+    }
+
+    /// The bytes so far.
+    fn finish(self) -> Recording {
+        self.recording.take()
+    }
+}
+
+impl<C: AbstractChannel> AbstractChannel for Recorder<C> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.recording.borrow_mut().written.extend_from_slice(bytes);
+        self.channel.write_bytes(bytes)
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> io::Result<()> {
+        self.channel.read_bytes(bytes)?;
+        self.recording.borrow_mut().read.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.channel.flush()
+    }
+
+    fn clone(&self) -> Self {
+        Recorder {
+            channel: self.channel.clone(),
+            recording: Rc::clone(&self.recording),
+        }
+    }
+}
+
+/// The peer of a recorded channel, played again: it reads the bytes written
+/// to the recorder, and fails to write anything but the bytes the recorder
+/// read. Its clones share their place in the recording.
+struct Replay {
+    recording: Rc<Recording>,
+    // how many bytes of `recording.written` were read, and of
+    // `recording.read` written
+    place: Rc<Cell<(usize, usize)>>,
+}
+
+impl Replay {
+    fn new(recording: Recording) -> Self {
+        Replay {
+            recording: Rc::new(recording),
+            place: Default::default(),
+        }
+    }
+
+    /// Whether all the bytes of the recording were read and written.
+    fn done(&self) -> bool {
+        self.place.get() == (self.recording.written.len(), self.recording.read.len())
+    }
+}
+
+impl AbstractChannel for Replay {
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let (read, written) = self.place.get();
+        if self.recording.read.get(written..written + bytes.len()) != Some(bytes) {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+        self.place.set((read, written + bytes.len()));
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> io::Result<()> {
+        let (read, written) = self.place.get();
+        let recorded = self
+            .recording
+            .written
+            .get(read..read + bytes.len())
+            .ok_or(io::ErrorKind::UnexpectedEof)?;
+        bytes.copy_from_slice(recorded);
+        self.place.set((read + bytes.len(), written));
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn clone(&self) -> Self {
+        Replay {
+            recording: Rc::clone(&self.recording),
+            place: Rc::clone(&self.place),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        circuit::CircuitBuilder, BinaryBundle, BinaryGadgets, BitOrder, Fancy, FancyBinary,
+    };
+    use ocelot::ot::{KosReceiver, KosSender};
+    use scuttlebutt::{unix_channel_pair, UnixChannel};
+
+    type P = Prover<UnixChannel, AesRng, KosSender, KosReceiver>;
+    type V = Verifier<UnixChannel, AesRng, KosSender>;
+
+    /// A circuit outputting whether the SHA-256 digest of its 32-byte input,
+    /// as the evaluator's inputs, is `digest`.
+    fn preimage(digest: &[u8]) -> BinaryCircuit {
+        let sha = BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../../circuits/sha-256.txt"
+        )))
+        .unwrap();
+        let mut padding = [0u8; 32];
+        padding[0] = 0x80;
+        padding[24..].copy_from_slice(&256u64.to_be_bytes());
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let mut block = b.evaluator_inputs(&[2; 256]);
+        for bit in BitOrder::MsbFirst.bytes_to_bits(&padding) {
+            block.push(b.constant(bit, 2).unwrap());
+        }
+        let hash = sha.eval_output_wires(&mut b, &block, &[]).unwrap();
+        let digest = BitOrder::MsbFirst
+            .bytes_to_bits(digest)
+            .into_iter()
+            .map(|bit| b.constant(bit, 2))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let eq = b
+            .bin_eq_bundles(&BinaryBundle::new(hash), &BinaryBundle::new(digest))
+            .unwrap();
+        b.output(&eq).unwrap();
+        b.finish()
+    }
+
+    /// Run a proof of `witness` for `circuit`, returning the results of the
+    /// prover and the verifier.
+    fn run(
+        circuit: BinaryCircuit,
+        witness: Vec<Modulus>,
+    ) -> (Result<(), TwopacError>, Result<(), TwopacError>) {
+        let circuit = std::sync::Arc::new(circuit);
+        let circuit_ = circuit.clone();
+        let (sender, receiver) = unix_channel_pair();
+        let handle =
+            std::thread::spawn(move || P::new(receiver, AesRng::new()).prove(&circuit_, &witness));
+        let verified = V::new(sender, AesRng::new()).verify(&circuit);
+        (handle.join().unwrap(), verified)
+    }
+
+    #[test]
+    fn test_sha256_preimage() {
+        let msg = AesRng::new().gen::<[u8; 32]>();
+        let circuit = preimage(&Sha256::digest(msg));
+        let witness = BitOrder::MsbFirst.bytes_to_bits(&msg);
+        assert_eq!(
+            crate::circuit::eval_plain(&circuit, &[], &witness).unwrap(),
+            [1]
+        );
+        let (proved, verified) = run(circuit, witness);
+        proved.unwrap();
+        verified.unwrap();
+    }
+
+    #[test]
+    fn test_invalid_witness() {
+        let msg = AesRng::new().gen::<[u8; 32]>();
+        let circuit = preimage(&Sha256::digest(msg));
+        let mut wrong = msg;
+        wrong[0] ^= 1;
+        let (proved, verified) = run(circuit, BitOrder::MsbFirst.bytes_to_bits(&wrong));
+        assert!(
+            matches!(proved, Err(TwopacError::ProofRejected)),
+            "{:?}",
+            proved
+        );
+        assert!(
+            matches!(verified, Err(TwopacError::ProofRejected)),
+            "{:?}",
+            verified
+        );
+    }
+
+    /// Run a verifier that garbles `circuit` honestly but sends the label of
+    /// `value` of evaluator input `input` flipped, against a prover of
+    /// `witness`, returning the result of the prover.
+    fn cheat(
+        circuit: BinaryCircuit,
+        witness: Vec<Modulus>,
+        input: usize,
+        value: Modulus,
+    ) -> Result<(), TwopacError> {
+        let circuit = std::sync::Arc::new(circuit);
+        let circuit_ = circuit.clone();
+        let (mut sender, receiver) = unix_channel_pair();
+        let handle =
+            std::thread::spawn(move || P::new(receiver, AesRng::new()).prove(&circuit_, &witness));
+        let n = circuit.num_evaluator_inputs();
+        sender.write_bytes(circuit.digest().as_bytes()).unwrap();
+        let seed = AesRng::new().gen::<Block>();
+        let (gb_rng, mut ot_rng) = verifier_rngs(seed);
+        let mut gb = Gb::<_, AesRng, WireMod2>::new(sender.clone(), gb_rng);
+        let (zeros, _) = gb.encode_many_wires(&vec![0; n], &vec![2; n]).unwrap();
+        let mut pairs = label_pairs(&zeros, &gb.delta(2));
+        let label = match value {
+            0 => &mut pairs[input].0,
+            _ => &mut pairs[input].1,
+        };
+        *label ^= Block::from(1u128);
+        let mut ot = KosSender::init(&mut sender, &mut ot_rng).unwrap();
+        ot.send(&mut sender, &pairs, &mut ot_rng).unwrap();
+        circuit.eval_output_wires(&mut gb, &[], &zeros).unwrap();
+        sender.flush().unwrap();
+        read_array::<_, 32>(&mut sender).unwrap();
+        sender.write_block(&seed).unwrap();
+        sender.flush().unwrap();
+        // Hang up, so that a prover going on fails rather than waits.
+        drop((gb, sender));
+        handle.join().unwrap()
+    }
+
+    #[test]
+    fn test_cheating_verifier() {
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let xs = b.evaluator_inputs(&[2; 2]);
+        let z = b.and(&xs[0], &xs[1]).unwrap();
+        b.output(&z).unwrap();
+        let circuit = b.finish();
+        // Whichever label of an input the verifier corrupts, the prover
+        // aborts before opening its commitment, so that the verifier learns
+        // nothing of the witness.
+        for witness in [[0, 0], [0, 1], [1, 0], [1, 1]] {
+            for (input, value) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+                let proved = cheat(circuit.clone(), witness.to_vec(), input, value);
+                assert!(
+                    matches!(proved, Err(TwopacError::InvalidGarbling)),
+                    "{:?} {:?}",
+                    witness,
+                    proved
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_circuit() {
+        let mut b = CircuitBuilder::<BinaryCircuit>::new();
+        let x = b.garbler_input(2);
+        let y = b.evaluator_input(2);
+        let z = b.and(&x, &y).unwrap();
+        b.output(&z).unwrap();
+        let circuit = b.finish();
+        let mut p = P::new(unix_channel_pair().0, AesRng::new());
+        assert!(matches!(
+            p.prove(&circuit, &[1]),
+            Err(TwopacError::FancyError(
+                FancyError::TooManyGarblerInputs { .. }
+            ))
+        ));
+    }
+}
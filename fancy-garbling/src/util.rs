@@ -7,7 +7,7 @@ use scuttlebutt::Block;
 use std::collections::HashMap;
 use vectoreyes::{SimdBase, U64x2, U8x16};
 
-use crate::WireLabel;
+use crate::{errors::FancyError, Modulus, WireLabel};
 
 ////////////////////////////////////////////////////////////////////////////////
 // tweak functions for garbling
@@ -23,7 +23,7 @@ pub fn tweak2(i: u64, j: u64) -> Block {
 }
 
 /// Compute the output tweak for a garbled gate where i is the gate id and k is the value.
-pub fn output_tweak(i: usize, k: u16) -> Block {
+pub fn output_tweak(i: usize, k: Modulus) -> Block {
     let (left, _) = (i as u128).overflowing_shl(64);
     Block::from(left + k as u128)
 }
@@ -32,7 +32,7 @@ pub fn output_tweak(i: usize, k: u16) -> Block {
 // mixed radix stuff
 
 /// Add a base `q` slice `ys` into `xs`.
-pub fn base_q_add_eq(xs: &mut [u16], ys: &[u16], q: u16) {
+pub fn base_q_add_eq(xs: &mut [Modulus], ys: &[Modulus], q: Modulus) {
     debug_assert!(
         xs.len() >= ys.len(),
         "q={} xs.len()={} ys.len()={} xs={:?} ys={:?}",
@@ -47,9 +47,10 @@ pub fn base_q_add_eq(xs: &mut [u16], ys: &[u16], q: u16) {
     let mut i = 0;
 
     while i < ys.len() {
-        xs[i] += ys[i] + c;
-        c = (xs[i] >= q) as u16;
-        xs[i] -= c * q;
+        // Written so that no intermediate exceeds `q`.
+        let y = ys[i] + c;
+        c = (xs[i] >= q - y) as Modulus;
+        xs[i] = if c == 1 { xs[i] - (q - y) } else { xs[i] + y };
         i += 1;
     }
 
@@ -67,15 +68,38 @@ pub fn base_q_add_eq(xs: &mut [u16], ys: &[u16], q: u16) {
     }
 }
 
+/// Compute `(x + y) mod q` without overflowing.
+pub fn add_mod(x: Modulus, y: Modulus, q: Modulus) -> Modulus {
+    ((x as u64 + y as u64) % q as u64) as Modulus
+}
+
+/// Compute `x * y mod q` without overflowing.
+pub fn mul_mod(x: Modulus, y: Modulus, q: Modulus) -> Modulus {
+    (x as u64 * y as u64 % q as u64) as Modulus
+}
+
+/// Compute `x^c mod q` without overflowing.
+pub fn pow_mod(x: Modulus, c: Modulus, q: Modulus) -> Modulus {
+    let (mut x, mut c, mut acc) = (x % q, c, 1 % q);
+    while c > 0 {
+        if c & 1 == 1 {
+            acc = mul_mod(acc, x, q);
+        }
+        x = mul_mod(x, x, q);
+        c >>= 1;
+    }
+    acc
+}
+
 /// Convert `x` into base `q`, building a vector of length `n`.
-fn as_base_q(x: u128, q: u16, n: usize) -> Vec<u16> {
+fn as_base_q(x: u128, q: Modulus, n: usize) -> Vec<Modulus> {
     let ms = std::iter::repeat(q).take(n).collect_vec();
     as_mixed_radix(x, &ms)
 }
 
 /// Determine how many `mod q` digits fit into a `u128` (includes the color
 /// digit).
-pub fn digits_per_u128(modulus: u16) -> usize {
+pub fn digits_per_u128(modulus: Modulus) -> usize {
     debug_assert_ne!(modulus, 0);
     debug_assert_ne!(modulus, 1);
     if modulus == 2 {
@@ -102,12 +126,12 @@ pub fn digits_per_u128(modulus: u16) -> usize {
 }
 
 /// Convert `x` into base `q`.
-pub fn as_base_q_u128(x: u128, q: u16) -> Vec<u16> {
+pub fn as_base_q_u128(x: u128, q: Modulus) -> Vec<Modulus> {
     as_base_q(x, q, digits_per_u128(q))
 }
 
 /// Convert `x` into mixed radix form using the provided `radii`.
-pub fn as_mixed_radix(x: u128, radii: &[u16]) -> Vec<u16> {
+pub fn as_mixed_radix(x: u128, radii: &[Modulus]) -> Vec<Modulus> {
     let mut x = x;
     radii
         .iter()
@@ -115,9 +139,9 @@ pub fn as_mixed_radix(x: u128, radii: &[u16]) -> Vec<u16> {
             if x >= m as u128 {
                 let d = x % m as u128;
                 x = (x - d) / m as u128;
-                d as u16
+                d as Modulus
             } else {
-                let d = x as u16;
+                let d = x as Modulus;
                 x = 0;
                 d
             }
@@ -126,7 +150,7 @@ pub fn as_mixed_radix(x: u128, radii: &[u16]) -> Vec<u16> {
 }
 
 /// Convert little-endian base `q` digits into `u128`.
-pub fn from_base_q(ds: &[u16], q: u16) -> u128 {
+pub fn from_base_q(ds: &[Modulus], q: Modulus) -> u128 {
     let mut x = 0u128;
     for &d in ds.iter().rev() {
         let (xp, overflow) = x.overflowing_mul(q.into());
@@ -137,7 +161,7 @@ pub fn from_base_q(ds: &[u16], q: u16) -> u128 {
 }
 
 /// Convert little-endian mixed radix digits into u128.
-pub fn from_mixed_radix(digits: &[u16], radii: &[u16]) -> u128 {
+pub fn from_mixed_radix(digits: &[Modulus], radii: &[Modulus]) -> u128 {
     let mut x: u128 = 0;
     for (&d, &q) in digits.iter().zip(radii.iter()).rev() {
         let (xp, overflow) = x.overflowing_mul(q as u128);
@@ -151,21 +175,21 @@ pub fn from_mixed_radix(digits: &[u16], radii: &[u16]) -> u128 {
 // bits
 
 /// Get the bits of a u128 encoded in 128 u16s, which is convenient for the rest of
-/// the library, which uses u16 as the base digit type in Wire.
-pub fn u128_to_bits(x: u128, n: usize) -> Vec<u16> {
+/// the library, which uses `Modulus` as the base digit type in Wire.
+pub fn u128_to_bits(x: u128, n: usize) -> Vec<Modulus> {
     let mut bits = Vec::with_capacity(n);
     let mut y = x;
     for _ in 0..n {
         let b = y & 1;
-        bits.push(b as u16);
+        bits.push(b as Modulus);
         y -= b;
         y /= 2;
     }
     bits
 }
 
-/// Convert into a u128 from the "bits" as u16. Assumes each "bit" is 0 or 1.
-pub fn u128_from_bits(bs: &[u16]) -> u128 {
+/// Convert into a u128 from the "bits" as `Modulus`. Assumes each "bit" is 0 or 1.
+pub fn u128_from_bits(bs: &[Modulus]) -> u128 {
     let mut x = 0;
     for &b in bs.iter().skip(1).rev() {
         x += b as u128;
@@ -175,6 +199,87 @@ pub fn u128_from_bits(bs: &[u16]) -> u128 {
     x
 }
 
+/// The order of the bits of a number in a sequence of bits or wires.
+///
+/// The binary gadgets, [`u128_to_bits`], and [`u128_from_bits`] put the least
+/// significant bit first; [`CircuitInput`](crate::circuit::CircuitInput),
+/// the [`stdlib`](crate::stdlib) circuits, and byte buffers put the most
+/// significant bit first. Converting between them with the methods here, and
+/// those of [`BinaryBundle`](crate::BinaryBundle) and
+/// [`CircuitBuilder`](crate::circuit::CircuitBuilder) that take a `BitOrder`,
+/// saves reversing bits by hand.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BitOrder {
+    /// Least significant bit first.
+    LsbFirst,
+    /// Most significant bit first.
+    MsbFirst,
+}
+
+impl BitOrder {
+    /// The low `nbits` bits of `x`, in this order.
+    pub fn bits(self, x: u128, nbits: usize) -> Vec<Modulus> {
+        self.arrange(u128_to_bits(x, nbits))
+    }
+
+    /// The number whose bits, in this order, are `bits`, each 0 or 1.
+    pub fn value(self, bits: &[Modulus]) -> u128 {
+        let bits = bits.iter().map(|&b| u128::from(b));
+        match self {
+            BitOrder::LsbFirst => bits.rev().fold(0, |acc, b| acc << 1 | b),
+            BitOrder::MsbFirst => bits.fold(0, |acc, b| acc << 1 | b),
+        }
+    }
+
+    /// The bits of `bytes`, read as a big-endian number, in this order.
+    ///
+    /// With [`BitOrder::MsbFirst`], this is each byte in turn, most
+    /// significant bit first, as [`CircuitInput`](crate::circuit::CircuitInput)
+    /// writes byte arrays.
+    pub fn bytes_to_bits(self, bytes: &[u8]) -> Vec<Modulus> {
+        let bits = bytes
+            .iter()
+            .rev()
+            .flat_map(|&byte| (0..8).map(move |i| Modulus::from((byte >> i) & 1)))
+            .collect();
+        self.arrange(bits)
+    }
+
+    /// The big-endian bytes of the number whose bits, in this order, are
+    /// `bits`, the inverse of [`BitOrder::bytes_to_bits`].
+    ///
+    /// Fails if the number of bits is not a multiple of 8, or if a bit is not
+    /// 0 or 1.
+    pub fn bits_to_bytes(self, bits: &[Modulus]) -> Result<Vec<u8>, FancyError> {
+        let (nbytes, partial) = (bits.len() / 8, bits.len() % 8);
+        if partial != 0 {
+            return Err(FancyError::InvalidArgNum {
+                got: bits.len(),
+                needed: (nbytes + 1) * 8,
+            });
+        }
+        if bits.iter().any(|&b| b > 1) {
+            return Err(FancyError::ArgNotBinary);
+        }
+        let bits = self.arrange(bits.to_vec());
+        Ok(bits
+            .chunks(8)
+            .rev()
+            .map(|byte| byte.iter().rev().fold(0, |acc, &b| acc << 1 | b as u8))
+            .collect())
+    }
+
+    /// Reorder `xs`, given least significant first, into this order, or, as
+    /// the reordering is its own inverse, `xs` given in this order into least
+    /// significant first.
+    pub fn arrange<T>(self, mut xs: Vec<T>) -> Vec<T> {
+        if self == BitOrder::MsbFirst {
+            xs.reverse();
+        }
+        xs
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // primes & crt
 
@@ -183,51 +288,128 @@ pub fn u128_from_bits(bs: &[u16]) -> u128 {
 ///
 /// We are limited by the size of the digits in Wire, and besides, if need large moduli,
 /// you should use BundleGadgets and save.
-pub fn factor(inp: u128) -> Vec<u16> {
+///
+/// Panics if `inp` is not a product of distinct primes in `PRIMES`; see
+/// [`factor_checked`].
+pub fn factor(inp: u128) -> Vec<Modulus> {
+    factor_checked(inp).expect("can only factor numbers with unique prime factors")
+}
+
+/// Factor `inp` using the primes in the global `PRIMES` array, failing with
+/// [`FancyError::UnfactorableModulus`] if it is not a product of distinct
+/// primes among them.
+pub fn factor_checked(inp: u128) -> Result<Vec<Modulus>, FancyError> {
     let mut x = inp;
     let mut fs = Vec::new();
     for &p in PRIMES.iter() {
-        let q = p as u128;
-        if x % q == 0 {
+        let (quotient, remainder) = (x / u128::from(p), x % u128::from(p));
+        if remainder == 0 {
             fs.push(p);
-            x /= q;
+            x = quotient;
         }
     }
-    if x != 1 {
-        panic!("can only factor numbers with unique prime factors");
+    if x == 1 {
+        Ok(fs)
+    } else {
+        Err(FancyError::UnfactorableModulus(inp))
     }
-    fs
 }
 
 /// Compute the CRT representation of x with respect to the primes ps.
-pub fn crt(x: u128, ps: &[u16]) -> Vec<u16> {
-    ps.iter().map(|&p| (x % p as u128) as u16).collect()
+///
+/// `x` is reduced modulo the product of `ps`; see [`crt_checked`].
+pub fn crt(x: u128, ps: &[Modulus]) -> Vec<Modulus> {
+    ps.iter().map(|&p| (x % p as u128) as Modulus).collect()
+}
+
+/// Compute the CRT representation of `x` with respect to the moduli `ps`.
+///
+/// Fails as [`crt_modulus_checked`] does, and with
+/// [`FancyError::CrtValueOutOfRange`] if `x` is not less than the product of
+/// `ps`, rather than reducing it.
+pub fn crt_checked(x: u128, ps: &[Modulus]) -> Result<Vec<Modulus>, FancyError> {
+    let q = crt_modulus_checked(ps)?;
+    if x >= q {
+        return Err(FancyError::CrtValueOutOfRange {
+            value: x,
+            modulus: q,
+        });
+    }
+    Ok(crt(x, ps))
+}
+
+/// Compute the composite modulus of the CRT representation with moduli `ps`.
+///
+/// Fails with [`FancyError::CrtModuliNotCoprime`] if two of the moduli share
+/// a factor, and with [`FancyError::CrtModulusOverflow`] if their product
+/// does not fit in a `u128`.
+pub fn crt_modulus_checked(ps: &[Modulus]) -> Result<u128, FancyError> {
+    for (i, &q) in ps.iter().enumerate() {
+        if let Some(&p) = ps[..i].iter().find(|&&p| gcd(p, q) != 1) {
+            return Err(FancyError::CrtModuliNotCoprime { p, q });
+        }
+    }
+    product_checked(ps).ok_or(FancyError::CrtModulusOverflow)
 }
 
 /// Compute the CRT representation of `x` with respect to the factorization of
 /// `q`.
-pub fn crt_factor(x: u128, q: u128) -> Vec<u16> {
+pub fn crt_factor(x: u128, q: u128) -> Vec<Modulus> {
     crt(x, &factor(q))
 }
 
 /// Compute the value x given a list of CRT primes and residues.
-pub fn crt_inv(xs: &[u16], ps: &[u16]) -> u128 {
-    let mut ret = 0;
-    let M = ps.iter().fold(1, |acc, &x| x as i128 * acc);
-    for (&p, &a) in ps.iter().zip(xs.iter()) {
-        let p = p as i128;
-        let q = M / p;
-        ret += a as i128 * inv(q, p) * q;
-        ret %= &M;
+///
+/// The primes must be pairwise coprime, with a product that fits in a
+/// `u128`; see [`crt_inv_checked`].
+pub fn crt_inv(xs: &[Modulus], ps: &[Modulus]) -> u128 {
+    // Garner's algorithm: find the digits of x in the mixed radix of ps, which
+    // takes no arithmetic on numbers larger than the square of a prime.
+    let mut digits = Vec::with_capacity(ps.len());
+    for (&x, &p) in xs.iter().zip(ps.iter()) {
+        let p_ = u128::from(p);
+        // the value of the digits so far, and the product of their radii, mod p
+        let (mut acc, mut radix) = (0, 1);
+        for (&d, &q) in digits.iter().zip(ps.iter()) {
+            acc = (acc + u128::from(d) * radix) % p_;
+            radix = radix * u128::from(q) % p_;
+        }
+        let diff = (u128::from(x) % p_ + p_ - acc) % p_;
+        let d = diff * inv(radix as i128, p_ as i128) as u128 % p_;
+        digits.push(d as Modulus);
     }
-    ret as u128
+    from_mixed_radix(&digits, ps)
+}
+
+/// Compute the value `x`, less than the product of `ps`, with residues `xs`
+/// modulo the moduli `ps`.
+///
+/// Fails as [`crt_modulus_checked`] does, and with
+/// [`FancyError::InvalidArgNum`] if there are not as many residues as moduli.
+pub fn crt_inv_checked(xs: &[Modulus], ps: &[Modulus]) -> Result<u128, FancyError> {
+    if xs.len() != ps.len() {
+        return Err(FancyError::InvalidArgNum {
+            got: xs.len(),
+            needed: ps.len(),
+        });
+    }
+    crt_modulus_checked(ps)?;
+    Ok(crt_inv(xs, ps))
 }
 
 /// Compute the value `x` given a composite CRT modulus provided by `xs`.
-pub fn crt_inv_factor(xs: &[u16], q: u128) -> u128 {
+pub fn crt_inv_factor(xs: &[Modulus], q: u128) -> u128 {
     crt_inv(xs, &factor(q))
 }
 
+/// The greatest common divisor of `a` and `b`.
+fn gcd(mut a: Modulus, mut b: Modulus) -> Modulus {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
 /// Invert inp_a mod inp_b.
 pub fn inv(inp_a: i128, inp_b: i128) -> i128 {
     let mut a = inp_a;
@@ -265,7 +447,7 @@ pub fn inv(inp_a: i128, inp_b: i128) -> i128 {
 pub const NPRIMES: usize = 29;
 
 /// Primes used in fancy garbling.
-pub const PRIMES: [u16; 29] = [
+pub const PRIMES: [Modulus; 29] = [
     2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
     101, 103, 107, 109,
 ];
@@ -289,28 +471,52 @@ pub fn modulus_with_width(n: u32) -> u128 {
 
 /// Generate the factors of a CRT modulus that support at least n-bit integers, using the
 /// built-in PRIMES.
-pub fn primes_with_width(n: u32) -> Vec<u16> {
+pub fn primes_with_width(n: u32) -> Vec<Modulus> {
     base_primes_with_width(n, &PRIMES)
 }
 
 /// Generate a CRT modulus that support at least n-bit integers, using provided primes.
-pub fn base_modulus_with_width(nbits: u32, primes: &[u16]) -> u128 {
+pub fn base_modulus_with_width(nbits: u32, primes: &[Modulus]) -> u128 {
     product(&base_primes_with_width(nbits, primes))
 }
 
+/// Generate the factors of a CRT modulus that support at least `n`-bit
+/// integers, using the built-in PRIMES, failing if there are not enough.
+pub fn primes_with_width_checked(n: u32) -> Result<Vec<Modulus>, FancyError> {
+    base_primes_with_width_checked(n, &PRIMES)
+}
+
 /// Generate the factors of a CRT modulus that support at least n-bit integers, using provided primes.
-pub fn base_primes_with_width(nbits: u32, primes: &[u16]) -> Vec<u16> {
-    let mut res = 1;
+///
+/// Panics if there are not enough primes; see
+/// [`base_primes_with_width_checked`].
+pub fn base_primes_with_width(nbits: u32, primes: &[Modulus]) -> Vec<Modulus> {
+    base_primes_with_width_checked(nbits, primes).expect("not enough primes!")
+}
+
+/// Generate the factors of a CRT modulus that support at least `nbits`-bit
+/// integers, using a prefix of `primes`.
+///
+/// Fails with [`FancyError::CrtWidthUnachievable`] if the product of all of
+/// `primes` is less than `2^nbits`, or if it would not fit in a `u128` by the
+/// time it reaches `2^nbits`, as for any `nbits` of 128 or more.
+pub fn base_primes_with_width_checked(
+    nbits: u32,
+    primes: &[Modulus],
+) -> Result<Vec<Modulus>, FancyError> {
+    let mut res: u128 = 1;
     let mut ps = Vec::new();
     for &p in primes.iter() {
-        res *= u128::from(p);
+        res = match res.checked_mul(u128::from(p)) {
+            Some(res) => res,
+            None => break,
+        };
         ps.push(p);
-        if (res >> nbits) > 0 {
-            break;
+        if nbits < 128 && res >> nbits > 0 {
+            return Ok(ps);
         }
     }
-    assert!((res >> nbits) > 0, "not enough primes!");
-    ps
+    Err(FancyError::CrtWidthUnachievable { nbits })
 }
 
 /// Generate a CRT modulus that support at least n-bit integers, using the built-in
@@ -320,10 +526,16 @@ pub fn base_primes_with_width(nbits: u32, primes: &[u16]) -> Vec<u16> {
 // }
 
 /// Compute the product of some u16s as a u128.
-pub fn product(xs: &[u16]) -> u128 {
+pub fn product(xs: &[Modulus]) -> u128 {
     xs.iter().fold(1, |acc, &x| acc * x as u128)
 }
 
+/// Compute the product of some moduli as a `u128`, or `None` if it overflows.
+pub fn product_checked(xs: &[Modulus]) -> Option<u128> {
+    xs.iter()
+        .try_fold(1u128, |acc, &x| acc.checked_mul(u128::from(x)))
+}
+
 /// Raise a u16 to a power mod some value.
 // pub fn powm(inp: u16, pow: u16, modulus: u16) -> u16 {
 //     let mut x = inp as u16;
@@ -342,12 +554,12 @@ pub fn product(xs: &[u16]) -> u128 {
 // }
 
 /// Returns `true` if `x` is a power of 2.
-pub fn is_power_of_2(x: u16) -> bool {
+pub fn is_power_of_2(x: Modulus) -> bool {
     (x & (x - 1)) == 0
 }
 
 /// Generate deltas ahead of time for the Garbler.
-pub fn generate_deltas<Wire: WireLabel>(primes: &[u16]) -> HashMap<u16, Wire> {
+pub fn generate_deltas<Wire: WireLabel>(primes: &[Modulus]) -> HashMap<Modulus, Wire> {
     let mut deltas = HashMap::new();
     let mut rng = rand::thread_rng();
     for q in primes {
@@ -356,6 +568,34 @@ pub fn generate_deltas<Wire: WireLabel>(primes: &[u16]) -> HashMap<u16, Wire> {
     deltas
 }
 
+////////////////////////////////////////////////////////////////////////////////
+// parallelism
+
+/// Number of items at which `par_map` switches over to using multiple threads.
+#[cfg(feature = "rayon")]
+pub(crate) const PARALLEL_THRESHOLD: usize = 1 << 12;
+
+/// Map `f` over `xs`, preserving order.
+///
+/// When the `rayon` feature is enabled and `xs` has at least
+/// `PARALLEL_THRESHOLD` elements, the map is computed on the rayon thread
+/// pool. Otherwise it is computed serially.
+pub(crate) fn par_map<T, U, F>(xs: &[T], f: F) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> U + Sync + Send,
+{
+    #[cfg(feature = "rayon")]
+    {
+        if xs.len() >= PARALLEL_THRESHOLD {
+            use rayon::prelude::*;
+            return xs.par_iter().map(f).collect();
+        }
+    }
+    xs.iter().map(f).collect()
+}
+
 /// Extra Rng functionality, useful for `fancy-garbling`.
 pub trait RngExt: rand::Rng + Sized {
     /// Randomly generate a `bool`.
@@ -366,6 +606,10 @@ pub trait RngExt: rand::Rng + Sized {
     fn gen_u16(&mut self) -> u16 {
         self.gen()
     }
+    /// Randomly generate a value mod `q`.
+    fn gen_residue(&mut self, q: Modulus) -> Modulus {
+        self.gen::<Modulus>() % q
+    }
     /// Randomly generate a `u32`.
     fn gen_u32(&mut self) -> u32 {
         self.gen()
@@ -387,7 +631,7 @@ pub trait RngExt: rand::Rng + Sized {
         self.gen()
     }
     /// Randomly generate a valid `Block`.
-    fn gen_usable_block(&mut self, modulus: u16) -> Block {
+    fn gen_usable_block(&mut self, modulus: Modulus) -> Block {
         if is_power_of_2(modulus) {
             let nbits = (modulus - 1).count_ones();
             if 128 % nbits == 0 {
@@ -399,19 +643,19 @@ pub trait RngExt: rand::Rng + Sized {
         Block::from(self.gen_u128() % max)
     }
     /// Randomly generate a prime (among the set of supported primes).
-    fn gen_prime(&mut self) -> u16 {
+    fn gen_prime(&mut self) -> Modulus {
         PRIMES[self.gen::<usize>() % NPRIMES]
     }
     /// Randomly generate a (supported) modulus.
-    fn gen_modulus(&mut self) -> u16 {
-        2 + (self.gen::<u16>() % 111)
+    fn gen_modulus(&mut self) -> Modulus {
+        2 + (self.gen::<Modulus>() % 111)
     }
     /// Randomly generate a valid composite modulus.
     fn gen_usable_composite_modulus(&mut self) -> u128 {
         product(&self.gen_usable_factors())
     }
     /// Randomly generate a vector of valid factor
-    fn gen_usable_factors(&mut self) -> Vec<u16> {
+    fn gen_usable_factors(&mut self) -> Vec<Modulus> {
         let mut x: u128 = 1;
         PRIMES[..25]
             .iter()
@@ -442,6 +686,29 @@ mod tests {
     use crate::util::RngExt;
     use rand::thread_rng;
 
+    #[test]
+    fn bit_order() {
+        let mut rng = thread_rng();
+        for order in [BitOrder::LsbFirst, BitOrder::MsbFirst] {
+            for _ in 0..64 {
+                let x = rng.gen_u64() as u128;
+                let bits = order.bits(x, 64);
+                assert_eq!(order.value(&bits), x);
+                let bytes = order.bits_to_bytes(&bits).unwrap();
+                assert_eq!(bytes, x.to_be_bytes()[8..]);
+                assert_eq!(order.bytes_to_bits(&bytes), bits);
+            }
+            assert_eq!(order.value(&[]), 0);
+            assert!(order.bits_to_bytes(&[0; 7]).is_err());
+            assert!(order.bits_to_bytes(&[2; 8]).is_err());
+        }
+        assert_eq!(BitOrder::LsbFirst.bits(6, 4), [0, 1, 1, 0]);
+        assert_eq!(BitOrder::MsbFirst.bits(6, 4), [0, 1, 1, 0]);
+        assert_eq!(BitOrder::MsbFirst.bits(1, 4), [0, 0, 0, 1]);
+        assert_eq!(BitOrder::MsbFirst.bytes_to_bits(&[0x80])[0], 1);
+        assert_eq!(BitOrder::LsbFirst.bits(1, 4), u128_to_bits(1, 4));
+    }
+
     #[test]
     fn crt_conversion() {
         let mut rng = thread_rng();
@@ -473,6 +740,78 @@ mod tests {
         }
     }
 
+    #[test]
+    fn crt_checked_boundary() {
+        // the largest product of a prefix of PRIMES that fits in a u128
+        let ps = &PRIMES[..26];
+        let q = product_checked(ps).unwrap();
+        assert_eq!(crt_modulus_checked(ps).unwrap(), q);
+        assert_eq!(
+            crt_inv_checked(&crt_checked(q - 1, ps).unwrap(), ps).unwrap(),
+            q - 1
+        );
+        assert!(matches!(
+            crt_checked(q, ps),
+            Err(FancyError::CrtValueOutOfRange { value, modulus }) if value == q && modulus == q
+        ));
+        assert!(crt_checked(u128::MAX, ps).is_err());
+    }
+
+    #[test]
+    fn crt_checked_overflow() {
+        let ps = &PRIMES[..27];
+        assert_eq!(product_checked(ps), None);
+        assert!(matches!(
+            crt_modulus_checked(ps),
+            Err(FancyError::CrtModulusOverflow)
+        ));
+        assert!(crt_checked(0, ps).is_err());
+        assert!(crt_inv_checked(&[0; 27], ps).is_err());
+        assert!(matches!(
+            crt_inv_checked(&[0; 3], &PRIMES[..4]),
+            Err(FancyError::InvalidArgNum { got: 3, needed: 4 })
+        ));
+        assert!(matches!(
+            crt_modulus_checked(&[3, 5, 9]),
+            Err(FancyError::CrtModuliNotCoprime { p: 3, q: 9 })
+        ));
+        assert!(matches!(
+            factor_checked(9),
+            Err(FancyError::UnfactorableModulus(9))
+        ));
+    }
+
+    #[test]
+    fn crt_round_trip_near_top() {
+        let mut rng = thread_rng();
+        for n in 1..=26 {
+            let ps = &PRIMES[..n];
+            let q = product_checked(ps).unwrap();
+            for _ in 0..16 {
+                let x = q - 1 - rng.gen_u128() % q.min(1 << 16);
+                assert_eq!(
+                    crt_inv_checked(&crt_checked(x, ps).unwrap(), ps).unwrap(),
+                    x
+                );
+                assert_eq!(crt_inv_factor(&crt(x, ps), q), x);
+            }
+        }
+    }
+
+    #[test]
+    fn crt_width() {
+        assert_eq!(primes_with_width_checked(127).unwrap(), PRIMES[..26]);
+        assert!(product(&primes_with_width(120)) >> 120 > 0);
+        assert!(matches!(
+            primes_with_width_checked(128),
+            Err(FancyError::CrtWidthUnachievable { nbits: 128 })
+        ));
+        assert!(matches!(
+            base_primes_with_width_checked(16, &PRIMES[..3]),
+            Err(FancyError::CrtWidthUnachievable { nbits: 16 })
+        ));
+    }
+
     #[test]
     fn bits() {
         let mut rng = thread_rng();
@@ -486,13 +825,32 @@ mod tests {
     fn base_q_conversion() {
         let mut rng = thread_rng();
         for _ in 0..1000 {
-            let q = 2 + (rng.gen_u16() % 111);
+            let q = 2 + rng.gen_residue(111);
             let x = u128::from(rng.gen_usable_block(q));
             let y = as_base_q(x, q, digits_per_u128(q));
             let z = from_base_q(&y, q);
             assert_eq!(x, z);
         }
     }
+
+    #[test]
+    fn modular_arithmetic() {
+        let mut rng = thread_rng();
+        for q in [2, 3, 113, 65521, Modulus::MAX] {
+            for _ in 0..128 {
+                let (x, y) = (rng.gen_residue(q), rng.gen_residue(q));
+                let c = rng.gen_residue(16);
+                let (x_, y_, q_) = (x as u128, y as u128, q as u128);
+                assert_eq!(add_mod(x, y, q) as u128, (x_ + y_) % q_);
+                assert_eq!(mul_mod(x, y, q) as u128, x_ * y_ % q_);
+                let pow = (0..c).fold(1 % q_, |acc, _| acc * x_ % q_);
+                assert_eq!(pow_mod(x, c, q) as u128, pow);
+                let mut xs = vec![x, 0];
+                base_q_add_eq(&mut xs, &[y], q);
+                assert_eq!(from_base_q(&xs, q), x_ + y_);
+            }
+        }
+    }
 }
 
 #[cfg(all(feature = "nightly", test))]
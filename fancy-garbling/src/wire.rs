@@ -1,17 +1,31 @@
 //! Low-level operations on wire-labels, the basic building block of garbled circuits.
 
-use crate::{fancy::HasModulus, util};
+use crate::{fancy::HasModulus, util, Modulus};
+#[cfg(not(feature = "wide-moduli"))]
 use fancy_garbling_base_conversion as base_conversion;
 use rand::{CryptoRng, Rng, RngCore};
-use scuttlebutt::{Block, AES_HASH};
+use scuttlebutt::{Aes256, Block, Block512};
+// The hash of wire labels: fixed-key AES, or BLAKE3 with the `blake3-hash`
+// feature.
+#[cfg(not(feature = "blake3-hash"))]
+use scuttlebutt::AES_HASH as HASH;
+#[cfg(feature = "blake3-hash")]
+use scuttlebutt::BLAKE3_HASH as HASH;
 use subtle::ConditionallySelectable;
 use vectoreyes::array_utils::{ArrayUnrolledExt, ArrayUnrolledOps, UnrollableArraySize};
+#[cfg(feature = "zeroize")]
+use zeroize::Zeroize;
 
+use crate::errors::LabelError;
 #[cfg(feature = "serde")]
 use crate::errors::{ModQDeserializationError, WireDeserializationError};
 
+mod digits;
+mod export;
 mod npaths_tab;
 
+pub use export::{ExportedLabel, WireRole, EXPORTED_LABEL_LEN, LABEL_FORMAT_VERSION};
+
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The core wire-label type.
@@ -32,7 +46,19 @@ where
     ArrayUnrolledOps: UnrollableArraySize<Q>,
 {
     let batch = wires.array_map(|x| x.as_block());
-    AES_HASH.tccr_hash_many(tweak, batch)
+    HASH.tccr_hash_many(tweak, batch)
+}
+
+/// Batch hashing of wires, each under the tweak of the same index in `tweaks`
+pub fn hash_wires_tweaked<const Q: usize, W: WireLabel>(
+    wires: [&W; Q],
+    tweaks: [Block; Q],
+) -> [Block; Q]
+where
+    ArrayUnrolledOps: UnrollableArraySize<Q>,
+{
+    let batch = wires.array_map(|x| x.as_block());
+    HASH.tccr_hash_many_tweaked(tweaks, batch)
 }
 
 /// Marker trait indicating an arithmetic wire
@@ -40,46 +66,80 @@ pub trait ArithmeticWire: Clone {}
 
 /// Trait implementing a wire that can be used for secure computation
 /// via garbled circuits
-pub trait WireLabel: Clone + HasModulus {
+pub trait WireLabel: Clone + HasModulus + Send + Sync {
+    /// The widest labels of binary wires this type holds, in bits; see
+    /// [`SecurityParams`](crate::SecurityParams).
+    const MAX_LABEL_BITS: usize = 128;
+
     /// Get the digits of the wire
-    fn digits(&self) -> Vec<u16>;
+    fn digits(&self) -> Vec<Modulus>;
 
     /// Pack the wire into a `Block`.
     fn as_block(&self) -> Block;
 
+    /// Pack the wire into a `Block512`: [`WireLabel::as_block`] followed by
+    /// zeros, for every label but a [`WideWireMod2`].
+    fn as_block512(&self) -> Block512 {
+        let zero = Block::default();
+        Block512::from([self.as_block(), zero, zero, zero])
+    }
+
     /// Get the color digit of the wire.
-    fn color(&self) -> u16;
+    fn color(&self) -> Modulus;
 
     /// Add another wire digit-wise into this one. Assumes that both wires have
     /// the same modulus.
     fn plus_eq<'a>(&'a mut self, other: &Self) -> &'a mut Self;
 
     /// Multiply each digit by a constant `c mod q`.
-    fn cmul_eq(&mut self, c: u16) -> &mut Self;
+    fn cmul_eq(&mut self, c: Modulus) -> &mut Self;
 
     /// Negate all the digits mod q.
     fn negate_eq(&mut self) -> &mut Self;
 
     /// Pack the wire into a `Block`.
-    fn from_block(inp: Block, q: u16) -> Self;
+    fn from_block(inp: Block, q: Modulus) -> Self;
+
+    /// Unpack a wire from a `Block512`, of which every label but a
+    /// [`WideWireMod2`] takes the first block.
+    fn from_block512(inp: Block512, q: Modulus) -> Self {
+        let [block, ..] = <[Block; 4]>::from(inp);
+        Self::from_block(block, q)
+    }
 
     /// The zero wire with modulus `q`
-    fn zero(q: u16) -> Self;
+    fn zero(q: Modulus) -> Self;
+
+    /// Export the label in the portable format of [`ExportedLabel`], in the
+    /// role [`WireRole::Unspecified`].
+    fn export(&self) -> ExportedLabel {
+        ExportedLabel::new(self, WireRole::Unspecified)
+    }
+
+    /// Import a label exported by [`WireLabel::export`], or written in the
+    /// same format elsewhere.
+    ///
+    /// Fails if the label has a modulus this type cannot hold, if its bytes
+    /// are not those of a label of its modulus, or if the color given with
+    /// it is not its own.
+    fn import(label: ExportedLabel) -> Result<Self, LabelError> {
+        export::import(label, |_| true)
+    }
 
     /// Get a random wire label mod `q`, with the first digit set to `1`
-    fn rand_delta<R: CryptoRng + Rng>(rng: &mut R, q: u16) -> Self;
+    fn rand_delta<R: CryptoRng + Rng>(rng: &mut R, q: Modulus) -> Self;
 
     /// Get a random wire `mod q`.
-    fn rand<R: CryptoRng + RngCore>(rng: &mut R, q: u16) -> Self;
+    fn rand<R: CryptoRng + RngCore>(rng: &mut R, q: Modulus) -> Self;
 
     /// Subroutine of hashback that converts the hash block into a valid wire of the given
     /// modulus. Also useful when batching hashes ahead of time for later conversion.
-    fn hash_to_mod(hash: Block, q: u16) -> Self;
+    fn hash_to_mod(hash: Block, q: Modulus) -> Self;
 
     /// Compute the hash of this wire, converting the result back to a wire.
     ///
     /// Uses fixed-key AES.
-    fn hashback(&self, tweak: Block, q: u16) -> Self {
+    fn hashback(&self, tweak: Block, q: Modulus) -> Self {
         let hash = self.hash(tweak);
         Self::hash_to_mod(hash, q)
     }
@@ -91,13 +151,13 @@ pub trait WireLabel: Clone + HasModulus {
     }
 
     /// Multiply each digit by a constant `c mod q`, consuming it for chained computations.
-    fn cmul_mov(mut self, c: u16) -> Self {
+    fn cmul_mov(mut self, c: Modulus) -> Self {
         self.cmul_eq(c);
         self
     }
 
     /// Multiply each digit by a constant `c mod q`, returning a new wire.
-    fn cmul(&self, c: u16) -> Self {
+    fn cmul(&self, c: Modulus) -> Self {
         self.clone().cmul_mov(c)
     }
 
@@ -136,10 +196,19 @@ pub trait WireLabel: Clone + HasModulus {
 
     /// Compute the hash of this wire.
     ///
-    /// Uses fixed-key AES.
+    /// Uses fixed-key AES, or BLAKE3 with the `blake3-hash` feature.
     #[inline(never)]
     fn hash(&self, tweak: Block) -> Block {
-        AES_HASH.tccr_hash(tweak, self.as_block())
+        HASH.tccr_hash(tweak, self.as_block())
+    }
+
+    /// Overwrite the label with the zero label, as its last use, so that a
+    /// secret label such as a delta does not linger in memory.
+    ///
+    /// With the `zeroize` feature, the labels of this crate are overwritten
+    /// with `zeroize`, which the compiler cannot optimize away.
+    fn wipe(&mut self) {
+        *self = Self::zero(self.modulus());
     }
 }
 
@@ -160,6 +229,53 @@ impl ConditionallySelectable for WireMod2 {
     }
 }
 
+/// Representation of a `mod-2` wire with a label of up to 256 bits, for
+/// security parameters with labels wider than 128 bits; see
+/// [`SecurityParams`](crate::SecurityParams).
+///
+/// The label is held in a `Block512`, of which the bits past the first 256
+/// are zero. Binary gates are garbled with [`WideWireMod2::hash_wide`] rather
+/// than the 128-bit hash of the other labels, and custom gates, whose
+/// [`CustomGate`](crate::circuit::CustomGate)s garble 128-bit labels, are
+/// refused.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WideWireMod2 {
+    /// A value of up to 256 bits.
+    val: Block512,
+}
+
+impl WideWireMod2 {
+    /// Hash the label under `tweak` to 256 bits, the rest of the result zero.
+    ///
+    /// The first 256 bits of the label key AES-256, which encrypts the tweak
+    /// and the tweak with its top bit flipped. Unlike the 128-bit hash, this
+    /// is not fixed-key: it relies on AES-256 being a pseudorandom function
+    /// under keys that differ by the secret delta.
+    pub fn hash_wide(&self, tweak: Block) -> Block512 {
+        let mut key = [0; 32];
+        key.copy_from_slice(&self.val.as_ref()[..32]);
+        let aes = Aes256::new(&key);
+        let flipped = tweak ^ Block::from(1 << 127);
+        let zero = Block::default();
+        Block512::from([aes.encrypt(tweak), aes.encrypt(flipped), zero, zero])
+    }
+}
+
+impl ConditionallySelectable for WideWireMod2 {
+    fn conditional_select(a: &Self, b: &Self, choice: subtle::Choice) -> Self {
+        let a = <[Block; 4]>::from(a.val);
+        let b = <[Block; 4]>::from(b.val);
+        let mut val = [Block::default(); 4];
+        for (v, (a, b)) in val.iter_mut().zip(a.iter().zip(b.iter())) {
+            *v = Block::conditional_select(a, b, choice);
+        }
+        WideWireMod2 {
+            val: Block512::from(val),
+        }
+    }
+}
+
 /// Intermediate struct to deserialize WireMod3 to
 ///
 /// Checks that both lsb and msb are not set before allowing to convert to WireMod3
@@ -194,9 +310,9 @@ impl TryFrom<UntrustedWireMod3> for WireMod3 {
 #[derive(serde::Deserialize)]
 struct UntrustedWireModQ {
     /// The modulus of the wire label
-    q: u16, // Assuming mod can fit in u16
+    q: Modulus, // Assuming mod can fit in u16
     /// A list of `mod-q` digits.
-    ds: Vec<u16>,
+    ds: Vec<Modulus>,
 }
 
 #[cfg(feature = "serde")]
@@ -267,31 +383,37 @@ pub struct WireMod3 {
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct WireModQ {
     /// The modulus of the wire label
-    q: u16,
+    q: Modulus,
     /// A list of `mod-q` digits.
-    ds: Vec<u16>,
+    ds: Vec<Modulus>,
 }
 
 impl HasModulus for WireMod2 {
-    fn modulus(&self) -> u16 {
+    fn modulus(&self) -> Modulus {
+        2
+    }
+}
+
+impl HasModulus for WideWireMod2 {
+    fn modulus(&self) -> Modulus {
         2
     }
 }
 
 impl HasModulus for WireMod3 {
-    fn modulus(&self) -> u16 {
+    fn modulus(&self) -> Modulus {
         3
     }
 }
 
 impl HasModulus for WireModQ {
-    fn modulus(&self) -> u16 {
+    fn modulus(&self) -> Modulus {
         self.q
     }
 }
 
 impl HasModulus for AllWire {
-    fn modulus(&self) -> u16 {
+    fn modulus(&self) -> Modulus {
         match &self {
             AllWire::Mod2(x) => x.modulus(),
             AllWire::Mod3(x) => x.modulus(),
@@ -301,7 +423,7 @@ impl HasModulus for AllWire {
 }
 
 impl WireLabel for AllWire {
-    fn rand_delta<R: CryptoRng + Rng>(rng: &mut R, q: u16) -> Self {
+    fn rand_delta<R: CryptoRng + Rng>(rng: &mut R, q: Modulus) -> Self {
         match q {
             2 => AllWire::Mod2(WireMod2::rand_delta(rng, q)),
             3 => AllWire::Mod3(WireMod3::rand_delta(rng, q)),
@@ -309,7 +431,7 @@ impl WireLabel for AllWire {
         }
     }
 
-    fn digits(&self) -> Vec<u16> {
+    fn digits(&self) -> Vec<Modulus> {
         match &self {
             AllWire::Mod2(x) => x.digits(),
             AllWire::Mod3(x) => x.digits(),
@@ -324,7 +446,7 @@ impl WireLabel for AllWire {
             AllWire::ModN(x) => x.as_block(),
         }
     }
-    fn color(&self) -> u16 {
+    fn color(&self) -> Modulus {
         match &self {
             AllWire::Mod2(x) => x.color(),
             AllWire::Mod3(x) => x.color(),
@@ -353,7 +475,7 @@ impl WireLabel for AllWire {
         self
     }
 
-    fn cmul_eq(&mut self, c: u16) -> &mut Self {
+    fn cmul_eq(&mut self, c: Modulus) -> &mut Self {
         match &mut *self {
             AllWire::Mod2(ref mut x) => {
                 x.cmul_eq(c);
@@ -381,7 +503,7 @@ impl WireLabel for AllWire {
         };
         self
     }
-    fn from_block(inp: Block, q: u16) -> Self {
+    fn from_block(inp: Block, q: Modulus) -> Self {
         match q {
             2 => AllWire::Mod2(WireMod2::from_block(inp, q)),
             3 => AllWire::Mod3(WireMod3::from_block(inp, q)),
@@ -389,7 +511,7 @@ impl WireLabel for AllWire {
         }
     }
 
-    fn zero(q: u16) -> Self {
+    fn zero(q: Modulus) -> Self {
         match q {
             2 => AllWire::Mod2(WireMod2::zero(q)),
             3 => AllWire::Mod3(WireMod3::zero(q)),
@@ -397,7 +519,7 @@ impl WireLabel for AllWire {
         }
     }
 
-    fn rand<R: CryptoRng + RngCore>(rng: &mut R, q: u16) -> Self {
+    fn rand<R: CryptoRng + RngCore>(rng: &mut R, q: Modulus) -> Self {
         match q {
             2 => AllWire::Mod2(WireMod2::rand(rng, q)),
             3 => AllWire::Mod3(WireMod3::rand(rng, q)),
@@ -405,13 +527,18 @@ impl WireLabel for AllWire {
         }
     }
 
-    fn hash_to_mod(hash: Block, q: u16) -> Self {
+    fn hash_to_mod(hash: Block, q: Modulus) -> Self {
         if q == 3 {
             AllWire::Mod3(WireMod3::encode_block_mod3(hash))
         } else {
             Self::from_block(hash, q)
         }
     }
+
+    #[cfg(feature = "zeroize")]
+    fn wipe(&mut self) {
+        self.zeroize();
+    }
 }
 
 impl WireMod3 {
@@ -425,7 +552,7 @@ impl WireMod3 {
         let mut ds = _unrank(u128::from(block), 3);
         for (i, v) in ds.drain(..64).enumerate() {
             lsb |= ((v & 1) as u64) << i;
-            msb |= (((v >> 1) & 1u16) as u64) << i;
+            msb |= (((v >> 1) & 1) as u64) << i;
         }
         debug_assert_eq!(lsb & msb, 0);
         Self { lsb, msb }
@@ -433,7 +560,11 @@ impl WireMod3 {
 }
 
 impl WireLabel for WireMod2 {
-    fn rand_delta<R: CryptoRng + Rng>(rng: &mut R, q: u16) -> Self {
+    fn import(label: ExportedLabel) -> Result<Self, LabelError> {
+        export::import(label, |q| q == 2)
+    }
+
+    fn rand_delta<R: CryptoRng + Rng>(rng: &mut R, q: Modulus) -> Self {
         if q != 2 {
             panic!("[WireMod2::rand_delta] Expected modulo 2. Got {}", q);
         }
@@ -442,9 +573,9 @@ impl WireLabel for WireMod2 {
         w
     }
 
-    fn digits(&self) -> Vec<u16> {
+    fn digits(&self) -> Vec<Modulus> {
         (0..128)
-            .map(|i| ((u128::from(self.val) >> i) as u16) & 1)
+            .map(|i| ((u128::from(self.val) >> i) as Modulus) & 1)
             .collect()
     }
 
@@ -452,8 +583,8 @@ impl WireLabel for WireMod2 {
         self.val
     }
 
-    fn color(&self) -> u16 {
-        self.val.lsb() as u16
+    fn color(&self) -> Modulus {
+        self.val.lsb() as Modulus
     }
 
     fn plus_eq<'a>(&'a mut self, other: &Self) -> &'a mut Self {
@@ -461,7 +592,7 @@ impl WireLabel for WireMod2 {
         self
     }
 
-    fn cmul_eq(&mut self, c: u16) -> &mut Self {
+    fn cmul_eq(&mut self, c: Modulus) -> &mut Self {
         if c & 1 == 0 {
             self.val = Block::default();
         }
@@ -473,21 +604,21 @@ impl WireLabel for WireMod2 {
         self
     }
 
-    fn from_block(inp: Block, q: u16) -> Self {
+    fn from_block(inp: Block, q: Modulus) -> Self {
         if q != 2 {
             panic!("[WireMod2::from_block] Expected modulo 2. Got {}", q);
         }
         Self { val: inp }
     }
 
-    fn zero(q: u16) -> Self {
+    fn zero(q: Modulus) -> Self {
         if q != 2 {
             panic!("[WireMod2::zero] Expected modulo 2. Got {}", q);
         }
         Self::default()
     }
 
-    fn rand<R: CryptoRng + RngCore>(rng: &mut R, q: u16) -> Self {
+    fn rand<R: CryptoRng + RngCore>(rng: &mut R, q: Modulus) -> Self {
         if q != 2 {
             panic!("[WireMod2::rand] Expected modulo 2. Got {}", q);
         }
@@ -495,16 +626,126 @@ impl WireLabel for WireMod2 {
         Self { val: rng.gen() }
     }
 
-    fn hash_to_mod(hash: Block, q: u16) -> Self {
+    fn hash_to_mod(hash: Block, q: Modulus) -> Self {
         if q != 2 {
             panic!("[WireMod2::hash_to_mod] Expected modulo 2. Got {}", q);
         }
         Self::from_block(hash, q)
     }
+
+    #[cfg(feature = "zeroize")]
+    fn wipe(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl WireLabel for WideWireMod2 {
+    const MAX_LABEL_BITS: usize = 256;
+
+    // An exported label holds 128 bits, which are imported as a label of up
+    // to 128 bits.
+    fn import(label: ExportedLabel) -> Result<Self, LabelError> {
+        export::import(label, |q| q == 2)
+    }
+
+    fn rand_delta<R: CryptoRng + Rng>(rng: &mut R, q: Modulus) -> Self {
+        if q != 2 {
+            panic!("[WideWireMod2::rand_delta] Expected modulo 2. Got {}", q);
+        }
+        let mut w = Self::rand(rng, q);
+        let [first, rest @ ..] = <[Block; 4]>::from(w.val);
+        w.val = Block512::from([first.set_lsb(), rest[0], rest[1], rest[2]]);
+        w
+    }
+
+    fn digits(&self) -> Vec<Modulus> {
+        let bytes = self.val.as_ref();
+        (0..Self::MAX_LABEL_BITS)
+            .map(|i| Modulus::from(bytes[i / 8] >> (i % 8)) & 1)
+            .collect()
+    }
+
+    /// The first 128 bits of the label.
+    fn as_block(&self) -> Block {
+        <[Block; 4]>::from(self.val)[0]
+    }
+
+    fn as_block512(&self) -> Block512 {
+        self.val
+    }
+
+    fn color(&self) -> Modulus {
+        self.as_block().lsb() as Modulus
+    }
+
+    fn plus_eq<'a>(&'a mut self, other: &Self) -> &'a mut Self {
+        self.val ^= other.val;
+        self
+    }
+
+    fn cmul_eq(&mut self, c: Modulus) -> &mut Self {
+        if c & 1 == 0 {
+            self.val = Block512::default();
+        }
+        self
+    }
+
+    fn negate_eq(&mut self) -> &mut Self {
+        // Do nothing. Additive inverse is a no-op for mod 2.
+        self
+    }
+
+    /// The label of `inp`, with the bits past its 128 zero.
+    fn from_block(inp: Block, q: Modulus) -> Self {
+        let zero = Block::default();
+        Self::from_block512(Block512::from([inp, zero, zero, zero]), q)
+    }
+
+    fn from_block512(inp: Block512, q: Modulus) -> Self {
+        if q != 2 {
+            panic!("[WideWireMod2::from_block512] Expected modulo 2. Got {}", q);
+        }
+        Self { val: inp }
+    }
+
+    fn zero(q: Modulus) -> Self {
+        if q != 2 {
+            panic!("[WideWireMod2::zero] Expected modulo 2. Got {}", q);
+        }
+        Self::default()
+    }
+
+    fn rand<R: CryptoRng + RngCore>(rng: &mut R, q: Modulus) -> Self {
+        if q != 2 {
+            panic!("[WideWireMod2::rand] Expected modulo 2. Got {}", q);
+        }
+        let zero = Block::default();
+        Self {
+            val: Block512::from([rng.gen(), rng.gen(), zero, zero]),
+        }
+    }
+
+    fn hash_to_mod(hash: Block, q: Modulus) -> Self {
+        Self::from_block(hash, q)
+    }
+
+    /// The first 128 bits of [`WideWireMod2::hash_wide`].
+    fn hash(&self, tweak: Block) -> Block {
+        <[Block; 4]>::from(self.hash_wide(tweak))[0]
+    }
+
+    #[cfg(feature = "zeroize")]
+    fn wipe(&mut self) {
+        self.zeroize();
+    }
 }
 
 impl WireLabel for WireMod3 {
-    fn rand_delta<R: CryptoRng + Rng>(rng: &mut R, q: u16) -> Self {
+    fn import(label: ExportedLabel) -> Result<Self, LabelError> {
+        export::import(label, |q| q == 3)
+    }
+
+    fn rand_delta<R: CryptoRng + Rng>(rng: &mut R, q: Modulus) -> Self {
         if q != 3 {
             panic!("[WireMod3::rand_delta] Expected modulo 3. Got {}", q);
         }
@@ -514,9 +755,9 @@ impl WireLabel for WireMod3 {
         w
     }
 
-    fn digits(&self) -> Vec<u16> {
+    fn digits(&self) -> Vec<Modulus> {
         (0..64)
-            .map(|i| (((self.lsb >> i) as u16) & 1) & ((((self.msb >> i) as u16) & 1) << 1))
+            .map(|i| (((self.lsb >> i) as Modulus) & 1) & ((((self.msb >> i) as Modulus) & 1) << 1))
             .collect()
     }
 
@@ -524,8 +765,8 @@ impl WireLabel for WireMod3 {
         Block::from(((self.msb as u128) << 64) | (self.lsb as u128))
     }
 
-    fn color(&self) -> u16 {
-        let color = (((self.msb & 1) as u16) << 1) | ((self.lsb & 1) as u16);
+    fn color(&self) -> Modulus {
+        let color = (((self.msb & 1) as Modulus) << 1) | ((self.lsb & 1) as Modulus);
         debug_assert_ne!(color, 3);
         color
     }
@@ -544,7 +785,7 @@ impl WireLabel for WireMod3 {
         self
     }
 
-    fn cmul_eq(&mut self, c: u16) -> &mut Self {
+    fn cmul_eq(&mut self, c: Modulus) -> &mut Self {
         match c {
             0 => {
                 self.msb = 0;
@@ -567,7 +808,7 @@ impl WireLabel for WireMod3 {
         self
     }
 
-    fn from_block(inp: Block, q: u16) -> Self {
+    fn from_block(inp: Block, q: Modulus) -> Self {
         if q != 3 {
             panic!("[WireMod3::from_block] Expected mod 3. Got mod {}", q)
         }
@@ -578,14 +819,14 @@ impl WireLabel for WireMod3 {
         Self { lsb, msb }
     }
 
-    fn zero(q: u16) -> Self {
+    fn zero(q: Modulus) -> Self {
         if q != 3 {
             panic!("[WireMod3::zero] Expected modulo 3. Got {}", q);
         }
         Self::default()
     }
 
-    fn rand<R: CryptoRng + RngCore>(rng: &mut R, q: u16) -> Self {
+    fn rand<R: CryptoRng + RngCore>(rng: &mut R, q: Modulus) -> Self {
         if q != 3 {
             panic!("[WireMod3::rand] Expected mod 3. Got mod {}", q)
         }
@@ -599,16 +840,26 @@ impl WireLabel for WireMod3 {
         Self { lsb, msb }
     }
 
-    fn hash_to_mod(hash: Block, q: u16) -> Self {
+    fn hash_to_mod(hash: Block, q: Modulus) -> Self {
         if q != 3 {
             panic!("[WireMod3::hash_to_mod] Expected mod 3. Got mod {}", q)
         }
         Self::encode_block_mod3(hash)
     }
+
+    #[cfg(feature = "zeroize")]
+    fn wipe(&mut self) {
+        self.zeroize();
+    }
 }
 
 impl WireLabel for WireModQ {
-    fn rand_delta<R: CryptoRng + Rng>(rng: &mut R, q: u16) -> Self {
+    // Labels of modulus 3 are packed as those of `WireMod3`.
+    fn import(label: ExportedLabel) -> Result<Self, LabelError> {
+        export::import(label, |q| q > 3)
+    }
+
+    fn rand_delta<R: CryptoRng + Rng>(rng: &mut R, q: Modulus) -> Self {
         if q < 2 {
             panic!(
                 "[WireModQ::rand_delta] Modulus must be at least 2. Got {}",
@@ -620,7 +871,7 @@ impl WireLabel for WireModQ {
         w
     }
 
-    fn digits(&self) -> Vec<u16> {
+    fn digits(&self) -> Vec<Modulus> {
         self.ds.clone()
     }
 
@@ -628,49 +879,35 @@ impl WireLabel for WireModQ {
         Block::from(util::from_base_q(&self.ds, self.q))
     }
 
-    fn color(&self) -> u16 {
+    fn color(&self) -> Modulus {
         let color = self.ds[0];
         debug_assert!(color < self.q);
         color
     }
 
     fn plus_eq<'a>(&'a mut self, other: &Self) -> &'a mut Self {
-        let xs = &mut self.ds;
-        let ys = &other.ds;
-        let q = self.q;
-
         // Assuming modulus has to be the same here
         // Will enforce by type system
         //debug_assert_eq!(, ymod);
-        debug_assert_eq!(xs.len(), ys.len());
-        xs.iter_mut().zip(ys.iter()).for_each(|(x, &y)| {
-            let (zp, overflow) = (*x + y).overflowing_sub(q);
-            *x = if overflow { *x + y } else { zp }
-        });
+        digits::add_assign(&mut self.ds, &other.ds, self.q);
+        self
+    }
 
+    fn minus_eq<'a>(&'a mut self, other: &Self) -> &'a mut Self {
+        digits::sub_assign(&mut self.ds, &other.ds, self.q);
         self
     }
 
-    fn cmul_eq(&mut self, c: u16) -> &mut Self {
-        let q = self.q;
-        self.ds
-            .iter_mut()
-            .for_each(|d| *d = (*d as u32 * c as u32 % q as u32) as u16);
+    fn cmul_eq(&mut self, c: Modulus) -> &mut Self {
+        digits::cmul(&mut self.ds, c, self.q);
         self
     }
 
     fn negate_eq(&mut self) -> &mut Self {
-        let q = self.q;
-        self.ds.iter_mut().for_each(|d| {
-            if *d > 0 {
-                *d = q - *d;
-            } else {
-                *d = 0;
-            }
-        });
+        digits::negate(&mut self.ds, self.q);
         self
     }
-    fn from_block(inp: Block, q: u16) -> Self {
+    fn from_block(inp: Block, q: Modulus) -> Self {
         if q < 2 {
             panic!(
                 "[WireModQ::from_block] Modulus must be at least 2. Got {}",
@@ -684,11 +921,11 @@ impl WireLabel for WireModQ {
             let mask = (1 << width) - 1;
             let x = u128::from(inp);
             (0..ndigits)
-                .map(|i| ((x >> (width * i)) & mask) as u16)
-                .collect::<Vec<u16>>()
+                .map(|i| ((x >> (width * i)) & mask) as Modulus)
+                .collect::<Vec<Modulus>>()
         } else if q <= 23 {
             _unrank(u128::from(inp), q)
-        } else if base_conversion::lookup_defined_for_mod(q) {
+        } else if has_lookup_table(q) {
             _from_block_lookup(inp, q)
         } else {
             // If all else fails, do unrank using naive division.
@@ -698,7 +935,7 @@ impl WireLabel for WireModQ {
     }
     /// Unpack the wire represented by a `Block` with modulus `q`. Assumes that
     /// the block was constructed through the `AllWire` API.
-    fn zero(q: u16) -> Self {
+    fn zero(q: Modulus) -> Self {
         if q < 2 {
             panic!("[WireModQ::zero] Modulus must be at least 2. Got {}", q);
         }
@@ -707,17 +944,17 @@ impl WireLabel for WireModQ {
             ds: vec![0; util::digits_per_u128(q)],
         }
     }
-    fn rand<R: CryptoRng + RngCore>(rng: &mut R, q: u16) -> Self {
+    fn rand<R: CryptoRng + RngCore>(rng: &mut R, q: Modulus) -> Self {
         if q < 2 {
             panic!("[WireModQ::rand] Modulus must be at least 2. Got {}", q);
         }
         let ds = (0..util::digits_per_u128(q))
-            .map(|_| rng.gen::<u16>() % q)
+            .map(|_| rng.gen::<Modulus>() % q)
             .collect();
         Self { q, ds }
     }
 
-    fn hash_to_mod(hash: Block, q: u16) -> Self {
+    fn hash_to_mod(hash: Block, q: Modulus) -> Self {
         if q < 2 {
             panic!(
                 "[WireModQ::hash_to_mod] Modulus must be at least 2. Got {}",
@@ -726,10 +963,33 @@ impl WireLabel for WireModQ {
         }
         Self::from_block(hash, q)
     }
+
+    #[cfg(feature = "zeroize")]
+    fn wipe(&mut self) {
+        self.zeroize();
+    }
 }
 
 // Helpers for mod 3 and q
-fn _from_block_lookup(inp: Block, q: u16) -> Vec<u16> {
+#[cfg(not(feature = "wide-moduli"))]
+fn has_lookup_table(q: Modulus) -> bool {
+    base_conversion::lookup_defined_for_mod(q)
+}
+
+// The base conversion tables are only built for `u16` moduli. `_unrank`
+// computes the same digits, just more slowly.
+#[cfg(feature = "wide-moduli")]
+fn has_lookup_table(_q: Modulus) -> bool {
+    false
+}
+
+#[cfg(feature = "wide-moduli")]
+fn _from_block_lookup(_inp: Block, _q: Modulus) -> Vec<Modulus> {
+    unreachable!("no base conversion tables with wide moduli")
+}
+
+#[cfg(not(feature = "wide-moduli"))]
+fn _from_block_lookup(inp: Block, q: Modulus) -> Vec<Modulus> {
     debug_assert!(q < 256);
     debug_assert!(base_conversion::lookup_defined_for_mod(q));
     let bytes: [u8; 16] = inp.into();
@@ -746,7 +1006,7 @@ fn _from_block_lookup(inp: Block, q: u16) -> Vec<u16> {
     ds
 }
 
-fn _unrank(inp: u128, q: u16) -> Vec<u16> {
+fn _unrank(inp: u128, q: Modulus) -> Vec<Modulus> {
     let mut x = inp;
     let ndigits = util::digits_per_u128(q);
     let npaths_tab = npaths_tab::lookup(q);
@@ -770,7 +1030,7 @@ fn _unrank(inp: u128, q: u16) -> Vec<u16> {
         } else {
             // naive division
             let d = x / npaths;
-            ds[i] = d as u16;
+            ds[i] = d as Modulus;
             x -= d * npaths;
         }
         // } else {
@@ -802,6 +1062,47 @@ impl ArithmeticWire for WireMod3 {}
 impl ArithmeticWire for WireModQ {}
 impl ArithmeticWire for AllWire {}
 
+#[cfg(feature = "zeroize")]
+impl Zeroize for WireMod2 {
+    fn zeroize(&mut self) {
+        self.val.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for WideWireMod2 {
+    fn zeroize(&mut self) {
+        <&mut [Block; 4]>::from(&mut self.val).zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for WireMod3 {
+    fn zeroize(&mut self) {
+        self.lsb.zeroize();
+        self.msb.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for WireModQ {
+    // The digits are zeroed rather than dropped, leaving the zero label.
+    fn zeroize(&mut self) {
+        self.ds.as_mut_slice().zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Zeroize for AllWire {
+    fn zeroize(&mut self) {
+        match self {
+            AllWire::Mod2(w) => w.zeroize(),
+            AllWire::Mod3(w) => w.zeroize(),
+            AllWire::ModN(w) => w.zeroize(),
+        }
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // tests
 //
@@ -826,11 +1127,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn wide_labels() {
+        let ref mut rng = thread_rng();
+        let delta = WideWireMod2::rand_delta(rng, 2);
+        assert_eq!(delta.color(), 1);
+        for _ in 0..100 {
+            let x = WideWireMod2::rand(rng, 2);
+            let blocks = <[Block; 4]>::from(x.as_block512());
+            assert_eq!(blocks[2..], [Block::default(); 2]);
+            assert_eq!(x, WideWireMod2::from_block512(x.as_block512(), 2));
+            assert_eq!(x.plus(&delta).minus(&delta), x);
+            assert_eq!(x.plus(&delta).color(), 1 - x.color());
+            // Labels that differ in their second block alone hash apart.
+            let y = WideWireMod2::from_block512(
+                Block512::from([
+                    blocks[0],
+                    blocks[1] ^ Block::from(1u128),
+                    Block::default(),
+                    Block::default(),
+                ]),
+                2,
+            );
+            let tweak = Block::from(rng.gen_u128());
+            let hash = <[Block; 4]>::from(x.hash_wide(tweak));
+            assert_ne!(x.hash_wide(tweak), y.hash_wide(tweak));
+            assert_eq!(hash[2..], [Block::default(); 2]);
+            assert_eq!(x.hash(tweak), hash[0]);
+        }
+    }
+
     #[test]
     fn base_conversion_lookup_method() {
         let ref mut rng = thread_rng();
         for _ in 0..1000 {
-            let q = 5 + (rng.gen_u16() % 110);
+            let q = 5 + rng.gen_residue(110);
             let x = rng.gen_u128();
             let w = AllWire::from_block(Block::from(x), q);
             let should_be = util::as_base_q_u128(x, q);
@@ -842,7 +1173,7 @@ mod tests {
     fn hash() {
         let mut rng = thread_rng();
         for _ in 0..100 {
-            let q = 2 + (rng.gen_u16() % 110);
+            let q = 2 + rng.gen_residue(110);
             let x = AllWire::rand(&mut rng, q);
             let y = x.hashback(Block::from(1u128), q);
             assert!(x != y);
@@ -873,7 +1204,7 @@ mod tests {
     fn zero() {
         let mut rng = thread_rng();
         for _ in 0..1000 {
-            let q = 3 + (rng.gen_u16() % 110);
+            let q = 3 + rng.gen_residue(110);
             let z = AllWire::zero(q);
             let ds = z.digits();
             assert_eq!(ds, vec![0; ds.len()], "q={}", q);
@@ -1023,7 +1354,7 @@ mod tests {
         let mut rng = thread_rng();
 
         for _ in 0..16 {
-            let q: u16 = rng.gen();
+            let q: Modulus = rng.gen();
             let w = WireModQ::rand(&mut rng, q);
             let serialized = serde_json::to_string(&w).unwrap();
 
@@ -1036,7 +1367,7 @@ mod tests {
     #[test]
     fn test_serialize_bad_modQ_mod() {
         let mut rng = thread_rng();
-        let q: u16 = rng.gen();
+        let q: Modulus = rng.gen();
 
         let mut w = WireModQ::rand(&mut rng, q);
 
@@ -1064,4 +1395,30 @@ mod tests {
         let deserialized: Result<WireModQ, _> = serde_json::from_str(&serialized);
         assert!(deserialized.is_err());
     }
+
+    #[test]
+    fn test_wipe() {
+        let mut rng = thread_rng();
+        for q in [2, 3, 5, 17] {
+            let mut w = AllWire::rand(&mut rng, q);
+            w.wipe();
+            assert_eq!(w, AllWire::zero(q));
+        }
+        let mut w = WireMod2::rand(&mut rng, 2);
+        w.wipe();
+        assert_eq!(w, WireMod2::zero(2));
+        let mut w = WireModQ::rand(&mut rng, 7);
+        w.wipe();
+        assert_eq!(w, WireModQ::zero(7));
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_zeroize() {
+        fn assert_zeroize<T: Zeroize>() {}
+        assert_zeroize::<WireMod2>();
+        assert_zeroize::<WireMod3>();
+        assert_zeroize::<WireModQ>();
+        assert_zeroize::<AllWire>();
+    }
 }
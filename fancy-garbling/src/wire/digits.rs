@@ -0,0 +1,217 @@
+//! Digit-wise arithmetic on `mod-q` wire labels.
+//!
+//! Digits are processed eight at a time using `vectoreyes`, which falls back to
+//! scalar code when the target lacks the required instructions. A sum of two
+//! digits is reduced by taking the unsigned minimum of `x` and `x - q`, which
+//! requires `2q` to fit in a `u16`; larger moduli use the scalar routines.
+//! With the `wide-moduli` feature digits are `u32`s, and only the scalar
+//! routines are used.
+
+#[cfg(feature = "wide-moduli")]
+pub(super) use scalar::{add_assign, cmul, negate, sub_assign};
+#[cfg(not(feature = "wide-moduli"))]
+use vectoreyes::{SimdBase, U16x8};
+
+/// Largest modulus supported by the vectorized routines.
+#[cfg(not(feature = "wide-moduli"))]
+const MAX_SIMD_MODULUS: u16 = 1 << 15;
+
+/// Apply `f` to `xs` and `ys` eight digits at a time, writing the result to `xs`.
+#[cfg(not(feature = "wide-moduli"))]
+#[inline]
+fn zip_map(xs: &mut [u16], ys: &[u16], f: impl Fn(U16x8, U16x8) -> U16x8) {
+    debug_assert_eq!(xs.len(), ys.len());
+    let mut xchunks = xs.chunks_exact_mut(8);
+    let mut ychunks = ys.chunks_exact(8);
+    for (x, y) in (&mut xchunks).zip(&mut ychunks) {
+        let z = f(
+            U16x8::from(<[u16; 8]>::try_from(&*x).unwrap()),
+            U16x8::from(<[u16; 8]>::try_from(y).unwrap()),
+        );
+        x.copy_from_slice(&z.as_array());
+    }
+    // The remaining digits are processed in a zero-padded vector.
+    let xrem = xchunks.into_remainder();
+    let yrem = ychunks.remainder();
+    if !xrem.is_empty() {
+        let mut x = [0; 8];
+        let mut y = [0; 8];
+        x[..xrem.len()].copy_from_slice(xrem);
+        y[..yrem.len()].copy_from_slice(yrem);
+        let z = f(U16x8::from(x), U16x8::from(y));
+        xrem.copy_from_slice(&z.as_array()[..xrem.len()]);
+    }
+}
+
+/// Apply `f` to `xs` eight digits at a time, writing the result to `xs`.
+#[cfg(not(feature = "wide-moduli"))]
+#[inline]
+fn map(xs: &mut [u16], f: impl Fn(U16x8) -> U16x8) {
+    let mut chunks = xs.chunks_exact_mut(8);
+    for x in &mut chunks {
+        let z = f(U16x8::from(<[u16; 8]>::try_from(&*x).unwrap()));
+        x.copy_from_slice(&z.as_array());
+    }
+    let rem = chunks.into_remainder();
+    if !rem.is_empty() {
+        let mut x = [0; 8];
+        x[..rem.len()].copy_from_slice(rem);
+        let z = f(U16x8::from(x));
+        rem.copy_from_slice(&z.as_array()[..rem.len()]);
+    }
+}
+
+/// Compute `(x + y) mod q` for `x, y < q`.
+#[cfg(not(feature = "wide-moduli"))]
+#[inline]
+fn add_mod(x: U16x8, y: U16x8, q: U16x8) -> U16x8 {
+    let z = x + y;
+    z.min(z - q)
+}
+
+/// Compute `xs = xs + ys` digit-wise mod `q`.
+#[cfg(not(feature = "wide-moduli"))]
+pub(super) fn add_assign(xs: &mut [u16], ys: &[u16], q: u16) {
+    if q > MAX_SIMD_MODULUS {
+        return scalar::add_assign(xs, ys, q);
+    }
+    let q_ = U16x8::broadcast(q);
+    zip_map(xs, ys, |x, y| add_mod(x, y, q_));
+}
+
+/// Compute `xs = xs - ys` digit-wise mod `q`.
+#[cfg(not(feature = "wide-moduli"))]
+pub(super) fn sub_assign(xs: &mut [u16], ys: &[u16], q: u16) {
+    if q > MAX_SIMD_MODULUS {
+        return scalar::sub_assign(xs, ys, q);
+    }
+    let q_ = U16x8::broadcast(q);
+    zip_map(xs, ys, |x, y| {
+        let z = x - y;
+        z.min(z + q_)
+    });
+}
+
+/// Compute `xs = -xs` digit-wise mod `q`.
+#[cfg(not(feature = "wide-moduli"))]
+pub(super) fn negate(xs: &mut [u16], q: u16) {
+    if q > MAX_SIMD_MODULUS {
+        return scalar::negate(xs, q);
+    }
+    let q_ = U16x8::broadcast(q);
+    map(xs, |x| (q_ - x).min(U16x8::ZERO - x));
+}
+
+/// Compute `xs = c * xs` digit-wise mod `q`.
+#[cfg(not(feature = "wide-moduli"))]
+pub(super) fn cmul(xs: &mut [u16], c: u16, q: u16) {
+    if q > MAX_SIMD_MODULUS {
+        return scalar::cmul(xs, c, q);
+    }
+    let q_ = U16x8::broadcast(q);
+    let c = c % q;
+    // Double-and-add, avoiding a division per digit.
+    map(xs, |x| {
+        let mut acc = U16x8::ZERO;
+        let mut base = x;
+        let mut c = c;
+        while c > 0 {
+            if c & 1 == 1 {
+                acc = add_mod(acc, base, q_);
+            }
+            base = add_mod(base, base, q_);
+            c >>= 1;
+        }
+        acc
+    });
+}
+
+/// Scalar versions of the digit-wise operations.
+pub(super) mod scalar {
+    use crate::Modulus;
+
+    pub fn add_assign(xs: &mut [Modulus], ys: &[Modulus], q: Modulus) {
+        debug_assert_eq!(xs.len(), ys.len());
+        // `x + y` itself may overflow for moduli above half the maximum.
+        xs.iter_mut()
+            .zip(ys.iter())
+            .for_each(|(x, &y)| *x = if *x >= q - y { *x - (q - y) } else { *x + y });
+    }
+
+    pub fn sub_assign(xs: &mut [Modulus], ys: &[Modulus], q: Modulus) {
+        debug_assert_eq!(xs.len(), ys.len());
+        xs.iter_mut()
+            .zip(ys.iter())
+            .for_each(|(x, &y)| *x = if *x >= y { *x - y } else { (q - y) + *x });
+    }
+
+    pub fn negate(xs: &mut [Modulus], q: Modulus) {
+        xs.iter_mut().for_each(|d| {
+            if *d > 0 {
+                *d = q - *d;
+            } else {
+                *d = 0;
+            }
+        });
+    }
+
+    pub fn cmul(xs: &mut [Modulus], c: Modulus, q: Modulus) {
+        xs.iter_mut()
+            .for_each(|d| *d = (*d as u64 * c as u64 % q as u64) as Modulus);
+    }
+}
+
+#[cfg(all(test, not(feature = "wide-moduli")))]
+mod tests {
+    use super::*;
+    use crate::util::RngExt;
+    use rand::thread_rng;
+
+    fn check(q: u16) {
+        let mut rng = thread_rng();
+        for len in 0..40 {
+            let xs = (0..len).map(|_| rng.gen_u16() % q).collect::<Vec<_>>();
+            let ys = (0..len).map(|_| rng.gen_u16() % q).collect::<Vec<_>>();
+            let c = rng.gen_u16();
+
+            let (mut a, mut b) = (xs.clone(), xs.clone());
+            add_assign(&mut a, &ys, q);
+            scalar::add_assign(&mut b, &ys, q);
+            assert_eq!(a, b, "add mod {}", q);
+
+            let (mut a, mut b) = (xs.clone(), xs.clone());
+            sub_assign(&mut a, &ys, q);
+            scalar::sub_assign(&mut b, &ys, q);
+            assert_eq!(a, b, "sub mod {}", q);
+
+            let (mut a, mut b) = (xs.clone(), xs.clone());
+            negate(&mut a, q);
+            scalar::negate(&mut b, q);
+            assert_eq!(a, b, "negate mod {}", q);
+
+            for c in [0, 1, q - 1, q, c] {
+                let (mut a, mut b) = (xs.clone(), xs.clone());
+                cmul(&mut a, c, q);
+                scalar::cmul(&mut b, c, q);
+                assert_eq!(a, b, "cmul by {} mod {}", c, q);
+            }
+        }
+    }
+
+    #[test]
+    fn vectorized_matches_scalar() {
+        for q in 2..=17 {
+            check(q);
+        }
+        for q in [
+            113,
+            257,
+            MAX_SIMD_MODULUS - 1,
+            MAX_SIMD_MODULUS,
+            MAX_SIMD_MODULUS + 1,
+            u16::MAX,
+        ] {
+            check(q);
+        }
+    }
+}
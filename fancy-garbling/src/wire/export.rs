@@ -0,0 +1,322 @@
+//! A portable format for wire labels, for composing garbled circuits with
+//! protocols implemented elsewhere.
+
+use super::WireLabel;
+use crate::{errors::LabelError, Modulus};
+use scuttlebutt::Block;
+
+/// The version of the label format written by [`ExportedLabel::to_bytes`].
+pub const LABEL_FORMAT_VERSION: u8 = 1;
+
+/// The length of an exported label in bytes.
+pub const EXPORTED_LABEL_LEN: usize = 24;
+
+const COLOR_GIVEN: u8 = 1;
+const COLOR_SET: u8 = 2;
+
+/// The part a wire plays in a garbled circuit, as recorded with its label.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[repr(u8)]
+pub enum WireRole {
+    /// No role is recorded.
+    Unspecified = 0,
+    /// An input of the garbler.
+    GarblerInput = 1,
+    /// An input of the evaluator.
+    EvaluatorInput = 2,
+    /// An output of the circuit.
+    Output = 3,
+}
+
+impl WireRole {
+    /// Every role, in order of its byte.
+    pub const ALL: [WireRole; 4] = [
+        WireRole::Unspecified,
+        WireRole::GarblerInput,
+        WireRole::EvaluatorInput,
+        WireRole::Output,
+    ];
+
+    /// The role with the given byte, if there is one.
+    pub fn from_u8(role: u8) -> Option<Self> {
+        Self::ALL.iter().copied().find(|r| *r as u8 == role)
+    }
+}
+
+/// A wire label in a portable format, made by [`WireLabel::export`] and read
+/// by [`WireLabel::import`].
+///
+/// An exported label is written as [`EXPORTED_LABEL_LEN`] bytes:
+///
+/// | Bytes    | Field                                                        |
+/// |----------|--------------------------------------------------------------|
+/// | `0`      | The format version, [`LABEL_FORMAT_VERSION`].                |
+/// | `1`      | The [`WireRole`].                                            |
+/// | `2`      | Flags: bit 0 is set if a color is given, bit 1 is the color. |
+/// | `3`      | Reserved, zero.                                              |
+/// | `4..8`   | The modulus, a little-endian `u32`.                          |
+/// | `8..24`  | The label, a little-endian `u128`.                           |
+///
+/// The label is the 128-bit packing of [`WireLabel::as_block`]: the bits of a
+/// binary label, whose least significant bit is its color; the least and
+/// most significant bits of the 64 digits of a label of modulus 3, in the
+/// low and high halves; and the digits of a label of a larger modulus, read
+/// as a number in base `q`, least significant digit first. This does not
+/// depend on the `wide-moduli` or `blake3-hash` features.
+///
+/// The format is stable: a label exported by one version of this crate is
+/// imported by every later one, which tests check against labels exported
+/// by earlier versions. A change to it bumps [`LABEL_FORMAT_VERSION`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ExportedLabel {
+    /// The label, a little-endian `u128`.
+    pub label: [u8; 16],
+    /// The modulus of the wire.
+    pub modulus: u32,
+    /// The part the wire plays.
+    pub role: WireRole,
+    /// The color bit of a binary label, if given; labels of other moduli
+    /// have none.
+    pub color: Option<bool>,
+}
+
+impl ExportedLabel {
+    /// Export `wire` in the role `role`, with its color if it is binary.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the label is wider than the 128 bits of the format, as a
+    /// [`WideWireMod2`](crate::WideWireMod2) of a wider label is.
+    pub fn new<W: WireLabel>(wire: &W, role: WireRole) -> Self {
+        assert!(
+            wire.as_block512().as_ref()[16..].iter().all(|&b| b == 0),
+            "labels wider than 128 bits cannot be exported"
+        );
+        let q = wire.modulus();
+        // `Modulus` is `u32` itself with the `wide-moduli` feature.
+        #[allow(clippy::useless_conversion)]
+        let modulus = u32::from(q);
+        ExportedLabel {
+            label: u128::from(wire.as_block()).to_le_bytes(),
+            modulus,
+            role,
+            color: (q == 2).then(|| wire.color() == 1),
+        }
+    }
+
+    /// The label in the role `role`.
+    pub fn with_role(self, role: WireRole) -> Self {
+        ExportedLabel { role, ..self }
+    }
+
+    /// Import the label as a `W`; see [`WireLabel::import`].
+    pub fn import<W: WireLabel>(self) -> Result<W, LabelError> {
+        W::import(self)
+    }
+
+    /// Write the label in the current version of the format.
+    pub fn to_bytes(&self) -> [u8; EXPORTED_LABEL_LEN] {
+        let mut bytes = [0; EXPORTED_LABEL_LEN];
+        bytes[0] = LABEL_FORMAT_VERSION;
+        bytes[1] = self.role as u8;
+        bytes[2] = match self.color {
+            None => 0,
+            Some(false) => COLOR_GIVEN,
+            Some(true) => COLOR_GIVEN | COLOR_SET,
+        };
+        bytes[4..8].copy_from_slice(&self.modulus.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.label);
+        bytes
+    }
+
+    /// Read a label written by [`ExportedLabel::to_bytes`].
+    ///
+    /// This checks the framing of the label, but not the label itself, which
+    /// [`WireLabel::import`] does.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LabelError> {
+        if bytes.len() != EXPORTED_LABEL_LEN {
+            return Err(LabelError::Truncated(bytes.len()));
+        }
+        if bytes[0] != LABEL_FORMAT_VERSION {
+            return Err(LabelError::UnsupportedVersion(bytes[0]));
+        }
+        let role = WireRole::from_u8(bytes[1]).ok_or(LabelError::UnknownRole(bytes[1]))?;
+        let color = match bytes[2] {
+            0 => None,
+            COLOR_GIVEN => Some(false),
+            flags if flags == COLOR_GIVEN | COLOR_SET => Some(true),
+            _ => return Err(LabelError::ReservedBits),
+        };
+        if bytes[3] != 0 {
+            return Err(LabelError::ReservedBits);
+        }
+        let mut modulus = [0; 4];
+        modulus.copy_from_slice(&bytes[4..8]);
+        let mut label = [0; 16];
+        label.copy_from_slice(&bytes[8..]);
+        Ok(ExportedLabel {
+            label,
+            modulus: u32::from_le_bytes(modulus),
+            role,
+            color,
+        })
+    }
+
+    /// Write `labels` one after the other.
+    pub fn to_bytes_many(labels: &[ExportedLabel]) -> Vec<u8> {
+        labels.iter().flat_map(|label| label.to_bytes()).collect()
+    }
+
+    /// Read labels written by [`ExportedLabel::to_bytes_many`].
+    pub fn from_bytes_many(bytes: &[u8]) -> Result<Vec<Self>, LabelError> {
+        let labels = bytes.chunks_exact(EXPORTED_LABEL_LEN);
+        if !labels.remainder().is_empty() {
+            return Err(LabelError::Truncated(bytes.len()));
+        }
+        labels.map(ExportedLabel::from_bytes).collect()
+    }
+}
+
+/// Import `label` as a `W`, if `W` holds labels of its modulus.
+pub(super) fn import<W: WireLabel>(
+    label: ExportedLabel,
+    holds: impl Fn(Modulus) -> bool,
+) -> Result<W, LabelError> {
+    #[cfg(not(feature = "wide-moduli"))]
+    let q = Modulus::try_from(label.modulus).ok();
+    #[cfg(feature = "wide-moduli")]
+    let q = Some(label.modulus);
+    let q = q
+        .filter(|&q| q >= 2 && holds(q))
+        .ok_or(LabelError::UnsupportedModulus(label.modulus))?;
+    let x = u128::from_le_bytes(label.label);
+    let invalid = LabelError::InvalidLabel {
+        modulus: label.modulus,
+    };
+    // A digit mod 3 has at most one of its two bits set.
+    if q == 3 && (x as u64) & ((x >> 64) as u64) != 0 {
+        return Err(invalid);
+    }
+    let block = Block::from(x);
+    let wire = W::from_block(block, q);
+    if wire.as_block() != block {
+        return Err(invalid);
+    }
+    if let Some(color) = label.color {
+        if q != 2 || color != (wire.color() == 1) {
+            return Err(LabelError::ColorMismatch);
+        }
+    }
+    Ok(wire)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AllWire, WireMod2, WireMod3, WireModQ};
+    use scuttlebutt::AesRng;
+
+    fn round_trip<W: WireLabel + PartialEq + std::fmt::Debug>(q: Modulus) {
+        let mut rng = AesRng::new();
+        for _ in 0..16 {
+            let wire = W::rand(&mut rng, q);
+            let label = ExportedLabel::from_bytes(&wire.export().to_bytes()).unwrap();
+            assert_eq!(label.role, WireRole::Unspecified);
+            assert_eq!(W::import(label).unwrap(), wire);
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        round_trip::<WireMod2>(2);
+        round_trip::<WireMod3>(3);
+        round_trip::<WireModQ>(5);
+        round_trip::<WireModQ>(17);
+        round_trip::<WireModQ>(64);
+        round_trip::<WireModQ>(257);
+        for q in [2, 3, 7, 113] {
+            round_trip::<AllWire>(q);
+        }
+    }
+
+    #[test]
+    fn test_bytes() {
+        let wire = WireMod2::from_block(Block::from(0x0102_u128), 2);
+        let label = wire.export().with_role(WireRole::Output);
+        let bytes = label.to_bytes();
+        assert_eq!(&bytes[..8], &[1, 3, 1, 0, 2, 0, 0, 0]);
+        assert_eq!(&bytes[8..10], &[2, 1]);
+
+        let many = ExportedLabel::to_bytes_many(&[label, label.with_role(WireRole::GarblerInput)]);
+        let labels = ExportedLabel::from_bytes_many(&many).unwrap();
+        assert_eq!(labels[0], label);
+        assert_eq!(labels[1].role, WireRole::GarblerInput);
+        assert_eq!(
+            ExportedLabel::from_bytes_many(&many[1..]),
+            Err(LabelError::Truncated(47))
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let label = WireMod2::from_block(Block::from(7), 2).export();
+        let edit = |i: usize, byte: u8| {
+            let mut bytes = label.to_bytes();
+            bytes[i] = byte;
+            ExportedLabel::from_bytes(&bytes)
+        };
+        assert_eq!(edit(0, 2), Err(LabelError::UnsupportedVersion(2)));
+        assert_eq!(edit(1, 9), Err(LabelError::UnknownRole(9)));
+        assert_eq!(edit(2, 4), Err(LabelError::ReservedBits));
+        assert_eq!(edit(3, 1), Err(LabelError::ReservedBits));
+
+        // The modulus must be one the wire type holds.
+        assert_eq!(
+            WireMod3::import(label),
+            Err(LabelError::UnsupportedModulus(2))
+        );
+        #[cfg(not(feature = "wide-moduli"))]
+        {
+            let wide = ExportedLabel {
+                modulus: 1 << 20,
+                color: None,
+                ..label
+            };
+            assert_eq!(
+                AllWire::import(wide),
+                Err(LabelError::UnsupportedModulus(1 << 20))
+            );
+        }
+        let unary = ExportedLabel {
+            modulus: 1,
+            color: None,
+            ..label
+        };
+        assert_eq!(
+            AllWire::import(unary),
+            Err(LabelError::UnsupportedModulus(1))
+        );
+
+        // The color must be that of the label.
+        let recolored = ExportedLabel {
+            color: Some(false),
+            ..label
+        };
+        assert_eq!(WireMod2::import(recolored), Err(LabelError::ColorMismatch));
+
+        // The label must be one of its modulus.
+        let mut bad = WireMod3::zero(3).export();
+        bad.label = (1u128 | 1 << 64).to_le_bytes();
+        assert_eq!(
+            WireMod3::import(bad),
+            Err(LabelError::InvalidLabel { modulus: 3 })
+        );
+        let mut bad = WireModQ::zero(5).export();
+        bad.label = u128::MAX.to_le_bytes();
+        assert_eq!(
+            WireModQ::import(bad),
+            Err(LabelError::InvalidLabel { modulus: 5 })
+        );
+    }
+}
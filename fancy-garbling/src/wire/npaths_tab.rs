@@ -1,8 +1,8 @@
 #![allow(clippy::unreadable_literal)]
 
-use crate::util;
+use crate::{util, Modulus};
 
-pub fn lookup(q: u16) -> Vec<u128> {
+pub fn lookup(q: Modulus) -> Vec<u128> {
     match q {
         5 => vec![
             1,
@@ -0,0 +1,361 @@
+//! Feeds the public entry points that read circuits, garbled circuits,
+//! encoders, and protocol messages truncated, corrupted, and hostile input,
+//! and checks that they fail with an error. Every call runs under
+//! `catch_unwind`, so a panic fails the test naming the input that caused it.
+
+use fancy_garbling::{
+    circuit::{eval_plain, ArithmeticCircuit, BinaryCircuit, CircuitBuilder, EvaluableCircuit},
+    classic::{garble, Encoder, GarbledCircuit},
+    dummy::Dummy,
+    errors::{DummyError, FancyError, TwopacError},
+    format::{self, FormatId},
+    twopac::semihonest::{Evaluator, Garbler},
+    BinaryGadgets, BundleGadgets, Fancy, FancyArithmetic, FancyInput, Modulus, WireLabel, WireMod2,
+};
+use ocelot::ot::{AlszReceiver, AlszSender};
+use scuttlebutt::{AbstractChannel, AesRng, Channel};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    io,
+    panic::{self, AssertUnwindSafe},
+};
+
+mod common;
+
+/// Run `f`, failing the test if it panics.
+fn no_panic<T>(what: &str, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|_| panic!("{} panicked", what))
+}
+
+/// Run `f`, failing the test if it panics or succeeds.
+fn fails<T: Debug, E>(what: &str, f: impl FnOnce() -> Result<T, E>) -> E {
+    match no_panic(what, f) {
+        Ok(x) => panic!("{} succeeded with {:?}", what, x),
+        Err(e) => e,
+    }
+}
+
+/// The lengths of the prefixes of `n` bytes to try: all of the short ones,
+/// then every `step` bytes, then all but the last few bytes.
+fn cuts(n: usize, step: usize) -> impl Iterator<Item = usize> {
+    let short = 0..n.min(64);
+    let middle = (64..n).step_by(step);
+    let end = n.saturating_sub(16)..n;
+    short.chain(middle).chain(end)
+}
+
+fn adder(n: usize) -> BinaryCircuit {
+    let mut b = CircuitBuilder::new();
+    let x = b.bin_garbler_input(n);
+    let y = b.bin_evaluator_input(n);
+    let z = b.bin_addition_no_carry(&x, &y).unwrap();
+    b.output_bundle(&z).unwrap();
+    b.finish()
+}
+
+fn affine() -> ArithmeticCircuit {
+    let mut b = CircuitBuilder::<ArithmeticCircuit>::new();
+    let x = b.garbler_input(7);
+    let y = b.evaluator_input(7);
+    let z = b.mul(&x, &y).unwrap();
+    let z = b.add(&z, &x).unwrap();
+    b.output(&z).unwrap();
+    b.finish()
+}
+
+type Gc = GarbledCircuit<WireMod2, BinaryCircuit>;
+
+const ADDER64: &[u8] = include_bytes!("../circuits/adder64.txt");
+
+#[test]
+fn truncated_circuit_files() {
+    // Every prefix that stops before the last gate leaves an output unset.
+    let last_line = ADDER64[..ADDER64.len() - 1]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .unwrap();
+    for n in cuts(last_line, 37) {
+        fails(&format!("parsing {} bytes of adder64", n), || {
+            BinaryCircuit::parse(&ADDER64[..n])
+        });
+    }
+}
+
+#[test]
+fn hostile_circuit_headers() {
+    for file in [
+        "",
+        "\n",
+        "99999999999999999999999 3\n1 1 1\n\n",
+        "1 18446744073709551615\n18446744073709551615 1 1\n\n",
+        "4 18446744073709551615\n1 1 1\n\n",
+        "1000000000 4\n1 1 1\n\n",
+        "1 3\n1 1 1\n\n2 1 0 1 99999999999 AND\n",
+        "1 3\n1 1 1\n\n2 1 0 99999999999 2 XOR\n",
+        "1 3\n1 1 1\n\n1 1 99999999999 2 INV\n",
+        "1 3\n1 1 1\n\n2 1 0 1 AND\n",
+        "1 3\n1 1 1\n\n3 1 0 1 2 AND\n",
+        "1 3\n-1 1 1\n\n",
+    ] {
+        fails(&format!("parsing {:?}", file), || {
+            BinaryCircuit::parse(file.as_bytes())
+        });
+    }
+}
+
+#[test]
+fn truncated_serialized_circuits() {
+    let binary = adder(8).to_bytes();
+    for n in cuts(binary.len(), 7) {
+        fails(&format!("reading {} bytes of a binary circuit", n), || {
+            BinaryCircuit::from_bytes(&binary[..n])
+        });
+    }
+    let arithmetic = affine().to_bytes();
+    for n in cuts(arithmetic.len(), 3) {
+        fails(
+            &format!("reading {} bytes of an arithmetic circuit", n),
+            || ArithmeticCircuit::from_bytes(&arithmetic[..n]),
+        );
+    }
+    // Corrupt bytes either fail validation or give some other valid circuit.
+    for i in format::HEADER_LEN..binary.len() {
+        let mut bytes = binary.clone();
+        bytes[i] ^= 0x5a;
+        let _ = no_panic(
+            &format!("reading a circuit with byte {} flipped", i),
+            || BinaryCircuit::from_bytes(&bytes),
+        );
+    }
+}
+
+/// Replace the payload of the frame `bytes` by `f` of it, keeping its format
+/// and version.
+fn reframe(bytes: &[u8], f: impl FnOnce(&mut Vec<u8>)) -> Vec<u8> {
+    let (header, payload, _) = format::split(bytes).unwrap();
+    let mut payload = payload.to_vec();
+    f(&mut payload);
+    format::encode(header.format, header.version, &payload)
+}
+
+#[test]
+fn hostile_garbled_circuits() {
+    let c = adder(8);
+    let (en, gc) = garble::<WireMod2, _>(&c).unwrap();
+    let bytes = gc.to_bytes();
+    for n in cuts(bytes.len(), 16) {
+        fails(&format!("reading {} bytes of a garbled circuit", n), || {
+            Gc::from_bytes(&bytes[..n])
+        });
+    }
+
+    // The security parameters come right after the garbling id and circuit
+    // digest, and the block count right after them.
+    for (i, bits) in [(0, 264), (0, 0), (2, 7), (4, u16::MAX)] {
+        let at = 16 + 32 + i;
+        let hostile = reframe(&bytes, |p| {
+            p[at..at + 2].copy_from_slice(&bits.to_le_bytes())
+        });
+        fails(
+            &format!("reading a garbled circuit of parameter {} bits", bits),
+            || Gc::from_bytes(&hostile),
+        );
+    }
+    let count = 16 + 32 + 6..16 + 32 + 6 + 8;
+    for nblocks in [0, 1, u64::MAX, u64::MAX / 16 + 1, gc.size() as u64 + 1] {
+        let hostile = reframe(&bytes, |p| {
+            p[count.clone()].copy_from_slice(&nblocks.to_le_bytes())
+        });
+        fails(
+            &format!("reading a garbled circuit of {} blocks", nblocks),
+            || Gc::from_bytes(&hostile),
+        );
+    }
+    let mut hostile = bytes.clone();
+    hostile[8..16].copy_from_slice(&u64::MAX.to_le_bytes());
+    fails("reading a garbled circuit of 2^64 bytes", || {
+        Gc::from_bytes(&hostile)
+    });
+    fails("reading a garbled circuit of an unknown format", || {
+        Gc::from_bytes(&format::encode(FormatId::Encoder, 2, &bytes))
+    });
+
+    // A garbled circuit missing its last blocks decodes, and then runs out of
+    // blocks during evaluation.
+    let xs = en.encode_garbler_inputs(&[1; 8]).unwrap();
+    let ys = en.encode_evaluator_inputs(&[0; 8]).unwrap();
+    for missing in [1, 2, 9, gc.size()] {
+        let short = reframe(&bytes, |p| {
+            let nblocks = (gc.size() - missing) as u64;
+            p[count.clone()].copy_from_slice(&nblocks.to_le_bytes());
+            p.truncate(p.len() - 16 * missing);
+        });
+        let short = Gc::from_bytes(&short).unwrap();
+        fails(
+            &format!("evaluating without the last {} blocks", missing),
+            || short.eval(&c, &xs, &ys),
+        );
+    }
+}
+
+#[test]
+fn hostile_encoders() {
+    let c = adder(8);
+    let (en, _) = garble::<WireMod2, _>(&c).unwrap();
+    let bytes = en.to_bytes();
+    for n in cuts(bytes.len(), 16) {
+        fails(&format!("reading {} bytes of an encoder", n), || {
+            Encoder::<WireMod2>::from_bytes(&bytes[..n])
+        });
+    }
+
+    for inputs in [&[][..], &[0; 7], &[0; 9]] {
+        fails(&format!("encoding {} garbler inputs", inputs.len()), || {
+            en.encode_garbler_inputs(inputs)
+        });
+        fails(
+            &format!("encoding {} evaluator inputs", inputs.len()),
+            || en.encode_evaluator_inputs(inputs),
+        );
+    }
+    for id in [8, usize::MAX] {
+        let e = fails(&format!("encoding garbler input {}", id), || {
+            en.encode_garbler_input(0, id)
+        });
+        assert!(matches!(
+            e,
+            FancyError::NotEnoughGarblerInputs { got: 8, .. }
+        ));
+        fails(&format!("encoding evaluator input {}", id), || {
+            en.encode_evaluator_input(0, id)
+        });
+    }
+
+    // An encoder without the deltas for its inputs.
+    let mut rng = AesRng::new();
+    let zeros = (0..8)
+        .map(|_| WireMod2::rand(&mut rng, 2))
+        .collect::<Vec<_>>();
+    let deltaless = Encoder::new(zeros.clone(), zeros, HashMap::new());
+    fails("encoding without deltas", || {
+        deltaless.encode_garbler_inputs(&[1; 8])
+    });
+    fails("encoding an input without deltas", || {
+        deltaless.encode_evaluator_input(1, 0)
+    });
+    fails("reading an encoder without deltas", || {
+        Encoder::<WireMod2>::from_bytes(&deltaless.to_bytes())
+    });
+}
+
+#[test]
+fn too_few_inputs() {
+    let c = adder(8);
+    for (gb, ev) in [(7, 8), (8, 7), (0, 0)] {
+        let what = format!("evaluating on {} and {} inputs", gb, ev);
+        fails(&what, || eval_plain(&c, &vec![0; gb], &vec![0; ev]));
+        let e = fails(&what, || {
+            let mut f = Dummy::new();
+            let xs = f.encode_many(&vec![0; gb], &vec![2; gb])?;
+            let ys = f.encode_many(&vec![0; ev], &vec![2; ev])?;
+            c.eval(&mut f, &xs, &ys)
+        });
+        assert!(
+            matches!(
+                e,
+                DummyError::FancyError(FancyError::NotEnoughGarblerInputs { .. })
+                    | DummyError::FancyError(FancyError::NotEnoughEvaluatorInputs { .. })
+            ),
+            "{:?}",
+            e
+        );
+    }
+}
+
+const N: usize = 64;
+
+/// Run the garbler's side of adding two `N`-bit numbers.
+fn garbler<C: AbstractChannel>(channel: C, c: &BinaryCircuit) -> Result<(), TwopacError> {
+    let mut gb = Garbler::<C, AesRng, AlszSender, WireMod2>::new(channel, AesRng::new())?;
+    let xs = gb.encode_many(&[1; N], &[2; N])?;
+    let ys = gb.receive_many(&[2; N])?;
+    gb.eval_circuit(c, &xs, &ys)?;
+    gb.get_channel().flush()?;
+    Ok(())
+}
+
+/// Run the evaluator's side of adding two `N`-bit numbers.
+fn evaluator<C: AbstractChannel>(
+    channel: C,
+    c: &BinaryCircuit,
+) -> Result<Vec<Modulus>, TwopacError> {
+    let mut ev = Evaluator::<C, AesRng, AlszReceiver, WireMod2>::new(channel, AesRng::new())?;
+    let xs = ev.receive_many(&[2; N])?;
+    let ys = ev.encode_many(&[0; N], &[2; N])?;
+    ev.eval_circuit(c, &xs, &ys)
+}
+
+/// Record what each party of an honest run reads from the other.
+fn transcripts(c: &BinaryCircuit) -> (Vec<u8>, Vec<u8>) {
+    common::record(
+        |channel| garbler(channel, c).unwrap(),
+        |channel| {
+            evaluator(channel, c).unwrap();
+        },
+    )
+}
+
+/// A channel reading `bytes` and discarding what is written to it.
+fn replay(bytes: &[u8]) -> Channel<io::Cursor<Vec<u8>>, io::Sink> {
+    Channel::new(io::Cursor::new(bytes.to_vec()), io::sink())
+}
+
+#[test]
+fn truncated_and_corrupt_messages() {
+    let c = adder(N);
+    let (gb_read, ev_read) = transcripts(&c);
+
+    for n in cuts(ev_read.len(), 1021) {
+        fails(&format!("evaluating on {} bytes of messages", n), || {
+            evaluator(replay(&ev_read[..n]), &c)
+        });
+    }
+    for n in cuts(gb_read.len(), 509) {
+        // Past the protocol version, the garbler only reads during OT, so it
+        // only notices a truncation there.
+        let _ = no_panic(&format!("garbling on {} bytes of messages", n), || {
+            garbler(replay(&gb_read[..n]), &c)
+        });
+    }
+    fails("garbling on no messages", || garbler(replay(&[]), &c));
+
+    // Messages of the right length but the wrong content give garbage outputs
+    // or an error.
+    for hostile in [vec![0xff; ev_read.len()], vec![0; ev_read.len()]] {
+        let _ = no_panic("evaluating on constant messages", || {
+            evaluator(replay(&hostile), &c)
+        });
+    }
+    for hostile in [vec![0xff; gb_read.len()], vec![0; gb_read.len()]] {
+        let _ = no_panic("garbling on constant messages", || {
+            garbler(replay(&hostile), &c)
+        });
+    }
+
+    // Too few input wires are rejected before anything is sent or read.
+    let e = fails("evaluating on too few inputs", || {
+        let mut ev =
+            Evaluator::<_, AesRng, AlszReceiver, WireMod2>::new(replay(&ev_read), AesRng::new())?;
+        let xs = ev.receive_many(&[2; N])?;
+        ev.eval_circuit(&c, &xs, &xs[..N - 1])
+    });
+    assert!(
+        matches!(
+            e,
+            TwopacError::FancyError(FancyError::NotEnoughEvaluatorInputs { .. })
+        ),
+        "{:?}",
+        e
+    );
+}
@@ -0,0 +1,192 @@
+//! Cancelling garblings and evaluations of SHA-256 partway through with
+//! `hooks::Hooks`.
+
+use fancy_garbling::{
+    circuit::{BinaryCircuit, CircuitType},
+    classic,
+    errors::{EvaluatorError, GarblerError, TwopacError},
+    hooks::{CancelToken, Hooks},
+    twopac::semihonest::{OutputMode, SemiHonest},
+    FancyInput, Modulus, WireMod2,
+};
+use scuttlebutt::{unix_channel_pair, AesRng};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+const INTERVAL: usize = 1000;
+
+fn sha256() -> BinaryCircuit {
+    BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+        "../circuits/sha-256.txt"
+    )))
+    .unwrap()
+}
+
+/// Hooks that cancel once `at` gates are done, from another thread, and
+/// record how many gates were done when last called.
+fn cancel_at(at: usize) -> (Hooks, Arc<AtomicUsize>) {
+    let token = CancelToken::new();
+    let seen = Arc::new(AtomicUsize::new(0));
+    let seen_ = seen.clone();
+    let token_ = token.clone();
+    let hooks = Hooks::new()
+        .every(INTERVAL)
+        .on_progress(move |done, _| {
+            seen_.store(done, Ordering::SeqCst);
+            if done >= at {
+                let token = token_.clone();
+                std::thread::spawn(move || token.cancel()).join().unwrap();
+            }
+        })
+        .cancel_token(&token);
+    (hooks, seen)
+}
+
+#[test]
+fn classic_progress() {
+    let circ = sha256();
+    let total = circ.get_gates().len();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let calls_ = calls.clone();
+    let hooks = Hooks::new().every(INTERVAL).on_progress(move |done, n| {
+        assert_eq!(n, total);
+        assert!(done == n || done % INTERVAL == 0);
+        calls_.fetch_add(1, Ordering::SeqCst);
+    });
+    let (en, gc) = classic::garble_with_hooks::<WireMod2, _>(&circ, AesRng::new(), &hooks).unwrap();
+    assert_eq!(calls.load(Ordering::SeqCst), total / INTERVAL + 1);
+
+    let xs = en.encode_garbler_inputs(&[0; 512]).unwrap();
+    let ys = en.encode_evaluator_inputs(&[]).unwrap();
+    let expected = gc.eval(&circ, &xs, &ys).unwrap();
+    assert_eq!(
+        gc.eval_with_hooks(&circ, &xs, &ys, &hooks).unwrap(),
+        expected
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 2 * (total / INTERVAL + 1));
+}
+
+#[test]
+fn classic_garbling_cancelled() {
+    let circ = sha256();
+    let half = circ.get_gates().len() / 2;
+    let (hooks, seen) = cancel_at(half);
+    let r = classic::garble_with_hooks::<WireMod2, _>(&circ, AesRng::new(), &hooks);
+    let stopped = (half + INTERVAL - 1) / INTERVAL * INTERVAL;
+    match r {
+        Err(GarblerError::Cancelled { gates_done }) => assert_eq!(gates_done, stopped),
+        r => panic!("expected the garbling to be cancelled: {:?}", r.err()),
+    }
+    assert_eq!(seen.load(Ordering::SeqCst), stopped);
+}
+
+#[test]
+fn classic_evaluation_cancelled() {
+    let circ = sha256();
+    let (en, gc) = classic::garble::<WireMod2, _>(&circ).unwrap();
+    let xs = en.encode_garbler_inputs(&[1; 512]).unwrap();
+    let ys = en.encode_evaluator_inputs(&[]).unwrap();
+    let (hooks, _) = cancel_at(INTERVAL);
+    let r = gc.eval_with_hooks(&circ, &xs, &ys, &hooks);
+    assert!(
+        matches!(
+            r,
+            Err(EvaluatorError::Cancelled {
+                gates_done: INTERVAL
+            })
+        ),
+        "{:?}",
+        r
+    );
+}
+
+/// Run SHA-256 between a garbler and an evaluator with the given hooks,
+/// returning each party's result and how long it took.
+#[allow(clippy::type_complexity)]
+fn twopac(
+    garbler_hooks: Hooks,
+    evaluator_hooks: Hooks,
+) -> (
+    (Result<Option<Vec<Modulus>>, TwopacError>, Duration),
+    (Result<Vec<Modulus>, TwopacError>, Duration),
+) {
+    let circ = Arc::new(sha256());
+    let circ_ = circ.clone();
+    // A timeout turns a hang into a failure rather than a stuck test.
+    let config = SemiHonest::builder()
+        .output_mode(OutputMode::Both)
+        .timeout(Duration::from_secs(60));
+    let gb_config = config.clone().hooks(garbler_hooks);
+    let ev_config = config.hooks(evaluator_hooks);
+    let (sender, receiver) = unix_channel_pair();
+    let handle = std::thread::spawn(move || {
+        let start = Instant::now();
+        let r = (|| {
+            let mut gb = gb_config.build_garbler::<_, _, WireMod2>(sender, AesRng::new())?;
+            let xs = gb.encode_many(&[0; 512], &[2; 512])?;
+            gb.eval_circuit(&*circ_, &xs, &[])
+        })();
+        (r, start.elapsed())
+    });
+    let start = Instant::now();
+    let r = (|| {
+        let mut ev = ev_config.build_evaluator::<_, _, WireMod2>(receiver, AesRng::new())?;
+        let xs = ev.receive_many(&[2; 512])?;
+        ev.eval_circuit(&*circ, &xs, &[])
+    })();
+    let ev = (r, start.elapsed());
+    (handle.join().unwrap(), ev)
+}
+
+#[test]
+fn twopac_completes_with_hooks() {
+    let ((gb, _), (ev, _)) = twopac(Hooks::new().every(INTERVAL), Hooks::new().every(INTERVAL));
+    let outputs = ev.unwrap();
+    assert_eq!(gb.unwrap(), Some(outputs));
+}
+
+#[test]
+fn twopac_garbler_cancelled() {
+    let half = sha256().get_gates().len() / 2;
+    let (hooks, _) = cancel_at(half);
+    let ((gb, gb_time), (ev, ev_time)) = twopac(hooks, Hooks::new().every(INTERVAL));
+    let stopped = (half + INTERVAL - 1) / INTERVAL * INTERVAL;
+    assert!(
+        matches!(gb, Err(TwopacError::Cancelled { gates_done }) if gates_done == stopped),
+        "{:?}",
+        gb
+    );
+    // The evaluator learns of the cancellation at the same checkpoint,
+    // rather than waiting for the rest of the circuit.
+    assert!(
+        matches!(ev, Err(TwopacError::PeerCancelled { gates_done }) if gates_done == stopped),
+        "{:?}",
+        ev
+    );
+    assert!(gb_time < Duration::from_secs(30) && ev_time < Duration::from_secs(30));
+}
+
+#[test]
+fn twopac_evaluator_cancelled() {
+    let (hooks, _) = cancel_at(INTERVAL);
+    let ((gb, gb_time), (ev, ev_time)) = twopac(Hooks::new().every(INTERVAL), hooks);
+    assert!(
+        matches!(
+            ev,
+            Err(TwopacError::Cancelled {
+                gates_done: INTERVAL
+            })
+        ),
+        "{:?}",
+        ev
+    );
+    // The evaluator has dropped its channel, so the garbler fails on it.
+    let gb = gb.unwrap_err();
+    assert!(gb.channel_error().is_some(), "{:?}", gb);
+    assert!(gb_time < Duration::from_secs(30) && ev_time < Duration::from_secs(30));
+}
@@ -0,0 +1,106 @@
+use assert_cmd::Command;
+
+const ADDER64: &str = "circuits/adder64.txt";
+
+fn fancy() -> Command {
+    Command::cargo_bin("fancy").unwrap()
+}
+
+#[test]
+fn info() {
+    let output = fancy().args(["info", ADDER64]).output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let field = |name: &str| -> usize {
+        let line = stdout.lines().find(|l| l.trim_start().starts_with(name));
+        let line = line.unwrap_or_else(|| panic!("no {} in {}", name, stdout));
+        line.split_whitespace().last().unwrap().parse().unwrap()
+    };
+    assert_eq!(field("garbler inputs:"), 64);
+    assert_eq!(field("evaluator inputs:"), 64);
+    assert_eq!(field("outputs:"), 64);
+    assert_eq!(field("gates:"), 314);
+    assert_eq!(field("and:"), 125);
+    assert_eq!(field("and depth:"), 63);
+}
+
+#[test]
+fn eval() {
+    // 0x0123456789abcdef + 0xfedcba9876543211 = 2^64 = 0 mod 2^64
+    let output = fancy()
+        .args(["eval", ADDER64])
+        .args(["--garbler-hex", "efcdab8967452301"])
+        .args(["--evaluator-hex", "1132547698badcfe"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains(&format!("hex:    {}", "00".repeat(8))),
+        "{}",
+        stdout
+    );
+
+    // 5 + 7 = 12
+    let output = fancy()
+        .args(["eval", ADDER64])
+        .args(["--garbler-hex", "0500000000000000"])
+        .args(["--evaluator-hex", "0700000000000000"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("hex:    0c00000000000000"), "{}", stdout);
+    assert!(
+        stdout.contains(&format!("binary: 0011{}", "0".repeat(60))),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn eval_rejects_bad_input() {
+    let output = fancy()
+        .args(["eval", ADDER64, "--garbler-hex", "05"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("expected 16 hex digits"), "{}", stderr);
+}
+
+#[test]
+fn oprf_aes() {
+    // The example vector of FIPS 197, appendix C.1.
+    let key = "000102030405060708090a0b0c0d0e0f";
+    let message = "00112233445566778899aabbccddeeff";
+    let ciphertext = "69c4e0d86a7b0430d8cdb78070b4c55a";
+    let output = fancy()
+        .args(["oprf-aes", "--key", key, "--message", message])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains(&format!("input holder: {}", ciphertext)),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("key holder:   nothing"), "{}", stdout);
+
+    let output = fancy()
+        .args(["oprf-aes", "--key", key, "--message", message, "--both"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.contains(&format!("key holder:   {}", ciphertext)),
+        "{}",
+        stdout
+    );
+
+    let output = fancy()
+        .args(["oprf-aes", "--key", "00", "--message", message])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}
@@ -0,0 +1,63 @@
+//! Helpers shared by the integration tests.
+
+use scuttlebutt::{unix_channel_pair, AbstractChannel, UnixChannel};
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+/// A channel that keeps a copy of everything read from it.
+pub struct RecordingChannel {
+    channel: UnixChannel,
+    read: Arc<Mutex<Vec<u8>>>,
+}
+
+impl AbstractChannel for RecordingChannel {
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> io::Result<()> {
+        self.channel.read_bytes(bytes)?;
+        self.read.lock().unwrap().extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.channel.write_bytes(bytes)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.channel.flush()
+    }
+
+    fn clone(&self) -> Self {
+        RecordingChannel {
+            channel: self.channel.clone(),
+            read: self.read.clone(),
+        }
+    }
+}
+
+/// Run `garbler` on a thread of its own and `evaluator` over a pair of
+/// recording channels, and return what the garbler and the evaluator read
+/// from each other, in that order.
+pub fn record(
+    garbler: impl FnOnce(RecordingChannel) + Send,
+    evaluator: impl FnOnce(RecordingChannel),
+) -> (Vec<u8>, Vec<u8>) {
+    let (sender, receiver) = unix_channel_pair();
+    let gb_read = Arc::new(Mutex::new(Vec::new()));
+    let ev_read = Arc::new(Mutex::new(Vec::new()));
+    let sender = RecordingChannel {
+        channel: sender,
+        read: gb_read.clone(),
+    };
+    let receiver = RecordingChannel {
+        channel: receiver,
+        read: ev_read.clone(),
+    };
+    std::thread::scope(|s| {
+        s.spawn(|| garbler(sender));
+        evaluator(receiver);
+    });
+    let gb_read = gb_read.lock().unwrap().clone();
+    let ev_read = ev_read.lock().unwrap().clone();
+    (gb_read, ev_read)
+}
@@ -0,0 +1,72 @@
+//! Imports wire labels exported at earlier versions of the label format, so
+//! that a change to the format cannot go unnoticed.
+//!
+//! Each file in `tests/labels` was written at the version in its name, and is
+//! never rewritten: every later build must import its labels as the same
+//! wires, and export those wires as the same bytes.
+
+use fancy_garbling::{
+    errors::LabelError, AllWire, ExportedLabel, HasModulus, WireLabel, WireMod2, WireMod3,
+    WireModQ, WireRole,
+};
+use scuttlebutt::Block;
+
+const V1: &[u8] = include_bytes!("labels/labels-v1.bin");
+
+#[test]
+fn import_v1() {
+    let labels = ExportedLabel::from_bytes_many(V1).unwrap();
+    let roles = labels.iter().map(|label| label.role).collect::<Vec<_>>();
+    assert_eq!(
+        roles,
+        [
+            WireRole::GarblerInput,
+            WireRole::EvaluatorInput,
+            WireRole::Output,
+            WireRole::Unspecified
+        ]
+    );
+
+    let binary = WireMod2::import(labels[0]).unwrap();
+    assert_eq!(
+        binary.as_block(),
+        Block::from(0x0123_4567_89ab_cdef_fedc_ba98_7654_3210_u128)
+    );
+    assert_eq!(binary.color(), 0);
+
+    let ternary = WireMod3::import(labels[1]).unwrap();
+    assert_eq!(ternary.as_block(), Block::from(0xa << 64 | 0x5));
+    assert_eq!(ternary.color(), 1);
+
+    let wide = WireModQ::import(labels[2]).unwrap();
+    assert_eq!(wide.modulus(), 17);
+    assert_eq!(wide.digits()[..4], [1, 2, 3, 0]);
+
+    let ones = WireMod2::import(labels[3]).unwrap();
+    assert_eq!(ones.as_block(), Block::from(u128::MAX));
+    assert_eq!(labels[3].color, Some(true));
+
+    // The same labels import as `AllWire`s, and export as the same bytes.
+    let exported = labels
+        .iter()
+        .map(|&label| {
+            let wire = AllWire::import(label).unwrap();
+            ExportedLabel::new(&wire, label.role)
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(exported[0], labels[0]);
+    assert_eq!(exported[1], labels[1]);
+    assert_eq!(exported[2], labels[2]);
+    // Exports always give the color of binary labels.
+    assert_eq!(ExportedLabel::to_bytes_many(&exported), V1);
+}
+
+#[test]
+fn reject_future_versions() {
+    let mut bytes = V1.to_vec();
+    bytes[0] = 2;
+    assert_eq!(
+        ExportedLabel::from_bytes_many(&bytes),
+        Err(LabelError::UnsupportedVersion(2))
+    );
+}
@@ -0,0 +1,124 @@
+//! Checks the spans and progress events emitted with the `tracing` feature
+//! over a semi-honest run of AES.
+
+use fancy_garbling::{
+    circuit::{BinaryCircuit, PROGRESS_INTERVAL},
+    twopac::semihonest::{Evaluator, Garbler},
+    FancyInput, WireMod2,
+};
+use ocelot::ot::{AlszReceiver, AlszSender};
+use scuttlebutt::{unix_channel_pair, AesRng, UnixChannel};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Subscriber,
+};
+use tracing_subscriber::{layer::Context, prelude::*, registry::LookupSpan, Layer};
+
+/// Records, each paired with the name of its enclosing span, if it has one.
+type Log<T> = Arc<Mutex<Vec<(T, Option<&'static str>)>>>;
+
+/// What was recorded by a `Capture` layer: each span as its name and the
+/// name of its parent, and each event as its `gates` field and the name of
+/// its span.
+#[derive(Clone, Default)]
+struct Capture {
+    spans: Log<&'static str>,
+    events: Log<u64>,
+}
+
+struct Gates(u64);
+
+impl Visit for Gates {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "gates" {
+            self.0 = value;
+        }
+    }
+
+    fn record_debug(&mut self, _: &Field, _: &dyn fmt::Debug) {}
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Capture {
+    fn on_new_span(&self, _: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let span = ctx.span(id).unwrap();
+        let parent = span.parent().map(|p| p.name());
+        self.spans.lock().unwrap().push((span.name(), parent));
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut gates = Gates(0);
+        event.record(&mut gates);
+        let parent = ctx.event_span(event).map(|s| s.name());
+        self.events.lock().unwrap().push((gates.0, parent));
+    }
+}
+
+fn capture<T>(f: impl FnOnce() -> T) -> (T, Capture) {
+    let capture = Capture::default();
+    let subscriber = tracing_subscriber::registry().with(capture.clone());
+    let out = tracing::subscriber::with_default(subscriber, f);
+    (out, capture)
+}
+
+/// The spans of a party whose circuit evaluation is in a span named `eval`:
+/// the garbler's inputs, the evaluator's inputs by OT, and the circuit.
+fn expected_spans(eval: &'static str) -> Vec<(&'static str, Option<&'static str>)> {
+    vec![
+        ("encode_inputs", None),
+        ("encode_inputs", None),
+        ("base_ot", Some("encode_inputs")),
+        ("ot_extension", Some("encode_inputs")),
+        (eval, None),
+        ("gates", Some(eval)),
+        ("outputs", Some(eval)),
+    ]
+}
+
+#[test]
+fn aes_128() {
+    let (circ, parse) = capture(|| {
+        BinaryCircuit::parse(std::io::Cursor::<&'static [u8]>::new(include_bytes!(
+            "../circuits/AES-non-expanded.txt"
+        )))
+        .unwrap()
+    });
+    assert_eq!(*parse.spans.lock().unwrap(), [("parse", None)]);
+
+    let ngates = circ.gates().len();
+    let circ = Arc::new(circ);
+    let circ_ = Arc::clone(&circ);
+    let (sender, receiver) = unix_channel_pair();
+    let handle = std::thread::spawn(move || {
+        capture(|| {
+            let mut gb =
+                Garbler::<UnixChannel, AesRng, AlszSender, WireMod2>::new(sender, AesRng::new())
+                    .unwrap();
+            let xs = gb.encode_many(&[0; 128], &[2; 128]).unwrap();
+            let ys = gb.receive_many(&[2; 128]).unwrap();
+            gb.eval_circuit(&*circ_, &xs, &ys).unwrap();
+        })
+        .1
+    });
+    let ((), ev) = capture(|| {
+        let mut ev =
+            Evaluator::<UnixChannel, AesRng, AlszReceiver, WireMod2>::new(receiver, AesRng::new())
+                .unwrap();
+        let xs = ev.receive_many(&[2; 128]).unwrap();
+        let ys = ev.encode_many(&[0; 128], &[2; 128]).unwrap();
+        ev.eval_circuit(&*circ, &xs, &ys).unwrap();
+    });
+    let gb = handle.join().unwrap();
+
+    let progress = (1..=ngates / PROGRESS_INTERVAL)
+        .map(|i| ((i * PROGRESS_INTERVAL) as u64, Some("gates")))
+        .collect::<Vec<_>>();
+    assert!(!progress.is_empty());
+    for (eval, capture) in [("stream_gates", gb), ("evaluate", ev)] {
+        assert_eq!(*capture.spans.lock().unwrap(), expected_spans(eval));
+        assert_eq!(*capture.events.lock().unwrap(), progress);
+    }
+}
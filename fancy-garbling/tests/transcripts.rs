@@ -0,0 +1,177 @@
+//! Replays golden transcripts of the semi-honest protocol, recorded at the
+//! current [`PROTOCOL_VERSION`], so that a change to what the parties send
+//! cannot go unnoticed.
+//!
+//! The parties' randomness is seeded, so an honest run sends the same bytes
+//! every time. Each party is run against the recorded messages of its peer,
+//! and must send exactly the recorded messages of its own. A change to the
+//! protocol must bump [`PROTOCOL_VERSION`] and record new transcripts, by
+//! running this test with `FANCY_GARBLING_REGENERATE_TRANSCRIPTS` set; the
+//! transcripts of a version, once checked in, are never rewritten.
+//!
+//! The `blake3-hash` and `wide-moduli` features change what is sent, so the
+//! transcripts are only checked without them.
+
+#![cfg(not(any(feature = "blake3-hash", feature = "wide-moduli")))]
+
+use fancy_garbling::{
+    circuit::{BinaryCircuit, CircuitBuilder},
+    errors::TwopacError,
+    twopac::semihonest::{Evaluator, Garbler, OutputMode, SemiHonest, PROTOCOL_VERSION},
+    util, BinaryGadgets, Fancy, FancyInput, Modulus, WireMod2,
+};
+use rand::SeedableRng;
+use scuttlebutt::{AbstractChannel, AesRng, Block, Channel};
+use std::{fs, io, path::PathBuf, rc::Rc};
+
+mod common;
+
+const REGENERATE: &str = "FANCY_GARBLING_REGENERATE_TRANSCRIPTS";
+
+const GARBLER_INPUT: u128 = 0xb5;
+const EVALUATOR_INPUT: u128 = 0x6c;
+const N: usize = 8;
+
+/// An `N`-bit adder outputting its sum and its carry.
+fn adder() -> BinaryCircuit {
+    let mut b = CircuitBuilder::<BinaryCircuit>::new();
+    let x = b.bin_garbler_input(N);
+    let y = b.bin_evaluator_input(N);
+    let (z, carry) = b.bin_addition(&x, &y).unwrap();
+    b.outputs(z.wires()).unwrap();
+    b.output(&carry).unwrap();
+    b.finish()
+}
+
+/// The outputs of the adder, least significant bit first.
+fn expected() -> Vec<Modulus> {
+    let sum = GARBLER_INPUT + EVALUATOR_INPUT;
+    (0..=N).map(|i| ((sum >> i) & 1) as Modulus).collect()
+}
+
+fn garbler<C: AbstractChannel>(channel: C) -> Result<Option<Vec<Modulus>>, TwopacError> {
+    let mut gb = SemiHonest::builder()
+        .output_mode(OutputMode::Both)
+        .build_garbler::<_, _, WireMod2>(channel, AesRng::from_seed(Block::from(1)))?;
+    let xs = gb.encode_many(&util::u128_to_bits(GARBLER_INPUT, N), &[2; N])?;
+    let ys = gb.receive_many(&[2; N])?;
+    gb.eval_circuit(&adder(), &xs, &ys)
+}
+
+fn evaluator<C: AbstractChannel>(channel: C) -> Result<Vec<Modulus>, TwopacError> {
+    let mut ev = SemiHonest::builder()
+        .output_mode(OutputMode::Both)
+        .build_evaluator::<_, _, WireMod2>(channel, AesRng::from_seed(Block::from(2)))?;
+    let xs = ev.receive_many(&[2; N])?;
+    let ys = ev.encode_many(&util::u128_to_bits(EVALUATOR_INPUT, N), &[2; N])?;
+    ev.eval_circuit(&adder(), &xs, &ys)
+}
+
+/// The path of the golden transcript of the messages sent to `party`.
+fn golden_path(party: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/transcripts")
+        .join(format!("semihonest-v{}.to-{}", PROTOCOL_VERSION, party))
+}
+
+fn golden(party: &str) -> Vec<u8> {
+    let path = golden_path(party);
+    fs::read(&path).unwrap_or_else(|e| {
+        panic!(
+            "no transcript at {} ({}); record one by running this test with {} set",
+            path.display(),
+            e,
+            REGENERATE
+        )
+    })
+}
+
+/// Run `party` against `messages`, returning its result and what it sent.
+fn replay<T>(
+    messages: Vec<u8>,
+    party: impl FnOnce(Channel<io::Cursor<Vec<u8>>, Vec<u8>>) -> T,
+) -> (T, Vec<u8>) {
+    let channel = Channel::new(io::Cursor::new(messages), Vec::new());
+    let result = party(channel.clone());
+    let sent = Rc::try_unwrap(channel.writer())
+        .expect("the party has been dropped")
+        .into_inner();
+    (result, sent)
+}
+
+#[test]
+fn record() {
+    let (to_garbler, to_evaluator) = common::record(
+        |channel| assert_eq!(garbler(channel).unwrap(), Some(expected())),
+        |channel| assert_eq!(evaluator(channel).unwrap(), expected()),
+    );
+    if std::env::var_os(REGENERATE).is_none() {
+        return;
+    }
+    for (party, messages) in [("garbler", to_garbler), ("evaluator", to_evaluator)] {
+        let path = golden_path(party);
+        match fs::read(&path) {
+            Ok(old) if old != messages => panic!(
+                "the messages sent to the {} differ from those of version {}; bump \
+                 PROTOCOL_VERSION rather than rewriting {}",
+                party,
+                PROTOCOL_VERSION,
+                path.display()
+            ),
+            Ok(_) => (),
+            Err(_) => fs::write(&path, messages).unwrap(),
+        }
+    }
+}
+
+#[test]
+fn evaluator_replays_golden() {
+    let (outputs, sent) = replay(golden("evaluator"), evaluator);
+    assert_eq!(outputs.unwrap(), expected());
+    assert!(
+        sent == golden("garbler"),
+        "the evaluator no longer sends what it did in version {}",
+        PROTOCOL_VERSION
+    );
+}
+
+#[test]
+fn garbler_replays_golden() {
+    let (outputs, sent) = replay(golden("garbler"), garbler);
+    assert_eq!(outputs.unwrap(), Some(expected()));
+    assert!(
+        sent == golden("evaluator"),
+        "the garbler no longer sends what it did in version {}",
+        PROTOCOL_VERSION
+    );
+}
+
+#[test]
+fn version_mismatch() {
+    let mut messages = golden("evaluator");
+    messages[..4].copy_from_slice(&(PROTOCOL_VERSION + 1).to_le_bytes());
+    let (ev, sent) = replay(messages, |channel| {
+        Evaluator::<_, AesRng, ocelot::ot::AlszReceiver, WireMod2>::new(channel, AesRng::new())
+            .map(drop)
+    });
+    match ev {
+        Err(TwopacError::VersionMismatch { ours, theirs }) => {
+            assert_eq!((ours, theirs), (PROTOCOL_VERSION, PROTOCOL_VERSION + 1))
+        }
+        r => panic!("expected a version mismatch: {:?}", r),
+    }
+    // The evaluator still announces its own version.
+    assert_eq!(sent, PROTOCOL_VERSION.to_le_bytes());
+
+    let mut messages = golden("garbler");
+    messages[..4].copy_from_slice(&0u32.to_le_bytes());
+    let (gb, _) = replay(messages, |channel| {
+        Garbler::<_, AesRng, ocelot::ot::AlszSender, WireMod2>::new(channel, AesRng::new())
+            .map(drop)
+    });
+    assert!(
+        matches!(gb, Err(TwopacError::VersionMismatch { theirs: 0, .. })),
+        "{:?}",
+        gb
+    );
+}
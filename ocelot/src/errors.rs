@@ -1,5 +1,6 @@
 /// Errors produced by `ocelot`.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// The input length is invalid.
     InvalidInputLength,
@@ -17,7 +18,28 @@ pub enum Error {
     InvalidOpening,
 }
 
-impl std::error::Error for Error {}
+impl Error {
+    /// How the channel failed, if this error came from a failed read or write
+    /// on it.
+    pub fn channel_error(&self) -> Option<scuttlebutt::ChannelError> {
+        match self {
+            Error::IoError(e) | Error::CoinTossError(scuttlebutt::cointoss::Error::IoError(e)) => {
+                Some(e.into())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::IoError(e) => Some(e),
+            Error::CoinTossError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 impl From<std::io::Error> for Error {
     fn from(e: std::io::Error) -> Error {
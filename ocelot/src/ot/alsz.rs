@@ -13,23 +13,29 @@ use crate::{
     utils,
 };
 use rand::{CryptoRng, Rng, RngCore, SeedableRng};
-use scuttlebutt::{
-    utils as scutils, AbstractChannel, AesHash, AesRng, Block, SemiHonest, AES_HASH,
-};
+use scuttlebutt::{utils as scutils, AbstractChannel, AesRng, Block, SemiHonest};
+// The correlation-robust hash of this and the KOS extension: fixed-key AES,
+// or BLAKE3 with the `blake3-hash` feature.
+#[cfg(not(feature = "blake3-hash"))]
+use scuttlebutt::{AesHash as Hash, AES_HASH as HASH};
+#[cfg(feature = "blake3-hash")]
+use scuttlebutt::{Blake3Hash as Hash, BLAKE3_HASH as HASH};
 use std::marker::PhantomData;
 
 /// Oblivious transfer sender.
+#[derive(Clone)]
 pub struct Sender<OT: OtReceiver<Msg = Block> + SemiHonest> {
     _ot: PhantomData<OT>,
-    pub(super) hash: AesHash,
+    pub(super) hash: Hash,
     s: Vec<bool>,
     pub(super) s_: Block,
     rngs: Vec<AesRng>,
 }
 /// Oblivious transfer receiver.
+#[derive(Clone)]
 pub struct Receiver<OT: OtSender<Msg = Block> + SemiHonest> {
     _ot: PhantomData<OT>,
-    pub(super) hash: AesHash,
+    pub(super) hash: Hash,
     rngs: Vec<(AesRng, AesRng)>,
 }
 
@@ -48,7 +54,7 @@ impl<OT: OtReceiver<Msg = Block> + SemiHonest> FixedKeyInitializer for Sender<OT
             .collect::<Vec<AesRng>>();
         Ok(Self {
             _ot: PhantomData::<OT>,
-            hash: AES_HASH,
+            hash: HASH,
             s,
             s_: Block::from(s_),
             rngs,
@@ -215,7 +221,7 @@ impl<OT: OtSender<Msg = Block> + SemiHonest> OtReceiver for Receiver<OT> {
             .collect::<Vec<(AesRng, AesRng)>>();
         Ok(Self {
             _ot: PhantomData::<OT>,
-            hash: AES_HASH,
+            hash: HASH,
             rngs,
         })
     }
@@ -292,3 +298,20 @@ impl<OT: OtSender<Msg = Block> + SemiHonest> std::fmt::Display for Receiver<OT>
 
 impl<OT: OtReceiver<Msg = Block> + SemiHonest> SemiHonest for Sender<OT> {}
 impl<OT: OtSender<Msg = Block> + SemiHonest> SemiHonest for Receiver<OT> {}
+
+// With the `zeroize` feature, the sender overwrites its choice of correlation
+// when dropped. The seeds of both parties are in `AesRng`s, which overwrite
+// themselves.
+#[cfg(feature = "zeroize")]
+impl<OT: OtReceiver<Msg = Block> + SemiHonest> Drop for Sender<OT> {
+    fn drop(&mut self) {
+        use zeroize::Zeroize;
+        self.s.zeroize();
+        self.s_.zeroize();
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<OT: OtReceiver<Msg = Block> + SemiHonest> zeroize::ZeroizeOnDrop for Sender<OT> {}
+#[cfg(feature = "zeroize")]
+impl<OT: OtSender<Msg = Block> + SemiHonest> zeroize::ZeroizeOnDrop for Receiver<OT> {}
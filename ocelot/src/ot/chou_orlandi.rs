@@ -24,6 +24,7 @@ use rand::{CryptoRng, Rng};
 use scuttlebutt::{AbstractChannel, Block, Malicious, SemiHonest};
 
 /// Oblivious transfer sender.
+#[derive(Clone)]
 pub struct Sender {
     y: Scalar,
     s: RistrettoPoint,
@@ -79,6 +80,7 @@ impl std::fmt::Display for Sender {
 }
 
 /// Oblivious transfer receiver.
+#[derive(Clone)]
 pub struct Receiver {
     s: RistrettoBasepointTable,
     counter: u128,
@@ -140,3 +142,15 @@ impl SemiHonest for Sender {}
 impl Malicious for Sender {}
 impl SemiHonest for Receiver {}
 impl Malicious for Receiver {}
+
+// With the `zeroize` feature, the sender overwrites its secret scalar when
+// dropped. The receiver holds only the sender's public point.
+#[cfg(feature = "zeroize")]
+impl Drop for Sender {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.y);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for Sender {}
@@ -9,8 +9,10 @@ use rand::{CryptoRng, Rng};
 use scuttlebutt::{AbstractChannel, Block};
 
 /// Oblivious transfer sender.
+#[derive(Clone)]
 pub struct Sender {}
 /// Oblivious transfer receiver.
+#[derive(Clone)]
 pub struct Receiver {}
 
 impl OtSender for Sender {
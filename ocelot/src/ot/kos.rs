@@ -15,17 +15,32 @@ use rand::{CryptoRng, Rng, RngCore, SeedableRng};
 use scuttlebutt::{cointoss, AbstractChannel, AesRng, Block, Malicious, SemiHonest};
 use std::io::ErrorKind;
 
-// The statistical security parameter.
+// The default statistical security parameter.
 const SSP: usize = 40;
 
 /// Oblivious transfer extension sender.
+#[derive(Clone)]
 pub struct Sender<OT: OtReceiver<Msg = Block> + Malicious> {
     pub(super) ot: AlszSender<OT>,
+    ssp: usize,
 }
 
 /// Oblivious transfer extension receiver.
+#[derive(Clone)]
 pub struct Receiver<OT: OtSender<Msg = Block> + Malicious> {
     ot: AlszReceiver<OT>,
+    ssp: usize,
+}
+
+// Check that `bits` is a statistical security parameter the correlation check
+// supports: the extra columns it adds must fill whole bytes.
+fn check_ssp(bits: usize) -> usize {
+    assert!(
+        bits > 0 && bits % 8 == 0,
+        "the statistical security parameter must be a positive multiple of 8, not {}",
+        bits
+    );
+    bits
 }
 
 impl<OT: OtReceiver<Msg = Block> + Malicious> Sender<OT> {
@@ -36,7 +51,7 @@ impl<OT: OtReceiver<Msg = Block> + Malicious> Sender<OT> {
         rng: &mut RNG,
     ) -> Result<Vec<u8>, Error> {
         let m = if m % 8 != 0 { m + (8 - m % 8) } else { m };
-        let ncols = m + 128 + SSP;
+        let ncols = m + 128 + self.ssp;
         let qs = self.ot.send_setup(channel, ncols)?;
         // Check correlation
         let mut seed = Block::default();
@@ -75,7 +90,7 @@ impl<OT: OtReceiver<Msg = Block> + Malicious> FixedKeyInitializer for Sender<OT>
         rng: &mut RNG,
     ) -> Result<Self, Error> {
         let ot = AlszSender::<OT>::init_fixed_key(channel, s_, rng)?;
-        Ok(Self { ot })
+        Ok(Self { ot, ssp: SSP })
     }
 }
 
@@ -87,7 +102,7 @@ impl<OT: OtReceiver<Msg = Block> + Malicious> OtSender for Sender<OT> {
         rng: &mut RNG,
     ) -> Result<Self, Error> {
         let ot = AlszSender::<OT>::init(channel, rng)?;
-        Ok(Self { ot })
+        Ok(Self { ot, ssp: SSP })
     }
 
     fn send<C: AbstractChannel, RNG: CryptoRng + Rng>(
@@ -112,6 +127,13 @@ impl<OT: OtReceiver<Msg = Block> + Malicious> OtSender for Sender<OT> {
         channel.flush()?;
         Ok(())
     }
+
+    /// # Panics
+    ///
+    /// Panics if `bits` is not a positive multiple of 8. The default is 40.
+    fn set_statistical_security(&mut self, bits: usize) {
+        self.ssp = check_ssp(bits);
+    }
 }
 
 impl<OT: OtReceiver<Msg = Block> + Malicious> CorrelatedSender for Sender<OT> {
@@ -177,9 +199,9 @@ impl<OT: OtSender<Msg = Block> + Malicious> Receiver<OT> {
     ) -> Result<Vec<u8>, Error> {
         let m = inputs.len();
         let m = if m % 8 != 0 { m + (8 - m % 8) } else { m };
-        let m_ = m + 128 + SSP;
+        let m_ = m + 128 + self.ssp;
         let mut r = utils::boolvec_to_u8vec(inputs);
-        r.extend((0..(m_ - m) / 8).map(|_| rand::random::<u8>()));
+        r.extend((0..(m_ - m) / 8).map(|_| rng.gen::<u8>()));
         let ts = self.ot.receive_setup(channel, &r, m_)?;
         // Check correlation
         let mut seed = Block::default();
@@ -215,7 +237,7 @@ impl<OT: OtSender<Msg = Block> + Malicious> OtReceiver for Receiver<OT> {
         rng: &mut RNG,
     ) -> Result<Self, Error> {
         let ot = AlszReceiver::<OT>::init(channel, rng)?;
-        Ok(Self { ot })
+        Ok(Self { ot, ssp: SSP })
     }
 
     fn receive<C: AbstractChannel, RNG: CryptoRng + Rng>(
@@ -241,6 +263,13 @@ impl<OT: OtSender<Msg = Block> + Malicious> OtReceiver for Receiver<OT> {
         }
         Ok(out)
     }
+
+    /// # Panics
+    ///
+    /// Panics if `bits` is not a positive multiple of 8. The default is 40.
+    fn set_statistical_security(&mut self, bits: usize) {
+        self.ssp = check_ssp(bits);
+    }
 }
 
 impl<OT: OtSender<Msg = Block> + Malicious> CorrelatedReceiver for Receiver<OT> {
@@ -299,3 +328,7 @@ impl<OT: OtReceiver<Msg = Block> + Malicious> SemiHonest for Sender<OT> {}
 impl<OT: OtSender<Msg = Block> + Malicious> SemiHonest for Receiver<OT> {}
 impl<OT: OtReceiver<Msg = Block> + Malicious> Malicious for Sender<OT> {}
 impl<OT: OtSender<Msg = Block> + Malicious> Malicious for Receiver<OT> {}
+#[cfg(feature = "zeroize")]
+impl<OT: OtReceiver<Msg = Block> + Malicious> zeroize::ZeroizeOnDrop for Sender<OT> {}
+#[cfg(feature = "zeroize")]
+impl<OT: OtSender<Msg = Block> + Malicious> zeroize::ZeroizeOnDrop for Receiver<OT> {}
@@ -13,10 +13,12 @@ use rand::{CryptoRng, Rng};
 use scuttlebutt::{AbstractChannel, Block, Malicious, SemiHonest};
 
 /// Oblivious transfer extension sender.
+#[derive(Clone)]
 pub struct Sender<OT: OtReceiver<Msg = Block> + Malicious> {
     ot: KosSender<OT>,
 }
 /// Oblivious transfer extension receiver.
+#[derive(Clone)]
 pub struct Receiver<OT: OtSender<Msg = Block> + Malicious> {
     ot: KosReceiver<OT>,
 }
@@ -62,6 +64,10 @@ impl<OT: OtReceiver<Msg = Block> + Malicious> OtSender for Sender<OT> {
     ) -> Result<(), Error> {
         self.ot.send(channel, inputs, rng)
     }
+
+    fn set_statistical_security(&mut self, bits: usize) {
+        self.ot.set_statistical_security(bits);
+    }
 }
 
 impl<OT: OtReceiver<Msg = Block> + Malicious> CorrelatedSender for Sender<OT> {
@@ -130,6 +136,10 @@ impl<OT: OtSender<Msg = Block> + Malicious> OtReceiver for Receiver<OT> {
     ) -> Result<Vec<Block>, Error> {
         self.ot.receive(channel, inputs, rng)
     }
+
+    fn set_statistical_security(&mut self, bits: usize) {
+        self.ot.set_statistical_security(bits);
+    }
 }
 
 impl<OT: OtSender<Msg = Block> + Malicious> CorrelatedReceiver for Receiver<OT> {
@@ -171,3 +181,7 @@ impl<OT: OtReceiver<Msg = Block> + Malicious> SemiHonest for Sender<OT> {}
 impl<OT: OtSender<Msg = Block> + Malicious> SemiHonest for Receiver<OT> {}
 impl<OT: OtReceiver<Msg = Block> + Malicious> Malicious for Sender<OT> {}
 impl<OT: OtSender<Msg = Block> + Malicious> Malicious for Receiver<OT> {}
+#[cfg(feature = "zeroize")]
+impl<OT: OtReceiver<Msg = Block> + Malicious> zeroize::ZeroizeOnDrop for Sender<OT> {}
+#[cfg(feature = "zeroize")]
+impl<OT: OtSender<Msg = Block> + Malicious> zeroize::ZeroizeOnDrop for Receiver<OT> {}
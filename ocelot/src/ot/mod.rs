@@ -67,6 +67,10 @@ where
         inputs: &[(Self::Msg, Self::Msg)],
         rng: &mut RNG,
     ) -> Result<(), Error>;
+    /// Sets the statistical security parameter of the protocol's consistency
+    /// check, in bits. The receiver must set the same value. Protocols
+    /// without such a check ignore it.
+    fn set_statistical_security(&mut self, _bits: usize) {}
 }
 
 /// Trait for initializing an oblivious transfer object with a fixed key.
@@ -105,6 +109,10 @@ where
         inputs: &[bool],
         rng: &mut RNG,
     ) -> Result<Vec<Self::Msg>, Error>;
+    /// Sets the statistical security parameter of the protocol's consistency
+    /// check, in bits. The sender must set the same value. Protocols without
+    /// such a check ignore it.
+    fn set_statistical_security(&mut self, _bits: usize) {}
 }
 
 /// Trait for one-out-of-two _correlated_ oblivious transfer from the sender's
@@ -401,6 +409,36 @@ mod tests {
         test_rotext::<KosSender, KosReceiver>(ninputs);
     }
 
+    #[test]
+    fn test_kos_statistical_security() {
+        let ninputs = 1 << 8;
+        let ms = rand_block_vec(2 * ninputs);
+        let ms = ms.chunks_exact(2).map(|m| (m[0], m[1])).collect::<Vec<_>>();
+        let bs = rand_bool_vec(ninputs);
+        let (sender, receiver) = UnixStream::pair().unwrap();
+        let ms_ = ms.clone();
+        let handle = std::thread::spawn(move || {
+            let mut rng = AesRng::new();
+            let reader = BufReader::new(sender.try_clone().unwrap());
+            let writer = BufWriter::new(sender);
+            let mut channel = Channel::new(reader, writer);
+            let mut otext = KosSender::init(&mut channel, &mut rng).unwrap();
+            otext.set_statistical_security(80);
+            otext.send(&mut channel, &ms_, &mut rng).unwrap();
+        });
+        let mut rng = AesRng::new();
+        let reader = BufReader::new(receiver.try_clone().unwrap());
+        let writer = BufWriter::new(receiver);
+        let mut channel = Channel::new(reader, writer);
+        let mut otext = KosReceiver::init(&mut channel, &mut rng).unwrap();
+        otext.set_statistical_security(80);
+        let results = otext.receive(&mut channel, &bs, &mut rng).unwrap();
+        handle.join().unwrap();
+        for j in 0..ninputs {
+            assert_eq!(results[j], if bs[j] { ms[j].1 } else { ms[j].0 })
+        }
+    }
+
     #[test]
     fn test_kos_delta() {
         let ninputs = 1 << 10;
@@ -414,4 +452,17 @@ mod tests {
         test_rotext::<KosDeltaSender, KosDeltaReceiver>(ninputs);
         test_rotext_fixed_key::<KosDeltaSender, KosDeltaReceiver>(ninputs);
     }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_zeroize_on_drop() {
+        fn assert_zeroize_on_drop<T: zeroize::ZeroizeOnDrop>() {}
+        assert_zeroize_on_drop::<ChouOrlandiSender>();
+        assert_zeroize_on_drop::<AlszSender>();
+        assert_zeroize_on_drop::<AlszReceiver>();
+        assert_zeroize_on_drop::<KosSender>();
+        assert_zeroize_on_drop::<KosReceiver>();
+        assert_zeroize_on_drop::<KosDeltaSender>();
+        assert_zeroize_on_drop::<KosDeltaReceiver>();
+    }
 }
@@ -15,8 +15,10 @@ use rand::{CryptoRng, Rng};
 use scuttlebutt::{AbstractChannel, Block, SemiHonest};
 
 /// Oblivious transfer sender.
+#[derive(Clone)]
 pub struct Sender {}
 /// Oblivious transfer receiver.
+#[derive(Clone)]
 pub struct Receiver {}
 
 impl OtSender for Sender {
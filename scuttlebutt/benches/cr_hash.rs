@@ -0,0 +1,35 @@
+//! Compare the correlation-robust hash functions on this CPU.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use scuttlebutt::{AesHash, Blake3Hash, Block, CrHash};
+
+fn bench_hash<H: CrHash>(c: &mut Criterion, name: &str, hash: H) {
+    let x = rand::random::<Block>();
+    let i = rand::random::<Block>();
+    c.bench_function(&format!("{}::tccr_hash", name), |b| {
+        b.iter(|| {
+            let z = hash.tccr_hash(black_box(i), black_box(x));
+            black_box(z)
+        });
+    });
+    c.bench_function(&format!("{}::tccr_hash_many (8)", name), |b| {
+        let xs = [x; 8];
+        b.iter(|| {
+            let z = hash.tccr_hash_many(black_box(i), black_box(xs));
+            black_box(z)
+        });
+    });
+}
+
+fn bench_cr_hash(c: &mut Criterion) {
+    let key = rand::random::<Block>();
+    bench_hash(c, "AesHash", AesHash::new(key));
+    bench_hash(c, "Blake3Hash", Blake3Hash::new(key));
+}
+
+criterion_group! {
+    name = cr_hash;
+    config = Criterion::default();
+    targets = bench_cr_hash
+}
+criterion_main!(cr_hash);
@@ -132,6 +132,14 @@ impl AsMut<[u8]> for Block {
     }
 }
 
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Block {
+    #[inline]
+    fn zeroize(&mut self) {
+        bytemuck::bytes_of_mut(self).zeroize();
+    }
+}
+
 impl rand::distributions::Distribution<Block> for rand::distributions::Standard {
     #[inline]
     fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> Block {
@@ -266,4 +274,13 @@ mod tests {
         let x_ = u128::from(Block::from(x));
         assert_eq!(x, x_);
     }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_zeroize() {
+        use zeroize::Zeroize;
+        let mut x = Block::from(u128::MAX);
+        x.zeroize();
+        assert_eq!(x, Block::default());
+    }
 }
@@ -1,15 +1,23 @@
+#[cfg(feature = "tokio")]
+mod async_channel;
 mod hash_channel;
 mod sync_channel;
+mod tcp_channel;
 mod track_channel;
 #[cfg(unix)]
 mod unix_channel;
 
+#[cfg(feature = "tokio")]
+pub use async_channel::{AsyncAbstractChannel, ChannelFuture};
 pub use hash_channel::HashChannel;
 pub use sync_channel::SyncChannel;
+pub use tcp_channel::{tcp_channel, TcpChannel};
 pub use track_channel::TrackChannel;
 
 #[cfg(unix)]
-pub use unix_channel::{track_unix_channel_pair, unix_channel_pair, TrackUnixChannel, UnixChannel};
+pub use unix_channel::{
+    track_unix_channel_pair, unix_channel, unix_channel_pair, TrackUnixChannel, UnixChannel,
+};
 
 use crate::{serialization::CanonicalSerialize, Block, Block512};
 #[cfg(feature = "curve25519-dalek")]
@@ -18,9 +26,42 @@ use generic_array::GenericArray;
 use std::{
     cell::RefCell,
     io::{Read, Result, Write},
+    net::TcpStream,
     rc::Rc,
+    time::Duration,
 };
 
+/// A socket whose blocking reads and writes can be given a timeout, after
+/// which they fail with an error that [`ChannelError`] classifies as
+/// [`ChannelError::Timeout`].
+pub trait SetTimeout {
+    /// Bound how long a read may block, or remove the bound with `None`.
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()>;
+    /// Bound how long a write may block, or remove the bound with `None`.
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> Result<()>;
+}
+
+impl SetTimeout for TcpStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        TcpStream::set_write_timeout(self, timeout)
+    }
+}
+
+#[cfg(unix)]
+impl SetTimeout for std::os::unix::net::UnixStream {
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        std::os::unix::net::UnixStream::set_read_timeout(self, timeout)
+    }
+
+    fn set_write_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        std::os::unix::net::UnixStream::set_write_timeout(self, timeout)
+    }
+}
+
 /// A trait for managing I/O. `AbstractChannel`s are clonable, and provide basic
 /// read/write capabilities for both common and scuttlebutt-specific types.
 pub trait AbstractChannel {
@@ -34,6 +75,19 @@ pub trait AbstractChannel {
     fn clone(&self) -> Self
     where
         Self: Sized;
+    /// Bound how long a read or write on the channel may block, or remove the
+    /// bounds with `None`.
+    ///
+    /// Channels over a socket they can reach, such as [`TcpChannel`] and
+    /// `UnixChannel`, support this; the default fails with
+    /// `ErrorKind::Unsupported`.
+    fn set_timeouts(&mut self, read: Option<Duration>, write: Option<Duration>) -> Result<()> {
+        let _ = (read, write);
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "channel does not support timeouts",
+        ))
+    }
     /// Read `nbytes` from the channel, and return it as a `Vec`.
     fn read_vec(&mut self, nbytes: usize) -> Result<Vec<u8>> {
         let mut data = vec![0; nbytes];
@@ -303,3 +357,48 @@ impl<S: Read + Write> AbstractChannel for SymChannel<S> {
         }
     }
 }
+
+/// The ways a read from or write to a channel can fail, as far as the caller
+/// of a protocol is concerned.
+///
+/// Channels report failures as [`std::io::Error`]s, which protocols pass on
+/// unchanged; `ChannelError::from` classifies one, so that a caller can tell
+/// a peer that went away, which is worth retrying the session for, from a
+/// local failure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelError {
+    /// The peer closed its end of the channel, or the connection to it was
+    /// lost.
+    PeerClosed,
+    /// The operation timed out.
+    Timeout,
+    /// Any other I/O error, of the given kind.
+    Io(std::io::ErrorKind),
+}
+
+impl From<&std::io::Error> for ChannelError {
+    fn from(e: &std::io::Error) -> Self {
+        use std::io::ErrorKind;
+        match e.kind() {
+            ErrorKind::UnexpectedEof
+            | ErrorKind::BrokenPipe
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected => ChannelError::PeerClosed,
+            ErrorKind::TimedOut | ErrorKind::WouldBlock => ChannelError::Timeout,
+            kind => ChannelError::Io(kind),
+        }
+    }
+}
+
+impl std::fmt::Display for ChannelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ChannelError::PeerClosed => "the peer closed the channel".fmt(f),
+            ChannelError::Timeout => "the channel timed out".fmt(f),
+            ChannelError::Io(kind) => write!(f, "I/O error on the channel: {:?}", kind),
+        }
+    }
+}
+
+impl std::error::Error for ChannelError {}
@@ -0,0 +1,43 @@
+use std::{future::Future, io::Result, pin::Pin};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// The future returned by the methods of an [`AsyncAbstractChannel`].
+pub type ChannelFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T>> + Send + 'a>>;
+
+/// A trait for managing I/O in asynchronous code, as [`AbstractChannel`](crate::AbstractChannel)
+/// does in blocking code.
+///
+/// It is implemented for every tokio stream that can be both read and written,
+/// such as `tokio::net::TcpStream` and the in-memory `tokio::io::DuplexStream`
+/// made by `tokio::io::duplex`. Its futures are `Send`, so a protocol over it
+/// can be spawned on a multithreaded runtime. Writes are not buffered; wrap
+/// the stream in a `tokio::io::BufStream` for many small writes.
+///
+/// Its methods return boxed futures, as those of traits made with the
+/// `async-trait` crate do, so that it can be implemented without `async fn`
+/// in traits.
+pub trait AsyncAbstractChannel: Send {
+    /// Read a slice of `u8`s from the channel.
+    fn read_bytes<'a>(&'a mut self, bytes: &'a mut [u8]) -> ChannelFuture<'a, ()>;
+    /// Write a slice of `u8`s to the channel.
+    fn write_bytes<'a>(&'a mut self, bytes: &'a [u8]) -> ChannelFuture<'a, ()>;
+    /// Flush the channel.
+    fn flush(&mut self) -> ChannelFuture<'_, ()>;
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> AsyncAbstractChannel for S {
+    fn read_bytes<'a>(&'a mut self, bytes: &'a mut [u8]) -> ChannelFuture<'a, ()> {
+        Box::pin(async move {
+            self.read_exact(bytes).await?;
+            Ok(())
+        })
+    }
+
+    fn write_bytes<'a>(&'a mut self, bytes: &'a [u8]) -> ChannelFuture<'a, ()> {
+        Box::pin(self.write_all(bytes))
+    }
+
+    fn flush(&mut self) -> ChannelFuture<'_, ()> {
+        Box::pin(AsyncWriteExt::flush(self))
+    }
+}
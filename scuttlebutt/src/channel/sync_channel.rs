@@ -1,13 +1,15 @@
-use crate::AbstractChannel;
+use crate::{channel::SetTimeout, AbstractChannel};
 use std::{
     io::{Read, Result, Write},
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
 /// A channel that implements `AbstractChannel` as well as `Send` and `Sync`.
 pub struct SyncChannel<R, W> {
     reader: Arc<Mutex<R>>,
     writer: Arc<Mutex<W>>,
+    socket: Option<Arc<dyn SetTimeout + Send + Sync>>,
 }
 
 impl<R: Read, W: Write> SyncChannel<R, W> {
@@ -15,7 +17,24 @@ impl<R: Read, W: Write> SyncChannel<R, W> {
     pub fn new(reader: R, writer: W) -> Self {
         let reader = Arc::new(Mutex::new(reader));
         let writer = Arc::new(Mutex::new(writer));
-        Self { reader, writer }
+        Self {
+            reader,
+            writer,
+            socket: None,
+        }
+    }
+
+    /// Make a new `Channel` from a `reader` and a `writer` over `socket`, on
+    /// which `set_timeouts` sets the timeouts.
+    pub fn with_socket<S: SetTimeout + Send + Sync + 'static>(
+        reader: R,
+        writer: W,
+        socket: S,
+    ) -> Self {
+        Self {
+            socket: Some(Arc::new(socket)),
+            ..Self::new(reader, writer)
+        }
     }
 
     /// Return a reader object wrapped in `Arc<Mutex<R>>`.
@@ -33,7 +52,7 @@ impl<R: Read, W: Write> AbstractChannel for SyncChannel<R, W> {
     #[inline(always)]
     fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
         self.writer.lock().unwrap().write_all(bytes)?;
-        self.flush().unwrap();
+        self.flush()?;
         Ok(())
     }
 
@@ -52,6 +71,20 @@ impl<R: Read, W: Write> AbstractChannel for SyncChannel<R, W> {
         Self {
             reader: self.reader.clone(),
             writer: self.writer.clone(),
+            socket: self.socket.clone(),
+        }
+    }
+
+    fn set_timeouts(&mut self, read: Option<Duration>, write: Option<Duration>) -> Result<()> {
+        match &self.socket {
+            Some(socket) => {
+                socket.set_read_timeout(read)?;
+                socket.set_write_timeout(write)
+            }
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "channel was not made with a socket",
+            )),
         }
     }
 }
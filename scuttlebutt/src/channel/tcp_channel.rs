@@ -0,0 +1,17 @@
+use crate::SyncChannel;
+use std::{
+    io::{BufReader, BufWriter},
+    net::TcpStream,
+};
+
+/// A SyncChannel which uses TcpStreams.
+pub type TcpChannel = SyncChannel<BufReader<TcpStream>, BufWriter<TcpStream>>;
+
+/// Make a TcpChannel over `stream`, supporting timeouts.
+pub fn tcp_channel(stream: TcpStream) -> std::io::Result<TcpChannel> {
+    Ok(SyncChannel::with_socket(
+        BufReader::new(stream.try_clone()?),
+        BufWriter::new(stream.try_clone()?),
+        stream,
+    ))
+}
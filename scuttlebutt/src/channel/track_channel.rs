@@ -2,6 +2,7 @@ use crate::AbstractChannel;
 use std::{
     io::Result,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
 /// A channel wrapping another channel for tracking the number of bits read/written.
@@ -85,4 +86,8 @@ impl<C: AbstractChannel> AbstractChannel for TrackChannel<C> {
     fn clone(&self) -> Self {
         Self(self.0.clone())
     }
+
+    fn set_timeouts(&mut self, read: Option<Duration>, write: Option<Duration>) -> Result<()> {
+        self.0.lock().unwrap().channel.set_timeouts(read, write)
+    }
 }
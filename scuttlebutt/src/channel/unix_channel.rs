@@ -10,24 +10,23 @@ pub type UnixChannel = SyncChannel<BufReader<UnixStream>, BufWriter<UnixStream>>
 /// A TrackChannel which uses UnixStreams.
 pub type TrackUnixChannel = TrackChannel<UnixChannel>;
 
+/// Make a UnixChannel over `stream`, supporting timeouts.
+pub fn unix_channel(stream: UnixStream) -> std::io::Result<UnixChannel> {
+    Ok(SyncChannel::with_socket(
+        BufReader::new(stream.try_clone()?),
+        BufWriter::new(stream.try_clone()?),
+        stream,
+    ))
+}
+
 /// Convenience function to create a pair of UnixChannels for local tests in `swanky`.
 pub fn unix_channel_pair() -> (UnixChannel, UnixChannel) {
     let (tx, rx) = UnixStream::pair().unwrap();
-    let sender = SyncChannel::new(BufReader::new(tx.try_clone().unwrap()), BufWriter::new(tx));
-    let receiver = SyncChannel::new(BufReader::new(rx.try_clone().unwrap()), BufWriter::new(rx));
-    (sender, receiver)
+    (unix_channel(tx).unwrap(), unix_channel(rx).unwrap())
 }
 
 /// Convenience function to create a pair of TrackUnixChannels for local tests in `swanky`.
 pub fn track_unix_channel_pair() -> (TrackUnixChannel, TrackUnixChannel) {
-    let (tx, rx) = UnixStream::pair().unwrap();
-    let sender = TrackChannel::new(SyncChannel::new(
-        BufReader::new(tx.try_clone().unwrap()),
-        BufWriter::new(tx),
-    ));
-    let receiver = TrackChannel::new(SyncChannel::new(
-        BufReader::new(rx.try_clone().unwrap()),
-        BufWriter::new(rx),
-    ));
-    (sender, receiver)
+    let (sender, receiver) = unix_channel_pair();
+    (TrackChannel::new(sender), TrackChannel::new(receiver))
 }
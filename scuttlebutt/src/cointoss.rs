@@ -11,6 +11,7 @@ use rand_core::{RngCore, SeedableRng};
 
 /// Errors produced by the coin tossing protocol.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// An I/O error occurred.
     IoError(std::io::Error),
@@ -33,6 +34,15 @@ impl std::fmt::Display for Error {
     }
 }
 
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::IoError(e) => Some(e),
+            Error::CommitmentCheckFailed => None,
+        }
+    }
+}
+
 /// Coin tossing sender.
 #[inline]
 pub fn send<C: AbstractChannel>(channel: &mut C, seeds: &[Block]) -> Result<Vec<Block>, Error> {
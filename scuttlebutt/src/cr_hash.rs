@@ -0,0 +1,79 @@
+//! A common interface to the correlation-robust hash functions.
+
+use crate::{AesHash, Block};
+use vectoreyes::array_utils::{ArrayUnrolledExt, ArrayUnrolledOps, UnrollableArraySize};
+
+/// A correlation-robust hash function and its variants (cf.
+/// <https://eprint.iacr.org/2019/074>), keyed by a tweak `i`.
+///
+/// [`AesHash`] builds these from fixed-key AES, which is fast on CPUs with
+/// AES instructions. With the `blake3` feature, [`Blake3Hash`](crate::Blake3Hash)
+/// builds them from BLAKE3 instead, for CPUs without them. The two compute
+/// different functions, so both parties of a protocol must use the same one.
+pub trait CrHash {
+    /// Correlation-robust hash function for 128-bit inputs.
+    fn cr_hash(&self, i: Block, x: Block) -> Block;
+
+    /// Circular correlation-robust hash function.
+    fn ccr_hash(&self, i: Block, x: Block) -> Block;
+
+    /// Tweakable circular correlation-robust hash function.
+    fn tccr_hash(&self, i: Block, x: Block) -> Block;
+
+    /// Batch tweakable circular correlation-robust hash function, hashing
+    /// each of `xs` under the tweak `i`.
+    fn tccr_hash_many<const Q: usize>(&self, i: Block, xs: [Block; Q]) -> [Block; Q]
+    where
+        ArrayUnrolledOps: UnrollableArraySize<Q>,
+    {
+        xs.array_map(
+            #[inline(always)]
+            |x| self.tccr_hash(i, x),
+        )
+    }
+
+    /// Batch tweakable circular correlation-robust hash function, hashing
+    /// each of `xs` under the tweak of the same index in `is`.
+    fn tccr_hash_many_tweaked<const Q: usize>(&self, is: [Block; Q], xs: [Block; Q]) -> [Block; Q]
+    where
+        ArrayUnrolledOps: UnrollableArraySize<Q>,
+    {
+        is.array_zip(xs).array_map(
+            #[inline(always)]
+            |(i, x)| self.tccr_hash(i, x),
+        )
+    }
+}
+
+impl CrHash for AesHash {
+    #[inline]
+    fn cr_hash(&self, i: Block, x: Block) -> Block {
+        AesHash::cr_hash(self, i, x)
+    }
+
+    #[inline]
+    fn ccr_hash(&self, i: Block, x: Block) -> Block {
+        AesHash::ccr_hash(self, i, x)
+    }
+
+    #[inline]
+    fn tccr_hash(&self, i: Block, x: Block) -> Block {
+        AesHash::tccr_hash(self, i, x)
+    }
+
+    #[inline]
+    fn tccr_hash_many<const Q: usize>(&self, i: Block, xs: [Block; Q]) -> [Block; Q]
+    where
+        ArrayUnrolledOps: UnrollableArraySize<Q>,
+    {
+        AesHash::tccr_hash_many(self, i, xs)
+    }
+
+    #[inline]
+    fn tccr_hash_many_tweaked<const Q: usize>(&self, is: [Block; Q], xs: [Block; Q]) -> [Block; Q]
+    where
+        ArrayUnrolledOps: UnrollableArraySize<Q>,
+    {
+        AesHash::tccr_hash_many_tweaked(self, is, xs)
+    }
+}
@@ -0,0 +1,123 @@
+//! Implementations of correlation-robust hash functions (and their variants)
+//! based on BLAKE3.
+
+use crate::{Block, CrHash};
+use vectoreyes::array_utils::{ArrayUnrolledExt, ArrayUnrolledOps, UnrollableArraySize};
+
+/// BLAKE3-based correlation-robust hash function.
+///
+/// Each variant computes the keyed BLAKE3 hash of `i || x`, truncated to 128
+/// bits. This is slower than [`AesHash`](crate::AesHash) on CPUs with AES
+/// instructions, and faster than its software fallback on CPUs without them.
+#[derive(Clone)]
+pub struct Blake3Hash {
+    key: [u8; 32],
+}
+
+/// `Blake3Hash` with a fixed key.
+pub const BLAKE3_HASH: Blake3Hash = Blake3Hash {
+    key: *b"scuttlebutt fixed-key Blake3Hash",
+};
+
+impl Blake3Hash {
+    /// Initialize the hash function using `key`.
+    #[inline]
+    pub fn new(key: Block) -> Self {
+        Blake3Hash {
+            key: blake3::derive_key("scuttlebutt Blake3Hash key", key.as_ref()),
+        }
+    }
+
+    #[inline]
+    fn hash(&self, i: Block, x: Block) -> Block {
+        let mut input = [0; 32];
+        input[..16].copy_from_slice(i.as_ref());
+        input[16..].copy_from_slice(x.as_ref());
+        let hash = blake3::keyed_hash(&self.key, &input);
+        let mut out = [0; 16];
+        out.copy_from_slice(&hash.as_bytes()[..16]);
+        Block::from(out)
+    }
+
+    /// Correlation-robust hash function for 128-bit inputs.
+    #[inline]
+    pub fn cr_hash(&self, i: Block, x: Block) -> Block {
+        self.hash(i, x)
+    }
+
+    /// Circular correlation-robust hash function.
+    #[inline]
+    pub fn ccr_hash(&self, i: Block, x: Block) -> Block {
+        self.hash(i, x)
+    }
+
+    /// Tweakable circular correlation-robust hash function.
+    #[inline]
+    pub fn tccr_hash(&self, i: Block, x: Block) -> Block {
+        self.hash(i, x)
+    }
+
+    /// Batch tweakable circular correlation robust hash function
+    pub fn tccr_hash_many<const Q: usize>(&self, i: Block, xs: [Block; Q]) -> [Block; Q]
+    where
+        ArrayUnrolledOps: UnrollableArraySize<Q>,
+    {
+        xs.array_map(
+            #[inline(always)]
+            |x| self.hash(i, x),
+        )
+    }
+
+    /// Batch tweakable circular correlation robust hash function, hashing
+    /// each of `xs` under the tweak of the same index in `is`.
+    pub fn tccr_hash_many_tweaked<const Q: usize>(
+        &self,
+        is: [Block; Q],
+        xs: [Block; Q],
+    ) -> [Block; Q]
+    where
+        ArrayUnrolledOps: UnrollableArraySize<Q>,
+    {
+        is.array_zip(xs).array_map(
+            #[inline(always)]
+            |(i, x)| self.hash(i, x),
+        )
+    }
+}
+
+impl CrHash for Blake3Hash {
+    #[inline]
+    fn cr_hash(&self, i: Block, x: Block) -> Block {
+        Blake3Hash::cr_hash(self, i, x)
+    }
+
+    #[inline]
+    fn ccr_hash(&self, i: Block, x: Block) -> Block {
+        Blake3Hash::ccr_hash(self, i, x)
+    }
+
+    #[inline]
+    fn tccr_hash(&self, i: Block, x: Block) -> Block {
+        Blake3Hash::tccr_hash(self, i, x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blake3_hash() {
+        let (i, x) = (Block::from(1u128), Block::from(2u128));
+        let h = BLAKE3_HASH.tccr_hash(i, x);
+        assert_eq!(h, BLAKE3_HASH.tccr_hash(i, x));
+        assert_ne!(h, BLAKE3_HASH.tccr_hash(Block::from(3u128), x));
+        assert_ne!(h, BLAKE3_HASH.tccr_hash(i, Block::from(3u128)));
+        assert_ne!(h, Blake3Hash::new(Block::default()).tccr_hash(i, x));
+        assert_eq!(BLAKE3_HASH.tccr_hash_many(i, [x; 2]), [h; 2]);
+        assert_eq!(
+            BLAKE3_HASH.tccr_hash_many_tweaked([i, Block::from(3u128)], [x; 2]),
+            [h, BLAKE3_HASH.tccr_hash(Block::from(3u128), x)]
+        );
+    }
+}
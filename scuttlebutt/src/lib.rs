@@ -13,8 +13,11 @@ pub mod bloomfilter;
 pub mod channel;
 pub mod cointoss;
 pub mod commitment;
+mod cr_hash;
 pub mod field;
 mod hash_aes;
+#[cfg(feature = "blake3")]
+mod hash_blake3;
 mod rand_aes;
 pub mod ring;
 #[macro_use]
@@ -31,14 +34,24 @@ pub use crate::{
     },
     block::Block,
     block512::Block512,
-    channel::{AbstractChannel, Channel, HashChannel, SymChannel, SyncChannel, TrackChannel},
+    channel::{
+        tcp_channel, AbstractChannel, Channel, ChannelError, HashChannel, SetTimeout, SymChannel,
+        SyncChannel, TcpChannel, TrackChannel,
+    },
+    cr_hash::CrHash,
     hash_aes::{AesHash, AES_HASH},
     rand_aes::{vectorized::UniformIntegersUnderBound, AesRng},
 };
 
+#[cfg(feature = "blake3")]
+pub use crate::hash_blake3::{Blake3Hash, BLAKE3_HASH};
+
+#[cfg(feature = "tokio")]
+pub use crate::channel::{AsyncAbstractChannel, ChannelFuture};
+
 #[cfg(unix)]
 pub use crate::channel::{
-    track_unix_channel_pair, unix_channel_pair, TrackUnixChannel, UnixChannel,
+    track_unix_channel_pair, unix_channel, unix_channel_pair, TrackUnixChannel, UnixChannel,
 };
 
 /// A marker trait denoting that the given scheme is semi-honest secure.
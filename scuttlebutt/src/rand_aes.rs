@@ -93,6 +93,30 @@ impl Default for AesRng {
     }
 }
 
+// With the `zeroize` feature, an `AesRng` overwrites its key when dropped.
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for AesRng {
+    fn zeroize(&mut self) {
+        // Overwrite the key schedule, the counter, and the buffered output with
+        // those of the all-zero seed, which are public.
+        //
+        // SAFETY: an `AesRng` owns no memory or other resources, so nothing
+        // leaks from overwriting it without dropping it first.
+        unsafe { std::ptr::write_volatile(self, AesRng::from_seed(Block::default())) };
+        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for AesRng {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::ZeroizeOnDrop for AesRng {}
+
 /// The core of `AesRng`, used with `BlockRng`.
 #[derive(Clone, Debug)]
 pub struct AesRngCore {
@@ -156,6 +180,17 @@ mod tests {
     use super::*;
     use rand::Rng;
 
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn test_zeroize() {
+        use zeroize::Zeroize;
+        let mut rng = AesRng::from_seed(Block::from(7));
+        let _: u8 = rng.gen();
+        rng.zeroize();
+        let mut zero = AesRng::from_seed(Block::default());
+        assert_eq!(rng.gen::<Block>(), zero.gen::<Block>());
+    }
+
     #[test]
     fn test_generate() {
         let mut rng = AesRng::new();